@@ -0,0 +1,64 @@
+//! Compares the throughput of the JSON library of loco with `serde_json`,
+//! reading and writing a list of records like an API handles:
+//!
+//! ```sh
+//! cargo bench --bench json
+//! cargo bench --bench json --features simd_json
+//! ```
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use loco_rs::controller::json;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Note {
+    id: i64,
+    title: String,
+    content: String,
+    tags: Vec<String>,
+    published: bool,
+    score: f64,
+}
+
+fn notes(count: i64) -> Vec<Note> {
+    (1..=count)
+        .map(|id| Note {
+            id,
+            title: format!("Note {id}"),
+            content: "Loco is a web framework for Rust, inspired by Rails. ".repeat(8),
+            tags: vec!["rust".to_string(), "web".to_string(), format!("tag-{id}")],
+            published: id % 2 == 0,
+            score: 0.5 * f64::from(u32::try_from(id).unwrap_or_default()),
+        })
+        .collect()
+}
+
+fn parse(c: &mut Criterion) {
+    let body = serde_json::to_vec(&notes(1_000)).unwrap();
+    let mut group = c.benchmark_group("parse request body");
+    group.throughput(Throughput::Bytes(body.len() as u64));
+    group.bench_function("serde_json", |b| {
+        b.iter(|| serde_json::from_slice::<Vec<Note>>(black_box(&body)).unwrap());
+    });
+    group.bench_function(json::LIBRARY, |b| {
+        b.iter(|| json::from_slice::<Vec<Note>>(black_box(&body)).unwrap());
+    });
+    group.finish();
+}
+
+fn serialize(c: &mut Criterion) {
+    let notes = notes(1_000);
+    let mut group = c.benchmark_group("serialize response");
+    group.throughput(Throughput::Bytes(
+        serde_json::to_vec(&notes).unwrap().len() as u64
+    ));
+    group.bench_function("serde_json", |b| {
+        b.iter(|| serde_json::to_vec(black_box(&notes)).unwrap());
+    });
+    group.bench_function(json::LIBRARY, |b| {
+        b.iter(|| json::to_vec(black_box(&notes)).unwrap());
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse, serialize);
+criterion_main!(benches);
@@ -5,28 +5,38 @@ use {sea_orm::DatabaseConnection, std::path::Path};
 
 use std::{
     any::{Any, TypeId},
+    collections::BTreeMap,
+    future::IntoFuture,
     net::SocketAddr,
     sync::Arc,
+    time::Duration,
 };
 
 use async_trait::async_trait;
 use axum::Router as AxumRouter;
+use chrono::{DateTime, Utc};
 use dashmap::DashMap;
+use tokio_util::sync::CancellationToken;
 
 use crate::{
-    bgworker::{self, Queue},
-    boot::{shutdown_signal, BootResult, ServeParams, StartMode},
+    audit::AuditSink,
+    bgworker::{self, BackgroundWorker, Queue},
+    boot::{BootResult, Listener, Readiness, RunAction, ServeParams, StartMode},
     cache::{self},
-    config::Config,
+    clock::Clock,
+    config::{Config, WorkerMode},
     controller::{
         middleware::{self, MiddlewareLayer},
-        AppRoutes,
+        AppRoutes, LayerRegistry,
     },
     environment::Environment,
+    events::EventBus,
+    flags::{FlagContext, FlagProvider},
+    http_client::HttpClient,
     mailer::EmailSender,
     storage::Storage,
     task::Tasks,
-    Result,
+    Error, Result,
 };
 
 /// Type-safe heterogeneous storage for arbitrary application data
@@ -254,7 +264,9 @@ pub struct AppContext {
     /// The environment in which the application is running.
     pub environment: Environment,
     #[cfg(feature = "with-db")]
-    /// A database connection used by the application.
+    /// A database connection used by the application. Disconnected when the
+    /// configuration has no `database`, prefer [`AppContext::db`] in code
+    /// that may run without one.
     pub db: DatabaseConnection,
     /// Queue provider
     pub queue_provider: Option<Arc<bgworker::Queue>>,
@@ -262,12 +274,369 @@ pub struct AppContext {
     pub config: Config,
     /// An optional email sender component that can be used to send email.
     pub mailer: Option<EmailSender>,
+    /// The other email senders, by name, from `mailer.senders`
+    pub mailers: Arc<BTreeMap<String, EmailSender>>,
     // An optional storage instance for the application
     pub storage: Arc<Storage>,
     // Cache instance for the application
     pub cache: Arc<cache::Cache>,
     /// Shared store for arbitrary application data
     pub shared_store: Arc<SharedStore>,
+    /// An optional audit sink, see [`crate::audit`]
+    pub audit: Option<Arc<dyn AuditSink>>,
+    /// The source of the current time, see [`crate::clock`]
+    pub clock: Arc<dyn Clock>,
+    /// The source of the feature flags, see [`crate::flags`]
+    pub flags: Arc<dyn FlagProvider>,
+    /// The shared client for outbound HTTP calls, see [`crate::http_client`]
+    pub http_client: HttpClient,
+    /// The event bus within the process, see [`crate::events`]
+    pub events: EventBus,
+    /// Whether the app is warming up or ready to take traffic
+    pub readiness: Readiness,
+    /// Cancelled when the app starts shutting down, to stop long-running work
+    /// such as streams, see [`crate::config::Shutdown`]
+    pub shutdown: CancellationToken,
+}
+
+impl AppContext {
+    /// Returns the database connection.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::DbNotConfigured`] when the app runs without a database
+    #[cfg(feature = "with-db")]
+    pub fn db(&self) -> Result<&DatabaseConnection> {
+        if matches!(self.db, DatabaseConnection::Disconnected) {
+            return Err(Error::DbNotConfigured);
+        }
+        Ok(&self.db)
+    }
+
+    /// Returns the email sender.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::MailerNotConfigured`] when the app runs without a mailer
+    pub fn mailer(&self) -> Result<&EmailSender> {
+        self.mailer.as_ref().ok_or(Error::MailerNotConfigured)
+    }
+
+    /// Returns the email sender `name`, from `mailer.senders`.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::MailerSenderNotConfigured`] when the app has no sender `name`
+    pub fn mailer_named(&self, name: &str) -> Result<&EmailSender> {
+        self.mailers
+            .get(name)
+            .ok_or_else(|| Error::MailerSenderNotConfigured(name.to_string()))
+    }
+
+    /// Returns the storage.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::StorageNotConfigured`] when the app runs with the default
+    /// null storage
+    pub fn storage(&self) -> Result<&Arc<Storage>> {
+        if !self.storage.is_configured() {
+            return Err(Error::StorageNotConfigured);
+        }
+        Ok(&self.storage)
+    }
+
+    /// Returns the Redis client of the Redis job queue. Prefix the keys of the
+    /// app with [`bgworker::redis::RedisPool::key`] to share the `key_prefix`
+    /// of the queue.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::RedisNotConfigured`] when the app has no Redis queue
+    #[cfg(feature = "bg_redis")]
+    pub fn redis(&self) -> Result<&bgworker::redis::RedisPool> {
+        match self.queue_provider.as_deref() {
+            Some(Queue::Redis(client, ..)) => Ok(client),
+            _ => Err(Error::RedisNotConfigured),
+        }
+    }
+
+    /// Returns the current time, from the context clock. Prefer it over
+    /// `Utc::now()` in time-sensitive code, so tests can control the time.
+    #[must_use]
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    /// Returns the event bus, to publish events and subscribe to them, see
+    /// [`crate::events`].
+    #[must_use]
+    pub const fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Whether the feature flag `name` is on for `context`, see
+    /// [`crate::flags`].
+    #[must_use]
+    pub fn feature(&self, name: &str, context: &FlagContext) -> bool {
+        self.flags.is_enabled(name, context)
+    }
+
+    /// Whether the app is in maintenance mode: the `maintenance` middleware is
+    /// enabled, and active from its configuration or its file.
+    pub async fn in_maintenance(&self) -> bool {
+        match &self.config.server.middlewares.maintenance {
+            Some(maintenance) if maintenance.enable => maintenance.is_active().await,
+            _ => false,
+        }
+    }
+
+    /// Whether the jobs of `queue` are processed in this environment: run in
+    /// the process by the `ForegroundBlocking` and `BackgroundAsync` modes, or
+    /// picked up by the queue worker of this instance, following
+    /// `workers.enable` and `workers.queues`.
+    #[must_use]
+    pub fn worker_enabled(&self, queue: &str) -> bool {
+        let workers = &self.config.workers;
+        if workers.mode != WorkerMode::BackgroundQueue {
+            return true;
+        }
+        self.queue_provider.is_some()
+            && workers.processes_jobs()
+            && workers
+                .queues
+                .as_ref()
+                .map_or(true, |queues| queues.iter().any(|name| name == queue))
+    }
+
+    /// Whether mails are delivered: a mailer is configured, and does not stub
+    /// the mails.
+    #[must_use]
+    pub fn mail_sending_enabled(&self) -> bool {
+        self.mailer.as_ref().is_some_and(|mailer| !mailer.is_stub())
+    }
+
+    /// Returns the runtime stats of the database and cache pools, and of the
+    /// job queue.
+    ///
+    /// # Errors
+    /// When the jobs of the queue cannot be read
+    pub async fn stats(&self) -> Result<crate::stats::Stats> {
+        crate::stats::collect(self).await
+    }
+
+    /// Enqueue a job for the worker `W` on the configured queue provider and
+    /// return the id assigned to the job.
+    ///
+    /// Unlike [`BackgroundWorker::perform_later`], this always goes through the
+    /// queue regardless of `workers.mode`.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let job_id = ctx
+    ///     .enqueue::<DownloadWorker, _>(DownloadWorkerArgs { user_guid })
+    ///     .await?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::QueueProviderMissing`] when no queue provider is
+    /// configured, or the provider error when the job could not be enqueued.
+    pub async fn enqueue<W, A>(&self, args: A) -> Result<String>
+    where
+        W: BackgroundWorker<A>,
+        A: serde::Serialize + Send + Sync + 'static,
+    {
+        let queue = self
+            .queue_provider
+            .as_ref()
+            .ok_or(Error::QueueProviderMissing)?;
+        let tags = W::tags();
+        let tags = if tags.is_empty() { None } else { Some(tags) };
+        queue
+            .enqueue(W::class_name(), W::queue(), args, tags)
+            .await?
+            .ok_or(Error::QueueProviderMissing)
+    }
+
+    /// Enqueue one job per item of `args` for the worker `W` and return their
+    /// ids, in order. On Redis the jobs are sent in pipelined chunks (see
+    /// `queue.batch_chunk_size`) instead of one round-trip per job.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::QueueProviderMissing`] when no queue provider is
+    /// configured, or the provider error when the jobs could not be enqueued.
+    pub async fn enqueue_batch<W, A>(
+        &self,
+        args: impl IntoIterator<Item = A>,
+    ) -> Result<Vec<String>>
+    where
+        W: BackgroundWorker<A>,
+        A: serde::Serialize + Send + Sync + 'static,
+    {
+        let queue = self
+            .queue_provider
+            .as_ref()
+            .ok_or(Error::QueueProviderMissing)?;
+        let tags = W::tags();
+        let tags = if tags.is_empty() { None } else { Some(tags) };
+        queue
+            .enqueue_batch(
+                W::class_name(),
+                W::queue(),
+                args.into_iter().collect(),
+                tags,
+            )
+            .await?
+            .ok_or(Error::QueueProviderMissing)
+    }
+
+    /// Returns the jobs scheduled at runtime, to add, remove and list them.
+    /// The scheduler process enqueues their jobs when they are due, see
+    /// [`bgworker::schedules`].
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let schedule = ctx
+    ///     .scheduler()
+    ///     .schedule::<ReminderWorker, _>("daily reminder", "every day at 9:00", args)
+    ///     .await?;
+    /// ```
+    #[must_use]
+    pub const fn scheduler(&self) -> bgworker::schedules::Schedules<'_> {
+        bgworker::schedules::Schedules::new(self)
+    }
+
+    /// Store the result of the job being performed, so that the enqueuer can
+    /// get it back by job id with [`AppContext::job_result`]. Call it from
+    /// your worker's `perform`.
+    ///
+    /// Results are best-effort: they are stored in Redis and expire after
+    /// `queue.result_ttl_sec`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when called outside of a job performed from the queue,
+    /// when no queue provider is configured, or when the provider does not
+    /// support job results.
+    pub async fn set_job_result<T: serde::Serialize + Sync>(&self, result: &T) -> Result<()> {
+        let job_id = bgworker::current_job_id().ok_or_else(|| {
+            Error::string("job results can only be set while performing a job from the queue")
+        })?;
+        self.queue_provider
+            .as_ref()
+            .ok_or(Error::QueueProviderMissing)?
+            .set_job_result(&job_id, &serde_json::to_value(result)?)
+            .await
+    }
+
+    /// Get the result stored by the job `job_id`, or `None` if the job did not
+    /// store a result yet or the result expired.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when no queue provider is configured, the provider does
+    /// not support job results, or the result cannot be deserialized into `T`.
+    pub async fn job_result<T: serde::de::DeserializeOwned>(
+        &self,
+        job_id: &str,
+    ) -> Result<Option<T>> {
+        let result = self
+            .queue_provider
+            .as_ref()
+            .ok_or(Error::QueueProviderMissing)?
+            .get_job_result(job_id)
+            .await?;
+        Ok(result.map(serde_json::from_value).transpose()?)
+    }
+
+    /// Wait up to `timeout` for the job `job_id` to store its result, polling
+    /// every 100 milliseconds. Returns `None` if no result was stored in time.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let job_id = ctx.enqueue::<RenderWorker, _>(RenderArgs { id }).await?;
+    /// let url: Option<String> = ctx
+    ///     .wait_job_result(&job_id, Duration::from_secs(10))
+    ///     .await?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// See [`AppContext::job_result`].
+    pub async fn wait_job_result<T: serde::de::DeserializeOwned>(
+        &self,
+        job_id: &str,
+        timeout: std::time::Duration,
+    ) -> Result<Option<T>> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            if let Some(result) = self.job_result(job_id).await? {
+                return Ok(Some(result));
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Ok(None);
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+    }
+}
+
+#[cfg(feature = "testing")]
+impl AppContext {
+    /// Create an [`AppContext`] for fast unit tests that don't need real
+    /// infrastructure: test configuration, in-memory storage, a null cache and
+    /// no queue or mailer.
+    ///
+    /// With the `with-db` feature, `db` is an empty
+    /// [`crate::testing::mock::MockDb`] (Postgres backend). Use
+    /// [`AppContext::mock_with_db`] to script query results, and see the
+    /// [`crate::testing::mock`] module for how this differs from a real
+    /// database.
+    ///
+    /// # Panics
+    ///
+    /// When the default HTTP client cannot be built
+    #[must_use]
+    pub fn mock() -> Self {
+        let config = crate::tests_cfg::config::test_config();
+        let shutdown = CancellationToken::new();
+        Self {
+            environment: Environment::Test,
+            #[cfg(feature = "with-db")]
+            db: crate::testing::mock::MockDb::new(sea_orm::DatabaseBackend::Postgres).connection(),
+            queue_provider: None,
+            events: EventBus::new(&config.events, shutdown.clone()),
+            config,
+            mailer: None,
+            mailers: Arc::new(BTreeMap::new()),
+            storage: Storage::single(crate::storage::drivers::mem::new()).into(),
+            cache: Arc::new(cache::Cache::new(cache::drivers::null::new())),
+            shared_store: Arc::new(SharedStore::default()),
+            audit: None,
+            clock: Arc::new(crate::clock::SystemClock),
+            flags: Arc::new(crate::flags::StaticFlags::new(
+                BTreeMap::new(),
+                Environment::Test,
+            )),
+            http_client: HttpClient::new(&crate::config::HttpClient::default())
+                .expect("the default http client should build"),
+            readiness: Readiness::default(),
+            shutdown,
+        }
+    }
+
+    /// Create a mocked [`AppContext`] (see [`AppContext::mock`]) whose `db` is
+    /// connected to the given scripted database.
+    #[cfg(feature = "with-db")]
+    #[must_use]
+    pub fn mock_with_db(db: &crate::testing::mock::MockDb) -> Self {
+        Self {
+            db: db.connection(),
+            ..Self::mock()
+        }
+    }
 }
 
 /// A trait that defines hooks for customizing and extending the behavior of a
@@ -322,32 +691,84 @@ pub trait Hooks: Send {
         -> Result<BootResult>;
 
     /// Start serving the Axum web application on the specified address and
-    /// port.
+    /// port, and on the additional [`Hooks::listeners`].
     ///
     /// # Returns
     /// A Result indicating success () or an error if the server fails to start.
     async fn serve(app: AxumRouter, ctx: &AppContext, serve_params: &ServeParams) -> Result<()> {
-        let listener = tokio::net::TcpListener::bind(&format!(
-            "{}:{}",
-            serve_params.binding, serve_params.port
-        ))
-        .await?;
-
-        let cloned_ctx = ctx.clone();
-        axum::serve(
-            listener,
-            app.into_make_service_with_connect_info::<SocketAddr>(),
-        )
-        .with_graceful_shutdown(async move {
-            shutdown_signal().await;
-            tracing::info!("shutting down...");
-            Self::on_shutdown(&cloned_ctx).await;
-        })
-        .await?;
+        let mut servers = vec![(
+            crate::boot::bind(
+                &serve_params.binding,
+                serve_params.port,
+                serve_params.retry_bind,
+            )
+            .await?,
+            app.clone(),
+        )];
+        for listener in Self::listeners(ctx).await? {
+            tracing::info!(
+                binding = listener.binding,
+                port = listener.port,
+                "listening on an additional address"
+            );
+            servers.push((
+                crate::boot::bind(&listener.binding, listener.port, serve_params.retry_bind)
+                    .await?,
+                listener.router.unwrap_or_else(|| app.clone()),
+            ));
+        }
+        for (listener, _) in &servers {
+            crate::boot::notify_serving(ctx, &listener.local_addr()?).await?;
+        }
+
+        let shutdown = CancellationToken::new();
+        let servers =
+            futures_util::future::try_join_all(servers.into_iter().map(|(listener, router)| {
+                axum::serve(
+                    listener,
+                    router.into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .with_graceful_shutdown(shutdown.clone().cancelled_owned())
+                .into_future()
+            }));
+        tokio::pin!(servers);
+
+        tokio::select! {
+            res = &mut servers => {
+                res?;
+            }
+            () = ctx.shutdown.cancelled() => {
+                tracing::info!("shutting down...");
+                // stop accepting connections and drain the in-flight requests
+                // of every listener
+                shutdown.cancel();
+                let timeout_ms = ctx.config.shutdown.drain_timeout;
+                if let Ok(res) = tokio::time::timeout(Duration::from_millis(timeout_ms), servers).await {
+                    res?;
+                } else {
+                    tracing::warn!(
+                        stage = "drain",
+                        timeout_ms,
+                        "shutdown stage did not finish in time, force-stopping it"
+                    );
+                }
+            }
+        }
 
         Ok(())
     }
 
+    /// Returns the additional addresses to serve next to the main
+    /// `server.binding` and `server.port`, for example to keep an internal
+    /// admin or metrics surface on its own port. Listeners share the app
+    /// context, and are drained together when the app shuts down.
+    ///
+    /// # Errors
+    /// Return an [`Result`] when the listeners could not be created
+    async fn listeners(_ctx: &AppContext) -> Result<Vec<Listener>> {
+        Ok(vec![])
+    }
+
     /// Override and return `Ok(true)` to provide an alternative logging and
     /// tracing stack of your own.
     /// When returning `Ok(true)`, Loco will *not* initialize its own logger,
@@ -387,6 +808,15 @@ pub trait Hooks: Send {
         Ok(router)
     }
 
+    /// Registers named layers, applied by their order index once the router
+    /// is finalized, after `after_routes`. See [`LayerRegistry`].
+    ///
+    /// # Errors
+    /// When the layers could not be created
+    fn register_layers(_layers: &mut LayerRegistry, _ctx: &AppContext) -> Result<()> {
+        Ok(())
+    }
+
     /// Provide a list of initializers
     /// An initializer can be used to seamlessly add functionality to your app
     /// or to initialize some aspects of it.
@@ -403,13 +833,40 @@ pub trait Hooks: Send {
     /// Calling the function before run the app
     /// You can now code some custom loading of resources or other things before
     /// the app runs
-    async fn before_run(_app_context: &AppContext) -> Result<()> {
+    ///
+    /// Return [`RunAction::Exit`] to stop after this hook (for example a
+    /// one-shot bootstrap run) instead of serving.
+    async fn before_run(_app_context: &AppContext) -> Result<RunAction> {
+        Ok(RunAction::Proceed)
+    }
+
+    /// Warms the app up before the server accepts connections, so the first
+    /// requests are not served at cold-start latency: pre-fill caches, run a
+    /// first query, open the pooled connections.
+    ///
+    /// Runs once the workers started, before [`Hooks::serve`] binds its
+    /// listeners, and not for a worker-only process. `/_health` reports the
+    /// app `warming` meanwhile, see [`crate::boot::Readiness`].
+    ///
+    /// # Errors
+    ///
+    /// When the app cannot be warmed up, which stops it
+    async fn warmup(_ctx: &AppContext) -> Result<()> {
         Ok(())
     }
 
     /// Defines the application's routing configuration.
     fn routes(_ctx: &AppContext) -> AppRoutes;
 
+    /// Called when a request fails with an [`Error`], before its `4xx` or
+    /// `5xx` response is sent, in the request span. Use it to report errors
+    /// (to Sentry, or as metrics) or to log more context in a single place.
+    /// It cannot change the response.
+    ///
+    /// Only errors returned by handlers and extractors are observed, not the
+    /// responses of the middlewares.
+    async fn on_error(_ctx: &AppContext, _err: &Error, _parts: &axum::http::request::Parts) {}
+
     // Provides the options to change Loco [`AppContext`] after initialization.
     async fn after_context(ctx: AppContext) -> Result<AppContext> {
         Ok(ctx)
@@ -434,7 +891,9 @@ pub trait Hooks: Send {
     #[cfg(feature = "with-db")]
     async fn seed(_ctx: &AppContext, path: &Path) -> Result<()>;
 
-    /// Called when the application is shutting down.
+    /// Called when the application is shutting down, once the in-flight
+    /// requests are drained and the queue worker stopped, before the
+    /// initializers `on_shutdown` and the database connections are closed.
     /// This function allows users to perform any necessary cleanup or final
     /// actions before the application stops completely.
     async fn on_shutdown(_ctx: &AppContext) {}
@@ -448,6 +907,18 @@ pub trait Initializer: Sync + Send {
     /// The initializer name or identifier
     fn name(&self) -> String;
 
+    /// Occurs once the initializers are loaded, with the configuration the
+    /// app was booted with. This is the first lifecycle event.
+    async fn on_config_loaded(&self, _config: &Config) -> Result<()> {
+        Ok(())
+    }
+
+    /// Occurs after `on_config_loaded`, with the fully built app context
+    /// (database, queue, cache...), before `before_run`.
+    async fn on_context_built(&self, _ctx: &AppContext) -> Result<()> {
+        Ok(())
+    }
+
     /// Occurs after the app's `before_run`.
     /// Use this to for one-time initializations, load caches, perform web
     /// hooks, etc.
@@ -461,6 +932,33 @@ pub trait Initializer: Sync + Send {
     async fn after_routes(&self, router: AxumRouter, _ctx: &AppContext) -> Result<AxumRouter> {
         Ok(router)
     }
+
+    /// Registers named layers next to the app's ones, see
+    /// [`Hooks::register_layers`].
+    ///
+    /// # Errors
+    /// When the layers could not be created
+    fn register_layers(&self, _layers: &mut LayerRegistry, _ctx: &AppContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Occurs once the server is bound, with the local address it listens
+    /// on, for each listener (see [`Hooks::listeners`]). The address has the
+    /// actual port, also when the app is configured with port `0`.
+    async fn on_serving(&self, _addr: &SocketAddr, _ctx: &AppContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Occurs when the app shuts down, after [`Hooks::on_shutdown`], in the
+    /// reverse order of the initializers, while the database is still
+    /// connected. This is the last lifecycle event.
+    ///
+    /// # Errors
+    ///
+    /// The error is logged, and the shutdown goes on
+    async fn on_shutdown(&self, _ctx: &AppContext) -> Result<()> {
+        Ok(())
+    }
 }
 // </snip>
 
@@ -469,6 +967,19 @@ mod tests {
     use super::*;
     use crate::tests_cfg::app::get_app_context;
 
+    struct TestWorker;
+
+    #[async_trait]
+    impl BackgroundWorker<String> for TestWorker {
+        fn build(_ctx: &AppContext) -> Self {
+            Self
+        }
+
+        async fn perform(&self, _args: String) -> Result<()> {
+            Ok(())
+        }
+    }
+
     struct TestService {
         name: String,
         value: i32,
@@ -696,4 +1207,140 @@ mod tests {
         }
         assert!(!ctx.shared_store.contains::<TestService>());
     }
+
+    #[tokio::test]
+    async fn enqueue_without_queue_provider() {
+        let ctx = get_app_context().await;
+
+        let res = ctx.enqueue::<TestWorker, _>("loco".to_string()).await;
+        assert!(matches!(res, Err(Error::QueueProviderMissing)));
+    }
+
+    #[tokio::test]
+    async fn set_job_result_outside_of_job() {
+        let ctx = get_app_context().await;
+
+        let res = ctx.set_job_result(&"result").await;
+        assert!(matches!(res, Err(Error::Message(msg)) if msg.contains("while performing a job")));
+    }
+
+    #[cfg(feature = "bg_sqlt")]
+    #[tokio::test]
+    async fn enqueue_returns_job_id() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .create()
+            .expect("create temp folder");
+        let qcfg = crate::config::SqliteQueueConfig {
+            uri: format!(
+                "sqlite://{}?mode=rwc",
+                tree_fs.root.join("sample.sqlite").display()
+            ),
+            dangerously_flush: false,
+            enable_logging: false,
+            max_connections: 1,
+            min_connections: 1,
+            connect_timeout: 500,
+            idle_timeout: 500,
+            poll_interval_sec: 1,
+            num_workers: 1,
+            limit: None,
+        };
+        let queue = bgworker::sqlt::create_provider(&qcfg)
+            .await
+            .expect("create sqlite queue");
+        queue.setup().await.expect("setup sqlite db");
+
+        let mut ctx = get_app_context().await;
+        ctx.queue_provider = Some(Arc::new(queue));
+
+        let job_id = ctx
+            .enqueue::<TestWorker, _>("loco".to_string())
+            .await
+            .expect("enqueue job");
+
+        let Some(Queue::Sqlite(pool, _, _, _)) = ctx.queue_provider.as_deref() else {
+            panic!("expected a sqlite queue");
+        };
+        let jobs = bgworker::sqlt::get_jobs(pool, None, None)
+            .await
+            .expect("get jobs");
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].id, job_id);
+        assert_eq!(jobs[0].name, "TestWorker");
+
+        let job_ids = ctx
+            .enqueue_batch::<TestWorker, _>(["a".to_string(), "b".to_string()])
+            .await
+            .expect("enqueue batch");
+        assert_eq!(job_ids.len(), 2);
+        let jobs = bgworker::sqlt::get_jobs(pool, None, None)
+            .await
+            .expect("get jobs");
+        assert_eq!(jobs.len(), 3);
+        assert!(job_ids
+            .iter()
+            .all(|job_id| jobs.iter().any(|job| &job.id == job_id)));
+    }
+
+    #[cfg(feature = "with-db")]
+    #[tokio::test]
+    async fn can_mock_context_db() {
+        use sea_orm::{DatabaseBackend, EntityTrait};
+
+        use crate::{testing::mock::MockDb, tests_cfg::db::test_db};
+
+        let model = test_db::Model {
+            id: 1,
+            name: "loco".to_string(),
+            created_at: chrono::NaiveDateTime::default(),
+            updated_at: chrono::NaiveDateTime::default(),
+        };
+        let db = MockDb::new(DatabaseBackend::Postgres).append_query_results([vec![model.clone()]]);
+        let ctx = AppContext::mock_with_db(&db);
+
+        let found = test_db::Entity::find_by_id(1)
+            .one(&ctx.clone().db)
+            .await
+            .expect("query mock db");
+        assert_eq!(found, Some(model));
+        assert_eq!(db.statements().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn can_get_configured_components() {
+        let mut ctx = get_app_context().await;
+        ctx.storage = Storage::single(crate::storage::drivers::null::new()).into();
+        assert!(matches!(ctx.mailer(), Err(Error::MailerNotConfigured)));
+        assert!(matches!(ctx.storage(), Err(Error::StorageNotConfigured)));
+        #[cfg(feature = "bg_redis")]
+        assert!(matches!(ctx.redis(), Err(Error::RedisNotConfigured)));
+
+        ctx.mailer = Some(EmailSender::stub());
+        ctx.storage = Storage::single(crate::storage::drivers::mem::new()).into();
+        assert!(ctx.mailer().is_ok());
+        assert!(ctx.storage().is_ok());
+    }
+
+    #[tokio::test]
+    async fn can_tell_what_the_environment_does() {
+        let mut ctx = get_app_context().await;
+        assert!(!ctx.in_maintenance().await);
+        ctx.config.server.middlewares.maintenance = Some(middleware::maintenance::Maintenance {
+            enable: true,
+            active: true,
+            ..Default::default()
+        });
+        assert!(ctx.in_maintenance().await);
+
+        ctx.config.workers.mode = WorkerMode::ForegroundBlocking;
+        assert!(ctx.worker_enabled("mailer"));
+        ctx.config.workers.mode = WorkerMode::BackgroundQueue;
+        // without a queue provider
+        assert!(!ctx.worker_enabled("mailer"));
+
+        assert!(!ctx.mail_sending_enabled());
+        ctx.mailer = Some(EmailSender::stub());
+        assert!(!ctx.mail_sending_enabled());
+    }
 }
@@ -0,0 +1,463 @@
+//! # Audit Log
+//!
+//! Records who did what in the application: for every audited mutation an
+//! [`AuditEntry`] captures the actor, the action, the target and a diff of the
+//! changes, and hands it to the configured [`AuditSink`].
+//!
+//! Auditing is opt-in: set [`AppContext::audit`] in [`crate::app::Hooks::after_context`]
+//! with the sink of your choice. [`DbAuditSink`] writes entries to the
+//! `audit_logs` table created by [`AuditMigration`].
+//!
+//! Writing an entry never fails the request: errors are logged and the entry
+//! is dropped.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! async fn after_context(ctx: AppContext) -> Result<AppContext> {
+//!     Ok(AppContext {
+//!         audit: Some(Arc::new(DbAuditSink::new(ctx.db.clone()))),
+//!         ..ctx
+//!     })
+//! }
+//!
+//! async fn update(
+//!     audit: Audit,
+//!     Path(id): Path<i32>,
+//!     State(ctx): State<AppContext>,
+//!     Json(params): Json<Params>,
+//! ) -> Result<Response> {
+//!     let item = load_item(&ctx, id).await?;
+//!     let before = item.clone();
+//!     let item = params.update(item).save(&ctx.db).await?;
+//!     audit
+//!         .record(format!("notes/{id}"), Some(audit::diff(&before, &item)?))
+//!         .await;
+//!     format::json(item)
+//! }
+//! ```
+use async_trait::async_trait;
+use axum::{
+    extract::{FromRef, FromRequestParts, MatchedPath},
+    http::request::Parts,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::{app::AppContext, Error, Result};
+
+/// A single audited action.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Who performed the action, usually the authenticated user pid.
+    pub actor: Option<String>,
+    /// What was done, for example `PUT /api/notes/{id}`.
+    pub action: String,
+    /// What the action was performed on, for example `notes/1`.
+    pub target: Option<String>,
+    /// The changes made by the action, see [`diff`].
+    pub diff: Option<Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuditEntry {
+    #[must_use]
+    pub fn new(action: impl Into<String>) -> Self {
+        Self {
+            actor: None,
+            action: action.into(),
+            target: None,
+            diff: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[must_use]
+    pub fn actor(mut self, actor: impl Into<String>) -> Self {
+        self.actor = Some(actor.into());
+        self
+    }
+
+    #[must_use]
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    #[must_use]
+    pub fn diff(mut self, diff: Value) -> Self {
+        self.diff = Some(diff);
+        self
+    }
+}
+
+/// Destination of audit entries: a table, a file, a remote service...
+#[async_trait]
+pub trait AuditSink: Send + Sync {
+    /// Persists the given entry.
+    ///
+    /// # Errors
+    ///
+    /// When the entry could not be written.
+    async fn write(&self, entry: &AuditEntry) -> Result<()>;
+}
+
+/// Writes `entry` to the audit sink of the application, if any.
+///
+/// A failure to write the entry is logged as an error and otherwise ignored,
+/// so that auditing never breaks the audited action.
+pub async fn record(ctx: &AppContext, entry: AuditEntry) {
+    let Some(sink) = &ctx.audit else {
+        tracing::debug!(
+            action = entry.action,
+            "audit sink is not configured, skipping entry"
+        );
+        return;
+    };
+    if let Err(err) = sink.write(&entry).await {
+        tracing::error!(
+            error.msg = %err,
+            error.details = ?err,
+            actor = entry.actor,
+            action = entry.action,
+            target = entry.target,
+            "could not write audit entry"
+        );
+    }
+}
+
+/// Returns the changes between two versions of a value, as an object of the
+/// changed top-level fields: `{"title": {"from": "old", "to": "new"}}`.
+///
+/// Values that do not serialize to objects are compared as a whole.
+///
+/// # Errors
+///
+/// When one of the values could not be serialized.
+pub fn diff<T: Serialize>(before: &T, after: &T) -> Result<Value> {
+    let before = serde_json::to_value(before)?;
+    let after = serde_json::to_value(after)?;
+
+    let (Value::Object(mut before), Value::Object(after)) = (before.clone(), after.clone()) else {
+        if before == after {
+            return Ok(json!({}));
+        }
+        return Ok(json!({ "from": before, "to": after }));
+    };
+
+    let mut changes = Map::new();
+    for (field, to) in after {
+        let from = before.remove(&field).unwrap_or(Value::Null);
+        if from != to {
+            changes.insert(field, json!({ "from": from, "to": to }));
+        }
+    }
+    for (field, from) in before {
+        changes.insert(field, json!({ "from": from, "to": Value::Null }));
+    }
+    Ok(Value::Object(changes))
+}
+
+/// Request scoped audit logger.
+///
+/// The action defaults to the request method and matched route (for example
+/// `PUT /api/notes/{id}`), and the actor to the pid of the user authenticated
+/// by JWT, when there is one.
+pub struct Audit {
+    ctx: AppContext,
+    actor: Option<String>,
+    action: String,
+}
+
+impl Audit {
+    /// Overrides the actor, for requests authenticated otherwise than by JWT.
+    #[must_use]
+    pub fn with_actor(mut self, actor: impl Into<String>) -> Self {
+        self.actor = Some(actor.into());
+        self
+    }
+
+    #[must_use]
+    pub fn actor(&self) -> Option<&str> {
+        self.actor.as_deref()
+    }
+
+    #[must_use]
+    pub fn action(&self) -> &str {
+        &self.action
+    }
+
+    /// Records the request action performed on `target`.
+    pub async fn record(&self, target: impl Into<String>, diff: Option<Value>) {
+        self.event(self.action.clone(), target, diff).await;
+    }
+
+    /// Records a domain event, for example `invoice.paid`, performed by the
+    /// request actor.
+    pub async fn event(
+        &self,
+        action: impl Into<String>,
+        target: impl Into<String>,
+        diff: Option<Value>,
+    ) {
+        let mut entry = AuditEntry::new(action).target(target);
+        entry.actor.clone_from(&self.actor);
+        entry.diff = diff;
+        entry.created_at = self.ctx.now();
+        record(&self.ctx, entry).await;
+    }
+}
+
+impl<S> FromRequestParts<S> for Audit
+where
+    AppContext: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Error> {
+        let route = parts.extensions.get::<MatchedPath>().map_or_else(
+            || parts.uri.path().to_string(),
+            |path| path.as_str().to_string(),
+        );
+
+        #[cfg(all(feature = "auth_jwt", feature = "with-db"))]
+        let actor =
+            crate::controller::extractor::auth::extract_jwt_from_request_parts(parts, state)
+                .ok()
+                .map(|jwt| jwt.claims.pid);
+        #[cfg(not(all(feature = "auth_jwt", feature = "with-db")))]
+        let actor = None;
+
+        Ok(Self {
+            ctx: AppContext::from_ref(state),
+            actor,
+            action: format!("{} {route}", parts.method),
+        })
+    }
+}
+
+#[cfg(feature = "with-db")]
+pub use self::db::{AuditMigration, DbAuditSink, TABLE_NAME};
+
+#[cfg(feature = "with-db")]
+mod db {
+    use async_trait::async_trait;
+    use sea_orm::{
+        sea_query::{Alias, Query},
+        ConnectionTrait, DatabaseConnection, DbErr,
+    };
+    use sea_orm_migration::{MigrationName, MigrationTrait, SchemaManager};
+
+    use super::{AuditEntry, AuditSink};
+    use crate::{
+        schema::{create_table, drop_table, ColType},
+        Result,
+    };
+
+    /// The table audit entries are written to by [`DbAuditSink`].
+    pub const TABLE_NAME: &str = "audit_logs";
+
+    /// Writes audit entries to the [`TABLE_NAME`] table.
+    pub struct DbAuditSink {
+        db: DatabaseConnection,
+    }
+
+    impl DbAuditSink {
+        #[must_use]
+        pub const fn new(db: DatabaseConnection) -> Self {
+            Self { db }
+        }
+    }
+
+    #[async_trait]
+    impl AuditSink for DbAuditSink {
+        async fn write(&self, entry: &AuditEntry) -> Result<()> {
+            let stmt = Query::insert()
+                .into_table(Alias::new(TABLE_NAME))
+                .columns([
+                    Alias::new("actor"),
+                    Alias::new("action"),
+                    Alias::new("target"),
+                    Alias::new("diff"),
+                    Alias::new("created_at"),
+                ])
+                .values_panic([
+                    entry.actor.clone().into(),
+                    entry.action.clone().into(),
+                    entry.target.clone().into(),
+                    entry.diff.clone().into(),
+                    entry.created_at.into(),
+                ])
+                .to_owned();
+            self.db
+                .execute(self.db.get_database_backend().build(&stmt))
+                .await?;
+            Ok(())
+        }
+    }
+
+    /// Creates the [`TABLE_NAME`] table. Add it to the migrations of your
+    /// `Migrator` to use [`DbAuditSink`].
+    pub struct AuditMigration;
+
+    impl MigrationName for AuditMigration {
+        fn name(&self) -> &'static str {
+            "m20250101_000000_loco_audit_logs"
+        }
+    }
+
+    #[async_trait]
+    impl MigrationTrait for AuditMigration {
+        async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+            create_table(
+                m,
+                TABLE_NAME,
+                &[
+                    ("id", ColType::PkAuto),
+                    ("actor", ColType::StringNull),
+                    ("action", ColType::String),
+                    ("target", ColType::StringNull),
+                    ("diff", ColType::JsonNull),
+                ],
+                &[],
+            )
+            .await
+        }
+
+        async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+            drop_table(m, TABLE_NAME).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::{body::Body, http::Request, routing::put, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    #[derive(Default)]
+    struct MemorySink {
+        entries: Mutex<Vec<AuditEntry>>,
+    }
+
+    #[async_trait]
+    impl AuditSink for MemorySink {
+        async fn write(&self, entry: &AuditEntry) -> Result<()> {
+            self.entries.lock().unwrap().push(entry.clone());
+            Ok(())
+        }
+    }
+
+    struct FailingSink;
+
+    #[async_trait]
+    impl AuditSink for FailingSink {
+        async fn write(&self, _entry: &AuditEntry) -> Result<()> {
+            Err(Error::string("sink is down"))
+        }
+    }
+
+    #[test]
+    fn can_diff_values() {
+        let before = json!({"title": "old", "body": "same", "tag": "x"});
+        let after = json!({"title": "new", "body": "same", "done": true});
+        assert_eq!(
+            diff(&before, &after).unwrap(),
+            json!({
+                "title": {"from": "old", "to": "new"},
+                "done": {"from": null, "to": true},
+                "tag": {"from": "x", "to": null},
+            })
+        );
+        assert_eq!(diff(&1, &2).unwrap(), json!({"from": 1, "to": 2}));
+        assert_eq!(diff(&1, &1).unwrap(), json!({}));
+    }
+
+    #[tokio::test]
+    async fn can_record_request_action() {
+        async fn action(audit: Audit) -> String {
+            audit.record("notes/1", Some(json!({"done": true}))).await;
+            audit
+                .with_actor("admin")
+                .event("note.done", "notes/1", None)
+                .await;
+            String::new()
+        }
+
+        let sink = Arc::new(MemorySink::default());
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        ctx.audit = Some(sink.clone());
+
+        let app = Router::new()
+            .route("/notes/{id}", put(action))
+            .with_state(ctx);
+        app.oneshot(Request::put("/notes/1").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let entries = sink.entries.lock().unwrap().clone();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].actor, None);
+        assert_eq!(entries[0].action, "PUT /notes/{id}");
+        assert_eq!(entries[0].target.as_deref(), Some("notes/1"));
+        assert_eq!(entries[0].diff, Some(json!({"done": true})));
+        assert_eq!(entries[1].actor.as_deref(), Some("admin"));
+        assert_eq!(entries[1].action, "note.done");
+    }
+
+    #[tokio::test]
+    async fn sink_failure_does_not_fail() {
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        ctx.audit = Some(Arc::new(FailingSink));
+        record(&ctx, AuditEntry::new("note.deleted")).await;
+
+        ctx.audit = None;
+        record(&ctx, AuditEntry::new("note.deleted")).await;
+    }
+
+    #[cfg(feature = "with-db")]
+    #[tokio::test]
+    async fn can_write_entries_to_db() {
+        use sea_orm::{ConnectionTrait, Statement};
+        use sea_orm_migration::{MigrationTrait, SchemaManager};
+
+        let db = tests_cfg::db::dummy_connection().await;
+        AuditMigration.up(&SchemaManager::new(&db)).await.unwrap();
+
+        let sink = DbAuditSink::new(db.clone());
+        sink.write(
+            &AuditEntry::new("PUT /notes/{id}")
+                .actor("user-pid")
+                .target("notes/1")
+                .diff(json!({"title": {"from": "a", "to": "b"}})),
+        )
+        .await
+        .unwrap();
+
+        let row = db
+            .query_one(Statement::from_string(
+                db.get_database_backend(),
+                "SELECT actor, action, target, diff FROM audit_logs",
+            ))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.try_get::<String>("", "actor").unwrap(), "user-pid");
+        assert_eq!(
+            row.try_get::<String>("", "action").unwrap(),
+            "PUT /notes/{id}"
+        );
+        assert_eq!(row.try_get::<String>("", "target").unwrap(), "notes/1");
+        assert_eq!(
+            row.try_get::<Value>("", "diff").unwrap(),
+            json!({"title": {"from": "a", "to": "b"}})
+        );
+    }
+}
@@ -2,9 +2,11 @@
 //!
 //! This module provides functionality for working with JSON Web Tokens (JWTs)
 //! and password hashing.
+use chrono::{DateTime, Utc};
 use jsonwebtoken::{
-    decode, encode, errors::Result as JWTResult, get_current_timestamp, Algorithm, DecodingKey,
-    EncodingKey, Header, TokenData, Validation,
+    decode, encode,
+    errors::{ErrorKind, Result as JWTResult},
+    get_current_timestamp, Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
@@ -34,6 +36,7 @@ pub struct UserClaims {
 pub struct JWT {
     secret: String,
     algorithm: Algorithm,
+    now: Option<u64>,
 }
 
 impl JWT {
@@ -43,9 +46,22 @@ impl JWT {
         Self {
             secret: secret.to_string(),
             algorithm: JWT_ALGORITHM,
+            now: None,
         }
     }
 
+    /// Generates and validates tokens as if the current time was `now`,
+    /// typically [`crate::app::AppContext::now`], instead of the system time.
+    #[must_use]
+    pub fn at(mut self, now: DateTime<Utc>) -> Self {
+        self.now = Some(u64::try_from(now.timestamp()).unwrap_or_default());
+        self
+    }
+
+    fn now(&self) -> u64 {
+        self.now.unwrap_or_else(get_current_timestamp)
+    }
+
     /// Override the default  JWT algorithm to be used.
     #[must_use]
     pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
@@ -73,7 +89,7 @@ impl JWT {
         pid: String,
         claims: Map<String, Value>,
     ) -> JWTResult<String> {
-        let exp = get_current_timestamp().saturating_add(expiration);
+        let exp = self.now().saturating_add(expiration);
 
         let claims = UserClaims { pid, exp, claims };
 
@@ -102,12 +118,18 @@ impl JWT {
     pub fn validate(&self, token: &str) -> JWTResult<TokenData<UserClaims>> {
         let mut validate = Validation::new(self.algorithm);
         validate.leeway = 0;
+        // expiration is checked below, against our own clock
+        validate.validate_exp = false;
 
-        decode::<UserClaims>(
+        let token = decode::<UserClaims>(
             token,
             &DecodingKey::from_base64_secret(&self.secret)?,
             &validate,
-        )
+        )?;
+        if token.claims.exp < self.now() {
+            return Err(ErrorKind::ExpiredSignature.into());
+        }
+        Ok(token)
     }
 }
 
@@ -152,6 +174,28 @@ mod tests {
         });
     }
 
+    #[test]
+    fn can_validate_at_given_time() {
+        let now = Utc::now();
+        let token = JWT::new("PqRwLF2rhHe8J22oBeHy")
+            .at(now)
+            .generate_token(60, "pid".to_string(), Map::new())
+            .unwrap();
+
+        let jwt = JWT::new("PqRwLF2rhHe8J22oBeHy");
+        assert!(jwt
+            .at(now + chrono::Duration::seconds(60))
+            .validate(&token)
+            .is_ok());
+
+        let jwt = JWT::new("PqRwLF2rhHe8J22oBeHy");
+        let err = jwt
+            .at(now + chrono::Duration::seconds(61))
+            .validate(&token)
+            .unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::ExpiredSignature);
+    }
+
     #[rstest]
     #[case::without_custom_claims(json!({}))]
     #[case::with_custom_string_claims(json!({ "custom": "claim",}))]
@@ -28,18 +28,20 @@ pub fn print_banner(boot_result: &BootResult, server_config: &ServeParams) {
     println!("environment: {}", ctx.environment.to_string().green());
 
     #[cfg(feature = "with-db")]
-    {
+    if let Some(db_config) = &config.database {
         let mut database = Vec::new();
-        if config.database.enable_logging {
+        if db_config.enable_logging {
             database.push("logging".green());
         }
-        if config.database.auto_migrate {
-            database.push("automigrate".yellow());
+        match db_config.migrate_on_boot() {
+            crate::config::MigrateOnBoot::Auto => database.push("automigrate".yellow()),
+            crate::config::MigrateOnBoot::Check => database.push("migration check".green()),
+            crate::config::MigrateOnBoot::Off => {}
         }
-        if config.database.dangerously_recreate {
+        if db_config.dangerously_recreate {
             database.push("recreate".bright_red());
         }
-        if config.database.dangerously_truncate {
+        if db_config.dangerously_truncate {
             database.push("truncate".bright_red());
         }
 
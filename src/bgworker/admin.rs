@@ -0,0 +1,324 @@
+//! # Queue Administration
+//!
+//! Visibility into the jobs of the queue provider, and control over them for
+//! operators: list the pending, scheduled and failed jobs, retry or delete a
+//! failed job, and purge a queue or all of them:
+//!
+//! ```rust,ignore
+//! let admin = ctx.queue_provider.as_ref().unwrap().admin();
+//! for job in admin.list_failed().await? {
+//!     tracing::info!(id = job.id, worker = job.name, error = job.error, "failed job");
+//! }
+//! admin.retry(&job_id).await?;
+//! admin.purge("mailer").await?;
+//! admin.purge_all().await?;
+//! ```
+//!
+//! Redis keeps the jobs of each queue apart. Postgres and SQLite keep the jobs
+//! of all the workers in a single queue: they do not report the queue of
+//! jobs, and only purge them all, with [`QueueAdmin::purge_all`].
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{JobStatus, Queue};
+use crate::{Error, Result};
+
+/// The jobs to list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    /// Queued jobs, due now
+    Pending,
+    /// Queued jobs, due later
+    Scheduled,
+    /// Jobs which failed, and are not retried
+    Failed,
+}
+
+/// A job, as seen by operators.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobInfo {
+    pub id: String,
+    /// The class name of the worker
+    pub name: String,
+    /// The queue of the job, when the provider keeps queues apart
+    pub queue: Option<String>,
+    pub status: JobStatus,
+    pub args: Value,
+    /// The error of a failed job
+    pub error: Option<String>,
+    pub run_at: DateTime<Utc>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub tags: Option<Vec<String>>,
+}
+
+impl JobInfo {
+    /// Moves the error providers add to the data of failed jobs out of their
+    /// arguments.
+    #[cfg_attr(not(any(feature = "bg_pg", feature = "bg_sqlt")), allow(dead_code))]
+    fn with_error(mut self) -> Self {
+        if self.status == JobStatus::Failed {
+            if let Some(Value::String(error)) = self
+                .args
+                .as_object_mut()
+                .and_then(|args| args.remove("error"))
+            {
+                self.error = Some(error);
+            }
+        }
+        self
+    }
+}
+
+/// Provider jobs all carry the same fields, but the queue and error of Redis
+/// jobs.
+#[cfg(any(feature = "bg_redis", feature = "bg_pg", feature = "bg_sqlt"))]
+macro_rules! job_info {
+    ($job:expr, $queue:expr, $error:expr) => {{
+        let job = $job;
+        JobInfo {
+            id: job.id,
+            name: job.name,
+            queue: $queue,
+            status: job.status,
+            args: job.data,
+            error: $error,
+            run_at: job.run_at,
+            created_at: job.created_at,
+            updated_at: job.updated_at,
+            tags: job.tags,
+        }
+    }};
+}
+
+/// The administration of the jobs of a queue provider, see
+/// [`Queue::admin`].
+pub struct QueueAdmin<'a> {
+    queue: &'a Queue,
+}
+
+impl<'a> QueueAdmin<'a> {
+    #[must_use]
+    pub const fn new(queue: &'a Queue) -> Self {
+        Self { queue }
+    }
+
+    /// Returns the jobs in `state`, the earliest due first.
+    ///
+    /// # Errors
+    ///
+    /// When the jobs could not be read, or no queue provider is configured
+    pub async fn list(&self, state: JobState) -> Result<Vec<JobInfo>> {
+        let status = match state {
+            JobState::Pending | JobState::Scheduled => JobStatus::Queued,
+            JobState::Failed => JobStatus::Failed,
+        };
+        let now = Utc::now();
+        let mut jobs = self
+            .jobs(&status)
+            .await?
+            .into_iter()
+            .filter(|job| match state {
+                JobState::Pending => job.run_at <= now,
+                JobState::Scheduled => job.run_at > now,
+                JobState::Failed => true,
+            })
+            .collect::<Vec<_>>();
+        jobs.sort_by(|a, b| a.run_at.cmp(&b.run_at).then_with(|| a.id.cmp(&b.id)));
+        Ok(jobs)
+    }
+
+    /// Returns the jobs due now, see [`QueueAdmin::list`].
+    ///
+    /// # Errors
+    ///
+    /// As [`QueueAdmin::list`]
+    pub async fn list_pending(&self) -> Result<Vec<JobInfo>> {
+        self.list(JobState::Pending).await
+    }
+
+    /// Returns the jobs due later, see [`QueueAdmin::list`].
+    ///
+    /// # Errors
+    ///
+    /// As [`QueueAdmin::list`]
+    pub async fn list_scheduled(&self) -> Result<Vec<JobInfo>> {
+        self.list(JobState::Scheduled).await
+    }
+
+    /// Returns the failed jobs, see [`QueueAdmin::list`].
+    ///
+    /// # Errors
+    ///
+    /// As [`QueueAdmin::list`]
+    pub async fn list_failed(&self) -> Result<Vec<JobInfo>> {
+        self.list(JobState::Failed).await
+    }
+
+    #[allow(unused_variables)]
+    async fn jobs(&self, status: &JobStatus) -> Result<Vec<JobInfo>> {
+        match self.queue {
+            #[cfg(feature = "bg_pg")]
+            Queue::Postgres(pool, _, _, _) => {
+                Ok(super::pg::get_jobs(pool, Some(&vec![status.clone()]), None)
+                    .await
+                    .map_err(Box::from)?
+                    .into_iter()
+                    .map(|job| job_info!(job, None, None).with_error())
+                    .collect())
+            }
+            #[cfg(feature = "bg_sqlt")]
+            Queue::Sqlite(pool, _, _, _) => {
+                Ok(
+                    super::sqlt::get_jobs(pool, Some(&vec![status.clone()]), None)
+                        .await?
+                        .into_iter()
+                        .map(|job| job_info!(job, None, None).with_error())
+                        .collect(),
+                )
+            }
+            #[cfg(feature = "bg_redis")]
+            Queue::Redis(pool, _, _, _) => Ok(super::redis::list_jobs(pool, status)
+                .await?
+                .into_iter()
+                .map(|(queue, mut job)| {
+                    let error = job.error.take();
+                    job_info!(job, Some(queue), error)
+                })
+                .collect()),
+            Queue::None => Err(not_configured()),
+        }
+    }
+
+    /// Queues the failed job `id` again, to run as soon as possible, and
+    /// returns whether there was such a failed job.
+    ///
+    /// # Errors
+    ///
+    /// When the job could not be queued, or no queue provider is configured
+    #[allow(unused_variables)]
+    pub async fn retry(&self, id: &str) -> Result<bool> {
+        tracing::info!(job_id = id, "retrying failed job");
+        match self.queue {
+            #[cfg(feature = "bg_pg")]
+            Queue::Postgres(pool, _, _, _) => super::pg::retry_job(pool, id).await,
+            #[cfg(feature = "bg_sqlt")]
+            Queue::Sqlite(pool, _, _, _) => super::sqlt::retry_job(pool, id).await,
+            #[cfg(feature = "bg_redis")]
+            Queue::Redis(pool, _, _, _) => super::redis::retry_job(pool, id).await,
+            Queue::None => Err(not_configured()),
+        }
+    }
+
+    /// Deletes the pending, scheduled or failed job `id`, and returns whether
+    /// there was such a job. Jobs being processed are not deleted.
+    ///
+    /// # Errors
+    ///
+    /// When the job could not be deleted, or no queue provider is configured
+    #[allow(unused_variables)]
+    pub async fn delete(&self, id: &str) -> Result<bool> {
+        tracing::info!(job_id = id, "deleting job");
+        match self.queue {
+            #[cfg(feature = "bg_pg")]
+            Queue::Postgres(pool, _, _, _) => super::pg::delete_job(pool, id).await,
+            #[cfg(feature = "bg_sqlt")]
+            Queue::Sqlite(pool, _, _, _) => super::sqlt::delete_job(pool, id).await,
+            #[cfg(feature = "bg_redis")]
+            Queue::Redis(pool, _, _, _) => super::redis::delete_job(pool, id).await,
+            Queue::None => Err(not_configured()),
+        }
+    }
+
+    /// Deletes the jobs waiting in `queue`, and returns how many there were.
+    ///
+    /// # Errors
+    ///
+    /// When the jobs could not be deleted, the provider keeps all the jobs in
+    /// a single queue (Postgres and SQLite, see [`QueueAdmin::purge_all`]), or
+    /// no queue provider is configured
+    #[allow(unused_variables)]
+    pub async fn purge(&self, queue: &str) -> Result<usize> {
+        match self.queue {
+            #[cfg(feature = "bg_pg")]
+            Queue::Postgres(..) => Err(single_queue(queue)),
+            #[cfg(feature = "bg_sqlt")]
+            Queue::Sqlite(..) => Err(single_queue(queue)),
+            #[cfg(feature = "bg_redis")]
+            Queue::Redis(pool, _, _, _) => {
+                tracing::warn!(queue, "purging queue");
+                super::redis::purge_queue(pool, queue).await
+            }
+            Queue::None => Err(not_configured()),
+        }
+    }
+
+    /// Deletes the jobs waiting in all the queues, and returns how many there
+    /// were.
+    ///
+    /// # Errors
+    ///
+    /// When the jobs could not be deleted, or no queue provider is configured
+    pub async fn purge_all(&self) -> Result<usize> {
+        tracing::warn!("purging all queues");
+        match self.queue {
+            #[cfg(feature = "bg_pg")]
+            Queue::Postgres(pool, _, _, _) => super::pg::purge_all(pool).await,
+            #[cfg(feature = "bg_sqlt")]
+            Queue::Sqlite(pool, _, _, _) => super::sqlt::purge_all(pool).await,
+            #[cfg(feature = "bg_redis")]
+            Queue::Redis(pool, _, _, _) => super::redis::purge_all(pool).await,
+            Queue::None => Err(not_configured()),
+        }
+    }
+}
+
+#[cfg(any(feature = "bg_pg", feature = "bg_sqlt"))]
+fn single_queue(queue: &str) -> Error {
+    Error::string(&format!(
+        "cannot purge the `{queue}` queue: the provider keeps the jobs of all the workers in a single queue, purge them all instead"
+    ))
+}
+
+fn not_configured() -> Error {
+    tracing::error!(
+        "No queue provider is configured: compile with at least one queue provider feature"
+    );
+    Error::string("provider not configured")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn job(status: JobStatus, args: Value) -> JobInfo {
+        JobInfo {
+            id: "01J".to_string(),
+            name: "ReportWorker".to_string(),
+            queue: None,
+            status,
+            args,
+            error: None,
+            run_at: Utc::now(),
+            created_at: None,
+            updated_at: None,
+            tags: None,
+        }
+    }
+
+    #[test]
+    fn can_split_the_error_of_failed_jobs() {
+        let failed = job(JobStatus::Failed, json!({ "user_id": 1, "error": "boom" })).with_error();
+        assert_eq!(failed.args, json!({ "user_id": 1 }));
+        assert_eq!(failed.error.as_deref(), Some("boom"));
+
+        // an argument named `error` of a queued job is kept
+        let queued = job(JobStatus::Queued, json!({ "error": "expected" })).with_error();
+        assert_eq!(queued.args, json!({ "error": "expected" }));
+        assert_eq!(queued.error, None);
+    }
+}
@@ -0,0 +1,541 @@
+//! # Job Chains
+//!
+//! Multi-step pipelines, where a job continues only once the previous one
+//! succeeded.
+//!
+//! A job calls [`perform_next`] to perform a follow-up job once it succeeds:
+//! the follow-up is dropped when the job fails.
+//!
+//! A [`Chain`] runs workers one after the other (A → B → C), each step
+//! getting the output of the previous one, set with [`set_output`], or the
+//! same arguments when it sets none. A failing step stops the chain: the
+//! steps after it are not performed.
+//!
+//! ```rust,ignore
+//! Chain::new()
+//!     .then::<ExtractWorker, _>()
+//!     .then::<TransformWorker, _>()
+//!     .then::<LoadWorker, _>()
+//!     .start(&ctx, ExtractArgs { source })
+//!     .await?;
+//!
+//! // in ExtractWorker::perform
+//! chain::set_output(TransformArgs { rows })?;
+//! ```
+//!
+//! With the `BackgroundQueue` worker mode, each step is a job of the queue,
+//! enqueued when the previous one completes, so the arguments of the steps
+//! must serialize to JSON objects to carry the rest of the chain. With the
+//! other modes, the steps are performed one after the other, in place.
+use std::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, OnceLock, Weak},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{BackgroundWorker, Queue};
+use crate::{app::AppContext, config::WorkerMode, Error, Result};
+
+/// The job data key carrying the steps left in the chain of the job.
+const CHAIN_KEY: &str = "_loco_chain";
+
+type BoxFuture<'a> = Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+
+/// Performs a job of a worker, from its serialized arguments.
+type PerformFn = for<'a> fn(&'a AppContext, Value) -> BoxFuture<'a>;
+
+/// Where a job of a chain or a follow-up job goes in the queue.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct QueuedStep {
+    class: String,
+    queue: Option<String>,
+    tags: Option<Vec<String>>,
+}
+
+impl QueuedStep {
+    fn of<W: BackgroundWorker<A>, A: Send + Sync + Serialize + 'static>() -> Self {
+        let tags = W::tags();
+        Self {
+            class: W::class_name(),
+            queue: W::queue(),
+            tags: if tags.is_empty() { None } else { Some(tags) },
+        }
+    }
+}
+
+/// A step of a [`Chain`].
+#[derive(Clone)]
+struct Step {
+    queued: QueuedStep,
+    perform: PerformFn,
+}
+
+/// A job to perform once the current one succeeded.
+struct NextJob {
+    queued: QueuedStep,
+    args: Value,
+    perform: PerformFn,
+}
+
+/// What a job leaves for after it succeeded.
+#[derive(Default)]
+struct Scope {
+    next: Vec<NextJob>,
+    output: Option<Value>,
+}
+
+tokio::task_local! {
+    static SCOPE: RefCell<Scope>;
+}
+
+fn perform_now<W, A>(ctx: &AppContext, args: Value) -> BoxFuture<'_>
+where
+    W: BackgroundWorker<A>,
+    A: Send + Sync + Serialize + DeserializeOwned + 'static,
+{
+    Box::pin(async move { W::build(ctx).perform(serde_json::from_value(args)?).await })
+}
+
+fn perform_later<W, A>(ctx: &AppContext, args: Value) -> BoxFuture<'_>
+where
+    W: BackgroundWorker<A>,
+    A: Send + Sync + Serialize + DeserializeOwned + 'static,
+{
+    Box::pin(async move { W::perform_later(ctx, serde_json::from_value(args)?).await })
+}
+
+/// Performs a job of `W` once the current job succeeded, as with
+/// [`BackgroundWorker::perform_later`].
+///
+/// # Errors
+///
+/// When not called from a job, or when the arguments do not serialize
+pub fn perform_next<W, A>(args: A) -> Result<()>
+where
+    W: BackgroundWorker<A>,
+    A: Send + Sync + Serialize + DeserializeOwned + 'static,
+{
+    let next = NextJob {
+        queued: QueuedStep::of::<W, A>(),
+        args: serde_json::to_value(args)?,
+        perform: perform_later::<W, A>,
+    };
+    SCOPE
+        .try_with(|scope| scope.borrow_mut().next.push(next))
+        .map_err(|_| Error::string("perform_next: not performing a job"))
+}
+
+/// Sets the arguments of the next step of the chain of the current job.
+///
+/// # Errors
+///
+/// When not called from a job, or when the output does not serialize
+pub fn set_output<T: Serialize>(output: T) -> Result<()> {
+    let output = serde_json::to_value(output)?;
+    SCOPE
+        .try_with(|scope| scope.borrow_mut().output = Some(output))
+        .map_err(|_| Error::string("set_output: not performing a job"))
+}
+
+/// Runs `perform` in a job scope, returning what it left for after it.
+async fn scoped<F: Future<Output = Result<()>>>(perform: F) -> (Result<()>, Scope) {
+    SCOPE
+        .scope(RefCell::new(Scope::default()), async {
+            let result = perform.await;
+            (result, SCOPE.with(RefCell::take))
+        })
+        .await
+}
+
+/// A linear chain of workers, each step performed once the previous one
+/// succeeded.
+#[derive(Clone, Default)]
+pub struct Chain {
+    steps: Vec<Step>,
+}
+
+impl Chain {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a step performed by `W`.
+    #[must_use]
+    pub fn then<W, A>(mut self) -> Self
+    where
+        W: BackgroundWorker<A>,
+        A: Send + Sync + Serialize + DeserializeOwned + 'static,
+    {
+        self.steps.push(Step {
+            queued: QueuedStep::of::<W, A>(),
+            perform: perform_now::<W, A>,
+        });
+        self
+    }
+
+    /// The worker class names of the steps, in order.
+    #[must_use]
+    pub fn classes(&self) -> Vec<&str> {
+        self.steps
+            .iter()
+            .map(|step| step.queued.class.as_str())
+            .collect()
+    }
+
+    /// Starts the chain, with the arguments of its first step.
+    ///
+    /// # Errors
+    ///
+    /// When the chain is empty, or its first job could not be enqueued. With
+    /// the `ForegroundBlocking` worker mode, when a step failed.
+    pub async fn start<A: Serialize>(self, ctx: &AppContext, args: A) -> Result<()> {
+        if self.steps.is_empty() {
+            return Err(Error::string("cannot start an empty chain"));
+        }
+        let args = serde_json::to_value(args)?;
+        match &ctx.config.workers.mode {
+            WorkerMode::BackgroundQueue => {
+                let queue = ctx
+                    .queue_provider
+                    .as_ref()
+                    .ok_or(Error::QueueProviderMissing)?;
+                let steps = self.steps.into_iter().map(|step| step.queued).collect();
+                enqueue_step(queue, steps, args).await
+            }
+            WorkerMode::ForegroundBlocking => self.run(ctx, args).await,
+            WorkerMode::BackgroundAsync => {
+                let ctx = ctx.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = self.run(&ctx, args).await {
+                        tracing::error!(err = err.to_string(), "chain failed");
+                    }
+                });
+                Ok(())
+            }
+        }
+    }
+
+    /// Performs the steps in place, one after the other.
+    async fn run(self, ctx: &AppContext, mut args: Value) -> Result<()> {
+        for step in self.steps {
+            let (result, scope) = scoped((step.perform)(ctx, args.clone())).await;
+            if let Err(err) = result {
+                tracing::warn!(
+                    job = step.queued.class,
+                    err = err.to_string(),
+                    "chain step failed, stopping the chain"
+                );
+                return Err(err);
+            }
+            if let Some(output) = scope.output {
+                args = output;
+            }
+            perform_next_jobs(ctx, scope.next).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Performs `perform`, a job performed in place, and its follow-up jobs once
+/// it succeeded.
+pub(crate) async fn perform_directly<F>(ctx: &AppContext, perform: F) -> Result<()>
+where
+    F: Future<Output = Result<()>>,
+{
+    let (result, scope) = scoped(perform).await;
+    result?;
+    perform_next_jobs(ctx, scope.next).await
+}
+
+async fn perform_next_jobs(ctx: &AppContext, next: Vec<NextJob>) -> Result<()> {
+    for job in next {
+        (job.perform)(ctx, job.args).await?;
+    }
+    Ok(())
+}
+
+/// Enqueues the first of `steps`, carrying the others in its arguments.
+async fn enqueue_step(queue: &Queue, mut steps: Vec<QueuedStep>, mut args: Value) -> Result<()> {
+    let step = steps.remove(0);
+    if !steps.is_empty() {
+        let Some(data) = args.as_object_mut() else {
+            return Err(Error::string(
+                "the arguments of a chained job must serialize to a JSON object",
+            ));
+        };
+        data.insert(CHAIN_KEY.to_string(), serde_json::to_value(steps)?);
+    }
+    queue
+        .enqueue(step.class, step.queue, args, step.tags)
+        .await?;
+    Ok(())
+}
+
+/// Removes the steps left in the chain of a job from its data.
+#[cfg_attr(
+    not(any(feature = "bg_redis", feature = "bg_pg", feature = "bg_sqlt")),
+    allow(dead_code)
+)]
+pub(crate) fn take_chain(mut data: Value) -> (Value, Vec<QueuedStep>) {
+    let steps = data
+        .as_object_mut()
+        .and_then(|data| data.remove(CHAIN_KEY))
+        .and_then(|steps| serde_json::from_value(steps).ok())
+        .unwrap_or_default();
+    (data, steps)
+}
+
+/// The queue a registry enqueues the follow-up jobs and the next steps of
+/// chains to, once the queue is shared in the app context.
+#[derive(Clone, Default)]
+pub(crate) struct QueueRef(Arc<OnceLock<Weak<Queue>>>);
+
+impl QueueRef {
+    #[cfg_attr(
+        not(any(feature = "bg_redis", feature = "bg_pg", feature = "bg_sqlt")),
+        allow(dead_code)
+    )]
+    pub(crate) fn set(&self, queue: &Arc<Queue>) {
+        let _ = self.0.set(Arc::downgrade(queue));
+    }
+
+    pub(crate) fn get(&self) -> Option<Arc<Queue>> {
+        self.0.get().and_then(Weak::upgrade)
+    }
+}
+
+/// Performs `perform`, a job of the queue, then enqueues its follow-up jobs
+/// and the next step of its `chain` once it succeeded. The job fails when
+/// they cannot be enqueued.
+#[cfg_attr(
+    not(any(feature = "bg_redis", feature = "bg_pg", feature = "bg_sqlt")),
+    allow(dead_code)
+)]
+pub(crate) async fn perform_queued<F>(
+    queue: &QueueRef,
+    chain: Vec<QueuedStep>,
+    args: Value,
+    perform: F,
+) -> Result<()>
+where
+    F: Future<Output = Result<()>>,
+{
+    let (result, scope) = scoped(perform).await;
+    if let Err(err) = result {
+        if let Some(step) = chain.first() {
+            tracing::warn!(
+                next = step.class,
+                err = err.to_string(),
+                "chain step failed, stopping the chain"
+            );
+        }
+        return Err(err);
+    }
+    if scope.next.is_empty() && chain.is_empty() {
+        return Ok(());
+    }
+
+    let queue = queue
+        .get()
+        .ok_or_else(|| Error::string("no queue to enqueue the next jobs to"))?;
+    for job in scope.next {
+        queue
+            .enqueue(
+                job.queued.class,
+                job.queued.queue,
+                job.args,
+                job.queued.tags,
+            )
+            .await?;
+    }
+    if !chain.is_empty() {
+        enqueue_step(&queue, chain, scope.output.unwrap_or(args)).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    static PERFORMED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    // the tests share `PERFORMED`
+    static SERIAL: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Numbers {
+        values: Vec<i64>,
+    }
+
+    struct ParseWorker;
+
+    #[async_trait]
+    impl BackgroundWorker<String> for ParseWorker {
+        fn build(_ctx: &AppContext) -> Self {
+            Self
+        }
+
+        async fn perform(&self, args: String) -> Result<()> {
+            let values = args
+                .split(',')
+                .map(|value| value.trim().parse().map_err(Error::msg))
+                .collect::<Result<Vec<i64>>>()?;
+            PERFORMED.lock().unwrap().push(format!("parse {args}"));
+            set_output(Numbers { values })
+        }
+    }
+
+    struct SumWorker;
+
+    #[async_trait]
+    impl BackgroundWorker<Numbers> for SumWorker {
+        fn build(_ctx: &AppContext) -> Self {
+            Self
+        }
+
+        async fn perform(&self, args: Numbers) -> Result<()> {
+            let sum: i64 = args.values.iter().sum();
+            PERFORMED.lock().unwrap().push(format!("sum {sum}"));
+            perform_next::<NotifyWorker, _>(Total { sum })
+        }
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Total {
+        sum: i64,
+    }
+
+    struct NotifyWorker;
+
+    #[async_trait]
+    impl BackgroundWorker<Total> for NotifyWorker {
+        fn build(_ctx: &AppContext) -> Self {
+            Self
+        }
+
+        async fn perform(&self, args: Total) -> Result<()> {
+            PERFORMED
+                .lock()
+                .unwrap()
+                .push(format!("notify {}", args.sum));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn can_run_chains() {
+        let _serial = SERIAL.lock().await;
+        let ctx = tests_cfg::app::get_app_context().await;
+        let chain = Chain::new().then::<ParseWorker, _>().then::<SumWorker, _>();
+        assert_eq!(chain.classes(), vec!["ParseWorker", "SumWorker"]);
+
+        chain.clone().start(&ctx, "1, 2, 3").await.unwrap();
+        assert_eq!(
+            std::mem::take(&mut *PERFORMED.lock().unwrap()),
+            vec!["parse 1, 2, 3", "sum 6", "notify 6"]
+        );
+
+        // a failing step stops the chain
+        assert!(chain.start(&ctx, "1, x").await.is_err());
+        assert!(PERFORMED.lock().unwrap().is_empty());
+
+        assert!(perform_next::<NotifyWorker, _>(Total { sum: 1 }).is_err());
+        assert!(Chain::new().start(&ctx, ()).await.is_err());
+    }
+
+    #[cfg(feature = "bg_sqlt")]
+    #[tokio::test]
+    async fn can_run_chains_on_the_queue() {
+        let _serial = SERIAL.lock().await;
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .create()
+            .expect("create temp folder");
+        let qcfg = crate::config::SqliteQueueConfig {
+            uri: format!(
+                "sqlite://{}?mode=rwc",
+                tree_fs.root.join("queue.sqlite").display()
+            ),
+            dangerously_flush: false,
+            enable_logging: false,
+            max_connections: 1,
+            min_connections: 1,
+            connect_timeout: 500,
+            idle_timeout: 500,
+            poll_interval_sec: 1,
+            num_workers: 1,
+            limit: None,
+        };
+        let queue = super::super::sqlt::create_provider(&qcfg)
+            .await
+            .unwrap()
+            .share()
+            .await;
+        queue.setup().await.unwrap();
+
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        ctx.config.workers.mode = WorkerMode::BackgroundQueue;
+        ctx.queue_provider = Some(queue.clone());
+        queue.register(ParseWorker::build(&ctx)).await.unwrap();
+        queue.register(SumWorker::build(&ctx)).await.unwrap();
+        queue.register(NotifyWorker::build(&ctx)).await.unwrap();
+
+        // the chained arguments are JSON objects
+        assert!(Chain::new()
+            .then::<ParseWorker, _>()
+            .then::<SumWorker, _>()
+            .start(&ctx, "4, 5")
+            .await
+            .is_err());
+        Chain::new()
+            .then::<SumWorker, _>()
+            .then::<SumWorker, _>()
+            .start(&ctx, Numbers { values: vec![4, 5] })
+            .await
+            .unwrap();
+
+        let worker = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.run(vec![]).await }
+        });
+        for _ in 0..100 {
+            if PERFORMED.lock().unwrap().len() >= 4 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        worker.abort();
+
+        let mut performed = std::mem::take(&mut *PERFORMED.lock().unwrap());
+        performed.sort();
+        assert_eq!(performed, vec!["notify 9", "notify 9", "sum 9", "sum 9"]);
+    }
+
+    #[test]
+    fn can_take_chain_from_job_data() {
+        let step = QueuedStep {
+            class: "SumWorker".to_string(),
+            queue: None,
+            tags: None,
+        };
+        let data = serde_json::json!({ "values": [1], CHAIN_KEY: [step] });
+        assert_eq!(
+            take_chain(data),
+            (serde_json::json!({ "values": [1] }), vec![step])
+        );
+        assert_eq!(
+            take_chain(serde_json::json!(1)),
+            (serde_json::json!(1), vec![])
+        );
+    }
+}
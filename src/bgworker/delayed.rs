@@ -0,0 +1,98 @@
+//! # Delayed Jobs
+//!
+//! The jobs performed later with [`super::BackgroundWorker::perform_at`] by
+//! the in-process worker modes. They are performed by a task sleeping until
+//! they are due, or, when the app context holds [`DelayedJobs`], kept there
+//! until a test travels past them with `testing::time::TestContext`.
+use std::{future::Future, pin::Pin, sync::Mutex};
+
+use chrono::{DateTime, Utc};
+
+use crate::{app::AppContext, Result};
+
+type BoxFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+
+/// A job performed at a later time.
+pub struct DelayedJob {
+    pub at: DateTime<Utc>,
+    pub class: String,
+    perform: Box<dyn FnOnce(AppContext) -> BoxFuture + Send>,
+}
+
+impl DelayedJob {
+    pub fn new(
+        at: DateTime<Utc>,
+        class: String,
+        perform: impl FnOnce(AppContext) -> BoxFuture + Send + 'static,
+    ) -> Self {
+        Self {
+            at,
+            class,
+            perform: Box::new(perform),
+        }
+    }
+
+    pub async fn perform(self, ctx: &AppContext) -> Result<()> {
+        (self.perform)(ctx.clone()).await
+    }
+}
+
+/// The delayed jobs held until a test performs them, in the shared store of
+/// the app context.
+#[derive(Default)]
+pub struct DelayedJobs {
+    jobs: Mutex<Vec<DelayedJob>>,
+}
+
+impl DelayedJobs {
+    fn hold(&self, job: DelayedJob) {
+        self.jobs.lock().unwrap().push(job);
+    }
+
+    /// The time of the next job, if any.
+    #[cfg(feature = "testing")]
+    pub fn next_at(&self) -> Option<DateTime<Utc>> {
+        self.jobs.lock().unwrap().iter().map(|job| job.at).min()
+    }
+
+    /// Takes the jobs due at `now`, the earliest first, and jobs due at the
+    /// same time in the order they were delayed.
+    #[cfg(feature = "testing")]
+    pub fn take_due(&self, now: DateTime<Utc>) -> Vec<DelayedJob> {
+        let mut due = {
+            let mut jobs = self.jobs.lock().unwrap();
+            let (due, later) = jobs.drain(..).partition::<Vec<_>, _>(|job| job.at <= now);
+            *jobs = later;
+            due
+        };
+        due.sort_by_key(|job| job.at);
+        due
+    }
+
+    #[cfg(feature = "testing")]
+    pub fn len(&self) -> usize {
+        self.jobs.lock().unwrap().len()
+    }
+}
+
+/// Performs `job` once it is due, with the in-process worker modes.
+pub fn perform_at(ctx: &AppContext, job: DelayedJob) {
+    if let Some(jobs) = ctx.shared_store.get_ref::<DelayedJobs>() {
+        tracing::debug!(worker = job.class, at = %job.at, "holding delayed job");
+        jobs.hold(job);
+        return;
+    }
+    let delay = (job.at - ctx.now()).to_std().unwrap_or_default();
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        let class = job.class.clone();
+        if let Err(err) = job.perform(&ctx).await {
+            tracing::error!(
+                worker = class,
+                err = err.to_string(),
+                "worker failed to perform delayed job"
+            );
+        }
+    });
+}
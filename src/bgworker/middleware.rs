@@ -0,0 +1,261 @@
+//! # Worker Middleware
+//!
+//! Cross-cutting behavior around every job performed by the queue workers,
+//! like HTTP middlewares for the worker tier: a tracing span, a database
+//! transaction, timing or alerting. Middlewares are added to the queue in
+//! [`crate::app::Hooks::connect_workers`], and wrap the `perform` of every
+//! job, the first added being the outermost:
+//!
+//! ```rust,ignore
+//! async fn connect_workers(ctx: &AppContext, queue: &Queue) -> Result<()> {
+//!     queue.add_middleware(middleware::timing()).await?;
+//!     queue
+//!         .add_middleware(middleware::from_fn(ctx, |ctx, job, next| async move {
+//!             tracing::info!(job = job.name, env = %ctx.environment, "starting job");
+//!             next.run().await
+//!         }))
+//!         .await?;
+//!     queue.register(DownloadWorker::build(ctx)).await?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! A panic in a middleware or a job fails the job, as an error would.
+//! Middlewares only run for jobs performed by a queue worker, not in the
+//! `ForegroundBlocking` and `BackgroundAsync` worker modes.
+use std::{
+    future::Future,
+    panic::AssertUnwindSafe,
+    pin::Pin,
+    sync::{Arc, RwLock},
+    time::Instant,
+};
+
+use futures_util::FutureExt;
+
+use crate::{app::AppContext, Error, Result};
+
+type BoxFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+
+/// The job a middleware runs around.
+#[derive(Debug, Clone)]
+pub struct JobInfo {
+    /// The job id given by the queue
+    pub id: String,
+    /// The worker class name
+    pub name: String,
+}
+
+/// Wraps the `perform` of every job.
+pub trait JobMiddleware: Send + Sync {
+    /// Runs around `job`, calling [`Next::run`] to continue with the next
+    /// middleware and eventually the job itself.
+    fn call(&self, job: JobInfo, next: Next) -> BoxFuture;
+}
+
+/// The rest of the middleware chain, ending with the job.
+pub struct Next {
+    middlewares: Arc<[Arc<dyn JobMiddleware>]>,
+    index: usize,
+    job: JobInfo,
+    perform: BoxFuture,
+}
+
+impl Next {
+    /// Runs the next middleware, or the job after the last one.
+    ///
+    /// # Errors
+    /// When the job or a middleware fails
+    pub async fn run(self) -> Result<()> {
+        let Some(middleware) = self.middlewares.get(self.index).cloned() else {
+            return self.perform.await;
+        };
+        let job = self.job.clone();
+        let next = Self {
+            index: self.index + 1,
+            ..self
+        };
+        middleware.call(job, next).await
+    }
+}
+
+/// The middlewares of a queue, shared with the registered job handlers.
+#[derive(Clone, Default)]
+pub(crate) struct JobMiddlewares(Arc<RwLock<Vec<Arc<dyn JobMiddleware>>>>);
+
+impl JobMiddlewares {
+    /// Adds a middleware, wrapping the jobs of the handlers already registered.
+    #[cfg_attr(
+        not(any(feature = "bg_redis", feature = "bg_pg", feature = "bg_sqlt")),
+        allow(dead_code)
+    )]
+    pub(crate) fn add(&self, middleware: Arc<dyn JobMiddleware>) -> Result<()> {
+        self.0
+            .write()
+            .map_err(|_| Error::string("cannot add worker middleware"))?
+            .push(middleware);
+        Ok(())
+    }
+
+    /// Performs `job` through the middlewares, turning a panic into a
+    /// failure.
+    #[cfg_attr(
+        not(any(feature = "bg_redis", feature = "bg_pg", feature = "bg_sqlt")),
+        allow(dead_code)
+    )]
+    pub(crate) async fn run<F>(&self, job: JobInfo, perform: F) -> Result<()>
+    where
+        F: Future<Output = Result<()>> + Send + 'static,
+    {
+        let middlewares = self.0.read().map_or_else(
+            |_| Arc::new([]) as Arc<[_]>,
+            |middlewares| middlewares.iter().cloned().collect(),
+        );
+        let next = Next {
+            middlewares,
+            index: 0,
+            job,
+            perform: Box::pin(perform),
+        };
+        match AssertUnwindSafe(next.run()).catch_unwind().await {
+            Ok(result) => result,
+            Err(panic) => {
+                let panic_msg = panic
+                    .downcast_ref::<String>()
+                    .map(String::as_str)
+                    .or_else(|| panic.downcast_ref::<&str>().copied())
+                    .unwrap_or("Unknown panic occurred");
+                tracing::error!(err = panic_msg, "worker panicked");
+                Err(Error::string(panic_msg))
+            }
+        }
+    }
+}
+
+struct FromFn<F> {
+    ctx: AppContext,
+    f: F,
+}
+
+impl<F, Fut> JobMiddleware for FromFn<F>
+where
+    F: Fn(AppContext, JobInfo, Next) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    fn call(&self, job: JobInfo, next: Next) -> BoxFuture {
+        Box::pin((self.f)(self.ctx.clone(), job, next))
+    }
+}
+
+/// Creates a middleware from an `async fn(ctx, job, next)`.
+pub fn from_fn<F, Fut>(ctx: &AppContext, f: F) -> impl JobMiddleware
+where
+    F: Fn(AppContext, JobInfo, Next) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    FromFn {
+        ctx: ctx.clone(),
+        f,
+    }
+}
+
+struct Timing;
+
+impl JobMiddleware for Timing {
+    fn call(&self, job: JobInfo, next: Next) -> BoxFuture {
+        Box::pin(async move {
+            let started = Instant::now();
+            let result = next.run().await;
+            let elapsed_ms = started.elapsed().as_millis();
+            match &result {
+                Ok(()) => tracing::info!(job = job.name, job_id = job.id, elapsed_ms, "job done"),
+                Err(err) => tracing::warn!(
+                    job = job.name,
+                    job_id = job.id,
+                    elapsed_ms,
+                    err = err.to_string(),
+                    "job failed"
+                ),
+            }
+            result
+        })
+    }
+}
+
+/// Logs how long every job took, and whether it failed.
+#[must_use]
+pub fn timing() -> impl JobMiddleware {
+    Timing
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    fn job() -> JobInfo {
+        JobInfo {
+            id: "01J".to_string(),
+            name: "DownloadWorker".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn can_run_middlewares_in_order() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let middlewares = JobMiddlewares::default();
+        for name in ["outer", "inner"] {
+            let calls = calls.clone();
+            let middleware = from_fn(&ctx, move |_ctx, job, next| {
+                let calls = calls.clone();
+                async move {
+                    calls.lock().unwrap().push(format!("{name} {}", job.name));
+                    let result = next.run().await;
+                    calls.lock().unwrap().push(format!("{name} done"));
+                    result
+                }
+            });
+            middlewares.add(Arc::new(middleware)).unwrap();
+        }
+        middlewares.add(Arc::new(timing())).unwrap();
+
+        let perform_calls = calls.clone();
+        let result = middlewares
+            .run(job(), async move {
+                perform_calls.lock().unwrap().push("perform".to_string());
+                Ok(())
+            })
+            .await;
+        assert!(result.is_ok());
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                "outer DownloadWorker",
+                "inner DownloadWorker",
+                "perform",
+                "inner done",
+                "outer done"
+            ]
+        );
+    }
+
+    #[allow(clippy::unused_async)]
+    async fn explode() -> Result<()> {
+        panic!("boom")
+    }
+
+    #[tokio::test]
+    async fn can_fail_panicking_jobs() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let middlewares = JobMiddlewares::default();
+        middlewares
+            .add(Arc::new(from_fn(&ctx, |_ctx, _job, next| next.run())))
+            .unwrap();
+
+        let result = middlewares.run(job(), explode()).await;
+        assert_eq!(result.unwrap_err().to_string(), "boom");
+    }
+}
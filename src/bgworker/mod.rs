@@ -1,4 +1,5 @@
 use std::{
+    collections::BTreeMap,
     fs::File,
     io::Write,
     path::{Path, PathBuf},
@@ -6,14 +7,20 @@ use std::{
 };
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 #[cfg(feature = "cli")]
 use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 use serde_variant::to_variant_name;
+pub mod admin;
+pub mod chain;
+pub(crate) mod delayed;
+pub mod middleware;
 #[cfg(feature = "bg_pg")]
 pub mod pg;
 #[cfg(feature = "bg_redis")]
 pub mod redis;
+pub mod schedules;
 #[cfg(feature = "bg_sqlt")]
 pub mod sqlt;
 
@@ -62,6 +69,66 @@ impl std::fmt::Display for JobStatus {
     }
 }
 
+/// The jobs processed by [`Queue::drain`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DrainReport {
+    /// The jobs which succeeded
+    pub completed: usize,
+    /// The jobs which failed
+    pub failed: usize,
+    /// The jobs without a registered worker, left processing
+    pub unhandled: usize,
+    /// Whether the timeout passed before the queue was empty
+    pub timed_out: bool,
+}
+
+impl DrainReport {
+    /// The number of jobs run, successfully or not.
+    #[must_use]
+    pub const fn processed(&self) -> usize {
+        self.completed + self.failed
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn count(&mut self, status: Option<JobStatus>) {
+        match status {
+            Some(JobStatus::Completed) => self.completed += 1,
+            Some(_) => self.failed += 1,
+            None => self.unhandled += 1,
+        }
+    }
+}
+
+/// The encoding of the jobs stored by the Redis queue provider.
+///
+/// Each job records the codec it was encoded with, so workers decode jobs of
+/// either codec while an app rolls from one codec to the other. The Postgres
+/// and Sqlite providers always store their jobs as JSON.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    #[default]
+    Json,
+    /// `MessagePack`, more compact and faster to encode. Requires the `msgpack`
+    /// feature.
+    MsgPack,
+}
+
+/// The outcome of enqueuing jobs with uniqueness keys, see
+/// [`Queue::enqueue_bulk_unique`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UniqueBatch {
+    /// The keys of the enqueued jobs, in the order they were given
+    pub enqueued: Vec<String>,
+    /// The keys of the jobs skipped, because a job with the same key was
+    /// still waiting in the queue
+    pub skipped: Vec<String>,
+}
+
+/// How often an enqueue blocked by a full queue checks its depth again.
+const QUEUE_FULL_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
 // Queue struct now holds both a QueueProvider and QueueRegistrar
 pub enum Queue {
     #[cfg(feature = "bg_redis")]
@@ -91,25 +158,80 @@ pub enum Queue {
 impl Queue {
     /// Add a job to the queue
     ///
+    /// Returns the id assigned to the job by the queue provider, or `None`
+    /// when no provider is compiled in.
+    ///
     /// # Errors
     ///
-    /// This function will return an error if fails
-    #[allow(unused_variables)]
+    /// This function will return an error if fails, or with
+    /// [`Error::QueueFull`] when the queue reached its max depth
     pub async fn enqueue<A: Serialize + Send + Sync>(
         &self,
         class: String,
         queue: Option<String>,
         args: A,
         tags: Option<Vec<String>>,
-    ) -> Result<()> {
-        tracing::debug!(worker = class, queue = ?queue, tags = ?tags, "Enqueuing background job");
-        match self {
+    ) -> Result<Option<String>> {
+        self.enqueue_with_priority(class, queue, args, tags, 0)
+            .await
+    }
+
+    /// Add a job to the queue with a `priority`: on Redis, jobs with a higher
+    /// priority are dequeued first, and jobs of the same priority in the
+    /// order they were enqueued. Other providers ignore the priority.
+    ///
+    /// Returns the id assigned to the job by the queue provider, or `None`
+    /// when no provider is compiled in.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if fails, or with
+    /// [`Error::QueueFull`] when the queue reached its max depth
+    pub async fn enqueue_with_priority<A: Serialize + Send + Sync>(
+        &self,
+        class: String,
+        queue: Option<String>,
+        args: A,
+        tags: Option<Vec<String>>,
+        priority: i32,
+    ) -> Result<Option<String>> {
+        self.reserve(queue.as_deref(), 1).await?;
+        self.push(class, queue, args, tags, priority).await
+    }
+
+    /// Adds a job to the queue, whatever its depth.
+    #[allow(unused_variables)]
+    async fn push<A: Serialize + Send + Sync>(
+        &self,
+        class: String,
+        queue: Option<String>,
+        args: A,
+        tags: Option<Vec<String>>,
+        priority: i32,
+    ) -> Result<Option<String>> {
+        tracing::debug!(worker = class, queue = ?queue, tags = ?tags, priority, "Enqueuing background job");
+        let args = {
+            let mut args = serde_json::to_value(args)?;
+            crate::request_context::inject_job_context(&mut args);
+            #[cfg(feature = "otel")]
+            crate::otel::inject_job_context(&mut args);
+            args
+        };
+        let job_id = match self {
             #[cfg(feature = "bg_redis")]
-            Self::Redis(pool, _, _, _) => {
-                redis::enqueue(pool, class, queue, args, tags).await?;
+            Self::Redis(pool, registry, run_opts, _) => {
+                let codec = registry
+                    .lock()
+                    .await
+                    .codec(&class)
+                    .unwrap_or(run_opts.codec);
+                Some(
+                    redis::enqueue_with_priority(pool, class, queue, args, tags, codec, priority)
+                        .await?,
+                )
             }
             #[cfg(feature = "bg_pg")]
-            Self::Postgres(pool, _, _, _) => {
+            Self::Postgres(pool, _, _, _) => Some(
                 pg::enqueue(
                     pool,
                     &class,
@@ -119,10 +241,10 @@ impl Queue {
                     tags,
                 )
                 .await
-                .map_err(Box::from)?;
-            }
+                .map_err(Box::from)?,
+            ),
             #[cfg(feature = "bg_sqlt")]
-            Self::Sqlite(pool, _, _, _) => {
+            Self::Sqlite(pool, _, _, _) => Some(
                 sqlt::enqueue(
                     pool,
                     &class,
@@ -132,11 +254,287 @@ impl Queue {
                     tags,
                 )
                 .await
-                .map_err(Box::from)?;
+                .map_err(Box::from)?,
+            ),
+            _ => None,
+        };
+        Ok(job_id)
+    }
+
+    /// Add a job to the queue, to be run at `run_at` rather than as soon as
+    /// possible.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if fails, when the provider does
+    /// not support delayed jobs (Redis does not), or with
+    /// [`Error::QueueFull`] when the queue reached its max depth
+    #[allow(unused_variables)]
+    pub async fn enqueue_at<A: Serialize + Send + Sync>(
+        &self,
+        class: String,
+        queue: Option<String>,
+        args: A,
+        tags: Option<Vec<String>>,
+        run_at: DateTime<Utc>,
+    ) -> Result<Option<String>> {
+        self.reserve(queue.as_deref(), 1).await?;
+        tracing::debug!(worker = class, queue = ?queue, tags = ?tags, run_at = %run_at, "Enqueuing delayed background job");
+        let args = {
+            let mut args = serde_json::to_value(args)?;
+            crate::request_context::inject_job_context(&mut args);
+            #[cfg(feature = "otel")]
+            crate::otel::inject_job_context(&mut args);
+            args
+        };
+        match self {
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(pool, _, _, _) => Ok(Some(
+                pg::enqueue(
+                    pool,
+                    &class,
+                    serde_json::to_value(args)?,
+                    run_at,
+                    None,
+                    tags,
+                )
+                .await
+                .map_err(Box::from)?,
+            )),
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(pool, _, _, _) => Ok(Some(
+                sqlt::enqueue(
+                    pool,
+                    &class,
+                    serde_json::to_value(args)?,
+                    run_at,
+                    None,
+                    tags,
+                )
+                .await
+                .map_err(Box::from)?,
+            )),
+            _ => Err(Error::string(
+                "delayed jobs are only supported by the Postgres and Sqlite queue providers",
+            )),
+        }
+    }
+
+    /// Add many jobs for the same worker to the queue
+    ///
+    /// On Redis, jobs are sent in pipelined chunks of
+    /// `queue.batch_chunk_size`, one round-trip per chunk. Other providers
+    /// enqueue the jobs one by one. Returns the job ids in the order of `args`,
+    /// or `None` when no provider is compiled in.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if fails, or with
+    /// [`Error::QueueFull`] when the batch does not fit under the max depth of
+    /// the queue
+    #[allow(unused_variables)]
+    pub async fn enqueue_batch<A: Serialize + Send + Sync>(
+        &self,
+        class: String,
+        queue: Option<String>,
+        args: Vec<A>,
+        tags: Option<Vec<String>>,
+    ) -> Result<Option<Vec<String>>> {
+        self.reserve(queue.as_deref(), args.len()).await?;
+        tracing::debug!(worker = class, queue = ?queue, tags = ?tags, count = args.len(), "Enqueuing background job batch");
+        let args = args
+            .into_iter()
+            .map(|args| {
+                let mut args = serde_json::to_value(args)?;
+                crate::request_context::inject_job_context(&mut args);
+                #[cfg(feature = "otel")]
+                crate::otel::inject_job_context(&mut args);
+                Ok(args)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        match self {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(pool, registry, run_opts, _) => {
+                let codec = registry
+                    .lock()
+                    .await
+                    .codec(&class)
+                    .unwrap_or(run_opts.codec);
+                Ok(Some(
+                    redis::enqueue_batch(
+                        pool,
+                        class,
+                        queue,
+                        args,
+                        tags,
+                        codec,
+                        run_opts.batch_chunk_size,
+                    )
+                    .await?,
+                ))
             }
-            _ => {}
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(..) => self.enqueue_each(class, queue, args, tags).await,
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(..) => self.enqueue_each(class, queue, args, tags).await,
+            _ => Ok(None),
+        }
+    }
+
+    /// Add many jobs for the same worker to the queue, each with a
+    /// uniqueness key such as the recipient of a notification. The jobs
+    /// whose key is held by a job still waiting in the queue, or by an
+    /// earlier job of `jobs`, are skipped. A key is held until its job is
+    /// dequeued, or for `lock_ttl` at most.
+    ///
+    /// Only supported by the Redis provider, which takes the locks of all the
+    /// jobs in a single pipelined round-trip.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if fails, when the provider is not
+    /// Redis, or with [`Error::QueueFull`] when the jobs do not fit under the
+    /// max depth of the queue
+    #[allow(unused_variables)]
+    pub async fn enqueue_bulk_unique<A: Serialize + Send + Sync>(
+        &self,
+        class: String,
+        queue: Option<String>,
+        jobs: Vec<(String, A)>,
+        tags: Option<Vec<String>>,
+        lock_ttl: std::time::Duration,
+    ) -> Result<UniqueBatch> {
+        self.reserve(queue.as_deref(), jobs.len()).await?;
+        tracing::debug!(worker = class, queue = ?queue, tags = ?tags, count = jobs.len(), "Enqueuing unique background job batch");
+        let jobs = jobs
+            .into_iter()
+            .map(|(key, args)| {
+                let mut args = serde_json::to_value(args)?;
+                crate::request_context::inject_job_context(&mut args);
+                #[cfg(feature = "otel")]
+                crate::otel::inject_job_context(&mut args);
+                Ok((key, args))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        match self {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(pool, registry, run_opts, _) => {
+                let codec = registry
+                    .lock()
+                    .await
+                    .codec(&class)
+                    .unwrap_or(run_opts.codec);
+                let batch =
+                    redis::enqueue_bulk_unique(pool, class, queue, jobs, tags, codec, lock_ttl)
+                        .await?;
+                tracing::debug!(
+                    enqueued = batch.enqueued.len(),
+                    skipped = batch.skipped.len(),
+                    "Enqueued unique background job batch"
+                );
+                Ok(batch)
+            }
+            _ => Err(Error::string(
+                "unique jobs are only supported by the Redis queue provider",
+            )),
+        }
+    }
+
+    #[cfg(any(feature = "bg_pg", feature = "bg_sqlt"))]
+    async fn enqueue_each<A: Serialize + Send + Sync>(
+        &self,
+        class: String,
+        queue: Option<String>,
+        args: Vec<A>,
+        tags: Option<Vec<String>>,
+    ) -> Result<Option<Vec<String>>> {
+        let mut job_ids = Vec::with_capacity(args.len());
+        for args in args {
+            if let Some(job_id) = self
+                .push(class.clone(), queue.clone(), args, tags.clone(), 0)
+                .await?
+            {
+                job_ids.push(job_id);
+            }
+        }
+        Ok(Some(job_ids))
+    }
+
+    /// The number of jobs waiting in `queue`, the default queue when `None`,
+    /// for monitoring. The Postgres and Sqlite providers have a single queue,
+    /// and ignore `queue`. Returns `None` when no provider is compiled in.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the queue cannot be read
+    #[allow(unused_variables)]
+    pub async fn depth(&self, queue: Option<&str>) -> Result<Option<usize>> {
+        match self {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(pool, _, _, _) => Ok(Some(
+                redis::queue_depth(pool, queue.unwrap_or("default")).await?,
+            )),
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(pool, _, _, _) => Ok(Some(pg::queued_count(pool).await?)),
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(pool, _, _, _) => Ok(Some(sqlt::queued_count(pool).await?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// The configured limit of `queue`, if any.
+    #[allow(unused_variables)]
+    fn limit(&self, queue: Option<&str>) -> Option<&config::QueueLimit> {
+        match self {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(_, _, run_opts, _) => run_opts.limits.get(queue.unwrap_or("default")),
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(_, _, run_opts, _) => run_opts.limit.as_ref(),
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(_, _, run_opts, _) => run_opts.limit.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Checks that `count` more jobs fit in `queue`, waiting for it to drain
+    /// when its limit blocks.
+    async fn reserve(&self, queue: Option<&str>, count: usize) -> Result<()> {
+        let Some(limit) = self.limit(queue) else {
+            return Ok(());
+        };
+        let deadline =
+            tokio::time::Instant::now() + std::time::Duration::from_millis(limit.block_timeout_ms);
+        loop {
+            let depth = self.depth(queue).await?.unwrap_or_default();
+            if depth + count <= limit.max_depth {
+                return Ok(());
+            }
+            if limit.when_full == config::WhenFull::Reject
+                || tokio::time::Instant::now() >= deadline
+            {
+                let queue = queue.unwrap_or("default").to_string();
+                tracing::warn!(queue, depth, max_depth = limit.max_depth, "queue is full");
+                return Err(Error::QueueFull {
+                    queue,
+                    depth,
+                    max_depth: limit.max_depth,
+                });
+            }
+            tokio::time::sleep(QUEUE_FULL_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Returns the queue references of the registered workers and of
+    /// `workers.queues` that the provider never dequeues from. Only the Redis
+    /// provider has named queues.
+    pub async fn unknown_queues(&self) -> Vec<String> {
+        match self {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(_, registry, run_opts, _) => {
+                redis::unknown_queues(run_opts, registry.lock().await.queues())
+            }
+            _ => vec![],
         }
-        Ok(())
     }
 
     /// Register a worker
@@ -174,6 +572,144 @@ impl Queue {
         Ok(())
     }
 
+    /// Builds a worker with [`BackgroundWorker::try_build`] and registers it,
+    /// recording a failure, or a panic, rather than returning it. The boot
+    /// checks the failures once [`crate::app::Hooks::connect_workers`]
+    /// returns, following `workers.on_registration_error`. Returns whether
+    /// the worker was registered.
+    pub async fn try_register<
+        A: Serialize + Send + Sync + 'static + for<'de> serde::Deserialize<'de>,
+        W: BackgroundWorker<A> + 'static,
+    >(
+        &self,
+        ctx: &AppContext,
+    ) -> bool {
+        let built = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| W::try_build(ctx)))
+            .unwrap_or_else(|panic| {
+                let panic_msg = panic
+                    .downcast_ref::<String>()
+                    .map(String::as_str)
+                    .or_else(|| panic.downcast_ref::<&str>().copied())
+                    .unwrap_or("Unknown panic occurred");
+                Err(Error::string(panic_msg))
+            });
+        let error = match built {
+            Ok(worker) => match self.register(worker).await {
+                Ok(()) => return true,
+                Err(err) => err,
+            },
+            Err(err) => err,
+        };
+        tracing::error!(worker = W::class_name(), error = %error, "Failed to register background worker");
+        match self {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(_, registry, _, _) => {
+                registry
+                    .lock()
+                    .await
+                    .add_failure(W::class_name(), error.to_string());
+            }
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(_, registry, _, _) => {
+                registry
+                    .lock()
+                    .await
+                    .add_failure(W::class_name(), error.to_string());
+            }
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(_, registry, _, _) => {
+                registry
+                    .lock()
+                    .await
+                    .add_failure(W::class_name(), error.to_string());
+            }
+            _ => {}
+        }
+        false
+    }
+
+    /// Returns the class names of the registered workers, which process
+    /// their jobs.
+    pub async fn workers(&self) -> Vec<String> {
+        let mut workers = match self {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(_, registry, _, _) => {
+                registry.lock().await.handlers().keys().cloned().collect()
+            }
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(_, registry, _, _) => {
+                registry.lock().await.handlers().keys().cloned().collect()
+            }
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(_, registry, _, _) => {
+                registry.lock().await.handlers().keys().cloned().collect()
+            }
+            _ => Vec::new(),
+        };
+        workers.sort();
+        workers
+    }
+
+    /// Returns the workers which failed to register with
+    /// [`Queue::try_register`], by class name, with their error.
+    pub async fn registration_failures(&self) -> BTreeMap<String, String> {
+        match self {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(_, registry, _, _) => registry.lock().await.failures().clone(),
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(_, registry, _, _) => registry.lock().await.failures().clone(),
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(_, registry, _, _) => registry.lock().await.failures().clone(),
+            _ => BTreeMap::new(),
+        }
+    }
+
+    /// Adds a middleware around every job performed by the workers of this
+    /// queue, see [`middleware`]. The first added middleware is the
+    /// outermost.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if fails
+    #[allow(unused_variables)]
+    pub async fn add_middleware(
+        &self,
+        middleware: impl middleware::JobMiddleware + 'static,
+    ) -> Result<()> {
+        match self {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(_, registry, _, _) => {
+                registry.lock().await.add_middleware(Arc::new(middleware))?;
+            }
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(_, registry, _, _) => {
+                registry.lock().await.add_middleware(Arc::new(middleware))?;
+            }
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(_, registry, _, _) => {
+                registry.lock().await.add_middleware(Arc::new(middleware))?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Shares the queue, for its workers to enqueue follow-up jobs and the
+    /// next steps of chains, see [`chain`].
+    pub async fn share(self) -> Arc<Self> {
+        let queue = Arc::new(self);
+        match queue.as_ref() {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(_, registry, _, _) => registry.lock().await.queue_ref().set(&queue),
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(_, registry, _, _) => registry.lock().await.queue_ref().set(&queue),
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(_, registry, _, _) => registry.lock().await.queue_ref().set(&queue),
+            Self::None => {}
+        }
+        queue
+    }
+
     /// Runs the worker loop for this [`Queue`].
     ///
     /// # Errors
@@ -213,111 +749,367 @@ impl Queue {
                 );
             }
         }
-        Ok(())
+        Ok(())
+    }
+
+    /// Processes the jobs due now, in the current task, until the queue is
+    /// empty or `timeout` passes, and reports how many ran. Jobs enqueued by
+    /// the processed jobs are processed too, and only the jobs matching `tags`
+    /// are, as with [`Queue::run`].
+    ///
+    /// This is meant for tests, to assert the side effects of jobs without
+    /// running workers and sleeping. Do not call it while workers run, they
+    /// would take some of the jobs. Recurring jobs run again only once their
+    /// interval passed, and Redis runs them again right away, so a queue with
+    /// a recurring job drains until the timeout.
+    ///
+    /// # Errors
+    ///
+    /// When the jobs could not be dequeued, or no queue provider is configured
+    #[allow(unused_variables, unused_mut)]
+    pub async fn drain(
+        &self,
+        tags: &[String],
+        timeout: std::time::Duration,
+    ) -> Result<DrainReport> {
+        let mut report = DrainReport::default();
+        let drained: std::result::Result<Result<()>, tokio::time::error::Elapsed> = match self {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(pool, registry, run_opts, _) => {
+                let handlers = registry.lock().await.handlers().clone();
+                let drain = redis::drain(pool, &handlers, run_opts, tags, &mut report);
+                tokio::time::timeout(timeout, drain).await
+            }
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(pool, registry, _, _) => {
+                let handlers = registry.lock().await.handlers().clone();
+                tokio::time::timeout(timeout, pg::drain(pool, &handlers, tags, &mut report)).await
+            }
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(pool, registry, _, _) => {
+                let handlers = registry.lock().await.handlers().clone();
+                tokio::time::timeout(timeout, sqlt::drain(pool, &handlers, tags, &mut report)).await
+            }
+            Self::None => Ok(Err(Error::QueueProviderMissing)),
+        };
+        match drained {
+            Ok(result) => result?,
+            Err(_) => report.timed_out = true,
+        }
+        tracing::debug!(?report, "drained the job queue");
+        Ok(report)
+    }
+
+    /// Process worker task handles and handle any errors
+    ///
+    /// # Errors
+    /// This function will return an error if a worker task fails to join
+    #[allow(dead_code)]
+    async fn process_worker_handles(handles: Vec<tokio::task::JoinHandle<()>>) -> Result<()> {
+        let handle_count = handles.len();
+        tracing::debug!(worker_count = handle_count, "Processing worker handles");
+
+        for (index, handle) in handles.into_iter().enumerate() {
+            if let Err(e) = handle.await {
+                if e.is_cancelled() {
+                    tracing::debug!(
+                        worker_index = index,
+                        "Worker task cancelled during shutdown"
+                    );
+                } else if e.is_panic() {
+                    tracing::error!(worker_index = index, "Worker task panicked");
+                    std::panic::resume_unwind(e.into_panic());
+                } else {
+                    tracing::error!(worker_index = index, error = ?e, "Worker task failed to join");
+                    return Err(crate::Error::Worker(format!("Worker join error: {e}")));
+                }
+            }
+        }
+        tracing::info!(
+            worker_count = handle_count,
+            "All worker tasks finished successfully"
+        );
+        Ok(())
+    }
+
+    /// Runs the setup of this [`Queue`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if fails
+    pub async fn setup(&self) -> Result<()> {
+        match self {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(pool, _, _, _) => {
+                redis::convert_list_queues(pool).await?;
+            }
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(pool, _, _, _) => {
+                pg::initialize_database(pool).await.map_err(Box::from)?;
+            }
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(pool, _, _, _) => {
+                sqlt::initialize_database(pool).await.map_err(Box::from)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Performs clear on this [`Queue`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if fails
+    pub async fn clear(&self) -> Result<()> {
+        tracing::info!("Clearing all jobs from queue");
+        match self {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(pool, _, _, _) => {
+                redis::clear(pool).await?;
+            }
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(pool, _, _, _) => {
+                pg::clear(pool).await.map_err(Box::from)?;
+            }
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(pool, _, _, _) => {
+                sqlt::clear(pool).await.map_err(Box::from)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Store the result of a job
+    ///
+    /// The enqueuer can get it back with [`Queue::get_job_result`] until it
+    /// expires after `queue.result_ttl_sec`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if fails, or when the provider does
+    /// not support job results (only Redis does)
+    #[allow(unused_variables)]
+    pub async fn set_job_result(&self, job_id: &str, result: &serde_json::Value) -> Result<()> {
+        match self {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(pool, _, run_opts, _) => {
+                redis::set_job_result(pool, job_id, result, run_opts.result_ttl_sec).await
+            }
+            _ => Err(Error::string(
+                "job results are only supported by the Redis queue provider",
+            )),
+        }
+    }
+
+    /// Get the result stored for a job
+    ///
+    /// Returns `None` when the job did not store a result (yet) or it expired.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if fails, or when the provider does
+    /// not support job results (only Redis does)
+    #[allow(unused_variables)]
+    pub async fn get_job_result(&self, job_id: &str) -> Result<Option<serde_json::Value>> {
+        match self {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(pool, _, _, _) => redis::get_job_result(pool, job_id).await,
+            _ => Err(Error::string(
+                "job results are only supported by the Redis queue provider",
+            )),
+        }
+    }
+
+    /// Whether the job of `class` keyed `key` is in the processed set, see
+    /// [`BackgroundWorker::job_key`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if fails
+    #[allow(unused_variables)]
+    pub async fn is_processed(&self, class: &str, key: &str) -> Result<bool> {
+        match self {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(pool, _, _, _) => redis::is_processed(pool, class, key).await,
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(pool, _, _, _) => pg::is_processed(pool, class, key).await,
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(pool, _, _, _) => sqlt::is_processed(pool, class, key).await,
+            Self::None => Ok(false),
+        }
+    }
+
+    /// Adds the job of `class` keyed `key` to the processed set, where it is
+    /// remembered for `ttl`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if fails
+    #[allow(unused_variables)]
+    pub async fn mark_processed(
+        &self,
+        class: &str,
+        key: &str,
+        ttl: std::time::Duration,
+    ) -> Result<()> {
+        match self {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(pool, _, _, _) => redis::mark_processed(pool, class, key, ttl).await,
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(pool, _, _, _) => pg::mark_processed(pool, class, key, ttl).await,
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(pool, _, _, _) => sqlt::mark_processed(pool, class, key, ttl).await,
+            Self::None => Ok(()),
+        }
+    }
+
+    /// Saves a runtime schedule, see [`schedules`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if fails
+    #[allow(unused_variables)]
+    pub async fn add_schedule(&self, schedule: &schedules::Schedule) -> Result<()> {
+        match self {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(pool, _, _, _) => redis::add_schedule(pool, schedule).await,
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(pool, _, _, _) => pg::add_schedule(pool, schedule).await,
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(pool, _, _, _) => sqlt::add_schedule(pool, schedule).await,
+            Self::None => Err(Error::QueueProviderMissing),
+        }
     }
 
-    /// Process worker task handles and handle any errors
+    /// Removes the runtime schedule `id`, returning whether it existed.
     ///
     /// # Errors
-    /// This function will return an error if a worker task fails to join
-    #[allow(dead_code)]
-    async fn process_worker_handles(handles: Vec<tokio::task::JoinHandle<()>>) -> Result<()> {
-        let handle_count = handles.len();
-        tracing::debug!(worker_count = handle_count, "Processing worker handles");
-
-        for (index, handle) in handles.into_iter().enumerate() {
-            if let Err(e) = handle.await {
-                if e.is_cancelled() {
-                    tracing::debug!(
-                        worker_index = index,
-                        "Worker task cancelled during shutdown"
-                    );
-                } else if e.is_panic() {
-                    tracing::error!(worker_index = index, "Worker task panicked");
-                    std::panic::resume_unwind(e.into_panic());
-                } else {
-                    tracing::error!(worker_index = index, error = ?e, "Worker task failed to join");
-                    return Err(crate::Error::Worker(format!("Worker join error: {e}")));
-                }
-            }
+    ///
+    /// This function will return an error if fails
+    #[allow(unused_variables)]
+    pub async fn remove_schedule(&self, id: &str) -> Result<bool> {
+        match self {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(pool, _, _, _) => redis::remove_schedule(pool, id).await,
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(pool, _, _, _) => pg::remove_schedule(pool, id).await,
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(pool, _, _, _) => sqlt::remove_schedule(pool, id).await,
+            Self::None => Err(Error::QueueProviderMissing),
         }
-        tracing::info!(
-            worker_count = handle_count,
-            "All worker tasks finished successfully"
-        );
-        Ok(())
     }
 
-    /// Runs the setup of this [`Queue`].
+    /// Returns the runtime schedules.
     ///
     /// # Errors
     ///
     /// This function will return an error if fails
-    pub async fn setup(&self) -> Result<()> {
+    pub async fn get_schedules(&self) -> Result<Vec<schedules::Schedule>> {
         match self {
             #[cfg(feature = "bg_redis")]
-            Self::Redis(_, _, _, _) => {}
+            Self::Redis(pool, _, _, _) => redis::get_schedules(pool).await,
             #[cfg(feature = "bg_pg")]
-            Self::Postgres(pool, _, _, _) => {
-                pg::initialize_database(pool).await.map_err(Box::from)?;
-            }
+            Self::Postgres(pool, _, _, _) => pg::get_schedules(pool).await,
             #[cfg(feature = "bg_sqlt")]
-            Self::Sqlite(pool, _, _, _) => {
-                sqlt::initialize_database(pool).await.map_err(Box::from)?;
-            }
-            _ => {}
+            Self::Sqlite(pool, _, _, _) => sqlt::get_schedules(pool).await,
+            Self::None => Err(Error::QueueProviderMissing),
         }
-        Ok(())
     }
 
-    /// Performs clear on this [`Queue`].
+    /// Claims the job of `schedule` due at its `next_run_at`, moving the
+    /// schedule to `next_run_at`, or removing it when `None`. Returns `false`
+    /// when another process claimed it first.
+    #[allow(unused_variables)]
+    pub(crate) async fn claim_schedule(
+        &self,
+        schedule: &schedules::Schedule,
+        next_run_at: Option<DateTime<Utc>>,
+    ) -> Result<bool> {
+        match self {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(pool, _, _, _) => redis::claim_schedule(pool, schedule, next_run_at).await,
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(pool, _, _, _) => pg::claim_schedule(pool, schedule, next_run_at).await,
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(pool, _, _, _) => sqlt::claim_schedule(pool, schedule, next_run_at).await,
+            Self::None => Err(Error::QueueProviderMissing),
+        }
+    }
+
+    /// Returns a ping of this [`Queue`].
     ///
     /// # Errors
     ///
     /// This function will return an error if fails
-    pub async fn clear(&self) -> Result<()> {
-        tracing::info!("Clearing all jobs from queue");
+    pub async fn ping(&self) -> Result<()> {
+        tracing::trace!("Pinging job queue");
         match self {
             #[cfg(feature = "bg_redis")]
             Self::Redis(pool, _, _, _) => {
-                redis::clear(pool).await?;
+                redis::ping(pool).await?;
             }
             #[cfg(feature = "bg_pg")]
             Self::Postgres(pool, _, _, _) => {
-                pg::clear(pool).await.map_err(Box::from)?;
+                pg::ping(pool).await.map_err(Box::from)?;
             }
             #[cfg(feature = "bg_sqlt")]
             Self::Sqlite(pool, _, _, _) => {
-                sqlt::clear(pool).await.map_err(Box::from)?;
+                sqlt::ping(pool).await.map_err(Box::from)?;
             }
             _ => {}
         }
         Ok(())
     }
 
-    /// Returns a ping of this [`Queue`].
+    /// Returns the administration of the jobs of the queue, see
+    /// [`admin::QueueAdmin`].
+    #[must_use]
+    pub const fn admin(&self) -> admin::QueueAdmin<'_> {
+        admin::QueueAdmin::new(self)
+    }
+
+    /// Returns the jobs of the queue by status, and its pool connections.
     ///
     /// # Errors
     ///
-    /// This function will return an error if fails
-    pub async fn ping(&self) -> Result<()> {
-        tracing::trace!("Pinging job queue");
+    /// This function will return an error if the jobs cannot be read
+    pub async fn stats(&self) -> Result<Option<crate::stats::QueueStats>> {
+        #[cfg(any(feature = "bg_pg", feature = "bg_sqlt"))]
+        use crate::stats::PoolStats;
+        #[cfg(any(feature = "bg_redis", feature = "bg_pg", feature = "bg_sqlt"))]
+        use crate::stats::QueueStats;
+
         match self {
             #[cfg(feature = "bg_redis")]
             Self::Redis(pool, _, _, _) => {
-                redis::ping(pool).await?;
+                let jobs = redis::get_jobs(pool, None, None).await?;
+                Ok(Some(QueueStats::count(
+                    None,
+                    jobs.iter().map(|job| &job.status),
+                )))
             }
             #[cfg(feature = "bg_pg")]
             Self::Postgres(pool, _, _, _) => {
-                pg::ping(pool).await.map_err(Box::from)?;
+                let jobs = pg::get_jobs(pool, None, None).await.map_err(Box::from)?;
+                let connections = PoolStats::from_sqlx(pool.size(), pool.num_idle());
+                Ok(Some(QueueStats::count(
+                    Some(connections),
+                    jobs.iter().map(|job| &job.status),
+                )))
             }
             #[cfg(feature = "bg_sqlt")]
             Self::Sqlite(pool, _, _, _) => {
-                sqlt::ping(pool).await.map_err(Box::from)?;
+                let jobs = sqlt::get_jobs(pool, None, None).await?;
+                let connections = PoolStats::from_sqlx(pool.size(), pool.num_idle());
+                Ok(Some(QueueStats::count(
+                    Some(connections),
+                    jobs.iter().map(|job| &job.status),
+                )))
             }
-            _ => {}
+            _ => Ok(None),
         }
-        Ok(())
     }
 
     #[must_use]
@@ -352,6 +1144,18 @@ impl Queue {
         Ok(())
     }
 
+    /// Closes the connections of the queue provider, once its workers are
+    /// stopped.
+    pub async fn close(&self) {
+        match self {
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(pool, _, _, _) => pool.close().await,
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(pool, _, _, _) => pool.close().await,
+            _ => {}
+        }
+    }
+
     async fn get_jobs(
         &self,
         status: Option<&Vec<JobStatus>>,
@@ -587,6 +1391,97 @@ impl Queue {
     }
 }
 
+tokio::task_local! {
+    static CURRENT_JOB_ID: String;
+}
+
+/// Returns the id of the job being performed by a queue worker.
+///
+/// Returns `None` outside of a job, or when the job was performed directly
+/// (`ForegroundBlocking` and `BackgroundAsync` modes).
+#[must_use]
+pub fn current_job_id() -> Option<String> {
+    CURRENT_JOB_ID.try_with(Clone::clone).ok()
+}
+
+#[cfg(any(feature = "bg_redis", feature = "bg_pg", feature = "bg_sqlt"))]
+async fn with_job_id<F: std::future::Future>(job_id: String, future: F) -> F::Output {
+    CURRENT_JOB_ID.scope(job_id, future).await
+}
+
+/// Performs a job within the worker's [`BackgroundWorker::timeout`]. When the
+/// timeout expires, the job future is dropped and the job fails.
+#[cfg(any(feature = "bg_redis", feature = "bg_pg", feature = "bg_sqlt"))]
+async fn with_timeout<F>(timeout: Option<std::time::Duration>, perform: F) -> Result<()>
+where
+    F: std::future::Future<Output = Result<()>>,
+{
+    let Some(timeout) = timeout else {
+        return perform.await;
+    };
+    tokio::time::timeout(timeout, perform)
+        .await
+        .unwrap_or_else(|_| {
+            tracing::error!(timeout_ms = timeout.as_millis(), "job timed out");
+            Err(Error::Message(format!(
+                "job timed out after {}ms",
+                timeout.as_millis()
+            )))
+        })
+}
+
+/// Performs a job keyed `key` unless the key is in the processed set of
+/// `class`, and adds it once the job succeeded. A job without a key is
+/// performed as is.
+///
+/// The job fails when the processed set cannot be checked, rather than risk
+/// performing it twice. It does not fail when the key cannot be added after
+/// it succeeded, as a retry would perform it again.
+#[cfg(any(feature = "bg_redis", feature = "bg_pg", feature = "bg_sqlt"))]
+async fn perform_once<F>(
+    queue: &chain::QueueRef,
+    class: &str,
+    key: Option<String>,
+    ttl: std::time::Duration,
+    perform: F,
+) -> Result<()>
+where
+    F: std::future::Future<Output = Result<()>>,
+{
+    let (Some(key), Some(queue)) = (key, queue.get()) else {
+        return perform.await;
+    };
+    if queue.is_processed(class, &key).await? {
+        tracing::info!(job = class, key, "job already processed, skipping");
+        return Ok(());
+    }
+    perform.await?;
+    if let Err(err) = queue.mark_processed(class, &key, ttl).await {
+        tracing::error!(
+            job = class,
+            key,
+            err = err.to_string(),
+            "job processed, but could not be added to the processed set"
+        );
+    }
+    Ok(())
+}
+
+/// Returns the job arguments, without the trace context propagated with them,
+/// and the span to perform the job in.
+#[cfg(any(feature = "bg_redis", feature = "bg_pg", feature = "bg_sqlt"))]
+fn job_span(job_id: &str, data: serde_json::Value) -> (serde_json::Value, tracing::Span) {
+    #[cfg(feature = "otel")]
+    {
+        crate::otel::job_span(job_id, data)
+    }
+    #[cfg(not(feature = "otel"))]
+    {
+        let _ = job_id;
+        (data, tracing::Span::none())
+    }
+}
+
 #[async_trait]
 pub trait BackgroundWorker<A: Send + Sync + serde::Serialize + 'static>: Send + Sync {
     /// If you have a specific queue
@@ -604,7 +1499,69 @@ pub trait BackgroundWorker<A: Send + Sync + serde::Serialize + 'static>: Send +
         Vec::new()
     }
 
+    /// The maximum time a queue worker spends performing a job. When it
+    /// expires, the job is cancelled and marked as failed, freeing the worker
+    /// slot. Returns `None` (no timeout) by default.
+    ///
+    /// Cancelling drops the `perform` future at its current `.await` point:
+    /// keep side effects that must not be interrupted halfway (such as a
+    /// multi-step write) inside a transaction, or make the job safe to run
+    /// again.
+    #[must_use]
+    fn timeout() -> Option<std::time::Duration> {
+        None
+    }
+
+    /// How long the uniqueness key of a job enqueued with
+    /// [`BackgroundWorker::perform_bulk_unique`] is held at most, when the
+    /// job is not dequeued before. One day by default.
+    #[must_use]
+    fn unique_for() -> std::time::Duration {
+        std::time::Duration::from_secs(24 * 60 * 60)
+    }
+
+    /// The key of a job which must not be performed twice, such as the id of
+    /// the payment it charges. Returns `None` (no key) by default.
+    ///
+    /// Queues deliver jobs at least once: a job whose worker crashed is
+    /// delivered again. Once a keyed job succeeded, its key is added to the
+    /// processed set of the worker, and a job delivered again with the same
+    /// key is skipped. This is best-effort exactly-once: a job which crashes
+    /// after its side effects, but before its key is added, runs again.
+    #[must_use]
+    fn job_key(_args: &A) -> Option<String> {
+        None
+    }
+
+    /// How long the key of a processed job is remembered, see
+    /// [`BackgroundWorker::job_key`]. Seven days by default.
+    #[must_use]
+    fn processed_for() -> std::time::Duration {
+        std::time::Duration::from_secs(7 * 24 * 60 * 60)
+    }
+
+    /// The codec the jobs of this worker are encoded with, on queue providers
+    /// supporting several. Returns `None` to use the `queue.codec` setting.
+    #[must_use]
+    fn codec() -> Option<Codec> {
+        None
+    }
+
     fn build(ctx: &AppContext) -> Self;
+
+    /// Creates the worker, or fails when something it needs is missing, such
+    /// as a storage or a setting. [`Queue::try_register`] builds workers with
+    /// it, and [`BackgroundWorker::build`] is called by default.
+    ///
+    /// # Errors
+    ///
+    /// When the worker cannot be created
+    fn try_build(ctx: &AppContext) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self::build(ctx))
+    }
     #[must_use]
     fn class_name() -> String
     where
@@ -616,6 +1573,20 @@ pub trait BackgroundWorker<A: Send + Sync + serde::Serialize + 'static>: Send +
         name.to_upper_camel_case()
     }
     async fn perform_later(ctx: &AppContext, args: A) -> crate::Result<()>
+    where
+        Self: Sized,
+    {
+        Self::perform_later_with_priority(ctx, args, 0).await
+    }
+
+    /// Like [`BackgroundWorker::perform_later`], with a `priority`: on Redis,
+    /// jobs with a higher priority jump ahead of the others in the worker
+    /// queue.
+    async fn perform_later_with_priority(
+        ctx: &AppContext,
+        args: A,
+        priority: i32,
+    ) -> crate::Result<()>
     where
         Self: Sized,
     {
@@ -624,8 +1595,14 @@ pub trait BackgroundWorker<A: Send + Sync + serde::Serialize + 'static>: Send +
                 if let Some(p) = &ctx.queue_provider {
                     let tags = Self::tags();
                     let tags_option = if tags.is_empty() { None } else { Some(tags) };
-                    p.enqueue(Self::class_name(), Self::queue(), args, tags_option)
-                        .await?;
+                    p.enqueue_with_priority(
+                        Self::class_name(),
+                        Self::queue(),
+                        args,
+                        tags_option,
+                        priority,
+                    )
+                    .await?;
                 } else {
                     tracing::error!(
                         "perform_later: background queue is selected, but queue was not populated \
@@ -634,20 +1611,134 @@ pub trait BackgroundWorker<A: Send + Sync + serde::Serialize + 'static>: Send +
                 }
             }
             WorkerMode::ForegroundBlocking => {
-                Self::build(ctx).perform(args).await?;
+                chain::perform_directly(ctx, Self::build(ctx).perform(args)).await?;
             }
             WorkerMode::BackgroundAsync => {
                 let dx = ctx.clone();
-                tokio::spawn(async move {
-                    if let Err(err) = Self::build(&dx).perform(args).await {
+                let context = crate::request_context::RequestContext::current();
+                tokio::spawn(crate::request_context::propagate(context, async move {
+                    if let Err(err) =
+                        chain::perform_directly(&dx, Self::build(&dx).perform(args)).await
+                    {
                         tracing::error!(err = err.to_string(), "worker failed to perform job");
                     }
+                }));
+            }
+        }
+        Ok(())
+    }
+
+    /// Fans out one job per `(key, args)`, skipping the keys that already
+    /// have a job waiting in the queue, as with [`Queue::enqueue_bulk_unique`].
+    /// Returns the keys of the enqueued and of the skipped jobs.
+    ///
+    /// With the in-process modes, there is no queue to wait in: only the keys
+    /// repeated in `jobs` are skipped, and the others performed as with
+    /// [`BackgroundWorker::perform_later`].
+    ///
+    /// # Errors
+    ///
+    /// When the jobs could not be enqueued, the queue provider is not Redis,
+    /// or performing a job failed with the `ForegroundBlocking` mode.
+    async fn perform_bulk_unique(
+        ctx: &AppContext,
+        jobs: Vec<(String, A)>,
+    ) -> crate::Result<UniqueBatch>
+    where
+        Self: Sized,
+    {
+        if ctx.config.workers.mode == WorkerMode::BackgroundQueue {
+            let Some(p) = &ctx.queue_provider else {
+                tracing::error!(
+                    "perform_bulk_unique: background queue is selected, but queue was not \
+                     populated in context"
+                );
+                return Ok(UniqueBatch::default());
+            };
+            let tags = Self::tags();
+            let tags_option = if tags.is_empty() { None } else { Some(tags) };
+            return p
+                .enqueue_bulk_unique(
+                    Self::class_name(),
+                    Self::queue(),
+                    jobs,
+                    tags_option,
+                    Self::unique_for(),
+                )
+                .await;
+        }
+
+        let mut batch = UniqueBatch::default();
+        for (key, args) in jobs {
+            if batch.enqueued.contains(&key) {
+                batch.skipped.push(key);
+                continue;
+            }
+            Self::perform_later(ctx, args).await?;
+            batch.enqueued.push(key);
+        }
+        Ok(batch)
+    }
+
+    /// Performs the job at `at` rather than now, a job already due being
+    /// performed as with [`BackgroundWorker::perform_later`].
+    ///
+    /// With the `BackgroundQueue` mode, the job is enqueued to run at `at`,
+    /// which the Postgres and Sqlite providers support. With the in-process
+    /// modes, the job is performed by a task sleeping until `at` on the clock
+    /// of the app, or held until a test travels past it with
+    /// `testing::time::TestContext::travel_to`.
+    ///
+    /// # Errors
+    ///
+    /// When the job could not be enqueued, or, when already due, performing
+    /// it failed with the `ForegroundBlocking` mode.
+    async fn perform_at(ctx: &AppContext, args: A, at: DateTime<Utc>) -> crate::Result<()>
+    where
+        Self: Sized + 'static,
+    {
+        if ctx.config.workers.mode != WorkerMode::BackgroundQueue && at <= ctx.now() {
+            return Self::perform_later(ctx, args).await;
+        }
+        match &ctx.config.workers.mode {
+            WorkerMode::BackgroundQueue => {
+                if let Some(p) = &ctx.queue_provider {
+                    let tags = Self::tags();
+                    let tags_option = if tags.is_empty() { None } else { Some(tags) };
+                    p.enqueue_at(Self::class_name(), Self::queue(), args, tags_option, at)
+                        .await?;
+                } else {
+                    tracing::error!(
+                        "perform_at: background queue is selected, but queue was not populated \
+                         in context"
+                    );
+                }
+            }
+            WorkerMode::ForegroundBlocking | WorkerMode::BackgroundAsync => {
+                let context = crate::request_context::RequestContext::current();
+                let job = delayed::DelayedJob::new(at, Self::class_name(), move |ctx| {
+                    Box::pin(crate::request_context::propagate(context, async move {
+                        chain::perform_directly(&ctx, Self::build(&ctx).perform(args)).await
+                    }))
                 });
+                delayed::perform_at(ctx, job);
             }
         }
         Ok(())
     }
 
+    /// Performs the job after `delay`, see [`BackgroundWorker::perform_at`].
+    ///
+    /// # Errors
+    ///
+    /// As [`BackgroundWorker::perform_at`]
+    async fn perform_in(ctx: &AppContext, args: A, delay: chrono::Duration) -> crate::Result<()>
+    where
+        Self: Sized + 'static,
+    {
+        Self::perform_at(ctx, args, ctx.now() + delay).await
+    }
+
     async fn perform(&self, args: A) -> crate::Result<()>;
 }
 
@@ -669,6 +1760,7 @@ pub async fn converge(queue: &Queue, config: &QueueConfig) -> Result<()> {
             poll_interval_sec: _,
             num_workers: _,
             min_connections: _,
+            limit: _,
         })
         | QueueConfig::Sqlite(SqliteQueueConfig {
             dangerously_flush,
@@ -680,12 +1772,18 @@ pub async fn converge(queue: &Queue, config: &QueueConfig) -> Result<()> {
             poll_interval_sec: _,
             num_workers: _,
             min_connections: _,
+            limit: _,
         })
         | QueueConfig::Redis(RedisQueueConfig {
             dangerously_flush,
             uri: _,
             queues: _,
             num_workers: _,
+            batch_chunk_size: _,
+            result_ttl_sec: _,
+            codec: _,
+            limits: _,
+            key_prefix: _,
         }) => {
             if *dangerously_flush {
                 tracing::warn!("Flush mode enabled - clearing all jobs from queue");
@@ -709,17 +1807,23 @@ pub async fn create_queue_provider(config: &Config) -> Result<Option<Arc<Queue>>
                 #[cfg(feature = "bg_redis")]
                 config::QueueConfig::Redis(qcfg) => {
                     tracing::debug!("Creating Redis queue provider");
-                    Ok(Some(Arc::new(redis::create_provider(qcfg).await?)))
+                    let mut queue = redis::create_provider(qcfg).await?;
+                    if let Queue::Redis(_, _, run_opts, _) = &mut queue {
+                        run_opts.processed_queues.clone_from(&config.workers.queues);
+                    }
+                    Ok(Some(queue.share().await))
                 }
                 #[cfg(feature = "bg_pg")]
                 config::QueueConfig::Postgres(qcfg) => {
                     tracing::debug!("Creating Postgres queue provider");
-                    Ok(Some(Arc::new(pg::create_provider(qcfg).await?)))
+                    warn_unsupported_queue_selection(config);
+                    Ok(Some(pg::create_provider(qcfg).await?.share().await))
                 }
                 #[cfg(feature = "bg_sqlt")]
                 config::QueueConfig::Sqlite(qcfg) => {
                     tracing::debug!("Creating SQLite queue provider");
-                    Ok(Some(Arc::new(sqlt::create_provider(qcfg).await?)))
+                    warn_unsupported_queue_selection(config);
+                    Ok(Some(sqlt::create_provider(qcfg).await?.share().await))
                 }
 
                 #[allow(unreachable_patterns)]
@@ -738,6 +1842,18 @@ pub async fn create_queue_provider(config: &Config) -> Result<Option<Arc<Queue>>
     }
 }
 
+/// The Postgres and `SQLite` providers have a single queue, so all jobs are
+/// processed regardless of `workers.queues`.
+#[cfg(any(feature = "bg_pg", feature = "bg_sqlt"))]
+fn warn_unsupported_queue_selection(config: &Config) {
+    if config.workers.queues.is_some() {
+        tracing::warn!(
+            "`workers.queues` is only supported by the Redis queue provider, all jobs will be \
+             processed"
+        );
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -762,9 +1878,18 @@ mod tests {
             idle_timeout: 500,
             poll_interval_sec: 1,
             num_workers: 1,
+            limit: None,
         }
     }
 
+    #[tokio::test]
+    async fn can_scope_current_job_id() {
+        assert_eq!(current_job_id(), None);
+        let job_id = with_job_id("job-1".to_string(), async { current_job_id() }).await;
+        assert_eq!(job_id, Some("job-1".to_string()));
+        assert_eq!(current_job_id(), None);
+    }
+
     #[tokio::test]
     async fn can_dump_jobs() {
         let tree_fs = tree_fs::TreeBuilder::default()
@@ -795,6 +1920,36 @@ mod tests {
         assert_debug_snapshot!(std::fs::read_to_string(dump_file).unwrap());
     }
 
+    #[tokio::test]
+    async fn can_only_purge_all_sqlite_queues() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .create()
+            .expect("create temp folder");
+        let qcfg = sqlite_config(tree_fs.root.as_path());
+        let queue = sqlt::create_provider(&qcfg)
+            .await
+            .expect("create sqlite queue");
+
+        let pool = sqlx::SqlitePool::connect(&qcfg.uri)
+            .await
+            .expect("connect to sqlite db");
+
+        queue.setup().await.expect("setup sqlite db");
+        tests_cfg::queue::sqlite_seed_data(&pool).await;
+
+        let admin = queue.admin();
+        let pending = admin.list_pending().await.unwrap().len();
+        let scheduled = admin.list_scheduled().await.unwrap().len();
+        // the jobs of all the workers share one queue, a named one is unknown
+        assert!(admin.purge("mailer").await.is_err());
+        assert_eq!(admin.list_pending().await.unwrap().len(), pending);
+
+        assert_eq!(admin.purge_all().await.unwrap(), pending + scheduled);
+        assert!(admin.list_pending().await.unwrap().is_empty());
+        assert!(admin.list_scheduled().await.unwrap().is_empty());
+    }
+
     #[tokio::test]
     async fn cat_import_jobs_form_file() {
         let tree_fs = tree_fs::TreeBuilder::default()
@@ -837,4 +1992,78 @@ mod tests {
 
         assert_eq!(count, 14);
     }
+
+    #[tokio::test]
+    async fn can_limit_queue_depth() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .create()
+            .expect("create temp folder");
+        let mut qcfg = sqlite_config(tree_fs.root.as_path());
+        qcfg.limit = Some(config::QueueLimit {
+            max_depth: 2,
+            when_full: config::WhenFull::Reject,
+            block_timeout_ms: 0,
+        });
+        let queue = sqlt::create_provider(&qcfg)
+            .await
+            .expect("create sqlite queue");
+        queue.setup().await.expect("setup sqlite db");
+
+        let enqueue = || queue.enqueue("Report".to_string(), None, serde_json::json!({}), None);
+        enqueue().await.unwrap();
+        assert_eq!(queue.depth(None).await.unwrap(), Some(1));
+        assert!(matches!(
+            queue
+                .enqueue_batch("Report".to_string(), None, vec![1, 2], None)
+                .await,
+            Err(Error::QueueFull {
+                depth: 1,
+                max_depth: 2,
+                ..
+            })
+        ));
+        enqueue().await.unwrap();
+        let err = enqueue().await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "queue `default` is full: 2 jobs are waiting, and its max depth is 2"
+        );
+        assert_eq!(queue.depth(None).await.unwrap(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn can_block_on_full_queue() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .create()
+            .expect("create temp folder");
+        let mut qcfg = sqlite_config(tree_fs.root.as_path());
+        qcfg.limit = Some(config::QueueLimit {
+            max_depth: 1,
+            when_full: config::WhenFull::Block,
+            block_timeout_ms: 5_000,
+        });
+        let queue = Arc::new(
+            sqlt::create_provider(&qcfg)
+                .await
+                .expect("create sqlite queue"),
+        );
+        queue.setup().await.expect("setup sqlite db");
+        queue
+            .enqueue("Report".to_string(), None, 1, None)
+            .await
+            .unwrap();
+
+        let blocked = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.enqueue("Report".to_string(), None, 2, None).await }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        assert!(!blocked.is_finished());
+
+        queue.clear().await.unwrap();
+        blocked.await.unwrap().unwrap();
+        assert_eq!(queue.depth(None).await.unwrap(), Some(1));
+    }
 }
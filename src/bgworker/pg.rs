@@ -1,13 +1,23 @@
 /// Postgres based background job queue provider
 use std::{
-    collections::HashMap, future::Future, panic::AssertUnwindSafe, pin::Pin, sync::Arc,
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    pin::Pin,
+    sync::Arc,
     time::Duration,
 };
 
-use super::{BackgroundWorker, JobStatus, Queue};
-use crate::{config::PostgresQueueConfig, Error, Result};
+use super::{
+    chain::{self, QueueRef},
+    middleware::{JobInfo, JobMiddleware, JobMiddlewares},
+    schedules::Schedule,
+    BackgroundWorker, DrainReport, JobStatus, Queue,
+};
+use crate::{
+    config::{PostgresQueueConfig, QueueLimit},
+    Error, Result,
+};
 use chrono::{DateTime, Utc};
-use futures_util::FutureExt;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 pub use sqlx::PgPool;
@@ -18,8 +28,7 @@ use sqlx::{
 use std::fmt::Write;
 use tokio::{task::JoinHandle, time::sleep};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, trace};
-use ulid::Ulid;
+use tracing::{debug, error, trace, Instrument};
 type JobId = String;
 type JobData = JsonValue;
 
@@ -48,6 +57,9 @@ pub struct Job {
 
 pub struct JobRegistry {
     handlers: Arc<HashMap<String, JobHandler>>,
+    middlewares: JobMiddlewares,
+    queue: QueueRef,
+    failures: BTreeMap<String, String>,
 }
 
 impl JobRegistry {
@@ -56,6 +68,9 @@ impl JobRegistry {
     pub fn new() -> Self {
         Self {
             handlers: Arc::new(HashMap::new()),
+            middlewares: JobMiddlewares::default(),
+            queue: QueueRef::default(),
+            failures: BTreeMap::new(),
         }
     }
 
@@ -69,26 +84,43 @@ impl JobRegistry {
         for<'de> Args: Deserialize<'de>,
     {
         let worker = Arc::new(worker);
-        let wrapped_handler = move |_job_id: String, job_data: JobData| {
+        let middlewares = self.middlewares.clone();
+        let queue = self.queue.clone();
+        let handler_name = name.clone();
+        let wrapped_handler = move |job_id: String, job_data: JobData| {
             let w = worker.clone();
 
+            let middlewares = middlewares.clone();
+            let queue = queue.clone();
+            let job = JobInfo {
+                id: job_id.clone(),
+                name: handler_name.clone(),
+            };
+
             Box::pin(async move {
-                let args = serde_json::from_value::<Args>(job_data);
+                let (job_data, span) = super::job_span(&job_id, job_data);
+                let (job_data, context) = crate::request_context::take_job_context(job_data);
+                let (job_data, chain) = chain::take_chain(job_data);
+                let args = serde_json::from_value::<Args>(job_data.clone());
                 match args {
                     Ok(args) => {
-                        // Wrap the perform call in catch_unwind to handle panics
-                        match AssertUnwindSafe(w.perform(args)).catch_unwind().await {
-                            Ok(result) => result,
-                            Err(panic) => {
-                                let panic_msg = panic
-                                    .downcast_ref::<String>()
-                                    .map(String::as_str)
-                                    .or_else(|| panic.downcast_ref::<&str>().copied())
-                                    .unwrap_or("Unknown panic occurred");
-                                error!(err = panic_msg, "worker panicked");
-                                Err(Error::string(panic_msg))
-                            }
-                        }
+                        let key = W::job_key(&args);
+                        let class = job.name.clone();
+                        // Run the middlewares around the perform call, turning panics into failures
+                        let perform =
+                            async move { super::with_timeout(W::timeout(), w.perform(args)).await };
+                        let perform = super::perform_once(
+                            &queue,
+                            &class,
+                            key,
+                            W::processed_for(),
+                            middlewares.run(job, perform),
+                        );
+                        let perform = chain::perform_queued(&queue, chain, job_data, perform);
+                        let perform = super::with_job_id(job_id, perform);
+                        crate::request_context::propagate(context, perform)
+                            .instrument(span)
+                            .await
                     }
                     Err(err) => Err(err.into()),
                 }
@@ -101,6 +133,32 @@ impl JobRegistry {
         Ok(())
     }
 
+    /// Records a worker which failed to register, with its error.
+    pub fn add_failure(&mut self, name: String, error: String) {
+        self.failures.insert(name, error);
+    }
+
+    /// Returns the workers which failed to register, with their error.
+    #[must_use]
+    pub const fn failures(&self) -> &BTreeMap<String, String> {
+        &self.failures
+    }
+
+    /// Adds a middleware around the jobs of all the workers.
+    ///
+    /// # Errors
+    ///
+    /// Fails if cannot add the middleware
+    pub fn add_middleware(&mut self, middleware: Arc<dyn JobMiddleware>) -> Result<()> {
+        self.middlewares.add(middleware)
+    }
+
+    /// The queue the follow-up jobs and the next steps of chains are enqueued
+    /// to.
+    pub(crate) fn queue_ref(&self) -> QueueRef {
+        self.queue.clone()
+    }
+
     /// Returns a reference to the job handlers.
     #[must_use]
     pub fn handlers(&self) -> &Arc<HashMap<String, JobHandler>> {
@@ -146,39 +204,7 @@ impl JobRegistry {
                     };
 
                     if let Some(job) = job_opt {
-                        debug!(job_id = %job.id, job_name = %job.name, "Processing job");
-                        if let Some(handler) = handlers.get(&job.name) {
-                            match handler(job.id.clone(), job.data.clone()).await {
-                                Ok(()) => {
-                                    if let Err(err) =
-                                        complete_job(&pool, &job.id, job.interval).await
-                                    {
-                                        error!(
-                                            error = %err,
-                                            job_id = %job.id,
-                                            job_name = %job.name,
-                                            "Failed to mark job as completed"
-                                        );
-                                    } else {
-                                        debug!(job_id = %job.id, "Job completed successfully");
-                                    }
-                                }
-                                Err(err) => {
-                                    if let Err(fail_err) = fail_job(&pool, &job.id, &err).await {
-                                        error!(
-                                            error = %fail_err,
-                                            job_id = %job.id,
-                                            job_name = %job.name,
-                                            "Failed to mark job as failed"
-                                        );
-                                    } else {
-                                        debug!(job_id = %job.id, error = %err, "Job execution failed");
-                                    }
-                                }
-                            }
-                        } else {
-                            error!(job_name = %job.name, "No handler registered for job");
-                        }
+                        process_job(&pool, &handlers, &job).await;
                     } else {
                         // Use tokio::select! to wait for interval or cancellation
                         tokio::select! {
@@ -208,6 +234,66 @@ impl Default for JobRegistry {
     }
 }
 
+/// Runs a dequeued job with its handler, and records its outcome: returns
+/// the status of the job, or `None` when no handler is registered for it.
+async fn process_job(
+    pool: &PgPool,
+    handlers: &HashMap<String, JobHandler>,
+    job: &Job,
+) -> Option<JobStatus> {
+    debug!(job_id = %job.id, job_name = %job.name, "Processing job");
+    let Some(handler) = handlers.get(&job.name) else {
+        error!(job_name = %job.name, "No handler registered for job");
+        return None;
+    };
+    match handler(job.id.clone(), job.data.clone()).await {
+        Ok(()) => {
+            if let Err(err) = complete_job(pool, &job.id, job.interval).await {
+                error!(
+                    error = %err,
+                    job_id = %job.id,
+                    job_name = %job.name,
+                    "Failed to mark job as completed"
+                );
+            } else {
+                debug!(job_id = %job.id, "Job completed successfully");
+            }
+            Some(JobStatus::Completed)
+        }
+        Err(err) => {
+            if let Err(fail_err) = fail_job(pool, &job.id, &err).await {
+                error!(
+                    error = %fail_err,
+                    job_id = %job.id,
+                    job_name = %job.name,
+                    "Failed to mark job as failed"
+                );
+            } else {
+                debug!(job_id = %job.id, error = %err, "Job execution failed");
+            }
+            Some(JobStatus::Failed)
+        }
+    }
+}
+
+/// Processes the jobs due now matching `tags`, including the jobs they
+/// enqueue, until there are none left. See [`Queue::drain`].
+///
+/// # Errors
+///
+/// When the jobs could not be dequeued
+pub async fn drain(
+    pool: &PgPool,
+    handlers: &HashMap<String, JobHandler>,
+    tags: &[String],
+    report: &mut DrainReport,
+) -> Result<()> {
+    while let Some(job) = dequeue(pool, tags).await? {
+        report.count(process_job(pool, handlers, &job).await);
+    }
+    Ok(())
+}
+
 async fn connect(cfg: &PostgresQueueConfig) -> Result<PgPool> {
     let mut conn_opts: PgConnectOptions = cfg.uri.parse()?;
     if !cfg.enable_logging {
@@ -243,6 +329,20 @@ pub async fn initialize_database(pool: &PgPool) -> Result<()> {
                 updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
                 tags JSONB
             );
+
+            CREATE TABLE IF NOT EXISTS pg_loco_schedules (
+                id VARCHAR NOT NULL PRIMARY KEY,
+                schedule JSONB NOT NULL,
+                next_run_at BIGINT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS pg_loco_processed (
+                name VARCHAR NOT NULL,
+                key VARCHAR NOT NULL,
+                processed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                expires_at TIMESTAMPTZ NOT NULL,
+                PRIMARY KEY (name, key)
+            );
             ",
         JobStatus::Queued
     ))
@@ -272,7 +372,7 @@ pub async fn enqueue(
     #[allow(clippy::cast_possible_truncation)]
     let interval_ms: Option<i64> = interval.map(|i| i.as_millis() as i64);
 
-    let id = Ulid::new().to_string();
+    let id = crate::id::generate();
     debug!(job_id = %id, job_name = %name, run_at = %run_at, tags = ?tags, "Enqueueing job");
     sqlx::query(
         "INSERT INTO pg_loco_queue (id, task_data, name, run_at, interval, tags) VALUES ($1, $2, $3, \
@@ -421,6 +521,128 @@ pub async fn cancel_jobs_by_name(pool: &PgPool, name: &str) -> Result<()> {
     Ok(())
 }
 
+/// Whether the job of `name` keyed `key` is in the processed set
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn is_processed(pool: &PgPool, name: &str, key: &str) -> Result<bool> {
+    let row = sqlx::query(
+        "SELECT EXISTS (SELECT 1 FROM pg_loco_processed WHERE name = $1 AND key = $2 AND \
+         expires_at > NOW())",
+    )
+    .bind(name)
+    .bind(key)
+    .fetch_one(pool)
+    .await?;
+    Ok(row.try_get(0)?)
+}
+
+/// Add the job of `name` keyed `key` to the processed set, for `ttl`, and
+/// drop the expired keys
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn mark_processed(pool: &PgPool, name: &str, key: &str, ttl: Duration) -> Result<()> {
+    let expires_at = chrono::Duration::from_std(ttl)
+        .ok()
+        .and_then(|ttl| Utc::now().checked_add_signed(ttl))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC);
+    sqlx::query("DELETE FROM pg_loco_processed WHERE expires_at <= NOW()")
+        .execute(pool)
+        .await?;
+    sqlx::query(
+        "INSERT INTO pg_loco_processed (name, key, expires_at) VALUES ($1, $2, $3) ON CONFLICT \
+         (name, key) DO UPDATE SET processed_at = NOW(), expires_at = EXCLUDED.expires_at",
+    )
+    .bind(name)
+    .bind(key)
+    .bind(expires_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Save a runtime schedule
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn add_schedule(pool: &PgPool, schedule: &Schedule) -> Result<()> {
+    sqlx::query("INSERT INTO pg_loco_schedules (id, schedule, next_run_at) VALUES ($1, $2, $3)")
+        .bind(&schedule.id)
+        .bind(serde_json::to_value(schedule)?)
+        .bind(schedule.next_run_at.timestamp_millis())
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Remove a runtime schedule, returning whether it existed
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn remove_schedule(pool: &PgPool, id: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM pg_loco_schedules WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Get the runtime schedules
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn get_schedules(pool: &PgPool) -> Result<Vec<Schedule>> {
+    let rows = sqlx::query("SELECT schedule, next_run_at FROM pg_loco_schedules")
+        .fetch_all(pool)
+        .await?;
+    rows.iter()
+        .map(|row| {
+            let mut schedule: Schedule = serde_json::from_value(row.try_get("schedule")?)?;
+            let next_run_at: i64 = row.try_get("next_run_at")?;
+            schedule.next_run_at = DateTime::from_timestamp_millis(next_run_at)
+                .ok_or_else(|| Error::string("invalid schedule time"))?;
+            Ok(schedule)
+        })
+        .collect()
+}
+
+/// Claim the job of a schedule due at its `next_run_at`, moving the schedule
+/// to `next_run_at`, or removing it when `None`
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn claim_schedule(
+    pool: &PgPool,
+    schedule: &Schedule,
+    next_run_at: Option<DateTime<Utc>>,
+) -> Result<bool> {
+    let due = schedule.next_run_at.timestamp_millis();
+    let result = if let Some(next_run_at) = next_run_at {
+        sqlx::query(
+            "UPDATE pg_loco_schedules SET next_run_at = $1 WHERE id = $2 AND next_run_at = $3",
+        )
+        .bind(next_run_at.timestamp_millis())
+        .bind(&schedule.id)
+        .bind(due)
+        .execute(pool)
+        .await?
+    } else {
+        sqlx::query("DELETE FROM pg_loco_schedules WHERE id = $1 AND next_run_at = $2")
+            .bind(&schedule.id)
+            .bind(due)
+            .execute(pool)
+            .await?
+    };
+    Ok(result.rows_affected() > 0)
+}
+
 /// Clear all jobs
 ///
 /// # Errors
@@ -433,6 +655,58 @@ pub async fn clear(pool: &PgPool) -> Result<()> {
     Ok(())
 }
 
+/// Queues the failed job `id` again, without its error, returning whether
+/// there was such a failed job.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn retry_job(pool: &PgPool, id: &str) -> Result<bool> {
+    debug!(job_id = id, "Retrying failed job");
+    let result = sqlx::query(
+        "UPDATE pg_loco_queue SET status = $1, run_at = $2, updated_at = $2, task_data = \
+         task_data - 'error' WHERE id = $3 AND status = $4",
+    )
+    .bind(JobStatus::Queued.to_string())
+    .bind(Utc::now())
+    .bind(id)
+    .bind(JobStatus::Failed.to_string())
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Deletes the job `id`, unless it is being processed, returning whether
+/// there was such a job.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn delete_job(pool: &PgPool, id: &str) -> Result<bool> {
+    debug!(job_id = id, "Deleting job");
+    let result = sqlx::query("DELETE FROM pg_loco_queue WHERE id = $1 AND status != $2")
+        .bind(id)
+        .bind(JobStatus::Processing.to_string())
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Deletes the queued jobs of all the workers, which share a single queue,
+/// returning how many there were.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn purge_all(pool: &PgPool) -> Result<usize> {
+    debug!("Purging queued jobs");
+    let result = sqlx::query("DELETE FROM pg_loco_queue WHERE status = $1")
+        .bind(JobStatus::Queued.to_string())
+        .execute(pool)
+        .await?;
+    Ok(usize::try_from(result.rows_affected()).unwrap_or(usize::MAX))
+}
+
 /// Deletes jobs from the `pg_loco_queue` table based on their status.
 ///
 /// This function removes all jobs with a status that matches any of the statuses provided
@@ -533,6 +807,19 @@ pub async fn ping(pool: &PgPool) -> Result<()> {
     Ok(())
 }
 
+/// Counts the jobs waiting to be processed.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn queued_count(pool: &PgPool) -> Result<usize> {
+    let count: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM pg_loco_queue WHERE status = 'queued'")
+            .fetch_one(pool)
+            .await?;
+    Ok(usize::try_from(count).unwrap_or_default())
+}
+
 /// Retrieves a list of jobs from the `pg_loco_queue` table in the database.
 ///
 /// This function queries the database for jobs, optionally filtering by their
@@ -619,6 +906,8 @@ fn to_job(row: &PgRow) -> Result<Job> {
 pub struct RunOpts {
     pub num_workers: u32,
     pub poll_interval_sec: u32,
+    /// The maximum depth of the queue
+    pub limit: Option<QueueLimit>,
 }
 
 /// Create this provider
@@ -641,6 +930,7 @@ pub async fn create_provider(qcfg: &PostgresQueueConfig) -> Result<Queue> {
         RunOpts {
             num_workers: qcfg.num_workers,
             poll_interval_sec: qcfg.poll_interval_sec,
+            limit: qcfg.limit.clone(),
         },
         token, // Pass the token
     ))
@@ -659,6 +949,10 @@ mod tests {
     fn reduction() -> &'static [(&'static str, &'static str)] {
         &[
             ("[A-Z0-9]{26}", "<REDACTED>"),
+            (
+                "[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}",
+                "<REDACTED>",
+            ),
             (
                 r"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}(?:\.\d+)?Z",
                 "<REDACTED>",
@@ -1157,6 +1451,7 @@ mod tests {
         let opts = RunOpts {
             num_workers: 1,
             poll_interval_sec: 1,
+            limit: None,
         };
         let token = CancellationToken::new();
         let handles = registry.run(&pool, &opts, &token, &[]);
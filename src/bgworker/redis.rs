@@ -1,28 +1,119 @@
 /// Redis based background job queue provider
+///
+/// Each queue is a sorted set of jobs, scored by their priority: workers pop
+/// the highest priority jobs first, and jobs of the same priority in the
+/// order they were enqueued, as each job is prefixed with a sequence number
+/// taken from a Redis counter.
+///
+/// All the keys are prefixed with the `key_prefix` of the queue
+/// configuration, so that several apps or environments can share one Redis.
 use std::{
-    collections::HashMap, future::Future, panic::AssertUnwindSafe, pin::Pin, sync::Arc,
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    ops::Deref,
+    pin::Pin,
+    sync::Arc,
     time::Duration,
 };
 
-use super::{BackgroundWorker, JobStatus, Queue};
-use crate::{config::RedisQueueConfig, Error, Result};
+use super::{
+    chain::{self, QueueRef},
+    middleware::{JobInfo, JobMiddleware, JobMiddlewares},
+    schedules::Schedule,
+    BackgroundWorker, Codec, DrainReport, JobStatus, Queue, UniqueBatch,
+};
+use crate::{
+    config::{QueueLimit, RedisQueueConfig},
+    Error, Result,
+};
 use chrono::{DateTime, Utc};
-use futures_util::FutureExt;
 use redis::{aio::MultiplexedConnection as Connection, AsyncCommands, Client};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use tokio::{task::JoinHandle, time::sleep};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, trace};
-use ulid::Ulid;
+use tracing::{debug, error, trace, Instrument};
+
+/// The Redis client of the queue, prefixing its keys with the `key_prefix`
+/// of the configuration.
+///
+/// Derefs to the underlying [`Client`], whose commands are not prefixed:
+/// build their keys with [`RedisPool::key`].
+#[derive(Clone, Debug)]
+pub struct RedisPool {
+    client: Client,
+    key_prefix: String,
+}
+
+impl RedisPool {
+    #[must_use]
+    pub fn new(client: Client, key_prefix: Option<&str>) -> Self {
+        Self {
+            client,
+            key_prefix: key_prefix.unwrap_or_default().to_string(),
+        }
+    }
+
+    /// The prefix of the keys, empty when not configured.
+    #[must_use]
+    pub fn key_prefix(&self) -> &str {
+        &self.key_prefix
+    }
+
+    /// Prefixes `key`.
+    #[must_use]
+    pub fn key(&self, key: impl AsRef<str>) -> String {
+        format!("{}{}", self.key_prefix, key.as_ref())
+    }
+
+    /// Removes the prefix of a key read from Redis.
+    #[must_use]
+    pub fn strip<'a>(&self, key: &'a str) -> &'a str {
+        key.strip_prefix(self.key_prefix.as_str()).unwrap_or(key)
+    }
 
-pub type RedisPool = Client;
+    /// Prefixes the `KEYS` pattern `pattern`, escaping the prefix.
+    #[must_use]
+    pub fn pattern(&self, pattern: &str) -> String {
+        let mut escaped = String::with_capacity(self.key_prefix.len() + pattern.len());
+        for c in self.key_prefix.chars() {
+            if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        escaped.push_str(pattern);
+        escaped
+    }
+}
+
+impl Deref for RedisPool {
+    type Target = Client;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
 type JobId = String;
 type JobData = JsonValue;
 
 const QUEUE_KEY_PREFIX: &str = "queue:";
 const JOB_KEY_PREFIX: &str = "job:";
 const PROCESSING_KEY_PREFIX: &str = "processing:";
+const FAILED_KEY_PREFIX: &str = "failed:";
+const RESULT_KEY_PREFIX: &str = "result:";
+const LEGACY_KEY_PREFIX: &str = "legacy:";
+const UNIQUE_KEY_PREFIX: &str = "unique:";
+const PROCESSED_KEY_PREFIX: &str = "processed:";
+const SCHEDULES_KEY: &str = "schedules";
+const SCHEDULE_CLAIM_KEY_PREFIX: &str = "schedule-claim:";
+const QUEUE_SEQ_KEY: &str = "queue-seq";
+// How long the claim of a scheduled job is kept, to enqueue it once
+const SCHEDULE_CLAIM_TTL_MS: u64 = 24 * 60 * 60 * 1000;
+
+// How many jobs at the head of a queue a worker looks at for one matching its
+// tags
+const DEQUEUE_SCAN: isize = 100;
 
 type JobHandler = Box<
     dyn Fn(
@@ -45,6 +136,18 @@ pub struct Job {
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
     pub tags: Option<Vec<String>>,
+    /// The codec the job is stored with
+    #[serde(default)]
+    pub codec: Codec,
+    /// Jobs with a higher priority are dequeued first
+    #[serde(default)]
+    pub priority: i32,
+    /// The uniqueness key the job holds a lock on until it is dequeued
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unique_key: Option<String>,
+    /// The error of a failed job, kept apart from its arguments to retry it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 // Implementation for job creation and serialization
@@ -62,22 +165,63 @@ impl Job {
             created_at: Some(now),
             updated_at: Some(now),
             tags: None,
+            codec: Codec::default(),
+            priority: 0,
+            unique_key: None,
+            error: None,
         }
     }
 
-    // Create JSON format for storing in Redis
-    fn to_json(&self) -> Result<String> {
-        Ok(serde_json::to_string(self)?)
+    // The score of the job in its queue: the lowest scores are popped first
+    fn score(&self) -> f64 {
+        -f64::from(self.priority)
     }
 
-    // Parse from JSON format
-    fn from_json(json: &str) -> Result<Self> {
-        Ok(serde_json::from_str(json)?)
+    // Encode with the job codec for storing in Redis
+    fn encode(&self) -> Result<Vec<u8>> {
+        match self.codec {
+            Codec::Json => Ok(serde_json::to_vec(self)?),
+            #[cfg(feature = "msgpack")]
+            Codec::MsgPack => rmp_serde::to_vec_named(self).map_err(Error::wrap),
+            #[cfg(not(feature = "msgpack"))]
+            Codec::MsgPack => Err(msgpack_disabled()),
+        }
     }
+
+    // Decode a job of either codec: JSON jobs are objects, starting with `{`.
+    // The sequence prefix of queue members is skipped, see `queue_member`
+    fn decode(bytes: impl AsRef<[u8]>) -> Result<Self> {
+        let bytes = bytes.as_ref();
+        let bytes = match bytes.iter().position(|b| !b.is_ascii_digit()) {
+            Some(end) if end > 0 && bytes[end] == b':' => &bytes[end + 1..],
+            _ => bytes,
+        };
+        if bytes.first() == Some(&b'{') {
+            return Ok(serde_json::from_slice(bytes)?);
+        }
+        #[cfg(feature = "msgpack")]
+        {
+            rmp_serde::from_slice(bytes).map_err(Error::wrap)
+        }
+        #[cfg(not(feature = "msgpack"))]
+        {
+            Err(msgpack_disabled())
+        }
+    }
+}
+
+#[cfg(not(feature = "msgpack"))]
+fn msgpack_disabled() -> Error {
+    Error::string("the msgpack job codec requires the `msgpack` feature")
 }
 
 pub struct JobRegistry {
     handlers: Arc<HashMap<String, JobHandler>>,
+    middlewares: JobMiddlewares,
+    queue: QueueRef,
+    failures: BTreeMap<String, String>,
+    queues: HashMap<String, String>,
+    codecs: HashMap<String, Codec>,
 }
 
 impl JobRegistry {
@@ -86,6 +230,11 @@ impl JobRegistry {
     pub fn new() -> Self {
         Self {
             handlers: Arc::new(HashMap::new()),
+            middlewares: JobMiddlewares::default(),
+            queue: QueueRef::default(),
+            failures: BTreeMap::new(),
+            queues: HashMap::new(),
+            codecs: HashMap::new(),
         }
     }
 
@@ -101,38 +250,100 @@ impl JobRegistry {
         for<'de> Args: Deserialize<'de>,
     {
         let worker = Arc::new(worker);
-        let wrapped_handler = move |_job_id: String, job_data: JobData| {
+        let middlewares = self.middlewares.clone();
+        let queue = self.queue.clone();
+        let handler_name = name.clone();
+        let wrapped_handler = move |job_id: String, job_data: JobData| {
             let w = worker.clone();
 
+            let middlewares = middlewares.clone();
+            let queue = queue.clone();
+            let job = JobInfo {
+                id: job_id.clone(),
+                name: handler_name.clone(),
+            };
+
             Box::pin(async move {
-                let args = serde_json::from_value::<Args>(job_data);
+                let (job_data, span) = super::job_span(&job_id, job_data);
+                let (job_data, context) = crate::request_context::take_job_context(job_data);
+                let (job_data, chain) = chain::take_chain(job_data);
+                let args = serde_json::from_value::<Args>(job_data.clone());
                 match args {
                     Ok(args) => {
-                        // Wrap the perform call in catch_unwind to handle panics
-                        match AssertUnwindSafe(w.perform(args)).catch_unwind().await {
-                            Ok(result) => result,
-                            Err(panic) => {
-                                let panic_msg = panic
-                                    .downcast_ref::<String>()
-                                    .map(String::as_str)
-                                    .or_else(|| panic.downcast_ref::<&str>().copied())
-                                    .unwrap_or("Unknown panic occurred");
-                                error!(err = panic_msg, "worker panicked");
-                                Err(Error::string(panic_msg))
-                            }
-                        }
+                        let key = W::job_key(&args);
+                        let class = job.name.clone();
+                        // Run the middlewares around the perform call, turning panics into failures
+                        let perform =
+                            async move { super::with_timeout(W::timeout(), w.perform(args)).await };
+                        let perform = super::perform_once(
+                            &queue,
+                            &class,
+                            key,
+                            W::processed_for(),
+                            middlewares.run(job, perform),
+                        );
+                        let perform = chain::perform_queued(&queue, chain, job_data, perform);
+                        let perform = super::with_job_id(job_id, perform);
+                        crate::request_context::propagate(context, perform)
+                            .instrument(span)
+                            .await
                     }
                     Err(err) => Err(err.into()),
                 }
             }) as Pin<Box<dyn Future<Output = Result<(), crate::Error>> + Send>>
         };
 
+        self.queues.insert(
+            name.clone(),
+            W::queue().unwrap_or_else(|| "default".to_string()),
+        );
+        if let Some(codec) = W::codec() {
+            self.codecs.insert(name.clone(), codec);
+        }
         Arc::get_mut(&mut self.handlers)
             .ok_or_else(|| Error::string("cannot register worker"))?
             .insert(name, Box::new(wrapped_handler));
         Ok(())
     }
 
+    /// Returns the queue each registered worker pushes its jobs to.
+    #[must_use]
+    pub fn queues(&self) -> &HashMap<String, String> {
+        &self.queues
+    }
+
+    /// Returns the codec set by a registered worker, if any.
+    #[must_use]
+    pub fn codec(&self, name: &str) -> Option<Codec> {
+        self.codecs.get(name).copied()
+    }
+
+    /// Records a worker which failed to register, with its error.
+    pub fn add_failure(&mut self, name: String, error: String) {
+        self.failures.insert(name, error);
+    }
+
+    /// Returns the workers which failed to register, with their error.
+    #[must_use]
+    pub const fn failures(&self) -> &BTreeMap<String, String> {
+        &self.failures
+    }
+
+    /// Adds a middleware around the jobs of all the workers.
+    ///
+    /// # Errors
+    ///
+    /// Fails if cannot add the middleware
+    pub fn add_middleware(&mut self, middleware: Arc<dyn JobMiddleware>) -> Result<()> {
+        self.middlewares.add(middleware)
+    }
+
+    /// The queue the follow-up jobs and the next steps of chains are enqueued
+    /// to.
+    pub(crate) fn queue_ref(&self) -> QueueRef {
+        self.queue.clone()
+    }
+
     /// Returns a reference to the job handlers.
     #[must_use]
     pub fn handlers(&self) -> &Arc<HashMap<String, JobHandler>> {
@@ -149,7 +360,7 @@ impl JobRegistry {
         tags: &[String],
     ) -> Vec<JoinHandle<()>> {
         let mut jobs = Vec::new();
-        let queues = get_queues(&opts.queues);
+        let queues = processed_queues(opts);
         let interval = opts.poll_interval_sec;
 
         for idx in 0..opts.num_workers {
@@ -176,36 +387,7 @@ impl JobRegistry {
                     };
 
                     if let Some((job, queue_name)) = job_opt {
-                        debug!(job_id = job.id, name = job.name, "working on job");
-                        if let Some(handler) = handlers.get(&job.name) {
-                            match handler(job.id.clone(), job.data.clone()).await {
-                                Ok(()) => {
-                                    if let Err(err) =
-                                        complete_job(&client, &job.id, &queue_name, job.interval)
-                                            .await
-                                    {
-                                        error!(
-                                            err = err.to_string(),
-                                            job = ?job,
-                                            "cannot complete job"
-                                        );
-                                    }
-                                }
-                                Err(err) => {
-                                    if let Err(err) =
-                                        fail_job(&client, &job.id, &queue_name, &err).await
-                                    {
-                                        error!(
-                                            err = err.to_string(),
-                                            job = ?job,
-                                            "cannot fail job"
-                                        );
-                                    }
-                                }
-                            }
-                        } else {
-                            error!(job = job.name, "no handler found for job");
-                        }
+                        process_job(&client, &handlers, &job, &queue_name).await;
                     } else {
                         // Use tokio::select! to wait for interval or cancellation
                         tokio::select! {
@@ -235,9 +417,59 @@ impl Default for JobRegistry {
     }
 }
 
-fn connect(url: &str) -> Result<RedisPool> {
+/// Runs a dequeued job with its handler, and records its outcome: returns
+/// the status of the job, or `None` when no handler is registered for it.
+async fn process_job(
+    client: &RedisPool,
+    handlers: &HashMap<String, JobHandler>,
+    job: &Job,
+    queue_name: &str,
+) -> Option<JobStatus> {
+    debug!(job_id = job.id, name = job.name, "working on job");
+    let Some(handler) = handlers.get(&job.name) else {
+        error!(job = job.name, "no handler found for job");
+        return None;
+    };
+    match handler(job.id.clone(), job.data.clone()).await {
+        Ok(()) => {
+            if let Err(err) = complete_job(client, &job.id, queue_name, job.interval).await {
+                error!(err = err.to_string(), job = ?job, "cannot complete job");
+            }
+            Some(JobStatus::Completed)
+        }
+        Err(err) => {
+            if let Err(err) = fail_job(client, &job.id, queue_name, &err).await {
+                error!(err = err.to_string(), job = ?job, "cannot fail job");
+            }
+            Some(JobStatus::Failed)
+        }
+    }
+}
+
+/// Processes the jobs due now in the processed queues matching `tags`,
+/// including the jobs they enqueue, until there are none left. See
+/// [`Queue::drain`].
+///
+/// # Errors
+///
+/// When the jobs could not be dequeued
+pub async fn drain(
+    client: &RedisPool,
+    handlers: &HashMap<String, JobHandler>,
+    opts: &RunOpts,
+    tags: &[String],
+    report: &mut DrainReport,
+) -> Result<()> {
+    let queues = processed_queues(opts);
+    while let Some((job, queue_name)) = dequeue(client, &queues, tags).await? {
+        report.count(process_job(client, handlers, &job, &queue_name).await);
+    }
+    Ok(())
+}
+
+fn connect(url: &str, key_prefix: Option<&str>) -> Result<RedisPool> {
     let client = Client::open(url.to_string())?;
-    Ok(client)
+    Ok(RedisPool::new(client, key_prefix))
 }
 
 async fn get_connection(client: &RedisPool) -> Result<Connection> {
@@ -245,18 +477,58 @@ async fn get_connection(client: &RedisPool) -> Result<Connection> {
     Ok(conn)
 }
 
-/// Clear tasks
+/// Reserves `count` sequence numbers for queue members, returning the first
+/// one.
+async fn next_seq(client: &RedisPool, conn: &mut Connection, count: usize) -> Result<u64> {
+    let last: u64 = conn.incr(client.key(QUEUE_SEQ_KEY), count).await?;
+    Ok(last + 1 - count as u64)
+}
+
+/// The member of an encoded job in its queue, prefixed with a fixed width
+/// sequence number: the members of the same score sort by their bytes, so the
+/// jobs of the same priority pop in the order they were queued.
+fn queue_member(seq: u64, job_json: &[u8]) -> Vec<u8> {
+    let mut member = format!("{seq:020}:").into_bytes();
+    member.extend_from_slice(job_json);
+    member
+}
+
+/// Clear tasks: flushes the database, or only deletes the keys of the
+/// prefix when one is configured.
 ///
 /// # Errors
 ///
 /// This function will return an error if it fails
 pub async fn clear(client: &RedisPool) -> Result<()> {
     let mut conn = get_connection(client).await?;
-    redis::cmd("FLUSHDB").query_async::<()>(&mut conn).await?;
+    if client.key_prefix().is_empty() {
+        redis::cmd("FLUSHDB").query_async::<()>(&mut conn).await?;
+        return Ok(());
+    }
+    let keys: Vec<String> = redis::cmd("KEYS")
+        .arg(client.pattern("*"))
+        .query_async(&mut conn)
+        .await?;
+    for keys in keys.chunks(1000) {
+        let _: () = conn.del(keys).await?;
+    }
     Ok(())
 }
 
-/// Add a task
+/// Counts the jobs waiting in `queue`.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn queue_depth(client: &RedisPool, queue: &str) -> Result<usize> {
+    let mut conn = get_connection(client).await?;
+    let depth: usize = conn
+        .zcard(client.key(format!("{QUEUE_KEY_PREFIX}{queue}")))
+        .await?;
+    Ok(depth)
+}
+
+/// Add a task, returning the id assigned to the new job
 ///
 /// # Errors
 ///
@@ -267,35 +539,407 @@ pub async fn enqueue(
     queue: Option<String>,
     args: impl serde::Serialize + Send,
     tags: Option<Vec<String>>,
-) -> Result<()> {
+    codec: Codec,
+) -> Result<JobId> {
+    enqueue_with_priority(client, class, queue, args, tags, codec, 0).await
+}
+
+/// Add a task with a `priority`, returning the id assigned to the new job.
+/// Jobs with a higher priority are dequeued first.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn enqueue_with_priority(
+    client: &RedisPool,
+    class: String,
+    queue: Option<String>,
+    args: impl serde::Serialize + Send,
+    tags: Option<Vec<String>>,
+    codec: Codec,
+    priority: i32,
+) -> Result<JobId> {
     let mut conn = get_connection(client).await?;
     let queue_name = queue.unwrap_or_else(|| "default".to_string());
-    let queue_key = format!("{QUEUE_KEY_PREFIX}{queue_name}");
+    let queue_key = client.key(format!("{QUEUE_KEY_PREFIX}{queue_name}"));
 
     // Convert args to JSON
     let args_json = serde_json::to_value(args)?;
 
     // Create a job ID using ULID
-    let job_id = Ulid::new().to_string();
+    let job_id = crate::id::generate();
 
     // Create job
     let mut job = Job::new(job_id.clone(), class, args_json);
     job.tags = tags;
+    job.codec = codec;
+    job.priority = priority;
 
     // Serialize job for Redis storage
-    let job_json = job.to_json()?;
+    let job_json = job.encode()?;
 
     // Store job in Redis queue and in job key
-    let job_key = String::from(JOB_KEY_PREFIX) + &job.id;
+    let job_key = client.key(format!("{JOB_KEY_PREFIX}{}", job.id));
+    let seq = next_seq(client, &mut conn, 1).await?;
     redis::pipe()
-        .rpush(queue_key, &job_json)
+        .zadd(queue_key, queue_member(seq, &job_json), job.score())
         .set(job_key, &job_json)
         .query_async::<()>(&mut conn)
         .await?;
 
+    Ok(job_id)
+}
+
+/// Add many jobs for the same worker to the queue, sending each chunk of
+/// `chunk_size` jobs to Redis in a single pipelined round-trip.
+///
+/// Returns the job ids in the order of `args`.
+///
+/// # Errors
+///
+/// This function will return an error if it fails. Chunks sent before the
+/// failing one stay enqueued.
+pub async fn enqueue_batch<A: serde::Serialize + Send + Sync>(
+    client: &RedisPool,
+    class: String,
+    queue: Option<String>,
+    args: Vec<A>,
+    tags: Option<Vec<String>>,
+    codec: Codec,
+    chunk_size: usize,
+) -> Result<Vec<JobId>> {
+    let mut conn = get_connection(client).await?;
+    let queue_name = queue.unwrap_or_else(|| "default".to_string());
+    let queue_key = client.key(format!("{QUEUE_KEY_PREFIX}{queue_name}"));
+
+    let mut job_ids = Vec::with_capacity(args.len());
+    for chunk in args.chunks(chunk_size.max(1)) {
+        let mut pipe = redis::pipe();
+        let mut queued = Vec::with_capacity(chunk.len());
+        let mut seq = next_seq(client, &mut conn, chunk.len()).await?;
+        for args in chunk {
+            let job_id = crate::id::generate();
+            let mut job = Job::new(job_id.clone(), class.clone(), serde_json::to_value(args)?);
+            job.tags.clone_from(&tags);
+            job.codec = codec;
+
+            let job_json = job.encode()?;
+            pipe.set(client.key(format!("{JOB_KEY_PREFIX}{}", job.id)), &job_json);
+            queued.push((job.score(), queue_member(seq, &job_json)));
+            seq += 1;
+            job_ids.push(job_id);
+        }
+        pipe.zadd_multiple(&queue_key, &queued)
+            .query_async::<()>(&mut conn)
+            .await?;
+    }
+
+    Ok(job_ids)
+}
+
+/// Add many jobs for the same worker to the queue, each with a uniqueness
+/// key, skipping the jobs whose key is locked by a job still waiting in the
+/// queue. The locks are taken in one pipelined round-trip, and the jobs
+/// holding one enqueued in another. A lock is released when its job is
+/// dequeued, or else expires after `lock_ttl`.
+///
+/// Returns the keys of the enqueued and of the skipped jobs.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn enqueue_bulk_unique<A: serde::Serialize + Send + Sync>(
+    client: &RedisPool,
+    class: String,
+    queue: Option<String>,
+    jobs: Vec<(String, A)>,
+    tags: Option<Vec<String>>,
+    codec: Codec,
+    lock_ttl: Duration,
+) -> Result<UniqueBatch> {
+    let mut conn = get_connection(client).await?;
+    let queue_name = queue.unwrap_or_else(|| "default".to_string());
+    let queue_key = client.key(format!("{QUEUE_KEY_PREFIX}{queue_name}"));
+
+    let jobs = jobs
+        .into_iter()
+        .map(|(key, args)| {
+            let mut job = Job::new(
+                crate::id::generate(),
+                class.clone(),
+                serde_json::to_value(args)?,
+            );
+            job.tags.clone_from(&tags);
+            job.codec = codec;
+            job.unique_key = Some(key);
+            Ok(job)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut locks = redis::pipe();
+    for job in &jobs {
+        locks
+            .cmd("SET")
+            .arg(unique_lock_key(
+                client,
+                &job.name,
+                job.unique_key.as_deref().unwrap_or_default(),
+            ))
+            .arg(&job.id)
+            .arg("NX")
+            .arg("PX")
+            .arg(
+                u64::try_from(lock_ttl.as_millis())
+                    .unwrap_or(u64::MAX)
+                    .max(1),
+            );
+    }
+    let locked: Vec<Option<String>> = locks.query_async(&mut conn).await?;
+
+    let mut batch = UniqueBatch::default();
+    let mut pipe = redis::pipe();
+    let mut queued = Vec::new();
+    let mut seq = next_seq(client, &mut conn, locked.iter().flatten().count()).await?;
+    for (job, locked) in jobs.into_iter().zip(locked) {
+        let key = job.unique_key.clone().unwrap_or_default();
+        if locked.is_none() {
+            batch.skipped.push(key);
+            continue;
+        }
+        let job_json = job.encode()?;
+        pipe.set(client.key(format!("{JOB_KEY_PREFIX}{}", job.id)), &job_json);
+        queued.push((job.score(), queue_member(seq, &job_json)));
+        seq += 1;
+        batch.enqueued.push(key);
+    }
+    if !queued.is_empty() {
+        pipe.zadd_multiple(&queue_key, &queued)
+            .query_async::<()>(&mut conn)
+            .await?;
+    }
+
+    Ok(batch)
+}
+
+fn unique_lock_key(client: &RedisPool, class: &str, key: &str) -> String {
+    client.key(format!("{UNIQUE_KEY_PREFIX}{class}:{key}"))
+}
+
+/// Whether the job of `class` keyed `key` is in the processed set
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn is_processed(client: &RedisPool, class: &str, key: &str) -> Result<bool> {
+    let mut conn = get_connection(client).await?;
+    Ok(conn.exists(processed_key(client, class, key)).await?)
+}
+
+/// Add the job of `class` keyed `key` to the processed set, for `ttl`
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn mark_processed(
+    client: &RedisPool,
+    class: &str,
+    key: &str,
+    ttl: Duration,
+) -> Result<()> {
+    let mut conn = get_connection(client).await?;
+    redis::cmd("SET")
+        .arg(processed_key(client, class, key))
+        .arg(Utc::now().to_rfc3339())
+        .arg("PX")
+        .arg(u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX).max(1))
+        .query_async::<()>(&mut conn)
+        .await?;
     Ok(())
 }
 
+fn processed_key(client: &RedisPool, class: &str, key: &str) -> String {
+    client.key(format!("{PROCESSED_KEY_PREFIX}{class}:{key}"))
+}
+
+/// Save a runtime schedule
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn add_schedule(client: &RedisPool, schedule: &Schedule) -> Result<()> {
+    let mut conn = get_connection(client).await?;
+    conn.hset::<_, _, _, ()>(
+        client.key(SCHEDULES_KEY),
+        &schedule.id,
+        serde_json::to_string(schedule)?,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Remove a runtime schedule, returning whether it existed
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn remove_schedule(client: &RedisPool, id: &str) -> Result<bool> {
+    let mut conn = get_connection(client).await?;
+    let removed: usize = conn.hdel(client.key(SCHEDULES_KEY), id).await?;
+    Ok(removed > 0)
+}
+
+/// Get the runtime schedules
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn get_schedules(client: &RedisPool) -> Result<Vec<Schedule>> {
+    let mut conn = get_connection(client).await?;
+    let schedules: Vec<String> = conn.hvals(client.key(SCHEDULES_KEY)).await?;
+    Ok(schedules
+        .iter()
+        .filter_map(|json| match serde_json::from_str(json) {
+            Ok(schedule) => Some(schedule),
+            Err(err) => {
+                error!(err = err.to_string(), "failed to parse schedule JSON");
+                None
+            }
+        })
+        .collect())
+}
+
+/// Claim the job of a schedule due at its `next_run_at`, moving the schedule
+/// to `next_run_at`, or removing it when `None`
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn claim_schedule(
+    client: &RedisPool,
+    schedule: &Schedule,
+    next_run_at: Option<DateTime<Utc>>,
+) -> Result<bool> {
+    let mut conn = get_connection(client).await?;
+    let claimed: Option<String> = redis::cmd("SET")
+        .arg(client.key(format!(
+            "{SCHEDULE_CLAIM_KEY_PREFIX}{}:{}",
+            schedule.id,
+            schedule.next_run_at.timestamp_millis()
+        )))
+        .arg(1)
+        .arg("NX")
+        .arg("PX")
+        .arg(SCHEDULE_CLAIM_TTL_MS)
+        .query_async(&mut conn)
+        .await?;
+    if claimed.is_none() {
+        return Ok(false);
+    }
+
+    let schedules_key = client.key(SCHEDULES_KEY);
+    match next_run_at {
+        Some(next_run_at) => {
+            // an unscheduled schedule stays removed
+            let exists: bool = conn.hexists(&schedules_key, &schedule.id).await?;
+            if exists {
+                let next = Schedule {
+                    next_run_at,
+                    ..schedule.clone()
+                };
+                conn.hset::<_, _, _, ()>(&schedules_key, &next.id, serde_json::to_string(&next)?)
+                    .await?;
+            }
+        }
+        None => {
+            conn.hdel::<_, _, ()>(&schedules_key, &schedule.id).await?;
+        }
+    }
+    Ok(true)
+}
+
+/// Converts the queues stored as lists by earlier versions into sorted sets,
+/// keeping their jobs.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn convert_list_queues(client: &RedisPool) -> Result<()> {
+    let mut conn = get_connection(client).await?;
+    let queue_keys: Vec<String> = redis::cmd("KEYS")
+        .arg(client.pattern(&format!("{QUEUE_KEY_PREFIX}*")))
+        .query_async(&mut conn)
+        .await?;
+
+    for queue_key in queue_keys {
+        let key_type: String = redis::cmd("TYPE")
+            .arg(&queue_key)
+            .query_async(&mut conn)
+            .await?;
+        if key_type != "list" {
+            continue;
+        }
+
+        // Jobs enqueued meanwhile go to the new sorted set
+        let legacy_key = client.key(format!("{LEGACY_KEY_PREFIX}{}", client.strip(&queue_key)));
+        let _: () = conn.rename(&queue_key, &legacy_key).await?;
+        let jobs: Vec<Vec<u8>> = conn.lrange(&legacy_key, 0, -1).await?;
+        let mut queued = Vec::with_capacity(jobs.len());
+        let mut seq = next_seq(client, &mut conn, jobs.len()).await?;
+        for json in jobs {
+            match Job::decode(&json) {
+                Ok(job) => {
+                    queued.push((job.score(), queue_member(seq, &json)));
+                    seq += 1;
+                }
+                Err(err) => error!(err = err.to_string(), "failed to parse job JSON"),
+            }
+        }
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        if !queued.is_empty() {
+            pipe.zadd_multiple(&queue_key, &queued);
+        }
+        pipe.del(&legacy_key).query_async::<()>(&mut conn).await?;
+        debug!(
+            queue = queue_key,
+            count = queued.len(),
+            "converted list queue to a sorted set"
+        );
+    }
+    Ok(())
+}
+
+/// Store the result of a job, expiring after `ttl_sec` seconds
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn set_job_result(
+    client: &RedisPool,
+    job_id: &str,
+    result: &JsonValue,
+    ttl_sec: u64,
+) -> Result<()> {
+    let mut conn = get_connection(client).await?;
+    let result_key = client.key(format!("{RESULT_KEY_PREFIX}{job_id}"));
+    conn.set_ex::<_, _, ()>(result_key, serde_json::to_string(result)?, ttl_sec)
+        .await?;
+    Ok(())
+}
+
+/// Get the result of a job, if it was stored and did not expire yet
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn get_job_result(client: &RedisPool, job_id: &str) -> Result<Option<JsonValue>> {
+    let mut conn = get_connection(client).await?;
+    let result_key = client.key(format!("{RESULT_KEY_PREFIX}{job_id}"));
+    let result: Option<String> = conn.get(result_key).await?;
+    Ok(result.map(|json| serde_json::from_str(&json)).transpose()?)
+}
+
 async fn dequeue(
     client: &RedisPool,
     queues: &[String],
@@ -309,13 +953,13 @@ async fn dequeue(
 
     // Try to get a job from each queue in order (round-robin is more complex)
     for queue_name in queues {
-        let queue_key = format!("{QUEUE_KEY_PREFIX}{queue_name}");
+        let queue_key = client.key(format!("{QUEUE_KEY_PREFIX}{queue_name}"));
 
-        // Use LPOP to get and remove the first job from the queue
-        let job_json: Option<String> = conn.lpop(&queue_key, None).await?;
+        // Look at the first jobs of the queue, highest priority first
+        let head: Vec<Vec<u8>> = conn.zrange(&queue_key, 0, DEQUEUE_SCAN - 1).await?;
 
-        if let Some(json) = job_json {
-            match Job::from_json(&json) {
+        for json in head {
+            match Job::decode(&json) {
                 Ok(job) => {
                     // Check tag filtering
                     let should_process = if tags.is_empty() {
@@ -329,25 +973,36 @@ async fn dequeue(
                     };
 
                     if !should_process {
-                        // Put the job back in the queue
-                        let _: () = conn.rpush(&queue_key, json).await?;
+                        // Leave the job in the queue for another worker
                         trace!(
                             job_id = job.id,
                             job_tags = ?job.tags,
                             worker_tags = ?tags,
-                            "Job doesn't match tag criteria, returning to queue"
+                            "Job doesn't match tag criteria, leaving it in the queue"
                         );
                         continue;
                     }
 
+                    // Claim the job, unless another worker removed it first
+                    let removed: i32 = conn.zrem(&queue_key, &json).await?;
+                    if removed == 0 {
+                        continue;
+                    }
+
                     // Store job ID in processing set
-                    let processing_key = format!("{PROCESSING_KEY_PREFIX}{queue_name}");
+                    let processing_key = client.key(format!("{PROCESSING_KEY_PREFIX}{queue_name}"));
                     let _: () = conn.sadd(&processing_key, &job.id).await?;
 
+                    // A new job with the same uniqueness key may be enqueued
+                    if let Some(key) = &job.unique_key {
+                        let _: () = conn.del(unique_lock_key(client, &job.name, key)).await?;
+                    }
+
                     return Ok(Some((job, queue_name.clone())));
                 }
                 Err(err) => {
                     error!(err = err.to_string(), "failed to parse job JSON");
+                    let _: i32 = conn.zrem(&queue_key, &json).await?;
                 }
             }
         }
@@ -363,7 +1018,7 @@ async fn complete_job(
     interval_ms: Option<i64>,
 ) -> Result<()> {
     let mut conn = get_connection(client).await?;
-    let processing_key = format!("{PROCESSING_KEY_PREFIX}{queue_name}");
+    let processing_key = client.key(format!("{PROCESSING_KEY_PREFIX}{queue_name}"));
 
     // Remove job from processing set
     let _: () = redis::pipe()
@@ -372,22 +1027,23 @@ async fn complete_job(
         .await?;
 
     // Get job details
-    let job_key = String::from(JOB_KEY_PREFIX) + id;
-    let job_json: Option<String> = conn.get(&job_key).await?;
+    let job_key = client.key(format!("{JOB_KEY_PREFIX}{id}"));
+    let job_json: Option<Vec<u8>> = conn.get(&job_key).await?;
 
     if let Some(json) = job_json {
-        if let Ok(mut job) = Job::from_json(&json) {
+        if let Ok(mut job) = Job::decode(&json) {
             // If the job has an interval, requeue it
             if let Some(interval) = interval_ms {
                 // Update run_at time for the job
                 job.run_at = Utc::now() + chrono::Duration::milliseconds(interval);
 
                 // Reserialize and push to queue
-                let new_json = job.to_json()?;
-                let queue_key = format!("{QUEUE_KEY_PREFIX}{queue_name}");
+                let new_json = job.encode()?;
+                let queue_key = client.key(format!("{QUEUE_KEY_PREFIX}{queue_name}"));
+                let seq = next_seq(client, &mut conn, 1).await?;
 
                 let _: () = redis::pipe()
-                    .rpush(queue_key, new_json.clone())
+                    .zadd(queue_key, queue_member(seq, &new_json), job.score())
                     .set(&job_key, new_json)
                     .query_async(&mut conn)
                     .await?;
@@ -397,7 +1053,7 @@ async fn complete_job(
                 job.updated_at = Some(Utc::now());
 
                 // Save updated job
-                let updated_json = job.to_json()?;
+                let updated_json = job.encode()?;
                 let _: () = conn.set(&job_key, updated_json).await?;
             }
         }
@@ -413,7 +1069,7 @@ async fn fail_job(
     error: &crate::Error,
 ) -> Result<()> {
     let mut conn = get_connection(client).await?;
-    let processing_key = format!("{PROCESSING_KEY_PREFIX}{queue_name}");
+    let processing_key = client.key(format!("{PROCESSING_KEY_PREFIX}{queue_name}"));
 
     // Remove job from processing set
     let _: () = redis::pipe()
@@ -422,19 +1078,24 @@ async fn fail_job(
         .await?;
 
     // Store the error with the job
-    let job_key = String::from(JOB_KEY_PREFIX) + id;
-    let job_json: Option<String> = conn.get(&job_key).await?;
+    let job_key = client.key(format!("{JOB_KEY_PREFIX}{id}"));
+    let job_json: Option<Vec<u8>> = conn.get(&job_key).await?;
 
     if let Some(json) = job_json {
-        if let Ok(mut job) = Job::from_json(&json) {
-            // Add error to job data
-            let error_json = serde_json::json!({ "error": error.to_string() });
-            job.data = error_json;
+        if let Ok(mut job) = Job::decode(&json) {
+            // Store the error beside the arguments, to retry the job with them
+            job.error = Some(error.to_string());
             job.status = JobStatus::Failed;
-
-            // Save updated job
-            let updated_json = job.to_json()?;
-            let _: () = conn.set(&job_key, updated_json).await?;
+            job.updated_at = Some(Utc::now());
+
+            // Save updated job, and track it in the failed set of its queue
+            let updated_json = job.encode()?;
+            let failed_key = client.key(format!("{FAILED_KEY_PREFIX}{queue_name}"));
+            let _: () = redis::pipe()
+                .set(&job_key, updated_json)
+                .sadd(&failed_key, id)
+                .query_async(&mut conn)
+                .await?;
         }
     }
 
@@ -470,14 +1131,14 @@ pub async fn get_jobs(
     let mut jobs = Vec::new();
 
     // Get all queue keys
-    let queue_pattern = format!("{QUEUE_KEY_PREFIX}*");
+    let queue_pattern = client.pattern(&format!("{QUEUE_KEY_PREFIX}*"));
     let queue_keys: Vec<String> = redis::cmd("KEYS")
         .arg(&queue_pattern)
         .query_async(&mut conn)
         .await?;
 
     // Get all processing keys
-    let processing_pattern = format!("{PROCESSING_KEY_PREFIX}*");
+    let processing_pattern = client.pattern(&format!("{PROCESSING_KEY_PREFIX}*"));
     let processing_keys: Vec<String> = redis::cmd("KEYS")
         .arg(&processing_pattern)
         .query_async(&mut conn)
@@ -485,9 +1146,9 @@ pub async fn get_jobs(
 
     // Collect jobs from queues
     for queue_key in queue_keys {
-        let queue_jobs: Vec<String> = conn.lrange(&queue_key, 0, -1).await?;
+        let queue_jobs: Vec<Vec<u8>> = conn.zrange(&queue_key, 0, -1).await?;
         for job_json in queue_jobs {
-            if let Ok(job) = Job::from_json(&job_json) {
+            if let Ok(job) = Job::decode(&job_json) {
                 if should_include_job(&job, status, age_days) {
                     jobs.push(job);
                 }
@@ -500,11 +1161,11 @@ pub async fn get_jobs(
         let job_ids: Vec<String> = conn.smembers(&processing_key).await?;
         for job_id in job_ids {
             // Get the job from the job_key using the ID
-            let job_key = String::from(JOB_KEY_PREFIX) + &job_id;
-            let job_json: Option<String> = conn.get(&job_key).await?;
+            let job_key = client.key(format!("{JOB_KEY_PREFIX}{job_id}"));
+            let job_json: Option<Vec<u8>> = conn.get(&job_key).await?;
 
             if let Some(json) = job_json {
-                if let Ok(mut job) = Job::from_json(&json) {
+                if let Ok(mut job) = Job::decode(&json) {
                     // Jobs in processing sets have status "queued" but should be "processing"
                     if job.status == JobStatus::Queued {
                         job.status = JobStatus::Processing;
@@ -556,21 +1217,21 @@ pub async fn clear_by_status(client: &RedisPool, status: Vec<JobStatus>) -> Resu
     let mut conn = get_connection(client).await?;
 
     // Get all queue keys
-    let queue_pattern = format!("{QUEUE_KEY_PREFIX}*");
+    let queue_pattern = client.pattern(&format!("{QUEUE_KEY_PREFIX}*"));
     let queue_keys: Vec<String> = redis::cmd("KEYS")
         .arg(&queue_pattern)
         .query_async(&mut conn)
         .await?;
 
     // Get all processing keys
-    let processing_pattern = format!("{PROCESSING_KEY_PREFIX}*");
+    let processing_pattern = client.pattern(&format!("{PROCESSING_KEY_PREFIX}*"));
     let processing_keys: Vec<String> = redis::cmd("KEYS")
         .arg(&processing_pattern)
         .query_async(&mut conn)
         .await?;
 
     // Get all job keys
-    let job_pattern = format!("{JOB_KEY_PREFIX}*");
+    let job_pattern = client.pattern(&format!("{JOB_KEY_PREFIX}*"));
     let job_keys: Vec<String> = redis::cmd("KEYS")
         .arg(&job_pattern)
         .query_async(&mut conn)
@@ -579,17 +1240,17 @@ pub async fn clear_by_status(client: &RedisPool, status: Vec<JobStatus>) -> Resu
     // Process queues
     for queue_key in queue_keys {
         // Get all jobs in the queue
-        let queue_jobs: Vec<String> = conn.lrange(&queue_key, 0, -1).await?;
+        let queue_jobs: Vec<Vec<u8>> = conn.zrange(&queue_key, 0, -1).await?;
 
         // Process each job individually
         for job_json in queue_jobs {
-            if let Ok(job) = Job::from_json(&job_json) {
+            if let Ok(job) = Job::decode(&job_json) {
                 if status.contains(&job.status) {
                     // Remove this specific job from the queue
-                    let _: i32 = conn.lrem(&queue_key, 1, &job_json).await?;
+                    let _: i32 = conn.zrem(&queue_key, &job_json).await?;
 
                     // Delete the job_key
-                    let job_key = String::from(JOB_KEY_PREFIX) + &job.id;
+                    let job_key = client.key(format!("{JOB_KEY_PREFIX}{}", job.id));
                     let _: () = conn.del(&job_key).await?;
                 }
             }
@@ -602,11 +1263,11 @@ pub async fn clear_by_status(client: &RedisPool, status: Vec<JobStatus>) -> Resu
 
         for job_id in job_ids {
             // Get the job from the job_key using the ID
-            let job_key = String::from(JOB_KEY_PREFIX) + &job_id;
-            let job_json: Option<String> = conn.get(&job_key).await?;
+            let job_key = client.key(format!("{JOB_KEY_PREFIX}{job_id}"));
+            let job_json: Option<Vec<u8>> = conn.get(&job_key).await?;
 
             if let Some(json) = job_json {
-                if let Ok(mut job) = Job::from_json(&json) {
+                if let Ok(mut job) = Job::decode(&json) {
                     // Jobs in processing sets have status "queued" but should be "processing"
                     if job.status == JobStatus::Queued {
                         job.status = JobStatus::Processing;
@@ -627,10 +1288,10 @@ pub async fn clear_by_status(client: &RedisPool, status: Vec<JobStatus>) -> Resu
     // Process standalone job keys that might not be in any queue or processing set
     // (e.g., completed, failed, or cancelled jobs)
     for job_key in job_keys {
-        let job_json: Option<String> = conn.get(&job_key).await?;
+        let job_json: Option<Vec<u8>> = conn.get(&job_key).await?;
 
         if let Some(json) = job_json {
-            if let Ok(job) = Job::from_json(&json) {
+            if let Ok(job) = Job::decode(&json) {
                 if status.contains(&job.status) {
                     // Delete the job key
                     let _: () = conn.del(&job_key).await?;
@@ -660,21 +1321,21 @@ pub async fn clear_jobs_older_than(
     let cutoff_date = Utc::now() - chrono::Duration::days(age_days);
 
     // Get all queue keys
-    let queue_pattern = format!("{QUEUE_KEY_PREFIX}*");
+    let queue_pattern = client.pattern(&format!("{QUEUE_KEY_PREFIX}*"));
     let queue_keys: Vec<String> = redis::cmd("KEYS")
         .arg(&queue_pattern)
         .query_async(&mut conn)
         .await?;
 
     // Get all processing keys
-    let processing_pattern = format!("{PROCESSING_KEY_PREFIX}*");
+    let processing_pattern = client.pattern(&format!("{PROCESSING_KEY_PREFIX}*"));
     let processing_keys: Vec<String> = redis::cmd("KEYS")
         .arg(&processing_pattern)
         .query_async(&mut conn)
         .await?;
 
     // Get all job keys
-    let job_pattern = format!("{JOB_KEY_PREFIX}*");
+    let job_pattern = client.pattern(&format!("{JOB_KEY_PREFIX}*"));
     let job_keys: Vec<String> = redis::cmd("KEYS")
         .arg(&job_pattern)
         .query_async(&mut conn)
@@ -683,11 +1344,11 @@ pub async fn clear_jobs_older_than(
     // Process queues
     for queue_key in queue_keys {
         // Get all jobs in the queue
-        let queue_jobs: Vec<String> = conn.lrange(&queue_key, 0, -1).await?;
+        let queue_jobs: Vec<Vec<u8>> = conn.zrange(&queue_key, 0, -1).await?;
 
         // Process each job individually
         for job_json in queue_jobs {
-            if let Ok(job) = Job::from_json(&job_json) {
+            if let Ok(job) = Job::decode(&job_json) {
                 // Check if the job should be removed based on age and status
                 let should_remove = job.created_at.is_some_and(|created_at| {
                     created_at < cutoff_date && status.map_or(true, |s| s.contains(&job.status))
@@ -695,10 +1356,10 @@ pub async fn clear_jobs_older_than(
 
                 if should_remove {
                     // Remove this specific job from the queue
-                    let _: i32 = conn.lrem(&queue_key, 1, &job_json).await?;
+                    let _: i32 = conn.zrem(&queue_key, &job_json).await?;
 
                     // Delete the job key
-                    let job_key = String::from(JOB_KEY_PREFIX) + &job.id;
+                    let job_key = client.key(format!("{JOB_KEY_PREFIX}{}", job.id));
                     let _: () = conn.del(&job_key).await?;
                 }
             }
@@ -711,11 +1372,11 @@ pub async fn clear_jobs_older_than(
 
         for job_id in job_ids {
             // Get the actual job data using the ID
-            let job_key = String::from(JOB_KEY_PREFIX) + &job_id;
-            let job_json: Option<String> = conn.get(&job_key).await?;
+            let job_key = client.key(format!("{JOB_KEY_PREFIX}{job_id}"));
+            let job_json: Option<Vec<u8>> = conn.get(&job_key).await?;
 
             if let Some(json) = job_json {
-                if let Ok(mut job) = Job::from_json(&json) {
+                if let Ok(mut job) = Job::decode(&json) {
                     // Jobs in processing sets have status "queued" but should be "processing"
                     if job.status == JobStatus::Queued {
                         job.status = JobStatus::Processing;
@@ -740,10 +1401,10 @@ pub async fn clear_jobs_older_than(
 
     // Process standalone job keys (completed, failed, or cancelled jobs)
     for job_key in job_keys {
-        let job_json: Option<String> = conn.get(&job_key).await?;
+        let job_json: Option<Vec<u8>> = conn.get(&job_key).await?;
 
         if let Some(json) = job_json {
-            if let Ok(job) = Job::from_json(&json) {
+            if let Ok(job) = Job::decode(&json) {
                 // Check if the job should be removed based on age and status
                 let should_remove = job.created_at.is_some_and(|created_at| {
                     created_at < cutoff_date && status.map_or(true, |s| s.contains(&job.status))
@@ -775,7 +1436,7 @@ pub async fn requeue(client: &RedisPool, age_minutes: &i64) -> Result<()> {
     let mut requeued_counts: HashMap<String, usize> = HashMap::new();
 
     // Get all processing set keys
-    let processing_pattern = format!("{PROCESSING_KEY_PREFIX}*");
+    let processing_pattern = client.pattern(&format!("{PROCESSING_KEY_PREFIX}*"));
     let processing_keys: Vec<String> = redis::cmd("KEYS")
         .arg(&processing_pattern)
         .query_async(&mut conn)
@@ -784,10 +1445,11 @@ pub async fn requeue(client: &RedisPool, age_minutes: &i64) -> Result<()> {
     // Process each processing set
     for processing_key in processing_keys {
         // Extract queue name from processing key
-        let queue_name = processing_key
+        let queue_name = client
+            .strip(&processing_key)
             .trim_start_matches(PROCESSING_KEY_PREFIX)
             .to_string();
-        let queue_key = format!("{QUEUE_KEY_PREFIX}{queue_name}");
+        let queue_key = client.key(format!("{QUEUE_KEY_PREFIX}{queue_name}"));
 
         // Get all jobs in the processing set
         let processing_jobs: Vec<String> = conn.smembers(&processing_key).await?;
@@ -795,11 +1457,11 @@ pub async fn requeue(client: &RedisPool, age_minutes: &i64) -> Result<()> {
         // Check each job in the processing set
         for job_id in &processing_jobs {
             // Get the actual job data using the ID
-            let job_key = String::from(JOB_KEY_PREFIX) + job_id;
-            let job_json: Option<String> = conn.get(&job_key).await?;
+            let job_key = client.key(format!("{JOB_KEY_PREFIX}{job_id}"));
+            let job_json: Option<Vec<u8>> = conn.get(&job_key).await?;
 
             if let Some(json) = job_json {
-                if let Ok(mut job) = Job::from_json(&json) {
+                if let Ok(mut job) = Job::decode(&json) {
                     // Check if the job is old enough to be requeued
                     let should_requeue = if let Some(updated_at) = job.updated_at {
                         updated_at < cutoff_time
@@ -816,7 +1478,7 @@ pub async fn requeue(client: &RedisPool, age_minutes: &i64) -> Result<()> {
                         job.updated_at = Some(Utc::now());
 
                         // Update the job in Redis
-                        if let Ok(updated_json) = job.to_json() {
+                        if let Ok(updated_json) = job.encode() {
                             // First, remove from the processing set
                             let _: i32 = conn.srem(&processing_key, job_id).await?;
 
@@ -824,7 +1486,10 @@ pub async fn requeue(client: &RedisPool, age_minutes: &i64) -> Result<()> {
                             let _: () = conn.set(&job_key, &updated_json).await?;
 
                             // Add back to the queue
-                            let _: () = conn.rpush(&queue_key, &updated_json).await?;
+                            let seq = next_seq(client, &mut conn, 1).await?;
+                            let _: () = conn
+                                .zadd(&queue_key, queue_member(seq, &updated_json), job.score())
+                                .await?;
 
                             // Track count for logging
                             *requeued_counts.entry(queue_name.clone()).or_insert(0) += 1;
@@ -836,16 +1501,19 @@ pub async fn requeue(client: &RedisPool, age_minutes: &i64) -> Result<()> {
     }
 
     // Also check for failed jobs to requeue
-    let failed_pattern = "failed:*";
+    let failed_pattern = client.pattern(&format!("{FAILED_KEY_PREFIX}*"));
     let failed_keys: Vec<String> = redis::cmd("KEYS")
-        .arg(failed_pattern)
+        .arg(&failed_pattern)
         .query_async(&mut conn)
         .await?;
 
     for failed_key in failed_keys {
         // Extract queue name from failed key
-        let queue_name = failed_key.trim_start_matches("failed:").to_string();
-        let queue_key = format!("{QUEUE_KEY_PREFIX}{queue_name}");
+        let queue_name = client
+            .strip(&failed_key)
+            .trim_start_matches(FAILED_KEY_PREFIX)
+            .to_string();
+        let queue_key = client.key(format!("{QUEUE_KEY_PREFIX}{queue_name}"));
 
         // Get all jobs in the failed set
         let failed_jobs: Vec<String> = conn.smembers(&failed_key).await?;
@@ -853,11 +1521,11 @@ pub async fn requeue(client: &RedisPool, age_minutes: &i64) -> Result<()> {
         // Check each job in the failed set
         for job_id in &failed_jobs {
             // Get the actual job data using the ID
-            let job_key = String::from(JOB_KEY_PREFIX) + job_id;
-            let job_json: Option<String> = conn.get(&job_key).await?;
+            let job_key = client.key(format!("{JOB_KEY_PREFIX}{job_id}"));
+            let job_json: Option<Vec<u8>> = conn.get(&job_key).await?;
 
             if let Some(json) = job_json {
-                if let Ok(mut job) = Job::from_json(&json) {
+                if let Ok(mut job) = Job::decode(&json) {
                     // Check if the job is old enough to be requeued
                     let should_requeue = if let Some(updated_at) = job.updated_at {
                         updated_at < cutoff_time && job.status == JobStatus::Failed
@@ -871,7 +1539,7 @@ pub async fn requeue(client: &RedisPool, age_minutes: &i64) -> Result<()> {
                         job.updated_at = Some(Utc::now());
 
                         // Update the job in Redis
-                        if let Ok(updated_json) = job.to_json() {
+                        if let Ok(updated_json) = job.encode() {
                             // First, remove from the failed set
                             let _: i32 = conn.srem(&failed_key, job_id).await?;
 
@@ -879,7 +1547,10 @@ pub async fn requeue(client: &RedisPool, age_minutes: &i64) -> Result<()> {
                             let _: () = conn.set(&job_key, &updated_json).await?;
 
                             // Add back to the queue
-                            let _: () = conn.rpush(&queue_key, &updated_json).await?;
+                            let seq = next_seq(client, &mut conn, 1).await?;
+                            let _: () = conn
+                                .zadd(&queue_key, queue_member(seq, &updated_json), job.score())
+                                .await?;
 
                             // Track count for logging
                             *requeued_counts.entry(queue_name.clone()).or_insert(0) += 1;
@@ -900,60 +1571,231 @@ pub async fn requeue(client: &RedisPool, age_minutes: &i64) -> Result<()> {
     Ok(())
 }
 
-/// Cancels jobs with the specified name in the Redis queue.
-///
-/// This function updates the status of jobs that match the provided `job_name`
-/// from [`JobStatus::Queued`] to [`JobStatus::Cancelled`]. Jobs are searched for in all queue keys,
-/// and only those that are currently in the [`JobStatus::Queued`] state will be affected.
+/// Cancels jobs with the specified name in the Redis queue.
+///
+/// This function updates the status of jobs that match the provided `job_name`
+/// from [`JobStatus::Queued`] to [`JobStatus::Cancelled`]. Jobs are searched for in all queue keys,
+/// and only those that are currently in the [`JobStatus::Queued`] state will be affected.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn cancel_jobs_by_name(client: &RedisPool, job_name: &str) -> Result<()> {
+    let mut conn = get_connection(client).await?;
+
+    // Get all queue keys
+    let queue_pattern = client.pattern(&format!("{QUEUE_KEY_PREFIX}*"));
+    let queue_keys: Vec<String> = redis::cmd("KEYS")
+        .arg(&queue_pattern)
+        .query_async(&mut conn)
+        .await?;
+
+    // Process each queue
+    for queue_key in queue_keys {
+        // Get all jobs in the queue
+        let queue_jobs: Vec<Vec<u8>> = conn.zrange(&queue_key, 0, -1).await?;
+
+        // Process each job individually
+        for job_json in queue_jobs {
+            if let Ok(mut job) = Job::decode(&job_json) {
+                if job.name == job_name && job.status == JobStatus::Queued {
+                    // Mark this job as cancelled
+                    job.status = JobStatus::Cancelled;
+                    job.updated_at = Some(Utc::now());
+
+                    // Update the job key
+                    let job_key = client.key(format!("{JOB_KEY_PREFIX}{}", job.id));
+                    let updated_json = job.encode()?;
+
+                    // Remove this specific job from the queue
+                    let _: i32 = conn.zrem(&queue_key, &job_json).await?;
+
+                    // Update the job in Redis
+                    let _: () = conn.set(&job_key, &updated_json).await?;
+
+                    // Store cancelled job in a set for tracking (optional)
+                    let cancelled_key = client.key(format!(
+                        "cancelled:{}",
+                        client
+                            .strip(&queue_key)
+                            .trim_start_matches(QUEUE_KEY_PREFIX)
+                    ));
+                    let _: () = conn.sadd(&cancelled_key, &updated_json).await?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the jobs waiting in the queues, for [`JobStatus::Queued`], or the
+/// failed jobs, for [`JobStatus::Failed`], with the name of their queue. Jobs
+/// of other statuses are not tracked by queue.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn list_jobs(client: &RedisPool, status: &JobStatus) -> Result<Vec<(String, Job)>> {
+    let mut conn = get_connection(client).await?;
+    let mut jobs = Vec::new();
+    match status {
+        JobStatus::Queued => {
+            for (queue, queue_key) in queue_keys(client, &mut conn, QUEUE_KEY_PREFIX).await? {
+                let queue_jobs: Vec<Vec<u8>> = conn.zrange(&queue_key, 0, -1).await?;
+                jobs.extend(
+                    queue_jobs
+                        .iter()
+                        .filter_map(|json| Job::decode(json).ok())
+                        .map(|job| (queue.clone(), job)),
+                );
+            }
+        }
+        JobStatus::Failed => {
+            for (queue, failed_key) in queue_keys(client, &mut conn, FAILED_KEY_PREFIX).await? {
+                let job_ids: Vec<String> = conn.smembers(&failed_key).await?;
+                for job_id in job_ids {
+                    let job_key = client.key(format!("{JOB_KEY_PREFIX}{job_id}"));
+                    let job_json: Option<Vec<u8>> = conn.get(&job_key).await?;
+                    if let Some(job) = job_json.and_then(|json| Job::decode(json).ok()) {
+                        jobs.push((queue.clone(), job));
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(jobs)
+}
+
+/// Returns the queue names and keys of the keys starting with `prefix`.
+async fn queue_keys(
+    client: &RedisPool,
+    conn: &mut Connection,
+    prefix: &str,
+) -> Result<Vec<(String, String)>> {
+    let keys: Vec<String> = redis::cmd("KEYS")
+        .arg(client.pattern(&format!("{prefix}*")))
+        .query_async(conn)
+        .await?;
+    Ok(keys
+        .into_iter()
+        .map(|key| {
+            (
+                client.strip(&key).trim_start_matches(prefix).to_string(),
+                key,
+            )
+        })
+        .collect())
+}
+
+/// Moves the failed job `id` back to its queue, without its error, returning
+/// whether there was such a failed job.
 ///
 /// # Errors
 ///
 /// This function will return an error if it fails
-pub async fn cancel_jobs_by_name(client: &RedisPool, job_name: &str) -> Result<()> {
+pub async fn retry_job(client: &RedisPool, id: &str) -> Result<bool> {
     let mut conn = get_connection(client).await?;
+    let job_key = client.key(format!("{JOB_KEY_PREFIX}{id}"));
+    let job_json: Option<Vec<u8>> = conn.get(&job_key).await?;
+    let Some(mut job) = job_json.and_then(|json| Job::decode(json).ok()) else {
+        return Ok(false);
+    };
+    let mut failed_in = None;
+    for (queue, failed_key) in queue_keys(client, &mut conn, FAILED_KEY_PREFIX).await? {
+        let removed: i32 = conn.srem(&failed_key, id).await?;
+        if removed > 0 {
+            failed_in = Some(queue);
+        }
+    }
+    let Some(queue) = failed_in else {
+        return Ok(false);
+    };
 
-    // Get all queue keys
-    let queue_pattern = format!("{QUEUE_KEY_PREFIX}*");
-    let queue_keys: Vec<String> = redis::cmd("KEYS")
-        .arg(&queue_pattern)
+    job.error = None;
+    job.status = JobStatus::Queued;
+    job.run_at = Utc::now();
+    job.updated_at = Some(job.run_at);
+    let job_json = job.encode()?;
+    let queue_key = client.key(format!("{QUEUE_KEY_PREFIX}{queue}"));
+    let seq = next_seq(client, &mut conn, 1).await?;
+    let _: () = redis::pipe()
+        .set(&job_key, &job_json)
+        .zadd(&queue_key, queue_member(seq, &job_json), job.score())
         .query_async(&mut conn)
         .await?;
+    debug!(job_id = id, queue, "retrying failed job");
+    Ok(true)
+}
 
-    // Process each queue
-    for queue_key in queue_keys {
-        // Get all jobs in the queue
-        let queue_jobs: Vec<String> = conn.lrange(&queue_key, 0, -1).await?;
-
-        // Process each job individually
-        for job_json in queue_jobs {
-            if let Ok(mut job) = Job::from_json(&job_json) {
-                if job.name == job_name && job.status == JobStatus::Queued {
-                    // Mark this job as cancelled
-                    job.status = JobStatus::Cancelled;
-                    job.updated_at = Some(Utc::now());
-
-                    // Update the job key
-                    let job_key = String::from(JOB_KEY_PREFIX) + &job.id;
-                    let updated_json = job.to_json()?;
-
-                    // Remove this specific job from the queue
-                    let _: i32 = conn.lrem(&queue_key, 1, &job_json).await?;
-
-                    // Update the job in Redis
-                    let _: () = conn.set(&job_key, &updated_json).await?;
-
-                    // Store cancelled job in a set for tracking (optional)
-                    let cancelled_key = format!(
-                        "cancelled:{}",
-                        queue_key.trim_start_matches(QUEUE_KEY_PREFIX)
-                    );
-                    let _: () = conn.sadd(&cancelled_key, &updated_json).await?;
-                }
+/// Deletes the waiting or failed job `id`, returning whether there was such a
+/// job. Jobs being processed are not deleted.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn delete_job(client: &RedisPool, id: &str) -> Result<bool> {
+    let mut conn = get_connection(client).await?;
+    let mut deleted = false;
+    for (_, failed_key) in queue_keys(client, &mut conn, FAILED_KEY_PREFIX).await? {
+        let removed: i32 = conn.srem(&failed_key, id).await?;
+        deleted |= removed > 0;
+    }
+    for (_, queue_key) in queue_keys(client, &mut conn, QUEUE_KEY_PREFIX).await? {
+        let queue_jobs: Vec<Vec<u8>> = conn.zrange(&queue_key, 0, -1).await?;
+        for json in queue_jobs {
+            let Some(job) = Job::decode(&json).ok().filter(|job| job.id == id) else {
+                continue;
+            };
+            let removed: i32 = conn.zrem(&queue_key, &json).await?;
+            deleted |= removed > 0;
+            if let Some(key) = &job.unique_key {
+                let _: () = conn.del(unique_lock_key(client, &job.name, key)).await?;
             }
         }
     }
+    if deleted {
+        let _: () = conn
+            .del(client.key(format!("{JOB_KEY_PREFIX}{id}")))
+            .await?;
+    }
+    Ok(deleted)
+}
 
-    Ok(())
+/// Deletes the jobs waiting in `queue`, returning how many there were.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn purge_queue(client: &RedisPool, queue: &str) -> Result<usize> {
+    let mut conn = get_connection(client).await?;
+    let queue_key = client.key(format!("{QUEUE_KEY_PREFIX}{queue}"));
+    let queue_jobs: Vec<Vec<u8>> = conn.zrange(&queue_key, 0, -1).await?;
+    let mut pipe = redis::pipe();
+    pipe.del(&queue_key);
+    for job in queue_jobs.iter().filter_map(|json| Job::decode(json).ok()) {
+        pipe.del(client.key(format!("{JOB_KEY_PREFIX}{}", job.id)));
+        if let Some(key) = &job.unique_key {
+            pipe.del(unique_lock_key(client, &job.name, key));
+        }
+    }
+    let _: () = pipe.query_async(&mut conn).await?;
+    Ok(queue_jobs.len())
+}
+
+/// Deletes the jobs waiting in every queue, returning how many there were.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn purge_all(client: &RedisPool) -> Result<usize> {
+    let mut conn = get_connection(client).await?;
+    let mut purged = 0;
+    for (queue, _) in queue_keys(client, &mut conn, QUEUE_KEY_PREFIX).await? {
+        purged += purge_queue(client, &queue).await?;
+    }
+    Ok(purged)
 }
 
 pub const DEFAULT_QUEUES: &[&str] = &["default", "mailer"];
@@ -976,10 +1818,54 @@ pub fn get_queues(config_queues: &Option<Vec<String>>) -> Vec<String> {
     queues
 }
 
+/// Returns the queue references no worker would ever dequeue from: queues
+/// of registered workers that are not declared in `queue.queues`, and
+/// `workers.queues` entries that are not declared either.
+#[must_use]
+pub fn unknown_queues<S: std::hash::BuildHasher>(
+    opts: &RunOpts,
+    worker_queues: &HashMap<String, String, S>,
+) -> Vec<String> {
+    let declared = get_queues(&opts.queues);
+    let mut unknown = worker_queues
+        .iter()
+        .filter(|(_, queue)| !declared.contains(queue))
+        .map(|(worker, queue)| {
+            format!("worker `{worker}` pushes to queue `{queue}`, missing from `queue.queues`")
+        })
+        .collect::<Vec<_>>();
+    unknown.sort();
+    for queue in opts.processed_queues.iter().flatten() {
+        if !declared.contains(queue) {
+            unknown.push(format!(
+                "`workers.queues` selects queue `{queue}`, missing from `queue.queues`"
+            ));
+        }
+    }
+    unknown
+}
+
+/// Returns the queues the workers dequeue from: the queues selected with
+/// `workers.queues` when set, otherwise the default and configured queues.
+fn processed_queues(opts: &RunOpts) -> Vec<String> {
+    opts.processed_queues
+        .clone()
+        .unwrap_or_else(|| get_queues(&opts.queues))
+}
+
 pub struct RunOpts {
     pub num_workers: u32,
     pub poll_interval_sec: u32,
     pub queues: Option<Vec<String>>,
+    /// Restricts processing to these queues, jobs pushed to other queues are
+    /// left for other instances.
+    pub processed_queues: Option<Vec<String>>,
+    pub batch_chunk_size: usize,
+    pub result_ttl_sec: u64,
+    /// The codec of the jobs whose worker does not set one
+    pub codec: Codec,
+    /// The maximum depth of queues, by queue name
+    pub limits: BTreeMap<String, QueueLimit>,
 }
 
 /// Create this provider
@@ -989,7 +1875,7 @@ pub struct RunOpts {
 /// This function will return an error if it fails
 #[allow(clippy::unused_async)]
 pub async fn create_provider(qcfg: &RedisQueueConfig) -> Result<Queue> {
-    let client = connect(&qcfg.uri)?;
+    let client = connect(&qcfg.uri, qcfg.key_prefix.as_deref())?;
     let registry = JobRegistry::new();
     let token = CancellationToken::new();
 
@@ -997,6 +1883,11 @@ pub async fn create_provider(qcfg: &RedisQueueConfig) -> Result<Queue> {
         num_workers: qcfg.num_workers,
         poll_interval_sec: 1,
         queues: qcfg.queues.clone(),
+        processed_queues: None,
+        batch_chunk_size: qcfg.batch_chunk_size,
+        result_ttl_sec: qcfg.result_ttl_sec,
+        codec: qcfg.codec,
+        limits: qcfg.limits.clone(),
     };
 
     debug!(
@@ -1027,7 +1918,7 @@ mod tests {
         let (redis_url, container) = setup_redis_container().await;
 
         // Connect to Redis
-        let client = connect(&redis_url).expect("connect to redis");
+        let client = connect(&redis_url, None).expect("connect to redis");
 
         (client, container)
     }
@@ -1046,18 +1937,25 @@ mod tests {
                 created_at: Some(now - chrono::Duration::days(15)),
                 updated_at: Some(now - chrono::Duration::days(15)),
                 tags: None,
+                codec: Codec::Json,
+                priority: 0,
+                unique_key: None,
+                error: None,
             };
 
             let mut conn = get_connection(client).await?;
             // Store job data
             let _: () = conn
-                .set(format!("{JOB_KEY_PREFIX}job{i}"), complete_job.to_json()?)
+                .set(
+                    client.key(format!("{JOB_KEY_PREFIX}job{i}")),
+                    complete_job.encode()?,
+                )
                 .await?;
         }
 
         // Create queued jobs
         let args = serde_json::json!({"hello": "world"});
-        enqueue(client, "TestJob".to_string(), None, args, None).await?;
+        enqueue(client, "TestJob".to_string(), None, args, None, Codec::Json).await?;
 
         // Create job with tags
         let args = serde_json::json!({"hello": "tagged"});
@@ -1067,12 +1965,25 @@ mod tests {
             None,
             args,
             Some(vec!["important".to_string(), "urgent".to_string()]),
+            Codec::Json,
         )
         .await?;
 
         Ok(())
     }
 
+    #[test]
+    fn can_prefix_keys() {
+        let client = connect("redis://127.0.0.1", Some("app[1]:")).unwrap();
+        assert_eq!(client.key("queue:default"), "app[1]:queue:default");
+        assert_eq!(client.strip("app[1]:queue:default"), "queue:default");
+        assert_eq!(client.pattern("queue:*"), "app\\[1\\]:queue:*");
+
+        let client = connect("redis://127.0.0.1", None).unwrap();
+        assert_eq!(client.key("queue:default"), "queue:default");
+        assert_eq!(client.pattern("queue:*"), "queue:*");
+    }
+
     async fn get_all_jobs(client: &RedisPool) -> Vec<Job> {
         get_jobs(client, None, None).await.unwrap_or_default()
     }
@@ -1090,6 +2001,185 @@ mod tests {
         assert!(job_opt.is_some());
     }
 
+    #[tokio::test]
+    async fn test_can_dequeue_by_priority() {
+        let (client, _container) = setup_redis().await;
+
+        for (name, priority) in [("Low", -1), ("First", 0), ("Urgent", 10), ("Second", 0)] {
+            let args = serde_json::json!({ "name": name });
+            enqueue_with_priority(
+                &client,
+                name.to_string(),
+                None,
+                args,
+                None,
+                Codec::Json,
+                priority,
+            )
+            .await
+            .expect("enqueue");
+        }
+
+        let queues = vec!["default".to_string()];
+        let mut dequeued = Vec::new();
+        while let Some((job, _)) = dequeue(&client, &queues, &[]).await.expect("dequeue") {
+            dequeued.push(job.name);
+        }
+        assert_eq!(dequeued, vec!["Urgent", "First", "Second", "Low"]);
+    }
+
+    #[test]
+    fn can_order_queue_members_by_sequence() {
+        // jobs enqueued within the same millisecond, with ids out of order
+        let now = Utc::now();
+        let members = ["zz", "aa", "mm"]
+            .iter()
+            .enumerate()
+            .map(|(seq, id)| {
+                let mut job = Job::new((*id).to_string(), "Job".to_string(), serde_json::json!(1));
+                job.run_at = now;
+                queue_member(seq as u64 + 9, &job.encode().unwrap())
+            })
+            .collect::<Vec<_>>();
+
+        let mut sorted = members.clone();
+        sorted.sort();
+        assert_eq!(sorted, members);
+        let ids = members
+            .iter()
+            .map(|member| Job::decode(member).unwrap().id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["zz", "aa", "mm"]);
+    }
+
+    #[tokio::test]
+    async fn test_can_dequeue_same_priority_in_enqueue_order() {
+        let (client, _container) = setup_redis().await;
+
+        // most of these are enqueued within the same millisecond
+        let mut job_ids = Vec::new();
+        for i in 0..50 {
+            job_ids.push(
+                enqueue(
+                    &client,
+                    "Ordered".to_string(),
+                    None,
+                    serde_json::json!({ "i": i }),
+                    None,
+                    Codec::Json,
+                )
+                .await
+                .expect("enqueue"),
+            );
+        }
+        job_ids.extend(
+            enqueue_batch(
+                &client,
+                "Ordered".to_string(),
+                None,
+                (50..100).map(|i| serde_json::json!({ "i": i })).collect(),
+                None,
+                Codec::Json,
+                7,
+            )
+            .await
+            .expect("enqueue batch"),
+        );
+
+        let queues = vec!["default".to_string()];
+        let mut dequeued = Vec::new();
+        while let Some((job, _)) = dequeue(&client, &queues, &[]).await.expect("dequeue") {
+            dequeued.push(job.id);
+        }
+        assert_eq!(dequeued, job_ids);
+    }
+
+    #[tokio::test]
+    async fn test_can_enqueue_bulk_unique() {
+        let (client, _container) = setup_redis().await;
+        let ttl = Duration::from_secs(60);
+        let jobs = |keys: &[&str]| {
+            keys.iter()
+                .map(|key| ((*key).to_string(), serde_json::json!({ "user": key })))
+                .collect::<Vec<_>>()
+        };
+
+        let batch = enqueue_bulk_unique(
+            &client,
+            "Notify".to_string(),
+            None,
+            jobs(&["a", "b", "a"]),
+            None,
+            Codec::Json,
+            ttl,
+        )
+        .await
+        .expect("enqueue");
+        assert_eq!(batch.enqueued, vec!["a", "b"]);
+        assert_eq!(batch.skipped, vec!["a"]);
+        assert_eq!(queue_depth(&client, "default").await.unwrap(), 2);
+
+        let batch = enqueue_bulk_unique(
+            &client,
+            "Notify".to_string(),
+            None,
+            jobs(&["b", "c"]),
+            None,
+            Codec::Json,
+            ttl,
+        )
+        .await
+        .expect("enqueue");
+        assert_eq!(batch.enqueued, vec!["c"]);
+        assert_eq!(batch.skipped, vec!["b"]);
+
+        // dequeuing a job releases its key
+        let queues = vec!["default".to_string()];
+        let (job, _) = dequeue(&client, &queues, &[])
+            .await
+            .expect("dequeue")
+            .expect("job");
+        let key = job.unique_key.expect("unique key");
+        let batch = enqueue_bulk_unique(
+            &client,
+            "Notify".to_string(),
+            None,
+            jobs(&[key.as_str()]),
+            None,
+            Codec::Json,
+            ttl,
+        )
+        .await
+        .expect("enqueue");
+        assert_eq!(batch.enqueued, vec![key]);
+    }
+
+    #[tokio::test]
+    async fn test_can_convert_list_queues() {
+        let (client, _container) = setup_redis().await;
+        let mut conn = get_connection(&client).await.expect("get connection");
+
+        let job = Job::new(
+            "01J".to_string(),
+            "TestJob".to_string(),
+            serde_json::json!({}),
+        );
+        let queue_key = client.key(format!("{QUEUE_KEY_PREFIX}default"));
+        let _: () = conn
+            .rpush(&queue_key, job.encode().unwrap())
+            .await
+            .expect("push job");
+
+        convert_list_queues(&client).await.expect("convert queues");
+
+        let queues = vec!["default".to_string()];
+        let (dequeued, _) = dequeue(&client, &queues, &[])
+            .await
+            .expect("dequeue")
+            .expect("converted job");
+        assert_eq!(dequeued.id, "01J");
+    }
+
     #[tokio::test]
     async fn test_can_clear_redis() {
         // Setup Redis directly with testcontainer
@@ -1128,11 +2218,16 @@ mod tests {
 
         // Test enqueue
         let args = serde_json::json!({"user_id": 42});
-        assert!(
-            enqueue(&client, "PasswordReset".to_string(), None, args, None)
-                .await
-                .is_ok()
-        );
+        assert!(enqueue(
+            &client,
+            "PasswordReset".to_string(),
+            None,
+            args,
+            None,
+            Codec::Json
+        )
+        .await
+        .is_ok());
 
         // Verify job was created
         let jobs = get_all_jobs(&client).await;
@@ -1144,6 +2239,72 @@ mod tests {
         assert_eq!(job.data, serde_json::json!({"user_id": 42}));
     }
 
+    #[tokio::test]
+    async fn test_can_enqueue_batch_redis() {
+        let (client, _container) = setup_redis().await;
+
+        let args = (0..5).map(|i| serde_json::json!({"user_id": i})).collect();
+        let job_ids = enqueue_batch(
+            &client,
+            "NotifySubscriber".to_string(),
+            Some("mailer".to_string()),
+            args,
+            Some(vec!["email".to_string()]),
+            Codec::Json,
+            2,
+        )
+        .await
+        .expect("enqueue batch");
+        assert_eq!(job_ids.len(), 5);
+
+        // Jobs are queued in order
+        let mut conn = get_connection(&client).await.expect("get connection");
+        let queued: Vec<String> = conn
+            .zrange(client.key(format!("{QUEUE_KEY_PREFIX}mailer")), 0, -1)
+            .await
+            .expect("get queue");
+        let queued_ids: Vec<String> = queued
+            .iter()
+            .map(|json| Job::decode(json).expect("parse job").id)
+            .collect();
+        assert_eq!(queued_ids, job_ids);
+
+        let jobs = get_all_jobs(&client).await;
+        assert_eq!(jobs.len(), 5);
+        assert!(jobs.iter().all(
+            |job| job.name == "NotifySubscriber" && job.tags == Some(vec!["email".to_string()])
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_can_set_and_get_job_result_redis() {
+        let (client, _container) = setup_redis().await;
+
+        assert!(get_job_result(&client, "job-1")
+            .await
+            .expect("get job result")
+            .is_none());
+
+        let result = serde_json::json!({"url": "https://example.com/report.pdf"});
+        set_job_result(&client, "job-1", &result, 60)
+            .await
+            .expect("set job result");
+        assert_eq!(
+            get_job_result(&client, "job-1")
+                .await
+                .expect("get job result"),
+            Some(result)
+        );
+
+        // Results expire after the TTL
+        let mut conn = get_connection(&client).await.expect("get connection");
+        let ttl: i64 = conn
+            .ttl(client.key(format!("{RESULT_KEY_PREFIX}job-1")))
+            .await
+            .expect("get ttl");
+        assert!(ttl > 0 && ttl <= 60);
+    }
+
     #[tokio::test]
     async fn test_can_enqueue_with_queue_redis() {
         // Setup Redis directly with testcontainer
@@ -1156,15 +2317,16 @@ mod tests {
             "EmailNotification".to_string(),
             Some("mailer".to_string()),
             args,
-            None
+            None,
+            Codec::Json
         )
         .await
         .is_ok());
 
         // Verify job was created in correct queue first
         let mut conn = get_connection(&client).await.expect("get connection");
-        let queue_key = format!("{QUEUE_KEY_PREFIX}mailer");
-        let queue_len: i64 = conn.llen(&queue_key).await.expect("get queue length");
+        let queue_key = client.key(format!("{QUEUE_KEY_PREFIX}mailer"));
+        let queue_len: i64 = conn.zcard(&queue_key).await.expect("get queue length");
         assert_eq!(queue_len, 1);
 
         // Test dequeue from mailer queue
@@ -1172,7 +2334,7 @@ mod tests {
         let _job_opt = dequeue(&client, &queues, &[]).await.expect("dequeue");
 
         // Queue should now be empty
-        let queue_len: i64 = conn.llen(&queue_key).await.expect("get queue length");
+        let queue_len: i64 = conn.zcard(&queue_key).await.expect("get queue length");
         assert_eq!(queue_len, 0);
     }
 
@@ -1184,9 +2346,16 @@ mod tests {
 
         // Add job
         let args = serde_json::json!({"task": "test"});
-        assert!(enqueue(&client, "TestJob".to_string(), None, args, None)
-            .await
-            .is_ok());
+        assert!(enqueue(
+            &client,
+            "TestJob".to_string(),
+            None,
+            args,
+            None,
+            Codec::Json
+        )
+        .await
+        .is_ok());
 
         // Dequeue job
         let queues = vec!["default".to_string()];
@@ -1198,7 +2367,7 @@ mod tests {
 
         // Verify job is not in processing set
         let mut conn = get_connection(&client).await.expect("get connection");
-        let processing_key = format!("{PROCESSING_KEY_PREFIX}{queue}");
+        let processing_key = client.key(format!("{PROCESSING_KEY_PREFIX}{queue}"));
         let is_member: bool = conn
             .sismember(&processing_key, &job.id)
             .await
@@ -1206,9 +2375,9 @@ mod tests {
         assert!(!is_member);
 
         // Verify job status is updated to Completed
-        let job_key = String::from(JOB_KEY_PREFIX) + &job.id;
+        let job_key = client.key(format!("{JOB_KEY_PREFIX}{}", job.id));
         let job_json: String = conn.get(&job_key).await.expect("get job");
-        let completed_job = Job::from_json(&job_json).expect("parse job");
+        let completed_job = Job::decode(&job_json).expect("parse job");
         assert_eq!(
             completed_job.status,
             JobStatus::Completed,
@@ -1223,11 +2392,16 @@ mod tests {
 
         // Add job
         let args = serde_json::json!({"task": "recurring"});
-        assert!(
-            enqueue(&client, "RecurringJob".to_string(), None, args, None)
-                .await
-                .is_ok()
-        );
+        assert!(enqueue(
+            &client,
+            "RecurringJob".to_string(),
+            None,
+            args,
+            None,
+            Codec::Json
+        )
+        .await
+        .is_ok());
 
         // Dequeue job
         let queues = vec!["default".to_string()];
@@ -1241,16 +2415,16 @@ mod tests {
 
         // Verify job is back in queue
         let mut conn = get_connection(&client).await.expect("get connection");
-        let queue_key = format!("{QUEUE_KEY_PREFIX}{queue}");
-        let queue_len: i64 = conn.llen(&queue_key).await.expect("get queue length");
+        let queue_key = client.key(format!("{QUEUE_KEY_PREFIX}{queue}"));
+        let queue_len: i64 = conn.zcard(&queue_key).await.expect("get queue length");
         assert_eq!(queue_len, 1);
 
         // Verify job has future run_at time
         let queue_jobs: Vec<String> = conn
-            .lrange(&queue_key, 0, -1)
+            .zrange(&queue_key, 0, -1)
             .await
             .expect("get queue jobs");
-        let requeued_job = Job::from_json(&queue_jobs[0]).expect("parse job");
+        let requeued_job = Job::decode(&queue_jobs[0]).expect("parse job");
         assert!(requeued_job.run_at > Utc::now());
     }
 
@@ -1261,9 +2435,16 @@ mod tests {
 
         // Add job
         let args = serde_json::json!({"task": "test"});
-        assert!(enqueue(&client, "TestJob".to_string(), None, args, None)
-            .await
-            .is_ok());
+        assert!(enqueue(
+            &client,
+            "TestJob".to_string(),
+            None,
+            args,
+            None,
+            Codec::Json
+        )
+        .await
+        .is_ok());
 
         // Dequeue job
         let queues = vec!["default".to_string()];
@@ -1276,7 +2457,7 @@ mod tests {
 
         // Verify job is not in processing set
         let mut conn = get_connection(&client).await.expect("get connection");
-        let processing_key = format!("{PROCESSING_KEY_PREFIX}{queue}");
+        let processing_key = client.key(format!("{PROCESSING_KEY_PREFIX}{queue}"));
         let is_member: bool = conn
             .sismember(&processing_key, &job.id)
             .await
@@ -1284,11 +2465,47 @@ mod tests {
         assert!(!is_member);
 
         // Verify job has error data
-        let job_key = String::from(JOB_KEY_PREFIX) + &job.id;
+        let job_key = client.key(format!("{JOB_KEY_PREFIX}{}", job.id));
         let job_json: String = conn.get(&job_key).await.expect("get job");
-        let failed_job = Job::from_json(&job_json).expect("parse job");
+        let failed_job = Job::decode(&job_json).expect("parse job");
         assert_eq!(failed_job.status, JobStatus::Failed);
-        assert!(failed_job.data.get("error").is_some());
+        assert_eq!(failed_job.error.as_deref(), Some("test failure"));
+        assert_eq!(failed_job.data, serde_json::json!({"task": "test"}));
+    }
+
+    #[tokio::test]
+    async fn can_retry_failed_jobs_with_any_args() {
+        let (client, _container) = setup_redis().await;
+
+        for args in [serde_json::json!(42), serde_json::json!(["a", "b"])] {
+            enqueue(
+                &client,
+                "TestJob".to_string(),
+                None,
+                args.clone(),
+                None,
+                Codec::Json,
+            )
+            .await
+            .expect("enqueue");
+            let queues = vec!["default".to_string()];
+            let (job, queue) = dequeue(&client, &queues, &[])
+                .await
+                .expect("dequeue")
+                .unwrap();
+            fail_job(&client, &job.id, &queue, &Error::string("boom"))
+                .await
+                .expect("fail job");
+
+            assert!(retry_job(&client, &job.id).await.expect("retry job"));
+            let (retried, _) = dequeue(&client, &queues, &[])
+                .await
+                .expect("dequeue")
+                .unwrap();
+            assert_eq!(retried.id, job.id);
+            assert_eq!(retried.data, args);
+            assert_eq!(retried.error, None);
+        }
     }
 
     #[tokio::test]
@@ -1360,15 +2577,27 @@ mod tests {
 
         // Add job
         let args = serde_json::json!("test args");
-        assert!(enqueue(&client, "TestJob".to_string(), None, args, None)
-            .await
-            .is_ok());
+        assert!(enqueue(
+            &client,
+            "TestJob".to_string(),
+            None,
+            args,
+            None,
+            Codec::Json
+        )
+        .await
+        .is_ok());
 
         // Run registry with worker for a short time
         let opts = RunOpts {
             num_workers: 1,
             poll_interval_sec: 1,
             queues: None,
+            processed_queues: None,
+            batch_chunk_size: 100,
+            result_ttl_sec: 60,
+            codec: Codec::Json,
+            limits: BTreeMap::new(),
         };
 
         let token = CancellationToken::new();
@@ -1399,7 +2628,8 @@ mod tests {
             "TaggedJob".to_string(),
             Some("default".to_string()),
             args1,
-            Some(vec!["tag1".to_string(), "common".to_string()])
+            Some(vec!["tag1".to_string(), "common".to_string()]),
+            Codec::Json
         )
         .await
         .is_ok());
@@ -1410,7 +2640,8 @@ mod tests {
             "TaggedJob".to_string(),
             Some("default".to_string()),
             args2,
-            Some(vec!["tag2".to_string(), "common".to_string()])
+            Some(vec!["tag2".to_string(), "common".to_string()]),
+            Codec::Json
         )
         .await
         .is_ok());
@@ -1421,7 +2652,8 @@ mod tests {
             "TaggedJob".to_string(),
             Some("default".to_string()),
             args3,
-            Some(vec!["tag3".to_string()])
+            Some(vec!["tag3".to_string()]),
+            Codec::Json
         )
         .await
         .is_ok());
@@ -1521,6 +2753,10 @@ mod tests {
             created_at: Some(Utc::now() - chrono::Duration::days(15)),
             updated_at: Some(Utc::now() - chrono::Duration::days(15)),
             tags: None,
+            codec: Codec::Json,
+            priority: 0,
+            unique_key: None,
+            error: None,
         };
 
         // Create an old completed job (older than 10 days)
@@ -1534,16 +2770,20 @@ mod tests {
             created_at: Some(Utc::now() - chrono::Duration::days(15)),
             updated_at: Some(Utc::now() - chrono::Duration::days(15)),
             tags: None,
+            codec: Codec::Json,
+            priority: 0,
+            unique_key: None,
+            error: None,
         };
 
         // Store both jobs directly
-        let old_failed_job_json = old_failed_job.to_json().expect("serialize old failed job");
+        let old_failed_job_json = old_failed_job.encode().expect("serialize old failed job");
         let old_completed_job_json = old_completed_job
-            .to_json()
+            .encode()
             .expect("serialize old completed job");
 
-        let old_failed_job_key = String::from(JOB_KEY_PREFIX) + &old_failed_job.id;
-        let old_completed_job_key = String::from(JOB_KEY_PREFIX) + &old_completed_job.id;
+        let old_failed_job_key = client.key(format!("{JOB_KEY_PREFIX}{}", old_failed_job.id));
+        let old_completed_job_key = client.key(format!("{JOB_KEY_PREFIX}{}", old_completed_job.id));
 
         let _: () = conn
             .set(&old_failed_job_key, &old_failed_job_json)
@@ -1607,4 +2847,94 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_processed_queues() {
+        let mut opts = RunOpts {
+            num_workers: 1,
+            poll_interval_sec: 1,
+            queues: Some(vec!["reports".to_string()]),
+            processed_queues: None,
+            batch_chunk_size: 100,
+            result_ttl_sec: 60,
+            codec: Codec::Json,
+            limits: BTreeMap::new(),
+        };
+        assert_eq!(
+            processed_queues(&opts),
+            vec!["default", "mailer", "reports"]
+        );
+
+        opts.processed_queues = Some(vec!["default".to_string()]);
+        assert_eq!(processed_queues(&opts), vec!["default"]);
+    }
+
+    #[test]
+    fn test_unknown_queues() {
+        let opts = RunOpts {
+            num_workers: 1,
+            poll_interval_sec: 1,
+            queues: Some(vec!["reports".to_string()]),
+            processed_queues: Some(vec!["default".to_string(), "report".to_string()]),
+            batch_chunk_size: 100,
+            result_ttl_sec: 60,
+            codec: Codec::Json,
+            limits: BTreeMap::new(),
+        };
+        let worker_queues = HashMap::from([
+            ("Mailer".to_string(), "mailer".to_string()),
+            ("Reporter".to_string(), "reports".to_string()),
+            ("Exporter".to_string(), "exports".to_string()),
+        ]);
+        assert_eq!(
+            unknown_queues(&opts, &worker_queues),
+            vec![
+                "worker `Exporter` pushes to queue `exports`, missing from `queue.queues`",
+                "`workers.queues` selects queue `report`, missing from `queue.queues`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_job_codecs() {
+        let mut job = Job::new(
+            "job1".to_string(),
+            "TestJob".to_string(),
+            serde_json::json!({"user_id": 42, "tags": ["a", "b"]}),
+        );
+        let json = job.encode().expect("encode json");
+        assert_eq!(json.first(), Some(&b'{'));
+        let decoded = Job::decode(&json).expect("decode json");
+        assert_eq!(decoded.codec, Codec::Json);
+        assert_eq!(decoded.data, job.data);
+
+        // jobs enqueued before the codec was recorded are JSON
+        let legacy = serde_json::json!({
+            "id": "job0",
+            "name": "TestJob",
+            "task_data": {"user_id": 1},
+            "status": "queued",
+            "run_at": "2025-01-01T00:00:00Z",
+            "interval": null,
+            "created_at": null,
+            "updated_at": null,
+            "tags": null,
+        });
+        let legacy = Job::decode(legacy.to_string()).expect("decode legacy job");
+        assert_eq!(legacy.codec, Codec::Json);
+
+        job.codec = Codec::MsgPack;
+        #[cfg(feature = "msgpack")]
+        {
+            let msgpack = job.encode().expect("encode msgpack");
+            assert!(msgpack.len() < json.len());
+            let decoded = Job::decode(&msgpack).expect("decode msgpack");
+            assert_eq!(decoded.codec, Codec::MsgPack);
+            assert_eq!(decoded.status, JobStatus::Queued);
+            assert_eq!(decoded.data, job.data);
+            assert_eq!(decoded.run_at, job.run_at);
+        }
+        #[cfg(not(feature = "msgpack"))]
+        assert!(job.encode().is_err());
+    }
 }
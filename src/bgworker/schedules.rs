@@ -0,0 +1,361 @@
+//! # Runtime Schedules
+//!
+//! Jobs scheduled while the app runs, such as reminders set by users, on top
+//! of the jobs of the scheduler configuration. Schedules are kept by the
+//! queue provider, so they survive restarts, and the scheduler process
+//! enqueues their jobs when they are due:
+//!
+//! ```rust,ignore
+//! let schedule = ctx
+//!     .scheduler()
+//!     .schedule::<ReminderWorker, _>("water the plants", "0 0 9 * * Mon", ReminderArgs { user_id })
+//!     .await?;
+//! let next_run_at = schedule.next_run_at;
+//!
+//! ctx.scheduler().unschedule(&schedule.id).await?;
+//! ```
+//!
+//! A schedule is either a cron expression, or an English schedule like
+//! `every 5 minutes`, for jobs repeating until unscheduled, or a time, for a
+//! single job. Their jobs always go through the queue, whatever the
+//! `workers.mode`.
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use super::{BackgroundWorker, Queue};
+use crate::{app::AppContext, scheduler, Error, Result};
+
+/// How often the scheduler process looks for due schedules.
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// When the jobs of a schedule run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum When {
+    /// Repeatedly, on a cron expression or an English schedule
+    Cron(String),
+    /// Once, at this time
+    At(DateTime<Utc>),
+}
+
+impl From<&str> for When {
+    fn from(cron: &str) -> Self {
+        Self::Cron(cron.to_string())
+    }
+}
+
+impl From<String> for When {
+    fn from(cron: String) -> Self {
+        Self::Cron(cron)
+    }
+}
+
+impl From<DateTime<Utc>> for When {
+    fn from(at: DateTime<Utc>) -> Self {
+        Self::At(at)
+    }
+}
+
+/// A job scheduled at runtime.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: String,
+    /// A name for the schedule, shown in logs
+    pub name: String,
+    /// The class name of the worker performing the jobs
+    pub worker: String,
+    pub queue: Option<String>,
+    pub tags: Option<Vec<String>>,
+    /// The cron expression of a repeating schedule, `None` for a single job
+    pub cron: Option<String>,
+    pub args: serde_json::Value,
+    /// When the next job is enqueued
+    pub next_run_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Schedule {
+    /// Returns when the job after the one due at `now` runs, or `None` for a
+    /// single job.
+    ///
+    /// # Errors
+    ///
+    /// When the cron expression is invalid
+    pub fn next_after(&self, now: DateTime<Utc>) -> Result<Option<DateTime<Utc>>> {
+        self.cron
+            .as_deref()
+            .map(|cron| next_run(cron, now))
+            .transpose()
+    }
+}
+
+/// Returns the first time after `now` matching `cron`.
+fn next_run(cron: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    let invalid = |error: String| {
+        Error::Scheduler(scheduler::Error::InvalidCronSyntax {
+            cron: cron.to_string(),
+            error,
+        })
+    };
+    let schedule = cron::Schedule::from_str(cron).map_err(|err| invalid(err.to_string()))?;
+    let next = schedule
+        .after(&now)
+        .next()
+        .ok_or_else(|| invalid("the schedule never runs again".to_string()))?;
+    Ok(truncate_millis(next))
+}
+
+/// Truncates `at` to the milliseconds kept by the queue providers.
+fn truncate_millis(at: DateTime<Utc>) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(at.timestamp_millis()).unwrap_or(at)
+}
+
+/// The runtime schedules of an app, see [`AppContext::scheduler`].
+pub struct Schedules<'a> {
+    ctx: &'a AppContext,
+}
+
+impl<'a> Schedules<'a> {
+    #[must_use]
+    pub const fn new(ctx: &'a AppContext) -> Self {
+        Self { ctx }
+    }
+
+    fn queue(&self) -> Result<&Queue> {
+        self.ctx
+            .queue_provider
+            .as_deref()
+            .ok_or(Error::QueueProviderMissing)
+    }
+
+    /// Schedules jobs of the worker `W` with `args`, repeating on a cron
+    /// expression or an English schedule, or once at a time.
+    ///
+    /// # Errors
+    ///
+    /// When no queue provider is configured, the schedule is invalid or in
+    /// the past, or it could not be saved
+    pub async fn schedule<W, A>(
+        &self,
+        name: &str,
+        when: impl Into<When>,
+        args: A,
+    ) -> Result<Schedule>
+    where
+        W: BackgroundWorker<A>,
+        A: Serialize + Send + Sync + 'static,
+    {
+        let now = self.ctx.now();
+        let (cron, next_run_at) = match when.into() {
+            When::Cron(cron) => {
+                let cron = scheduler::cron_syntax(&cron)?;
+                let next_run_at = next_run(&cron, now)?;
+                (Some(cron), next_run_at)
+            }
+            When::At(at) if at < now => {
+                return Err(Error::Message(format!(
+                    "cannot schedule `{name}` at {at}, in the past"
+                )));
+            }
+            When::At(at) => (None, truncate_millis(at)),
+        };
+        let tags = W::tags();
+        let schedule = Schedule {
+            id: crate::id::generate(),
+            name: name.to_string(),
+            worker: W::class_name(),
+            queue: W::queue(),
+            tags: if tags.is_empty() { None } else { Some(tags) },
+            cron,
+            args: serde_json::to_value(args)?,
+            next_run_at,
+            created_at: now,
+        };
+        self.queue()?.add_schedule(&schedule).await?;
+        tracing::info!(
+            schedule = schedule.name,
+            id = schedule.id,
+            next_run_at = %schedule.next_run_at,
+            "scheduled job"
+        );
+        Ok(schedule)
+    }
+
+    /// Removes the schedule `id`, returning whether it existed.
+    ///
+    /// # Errors
+    ///
+    /// When no queue provider is configured, or the schedule could not be
+    /// removed
+    pub async fn unschedule(&self, id: &str) -> Result<bool> {
+        self.queue()?.remove_schedule(id).await
+    }
+
+    /// Returns the schedules, the next to run first.
+    ///
+    /// # Errors
+    ///
+    /// When no queue provider is configured, or the schedules could not be
+    /// read
+    pub async fn list(&self) -> Result<Vec<Schedule>> {
+        let mut schedules = self.queue()?.get_schedules().await?;
+        schedules.sort_by(|a, b| {
+            a.next_run_at
+                .cmp(&b.next_run_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(schedules)
+    }
+
+    /// Returns the schedule `id`.
+    ///
+    /// # Errors
+    ///
+    /// As [`Schedules::list`]
+    pub async fn get(&self, id: &str) -> Result<Option<Schedule>> {
+        Ok(self
+            .queue()?
+            .get_schedules()
+            .await?
+            .into_iter()
+            .find(|schedule| schedule.id == id))
+    }
+
+    /// Returns when the next job of the schedule `id` runs, `None` when there
+    /// is no such schedule.
+    ///
+    /// # Errors
+    ///
+    /// As [`Schedules::list`]
+    pub async fn next_run_at(&self, id: &str) -> Result<Option<DateTime<Utc>>> {
+        Ok(self.get(id).await?.map(|schedule| schedule.next_run_at))
+    }
+
+    /// Enqueues the jobs of the due schedules, and returns how many were
+    /// enqueued. A repeating schedule missed for a while runs once, then
+    /// resumes at its next time.
+    ///
+    /// Each due job is claimed before it is enqueued, so that several
+    /// scheduler processes enqueue it once.
+    ///
+    /// # Errors
+    ///
+    /// When no queue provider is configured, or the schedules could not be
+    /// read
+    pub async fn enqueue_due(&self) -> Result<usize> {
+        let queue = self.queue()?;
+        let now = self.ctx.now();
+        let mut enqueued = 0;
+        for schedule in queue.get_schedules().await? {
+            if schedule.next_run_at > now {
+                continue;
+            }
+            let next_run_at = match schedule.next_after(now) {
+                Ok(next_run_at) => next_run_at,
+                Err(err) => {
+                    tracing::error!(
+                        schedule = schedule.name,
+                        id = schedule.id,
+                        err = err.to_string(),
+                        "invalid schedule, skipping"
+                    );
+                    continue;
+                }
+            };
+            if !queue.claim_schedule(&schedule, next_run_at).await? {
+                continue;
+            }
+            let result = queue
+                .enqueue(
+                    schedule.worker.clone(),
+                    schedule.queue.clone(),
+                    schedule.args.clone(),
+                    schedule.tags.clone(),
+                )
+                .await;
+            match result {
+                Ok(_) => {
+                    tracing::info!(
+                        schedule = schedule.name,
+                        id = schedule.id,
+                        worker = schedule.worker,
+                        "enqueued scheduled job"
+                    );
+                    enqueued += 1;
+                }
+                Err(err) => tracing::error!(
+                    schedule = schedule.name,
+                    id = schedule.id,
+                    err = err.to_string(),
+                    "could not enqueue scheduled job"
+                ),
+            }
+        }
+        Ok(enqueued)
+    }
+}
+
+/// Enqueues the jobs of the runtime schedules when they are due, until
+/// `token` is cancelled. Schedules added or removed meanwhile are picked up
+/// on the next poll.
+pub async fn run(ctx: AppContext, token: CancellationToken) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        tokio::select! {
+            () = token.cancelled() => return,
+            _ = interval.tick() => {}
+        }
+        if let Err(err) = Schedules::new(&ctx).enqueue_due().await {
+            tracing::error!(
+                err = err.to_string(),
+                "could not enqueue the scheduled jobs"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn schedule(cron: Option<&str>) -> Schedule {
+        Schedule {
+            id: "01J".to_string(),
+            name: "reminder".to_string(),
+            worker: "ReminderWorker".to_string(),
+            queue: None,
+            tags: None,
+            cron: cron.map(ToString::to_string),
+            args: serde_json::json!({ "user_id": 1 }),
+            next_run_at: Utc.with_ymd_and_hms(2025, 1, 1, 9, 0, 0).unwrap(),
+            created_at: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn can_compute_next_run() {
+        // a run missed for days resumes at the next time, without catching up
+        let now = Utc.with_ymd_and_hms(2025, 1, 3, 10, 30, 0).unwrap();
+        assert_eq!(
+            schedule(Some("0 0 9 * * *")).next_after(now).unwrap(),
+            Some(Utc.with_ymd_and_hms(2025, 1, 4, 9, 0, 0).unwrap())
+        );
+        assert_eq!(schedule(None).next_after(now).unwrap(), None);
+        assert!(schedule(Some("not a cron")).next_after(now).is_err());
+    }
+
+    #[test]
+    fn can_convert_schedules() {
+        let at = Utc.with_ymd_and_hms(2025, 1, 1, 9, 0, 0).unwrap();
+        assert_eq!(When::from(at), When::At(at));
+        assert_eq!(
+            When::from("every 5 minutes"),
+            When::Cron("every 5 minutes".to_string())
+        );
+    }
+}
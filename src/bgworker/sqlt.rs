@@ -1,13 +1,23 @@
 /// `SQLite` based background job queue provider
 use std::{
-    collections::HashMap, future::Future, panic::AssertUnwindSafe, pin::Pin, sync::Arc,
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    pin::Pin,
+    sync::Arc,
     time::Duration,
 };
 
-use super::{BackgroundWorker, JobStatus, Queue};
-use crate::{config::SqliteQueueConfig, Error, Result};
+use super::{
+    chain::{self, QueueRef},
+    middleware::{JobInfo, JobMiddleware, JobMiddlewares},
+    schedules::Schedule,
+    BackgroundWorker, DrainReport, JobStatus, Queue,
+};
+use crate::{
+    config::{QueueLimit, SqliteQueueConfig},
+    Error, Result,
+};
 use chrono::{DateTime, Utc};
-use futures_util::FutureExt;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 pub use sqlx::SqlitePool;
@@ -18,8 +28,7 @@ use sqlx::{
 use std::fmt::Write;
 use tokio::{task::JoinHandle, time::sleep};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, trace};
-use ulid::Ulid;
+use tracing::{debug, error, trace, Instrument};
 type JobId = String;
 type JobData = JsonValue;
 
@@ -48,6 +57,9 @@ pub struct Job {
 
 pub struct JobRegistry {
     handlers: Arc<HashMap<String, JobHandler>>,
+    middlewares: JobMiddlewares,
+    queue: QueueRef,
+    failures: BTreeMap<String, String>,
 }
 
 impl JobRegistry {
@@ -56,6 +68,9 @@ impl JobRegistry {
     pub fn new() -> Self {
         Self {
             handlers: Arc::new(HashMap::new()),
+            middlewares: JobMiddlewares::default(),
+            queue: QueueRef::default(),
+            failures: BTreeMap::new(),
         }
     }
 
@@ -69,26 +84,43 @@ impl JobRegistry {
         for<'de> Args: Deserialize<'de>,
     {
         let worker = Arc::new(worker);
-        let wrapped_handler = move |_job_id: String, job_data: JobData| {
+        let middlewares = self.middlewares.clone();
+        let queue = self.queue.clone();
+        let handler_name = name.clone();
+        let wrapped_handler = move |job_id: String, job_data: JobData| {
             let w = worker.clone();
 
+            let middlewares = middlewares.clone();
+            let queue = queue.clone();
+            let job = JobInfo {
+                id: job_id.clone(),
+                name: handler_name.clone(),
+            };
+
             Box::pin(async move {
-                let args = serde_json::from_value::<Args>(job_data);
+                let (job_data, span) = super::job_span(&job_id, job_data);
+                let (job_data, context) = crate::request_context::take_job_context(job_data);
+                let (job_data, chain) = chain::take_chain(job_data);
+                let args = serde_json::from_value::<Args>(job_data.clone());
                 match args {
                     Ok(args) => {
-                        // Wrap the perform call in catch_unwind to handle panics
-                        match AssertUnwindSafe(w.perform(args)).catch_unwind().await {
-                            Ok(result) => result,
-                            Err(panic) => {
-                                let panic_msg = panic
-                                    .downcast_ref::<String>()
-                                    .map(String::as_str)
-                                    .or_else(|| panic.downcast_ref::<&str>().copied())
-                                    .unwrap_or("Unknown panic occurred");
-                                error!(error = panic_msg, "Worker panicked during execution");
-                                Err(Error::string(panic_msg))
-                            }
-                        }
+                        let key = W::job_key(&args);
+                        let class = job.name.clone();
+                        // Run the middlewares around the perform call, turning panics into failures
+                        let perform =
+                            async move { super::with_timeout(W::timeout(), w.perform(args)).await };
+                        let perform = super::perform_once(
+                            &queue,
+                            &class,
+                            key,
+                            W::processed_for(),
+                            middlewares.run(job, perform),
+                        );
+                        let perform = chain::perform_queued(&queue, chain, job_data, perform);
+                        let perform = super::with_job_id(job_id, perform);
+                        crate::request_context::propagate(context, perform)
+                            .instrument(span)
+                            .await
                     }
                     Err(err) => Err(err.into()),
                 }
@@ -101,6 +133,32 @@ impl JobRegistry {
         Ok(())
     }
 
+    /// Records a worker which failed to register, with its error.
+    pub fn add_failure(&mut self, name: String, error: String) {
+        self.failures.insert(name, error);
+    }
+
+    /// Returns the workers which failed to register, with their error.
+    #[must_use]
+    pub const fn failures(&self) -> &BTreeMap<String, String> {
+        &self.failures
+    }
+
+    /// Adds a middleware around the jobs of all the workers.
+    ///
+    /// # Errors
+    ///
+    /// Fails if cannot add the middleware
+    pub fn add_middleware(&mut self, middleware: Arc<dyn JobMiddleware>) -> Result<()> {
+        self.middlewares.add(middleware)
+    }
+
+    /// The queue the follow-up jobs and the next steps of chains are enqueued
+    /// to.
+    pub(crate) fn queue_ref(&self) -> QueueRef {
+        self.queue.clone()
+    }
+
     /// Returns a reference to the job handlers.
     #[must_use]
     pub fn handlers(&self) -> &Arc<HashMap<String, JobHandler>> {
@@ -145,39 +203,7 @@ impl JobRegistry {
                     };
 
                     if let Some(job) = job_opt {
-                        debug!(job_id = %job.id, job_name = %job.name, "Processing job");
-                        if let Some(handler) = handlers.get(&job.name) {
-                            match handler(job.id.clone(), job.data.clone()).await {
-                                Ok(()) => {
-                                    if let Err(err) =
-                                        complete_job(&pool, &job.id, job.interval).await
-                                    {
-                                        error!(
-                                            error = %err,
-                                            job_id = %job.id,
-                                            job_name = %job.name,
-                                            "Failed to mark job as completed"
-                                        );
-                                    } else {
-                                        debug!(job_id = %job.id, "Job completed successfully");
-                                    }
-                                }
-                                Err(err) => {
-                                    if let Err(fail_err) = fail_job(&pool, &job.id, &err).await {
-                                        error!(
-                                            error = %fail_err,
-                                            job_id = %job.id,
-                                            job_name = %job.name,
-                                            "Failed to mark job as failed"
-                                        );
-                                    } else {
-                                        debug!(job_id = %job.id, error = %err, "Job execution failed");
-                                    }
-                                }
-                            }
-                        } else {
-                            error!(job_name = %job.name, "No handler registered for job");
-                        }
+                        process_job(&pool, &handlers, &job).await;
                     } else {
                         tokio::select! {
                             biased;
@@ -206,6 +232,66 @@ impl Default for JobRegistry {
     }
 }
 
+/// Runs a dequeued job with its handler, and records its outcome: returns
+/// the status of the job, or `None` when no handler is registered for it.
+async fn process_job(
+    pool: &SqlitePool,
+    handlers: &HashMap<String, JobHandler>,
+    job: &Job,
+) -> Option<JobStatus> {
+    debug!(job_id = %job.id, job_name = %job.name, "Processing job");
+    let Some(handler) = handlers.get(&job.name) else {
+        error!(job_name = %job.name, "No handler registered for job");
+        return None;
+    };
+    match handler(job.id.clone(), job.data.clone()).await {
+        Ok(()) => {
+            if let Err(err) = complete_job(pool, &job.id, job.interval).await {
+                error!(
+                    error = %err,
+                    job_id = %job.id,
+                    job_name = %job.name,
+                    "Failed to mark job as completed"
+                );
+            } else {
+                debug!(job_id = %job.id, "Job completed successfully");
+            }
+            Some(JobStatus::Completed)
+        }
+        Err(err) => {
+            if let Err(fail_err) = fail_job(pool, &job.id, &err).await {
+                error!(
+                    error = %fail_err,
+                    job_id = %job.id,
+                    job_name = %job.name,
+                    "Failed to mark job as failed"
+                );
+            } else {
+                debug!(job_id = %job.id, error = %err, "Job execution failed");
+            }
+            Some(JobStatus::Failed)
+        }
+    }
+}
+
+/// Processes the jobs due now matching `tags`, including the jobs they
+/// enqueue, until there are none left. See [`Queue::drain`].
+///
+/// # Errors
+///
+/// When the jobs could not be dequeued
+pub async fn drain(
+    pool: &SqlitePool,
+    handlers: &HashMap<String, JobHandler>,
+    tags: &[String],
+    report: &mut DrainReport,
+) -> Result<()> {
+    while let Some(job) = dequeue(pool, tags).await? {
+        report.count(process_job(pool, handlers, &job).await);
+    }
+    Ok(())
+}
+
 async fn connect(cfg: &SqliteQueueConfig) -> Result<SqlitePool> {
     let mut conn_opts: SqliteConnectOptions = cfg.uri.parse()?;
     if !cfg.enable_logging {
@@ -250,6 +336,20 @@ pub async fn initialize_database(pool: &SqlitePool) -> Result<()> {
 
             INSERT OR IGNORE INTO sqlt_loco_queue_lock (id, is_locked) VALUES (1, FALSE);
 
+            CREATE TABLE IF NOT EXISTS sqlt_loco_schedules (
+                id TEXT NOT NULL PRIMARY KEY,
+                schedule JSON NOT NULL,
+                next_run_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS sqlt_loco_processed (
+                name TEXT NOT NULL,
+                key TEXT NOT NULL,
+                processed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                expires_at INTEGER NOT NULL,
+                PRIMARY KEY (name, key)
+            );
+
             CREATE INDEX IF NOT EXISTS idx_sqlt_queue_status_run_at ON sqlt_loco_queue(status, run_at);
             ", JobStatus::Queued),
     )
@@ -280,7 +380,7 @@ pub async fn enqueue(
     #[allow(clippy::cast_possible_truncation)]
     let interval_ms: Option<i64> = interval.map(|i| i.as_millis() as i64);
 
-    let id = Ulid::new().to_string();
+    let id = crate::id::generate();
     debug!(job_id = %id, job_name = %name, run_at = %run_at, tags = ?tags, "Enqueueing job");
     sqlx::query(
         "INSERT INTO sqlt_loco_queue (id, task_data, name, run_at, interval, tags) VALUES ($1, $2, $3, \
@@ -472,6 +572,129 @@ pub async fn cancel_jobs_by_name(pool: &SqlitePool, name: &str) -> Result<()> {
     Ok(())
 }
 
+/// Whether the job of `name` keyed `key` is in the processed set
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn is_processed(pool: &SqlitePool, name: &str, key: &str) -> Result<bool> {
+    let row = sqlx::query(
+        "SELECT EXISTS (SELECT 1 FROM sqlt_loco_processed WHERE name = ? AND key = ? AND \
+         expires_at > ?)",
+    )
+    .bind(name)
+    .bind(key)
+    .bind(Utc::now().timestamp_millis())
+    .fetch_one(pool)
+    .await?;
+    Ok(row.try_get(0)?)
+}
+
+/// Add the job of `name` keyed `key` to the processed set, for `ttl`, and
+/// drop the expired keys
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn mark_processed(pool: &SqlitePool, name: &str, key: &str, ttl: Duration) -> Result<()> {
+    let now = Utc::now().timestamp_millis();
+    let expires_at = now.saturating_add(i64::try_from(ttl.as_millis()).unwrap_or(i64::MAX));
+    sqlx::query("DELETE FROM sqlt_loco_processed WHERE expires_at <= ?")
+        .bind(now)
+        .execute(pool)
+        .await?;
+    sqlx::query(
+        "INSERT INTO sqlt_loco_processed (name, key, expires_at) VALUES (?, ?, ?) ON CONFLICT \
+         (name, key) DO UPDATE SET processed_at = CURRENT_TIMESTAMP, expires_at = \
+         excluded.expires_at",
+    )
+    .bind(name)
+    .bind(key)
+    .bind(expires_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Save a runtime schedule
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn add_schedule(pool: &SqlitePool, schedule: &Schedule) -> Result<()> {
+    sqlx::query("INSERT INTO sqlt_loco_schedules (id, schedule, next_run_at) VALUES (?, ?, ?)")
+        .bind(&schedule.id)
+        .bind(serde_json::to_value(schedule)?)
+        .bind(schedule.next_run_at.timestamp_millis())
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Remove a runtime schedule, returning whether it existed
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn remove_schedule(pool: &SqlitePool, id: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM sqlt_loco_schedules WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Get the runtime schedules
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn get_schedules(pool: &SqlitePool) -> Result<Vec<Schedule>> {
+    let rows = sqlx::query("SELECT schedule, next_run_at FROM sqlt_loco_schedules")
+        .fetch_all(pool)
+        .await?;
+    rows.iter()
+        .map(|row| {
+            let mut schedule: Schedule = serde_json::from_value(row.try_get("schedule")?)?;
+            let next_run_at: i64 = row.try_get("next_run_at")?;
+            schedule.next_run_at = DateTime::from_timestamp_millis(next_run_at)
+                .ok_or_else(|| Error::string("invalid schedule time"))?;
+            Ok(schedule)
+        })
+        .collect()
+}
+
+/// Claim the job of a schedule due at its `next_run_at`, moving the schedule
+/// to `next_run_at`, or removing it when `None`
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn claim_schedule(
+    pool: &SqlitePool,
+    schedule: &Schedule,
+    next_run_at: Option<DateTime<Utc>>,
+) -> Result<bool> {
+    let due = schedule.next_run_at.timestamp_millis();
+    let result = if let Some(next_run_at) = next_run_at {
+        sqlx::query(
+            "UPDATE sqlt_loco_schedules SET next_run_at = ? WHERE id = ? AND next_run_at = ?",
+        )
+        .bind(next_run_at.timestamp_millis())
+        .bind(&schedule.id)
+        .bind(due)
+        .execute(pool)
+        .await?
+    } else {
+        sqlx::query("DELETE FROM sqlt_loco_schedules WHERE id = ? AND next_run_at = ?")
+            .bind(&schedule.id)
+            .bind(due)
+            .execute(pool)
+            .await?
+    };
+    Ok(result.rows_affected() > 0)
+}
+
 /// Clear all jobs
 ///
 /// # Errors
@@ -491,6 +714,58 @@ pub async fn clear(pool: &SqlitePool) -> Result<()> {
     Ok(())
 }
 
+/// Queues the failed job `id` again, without its error, returning whether
+/// there was such a failed job.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn retry_job(pool: &SqlitePool, id: &str) -> Result<bool> {
+    debug!(job_id = id, "Retrying failed job");
+    let result = sqlx::query(
+        "UPDATE sqlt_loco_queue SET status = $1, run_at = $2, updated_at = $2, task_data = \
+         json_remove(task_data, '$.error') WHERE id = $3 AND status = $4",
+    )
+    .bind(JobStatus::Queued.to_string())
+    .bind(Utc::now())
+    .bind(id)
+    .bind(JobStatus::Failed.to_string())
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Deletes the job `id`, unless it is being processed, returning whether
+/// there was such a job.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn delete_job(pool: &SqlitePool, id: &str) -> Result<bool> {
+    debug!(job_id = id, "Deleting job");
+    let result = sqlx::query("DELETE FROM sqlt_loco_queue WHERE id = $1 AND status != $2")
+        .bind(id)
+        .bind(JobStatus::Processing.to_string())
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Deletes the queued jobs of all the workers, which share a single queue,
+/// returning how many there were.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn purge_all(pool: &SqlitePool) -> Result<usize> {
+    debug!("Purging queued jobs");
+    let result = sqlx::query("DELETE FROM sqlt_loco_queue WHERE status = $1")
+        .bind(JobStatus::Queued.to_string())
+        .execute(pool)
+        .await?;
+    Ok(usize::try_from(result.rows_affected()).unwrap_or(usize::MAX))
+}
+
 /// Deletes jobs from the `sqlt_loco_queue` table based on their status.
 ///
 /// This function removes all jobs with a status that matches any of the statuses provided
@@ -596,6 +871,8 @@ pub async fn ping(pool: &SqlitePool) -> Result<()> {
 pub struct RunOpts {
     pub num_workers: u32,
     pub poll_interval_sec: u32,
+    /// The maximum depth of the queue
+    pub limit: Option<QueueLimit>,
 }
 
 /// Create this provider
@@ -618,11 +895,25 @@ pub async fn create_provider(qcfg: &SqliteQueueConfig) -> Result<Queue> {
         RunOpts {
             num_workers: qcfg.num_workers,
             poll_interval_sec: qcfg.poll_interval_sec,
+            limit: qcfg.limit.clone(),
         },
         token,
     ))
 }
 
+/// Counts the jobs waiting to be processed.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn queued_count(pool: &SqlitePool) -> Result<usize> {
+    let count: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM sqlt_loco_queue WHERE status = 'queued'")
+            .fetch_one(pool)
+            .await?;
+    Ok(usize::try_from(count).unwrap_or_default())
+}
+
 /// Retrieves a list of jobs from the `sqlt_loco_queue` table in the database.
 ///
 /// This function queries the database for jobs, optionally filtering by their
@@ -737,6 +1028,10 @@ mod tests {
     fn reduction() -> &'static [(&'static str, &'static str)] {
         &[
             ("[A-Z0-9]{26}", "<REDACTED>"),
+            (
+                "[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}",
+                "<REDACTED>",
+            ),
             (r"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}Z", "<REDACTED>"),
         ]
     }
@@ -755,6 +1050,7 @@ mod tests {
             idle_timeout: 500,
             poll_interval_sec: 1,
             num_workers: 1,
+            limit: None,
         };
 
         let pool = connect(&qcfg).await.unwrap();
@@ -762,6 +1058,8 @@ mod tests {
             r"
         DROP TABLE IF EXISTS sqlt_loco_queue;
         DROP TABLE IF EXISTS sqlt_loco_queue_lock;
+        DROP TABLE IF EXISTS sqlt_loco_processed;
+        DROP TABLE IF EXISTS sqlt_loco_schedules;
         ",
         )
         .execute(&pool)
@@ -1040,6 +1338,106 @@ mod tests {
         assert_eq!(count_cancelled_jobs, 2);
     }
 
+    #[tokio::test]
+    async fn can_get_queue_stats() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .create()
+            .expect("create temp folder");
+        let pool = init(&tree_fs.root).await;
+
+        assert!(initialize_database(&pool).await.is_ok());
+        tests_cfg::queue::sqlite_seed_data(&pool).await;
+        let jobs = get_all_jobs(&pool).await;
+
+        let queue = Queue::Sqlite(
+            pool,
+            Arc::new(tokio::sync::Mutex::new(JobRegistry::new())),
+            RunOpts {
+                num_workers: 1,
+                poll_interval_sec: 1,
+                limit: None,
+            },
+            tokio_util::sync::CancellationToken::new(),
+        );
+        let stats = queue.stats().await.unwrap().unwrap();
+
+        let count = |status: JobStatus| jobs.iter().filter(|job| job.status == status).count();
+        assert_eq!(stats.queued, count(JobStatus::Queued));
+        assert_eq!(stats.processing, count(JobStatus::Processing));
+        assert_eq!(stats.failed, count(JobStatus::Failed));
+        assert_eq!(
+            stats.queued + stats.processing + stats.completed + stats.failed + stats.cancelled,
+            jobs.len()
+        );
+        assert_eq!(stats.pool.unwrap().size, 1);
+    }
+
+    #[tokio::test]
+    async fn can_remember_processed_jobs() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .create()
+            .expect("create temp folder");
+        let pool = init(&tree_fs.root).await;
+
+        assert!(initialize_database(&pool).await.is_ok());
+
+        assert!(!is_processed(&pool, "ChargeJob", "payment-1").await.unwrap());
+        mark_processed(&pool, "ChargeJob", "payment-1", Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert!(is_processed(&pool, "ChargeJob", "payment-1").await.unwrap());
+        assert!(!is_processed(&pool, "RefundJob", "payment-1").await.unwrap());
+
+        // marking again replaces the expiry, an expired key is forgotten
+        mark_processed(&pool, "ChargeJob", "payment-1", Duration::ZERO)
+            .await
+            .unwrap();
+        assert!(!is_processed(&pool, "ChargeJob", "payment-1").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn can_save_and_claim_schedules() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .create()
+            .expect("create temp folder");
+        let pool = init(&tree_fs.root).await;
+
+        assert!(initialize_database(&pool).await.is_ok());
+
+        let due = Utc.with_ymd_and_hms(2025, 1, 1, 9, 0, 0).unwrap();
+        let schedule = Schedule {
+            id: "01J".to_string(),
+            name: "reminder".to_string(),
+            worker: "ReminderWorker".to_string(),
+            queue: None,
+            tags: None,
+            cron: Some("0 0 9 * * *".to_string()),
+            args: serde_json::json!({ "user_id": 1 }),
+            next_run_at: due,
+            created_at: due,
+        };
+        add_schedule(&pool, &schedule).await.unwrap();
+        assert_eq!(get_schedules(&pool).await.unwrap(), vec![schedule.clone()]);
+
+        // the job due is claimed once
+        let next = due + chrono::Duration::days(1);
+        assert!(claim_schedule(&pool, &schedule, Some(next)).await.unwrap());
+        assert!(!claim_schedule(&pool, &schedule, Some(next)).await.unwrap());
+        let schedules = get_schedules(&pool).await.unwrap();
+        assert_eq!(schedules[0].next_run_at, next);
+
+        // the last job removes the schedule
+        assert!(claim_schedule(&pool, &schedules[0], None).await.unwrap());
+        assert!(get_schedules(&pool).await.unwrap().is_empty());
+
+        add_schedule(&pool, &schedule).await.unwrap();
+        assert!(remove_schedule(&pool, "01J").await.unwrap());
+        assert!(!remove_schedule(&pool, "01J").await.unwrap());
+    }
+
     #[tokio::test]
     async fn can_clear() {
         let tree_fs = tree_fs::TreeBuilder::default()
@@ -1075,6 +1473,44 @@ mod tests {
         assert_eq!(lock_count, 0);
     }
 
+    #[tokio::test]
+    async fn can_retry_delete_and_purge_jobs() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .create()
+            .expect("create temp folder");
+        let pool = init(&tree_fs.root).await;
+
+        assert!(initialize_database(&pool).await.is_ok());
+        tests_cfg::queue::sqlite_seed_data(&pool).await;
+
+        let failed = get_jobs(&pool, Some(&vec![JobStatus::Failed]), None)
+            .await
+            .unwrap();
+        assert_eq!(failed.len(), 2);
+
+        assert!(retry_job(&pool, &failed[0].id).await.unwrap());
+        let retried = get_job(&pool, &failed[0].id).await;
+        assert_eq!(retried.status, JobStatus::Queued);
+        assert!(retried.data.get("error").is_none());
+        // only failed jobs are retried
+        assert!(!retry_job(&pool, &failed[0].id).await.unwrap());
+
+        assert!(delete_job(&pool, &failed[1].id).await.unwrap());
+        assert!(!delete_job(&pool, &failed[1].id).await.unwrap());
+
+        let queued = get_jobs(&pool, Some(&vec![JobStatus::Queued]), None)
+            .await
+            .unwrap();
+        assert_eq!(purge_all(&pool).await.unwrap(), queued.len());
+
+        let jobs = get_all_jobs(&pool).await;
+        assert_eq!(jobs.len(), 14 - 1 - queued.len());
+        assert!(jobs
+            .iter()
+            .all(|j| j.status != JobStatus::Queued && j.status != JobStatus::Failed));
+    }
+
     #[tokio::test]
     async fn can_clear_by_status() {
         let tree_fs = tree_fs::TreeBuilder::default()
@@ -1335,6 +1771,7 @@ mod tests {
         let opts = RunOpts {
             num_workers: 1,
             poll_interval_sec: 1,
+            limit: None,
         };
         let token = CancellationToken::new();
         let handles = registry.run(&pool, &opts, &token, &[]);
@@ -1365,6 +1802,221 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn can_drain_jobs() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .create()
+            .expect("create temp folder");
+        let pool = init(&tree_fs.root).await;
+
+        assert!(initialize_database(&pool).await.is_ok());
+
+        // enqueues a job for each of its remaining steps
+        #[derive(Deserialize, Serialize)]
+        struct Countdown {
+            steps: u32,
+        }
+        struct CountdownWorker(SqlitePool);
+        #[async_trait::async_trait]
+        impl BackgroundWorker<Countdown> for CountdownWorker {
+            fn build(_ctx: &crate::app::AppContext) -> Self {
+                unreachable!()
+            }
+            async fn perform(&self, Countdown { steps }: Countdown) -> crate::Result<()> {
+                if steps == 0 {
+                    return Err(Error::string("no steps left"));
+                }
+                enqueue(
+                    &self.0,
+                    "CountdownJob",
+                    serde_json::json!({ "steps": steps - 1 }),
+                    Utc::now(),
+                    None,
+                    None,
+                )
+                .await?;
+                Ok(())
+            }
+        }
+
+        let mut registry = JobRegistry::new();
+        assert!(registry
+            .register_worker("CountdownJob".to_string(), CountdownWorker(pool.clone()))
+            .is_ok());
+        for (name, data) in [("CountdownJob", 2), ("UnknownJob", 0)] {
+            enqueue(
+                &pool,
+                name,
+                serde_json::json!({ "steps": data }),
+                Utc::now(),
+                None,
+                None,
+            )
+            .await
+            .expect("Failed to enqueue job");
+        }
+        // scheduled jobs are left for later
+        let later = Utc::now() + chrono::Duration::hours(1);
+        let scheduled_id = enqueue(
+            &pool,
+            "CountdownJob",
+            serde_json::json!({ "steps": 1 }),
+            later,
+            None,
+            None,
+        )
+        .await
+        .expect("Failed to enqueue job");
+
+        let mut report = DrainReport::default();
+        drain(&pool, registry.handlers(), &[], &mut report)
+            .await
+            .expect("drain the queue");
+        assert_eq!(
+            report,
+            DrainReport {
+                completed: 2,
+                failed: 1,
+                unhandled: 1,
+                timed_out: false,
+            }
+        );
+        assert_eq!(report.processed(), 3);
+        assert_eq!(
+            get_job(&pool, &scheduled_id).await.status,
+            JobStatus::Queued
+        );
+    }
+
+    #[tokio::test]
+    async fn can_wrap_jobs_with_middleware() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .create()
+            .expect("create temp folder");
+        let pool = init(&tree_fs.root).await;
+
+        assert!(initialize_database(&pool).await.is_ok());
+
+        let job_id = enqueue(
+            &pool,
+            "GuardedJob",
+            serde_json::json!(null),
+            Utc::now(),
+            None,
+            None,
+        )
+        .await
+        .expect("Failed to enqueue job");
+
+        struct GuardedWorker;
+        #[async_trait::async_trait]
+        impl BackgroundWorker<()> for GuardedWorker {
+            fn build(_ctx: &crate::app::AppContext) -> Self {
+                Self
+            }
+            async fn perform(&self, _args: ()) -> crate::Result<()> {
+                Ok(())
+            }
+        }
+
+        let ctx = crate::tests_cfg::app::get_app_context().await;
+        let mut registry = JobRegistry::new();
+        assert!(registry
+            .register_worker("GuardedJob".to_string(), GuardedWorker)
+            .is_ok());
+        // middlewares added after the workers still wrap their jobs
+        let guard = super::super::middleware::from_fn(&ctx, |_ctx, job, _next| async move {
+            Err(Error::string(&format!("{} rejected", job.name)))
+        });
+        assert!(registry.add_middleware(Arc::new(guard)).is_ok());
+
+        let opts = RunOpts {
+            num_workers: 1,
+            poll_interval_sec: 1,
+            limit: None,
+        };
+        let token = CancellationToken::new();
+        let handles = registry.run(&pool, &opts, &token, &[]);
+        sleep(Duration::from_secs(1)).await;
+        for handle in handles {
+            handle.abort();
+        }
+
+        let failed_job = get_job(&pool, &job_id).await;
+        assert_eq!(failed_job.status, JobStatus::Failed);
+        assert_eq!(
+            failed_job.data.get("error").and_then(|v| v.as_str()),
+            Some("GuardedJob rejected")
+        );
+    }
+
+    #[tokio::test]
+    async fn can_time_out_hung_job() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .create()
+            .expect("create temp folder");
+        let pool = init(&tree_fs.root).await;
+
+        assert!(initialize_database(&pool).await.is_ok());
+
+        let job_id = enqueue(
+            &pool,
+            "HungJob",
+            serde_json::json!(null),
+            Utc::now(),
+            None,
+            None,
+        )
+        .await
+        .expect("Failed to enqueue job");
+
+        struct HungWorker;
+        #[async_trait::async_trait]
+        impl BackgroundWorker<()> for HungWorker {
+            fn timeout() -> Option<Duration> {
+                Some(Duration::from_millis(100))
+            }
+            fn build(_ctx: &crate::app::AppContext) -> Self {
+                Self
+            }
+            async fn perform(&self, _args: ()) -> crate::Result<()> {
+                std::future::pending().await
+            }
+        }
+
+        let mut registry = JobRegistry::new();
+        assert!(registry
+            .register_worker("HungJob".to_string(), HungWorker)
+            .is_ok());
+
+        let opts = RunOpts {
+            num_workers: 1,
+            poll_interval_sec: 1,
+            limit: None,
+        };
+        let token = CancellationToken::new();
+        let handles = registry.run(&pool, &opts, &token, &[]);
+
+        sleep(Duration::from_secs(1)).await;
+
+        for handle in handles {
+            handle.abort();
+        }
+
+        let failed_job = get_job(&pool, &job_id).await;
+        assert_eq!(failed_job.status, JobStatus::Failed);
+        let error_msg = failed_job
+            .data
+            .as_object()
+            .and_then(|obj| obj.get("error"))
+            .and_then(|v| v.as_str())
+            .expect("Expected error message in job data");
+        assert_eq!(error_msg, "job timed out after 100ms");
+    }
+
     #[tokio::test]
     async fn can_dequeue_with_tags() {
         let tree_fs = tree_fs::TreeBuilder::default()
@@ -69,4 +69,9 @@ pub trait CacheDriver: Sync + Send {
     /// Returns a [`super::CacheError`] if there is an error during the
     /// operation.
     async fn clear(&self) -> CacheResult<()>;
+
+    /// Returns the connections of the driver pool, for drivers using one.
+    fn pool_stats(&self) -> Option<crate::stats::PoolStats> {
+        None
+    }
 }
@@ -1,6 +1,8 @@
 //! # Redis Cache Driver
 //!
-//! This module implements a cache driver using Redis.
+//! This module implements a cache driver using Redis. Its keys are prefixed
+//! with the `key_prefix` of the configuration, so that several apps or
+//! environments can share one Redis.
 use std::time::Duration;
 
 use async_trait::async_trait;
@@ -31,13 +33,17 @@ pub async fn new(config: &RedisCacheConfig) -> CacheResult<crate::cache::Cache>
         .build(manager)
         .await?;
 
-    Ok(crate::cache::Cache::new(Redis::from(pool)))
+    Ok(crate::cache::Cache::new(Redis::with_key_prefix(
+        pool,
+        config.key_prefix.as_deref().unwrap_or_default(),
+    )))
 }
 
 /// Represents the Redis cache driver.
 #[derive(Clone, Debug)]
 pub struct Redis {
     pool: Pool<RedisConnectionManager>,
+    key_prefix: String,
 }
 
 impl Redis {
@@ -48,7 +54,28 @@ impl Redis {
     /// A boxed [`CacheDriver`] instance.
     #[must_use]
     pub fn from(pool: Pool<RedisConnectionManager>) -> Box<dyn CacheDriver> {
-        Box::new(Self { pool })
+        Self::with_key_prefix(pool, "")
+    }
+
+    /// Constructs a new [`Redis`] instance from a given connection pool,
+    /// prefixing its keys with `key_prefix`.
+    ///
+    /// # Returns
+    ///
+    /// A boxed [`CacheDriver`] instance.
+    #[must_use]
+    pub fn with_key_prefix(
+        pool: Pool<RedisConnectionManager>,
+        key_prefix: &str,
+    ) -> Box<dyn CacheDriver> {
+        Box::new(Self {
+            pool,
+            key_prefix: key_prefix.to_string(),
+        })
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{key}", self.key_prefix)
     }
 }
 
@@ -61,7 +88,7 @@ impl CacheDriver for Redis {
     /// Returns a `CacheError` if there is an error during the operation.
     async fn contains_key(&self, key: &str) -> CacheResult<bool> {
         let mut connection = self.pool.get().await?;
-        Ok(connection.exists(key).await?)
+        Ok(connection.exists(self.key(key)).await?)
     }
 
     /// Retrieves a value from the cache based on the provided key.
@@ -71,7 +98,7 @@ impl CacheDriver for Redis {
     /// Returns a `CacheError` if there is an error during the operation.
     async fn get(&self, key: &str) -> CacheResult<Option<String>> {
         let mut conn = self.pool.get().await?;
-        let result: Option<String> = conn.get(key).await?;
+        let result: Option<String> = conn.get(self.key(key)).await?;
         Ok(result)
     }
 
@@ -82,7 +109,7 @@ impl CacheDriver for Redis {
     /// Returns a `CacheError` if there is an error during the operation.
     async fn insert(&self, key: &str, value: &str) -> CacheResult<()> {
         let mut conn = self.pool.get().await?;
-        conn.set::<_, _, ()>(key, value).await?;
+        conn.set::<_, _, ()>(self.key(key), value).await?;
         Ok(())
     }
 
@@ -101,7 +128,7 @@ impl CacheDriver for Redis {
     ) -> CacheResult<()> {
         let mut conn = self.pool.get().await?;
         // Redis expects the expiry in seconds as a u64
-        conn.set_ex::<_, _, ()>(key, value, duration.as_secs())
+        conn.set_ex::<_, _, ()>(self.key(key), value, duration.as_secs())
             .await?;
         Ok(())
     }
@@ -113,20 +140,49 @@ impl CacheDriver for Redis {
     /// Returns a `CacheError` if there is an error during the operation.
     async fn remove(&self, key: &str) -> CacheResult<()> {
         let mut conn = self.pool.get().await?;
-        conn.del::<_, ()>(key).await?;
+        conn.del::<_, ()>(self.key(key)).await?;
         Ok(())
     }
 
-    /// Clears all key-value pairs from the cache.
+    /// Clears all key-value pairs from the cache: flushes the database, or
+    /// only deletes the keys of the prefix when one is configured.
     ///
     /// # Errors
     ///
     /// Returns a `CacheError` if there is an error during the operation.
     async fn clear(&self) -> CacheResult<()> {
         let mut conn = self.pool.get().await?;
-        cmd("FLUSHDB").query_async::<()>(&mut *conn).await?;
+        if self.key_prefix.is_empty() {
+            cmd("FLUSHDB").query_async::<()>(&mut *conn).await?;
+            return Ok(());
+        }
+        let pattern = format!("{}*", glob_escape(&self.key_prefix));
+        let keys: Vec<String> = cmd("KEYS").arg(pattern).query_async(&mut *conn).await?;
+        for keys in keys.chunks(1000) {
+            conn.del::<_, ()>(keys).await?;
+        }
         Ok(())
     }
+
+    fn pool_stats(&self) -> Option<crate::stats::PoolStats> {
+        let state = self.pool.state();
+        Some(crate::stats::PoolStats::new(
+            state.connections,
+            state.idle_connections,
+        ))
+    }
+}
+
+/// Escapes the `KEYS` pattern special characters of `value`.
+fn glob_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
 }
 
 #[cfg(test)]
@@ -143,6 +199,7 @@ mod tests {
         let redis_config = crate::config::RedisCacheConfig {
             uri: redis_url,
             max_size: 10,
+            key_prefix: None,
         };
 
         let cache = new(&redis_config)
@@ -276,4 +333,27 @@ mod tests {
             .await
             .expect("Failed to check if key exists after expiry"));
     }
+
+    #[tokio::test]
+    async fn test_key_prefix() {
+        let (redis_url, _container) = setup_redis_container().await;
+        let config = |key_prefix: Option<&str>| crate::config::RedisCacheConfig {
+            uri: redis_url.clone(),
+            max_size: 10,
+            key_prefix: key_prefix.map(ToString::to_string),
+        };
+        let app = new(&config(Some("app:"))).await.unwrap().driver;
+        let other = new(&config(Some("other:"))).await.unwrap().driver;
+        let raw = new(&config(None)).await.unwrap().driver;
+
+        app.insert("key", "app").await.unwrap();
+        other.insert("key", "other").await.unwrap();
+        assert_eq!(app.get("key").await.unwrap().as_deref(), Some("app"));
+        assert_eq!(raw.get("app:key").await.unwrap().as_deref(), Some("app"));
+        assert!(!raw.contains_key("key").await.unwrap());
+
+        app.clear().await.unwrap();
+        assert!(!app.contains_key("key").await.unwrap());
+        assert_eq!(other.get("key").await.unwrap().as_deref(), Some("other"));
+    }
 }
@@ -37,10 +37,11 @@ use crate::controller;
 use crate::{
     app::{AppContext, Hooks},
     boot::{
-        create_app, create_context, list_endpoints, list_middlewares, run_scheduler, run_task,
-        start, RunDbCommand, ServeParams, StartMode,
+        check, create_app, create_context, list_endpoints, list_middlewares, run_scheduler,
+        run_task, start, task_context, BootPhase, RunDbCommand, ServeParams, StartMode,
     },
     config::Config,
+    config_report::{self, ConfigReport},
     environment::{resolve_from_env, Environment, DEFAULT_ENVIRONMENT},
     logger, task, Error,
 };
@@ -90,6 +91,19 @@ enum Commands {
         /// disable the banner display
         #[arg(short, long, action = ArgAction::SetTrue)]
         no_banner: bool,
+        /// retry binding the server port, with a backoff, while it is in use,
+        /// for example by the previous instance of the app while it restarts
+        #[arg(long, action = ArgAction::SetTrue)]
+        retry_bind: bool,
+        /// check that the app boots (config, routes, workers and tasks)
+        /// without connecting to anything, then exit
+        #[arg(long, action = ArgAction::SetTrue)]
+        check: bool,
+        /// run the pending migrations, then exit without starting the server,
+        /// the workers or the scheduler
+        #[cfg(feature = "with-db")]
+        #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = &["worker", "server_and_worker", "all", "check"])]
+        migrate: bool,
     },
     #[cfg(feature = "with-db")]
     /// Perform DB operations
@@ -155,6 +169,12 @@ enum Commands {
         #[arg(short, long, action)]
         production: bool,
     },
+    #[cfg(feature = "config_schema")]
+    /// Inspect the configuration format.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
     /// Display the app version
     Version {},
 
@@ -608,6 +628,59 @@ enum JobsCommands {
     },
 }
 
+#[cfg(feature = "config_schema")]
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Writes the JSON Schema of the configuration files, for editor
+    /// autocompletion and validation.
+    Schema {
+        /// File to write the schema to (default: stdout).
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Prints where each configuration value comes from, when
+/// `LOCO_CONFIG_REPORT` is set, with the `--port` and `--binding` flags.
+fn print_config_report(
+    environment: &Environment,
+    config: &Config,
+    port: Option<i32>,
+    binding: Option<&str>,
+) {
+    if !config_report::is_enabled() {
+        return;
+    }
+    match ConfigReport::new(environment, config) {
+        Ok(mut report) => {
+            if let Some(port) = port {
+                report.set_by_flag("server.port", "--port", port);
+            }
+            if let Some(binding) = binding {
+                report.set_by_flag("server.binding", "--binding", binding);
+            }
+            println!("{report}");
+        }
+        Err(err) => tracing::warn!(error = %err, "could not report the configuration sources"),
+    }
+}
+
+#[cfg(feature = "config_schema")]
+fn handle_config_command(command: &ConfigCommands) -> crate::Result<()> {
+    match command {
+        ConfigCommands::Schema { output } => {
+            let schema = serde_json::to_string_pretty(&Config::json_schema())?;
+            if let Some(output) = output {
+                std::fs::write(output, schema)?;
+                println!("config schema written to {}", output.display());
+            } else {
+                println!("{schema}");
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Parse a single key-value pair
 fn parse_key_val<T, U>(
     s: &str,
@@ -675,7 +748,16 @@ pub async fn main<H: Hooks, M: MigratorTrait>() -> crate::Result<()> {
     let cli: Cli = Cli::parse();
     let environment: Environment = cli.environment.unwrap_or_else(resolve_from_env).into();
 
+    let started = std::time::Instant::now();
     let config = H::load_config(&environment).await?;
+    let config_load = BootPhase {
+        name: "config_load".to_string(),
+        duration: started.elapsed(),
+    };
+    if let Commands::Start { check: true, .. } = cli.command {
+        logger::init::<H>(&config.logger)?;
+        return check::<H>(&environment, config).await;
+    }
     let app_context = create_context::<H>(&environment, config).await?;
 
     if !H::init_logger(&app_context)? {
@@ -693,9 +775,14 @@ pub async fn main<H: Hooks, M: MigratorTrait>() -> crate::Result<()> {
             binding,
             port,
             no_banner,
+            retry_bind,
+            migrate,
+            ..
         } => {
             let start_mode = worker.map_or(
-                if server_and_worker {
+                if migrate {
+                    StartMode::Migrate
+                } else if server_and_worker {
                     StartMode::ServerAndWorker
                 } else if all {
                     StartMode::All
@@ -705,19 +792,27 @@ pub async fn main<H: Hooks, M: MigratorTrait>() -> crate::Result<()> {
                 |tags| StartMode::WorkerOnly { tags },
             );
 
-            let boot_result =
+            let mut boot_result =
                 create_app::<H, M>(start_mode, &environment, app_context.config).await?;
+            boot_result.timings.insert(0, config_load);
+            print_config_report(
+                &environment,
+                &boot_result.app_context.config,
+                port,
+                binding.as_deref(),
+            );
             let serve_params = ServeParams {
                 port: port.map_or(boot_result.app_context.config.server.port, |p| p),
                 binding: binding
                     .unwrap_or_else(|| boot_result.app_context.config.server.binding.to_string()),
+                retry_bind,
             };
             start::<H>(boot_result, serve_params, no_banner).await?;
         }
         #[cfg(feature = "with-db")]
         Commands::Db { command } => {
             if matches!(command, DbCommands::Create) {
-                db::create(&app_context.config.database.uri).await?;
+                db::create(&app_context.config.database()?.uri).await?;
             } else {
                 run_db::<H, M>(&app_context, command.into()).await?;
             }
@@ -751,7 +846,7 @@ pub async fn main<H: Hooks, M: MigratorTrait>() -> crate::Result<()> {
         }
         Commands::Task { name, params } => {
             let vars = task::Vars::from_cli_args(params);
-            let app_context = create_context::<H>(&environment, app_context.config).await?;
+            let app_context = task_context::<H>(&environment, app_context.config).await?;
             run_task::<H>(&app_context, name.as_ref(), &vars).await?;
         }
         Commands::Scheduler {
@@ -772,7 +867,8 @@ pub async fn main<H: Hooks, M: MigratorTrait>() -> crate::Result<()> {
             production,
         } => {
             if config_arg {
-                println!("{}", &app_context.config);
+                let redactor = logger::Redactor::new(&app_context.config.logger.redact)?;
+                println!("{}", redactor.redact(&app_context.config.to_string()));
                 println!("Environment: {}", &environment);
             } else {
                 let mut should_exit = false;
@@ -787,6 +883,8 @@ pub async fn main<H: Hooks, M: MigratorTrait>() -> crate::Result<()> {
                 }
             }
         }
+        #[cfg(feature = "config_schema")]
+        Commands::Config { command } => handle_config_command(&command)?,
         Commands::Version {} => {
             println!("{}", H::app_version(),);
         }
@@ -825,7 +923,16 @@ pub async fn main<H: Hooks>() -> crate::Result<()> {
     let cli = Cli::parse();
     let environment: Environment = cli.environment.unwrap_or_else(resolve_from_env).into();
 
+    let started = std::time::Instant::now();
     let config = H::load_config(&environment).await?;
+    let config_load = BootPhase {
+        name: "config_load".to_string(),
+        duration: started.elapsed(),
+    };
+    if let Commands::Start { check: true, .. } = cli.command {
+        logger::init::<H>(&config.logger)?;
+        return check::<H>(&environment, config).await;
+    }
     let app_context = create_context::<H>(&environment, config).await?;
 
     if !H::init_logger(&app_context)? {
@@ -843,6 +950,8 @@ pub async fn main<H: Hooks>() -> crate::Result<()> {
             binding,
             port,
             no_banner,
+            retry_bind,
+            ..
         } => {
             let start_mode = worker.map_or(
                 if server_and_worker {
@@ -855,13 +964,22 @@ pub async fn main<H: Hooks>() -> crate::Result<()> {
                 |tags| StartMode::WorkerOnly { tags },
             );
 
-            let boot_result = create_app::<H>(start_mode, &environment, app_context.config).await?;
+            let mut boot_result =
+                create_app::<H>(start_mode, &environment, app_context.config).await?;
+            boot_result.timings.insert(0, config_load);
+            print_config_report(
+                &environment,
+                &boot_result.app_context.config,
+                port,
+                binding.as_deref(),
+            );
             let serve_params = ServeParams {
                 port: port.map_or(boot_result.app_context.config.server.port, |p| p),
                 binding: binding.map_or(
                     boot_result.app_context.config.server.binding.to_string(),
                     |b| b,
                 ),
+                retry_bind,
             };
             start::<H>(boot_result, serve_params, no_banner).await?;
         }
@@ -886,6 +1004,7 @@ pub async fn main<H: Hooks>() -> crate::Result<()> {
         }
         Commands::Task { name, params } => {
             let vars = task::Vars::from_cli_args(params);
+            let app_context = task_context::<H>(&environment, app_context.config).await?;
             run_task::<H>(&app_context, name.as_ref(), &vars).await?;
         }
         #[cfg(any(feature = "bg_redis", feature = "bg_pg", feature = "bg_sqlt"))]
@@ -904,6 +1023,8 @@ pub async fn main<H: Hooks>() -> crate::Result<()> {
         Commands::Generate { component } => {
             handle_generate_command::<H>(component, &app_context.config)?;
         }
+        #[cfg(feature = "config_schema")]
+        Commands::Config { command } => handle_config_command(&command)?,
         Commands::Version {} => {
             println!("{}", H::app_version(),);
         }
@@ -0,0 +1,140 @@
+//! # Clock
+//!
+//! The source of the current time for the application, available as
+//! [`crate::app::AppContext::now`]. Apps use the system clock, and tests can
+//! swap it for a [`FrozenClock`] to test time-sensitive behavior (token
+//! expiry, timestamps) deterministically, without sleeping. To boot a whole
+//! test app on a frozen clock, use `testing::request::boot_test_with_clock`.
+//!
+//! # Example
+//!
+//! ```rust
+//! use std::sync::Arc;
+//!
+//! use chrono::{Duration, TimeZone, Utc};
+//! use loco_rs::{app::AppContext, clock::FrozenClock};
+//!
+//! let clock = FrozenClock::new(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap());
+//! let ctx = AppContext {
+//!     clock: Arc::new(clock.clone()),
+//!     ..AppContext::mock()
+//! };
+//!
+//! clock.advance(Duration::days(1));
+//! assert_eq!(ctx.now(), Utc.with_ymd_and_hms(2025, 1, 2, 0, 0, 0).unwrap());
+//! ```
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+
+#[cfg(feature = "testing")]
+tokio::task_local! {
+    static BOOT_CLOCK: Arc<dyn Clock>;
+}
+
+/// Returns the clock of an app context being created: the system clock,
+/// unless the app is booted by `testing::request::boot_test_with_clock`.
+pub(crate) fn boot_clock() -> Arc<dyn Clock> {
+    #[cfg(feature = "testing")]
+    if let Ok(clock) = BOOT_CLOCK.try_with(Clone::clone) {
+        return clock;
+    }
+    Arc::new(SystemClock)
+}
+
+/// Runs `boot` with `clock` as the clock of the app contexts it creates.
+#[cfg(feature = "testing")]
+pub(crate) async fn with_boot_clock<F: std::future::Future>(
+    clock: Arc<dyn Clock>,
+    boot: F,
+) -> F::Output {
+    BOOT_CLOCK.scope(clock, boot).await
+}
+
+/// A source of the current time.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system clock, used by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time, so a
+/// test can keep a clone to move the time of the app context it configured.
+#[derive(Debug, Clone)]
+pub struct FrozenClock {
+    now: Arc<Mutex<DateTime<Utc>>>,
+}
+
+impl FrozenClock {
+    #[must_use]
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    /// Sets the current time.
+    ///
+    /// # Panics
+    /// When the clock lock is poisoned
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    /// Moves the current time forward by `duration`.
+    ///
+    /// # Panics
+    /// When the clock lock is poisoned
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for FrozenClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn can_move_frozen_clock() {
+        let clock = FrozenClock::new(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap());
+        let shared = clock.clone();
+
+        clock.advance(Duration::hours(2));
+        assert_eq!(
+            shared.now(),
+            Utc.with_ymd_and_hms(2025, 1, 1, 2, 0, 0).unwrap()
+        );
+
+        clock.set(Utc.with_ymd_and_hms(2030, 6, 1, 0, 0, 0).unwrap());
+        assert_eq!(
+            shared.now(),
+            Utc.with_ymd_and_hms(2030, 6, 1, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn can_boot_with_clock() {
+        let now = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let clock = with_boot_clock(Arc::new(FrozenClock::new(now)), async { boot_clock() }).await;
+        assert_eq!(clock.now(), now);
+
+        assert!(boot_clock().now() > now);
+    }
+}
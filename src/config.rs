@@ -32,23 +32,37 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tracing::info;
 
-use crate::{controller::middleware, environment::Environment, logger, scheduler, Error, Result};
+use crate::{
+    controller::{error_format::ErrorFormat, middleware},
+    environment::Environment,
+    logger, scheduler, Error, Result,
+};
 
 static DEFAULT_FOLDER: OnceLock<PathBuf> = OnceLock::new();
 
 fn get_default_folder() -> &'static PathBuf {
     DEFAULT_FOLDER.get_or_init(|| PathBuf::from("config"))
 }
+
+/// The folder [`Environment::load`] reads the configuration from.
+pub(crate) fn folder() -> PathBuf {
+    crate::env_vars::get(crate::env_vars::CONFIG_FOLDER)
+        .map_or_else(|_| get_default_folder().clone(), PathBuf::from)
+}
 /// Main application configuration structure.
 ///
 /// This struct encapsulates various configuration settings. The configuration
 /// can be customized through YAML files for different environments.
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct Config {
     pub logger: Logger,
     pub server: Server,
+    /// The database, `None` to run the app without one: the
+    /// [`crate::app::AppContext::db`] accessor then returns
+    /// [`Error::DbNotConfigured`].
     #[cfg(feature = "with-db")]
-    pub database: Database,
+    pub database: Option<Database>,
     #[serde(default)]
     pub cache: CacheConfig,
     pub queue: Option<QueueConfig>,
@@ -73,6 +87,26 @@ pub struct Config {
     pub settings: Option<serde_json::Value>,
 
     pub scheduler: Option<scheduler::Config>,
+
+    /// The timeouts of the graceful shutdown stages
+    #[serde(default)]
+    pub shutdown: Shutdown,
+
+    /// The scheme of the request and job ids, see [`crate::id`]
+    #[serde(default)]
+    pub ids: crate::id::IdScheme,
+
+    /// The feature flags, by name, see [`crate::flags`]
+    #[serde(default)]
+    pub flags: BTreeMap<String, crate::flags::FlagRule>,
+
+    /// The shared HTTP client for outbound calls
+    #[serde(default)]
+    pub http_client: HttpClient,
+
+    /// The event bus within the process, see [`crate::events`]
+    #[serde(default)]
+    pub events: Events,
 }
 
 /// Logger configuration
@@ -90,8 +124,12 @@ pub struct Config {
 ///   pretty_backtrace: true
 ///   level: debug
 ///   format: compact
+///   redact:
+///     fields: [password, token]
+///     headers: [authorization, cookie]
 /// ```
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct Logger {
     /// Enable log write to stdout
     pub enable: bool,
@@ -119,9 +157,45 @@ pub struct Logger {
 
     /// Set this if you want to write log to file
     pub file_appender: Option<LoggerFileAppender>,
+
+    /// Export traces to an OpenTelemetry collector, alongside the regular
+    /// logs. Requires the `otel` feature.
+    pub otel: Option<Otel>,
+
+    /// Sensitive values replaced with `***` in the logs, see
+    /// [`logger::Redaction`].
+    #[serde(default)]
+    pub redact: logger::Redaction,
+}
+
+/// OpenTelemetry tracing export configuration
+///
+/// Example (production):
+/// ```yaml
+/// # config/production.yaml
+/// logger:
+///   otel:
+///     enable: true
+///     endpoint: http://otel-collector:4318/v1/traces
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+pub struct Otel {
+    #[serde(default)]
+    pub enable: bool,
+    /// The OTLP/HTTP traces endpoint of the collector.
+    #[serde(default = "default_otel_endpoint")]
+    pub endpoint: String,
+    /// The service name reported with the traces. Defaults to the app name.
+    pub service_name: Option<String>,
+}
+
+fn default_otel_endpoint() -> String {
+    "http://localhost:4318/v1/traces".to_string()
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct LoggerFileAppender {
     /// Enable logger file appender
     pub enable: bool,
@@ -169,15 +243,19 @@ pub struct LoggerFileAppender {
 /// database:
 ///   uri: {{ get_env(name="DATABASE_URL", default="...") }}
 ///   enable_logging: true
+///   slow_query_threshold: 200
 ///   connect_timeout: 500
 ///   idle_timeout: 500
 ///   min_connections: 1
 ///   max_connections: 1
 ///   auto_migrate: true
+///   # or: migrate_on_boot: auto | check | off
 ///   dangerously_truncate: false
 ///   dangerously_recreate: false
+///   destructive_environments: ["development", "test"]
 /// ```
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 #[allow(clippy::struct_excessive_bools)]
 pub struct Database {
     /// The URI for connecting to the database. For example:
@@ -188,6 +266,17 @@ pub struct Database {
     /// Enable `SQLx` statement logging
     pub enable_logging: bool,
 
+    /// Log every query with the request id and route of the request (or job)
+    /// running it, from its [`crate::request_context::RequestContext`].
+    #[serde(default)]
+    pub log_queries: bool,
+
+    /// Queries slower than this, in milliseconds, are logged as warnings with
+    /// the context of the request running them, even when `log_queries` is
+    /// off.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slow_query_threshold: Option<u64>,
+
     /// Minimum number of connections for a pool
     pub min_connections: u32,
 
@@ -205,10 +294,15 @@ pub struct Database {
 
     /// Run migration up when application loads. It is recommended to turn it on
     /// in development. In production keep it off, and explicitly migrate your
-    /// database every time you need.
+    /// database every time you need. Same as `migrate_on_boot: auto`.
     #[serde(default)]
     pub auto_migrate: bool,
 
+    /// What to do about pending migrations when the application loads, see
+    /// [`MigrateOnBoot`]. Takes precedence over `auto_migrate` when set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub migrate_on_boot: Option<MigrateOnBoot>,
+
     /// Truncate database when application loads. It will delete data from your
     /// tables. Commonly used in `test`.
     #[serde(default)]
@@ -221,6 +315,24 @@ pub struct Database {
     #[serde(default)]
     pub dangerously_recreate: bool,
 
+    /// Environments in which destructive operations (`dangerously_truncate`,
+    /// `dangerously_recreate`, `db truncate` and `db seed --reset`) are
+    /// allowed to run. They are always refused in `production`, whatever this
+    /// list contains.
+    #[serde(default = "destructive_environments")]
+    pub destructive_environments: Vec<String>,
+
+    /// Tenants with their own Postgres schema, created and migrated on boot
+    /// along with the main schema, following `migrate_on_boot`. See
+    /// `loco_rs::tenancy`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tenants: Vec<String>,
+
+    /// Recovery of the connections after a failover of the database, see
+    /// [`DatabaseFailover`].
+    #[serde(default)]
+    pub failover: DatabaseFailover,
+
     // Execute query after initializing the DB
     /// for e.g. this can be used to confiure PRAGMAs for `SQLite` where you can pass all values as a string.
     /// Default values are:
@@ -241,8 +353,84 @@ pub struct Database {
     pub run_on_start: Option<String>,
 }
 
-/// Cache configurations for the application
+/// Recovery of the database connections after a failover, when the primary
+/// changes and the connections to the former one break or turn read-only.
+/// See `loco_rs::failover`.
+///
+/// ```yaml
+/// database:
+///   failover:
+///     enable: true
+///     min_backoff: 100
+///     max_backoff: 10000
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+pub struct DatabaseFailover {
+    /// Check the database when a query fails, and reconnect when it is
+    /// unavailable. Enabled by default.
+    #[serde(default = "failover_enable")]
+    pub enable: bool,
+    /// Milliseconds before the first reconnection attempt, doubled after
+    /// each failed one
+    #[serde(default = "failover_min_backoff")]
+    pub min_backoff: u64,
+    /// The longest delay, in milliseconds, between two reconnection attempts
+    #[serde(default = "failover_max_backoff")]
+    pub max_backoff: u64,
+}
+
+const fn failover_enable() -> bool {
+    true
+}
+
+const fn failover_min_backoff() -> u64 {
+    100
+}
+
+const fn failover_max_backoff() -> u64 {
+    10_000
+}
+
+impl Default for DatabaseFailover {
+    fn default() -> Self {
+        Self {
+            enable: failover_enable(),
+            min_backoff: failover_min_backoff(),
+            max_backoff: failover_max_backoff(),
+        }
+    }
+}
+
+/// What to do about pending migrations when the application loads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum MigrateOnBoot {
+    /// Apply the pending migrations.
+    Auto,
+    /// Refuse to boot when migrations are pending, without applying them.
+    /// Useful when migrations run as a separate deploy step.
+    Check,
+    /// Ignore pending migrations.
+    Off,
+}
+
+impl Database {
+    /// Returns the configured [`MigrateOnBoot`] mode, falling back to
+    /// `auto_migrate`.
+    #[must_use]
+    pub fn migrate_on_boot(&self) -> MigrateOnBoot {
+        self.migrate_on_boot.unwrap_or(if self.auto_migrate {
+            MigrateOnBoot::Auto
+        } else {
+            MigrateOnBoot::Off
+        })
+    }
+}
+
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 #[serde(tag = "kind")]
 pub enum CacheConfig {
     #[cfg(feature = "cache_inmem")]
@@ -257,6 +445,7 @@ pub enum CacheConfig {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct InMemCacheConfig {
     #[serde(default = "cache_in_mem_max_capacity")]
     pub max_capacity: u64,
@@ -267,13 +456,19 @@ fn cache_in_mem_max_capacity() -> u64 {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct RedisCacheConfig {
     pub uri: String,
     /// Sets the maximum number of connections managed by the pool.
     pub max_size: u32,
+    /// Prefixes all the keys of the cache, for example `myapp:production:`,
+    /// so that several apps or environments can share one Redis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_prefix: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 #[serde(tag = "kind")]
 pub enum QueueConfig {
     /// Redis queue
@@ -285,6 +480,7 @@ pub enum QueueConfig {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct RedisQueueConfig {
     pub uri: String,
     #[serde(default)]
@@ -296,9 +492,33 @@ pub struct RedisQueueConfig {
 
     #[serde(default = "num_workers")]
     pub num_workers: u32,
+
+    /// Maximum number of jobs sent to Redis in a single pipelined command when
+    /// enqueuing a batch.
+    #[serde(default = "redis_batch_chunk_size")]
+    pub batch_chunk_size: usize,
+
+    /// Time to live, in seconds, of job results stored by workers.
+    #[serde(default = "redis_result_ttl_sec")]
+    pub result_ttl_sec: u64,
+
+    /// The codec jobs are encoded with, unless their worker sets one.
+    #[serde(default)]
+    pub codec: crate::bgworker::Codec,
+
+    /// The maximum depth of queues, by queue name. Queues not listed are
+    /// unbounded.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub limits: BTreeMap<String, QueueLimit>,
+
+    /// Prefixes all the keys of the queue, for example `myapp:production:`,
+    /// so that several apps or environments can share one Redis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_prefix: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct PostgresQueueConfig {
     pub uri: String,
 
@@ -325,9 +545,14 @@ pub struct PostgresQueueConfig {
 
     #[serde(default = "num_workers")]
     pub num_workers: u32,
+
+    /// The maximum depth of the queue, unbounded by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<QueueLimit>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct SqliteQueueConfig {
     pub uri: String,
 
@@ -354,6 +579,62 @@ pub struct SqliteQueueConfig {
 
     #[serde(default = "num_workers")]
     pub num_workers: u32,
+
+    /// The maximum depth of the queue, unbounded by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<QueueLimit>,
+}
+
+/// The maximum depth of a queue: the number of jobs waiting to be processed
+/// it holds before enqueuing more fails with [`Error::QueueFull`], or waits.
+///
+/// ```yaml
+/// queue:
+///   kind: Redis
+///   uri: "redis://127.0.0.1"
+///   limits:
+///     mailer:
+///       max_depth: 10000
+///     reports:
+///       max_depth: 100
+///       when_full: block
+///       block_timeout_ms: 2000
+/// ```
+///
+/// The depth is read before each enqueue, so concurrent producers can
+/// overshoot the limit by the jobs they enqueue at the same time.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+pub struct QueueLimit {
+    pub max_depth: usize,
+
+    #[serde(default)]
+    pub when_full: WhenFull,
+
+    /// How long a blocked enqueue waits for the queue to drain before failing
+    /// with [`Error::QueueFull`].
+    #[serde(default = "queue_block_timeout_ms")]
+    pub block_timeout_ms: u64,
+}
+
+/// What enqueuing to a full queue does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum WhenFull {
+    /// Fail with [`Error::QueueFull`] right away, to shed load.
+    #[default]
+    Reject,
+    /// Wait for the queue to drain, up to `block_timeout_ms`.
+    Block,
+}
+
+fn queue_block_timeout_ms() -> u64 {
+    5000
+}
+
+fn destructive_environments() -> Vec<String> {
+    vec!["development".to_string(), "test".to_string()]
 }
 
 fn db_min_conn() -> u32 {
@@ -384,6 +665,14 @@ fn num_workers() -> u32 {
     2
 }
 
+fn redis_batch_chunk_size() -> usize {
+    500
+}
+
+fn redis_result_ttl_sec() -> u64 {
+    3600
+}
+
 /// User authentication configuration.
 ///
 /// Example (development):
@@ -393,15 +682,85 @@ fn num_workers() -> u32 {
 ///   jwt:
 ///     secret: <your secret>
 ///     expiration: 604800 # 7 days
+///   webhooks:
+///     stripe:
+///       secret: {{ get_env(name="STRIPE_WEBHOOK_SECRET") }}
+///       header: stripe-signature
+///       scheme: stripe
 /// ```
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct Auth {
     /// JWT authentication config
     pub jwt: Option<JWT>,
+    /// Signed webhooks, by name, verified by the
+    /// `controller::extractor::webhook::Webhook` extractor
+    #[serde(default)]
+    pub webhooks: BTreeMap<String, Webhook>,
+}
+
+/// Signed webhook configuration.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+pub struct Webhook {
+    /// The secret the webhook bodies are signed with (HMAC-SHA256)
+    pub secret: String,
+    /// The request header holding the signature
+    pub header: String,
+    /// How the signature and its timestamp are sent
+    #[serde(default)]
+    pub scheme: WebhookScheme,
+    /// With the `hmac` scheme, the request header holding the signing
+    /// timestamp (in seconds), signed as `<timestamp>.<body>`. Without it,
+    /// only the body is signed and replays are not detected.
+    pub timestamp_header: Option<String>,
+    /// How old (in seconds) a signing timestamp can be
+    #[serde(default = "default_webhook_tolerance")]
+    pub tolerance: u64,
+    /// The steps decoding the body into the payload, in order, for providers
+    /// sending encoded, compressed or wrapped payloads. The signature is
+    /// verified over the body as received, before these steps.
+    #[serde(default)]
+    pub decode: Vec<WebhookDecode>,
+}
+
+fn default_webhook_tolerance() -> u64 {
+    300
+}
+
+/// How a webhook signature is sent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookScheme {
+    /// The hex encoded signature, optionally prefixed with `sha256=` (GitHub)
+    #[default]
+    Hmac,
+    /// A `t=<timestamp>,v1=<signature>` header, signed as
+    /// `<timestamp>.<body>` (Stripe)
+    Stripe,
+}
+
+/// A step decoding a webhook body.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookDecode {
+    /// Base64 (standard alphabet, padded or not)
+    Base64,
+    /// gzip decompression
+    Gzip,
+    /// zlib decompression, as the `deflate` content encoding
+    Deflate,
+    /// The JSON value at a `.` separated path of a JSON envelope, such as
+    /// `envelope: message.data`. String values are taken as is, other values
+    /// as JSON.
+    Envelope(String),
 }
 
 /// JWT configuration structure.
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct JWT {
     /// The location where JWT tokens are expected to be found during
     /// authentication.
@@ -417,6 +776,7 @@ pub struct JWT {
 /// This enum represents various ways to authenticate using JSON Web Tokens
 /// (JWT) within middleware.
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 #[serde(tag = "from")]
 pub enum JWTLocation {
     /// Authenticate using a Bearer token.
@@ -452,6 +812,7 @@ pub enum JWTLocation {
 ///       enable: true
 /// ```
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct Server {
     /// The address on which the server should listen on for incoming
     /// connections.
@@ -467,6 +828,50 @@ pub struct Server {
     /// logging, and error handling.
     #[serde(default)]
     pub middlewares: middleware::Config,
+    /// Delegate storage-backed downloads to the reverse proxy in front of the
+    /// app (nginx `X-Accel-Redirect`, apache `X-Sendfile`) instead of
+    /// streaming the content from the app.
+    #[serde(default)]
+    pub sendfile: Option<SendFile>,
+    /// Headers added to every response, unless the response already has
+    /// them. An empty value leaves out a header of `security_headers`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// Adds secure default headers (`Strict-Transport-Security`,
+    /// `X-Content-Type-Options`, `X-Frame-Options`, `Referrer-Policy` and
+    /// `Content-Security-Policy`) to every response.
+    #[serde(default)]
+    pub security_headers: bool,
+    /// The format of the error responses of the routes: `json` (the
+    /// default), `html`, or `negotiate` from the `Accept` header, see
+    /// [`crate::controller::error_format`]
+    #[serde(default)]
+    pub error_format: ErrorFormat,
+}
+
+/// Reverse proxy file delegation configuration
+/// Example:
+/// ```yaml
+/// # config/production.yaml
+/// server:
+///   sendfile:
+///     header: X-Accel-Redirect
+///     prefix: /protected
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+pub struct SendFile {
+    /// The response header the proxy acts on.
+    #[serde(default = "default_sendfile_header")]
+    pub header: String,
+    /// The location the proxy serves the storage content from. The storage
+    /// path is appended to it.
+    #[serde(default)]
+    pub prefix: String,
+}
+
+fn default_sendfile_header() -> String {
+    "X-Accel-Redirect".to_string()
 }
 
 fn default_binding() -> String {
@@ -486,14 +891,239 @@ impl Server {
 /// workers:
 ///   mode: BackgroundQueue
 /// ```
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+///
+/// Example (staging), to keep mails in the queue instead of sending them:
+/// ```yaml
+/// # config/staging.yaml
+/// workers:
+///   mode: BackgroundQueue
+///   queues:
+///     - default
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct Workers {
     /// Toggle between different worker modes
     pub mode: WorkerMode,
+    /// Process queued jobs in this environment. When disabled, jobs are
+    /// still enqueued and accumulate until a worker with processing enabled
+    /// picks them up. Only applies to the `BackgroundQueue` mode.
+    #[serde(default = "workers_enable")]
+    pub enable: bool,
+    /// The queues processed by this instance. Defaults to all the queues of
+    /// the queue provider. Only supported by the Redis queue provider.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queues: Option<Vec<String>>,
+    /// What the boot does when workers registered with
+    /// `Queue::try_register` fail to build or register
+    #[serde(default)]
+    pub on_registration_error: OnRegistrationError,
+}
+
+const fn workers_enable() -> bool {
+    true
+}
+
+impl Default for Workers {
+    fn default() -> Self {
+        Self {
+            mode: WorkerMode::default(),
+            enable: true,
+            queues: None,
+            on_registration_error: OnRegistrationError::default(),
+        }
+    }
+}
+
+/// What the boot does when workers fail to register.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum OnRegistrationError {
+    /// Fail the boot, listing the failed workers
+    #[default]
+    Fail,
+    /// Start with the healthy workers, and log the failed ones
+    Skip,
+}
+
+impl Workers {
+    /// Returns whether this instance processes jobs from the queue provider.
+    #[must_use]
+    pub fn processes_jobs(&self) -> bool {
+        self.mode == WorkerMode::BackgroundQueue && self.enable
+    }
+}
+
+/// Graceful shutdown configuration
+///
+/// On `SIGTERM` or Ctrl+C, the app shuts down in stages, each bounded by its
+/// timeout: a stage still running past it is force-stopped with a warning,
+/// and the next stage starts.
+///
+/// 1. `drain_timeout`: stop accepting connections and drain the in-flight
+///    requests
+/// 2. `workers_timeout`: stop dequeuing and wait for the jobs in progress
+/// 3. `hooks_timeout`: run [`crate::app::Hooks::on_shutdown`], then the
+///    initializers [`crate::app::Initializer::on_shutdown`] in reverse order
+/// 4. `close_timeout`: close the database and queue connections
+///
+/// Example (development):
+/// ```yaml
+/// # config/development.yaml
+/// shutdown:
+///   drain_timeout: 30000
+///   workers_timeout: 30000
+///   hooks_timeout: 10000
+///   close_timeout: 5000
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+pub struct Shutdown {
+    /// Milliseconds to drain the in-flight requests
+    #[serde(default = "shutdown_drain_timeout")]
+    pub drain_timeout: u64,
+    /// Milliseconds to wait for the jobs in progress
+    #[serde(default = "shutdown_workers_timeout")]
+    pub workers_timeout: u64,
+    /// Milliseconds to run the shutdown hooks
+    #[serde(default = "shutdown_hooks_timeout")]
+    pub hooks_timeout: u64,
+    /// Milliseconds to close the connections
+    #[serde(default = "shutdown_close_timeout")]
+    pub close_timeout: u64,
+}
+
+const fn shutdown_drain_timeout() -> u64 {
+    30_000
+}
+
+const fn shutdown_workers_timeout() -> u64 {
+    30_000
+}
+
+const fn shutdown_hooks_timeout() -> u64 {
+    10_000
+}
+
+const fn shutdown_close_timeout() -> u64 {
+    5_000
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self {
+            drain_timeout: shutdown_drain_timeout(),
+            workers_timeout: shutdown_workers_timeout(),
+            hooks_timeout: shutdown_hooks_timeout(),
+            close_timeout: shutdown_close_timeout(),
+        }
+    }
+}
+
+/// The shared HTTP client for outbound calls, see [`crate::http_client`].
+///
+/// Example (development):
+/// ```yaml
+/// # config/development.yaml
+/// http_client:
+///   connect_timeout: 5000
+///   timeout: 30000
+///   retries: 2
+///   retry_backoff: 100
+///   proxy: http://proxy.internal:3128
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+pub struct HttpClient {
+    /// Milliseconds to establish a connection
+    #[serde(default = "http_client_connect_timeout")]
+    pub connect_timeout: u64,
+    /// Milliseconds for a whole request, until its response body is read
+    #[serde(default = "http_client_timeout")]
+    pub timeout: u64,
+    /// How many times idempotent requests are retried on connection errors,
+    /// timeouts and `502`, `503` or `504` responses
+    #[serde(default = "http_client_retries")]
+    pub retries: u32,
+    /// Milliseconds to wait before the first retry, doubled for each next one
+    #[serde(default = "http_client_retry_backoff")]
+    pub retry_backoff: u64,
+    /// Milliseconds an idle pooled connection is kept
+    #[serde(default = "http_client_pool_idle_timeout")]
+    pub pool_idle_timeout: u64,
+    /// The proxy of all the requests, like `http://proxy.internal:3128`
+    pub proxy: Option<String>,
+    /// The `User-Agent` of the requests
+    pub user_agent: Option<String>,
+}
+
+const fn http_client_connect_timeout() -> u64 {
+    5_000
+}
+
+const fn http_client_timeout() -> u64 {
+    30_000
+}
+
+const fn http_client_retries() -> u32 {
+    2
+}
+
+const fn http_client_retry_backoff() -> u64 {
+    100
+}
+
+const fn http_client_pool_idle_timeout() -> u64 {
+    90_000
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self {
+            connect_timeout: http_client_connect_timeout(),
+            timeout: http_client_timeout(),
+            retries: http_client_retries(),
+            retry_backoff: http_client_retry_backoff(),
+            pool_idle_timeout: http_client_pool_idle_timeout(),
+            proxy: None,
+            user_agent: None,
+        }
+    }
+}
+
+/// The event bus within the process, see [`crate::events`].
+///
+/// Example (development):
+/// ```yaml
+/// # config/development.yaml
+/// events:
+///   capacity: 1024
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+pub struct Events {
+    /// The number of events of a type buffered for its subscribers. A
+    /// subscriber falling further behind misses the oldest events
+    #[serde(default = "events_capacity")]
+    pub capacity: usize,
+}
+
+const fn events_capacity() -> usize {
+    1024
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Self {
+            capacity: events_capacity(),
+        }
+    }
 }
 
 /// Worker mode configuration
 #[derive(Clone, Default, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub enum WorkerMode {
     /// Workers operate asynchronously in the background, processing queued
     /// tasks. **Requires a Redis connection**.
@@ -519,12 +1149,57 @@ pub enum WorkerMode {
 ///     port: 1025
 ///     secure: false
 /// ```
+///
+/// Example (staging), to deliver all mails to a test inbox:
+/// ```yaml
+/// # config/staging.yaml
+/// mailer:
+///   catch_all: {{ get_env(name="MAILER_CATCH_ALL", default="") }}
+/// ```
+///
+/// Example (production), with a separate sender for the marketing mails:
+/// ```yaml
+/// mailer:
+///   smtp:
+///     enable: true
+///     host: smtp.example.com
+///     port: 587
+///     secure: true
+///   senders:
+///     marketing:
+///       from: "Newsletter <news@example.com>"
+///       rate_limit: 10
+///       smtp:
+///         enable: true
+///         host: smtp.marketing.example.com
+///         port: 587
+///         secure: true
+/// ```
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct Mailer {
     pub smtp: Option<SmtpMailer>,
 
+    /// Records the mails in memory instead of sending them, see
+    /// `testing::mailer::sent_mails`
     #[serde(default)]
     pub stub: bool,
+
+    /// Delivers every mail to this address instead of its recipients (`Cc`
+    /// and `Bcc` included), for example to keep staging mails in a test
+    /// inbox. An empty value is ignored.
+    pub catch_all: Option<String>,
+
+    /// The `From` of the mails that do not set one.
+    pub from: Option<String>,
+
+    /// The maximum number of mails sent per second, by each process.
+    pub rate_limit: Option<u32>,
+
+    /// Other senders, by name, each with its own transport, `from` and rate
+    /// limit, see `AppContext::mailer_named`. Senders cannot have senders.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub senders: BTreeMap<String, Self>,
 }
 
 /// Initializers configuration
@@ -539,10 +1214,20 @@ pub struct Mailer {
 ///     - client_identifier: google # Identifier for the `OAuth2` provider.
 ///       Replace 'google' with your provider's name if different, must be
 ///       unique within the oauth2 config. ... # other fields
+/// ```
+///
+/// The `disabled` key lists initializers, by name, that are not run:
+/// ```yaml
+/// initializers:
+///   disabled:
+///     - request_id
+///     - metrics
+/// ```
 pub type Initializers = BTreeMap<String, serde_json::Value>;
 
 /// SMTP mailer configuration structure.
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct SmtpMailer {
     pub enable: bool,
     /// SMTP host. for example: localhost, smtp.gmail.com etc.
@@ -559,6 +1244,7 @@ pub struct SmtpMailer {
 
 /// Authentication details for the mailer
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct MailerAuth {
     /// User
     pub user: String,
@@ -567,6 +1253,16 @@ pub struct MailerAuth {
 }
 
 impl Config {
+    /// Returns the database configuration.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::DbNotConfigured`] when the configuration has no `database`
+    #[cfg(feature = "with-db")]
+    pub fn database(&self) -> Result<&Database> {
+        self.database.as_ref().ok_or(Error::DbNotConfigured)
+    }
+
     /// Creates a new configuration instance based on the specified environment.
     ///
     /// # Errors
@@ -612,28 +1308,94 @@ impl Config {
     ///     Config::from_folder(environment, &PathBuf::from("config")).expect("configuration loading")
     /// }
     pub fn from_folder(env: &Environment, path: &Path) -> Result<Self> {
-        // by order of precedence
-        let files = [
-            path.join(format!("{env}.local.yaml")),
-            path.join(format!("{env}.yaml")),
-        ];
-
-        let selected_path = files.iter().find(|p| p.exists()).ok_or_else(|| {
-            Error::Message(format!(
+        let Some(selected_path) = selected_file(env, path) else {
+            let dir = path.join(env.to_string());
+            if dir.is_dir() {
+                return Self::from_dir(&dir);
+            }
+            return Err(Error::Message(format!(
                 "no configuration file found in folder: {}",
                 path.display()
-            ))
-        })?;
+            )));
+        };
 
         info!(selected_path =? selected_path, "loading environment from");
 
-        let content = fs::read_to_string(selected_path)?;
-        let rendered = crate::tera::render_string(&content, &json!({}))?;
-
-        serde_yaml::from_str(&rendered)
+        serde_yaml::from_str(&render_file(&selected_path)?)
             .map_err(|err| Error::YAMLFile(err, selected_path.to_string_lossy().to_string()))
     }
 
+    /// Loads the configuration spread over the YAML files of `dir`, such as
+    /// `config/production/database.yaml` and `config/production/server.yaml`,
+    /// read in the order of their names. Sections spread over several files
+    /// merge, and a value set by two files is a conflict.
+    fn from_dir(dir: &Path) -> Result<Self> {
+        let files = yaml_files(dir)?;
+
+        info!(selected_path =? dir, "loading environment from");
+
+        let mut merged = serde_yaml::Mapping::new();
+        let mut sources = BTreeMap::new();
+        for file in &files {
+            let value: serde_yaml::Value = serde_yaml::from_str(&render_file(file)?)
+                .map_err(|err| Error::YAMLFile(err, file.to_string_lossy().to_string()))?;
+            match value {
+                serde_yaml::Value::Mapping(sections) => {
+                    merge_sections(&mut merged, sections, "", file, &mut sources)?;
+                }
+                serde_yaml::Value::Null => {}
+                _ => {
+                    return Err(Error::Message(format!(
+                        "configuration file {} must contain configuration sections",
+                        file.display()
+                    )));
+                }
+            }
+        }
+
+        serde_yaml::from_value(serde_yaml::Value::Mapping(merged))
+            .map_err(|err| Error::YAMLFile(err, dir.to_string_lossy().to_string()))
+    }
+
+    /// Returns the JSON Schema describing the configuration files, for editor
+    /// autocompletion and validation.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use loco_rs::config::Config;
+    ///
+    /// let schema = Config::json_schema();
+    /// assert!(schema["properties"]["server"].is_object());
+    /// ```
+    #[cfg(feature = "config_schema")]
+    #[must_use]
+    pub fn json_schema() -> serde_json::Value {
+        schemars::schema_for!(Self).to_value()
+    }
+
+    /// Returns the names of the initializers disabled with
+    /// `initializers.disabled`.
+    ///
+    /// # Errors
+    ///
+    /// When `initializers.disabled` is not a list of names
+    pub fn disabled_initializers(&self) -> Result<Vec<String>> {
+        self.initializers
+            .as_ref()
+            .and_then(|initializers| initializers.get("disabled"))
+            .map_or_else(
+                || Ok(vec![]),
+                |disabled| {
+                    serde_json::from_value(disabled.clone()).map_err(|err| {
+                        Error::Message(format!(
+                            "`initializers.disabled` must be a list of initializer names: {err}"
+                        ))
+                    })
+                },
+            )
+    }
+
     /// Get a reference to the JWT configuration.
     ///
     /// # Errors
@@ -647,6 +1409,119 @@ impl Config {
                 Ok,
             )
     }
+
+    /// Get the configuration of a signed webhook.
+    ///
+    /// # Errors
+    /// return an error when the webhook is not configured
+    pub fn get_webhook_config(&self, name: &str) -> Result<&Webhook> {
+        self.auth
+            .as_ref()
+            .and_then(|auth| auth.webhooks.get(name))
+            .ok_or_else(|| Error::Message(format!("no `auth.webhooks.{name}` config found")))
+    }
+}
+
+/// The file the configuration of `env` is read from in `path`, by order of
+/// precedence `{env}.local.yaml` then `{env}.yaml`.
+fn selected_file(env: &Environment, path: &Path) -> Option<PathBuf> {
+    [
+        path.join(format!("{env}.local.yaml")),
+        path.join(format!("{env}.yaml")),
+    ]
+    .into_iter()
+    .find(|file| file.exists())
+}
+
+/// The YAML files of `dir`, in the order of their names.
+fn yaml_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    files.retain(|file| {
+        file.is_file()
+            && matches!(
+                file.extension().and_then(|ext| ext.to_str()),
+                Some("yaml" | "yml")
+            )
+    });
+    files.sort();
+    if files.is_empty() {
+        return Err(Error::Message(format!(
+            "no configuration file found in folder: {}",
+            dir.display()
+        )));
+    }
+    Ok(files)
+}
+
+/// The files the configuration of `env` is read from in `path`: a single
+/// file, or the files of the `{env}` folder.
+///
+/// # Errors
+///
+/// When no configuration file is found
+pub(crate) fn source_files(env: &Environment, path: &Path) -> Result<Vec<PathBuf>> {
+    if let Some(file) = selected_file(env, path) {
+        return Ok(vec![file]);
+    }
+    let dir = path.join(env.to_string());
+    if dir.is_dir() {
+        return yaml_files(&dir);
+    }
+    Err(Error::Message(format!(
+        "no configuration file found in folder: {}",
+        path.display()
+    )))
+}
+
+/// Renders a configuration file, which is a Tera template.
+pub(crate) fn render_file(path: &Path) -> Result<String> {
+    let content = fs::read_to_string(path)?;
+    crate::tera::render_string(&content, &json!({}))
+}
+
+/// Merges the `sections` of `file` into the configuration read so far, under
+/// the dotted key `prefix`. `sources` records the file setting each key, to
+/// name both files of a conflict.
+fn merge_sections(
+    merged: &mut serde_yaml::Mapping,
+    sections: serde_yaml::Mapping,
+    prefix: &str,
+    file: &Path,
+    sources: &mut BTreeMap<String, PathBuf>,
+) -> Result<()> {
+    for (key, value) in sections {
+        let name = key
+            .as_str()
+            .map_or_else(|| format!("{key:?}"), ToString::to_string);
+        let name = if prefix.is_empty() {
+            name
+        } else {
+            format!("{prefix}.{name}")
+        };
+        match (merged.get_mut(&key), value) {
+            (Some(serde_yaml::Value::Mapping(existing)), serde_yaml::Value::Mapping(value)) => {
+                merge_sections(existing, value, &name, file, sources)?;
+            }
+            (Some(_), _) => {
+                let source = std::iter::successors(Some(name.as_str()), |name| {
+                    name.rsplit_once('.').map(|(parent, _)| parent)
+                })
+                .find_map(|name| sources.get(name))
+                .map_or_else(String::new, |source| source.display().to_string());
+                return Err(Error::Message(format!(
+                    "conflicting configuration: `{name}` is set in both {source} and {}",
+                    file.display()
+                )));
+            }
+            (None, value) => {
+                sources.insert(name, file.to_path_buf());
+                merged.insert(key, value);
+            }
+        }
+    }
+    Ok(())
 }
 
 impl std::fmt::Display for Config {
@@ -655,3 +1530,70 @@ impl std::fmt::Display for Config {
         write!(f, "{content}")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests_cfg;
+
+    /// The test configuration, without its server port.
+    fn sections() -> (String, String) {
+        let serde_yaml::Value::Mapping(mut config) =
+            serde_yaml::to_value(tests_cfg::config::test_config()).unwrap()
+        else {
+            unreachable!("the configuration is a mapping")
+        };
+        let mut server = config.remove("server").unwrap();
+        server.as_mapping_mut().unwrap().remove("port");
+        let server: serde_yaml::Mapping = [("server".into(), server)].into_iter().collect();
+        (
+            serde_yaml::to_string(&config).unwrap(),
+            serde_yaml::to_string(&server).unwrap(),
+        )
+    }
+
+    #[test]
+    fn can_load_config_from_directory() {
+        let (rest, server) = sections();
+        let tree = tree_fs::TreeBuilder::default()
+            .add_file("test/app.yaml", &rest)
+            .add_file("test/server.yml", &server)
+            .add_file(
+                "test/server_port.yaml",
+                "server:\n  port: {{ 5000 + 150 }}\n",
+            )
+            .add_file("test/notes.txt", "not configuration")
+            .create()
+            .unwrap();
+
+        let config = Config::from_folder(&Environment::Test, &tree.root).unwrap();
+        assert_eq!(config.server.port, 5150);
+        assert_eq!(
+            config.server.host,
+            tests_cfg::config::test_config().server.host
+        );
+    }
+
+    #[test]
+    fn reports_conflicts_between_files() {
+        let (rest, server) = sections();
+        let tree = tree_fs::TreeBuilder::default()
+            .add_file("test/app.yaml", &rest)
+            .add_file("test/server.yaml", &server)
+            .add_file(
+                "test/server_port.yaml",
+                "server:\n  port: 5150\n  host: http://other\n",
+            )
+            .create()
+            .unwrap();
+
+        let err = Config::from_folder(&Environment::Test, &tree.root)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("`server.host`"), "{err}");
+        assert!(
+            err.contains("server.yaml") && err.contains("server_port.yaml"),
+            "{err}"
+        );
+    }
+}
@@ -2,28 +2,62 @@
 //! configuring routes in an Axum application. It allows you to define route
 //! prefixes, add routes, and configure middlewares for the application.
 
-use std::{fmt, sync::OnceLock};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    future::Future,
+    panic::Location,
+    pin::Pin,
+    sync::{Arc, OnceLock},
+};
 
-use axum::Router as AXRouter;
+use axum::{
+    extract::{Request, State},
+    http::request::Parts,
+    middleware::Next,
+    response::Response,
+    Router as AXRouter,
+};
 use regex::Regex;
 
 use crate::{
     app::{AppContext, Hooks},
-    controller::{middleware::MiddlewareLayer, routes::Routes},
-    Result,
+    controller::{
+        error_format::{self, ErrorFormat},
+        middleware::{
+            deprecation::Deprecation, response_cache::ResponseCache, slow_request, MiddlewareLayer,
+        },
+        routes::{FilterContext, Routes},
+        versioning::{ApiVersion, Dispatch},
+        ErrorResponse,
+    },
+    Error, Result,
 };
 
 static NORMALIZE_URL: OnceLock<Regex> = OnceLock::new();
+static PATH_PARAMS: OnceLock<Regex> = OnceLock::new();
 
 fn get_normalize_url() -> &'static Regex {
     NORMALIZE_URL.get_or_init(|| Regex::new(r"/+").unwrap())
 }
 
+/// Returns the path with its parameter names removed: `/users/{id}` and
+/// `/users/{user_id}` match the same requests, and cannot be registered
+/// together.
+fn path_pattern(uri: &str) -> String {
+    PATH_PARAMS
+        .get_or_init(|| Regex::new(r"\{(\*?)[^}]*\}").unwrap())
+        .replace_all(uri, "{$1}")
+        .to_string()
+}
+
 /// Represents the routes of the application.
 #[derive(Clone)]
 pub struct AppRoutes {
     prefix: Option<String>,
     routes: Vec<Routes>,
+    versions: Vec<ApiVersion>,
+    default_version: Option<String>,
 }
 
 #[derive(Debug)]
@@ -31,6 +65,17 @@ pub struct ListRoutes {
     pub uri: String,
     pub actions: Vec<axum::http::Method>,
     pub method: axum::routing::MethodRouter<AppContext>,
+    pub source: Option<&'static Location<'static>>,
+    pub overrides: bool,
+    pub deprecation: Option<Deprecation>,
+    pub cache: Option<ResponseCache>,
+}
+
+impl ListRoutes {
+    fn source(&self) -> String {
+        self.source
+            .map_or_else(|| "an unknown location".to_string(), ToString::to_string)
+    }
 }
 
 impl fmt::Display for ListRoutes {
@@ -42,7 +87,14 @@ impl fmt::Display for ListRoutes {
             .collect::<Vec<_>>()
             .join(",");
 
-        write!(f, "[{}] {}", actions_str, self.uri)
+        write!(f, "[{}] {}", actions_str, self.uri)?;
+        if let Some(deprecation) = &self.deprecation {
+            write!(f, " (deprecated, sunset {})", deprecation.sunset())?;
+        }
+        if let Some(cache) = &self.cache {
+            write!(f, " (cached {}s)", cache.ttl().as_secs())?;
+        }
+        Ok(())
     }
 }
 
@@ -63,6 +115,8 @@ impl AppRoutes {
         Self {
             prefix: None,
             routes: vec![],
+            versions: vec![],
+            default_version: None,
         }
     }
 
@@ -98,12 +152,81 @@ impl AppRoutes {
                         uri,
                         actions: handler.actions.clone(),
                         method: handler.method.clone(),
+                        source: handler.source,
+                        overrides: handler.overrides,
+                        deprecation: handler.deprecation.clone(),
+                        cache: handler.cache.clone(),
                     }
                 })
             })
             .collect()
     }
 
+    /// Returns the routes to register, after checking that no method and path
+    /// is registered twice. A route added with [`Routes::add_override`]
+    /// replaces the routes registered before it for the same methods and path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming both registrations when a route is registered
+    /// twice, or when two paths only differ by their parameter names.
+    pub fn resolve(&self) -> Result<Vec<ListRoutes>> {
+        let mut resolved: Vec<ListRoutes> = Vec::new();
+        for route in self.collect() {
+            let pattern = path_pattern(&route.uri);
+            if let Some(existing) = resolved.iter().find(|existing| {
+                existing.uri != route.uri && path_pattern(&existing.uri) == pattern
+            }) {
+                return Err(Error::Message(format!(
+                    "route `{}` (added at {}) conflicts with `{}` (added at {}): paths cannot only \
+                     differ by their parameter names",
+                    route.uri,
+                    route.source(),
+                    existing.uri,
+                    existing.source(),
+                )));
+            }
+
+            let overlapping = |existing: &ListRoutes| {
+                existing.uri == route.uri
+                    && existing
+                        .actions
+                        .iter()
+                        .any(|action| route.actions.contains(action))
+            };
+            for existing in resolved.iter().filter(|existing| overlapping(existing)) {
+                if !route.overrides {
+                    return Err(Error::Message(format!(
+                        "route `{route}` is registered twice, at {} and at {}. Use \
+                         `Routes::add_override` to replace a route intentionally",
+                        existing.source(),
+                        route.source(),
+                    )));
+                }
+                if !existing
+                    .actions
+                    .iter()
+                    .all(|action| route.actions.contains(action))
+                {
+                    return Err(Error::Message(format!(
+                        "route `{route}` (added at {}) only overrides some methods of `{existing}` \
+                         (added at {}): override all of them",
+                        route.source(),
+                        existing.source(),
+                    )));
+                }
+                tracing::info!(
+                    "{} overrides the route added at {}",
+                    route,
+                    existing.source()
+                );
+            }
+            resolved.retain(|existing| !overlapping(existing));
+            resolved.push(route);
+        }
+        Ok(resolved)
+    }
+
     /// Get the prefix of the routes.
     #[must_use]
     pub fn get_prefix(&self) -> Option<&String> {
@@ -231,6 +354,91 @@ impl AppRoutes {
         self
     }
 
+    /// Add the routes of an API version, under the current prefix followed by
+    /// the version name.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use axum::routing::get;
+    /// use loco_rs::controller::{AppRoutes, Routes};
+    ///
+    /// let app_routes = AppRoutes::with_default_routes()
+    ///     .prefix("api")
+    ///     .version("v1", vec![Routes::new().add("/notes", get(|| async { "notes" }))])
+    ///     .default_version("v1");
+    ///
+    /// // This will result in the route `/api/v1/notes`, and `/api/notes` routing to
+    /// // the version asked for by the `Accept` header, `v1` by default
+    /// ```
+    #[must_use]
+    pub fn version(mut self, name: &str, routes: Vec<Routes>) -> Self {
+        let mount = self
+            .clone()
+            .nest_prefix(name)
+            .prefix
+            .unwrap_or_else(|| format!("/{name}/"));
+        self.versions.push(ApiVersion {
+            name: name.to_string(),
+            mount,
+            alias: self.prefix.clone().unwrap_or_else(|| "/".to_string()),
+            routes: routes.clone(),
+        });
+        self.nest_routes(name, routes)
+    }
+
+    /// Add an API version sharing the routes of the `base` version, except for
+    /// the ones `routes` replace: a route of `routes` overrides the route of
+    /// `base` for the same methods and path, like [`Routes::add_override`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use axum::routing::get;
+    /// use loco_rs::controller::{AppRoutes, Routes};
+    ///
+    /// let app_routes = AppRoutes::empty()
+    ///     .prefix("api")
+    ///     .version("v1", vec![
+    ///         Routes::new()
+    ///             .add("/notes", get(|| async { "notes" }))
+    ///             .add("/users", get(|| async { "users" })),
+    ///     ])
+    ///     .version_from("v2", "v1", vec![
+    ///         Routes::new().add("/notes", get(|| async { "notes v2" })),
+    ///     ]);
+    ///
+    /// // `/api/v2/users` is the same route as `/api/v1/users`
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics when the `base` version is not registered yet.
+    #[must_use]
+    pub fn version_from(self, name: &str, base: &str, routes: Vec<Routes>) -> Self {
+        let Some(base) = self.versions.iter().find(|version| version.name == base) else {
+            panic!("API version `{name}` is based on `{base}`, which is not registered");
+        };
+        let mut inherited = base.routes.clone();
+        inherited.extend(routes.into_iter().map(|mut route| {
+            for handler in &mut route.handlers {
+                handler.overrides = true;
+            }
+            route
+        }));
+        self.version(name, inherited)
+    }
+
+    /// Route the unversioned paths of the API versions, like `/api/notes`, to
+    /// the version named by the `Accept` header of the request, either as
+    /// `application/vnd.myapp.v2+json` or as `application/json; version=v2`,
+    /// and to the `name` version otherwise.
+    #[must_use]
+    pub fn default_version(mut self, name: &str) -> Self {
+        self.default_version = Some(name.to_string());
+        self
+    }
+
     /// Add a single route.
     #[must_use]
     pub fn add_route(mut self, mut route: Routes) -> Self {
@@ -277,7 +485,7 @@ impl AppRoutes {
     ///
     /// # Errors
     /// Return an [`Result`] when could not convert the router setup to
-    /// [`axum::Router`].
+    /// [`axum::Router`], or when a route is registered twice.
     #[allow(clippy::cognitive_complexity)]
     pub fn to_router<H: Hooks>(
         &self,
@@ -301,10 +509,39 @@ impl AppRoutes {
         // using the router directly, and ServiceBuilder has been reported to give
         // issues in compile times itself (https://github.com/rust-lang/crates.io/pull/7443).
         //
-        for router in self.collect() {
+        let resolved = self.resolve()?;
+        let aliases = self.version_aliases(&ctx, &resolved)?;
+        let has_routes = !resolved.is_empty() || !aliases.is_empty();
+        for router in resolved {
             tracing::info!("{}", router.to_string());
             app = app.route(&router.uri, router.method);
         }
+        for (uri, dispatch) in aliases {
+            tracing::info!("[*] {uri} (versioned)");
+            app = app.route(
+                &uri,
+                axum::routing::any(move |request: Request| dispatch.clone().call(request)),
+            );
+        }
+        // the time of the matched route, for the slow request breakdown
+        if has_routes {
+            app = app.route_layer(axum::middleware::from_fn(slow_request::time_handler));
+        }
+        // the error responses not formatted by their route group
+        let error_format = ctx.config.server.error_format;
+        if error_format != ErrorFormat::Json {
+            app = app.layer(axum::middleware::from_fn(move |request, next| {
+                error_format::format_errors(error_format, request, next)
+            }));
+        }
+        // the app context of the route filters
+        app = app.layer(axum::Extension(FilterContext(Arc::new(ctx.clone()))));
+        // the innermost layer, to observe the errors in the request span
+        let on_error: OnError = |ctx, err, parts| H::on_error(ctx, err, parts);
+        app = app.layer(axum::middleware::from_fn_with_state(
+            (ctx.clone(), on_error),
+            observe_errors,
+        ));
 
         let middlewares = self.middlewares::<H>(&ctx);
         for mid in middlewares {
@@ -314,6 +551,89 @@ impl AppRoutes {
         let router = app.with_state(ctx);
         Ok(router)
     }
+
+    /// Returns the unversioned paths of the API versions, when a default
+    /// version is set, with the versions having each of them.
+    fn version_aliases(
+        &self,
+        ctx: &AppContext,
+        routes: &[ListRoutes],
+    ) -> Result<BTreeMap<String, Dispatch>> {
+        let Some(default) = &self.default_version else {
+            return Ok(BTreeMap::new());
+        };
+        if !self.versions.iter().any(|version| &version.name == default) {
+            return Err(Error::Message(format!(
+                "the default API version `{default}` is not registered"
+            )));
+        }
+
+        let mut aliases: BTreeMap<String, Vec<(String, axum::routing::MethodRouter)>> =
+            BTreeMap::new();
+        for route in routes {
+            let Some((version, alias)) = self
+                .versions
+                .iter()
+                .find_map(|version| Some((version, version.unversioned(&route.uri)?)))
+            else {
+                continue;
+            };
+            let method = route.method.clone().with_state(ctx.clone());
+            let versions = aliases.entry(alias).or_default();
+            match versions.iter_mut().find(|(name, _)| *name == version.name) {
+                Some((_, existing)) => *existing = existing.clone().merge(method),
+                None => versions.push((version.name.clone(), method)),
+            }
+        }
+
+        let mut patterns: BTreeMap<String, String> = routes
+            .iter()
+            .map(|route| (path_pattern(&route.uri), route.uri.clone()))
+            .collect();
+        for alias in aliases.keys() {
+            if let Some(existing) = patterns.insert(path_pattern(alias), alias.clone()) {
+                return Err(Error::Message(format!(
+                    "the unversioned route `{alias}` conflicts with `{existing}`: versions must \
+                     name their path parameters alike, and not shadow other routes"
+                )));
+            }
+        }
+
+        Ok(aliases
+            .into_iter()
+            .map(|(alias, versions)| {
+                let dispatch = Dispatch {
+                    versions: Arc::new(versions),
+                    default: default.clone(),
+                };
+                (alias, dispatch)
+            })
+            .collect())
+    }
+}
+
+type OnError = for<'a> fn(
+    &'a AppContext,
+    &'a Error,
+    &'a Parts,
+) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+/// Calls [`Hooks::on_error`] for the responses made from an error.
+async fn observe_errors(
+    State((ctx, on_error)): State<(AppContext, OnError)>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (parts, body) = request.into_parts();
+    let request_parts = parts.clone();
+    let response = next.run(Request::from_parts(parts, body)).await;
+    let status = response.status();
+    if status.is_client_error() || status.is_server_error() {
+        if let Some(ErrorResponse(err)) = response.extensions().get::<ErrorResponse>() {
+            on_error(&ctx, err, &request_parts).await;
+        }
+    }
+    response
 }
 
 #[cfg(test)]
@@ -459,4 +779,487 @@ mod tests {
         let response = router.oneshot(req).await.unwrap();
         assert!(response.status().is_success());
     }
+
+    #[test]
+    fn rejects_duplicate_routes() {
+        let app_router = AppRoutes::empty()
+            .add_route(Routes::new().add("/notes", get(action).post(action)))
+            .add_route(Routes::at("notes").add("/", post(action)));
+
+        let err = app_router.resolve().unwrap_err().to_string();
+        assert!(err.starts_with(
+            "route `[POST] /notes` is registered twice, at src/controller/app_routes.rs:"
+        ));
+        assert!(err.contains("`Routes::add_override`"));
+    }
+
+    #[test]
+    fn rejects_routes_differing_by_parameter_names() {
+        let app_router = AppRoutes::empty()
+            .add_route(Routes::new().add("/notes/{id}", get(action)))
+            .add_route(Routes::new().add("/notes/{note_id}", delete(action)));
+
+        let err = app_router.resolve().unwrap_err().to_string();
+        assert!(err.starts_with("route `/notes/{note_id}` (added at src/controller/app_routes.rs:"));
+    }
+
+    #[test]
+    fn can_merge_methods_of_the_same_path() {
+        let app_router = AppRoutes::empty()
+            .add_route(Routes::new().add("/notes", get(action)))
+            .add_route(Routes::new().add("/notes", post(action)));
+
+        assert_eq!(app_router.resolve().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn can_override_route() {
+        async fn replaced() -> Result<Response> {
+            format::json("replaced")
+        }
+
+        let app_router = AppRoutes::empty()
+            .add_route(Routes::new().add("/loco", get(action)))
+            .add_route(Routes::new().add_override("/loco", get(replaced)));
+
+        let ctx = tests_cfg::app::get_app_context().await;
+        let router = app_router
+            .to_router::<tests_cfg::db::AppHook>(ctx, axum::Router::new())
+            .unwrap();
+
+        let req = axum::http::Request::builder()
+            .uri("/loco")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = router.oneshot(req).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, r#""replaced""#);
+    }
+
+    #[test]
+    fn rejects_partial_override() {
+        let app_router = AppRoutes::empty()
+            .add_route(Routes::new().add("/notes", get(action).post(action)))
+            .add_route(Routes::new().add_override("/notes", get(action)));
+
+        let err = app_router.resolve().unwrap_err().to_string();
+        assert!(err.contains("only overrides some methods of `[GET,POST] /notes`"));
+    }
+
+    #[tokio::test]
+    async fn can_limit_route_concurrency() {
+        async fn report() -> Result<Response> {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            format::json("report")
+        }
+
+        let app_router = AppRoutes::empty()
+            .add_route(
+                Routes::new()
+                    .add("/reports", get(report))
+                    .concurrency_limit(1),
+            )
+            .add_route(Routes::new().add("/loco", get(action)));
+
+        let ctx = tests_cfg::app::get_app_context().await;
+        let router = app_router
+            .to_router::<tests_cfg::db::AppHook>(ctx, axum::Router::new())
+            .unwrap();
+
+        let send = |uri: &'static str| {
+            let router = router.clone();
+            tokio::spawn(async move {
+                let req = axum::http::Request::builder()
+                    .uri(uri)
+                    .body(axum::body::Body::empty())
+                    .unwrap();
+                router.oneshot(req).await.unwrap().status()
+            })
+        };
+        let first = send("/reports");
+        let second = send("/reports");
+        let other = send("/loco");
+
+        let mut reports = vec![first.await.unwrap(), second.await.unwrap()];
+        reports.sort();
+        assert_eq!(
+            reports,
+            vec![
+                axum::http::StatusCode::OK,
+                axum::http::StatusCode::SERVICE_UNAVAILABLE
+            ]
+        );
+        assert_eq!(other.await.unwrap(), axum::http::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn can_deprecate_routes() {
+        let sunset = chrono::NaiveDate::from_ymd_opt(2026, 6, 30).unwrap();
+        let app_router = AppRoutes::empty()
+            .add_route(
+                Routes::at("v1")
+                    .add("/notes", get(action))
+                    .deprecated(sunset),
+            )
+            .add_route(Routes::at("v2").add("/notes", get(action)));
+
+        let listed = app_router.collect();
+        assert_eq!(
+            listed.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            vec![
+                "[GET] /v1/notes (deprecated, sunset 2026-06-30)",
+                "[GET] /v2/notes"
+            ]
+        );
+        let deprecation = listed[0].deprecation.clone().unwrap();
+
+        let ctx = tests_cfg::app::get_app_context().await;
+        let router = app_router
+            .to_router::<tests_cfg::db::AppHook>(ctx, axum::Router::new())
+            .unwrap();
+        let call = |uri: &str| {
+            let req = axum::http::Request::builder()
+                .uri(uri)
+                .body(axum::body::Body::empty())
+                .unwrap();
+            router.clone().oneshot(req)
+        };
+
+        let response = call("/v1/notes").await.unwrap();
+        assert!(response.status().is_success());
+        assert_eq!(response.headers()["deprecation"], "true");
+        assert_eq!(
+            response.headers()["sunset"],
+            "Tue, 30 Jun 2026 00:00:00 GMT"
+        );
+
+        let response = call("/v2/notes").await.unwrap();
+        assert!(!response.headers().contains_key("deprecation"));
+        assert_eq!(deprecation.calls(), 1);
+    }
+
+    #[cfg(feature = "cache_inmem")]
+    #[tokio::test]
+    async fn can_cache_responses() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use crate::controller::middleware::response_cache::{self, ResponseCache};
+
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        async fn products() -> Result<Response> {
+            format::json(CALLS.fetch_add(1, Ordering::SeqCst))
+        }
+
+        let app_router = AppRoutes::empty().add_route(
+            Routes::at("products")
+                .add("/", get(products))
+                .cache(ResponseCache::new(std::time::Duration::from_secs(60)).vary_query("page")),
+        );
+        assert_eq!(
+            app_router.collect()[0].to_string(),
+            "[GET] /products (cached 60s)"
+        );
+
+        let ctx = tests_cfg::app::get_app_context().await;
+        let cache = ctx.cache.clone();
+        let router = app_router
+            .to_router::<tests_cfg::db::AppHook>(ctx, axum::Router::new())
+            .unwrap();
+        let call = |uri: &str, authorization: Option<&str>| {
+            let mut req = axum::http::Request::builder().uri(uri);
+            if let Some(authorization) = authorization {
+                req = req.header(axum::http::header::AUTHORIZATION, authorization);
+            }
+            let router = router.clone();
+            let req = req.body(axum::body::Body::empty()).unwrap();
+            async move {
+                let response = router.oneshot(req).await.unwrap();
+                let x_cache = response.headers().get("x-cache").cloned();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                (String::from_utf8(body.to_vec()).unwrap(), x_cache)
+            }
+        };
+
+        let (first, x_cache) = call("/products?page=1&utm=a", None).await;
+        assert_eq!(x_cache.unwrap(), "MISS");
+        // other query parameters than `page` are ignored
+        let (hit, x_cache) = call("/products?utm=b&page=1", None).await;
+        assert_eq!(hit, first);
+        assert_eq!(x_cache.unwrap(), "HIT");
+        assert_ne!(call("/products?page=2", None).await.0, first);
+        // authenticated requests bypass the cache
+        let (bypassed, x_cache) = call("/products?page=1", Some("Bearer token")).await;
+        assert_ne!(bypassed, first);
+        assert!(x_cache.is_none());
+
+        response_cache::invalidate(&cache, "products")
+            .await
+            .unwrap();
+        assert_ne!(call("/products?page=1", None).await.0, first);
+    }
+
+    fn versioned_routes() -> AppRoutes {
+        async fn notes_v2() -> Result<Response> {
+            format::json("notes v2")
+        }
+
+        AppRoutes::empty()
+            .prefix("api")
+            .version(
+                "v1",
+                vec![Routes::new()
+                    .add("/notes", get(action))
+                    .add("/users", get(action))],
+            )
+            .version_from("v2", "v1", vec![Routes::new().add("/notes", get(notes_v2))])
+    }
+
+    #[tokio::test]
+    async fn can_share_routes_across_versions() {
+        let app_router = versioned_routes();
+        let uris = app_router
+            .resolve()
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            uris,
+            vec![
+                "[GET] /api/v1/notes",
+                "[GET] /api/v1/users",
+                "[GET] /api/v2/users",
+                "[GET] /api/v2/notes"
+            ]
+        );
+
+        let ctx = tests_cfg::app::get_app_context().await;
+        let router = app_router
+            .to_router::<tests_cfg::db::AppHook>(ctx, axum::Router::new())
+            .unwrap();
+        for (uri, expected) in [
+            ("/api/v1/notes", r#""loco""#),
+            ("/api/v2/notes", r#""notes v2""#),
+            ("/api/v2/users", r#""loco""#),
+        ] {
+            let req = axum::http::Request::builder()
+                .uri(uri)
+                .body(axum::body::Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(req).await.unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(body, expected, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn can_route_versions_by_accept_header() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let router = versioned_routes()
+            .default_version("v1")
+            .to_router::<tests_cfg::db::AppHook>(ctx, axum::Router::new())
+            .unwrap();
+
+        for (accept, expected) in [
+            (None, r#""loco""#),
+            (Some("application/vnd.myapp.v2+json"), r#""notes v2""#),
+            (Some("application/json; version=v1"), r#""loco""#),
+        ] {
+            let mut req = axum::http::Request::builder().uri("/api/notes");
+            if let Some(accept) = accept {
+                req = req.header("accept", accept);
+            }
+            let response = router
+                .clone()
+                .oneshot(req.body(axum::body::Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.headers()["vary"], "Accept");
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(body, expected, "{accept:?}");
+        }
+
+        let req = axum::http::Request::builder()
+            .uri("/api/notes")
+            .header("accept", "application/vnd.myapp.v3+json")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = router.oneshot(req).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn rejects_unknown_default_version() {
+        let ctx = tests_cfg::app::get_app_context();
+        let ctx = tokio::runtime::Runtime::new().unwrap().block_on(ctx);
+        let err = versioned_routes()
+            .default_version("v3")
+            .to_router::<tests_cfg::db::AppHook>(ctx, axum::Router::new())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the default API version `v3` is not registered"
+        );
+    }
+
+    static OBSERVED: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    struct ErrorHook;
+
+    #[async_trait::async_trait]
+    impl Hooks for ErrorHook {
+        fn app_name() -> &'static str {
+            "TEST"
+        }
+
+        async fn boot(
+            mode: crate::boot::StartMode,
+            environment: &crate::environment::Environment,
+            config: crate::config::Config,
+        ) -> Result<crate::boot::BootResult> {
+            crate::boot::create_app::<Self, tests_cfg::db::Migrator>(mode, environment, config)
+                .await
+        }
+
+        fn routes(_ctx: &AppContext) -> AppRoutes {
+            AppRoutes::empty()
+        }
+
+        async fn on_error(_ctx: &AppContext, err: &Error, parts: &Parts) {
+            OBSERVED
+                .lock()
+                .unwrap()
+                .push(format!("{} {}: {err}", parts.method, parts.uri));
+        }
+
+        async fn connect_workers(_ctx: &AppContext, _q: &crate::bgworker::Queue) -> Result<()> {
+            Ok(())
+        }
+
+        fn register_tasks(_tasks: &mut crate::task::Tasks) {}
+
+        async fn truncate(_ctx: &AppContext) -> Result<()> {
+            Ok(())
+        }
+
+        async fn seed(_ctx: &AppContext, _base: &std::path::Path) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn can_observe_errors() {
+        async fn missing() -> Result<Response> {
+            Err(Error::NotFound)
+        }
+
+        let app_router = AppRoutes::empty()
+            .add_route(Routes::new().add("/missing", get(missing)))
+            .add_route(Routes::new().add("/loco", get(action)));
+        let ctx = tests_cfg::app::get_app_context().await;
+        let router = app_router
+            .to_router::<ErrorHook>(ctx, axum::Router::new())
+            .unwrap();
+
+        for uri in ["/missing?page=2", "/loco"] {
+            let req = axum::http::Request::builder()
+                .uri(uri)
+                .body(axum::body::Body::empty())
+                .unwrap();
+            router.clone().oneshot(req).await.unwrap();
+        }
+        assert_eq!(
+            *OBSERVED.lock().unwrap(),
+            vec!["GET /missing?page=2: not found"]
+        );
+    }
+
+    #[tokio::test]
+    async fn can_filter_routes() {
+        async fn admin() -> Result<Response> {
+            format::text("admin")
+        }
+
+        let app_router = AppRoutes::empty().add_route(
+            Routes::at("admin")
+                .add("/", get(admin))
+                .before(|_ctx: AppContext, request: Request| async move {
+                    if request.headers().contains_key("x-admin") {
+                        Ok(request)
+                    } else {
+                        Err(Redirect::to("/login"))
+                    }
+                })
+                .after(|ctx: AppContext, mut response: Response| async move {
+                    response.headers_mut().insert(
+                        "x-environment",
+                        ctx.environment.to_string().parse().unwrap(),
+                    );
+                    response
+                }),
+        );
+        let ctx = tests_cfg::app::get_app_context().await;
+        let router = app_router
+            .to_router::<tests_cfg::db::AppHook>(ctx, axum::Router::new())
+            .unwrap();
+
+        let req = Request::get("/admin")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::SEE_OTHER);
+        assert_eq!(response.headers()["location"], "/login");
+        assert_eq!(response.headers()["x-environment"], "test");
+
+        let req = Request::get("/admin")
+            .header("x-admin", "1")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = router.oneshot(req).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        assert_eq!(response.headers()["x-environment"], "test");
+    }
+
+    #[tokio::test]
+    async fn can_reject_requests_with_errors_in_before_filters() {
+        async fn report() -> Result<Response> {
+            format::text("report")
+        }
+
+        let app_router =
+            AppRoutes::empty().add_route(Routes::at("reports").add("/", get(report)).before(
+                |_ctx: AppContext, request: Request| async move {
+                    if request.headers().contains_key("authorization") {
+                        Ok(request)
+                    } else {
+                        Err(Error::Unauthorized("missing credentials".to_string()))
+                    }
+                },
+            ));
+        let ctx = tests_cfg::app::get_app_context().await;
+        let router = app_router
+            .to_router::<tests_cfg::db::AppHook>(ctx, axum::Router::new())
+            .unwrap();
+
+        let req = Request::get("/reports")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::UNAUTHORIZED);
+
+        let req = Request::get("/reports")
+            .header("authorization", "Bearer token")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = router.oneshot(req).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+    }
 }
@@ -0,0 +1,214 @@
+//! Error Response Formats
+//!
+//! Errors returned by handlers, extractors and route filters respond with a
+//! JSON [`super::ErrorDetail`] by default. `server.error_format` sets the
+//! format of all the routes, and [`super::Routes::error_format`] the format
+//! of a group of routes, overriding it:
+//!
+//! * `json`: a JSON error detail, whatever the request accepts,
+//! * `html`: an HTML error page,
+//! * `negotiate`: HTML when the `Accept` header lists `text/html` before any
+//!   JSON media type, as browsers do, and JSON otherwise, including for an
+//!   ambiguous `Accept` such as `*/*` or none.
+//!
+//! ```rust
+//! use loco_rs::{controller::error_format::ErrorFormat, prelude::*};
+//!
+//! async fn list() -> Result<Response> {
+//!     format::json("Ok")
+//! }
+//! // API clients always get JSON errors, also with `server.error_format: html`
+//! Routes::at("api/notes")
+//!     .add("/", get(list))
+//!     .error_format(ErrorFormat::Json);
+//! ```
+//!
+//! Responses made by the middlewares, such as timeouts, keep their own
+//! format.
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{
+        header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE},
+        HeaderMap, HeaderValue,
+    },
+    middleware::Next,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{ErrorBody, ErrorDetail};
+
+/// The format of the error responses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorFormat {
+    /// A JSON error detail
+    #[default]
+    Json,
+    /// An HTML error page
+    Html,
+    /// HTML or JSON, following the `Accept` header of the request
+    Negotiate,
+}
+
+/// Marks an error response rendered by a route group, for the outer layers
+/// to leave it alone.
+#[derive(Clone)]
+struct Formatted;
+
+/// Renders the error responses in `format`, unless a route group within
+/// already did.
+pub(crate) async fn format_errors(format: ErrorFormat, request: Request, next: Next) -> Response {
+    let format = match format {
+        ErrorFormat::Negotiate => negotiate(request.headers()),
+        format => format,
+    };
+    let mut response = next.run(request).await;
+    if response.extensions().get::<Formatted>().is_some() {
+        return response;
+    }
+    let Some(ErrorBody(detail)) = response.extensions().get::<ErrorBody>().cloned() else {
+        return response;
+    };
+    response.extensions_mut().insert(Formatted);
+    if format == ErrorFormat::Html {
+        html_page(response, &detail)
+    } else {
+        response
+    }
+}
+
+/// HTML when `text/html` comes before any JSON media type in the `Accept`
+/// header, JSON otherwise.
+fn negotiate(headers: &HeaderMap) -> ErrorFormat {
+    let accept = headers
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .unwrap_or_default();
+    accept
+        .split(',')
+        .filter_map(|media| media.split(';').next())
+        .map(str::trim)
+        .find_map(|media| match media {
+            "text/html" | "application/xhtml+xml" => Some(ErrorFormat::Html),
+            "application/json" => Some(ErrorFormat::Json),
+            media if media.ends_with("+json") => Some(ErrorFormat::Json),
+            _ => None,
+        })
+        .unwrap_or(ErrorFormat::Json)
+}
+
+/// Replaces the JSON body of an error response with an HTML page, keeping
+/// its status, headers and extensions.
+fn html_page(response: Response, detail: &ErrorDetail) -> Response {
+    let status = response.status();
+    let title = format!(
+        "{} {}",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or("Error")
+    );
+    let message = detail
+        .description
+        .as_deref()
+        .or(detail.error.as_deref())
+        .unwrap_or_default();
+    let page = format!(
+        "<!DOCTYPE html><html><head><title>{title}</title></head><body><h1>{title}</h1><p>{}</p></body></html>",
+        tera::escape_html(message)
+    );
+    let (mut parts, _) = response.into_parts();
+    parts.headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    parts.headers.remove(CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(page))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{http::StatusCode, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::Error;
+
+    async fn call(app: &Router, path: &str, accept: &str) -> (StatusCode, String, String) {
+        let request = Request::builder()
+            .uri(path)
+            .header(ACCEPT, accept)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let content_type = response.headers()[CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            content_type,
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn can_format_errors_per_group() {
+        let api = Router::new()
+            .route(
+                "/api",
+                get(|| async { Err::<(), _>(Error::BadRequest("<bad>".to_string())) }),
+            )
+            .layer(axum::middleware::from_fn(|request, next| {
+                format_errors(ErrorFormat::Json, request, next)
+            }));
+        let app = Router::new()
+            .route("/page", get(|| async { Err::<(), _>(Error::NotFound) }))
+            .route("/ok", get(|| async { "ok" }))
+            .merge(api)
+            .layer(axum::middleware::from_fn(|request, next| {
+                format_errors(ErrorFormat::Negotiate, request, next)
+            }));
+        let browser = "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+
+        let (status, content_type, body) = call(&app, "/page", browser).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(content_type, "text/html; charset=utf-8");
+        assert!(body.contains("<h1>404 Not Found</h1><p>Resource was not found</p>"));
+        let (_, content_type, _) = call(&app, "/page", "*/*").await;
+        assert_eq!(content_type, "application/json");
+
+        // the group overrides the negotiated format
+        let (status, content_type, body) = call(&app, "/api", browser).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(content_type, "application/json");
+        assert!(body.contains("<bad>"));
+
+        let (_, content_type, body) = call(&app, "/ok", browser).await;
+        assert_eq!(content_type, "text/plain; charset=utf-8");
+        assert_eq!(body, "ok");
+    }
+
+    #[tokio::test]
+    async fn can_render_html_pages() {
+        let response = html_page(
+            Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(Body::empty())
+                .unwrap(),
+            &ErrorDetail::new("Bad Request", "<script>"),
+        );
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(String::from_utf8(body.to_vec())
+            .unwrap()
+            .contains("<p>&lt;script&gt;</p>"));
+    }
+}
@@ -72,7 +72,10 @@ where
 
         let jwt_secret = ctx.config.get_jwt_config()?;
 
-        match auth::jwt::JWT::new(&jwt_secret.secret).validate(&token) {
+        match auth::jwt::JWT::new(&jwt_secret.secret)
+            .at(ctx.now())
+            .validate(&token)
+        {
             Ok(claims) => {
                 let user = T::find_by_claims_key(&ctx.db, &claims.claims.pid)
                     .await
@@ -135,7 +138,10 @@ where
 
     let jwt_secret = ctx.config.get_jwt_config()?;
 
-    match auth::jwt::JWT::new(&jwt_secret.secret).validate(&token) {
+    match auth::jwt::JWT::new(&jwt_secret.secret)
+        .at(ctx.now())
+        .validate(&token)
+    {
         Ok(claims) => Ok(JWT {
             claims: claims.claims,
         }),
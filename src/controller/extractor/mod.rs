@@ -1,4 +1,10 @@
 #[cfg(all(feature = "auth_jwt", feature = "with-db"))]
 pub mod auth;
+pub mod normalize;
+pub mod path;
+pub mod payload;
 pub mod shared_store;
+#[cfg(feature = "with-db")]
+pub mod tenant;
 pub mod validate;
+pub mod webhook;
@@ -0,0 +1,216 @@
+//! # Normalize
+//!
+//! Normalizes request inputs, like trimming strings or lowercasing emails,
+//! before they are validated, so that validation runs on the values the
+//! handler gets.
+//!
+//! Common normalizations are field attributes, applied while the body is
+//! deserialized by any extractor:
+//!
+//! ```rust
+//! use loco_rs::controller::extractor::normalize;
+//! use serde::Deserialize;
+//! use validator::Validate;
+//!
+//! #[derive(Deserialize, Validate)]
+//! pub struct Subscribe {
+//!     #[serde(deserialize_with = "normalize::email")]
+//!     #[validate(email)]
+//!     pub email: String,
+//!     #[serde(deserialize_with = "normalize::trim")]
+//!     #[validate(length(min = 1))]
+//!     pub name: String,
+//!     #[serde(default, deserialize_with = "normalize::empty_as_none")]
+//!     pub company: Option<String>,
+//! }
+//! ```
+//!
+//! Other normalizations implement [`Normalize`], which the [`Normalized`]
+//! extractor applies after deserializing and before validating:
+//!
+//! ```rust
+//! use loco_rs::prelude::*;
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize, Validate)]
+//! pub struct Search {
+//!     #[validate(length(max = 10))]
+//!     pub tags: Vec<String>,
+//! }
+//!
+//! impl Normalize for Search {
+//!     fn normalize(&mut self) {
+//!         self.tags.sort();
+//!         self.tags.dedup();
+//!     }
+//! }
+//!
+//! async fn search(Normalized(params): Normalized<Search>) -> Result<Response> {
+//!     format::json(params.tags)
+//! }
+//! ```
+use axum::extract::{FromRequest, Request};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+use validator::Validate;
+
+use crate::{controller::extractor::payload::Payload, Error};
+
+/// Normalizes a deserialized input before it is validated.
+pub trait Normalize {
+    fn normalize(&mut self);
+}
+
+/// Deserializes the request body like [`Payload`], normalizes it with
+/// [`Normalize`], then validates it like
+/// [`crate::controller::extractor::validate::Validated`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Normalized<T>(pub T);
+
+impl<T, S> FromRequest<S> for Normalized<T>
+where
+    T: DeserializeOwned + Normalize + Validate,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Payload(mut value) = Payload::<T>::from_request(req, state).await?;
+        value.normalize();
+        value.validate()?;
+        Ok(Self(value))
+    }
+}
+
+/// Deserializes a string without its leading and trailing whitespace.
+///
+/// # Errors
+///
+/// When the value is not a string.
+pub fn trim<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    Ok(value.trim().to_string())
+}
+
+/// Deserializes a string in lowercase.
+///
+/// # Errors
+///
+/// When the value is not a string.
+pub fn lowercase<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    Ok(value.to_lowercase())
+}
+
+/// Deserializes an email address trimmed and in lowercase.
+///
+/// # Errors
+///
+/// When the value is not a string.
+pub fn email<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    Ok(value.trim().to_lowercase())
+}
+
+/// Deserializes a string trimmed, with its inner runs of whitespace
+/// collapsed into single spaces.
+///
+/// # Errors
+///
+/// When the value is not a string.
+pub fn collapse_whitespace<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    Ok(value.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// Deserializes an optional string trimmed, and blank strings as `None`.
+/// Use with `#[serde(default)]` for the field to be optional.
+///
+/// # Errors
+///
+/// When the value is neither a string nor null.
+pub fn empty_as_none<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    let value = Option::<String>::deserialize(deserializer)?;
+    Ok(value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Request as HttpRequest},
+    };
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, Validate)]
+    struct Signup {
+        #[serde(deserialize_with = "email")]
+        #[validate(email)]
+        email: String,
+        #[serde(deserialize_with = "collapse_whitespace")]
+        #[validate(length(min = 1))]
+        name: String,
+        #[serde(default, deserialize_with = "empty_as_none")]
+        company: Option<String>,
+        #[validate(length(max = 2))]
+        tags: Vec<String>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Contact {
+        #[serde(deserialize_with = "email")]
+        email: String,
+        #[serde(default, deserialize_with = "empty_as_none")]
+        company: Option<String>,
+    }
+
+    impl Normalize for Signup {
+        fn normalize(&mut self) {
+            self.tags.sort();
+            self.tags.dedup();
+        }
+    }
+
+    fn request(content_type: &str, body: &str) -> HttpRequest<Body> {
+        HttpRequest::post("/")
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn normalizes_before_validating() {
+        let body = r#"{"email": "  Ada@Example.COM ", "name": " Ada   Lovelace ", "company": "  ", "tags": ["b", "a", "b", "a"]}"#;
+        let Normalized(signup) =
+            Normalized::<Signup>::from_request(request("application/json", body), &())
+                .await
+                .unwrap();
+        assert_eq!(signup.email, "ada@example.com");
+        assert_eq!(signup.name, "Ada Lovelace");
+        assert_eq!(signup.company, None);
+        assert_eq!(signup.tags, vec!["a", "b"]);
+
+        let form = "email=+Ada%40Example.com&company=+Loco+";
+        let Payload(contact) = Payload::<Contact>::from_request(
+            request("application/x-www-form-urlencoded", form),
+            &(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(contact.email, "ada@example.com");
+        assert_eq!(contact.company.as_deref(), Some("Loco"));
+    }
+
+    #[tokio::test]
+    async fn rejects_values_invalid_once_normalized() {
+        let body = r#"{"email": "ada@example.com", "name": "   ", "tags": []}"#;
+        let result =
+            Normalized::<Signup>::from_request(request("application/json", body), &()).await;
+        assert!(matches!(result, Err(Error::ValidationError(_))));
+    }
+}
@@ -0,0 +1,160 @@
+//! # Path
+//!
+//! An extractor for typed path parameters, a drop-in for
+//! [`axum::extract::Path`] rejecting with the structured errors of the other
+//! extractors:
+//!
+//! * a parameter that cannot be parsed into its type, like `/users/abc` for
+//!   an `i64` id, is a `404 Not Found`, as such a resource cannot exist,
+//! * other failures, like a wrong number of parameters, are a
+//!   `400 Bad Request`.
+//!
+//! Several parameters are extracted as a tuple, or by name into a struct.
+//!
+//! # Example:
+//!
+//! ```rust
+//! use loco_rs::{controller::extractor::path::Path, prelude::*};
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! pub struct CommentPath {
+//!     pub post_id: i64,
+//!     pub id: i64,
+//! }
+//!
+//! async fn show(Path(id): Path<i64>) -> Result<Response> {
+//!     format::text(&id.to_string())
+//! }
+//!
+//! async fn show_comment(Path(path): Path<CommentPath>) -> Result<Response> {
+//!     format::text(&format!("{}/{}", path.post_id, path.id))
+//! }
+//! ```
+use axum::{
+    extract::{path::ErrorKind, rejection::PathRejection, FromRequestParts},
+    http::request::Parts,
+};
+use serde::de::DeserializeOwned;
+
+use crate::Error;
+
+/// Deserializes the path parameters of the route into `T`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Path<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match axum::extract::Path::<T>::from_request_parts(parts, state).await {
+            Ok(axum::extract::Path(value)) => Ok(Self(value)),
+            Err(rejection) => Err(rejection_error(&rejection)),
+        }
+    }
+}
+
+/// Maps a path rejection to the error of the response.
+fn rejection_error(rejection: &PathRejection) -> Error {
+    let PathRejection::FailedToDeserializePathParams(err) = rejection else {
+        // the route has no parameters, a bug of the app rather than the request
+        tracing::error!(err = rejection.body_text(), "path rejection");
+        return Error::InternalServerError;
+    };
+    tracing::debug!(err = err.body_text(), "path rejection");
+    match err.kind() {
+        ErrorKind::ParseErrorAtKey { .. }
+        | ErrorKind::ParseErrorAtIndex { .. }
+        | ErrorKind::ParseError { .. }
+        | ErrorKind::DeserializeError { .. } => Error::NotFound,
+        _ => Error::BadRequest(err.body_text()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+        routing::get,
+        Router,
+    };
+    use serde::Deserialize;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct CommentPath {
+        post_id: i64,
+        id: i64,
+    }
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/users/{id}",
+                get(|Path(id): Path<i64>| async move { id.to_string() }),
+            )
+            .route(
+                "/posts/{post_id}/comments/{id}",
+                get(|Path(path): Path<CommentPath>| async move {
+                    format!("{}/{}", path.post_id, path.id)
+                }),
+            )
+            .route(
+                "/pairs/{a}/{b}",
+                get(|Path((a, b)): Path<(String, u32)>| async move { format!("{a}:{b}") }),
+            )
+            .route(
+                "/wrong/{id}",
+                get(|Path(path): Path<(i64, i64)>| async move { format!("{path:?}") }),
+            )
+    }
+
+    async fn call(uri: &str) -> (StatusCode, String) {
+        let response = app()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn can_extract_path_params() {
+        assert_eq!(call("/users/42").await, (StatusCode::OK, "42".to_string()));
+        assert_eq!(
+            call("/posts/1/comments/2").await,
+            (StatusCode::OK, "1/2".to_string())
+        );
+        assert_eq!(
+            call("/pairs/loco/3").await,
+            (StatusCode::OK, "loco:3".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn unparseable_params_are_not_found() {
+        let (status, body) = call("/users/abc").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(body.contains("not_found"), "{body}");
+
+        assert_eq!(call("/posts/1/comments/x").await.0, StatusCode::NOT_FOUND);
+        assert_eq!(call("/pairs/loco/-1").await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn wrong_params_are_bad_requests() {
+        let (status, body) = call("/wrong/1").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("Wrong number of path arguments"), "{body}");
+    }
+}
@@ -0,0 +1,290 @@
+//! # Payload
+//!
+//! An extractor deserializing the request body according to its
+//! `Content-Type`, so one handler can accept several input encodings, for
+//! example a webhook sending either JSON or a form, or an endpoint serving
+//! both HTML forms and API clients:
+//!
+//! * `application/json` (and `+json` types),
+//! * `application/x-www-form-urlencoded`,
+//! * `multipart/form-data`, from its text fields. File fields are ignored,
+//!   use [`axum::extract::Multipart`] to handle uploads,
+//! * `application/msgpack`, with the `msgpack` feature.
+//!
+//! Other content types are rejected with `415 Unsupported Media Type`, bodies
+//! that cannot be deserialized with `400 Bad Request`, and bodies over the
+//! payload limit with `413 Payload Too Large`.
+//!
+//! # Example:
+//!
+//! ```rust
+//! use loco_rs::{controller::extractor::payload::Payload, prelude::*};
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! pub struct Subscribe {
+//!     pub email: String,
+//! }
+//!
+//! async fn subscribe(Payload(params): Payload<Subscribe>) -> Result<Response> {
+//!     format::text(&params.email)
+//! }
+//! ```
+use axum::{
+    extract::{Form, FromRequest, Multipart, Request},
+    http::{header, StatusCode},
+};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    controller::{json, ErrorDetail},
+    Error,
+};
+
+/// The body encodings understood by [`Payload`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Json,
+    Form,
+    Multipart,
+    #[cfg(feature = "msgpack")]
+    MsgPack,
+}
+
+impl Encoding {
+    fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        match mime.as_str() {
+            "application/x-www-form-urlencoded" => Some(Self::Form),
+            "multipart/form-data" => Some(Self::Multipart),
+            #[cfg(feature = "msgpack")]
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(Self::MsgPack)
+            }
+            mime if json::is_json(mime) => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Deserializes the request body into `T`, whatever its supported encoding.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Payload<T>(pub T);
+
+impl<T, S> FromRequest<S> for Payload<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        let Some(encoding) = Encoding::from_content_type(content_type) else {
+            return Err(unsupported_media_type(content_type));
+        };
+
+        let value = match encoding {
+            Encoding::Json => {
+                let bytes = bytes::Bytes::from_request(req, state)
+                    .await
+                    .map_err(|err| rejection(err.status(), err.body_text()))?;
+                json::from_slice(&bytes).map_err(|err| {
+                    Error::BadRequest(format!("Failed to deserialize the JSON body: {err}"))
+                })?
+            }
+            Encoding::Form => {
+                let Form(value) = Form::<T>::from_request(req, state)
+                    .await
+                    .map_err(|err| rejection(err.status(), err.body_text()))?;
+                value
+            }
+            Encoding::Multipart => {
+                let multipart = Multipart::from_request(req, state)
+                    .await
+                    .map_err(|err| rejection(err.status(), err.body_text()))?;
+                from_multipart(multipart).await?
+            }
+            #[cfg(feature = "msgpack")]
+            Encoding::MsgPack => {
+                let bytes = bytes::Bytes::from_request(req, state)
+                    .await
+                    .map_err(|err| rejection(err.status(), err.body_text()))?;
+                rmp_serde::from_slice(&bytes).map_err(|err| {
+                    Error::BadRequest(format!("Failed to deserialize the MessagePack body: {err}"))
+                })?
+            }
+        };
+        Ok(Self(value))
+    }
+}
+
+/// Deserializes the text fields of a multipart body the same way as a form.
+async fn from_multipart<T: DeserializeOwned>(mut multipart: Multipart) -> crate::Result<T> {
+    let mut fields = Vec::new();
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|err| rejection(err.status(), err.body_text()))?
+    {
+        if field.file_name().is_some() {
+            continue;
+        }
+        let Some(name) = field.name().map(ToString::to_string) else {
+            continue;
+        };
+        let value = field
+            .text()
+            .await
+            .map_err(|err| rejection(err.status(), err.body_text()))?;
+        fields.push((name, value));
+    }
+
+    let encoded = serde_urlencoded::to_string(&fields).map_err(Error::msg)?;
+    serde_urlencoded::from_str(&encoded).map_err(|err| {
+        Error::BadRequest(format!("Failed to deserialize the multipart body: {err}"))
+    })
+}
+
+/// A `400 Bad Request` for a body that could not be read, or a `413 Payload
+/// Too Large` for a body over the payload limit.
+fn rejection(status: StatusCode, body_text: String) -> Error {
+    if status == StatusCode::PAYLOAD_TOO_LARGE {
+        Error::CustomError(
+            status,
+            ErrorDetail::new("payload_too_large", "Request body is too large"),
+        )
+    } else {
+        Error::BadRequest(body_text)
+    }
+}
+
+fn unsupported_media_type(content_type: &str) -> Error {
+    let description = if content_type.is_empty() {
+        "Missing `Content-Type` header".to_string()
+    } else {
+        format!("Unsupported `Content-Type`: {content_type}")
+    };
+    Error::CustomError(
+        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        ErrorDetail::new("unsupported_media_type", &description),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request as HttpRequest, response::IntoResponse};
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Subscribe {
+        email: String,
+        count: u32,
+    }
+
+    fn request(content_type: &str, body: impl Into<Body>) -> HttpRequest<Body> {
+        HttpRequest::post("/")
+            .header(header::CONTENT_TYPE, content_type)
+            .body(body.into())
+            .unwrap()
+    }
+
+    async fn extract(request: HttpRequest<Body>) -> Result<Subscribe, Error> {
+        Payload::<Subscribe>::from_request(request, &())
+            .await
+            .map(|Payload(value)| value)
+    }
+
+    fn expected() -> Subscribe {
+        Subscribe {
+            email: "user@loco.rs".to_string(),
+            count: 2,
+        }
+    }
+
+    #[tokio::test]
+    async fn can_extract_json_and_form() {
+        let json = request(
+            "application/json; charset=utf-8",
+            r#"{"email":"user@loco.rs","count":2}"#,
+        );
+        assert_eq!(extract(json).await.unwrap(), expected());
+
+        let form = request(
+            "application/x-www-form-urlencoded",
+            "email=user%40loco.rs&count=2",
+        );
+        assert_eq!(extract(form).await.unwrap(), expected());
+    }
+
+    #[tokio::test]
+    async fn can_extract_multipart_text_fields() {
+        let body = "--BOUNDARY\r\n\
+             Content-Disposition: form-data; name=\"email\"\r\n\r\n\
+             user@loco.rs\r\n\
+             --BOUNDARY\r\n\
+             Content-Disposition: form-data; name=\"count\"\r\n\r\n\
+             2\r\n\
+             --BOUNDARY\r\n\
+             Content-Disposition: form-data; name=\"avatar\"; filename=\"a.png\"\r\n\
+             Content-Type: image/png\r\n\r\n\
+             PNG\r\n\
+             --BOUNDARY--\r\n";
+        let multipart = request("multipart/form-data; boundary=BOUNDARY", body);
+        assert_eq!(extract(multipart).await.unwrap(), expected());
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[tokio::test]
+    async fn can_extract_msgpack() {
+        let body = rmp_serde::to_vec_named(&serde_json::json!({
+            "email": "user@loco.rs",
+            "count": 2,
+        }))
+        .unwrap();
+        let msgpack = request("application/msgpack", body);
+        assert_eq!(extract(msgpack).await.unwrap(), expected());
+    }
+
+    #[tokio::test]
+    async fn rejects_unsupported_content_type() {
+        let err = extract(request("text/plain", "email")).await.unwrap_err();
+        assert_eq!(
+            err.into_response().status(),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+
+        let missing = HttpRequest::post("/").body(Body::empty()).unwrap();
+        assert_eq!(
+            extract(missing).await.unwrap_err().into_response().status(),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_body_as_bad_request() {
+        let err = extract(request("application/json", r#"{"email":1}"#))
+            .await
+            .unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::BAD_REQUEST);
+
+        let err = extract(request(
+            "application/x-www-form-urlencoded",
+            "email=a&count=x",
+        ))
+        .await
+        .unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::BAD_REQUEST);
+    }
+}
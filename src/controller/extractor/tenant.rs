@@ -0,0 +1,63 @@
+//! An extractor for a database transaction scoped to the tenant of the
+//! request, as bound by the tenant middleware.
+//!
+//! ```rust,ignore
+//! async fn list(db: TenantDb) -> Result<Response> {
+//!     let notes = notes::Entity::find().all(&*db).await?;
+//!     db.commit().await?;
+//!     format::json(notes)
+//! }
+//! ```
+use std::ops::Deref;
+
+use axum::{
+    extract::{FromRef, FromRequestParts},
+    http::request::Parts,
+};
+use sea_orm::DatabaseTransaction;
+
+use crate::{
+    app::AppContext, controller::middleware::tenant::CurrentTenant, tenancy, Error, Result,
+};
+
+/// A transaction with the schema of the request tenant first in its
+/// `search_path`. It is rolled back when dropped, unless committed.
+#[derive(Debug)]
+pub struct TenantDb {
+    pub tenant: String,
+    pub txn: DatabaseTransaction,
+}
+
+impl TenantDb {
+    /// Commits the transaction.
+    ///
+    /// # Errors
+    ///
+    /// When the transaction could not be committed
+    pub async fn commit(self) -> Result<()> {
+        Ok(self.txn.commit().await?)
+    }
+}
+
+impl Deref for TenantDb {
+    type Target = DatabaseTransaction;
+
+    fn deref(&self) -> &Self::Target {
+        &self.txn
+    }
+}
+
+impl<S> FromRequestParts<S> for TenantDb
+where
+    AppContext: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        let CurrentTenant(tenant) = CurrentTenant::from_request_parts(parts, state).await?;
+        let ctx = AppContext::from_ref(state);
+        let txn = tenancy::scoped(ctx.db()?, &tenant).await?;
+        Ok(Self { tenant, txn })
+    }
+}
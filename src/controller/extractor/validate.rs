@@ -1,5 +1,8 @@
-use crate::Error;
-use axum::extract::{Form, FromRequest, Json, Request};
+use crate::{
+    controller::{extractor::payload::Payload, Json},
+    Error,
+};
+use axum::extract::{Form, FromRequest, Request};
 use serde::de::DeserializeOwned;
 use validator::Validate;
 
@@ -77,6 +80,27 @@ where
     }
 }
 
+/// Deserializes the request body like [`Payload`], then validates it.
+///
+/// Invalid values are rejected with `422 Unprocessable Entity` and their
+/// errors by field path, see [`crate::validation::error_detail`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Validated<T>(pub T);
+
+impl<T, S> FromRequest<S> for Validated<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Payload(value) = Payload::<T>::from_request(req, state).await?;
+        value.validate()?;
+        Ok(Self(value))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -164,6 +188,8 @@ mod tests {
         assert!(result.is_err());
 
         let expected = json!({
+            "error": "unprocessable_entity",
+            "description": "validation failed",
             "errors": {
                 "username": [
                     {
@@ -187,8 +213,12 @@ mod tests {
             }
         });
 
-        assert_response_status_and_body(result.unwrap_err(), StatusCode::BAD_REQUEST, expected)
-            .await;
+        assert_response_status_and_body(
+            result.unwrap_err(),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            expected,
+        )
+        .await;
     }
 
     #[tokio::test]
@@ -213,6 +243,8 @@ mod tests {
         assert!(result.is_err());
 
         let expected = json!({
+            "error": "unprocessable_entity",
+            "description": "validation failed",
             "errors": {
                 "username": [
                     {
@@ -236,8 +268,12 @@ mod tests {
             }
         });
 
-        assert_response_status_and_body(result.unwrap_err(), StatusCode::BAD_REQUEST, expected)
-            .await;
+        assert_response_status_and_body(
+            result.unwrap_err(),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            expected,
+        )
+        .await;
     }
 
     #[tokio::test]
@@ -346,4 +382,68 @@ mod tests {
         )
         .await;
     }
+
+    #[derive(Debug, Deserialize, Validate)]
+    struct Address {
+        #[validate(length(min = 2, message = "city is too short"))]
+        city: String,
+    }
+
+    #[derive(Debug, Deserialize, Validate)]
+    struct Item {
+        #[validate(range(min = 1))]
+        quantity: u32,
+    }
+
+    #[derive(Debug, Deserialize, Validate)]
+    struct Order {
+        #[validate(nested)]
+        address: Address,
+        #[validate(nested)]
+        items: Vec<Item>,
+    }
+
+    #[tokio::test]
+    async fn test_validated_valid() {
+        let request = create_form_request("username=valid_user&email=test%40example.com");
+        let Validated(user) = Validated::<TestUser>::from_request(request, &())
+            .await
+            .unwrap();
+        assert_eq!(user.username, "valid_user");
+    }
+
+    #[tokio::test]
+    async fn test_validated_flattens_nested_errors() {
+        let request = create_json_request(
+            r#"{"address": {"city": "x"}, "items": [{"quantity": 1}, {"quantity": 0}]}"#,
+        );
+        let result = Validated::<Order>::from_request(request, &()).await;
+
+        let expected = json!({
+            "error": "unprocessable_entity",
+            "description": "validation failed",
+            "errors": {
+                "address.city": [
+                    {
+                        "code": "length",
+                        "message": "city is too short",
+                        "params": {"min": 2, "value": "x"}
+                    }
+                ],
+                "items.1.quantity": [
+                    {
+                        "code": "range",
+                        "message": "invalid range",
+                        "params": {"min": 1, "value": 0}
+                    }
+                ]
+            }
+        });
+        assert_response_status_and_body(
+            result.unwrap_err(),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            expected,
+        )
+        .await;
+    }
 }
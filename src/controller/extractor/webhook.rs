@@ -0,0 +1,528 @@
+//! # Webhook
+//!
+//! An extractor verifying the HMAC-SHA256 signature of a webhook request
+//! (Stripe, GitHub and alike) over its raw body, before deserializing the
+//! JSON body for the handler. Webhooks are configured by name under
+//! `auth.webhooks`, with their secret, signature header and scheme:
+//!
+//! ```yaml
+//! auth:
+//!   webhooks:
+//!     stripe:
+//!       secret: {{ get_env(name="STRIPE_WEBHOOK_SECRET") }}
+//!       header: stripe-signature
+//!       scheme: stripe
+//!       tolerance: 300
+//!     github:
+//!       secret: {{ get_env(name="GITHUB_WEBHOOK_SECRET") }}
+//!       header: x-hub-signature-256
+//! ```
+//!
+//! Requests with a missing or invalid signature, or a signing timestamp older
+//! than `tolerance` seconds, are rejected with `401 Unauthorized`.
+//!
+//! Providers sending encoded, compressed or wrapped payloads list the steps
+//! decoding the body under `decode`, run in order once the signature is
+//! verified over the body as received:
+//!
+//! ```yaml
+//! auth:
+//!   webhooks:
+//!     logs:
+//!       secret: {{ get_env(name="LOGS_WEBHOOK_SECRET") }}
+//!       header: x-signature
+//!       decode:
+//!         - envelope: data
+//!         - base64
+//!         - gzip
+//! ```
+//!
+//! # Example:
+//!
+//! ```rust
+//! use loco_rs::{
+//!     controller::extractor::webhook::{Webhook, WebhookSource},
+//!     prelude::*,
+//! };
+//!
+//! pub struct Stripe;
+//!
+//! impl WebhookSource for Stripe {
+//!     const NAME: &'static str = "stripe";
+//! }
+//!
+//! async fn stripe(webhook: Webhook<Stripe>) -> Result<Response> {
+//!     format::text(webhook.payload["type"].as_str().unwrap_or_default())
+//! }
+//! ```
+use std::{io::Read, marker::PhantomData};
+
+use axum::{
+    body::Bytes,
+    extract::{FromRef, FromRequest, Request},
+    http::{HeaderMap, StatusCode},
+};
+use base64::{
+    alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine,
+};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::de::DeserializeOwned;
+use sha2::Sha256;
+
+use crate::{
+    app::AppContext,
+    config::{Webhook as WebhookConfig, WebhookDecode, WebhookScheme},
+    controller::{middleware::decompression::RequestDecompression, ErrorDetail},
+    Error, Result,
+};
+
+/// Standard base64, padded or not.
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// Names the `auth.webhooks` configuration of a [`Webhook`].
+pub trait WebhookSource {
+    const NAME: &'static str;
+}
+
+/// A webhook request with a verified signature.
+pub struct Webhook<S, T = serde_json::Value> {
+    /// The deserialized JSON payload
+    pub payload: T,
+    /// The raw body the signature was verified against
+    pub body: Bytes,
+    /// The body after the `decode` steps, which the payload is deserialized
+    /// from. The raw body without steps.
+    pub decoded: Bytes,
+    source: PhantomData<S>,
+}
+
+impl<S, T: std::fmt::Debug> std::fmt::Debug for Webhook<S, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Webhook")
+            .field("payload", &self.payload)
+            .field("body", &self.body)
+            .field("decoded", &self.decoded)
+            .finish()
+    }
+}
+
+impl<S, T, St> FromRequest<St> for Webhook<S, T>
+where
+    AppContext: FromRef<St>,
+    St: Send + Sync,
+    S: WebhookSource,
+    T: DeserializeOwned,
+{
+    type Rejection = Error;
+
+    async fn from_request(req: Request, state: &St) -> std::result::Result<Self, Self::Rejection> {
+        let ctx = AppContext::from_ref(state);
+        let config = ctx.config.get_webhook_config(S::NAME)?;
+
+        let headers = req.headers().clone();
+        let body = Bytes::from_request(req, state)
+            .await
+            .map_err(|err| Error::BadRequest(err.body_text()))?;
+        verify(config, &headers, &body, ctx.now())?;
+
+        // decompressed bodies are limited as the request bodies decompressed
+        // by the middleware
+        let max_size = ctx
+            .config
+            .server
+            .middlewares
+            .request_decompression
+            .as_ref()
+            .map_or_else(|| RequestDecompression::default().max_size, |d| d.max_size);
+        let decoded = decode(&config.decode, body.clone(), max_size)?;
+        let payload = serde_json::from_slice(&decoded).map_err(|err| {
+            Error::BadRequest(format!("Failed to deserialize the webhook body: {err}"))
+        })?;
+        Ok(Self {
+            payload,
+            body,
+            decoded,
+            source: PhantomData,
+        })
+    }
+}
+
+/// Verifies the signature of a webhook body, for handlers reading the body
+/// on their own.
+///
+/// # Errors
+/// [`Error::Unauthorized`] when the signature is missing, does not match the
+/// body or its timestamp is outside of the configured tolerance
+pub fn verify(
+    config: &WebhookConfig,
+    headers: &HeaderMap,
+    body: &[u8],
+    now: DateTime<Utc>,
+) -> Result<()> {
+    let header = headers
+        .get(config.header.as_str())
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| unauthorized(&format!("missing `{}` header", config.header)))?;
+
+    let (timestamp, signatures) = match config.scheme {
+        WebhookScheme::Hmac => {
+            let timestamp = config
+                .timestamp_header
+                .as_ref()
+                .map(|name| {
+                    headers
+                        .get(name.as_str())
+                        .and_then(|value| value.to_str().ok())
+                        .ok_or_else(|| unauthorized(&format!("missing `{name}` header")))
+                })
+                .transpose()?;
+            let signature = header.strip_prefix("sha256=").unwrap_or(header);
+            (timestamp, vec![signature])
+        }
+        WebhookScheme::Stripe => {
+            let mut timestamp = None;
+            let mut signatures = Vec::new();
+            for (key, value) in header.split(',').filter_map(|part| part.split_once('=')) {
+                match key.trim() {
+                    "t" => timestamp = Some(value.trim()),
+                    "v1" => signatures.push(value.trim()),
+                    _ => {}
+                }
+            }
+            let timestamp = timestamp.ok_or_else(|| unauthorized("missing signature timestamp"))?;
+            (Some(timestamp), signatures)
+        }
+    };
+
+    if let Some(timestamp) = timestamp {
+        let signed_at = timestamp
+            .parse::<i64>()
+            .map_err(|_| unauthorized(&format!("invalid signature timestamp `{timestamp}`")))?;
+        let age = now.timestamp().abs_diff(signed_at);
+        if age > config.tolerance {
+            return Err(unauthorized(&format!(
+                "signature timestamp is {age}s away from now"
+            )));
+        }
+    }
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(config.secret.as_bytes())
+        .map_err(|err| Error::Message(format!("invalid webhook secret: {err}")))?;
+    if let Some(timestamp) = timestamp {
+        mac.update(timestamp.as_bytes());
+        mac.update(b".");
+    }
+    mac.update(body);
+
+    let valid = signatures.iter().any(|signature| {
+        hex::decode(signature).is_ok_and(|signature| mac.clone().verify_slice(&signature).is_ok())
+    });
+    if valid {
+        Ok(())
+    } else {
+        Err(unauthorized("invalid signature"))
+    }
+}
+
+/// Decodes a verified webhook body with the `decode` steps of its
+/// configuration, for handlers reading the body on their own. Decompressed
+/// bodies are limited to `max_size` bytes.
+///
+/// # Errors
+/// [`Error::BadRequest`] when a step cannot decode the body, and `413 Payload
+/// Too Large` when a decompressed body exceeds `max_size`
+pub fn decode(steps: &[WebhookDecode], body: Bytes, max_size: usize) -> Result<Bytes> {
+    steps.iter().try_fold(body, |body, step| match step {
+        WebhookDecode::Base64 => {
+            // line-wrapped base64 is common
+            let encoded = body
+                .iter()
+                .copied()
+                .filter(|byte| !byte.is_ascii_whitespace())
+                .collect::<Vec<_>>();
+            BASE64
+                .decode(encoded)
+                .map(Bytes::from)
+                .map_err(|err| bad_body("base64", &err.to_string()))
+        }
+        WebhookDecode::Gzip => {
+            decompress(flate2::read::GzDecoder::new(&body[..]), max_size, "gzip")
+        }
+        WebhookDecode::Deflate => decompress(
+            flate2::read::ZlibDecoder::new(&body[..]),
+            max_size,
+            "deflate",
+        ),
+        WebhookDecode::Envelope(path) => {
+            let envelope: serde_json::Value = serde_json::from_slice(&body)
+                .map_err(|err| bad_body("envelope", &err.to_string()))?;
+            let value = path
+                .split('.')
+                .try_fold(&envelope, |value, key| match value {
+                    serde_json::Value::Array(items) => {
+                        key.parse::<usize>().ok().and_then(|index| items.get(index))
+                    }
+                    _ => value.get(key),
+                })
+                .ok_or_else(|| bad_body("envelope", &format!("no `{path}` value")))?;
+            match value {
+                serde_json::Value::String(value) => Ok(Bytes::from(value.clone())),
+                value => Ok(Bytes::from(serde_json::to_vec(value)?)),
+            }
+        }
+    })
+}
+
+fn decompress(reader: impl Read, max_size: usize, step: &str) -> Result<Bytes> {
+    let mut decoded = Vec::new();
+    reader
+        .take(
+            u64::try_from(max_size)
+                .unwrap_or(u64::MAX)
+                .saturating_add(1),
+        )
+        .read_to_end(&mut decoded)
+        .map_err(|err| bad_body(step, &err.to_string()))?;
+    if decoded.len() > max_size {
+        return Err(Error::CustomError(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorDetail::new(
+                "payload_too_large",
+                "The decompressed webhook body is too large",
+            ),
+        ));
+    }
+    Ok(decoded.into())
+}
+
+fn bad_body(step: &str, reason: &str) -> Error {
+    Error::BadRequest(format!(
+        "Failed to decode the webhook body ({step}): {reason}"
+    ))
+}
+
+fn unauthorized(reason: &str) -> Error {
+    Error::Unauthorized(format!("webhook: {reason}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request as HttpRequest, StatusCode},
+        response::IntoResponse,
+    };
+    use chrono::TimeZone;
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{config::Auth, tests_cfg};
+
+    const BODY: &str = r#"{"type":"invoice.paid"}"#;
+
+    struct Stripe;
+
+    impl WebhookSource for Stripe {
+        const NAME: &'static str = "stripe";
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Event {
+        #[serde(rename = "type")]
+        kind: String,
+    }
+
+    fn config(scheme: WebhookScheme) -> WebhookConfig {
+        WebhookConfig {
+            secret: "whsec_loco".to_string(),
+            header: "stripe-signature".to_string(),
+            scheme,
+            timestamp_header: None,
+            tolerance: 300,
+            decode: Vec::new(),
+        }
+    }
+
+    fn sign(payload: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"whsec_loco").unwrap();
+        mac.update(payload.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn headers(name: &str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            axum::http::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+            value.parse().unwrap(),
+        );
+        headers
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000, 0).unwrap()
+    }
+
+    #[test]
+    fn can_verify_hmac_signature() {
+        let config = WebhookConfig {
+            header: "x-hub-signature-256".to_string(),
+            ..config(WebhookScheme::Hmac)
+        };
+        let valid = format!("sha256={}", sign(BODY));
+        assert!(verify(
+            &config,
+            &headers(&config.header, &valid),
+            BODY.as_bytes(),
+            now()
+        )
+        .is_ok());
+        assert!(verify(
+            &config,
+            &headers(&config.header, &sign(BODY)),
+            BODY.as_bytes(),
+            now()
+        )
+        .is_ok());
+
+        let tampered = verify(&config, &headers(&config.header, &valid), b"{}", now());
+        assert!(matches!(tampered, Err(Error::Unauthorized(_))));
+        let missing = verify(&config, &HeaderMap::new(), BODY.as_bytes(), now());
+        assert!(matches!(missing, Err(Error::Unauthorized(_))));
+    }
+
+    #[test]
+    fn can_verify_stripe_signature() {
+        let config = config(WebhookScheme::Stripe);
+        let signed_at = now().timestamp();
+        let signature = sign(&format!("{signed_at}.{BODY}"));
+        let header = format!("t={signed_at},v1=deadbeef,v1={signature}");
+
+        let headers = headers(&config.header, &header);
+        assert!(verify(&config, &headers, BODY.as_bytes(), now()).is_ok());
+        assert!(verify(
+            &config,
+            &headers,
+            BODY.as_bytes(),
+            now() + chrono::Duration::seconds(299)
+        )
+        .is_ok());
+
+        // replays outside the tolerance are rejected
+        let replayed = verify(
+            &config,
+            &headers,
+            BODY.as_bytes(),
+            now() + chrono::Duration::seconds(301),
+        );
+        assert!(matches!(replayed, Err(Error::Unauthorized(_))));
+    }
+
+    #[test]
+    fn can_decode_body() {
+        /// `{"name":"loco"}`, gzip compressed
+        const GZIP_BODY: &[u8] = &[
+            31, 139, 8, 0, 0, 0, 0, 0, 2, 3, 171, 86, 202, 75, 204, 77, 85, 178, 82, 202, 201, 79,
+            206, 87, 170, 5, 0, 139, 92, 127, 175, 15, 0, 0, 0,
+        ];
+        let envelope = serde_json::json!({
+            "records": [{ "data": BASE64.encode(GZIP_BODY) }],
+        });
+        let steps = [
+            WebhookDecode::Envelope("records.0.data".to_string()),
+            WebhookDecode::Base64,
+            WebhookDecode::Gzip,
+        ];
+        let body = Bytes::from(envelope.to_string());
+        assert_eq!(
+            decode(&steps, body.clone(), 1024).unwrap(),
+            r#"{"name":"loco"}"#
+        );
+        assert_eq!(decode(&[], body.clone(), 1024).unwrap(), body);
+
+        // envelopes holding JSON objects
+        let nested = Bytes::from(r#"{"detail":{"type":"invoice.paid"}}"#);
+        assert_eq!(
+            decode(
+                &[WebhookDecode::Envelope("detail".to_string())],
+                nested,
+                1024
+            )
+            .unwrap(),
+            BODY
+        );
+
+        let too_large = decode(&steps, body, 8).unwrap_err();
+        assert_eq!(
+            too_large.into_response().status(),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+        let invalid = decode(&[WebhookDecode::Base64], Bytes::from("!"), 1024);
+        assert!(matches!(invalid, Err(Error::BadRequest(_))));
+        let missing = decode(
+            &[WebhookDecode::Envelope("message".to_string())],
+            Bytes::from(BODY),
+            1024,
+        );
+        assert!(matches!(missing, Err(Error::BadRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn can_extract_verified_payload() {
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        ctx.config.auth = Some(Auth {
+            jwt: None,
+            webhooks: [("stripe".to_string(), config(WebhookScheme::Stripe))].into(),
+        });
+
+        let signed_at = ctx.now().timestamp();
+        let signature = sign(&format!("{signed_at}.{BODY}"));
+        let request = |signature: &str| {
+            HttpRequest::post("/")
+                .header("stripe-signature", format!("t={signed_at},v1={signature}"))
+                .body(Body::from(BODY))
+                .unwrap()
+        };
+
+        let webhook = Webhook::<Stripe, Event>::from_request(request(&signature), &ctx)
+            .await
+            .unwrap();
+        assert_eq!(webhook.payload.kind, "invoice.paid");
+        assert_eq!(webhook.body, BODY);
+
+        let err = Webhook::<Stripe, Event>::from_request(request("deadbeef"), &ctx)
+            .await
+            .unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::UNAUTHORIZED);
+
+        // the signature covers the body as received, before decoding it
+        let encoded = BASE64.encode(BODY);
+        ctx.config.auth = Some(Auth {
+            jwt: None,
+            webhooks: [(
+                "stripe".to_string(),
+                WebhookConfig {
+                    decode: vec![WebhookDecode::Base64],
+                    ..config(WebhookScheme::Stripe)
+                },
+            )]
+            .into(),
+        });
+        let signature = sign(&format!("{signed_at}.{encoded}"));
+        let request = HttpRequest::post("/")
+            .header("stripe-signature", format!("t={signed_at},v1={signature}"))
+            .body(Body::from(encoded.clone()))
+            .unwrap();
+        let webhook = Webhook::<Stripe, Event>::from_request(request, &ctx)
+            .await
+            .unwrap();
+        assert_eq!(webhook.payload.kind, "invoice.paid");
+        assert_eq!(webhook.body, encoded);
+        assert_eq!(webhook.decoded, BODY);
+    }
+}
@@ -20,23 +20,28 @@
 //!    format::json(Health { ok: true })
 //! }
 //! ```
-use std::convert::TryInto;
+use std::{convert::TryInto, ops::Range, path::Path};
 
 use axum::{
     body::Body,
-    http::{header, response::Builder, HeaderName, HeaderValue, StatusCode},
+    http::{header, response::Builder, HeaderMap, HeaderName, HeaderValue, StatusCode},
     response::{Html, IntoResponse, Redirect, Response},
 };
 use axum_extra::extract::cookie::Cookie;
-use bytes::{BufMut, BytesMut};
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_util::{future, Stream, StreamExt};
 use serde::Serialize;
 use serde_json::json;
 
 use crate::{
+    app::AppContext,
     controller::{
+        json,
+        middleware::json_format::JsonFormat,
         views::{self, ViewRenderer},
         Json,
     },
+    storage::drivers::StoreDriver,
     Result,
 };
 
@@ -120,6 +125,76 @@ pub fn empty_json() -> Result<Response> {
     json(json!({}))
 }
 
+/// Respond with a stream of JSON Lines (`application/x-ndjson`): each item of
+/// `stream` is serialized on its own line, and sent as soon as it is ready, so
+/// large exports are never buffered in memory.
+///
+/// The headers are sent before the first item, so an error in the stream,
+/// or one serializing an item, cannot become an error response anymore: it
+/// is logged, and the body is aborted, which the client sees as an
+/// incomplete response rather than a complete but truncated export.
+///
+/// # Example:
+///
+/// A sea-orm stream borrows the connection, so it is read in a task, which
+/// sends the rows through a bounded channel as fast as the client reads them:
+///
+/// ```rust, ignore
+/// use loco_rs::prelude::*;
+///
+/// async fn export(State(ctx): State<AppContext>) -> Result<Response> {
+///     let (tx, rx) = tokio::sync::mpsc::channel(64);
+///     tokio::spawn(async move {
+///         let mut notes = match notes::Entity::find().stream(&ctx.db).await {
+///             Ok(notes) => notes,
+///             Err(err) => return drop(tx.send(Err(err)).await),
+///         };
+///         while let Some(note) = notes.next().await {
+///             if tx.send(note).await.is_err() {
+///                 break; // the client went away
+///             }
+///         }
+///     });
+///     format::ndjson(futures_util::stream::unfold(rx, |mut rx| async {
+///         rx.recv().await.map(|note| (note, rx))
+///     }))
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Currently this function doesn't return any error. this is for feature
+/// functionality
+pub fn ndjson<S, T, E>(stream: S) -> Result<Response>
+where
+    S: Stream<Item = std::result::Result<T, E>> + Send + 'static,
+    T: Serialize,
+    E: std::fmt::Display,
+{
+    let lines = stream
+        .map(|item| {
+            let item = item.map_err(|err| err.to_string())?;
+            let mut line = json::to_vec(&item).map_err(|err| err.to_string())?;
+            line.push(b'\n');
+            Ok(Bytes::from(line))
+        })
+        .scan(false, |failed, line: std::result::Result<Bytes, String>| {
+            if *failed {
+                return future::ready(None);
+            }
+            let line = line.map_err(|err| {
+                tracing::error!(err, "aborting the JSON Lines response");
+                *failed = true;
+                std::io::Error::new(std::io::ErrorKind::Other, err)
+            });
+            future::ready(Some(line))
+        });
+
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "application/x-ndjson")
+        .body(Body::from_stream(lines))?)
+}
+
 /// Returns an HTML response
 ///
 /// # Example:
@@ -356,7 +431,11 @@ impl RenderBuilder {
         T: Serialize,
     {
         let mut buf = BytesMut::with_capacity(128).writer();
-        serde_json::to_writer(&mut buf, &item)?;
+        if let Some(format) = JsonFormat::current() {
+            json::to_writer(&mut buf, &format.apply(serde_json::to_value(item)?))?;
+        } else {
+            json::to_writer(&mut buf, &item)?;
+        }
         let body = Body::from(buf.into_inner().freeze());
         Ok(self
             .response
@@ -406,6 +485,216 @@ pub fn render() -> RenderBuilder {
     RenderBuilder::new()
 }
 
+/// A byte range requested with the `Range` header
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRange {
+    /// No (supported) range was requested, serve the whole content
+    Full,
+    /// A satisfiable range, end exclusive
+    Partial(Range<u64>),
+    /// The range is outside of the content
+    Unsatisfiable,
+}
+
+/// Parses a single range of a `Range` header value against the content size.
+/// Multiple ranges and malformed values fall back to serving the full content,
+/// as allowed by RFC 9110.
+fn parse_range(value: &str, size: u64) -> ByteRange {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+    let (start, end) = (start.trim(), end.trim());
+
+    if start.is_empty() {
+        // suffix range: the last `end` bytes
+        return match end.parse::<u64>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(suffix) if size > 0 => ByteRange::Partial(size.saturating_sub(suffix)..size),
+            Ok(_) => ByteRange::Unsatisfiable,
+            Err(_) => ByteRange::Full,
+        };
+    }
+
+    let Ok(start) = start.parse::<u64>() else {
+        return ByteRange::Full;
+    };
+    let end = if end.is_empty() {
+        size.saturating_sub(1)
+    } else {
+        match end.parse::<u64>() {
+            Ok(end) if end >= start => end.min(size.saturating_sub(1)),
+            _ => return ByteRange::Full,
+        }
+    };
+    if start >= size {
+        return ByteRange::Unsatisfiable;
+    }
+    ByteRange::Partial(start..end + 1)
+}
+
+/// Returns a response serving the content stored at `path`, honoring the
+/// `Range` request header.
+///
+/// * A single satisfiable range is served as `206 Partial Content`
+/// * A range outside of the content is rejected with `416 Range Not
+///   Satisfiable`
+/// * Otherwise the whole content is streamed with `200 OK`
+///
+/// When `server.sendfile` is configured and `store` is on the local disk (see
+/// [`StoreDriver::is_local`]), the content is not read at all: an empty
+/// response carrying the configured header (for example `X-Accel-Redirect`)
+/// is returned, and the reverse proxy serves the file, including ranges, from
+/// `{prefix}/{path}`. The content of other stores, such as cloud or encrypted
+/// ones, is always streamed.
+///
+/// # Example:
+///
+/// ```rust
+/// use std::path::Path;
+///
+/// use axum::http::HeaderMap;
+/// use loco_rs::prelude::*;
+///
+/// async fn download(State(ctx): State<AppContext>, headers: HeaderMap) -> Result<Response> {
+///     let store = ctx.storage.as_store_err("default")?;
+///     format::storage_file(&ctx, store, Path::new("report.pdf"), &headers).await
+/// }
+/// ```
+///
+/// # Errors
+///
+/// This function will return an error if the content could not be read from
+/// the store
+pub async fn storage_file(
+    ctx: &AppContext,
+    store: &dyn StoreDriver,
+    path: &Path,
+    headers: &HeaderMap,
+) -> Result<Response> {
+    let content_type = mime_guess::from_path(path).first_or_octet_stream();
+    let response = Response::builder().header(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(content_type.as_ref())?,
+    );
+
+    if let Some(sendfile) = ctx
+        .config
+        .server
+        .sendfile
+        .as_ref()
+        .filter(|_| store.is_local())
+    {
+        let location = format!(
+            "{}/{}",
+            sendfile.prefix.trim_end_matches('/'),
+            path.display().to_string().trim_start_matches('/')
+        );
+        return Ok(response
+            .header(sendfile.header.as_str(), location)
+            .body(Body::empty())?);
+    }
+
+    let size = store.size(path).await?;
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .map_or(ByteRange::Full, |value| parse_range(value, size));
+    let response = response.header(header::ACCEPT_RANGES, "bytes");
+
+    match range {
+        ByteRange::Full => {
+            let stream = store.get(path).await?.into_stream().await?;
+            Ok(response
+                .header(header::CONTENT_LENGTH, size)
+                .body(Body::from_stream(stream))?)
+        }
+        ByteRange::Partial(range) => {
+            let content_range = format!("bytes {}-{}/{size}", range.start, range.end - 1);
+            let length = range.end - range.start;
+            let stream = store.get(path).await?.into_range_stream(range).await?;
+            Ok(response
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_RANGE, content_range)
+                .header(header::CONTENT_LENGTH, length)
+                .body(Body::from_stream(stream))?)
+        }
+        ByteRange::Unsatisfiable => Ok(response
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{size}"))
+            .body(Body::empty())?),
+    }
+}
+
+/// Serves the file a background job writes to storage, such as a generated
+/// report, once the job has written it.
+///
+/// When the file is in `store`, it is served like [`storage_file`], honoring
+/// the `Range` header. Until then, the job is still generating it, and a
+/// `202 Accepted` response points the client to `status_url`, the endpoint
+/// reporting the status of the job, with a `Location` header, a
+/// `Retry-After` of `retry_after_secs` seconds and a JSON body:
+///
+/// ```json
+/// { "job_id": "01J...", "status": "pending", "status_url": "/api/reports/01J.../status" }
+/// ```
+///
+/// # Example:
+///
+/// ```rust
+/// use std::path::PathBuf;
+///
+/// use axum::http::HeaderMap;
+/// use loco_rs::prelude::*;
+///
+/// async fn download(
+///     State(ctx): State<AppContext>,
+///     Path(job_id): Path<String>,
+///     headers: HeaderMap,
+/// ) -> Result<Response> {
+///     let store = ctx.storage.as_store_err("default")?;
+///     let path = PathBuf::from("reports").join(format!("{job_id}.csv"));
+///     let status_url = format!("/api/reports/{job_id}/status");
+///     format::job_artifact(&ctx, store, &job_id, &path, &headers, &status_url, 5).await
+/// }
+/// ```
+///
+/// # Errors
+///
+/// This function will return an error if the store could not be read
+pub async fn job_artifact(
+    ctx: &AppContext,
+    store: &dyn StoreDriver,
+    job_id: &str,
+    path: &Path,
+    headers: &HeaderMap,
+    status_url: &str,
+    retry_after_secs: u64,
+) -> Result<Response> {
+    if store.exists(path).await? {
+        return storage_file(ctx, store, path, headers).await;
+    }
+    let body = serde_json::to_vec(&json!({
+        "job_id": job_id,
+        "status": "pending",
+        "status_url": status_url,
+    }))?;
+    Ok(Response::builder()
+        .status(StatusCode::ACCEPTED)
+        .header(header::LOCATION, status_url)
+        .header(header::RETRY_AFTER, retry_after_secs)
+        .header(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        )
+        .body(Body::from(body))?)
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -414,7 +703,7 @@ mod tests {
     use tree_fs;
 
     use super::*;
-    use crate::{controller::views::engines::TeraView, prelude::*};
+    use crate::{controller::views::engines::TeraView, prelude::*, tests_cfg};
 
     async fn response_body_to_string(response: Response<Body>) -> String {
         let bytes = axum::body::to_bytes(response.into_body(), 200)
@@ -467,6 +756,36 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn ndjson_response_format() {
+        let rows =
+            futures_util::stream::iter((1..=3).map(|id| Ok::<_, Error>(json!({ "id": id }))));
+        let response = ndjson(rows).unwrap();
+
+        assert_eq!(
+            get_header_from_response(&response, "content-type"),
+            Some("application/x-ndjson".to_string())
+        );
+        assert_eq!(
+            response_body_to_string(response).await,
+            "{\"id\":1}\n{\"id\":2}\n{\"id\":3}\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn ndjson_aborts_on_stream_error() {
+        let rows = futures_util::stream::iter(vec![
+            Ok(json!({ "id": 1 })),
+            Err(Error::string("connection lost")),
+            Ok(json!({ "id": 2 })),
+        ]);
+        let mut body = ndjson(rows).unwrap().into_body().into_data_stream();
+
+        assert_eq!(body.next().await.unwrap().unwrap(), "{\"id\":1}\n");
+        assert!(body.next().await.unwrap().is_err());
+        assert!(body.next().await.is_none());
+    }
+
     #[tokio::test]
     async fn html_response_format() {
         let response_content: &str = "<h1>loco</h1>";
@@ -653,4 +972,167 @@ mod tests {
         assert_debug_snapshot!(response);
         assert_eq!(response_body_to_string(response).await, String::new());
     }
+
+    #[test]
+    fn can_parse_range() {
+        assert_eq!(parse_range("bytes=0-3", 10), ByteRange::Partial(0..4));
+        assert_eq!(parse_range("bytes=4-", 10), ByteRange::Partial(4..10));
+        assert_eq!(parse_range("bytes=-3", 10), ByteRange::Partial(7..10));
+        assert_eq!(parse_range("bytes=5-100", 10), ByteRange::Partial(5..10));
+        assert_eq!(parse_range("bytes=10-", 10), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-0", 10), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-1,4-5", 10), ByteRange::Full);
+        assert_eq!(parse_range("bytes=5-1", 10), ByteRange::Full);
+        assert_eq!(parse_range("items=0-1", 10), ByteRange::Full);
+    }
+
+    async fn storage_response(ctx: &AppContext, range: Option<&str>) -> Response<Body> {
+        let store = crate::storage::drivers::mem::new();
+        let path = std::path::Path::new("notes/loco.txt");
+        store.upload(path, &"loco framework".into()).await.unwrap();
+        let mut headers = HeaderMap::new();
+        if let Some(range) = range {
+            headers.insert(header::RANGE, range.parse().unwrap());
+        }
+        storage_file(ctx, store.as_ref(), path, &headers)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn storage_file_full_response() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let response = storage_response(&ctx, None).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            get_header_from_response(&response, "content-type"),
+            Some("text/plain".to_string())
+        );
+        assert_eq!(
+            get_header_from_response(&response, "accept-ranges"),
+            Some("bytes".to_string())
+        );
+        assert_eq!(
+            get_header_from_response(&response, "content-length"),
+            Some("14".to_string())
+        );
+        assert_eq!(response_body_to_string(response).await, "loco framework");
+    }
+
+    #[tokio::test]
+    async fn storage_file_partial_response() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let response = storage_response(&ctx, Some("bytes=5-")).await;
+
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            get_header_from_response(&response, "content-range"),
+            Some("bytes 5-13/14".to_string())
+        );
+        assert_eq!(
+            get_header_from_response(&response, "content-length"),
+            Some("9".to_string())
+        );
+        assert_eq!(response_body_to_string(response).await, "framework");
+
+        let response = storage_response(&ctx, Some("bytes=-4")).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response_body_to_string(response).await, "work");
+    }
+
+    #[tokio::test]
+    async fn storage_file_unsatisfiable_range() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let response = storage_response(&ctx, Some("bytes=100-")).await;
+
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(
+            get_header_from_response(&response, "content-range"),
+            Some("bytes */14".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn job_artifact_pending_then_served() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let store = crate::storage::drivers::mem::new();
+        let path = std::path::Path::new("reports/42.csv");
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, "bytes=0-3".parse().unwrap());
+
+        let response = job_artifact(&ctx, store.as_ref(), "42", path, &headers, "/jobs/42", 5)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            get_header_from_response(&response, "location"),
+            Some("/jobs/42".to_string())
+        );
+        assert_eq!(
+            get_header_from_response(&response, "retry-after"),
+            Some("5".to_string())
+        );
+        assert_eq!(
+            response_body_to_string(response).await,
+            r#"{"job_id":"42","status":"pending","status_url":"/jobs/42"}"#
+        );
+
+        store.upload(path, &"id,name".into()).await.unwrap();
+        let response = job_artifact(&ctx, store.as_ref(), "42", path, &headers, "/jobs/42", 5)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response_body_to_string(response).await, "id,n");
+    }
+
+    #[tokio::test]
+    async fn storage_file_delegates_to_proxy() {
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        ctx.config.server.sendfile = Some(crate::config::SendFile {
+            header: "X-Accel-Redirect".to_string(),
+            prefix: "/protected/".to_string(),
+        });
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .add_file("notes/loco.txt", "loco framework")
+            .create()
+            .unwrap();
+        let store = crate::storage::drivers::local::new_with_prefix(&tree_fs.root).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, "bytes=5-".parse().unwrap());
+        let response = storage_file(
+            &ctx,
+            store.as_ref(),
+            std::path::Path::new("notes/loco.txt"),
+            &headers,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            get_header_from_response(&response, "x-accel-redirect"),
+            Some("/protected/notes/loco.txt".to_string())
+        );
+        assert_eq!(response_body_to_string(response).await, String::new());
+    }
+
+    #[tokio::test]
+    async fn storage_file_streams_remote_stores() {
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        ctx.config.server.sendfile = Some(crate::config::SendFile {
+            header: "X-Accel-Redirect".to_string(),
+            prefix: "/protected/".to_string(),
+        });
+        // the proxy cannot serve the content of a store off the local disk
+        let response = storage_response(&ctx, Some("bytes=5-")).await;
+
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            get_header_from_response(&response, "x-accel-redirect"),
+            None
+        );
+        assert_eq!(response_body_to_string(response).await, "framework");
+    }
 }
@@ -6,18 +6,22 @@ use axum::{extract::State, response::Response, routing::get};
 use serde::Serialize;
 
 use super::{format, routes::Routes};
-use crate::{app::AppContext, Result};
+use crate::{app::AppContext, boot::ReadinessState, Result};
 
 /// Represents the health status of the application.
 #[derive(Serialize)]
 struct Health {
     pub ok: bool,
+    pub status: ReadinessState,
 }
 
 /// Check the healthiness of the application bt ping to the redis and the DB to
 /// insure that connection
 async fn health(State(ctx): State<AppContext>) -> Result<Response> {
-    let mut is_ok = match ctx.db.ping().await {
+    let status = ctx.readiness.state();
+    // also fails on a read-only database, until the app recovered from a
+    // failover
+    let mut is_ok = match crate::failover::check(&ctx.db).await {
         Ok(()) => true,
         Err(error) => {
             tracing::error!(err.msg = %error, err.detail = ?error, "health_db_ping_error");
@@ -31,7 +35,10 @@ async fn health(State(ctx): State<AppContext>) -> Result<Response> {
             is_ok = false;
         }
     }
-    format::json(Health { ok: is_ok })
+    format::json(Health {
+        ok: is_ok && status == ReadinessState::Ready,
+        status,
+    })
 }
 
 /// Defines and returns the health-related routes.
@@ -0,0 +1,178 @@
+//! # JSON Library
+//!
+//! The library reading and writing the JSON of the [`Json`](super::Json)
+//! extractor and responses, of
+//! [`Payload`](super::extractor::payload::Payload), `format::json`,
+//! `format::render().json` and `format::ndjson`. It is `serde_json` by
+//! default, or `simd-json` with the `simd_json` feature, which parses and
+//! serializes with SIMD instructions, and is faster on large bodies. Both go
+//! through serde, so the types of the app work with either:
+//!
+//! ```toml
+//! loco-rs = { version = "*", features = ["simd_json"] }
+//! ```
+//!
+//! `cargo bench --bench json`, with and without the feature, compares their
+//! throughput.
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, Request},
+    http::{header, StatusCode},
+};
+use serde::de::DeserializeOwned;
+
+pub use self::library::{from_slice, to_vec, to_writer, Error, LIBRARY};
+use crate::controller::ErrorDetail;
+
+#[cfg(not(feature = "simd_json"))]
+mod library {
+    pub use serde_json::{from_slice, to_vec, to_writer, Error};
+
+    /// The name of the JSON library in use.
+    pub const LIBRARY: &str = "serde_json";
+
+    /// Whether `err` is valid JSON of another shape than expected.
+    pub(super) fn is_data_error(err: &Error) -> bool {
+        err.classify() == serde_json::error::Category::Data
+    }
+}
+
+#[cfg(feature = "simd_json")]
+mod library {
+    use serde::de::DeserializeOwned;
+    pub use simd_json::{
+        serde::{to_vec, to_writer},
+        Error,
+    };
+
+    /// The name of the JSON library in use.
+    pub const LIBRARY: &str = "simd-json";
+
+    /// Deserializes `T` from JSON. simd-json parses in place, so `bytes` are
+    /// copied first.
+    ///
+    /// # Errors
+    ///
+    /// When `bytes` is not JSON, or not a `T`
+    pub fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Error> {
+        simd_json::serde::from_slice(&mut bytes.to_vec())
+    }
+
+    /// Whether `err` is valid JSON of another shape than expected.
+    pub(super) fn is_data_error(err: &Error) -> bool {
+        matches!(err.error(), simd_json::ErrorType::Serde(_))
+    }
+}
+
+/// Whether `content_type` is `application/json`, or a `+json` type.
+pub(crate) fn is_json(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    mime == "application/json" || (mime.starts_with("application/") && mime.ends_with("+json"))
+}
+
+/// Deserializes a JSON request body into `T`. Other content types are
+/// rejected with `415 Unsupported Media Type`, malformed JSON with `400 Bad
+/// Request`, JSON that is not a `T` with `422 Unprocessable Entity`, and
+/// bodies over the payload limit with `413 Payload Too Large`.
+pub(crate) async fn from_request<T, S>(req: Request, state: &S) -> crate::Result<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if !is_json(content_type) {
+        return Err(rejection(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Expected request with `Content-Type: application/json`",
+        ));
+    }
+    let bytes = Bytes::from_request(req, state)
+        .await
+        .map_err(|err| rejection(err.status(), &err.body_text()))?;
+    from_slice(&bytes).map_err(|err| {
+        let status = if library::is_data_error(&err) {
+            StatusCode::UNPROCESSABLE_ENTITY
+        } else {
+            StatusCode::BAD_REQUEST
+        };
+        rejection(
+            status,
+            &format!("Failed to deserialize the JSON body: {err}"),
+        )
+    })
+}
+
+fn rejection(status: StatusCode, body_text: &str) -> crate::Error {
+    tracing::debug!(err = body_text, "json rejection");
+    crate::Error::CustomError(status, ErrorDetail::with_reason("Bad Request"))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, response::IntoResponse};
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Note {
+        title: String,
+        tags: Vec<String>,
+    }
+
+    fn request(content_type: &str, body: &'static str) -> Request {
+        Request::post("/")
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    async fn status(request: Request) -> StatusCode {
+        from_request::<Note, _>(request, &())
+            .await
+            .unwrap_err()
+            .into_response()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn can_read_json_bodies() {
+        let note = Note {
+            title: "loco".to_string(),
+            tags: vec!["rust".to_string()],
+        };
+        let body = r#"{"title":"loco","tags":["rust"]}"#;
+        assert_eq!(to_vec(&note).unwrap(), body.as_bytes());
+
+        let read: Note = from_request(request("application/json", body), &())
+            .await
+            .unwrap();
+        assert_eq!(read, note);
+        let read: Note = from_request(request("application/vnd.api+json", body), &())
+            .await
+            .unwrap();
+        assert_eq!(read, note);
+
+        assert_eq!(
+            status(request("text/plain", body)).await,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+        assert_eq!(
+            status(request("application/json", "{\"title\":")).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(request("application/json", r#"{"title":1}"#)).await,
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+    }
+}
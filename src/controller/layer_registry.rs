@@ -0,0 +1,174 @@
+//! This module defines the [`LayerRegistry`], where the app and its
+//! initializers contribute named layers with an order index, instead of
+//! chaining them all in `after_routes`. The layers are applied in a
+//! deterministic sequence when the router is finalized, after the
+//! `after_routes` hooks.
+//!
+//! Layers with a lower order are closer to the handlers: a request meets the
+//! layer with the highest order first. Layers with the same order are applied
+//! in the order they were added.
+//!
+//! Like any `Router::layer`, registry layers run once the request was routed,
+//! so a layer rewriting the request path (such as `NormalizePathLayer`) does
+//! not change which route handles it. Path rewriting layers have to wrap the
+//! whole router instead, in [`crate::app::Hooks::serve`].
+use std::convert::Infallible;
+
+use axum::{extract::Request, response::IntoResponse, routing::Route, Router as AXRouter};
+use tower::{Layer, Service};
+
+use crate::{Error, Result};
+
+type ApplyLayer = Box<dyn FnOnce(AXRouter) -> AXRouter + Send + Sync>;
+
+struct Entry {
+    name: String,
+    order: i32,
+    apply: ApplyLayer,
+}
+
+/// The named layers contributed by the app and its initializers.
+#[derive(Default)]
+pub struct LayerRegistry {
+    entries: Vec<Entry>,
+}
+
+impl std::fmt::Debug for LayerRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+impl LayerRegistry {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a layer named `name`, applied according to its `order`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use loco_rs::controller::LayerRegistry;
+    /// use tower_http::catch_panic::CatchPanicLayer;
+    ///
+    /// let mut layers = LayerRegistry::new();
+    /// layers.add("catch_panic", 10, CatchPanicLayer::new());
+    /// ```
+    pub fn add<L>(&mut self, name: &str, order: i32, layer: L) -> &mut Self
+    where
+        L: Layer<Route> + Clone + Send + Sync + 'static,
+        L::Service: Service<Request> + Clone + Send + Sync + 'static,
+        <L::Service as Service<Request>>::Response: IntoResponse + 'static,
+        <L::Service as Service<Request>>::Error: Into<Infallible> + 'static,
+        <L::Service as Service<Request>>::Future: Send + 'static,
+    {
+        self.entries.push(Entry {
+            name: name.to_string(),
+            order,
+            apply: Box::new(move |router: AXRouter| router.layer(layer)),
+        });
+        self
+    }
+
+    /// Returns the names and orders of the layers, in the sequence they are
+    /// applied (innermost first).
+    #[must_use]
+    pub fn names(&self) -> Vec<(String, i32)> {
+        self.sorted_indexes()
+            .into_iter()
+            .map(|index| (self.entries[index].name.clone(), self.entries[index].order))
+            .collect()
+    }
+
+    fn sorted_indexes(&self) -> Vec<usize> {
+        let mut indexes = (0..self.entries.len()).collect::<Vec<_>>();
+        indexes.sort_by_key(|index| self.entries[*index].order);
+        indexes
+    }
+
+    /// Applies the layers to `router`, innermost first.
+    ///
+    /// # Errors
+    /// When two layers have the same name
+    pub fn apply(self, mut router: AXRouter) -> Result<AXRouter> {
+        let mut entries = self.entries;
+        entries.sort_by_key(|entry| entry.order);
+        for (index, entry) in entries.iter().enumerate() {
+            if entries[..index]
+                .iter()
+                .any(|other| other.name == entry.name)
+            {
+                return Err(Error::Message(format!(
+                    "layer `{}` is registered twice",
+                    entry.name
+                )));
+            }
+        }
+        for entry in entries {
+            tracing::info!(name = entry.name, order = entry.order, "+layer");
+            router = (entry.apply)(router);
+        }
+        Ok(router)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::HeaderValue, routing::get};
+    use tower::ServiceExt;
+    use tower_http::set_header::SetResponseHeaderLayer;
+
+    use super::*;
+
+    fn tag(value: &'static str) -> SetResponseHeaderLayer<HeaderValue> {
+        SetResponseHeaderLayer::appending(
+            axum::http::HeaderName::from_static("x-layer"),
+            HeaderValue::from_static(value),
+        )
+    }
+
+    #[tokio::test]
+    async fn can_apply_layers_in_order() {
+        let mut layers = LayerRegistry::new();
+        layers
+            .add("outer", 20, tag("outer"))
+            .add("inner", -5, tag("inner"))
+            .add("middle", 10, tag("middle"))
+            .add("middle-2", 10, tag("middle-2"));
+        assert_eq!(
+            layers.names(),
+            vec![
+                ("inner".to_string(), -5),
+                ("middle".to_string(), 10),
+                ("middle-2".to_string(), 10),
+                ("outer".to_string(), 20),
+            ]
+        );
+
+        let router = layers
+            .apply(AXRouter::new().route("/", get(|| async { "loco" })))
+            .unwrap();
+        let response = router
+            .oneshot(Request::get("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        // the innermost layer sees the response first
+        let tags = response
+            .headers()
+            .get_all("x-layer")
+            .iter()
+            .map(|value| value.to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(tags, vec!["inner", "middle", "middle-2", "outer"]);
+    }
+
+    #[test]
+    fn rejects_duplicate_names() {
+        let mut layers = LayerRegistry::new();
+        layers.add("tag", 1, tag("a")).add("tag", 2, tag("b"));
+        let err = layers.apply(AXRouter::new()).unwrap_err();
+        assert!(err.to_string().contains("layer `tag` is registered twice"));
+    }
+}
@@ -0,0 +1,180 @@
+//! # Long Polling
+//!
+//! For clients which can use neither Server-Sent Events nor WebSockets: the
+//! request is held open until an event arrives, and responds with it as JSON,
+//! or with `204 No Content` after a timeout, for the client to poll again.
+//!
+//! * The number of requests held at once is capped. Requests above the cap
+//!   are refused right away with `503 Service Unavailable`.
+//! * When the client disconnects the handler is dropped, which releases its
+//!   permit and its subscription.
+//! * The wait never outlives the [deadline](crate::deadline) of the request.
+//!
+//! # Example:
+//!
+//! ```rust
+//! use std::time::Duration;
+//!
+//! use axum::extract::State;
+//! use loco_rs::{controller::long_poll::LongPoll, prelude::*};
+//! use tokio::sync::broadcast;
+//!
+//! #[derive(Clone)]
+//! struct Notifications(broadcast::Sender<String>, LongPoll);
+//!
+//! async fn poll(State(ctx): State<AppContext>) -> Result<Response> {
+//!     let Notifications(sender, long_poll) = ctx
+//!         .shared_store
+//!         .get::<Notifications>()
+//!         .ok_or_else(|| Error::string("notifications are not configured"))?;
+//!     long_poll.recv(sender.subscribe()).await
+//! }
+//! ```
+//!
+//! Events broadcast between two polls of a client are not delivered to it. A
+//! client which cannot miss events polls with the cursor of the last event it
+//! received, and the handler waits with [`LongPoll::wait`] only when there is
+//! nothing newer to return right away.
+use std::{future::Future, pin::pin, sync::Arc, time::Duration};
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use futures_util::StreamExt;
+use serde::Serialize;
+use tokio::sync::{broadcast, Semaphore};
+
+use super::{format, sse, ErrorDetail};
+use crate::{deadline::Deadline, Error, Result};
+
+/// The time a request is held, waiting for an event.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The number of requests held at once.
+pub const DEFAULT_MAX_HELD: usize = 1024;
+
+/// Holds requests open until an event arrives, see the [module
+/// documentation](self). Clones share the same cap of held requests.
+#[derive(Debug, Clone)]
+pub struct LongPoll {
+    permits: Arc<Semaphore>,
+    max_held: usize,
+    timeout: Duration,
+}
+
+impl Default for LongPoll {
+    fn default() -> Self {
+        Self::new(DEFAULT_TIMEOUT, DEFAULT_MAX_HELD)
+    }
+}
+
+impl LongPoll {
+    /// Holds up to `max_held` requests at once, each for up to `timeout`.
+    #[must_use]
+    pub fn new(timeout: Duration, max_held: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max_held)),
+            max_held,
+            timeout,
+        }
+    }
+
+    /// The number of requests held now.
+    #[must_use]
+    pub fn held(&self) -> usize {
+        self.max_held - self.permits.available_permits()
+    }
+
+    /// Waits for the next message of a broadcast subscription, see
+    /// [`LongPoll::wait`]. The response is empty when all the senders are
+    /// dropped.
+    ///
+    /// # Errors
+    ///
+    /// As [`LongPoll::wait`]
+    pub async fn recv<T>(&self, receiver: broadcast::Receiver<T>) -> Result<Response>
+    where
+        T: Clone + Serialize + Send + 'static,
+    {
+        self.wait(async move { pin!(sse::subscribe(receiver)).next().await })
+            .await
+    }
+
+    /// Waits for `event`, and responds with its output as JSON, or with `204
+    /// No Content` when the timeout, or the request deadline, passes first, or
+    /// when `event` resolves to `None`.
+    ///
+    /// # Errors
+    ///
+    /// With `503 Service Unavailable` when too many requests are held
+    /// already, or when the event could not be serialized
+    pub async fn wait<T, F>(&self, event: F) -> Result<Response>
+    where
+        T: Serialize,
+        F: Future<Output = Option<T>>,
+    {
+        let Ok(_permit) = self.permits.try_acquire() else {
+            tracing::warn!(
+                max_held = self.max_held,
+                "long poll refused: too many requests held"
+            );
+            return Err(Error::CustomError(
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorDetail::new(
+                    "too_many_long_polls",
+                    "The server is busy, please retry later",
+                ),
+            ));
+        };
+        let timeout = Deadline::current().map_or(self.timeout, |deadline| {
+            self.timeout.min(deadline.remaining())
+        });
+        match tokio::time::timeout(timeout, event).await {
+            Ok(Some(event)) => format::json(event),
+            Ok(None) | Err(_) => Ok(StatusCode::NO_CONTENT.into_response()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn can_wait_for_an_event() {
+        let long_poll = LongPoll::new(Duration::from_millis(50), 1);
+        let (sender, receiver) = broadcast::channel(4);
+        let waiting = long_poll.recv(receiver);
+        sender.send("loco".to_string()).unwrap();
+        let response = waiting.await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"\"loco\"");
+
+        let timed_out = long_poll.recv(sender.subscribe()).await.unwrap();
+        assert_eq!(timed_out.status(), StatusCode::NO_CONTENT);
+        assert_eq!(long_poll.held(), 0);
+    }
+
+    #[tokio::test]
+    async fn caps_the_held_requests() {
+        let long_poll = LongPoll::new(Duration::from_secs(5), 1);
+        let (sender, _) = broadcast::channel::<String>(4);
+        let mut held = Box::pin(long_poll.recv(sender.subscribe()));
+        assert!(futures_util::poll!(held.as_mut()).is_pending());
+        assert_eq!(long_poll.held(), 1);
+
+        let refused = long_poll.recv(sender.subscribe()).await.unwrap_err();
+        assert_eq!(
+            refused.into_response().status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        // a client going away releases its permit
+        drop(held);
+        assert_eq!(long_poll.held(), 0);
+    }
+}
@@ -16,6 +16,7 @@ use crate::{
 };
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct CatchPanic {
     #[serde(default)]
     pub enable: bool,
@@ -0,0 +1,339 @@
+//! Request Coalescing Middleware
+//!
+//! This middleware gives expensive `GET` endpoints single-flight behavior:
+//! while a request is in flight, identical requests (same method, URI and
+//! `vary` request headers) wait for it and share its response, instead of
+//! all hitting the backend at once, for example right after a cache expired.
+//!
+//! A waiting request runs on its own, as if the middleware was disabled, when
+//! the in-flight request takes longer than `max_wait` milliseconds, or when
+//! its response cannot be shared: responses setting cookies, marked as
+//! `private` or `no-store`, or with a body of unknown size or larger than
+//! `max_body` bytes.
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    body::{Bytes, HttpBody},
+    extract::Request,
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router as AXRouter,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::broadcast;
+
+use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Result};
+
+/// Request coalescing middleware configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+pub struct Coalesce {
+    #[serde(default)]
+    pub enable: bool,
+    /// The request headers that make two requests different, in addition to
+    /// their method and URI.
+    #[serde(default = "default_vary")]
+    pub vary: Vec<String>,
+    /// How long (in milliseconds) a request waits for an identical in-flight
+    /// request before running on its own.
+    #[serde(default = "default_max_wait")]
+    pub max_wait: u64,
+    /// The largest response body (in bytes) shared between requests.
+    #[serde(default = "default_max_body")]
+    pub max_body: u64,
+}
+
+impl Default for Coalesce {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+fn default_vary() -> Vec<String> {
+    ["accept", "accept-language", "authorization", "cookie"]
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+}
+
+fn default_max_wait() -> u64 {
+    5_000
+}
+
+fn default_max_body() -> u64 {
+    1024 * 1024
+}
+
+/// A response shared with the waiting requests.
+#[derive(Debug)]
+struct SharedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl SharedResponse {
+    fn to_response(&self) -> Response {
+        let mut response = (self.status, self.body.clone()).into_response();
+        *response.headers_mut() = self.headers.clone();
+        response
+    }
+}
+
+type InFlight = Arc<Mutex<HashMap<String, broadcast::Sender<Arc<SharedResponse>>>>>;
+
+/// Removes the in-flight entry of a request when it completes, or when it is
+/// dropped before completing, which lets the waiting requests run on their
+/// own.
+struct InFlightGuard {
+    in_flight: InFlight,
+    key: String,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if let Ok(mut in_flight) = self.in_flight.lock() {
+            in_flight.remove(&self.key);
+        }
+    }
+}
+
+impl MiddlewareLayer for Coalesce {
+    /// Returns the name of the middleware.
+    fn name(&self) -> &'static str {
+        "coalesce"
+    }
+
+    /// Checks if the request coalescing middleware is enabled.
+    fn is_enabled(&self) -> bool {
+        self.enable
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(self)
+    }
+
+    /// Applies the request coalescing middleware to the application router.
+    fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        let config = Arc::new(self.clone());
+        let in_flight = InFlight::default();
+        Ok(app.layer(axum::middleware::from_fn(move |request, next| {
+            coalesce_middleware(config.clone(), in_flight.clone(), request, next)
+        })))
+    }
+}
+
+async fn coalesce_middleware(
+    config: Arc<Coalesce>,
+    in_flight: InFlight,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method() != Method::GET {
+        return next.run(request).await;
+    }
+    let key = signature(&request, &config.vary);
+
+    let waiting = {
+        let Ok(mut in_flight) = in_flight.lock() else {
+            return next.run(request).await;
+        };
+        match in_flight.entry(key.clone()) {
+            Entry::Occupied(entry) => Some(entry.get().subscribe()),
+            Entry::Vacant(entry) => {
+                entry.insert(broadcast::channel(1).0);
+                None
+            }
+        }
+    };
+
+    if let Some(mut receiver) = waiting {
+        let max_wait = Duration::from_millis(config.max_wait);
+        if let Ok(Ok(shared)) = tokio::time::timeout(max_wait, receiver.recv()).await {
+            tracing::debug!(key, "coalesced request");
+            return shared.to_response();
+        }
+        return next.run(request).await;
+    }
+
+    let guard = InFlightGuard {
+        in_flight: in_flight.clone(),
+        key,
+    };
+    let response = next.run(request).await;
+    if !is_shareable(&response, config.max_body) {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let Ok(body) = axum::body::to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let shared = Arc::new(SharedResponse {
+        status: parts.status,
+        headers: parts.headers,
+        body,
+    });
+    let sender = in_flight
+        .lock()
+        .ok()
+        .and_then(|mut in_flight| in_flight.remove(&guard.key));
+    if let Some(sender) = sender {
+        // no receivers is fine, nobody was waiting
+        let _ = sender.send(shared.clone());
+    }
+    shared.to_response()
+}
+
+/// The method, URI and `vary` request headers of a request.
+fn signature(request: &Request, vary: &[String]) -> String {
+    let mut key = format!("{} {}", request.method(), request.uri());
+    for name in vary {
+        key.push('\n');
+        key.push_str(name);
+        for value in request.headers().get_all(name.as_str()) {
+            key.push(':');
+            key.push_str(&String::from_utf8_lossy(value.as_bytes()));
+        }
+    }
+    key
+}
+
+/// Whether a response can be handed to other requests than its own.
+pub(crate) fn is_shareable(response: &Response, max_body: u64) -> bool {
+    let headers = response.headers();
+    if headers.contains_key(header::SET_COOKIE) {
+        return false;
+    }
+    let private = headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|directive| {
+            let directive = directive.trim();
+            directive.eq_ignore_ascii_case("private") || directive.eq_ignore_ascii_case("no-store")
+        });
+    if private {
+        return false;
+    }
+    response
+        .body()
+        .size_hint()
+        .exact()
+        .is_some_and(|size| size <= max_body)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::{body::Body, http::Request as HttpRequest, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    async fn expensive() -> String {
+        let call = CALLS.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        format!("call {call}")
+    }
+
+    async fn private() -> impl IntoResponse {
+        let call = CALLS.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        ([(header::CACHE_CONTROL, "private")], format!("call {call}"))
+    }
+
+    async fn app(max_wait: u64) -> Router {
+        Coalesce {
+            enable: true,
+            max_wait,
+            ..Default::default()
+        }
+        .apply(
+            Router::new()
+                .route("/expensive", get(expensive))
+                .route("/private", get(private)),
+        )
+        .unwrap()
+        .with_state(tests_cfg::app::get_app_context().await)
+    }
+
+    async fn get_body(app: Router, uri: &str, accept: &str) -> String {
+        let request = HttpRequest::get(uri)
+            .header(header::ACCEPT, accept)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    async fn concurrently(app: &Router, requests: &[(&'static str, &'static str)]) -> Vec<String> {
+        let handles = requests
+            .iter()
+            .map(|(uri, accept)| tokio::spawn(get_body(app.clone(), uri, accept)))
+            .collect::<Vec<_>>();
+        let mut bodies = Vec::new();
+        for handle in handles {
+            bodies.push(handle.await.unwrap());
+        }
+        bodies
+    }
+
+    // a single test, since the handlers share the call counter
+    #[tokio::test]
+    async fn can_coalesce_identical_requests() {
+        let app = app(5_000).await;
+
+        let bodies = concurrently(
+            &app,
+            &[
+                ("/expensive", "text/plain"),
+                ("/expensive", "text/plain"),
+                ("/expensive", "text/plain"),
+                ("/expensive", "text/html"),
+            ],
+        )
+        .await;
+        assert_eq!(CALLS.swap(0, Ordering::SeqCst), 2);
+        assert_eq!(bodies[0], bodies[1]);
+        assert_eq!(bodies[1], bodies[2]);
+        assert_ne!(bodies[2], bodies[3]);
+
+        // private responses are not shared
+        let bodies = concurrently(&app, &[("/private", "*/*"), ("/private", "*/*")]).await;
+        assert_eq!(CALLS.swap(0, Ordering::SeqCst), 2);
+        assert_ne!(bodies[0], bodies[1]);
+
+        // requests stop waiting after `max_wait`
+        let app = self::app(10).await;
+        concurrently(&app, &[("/expensive", "*/*"), ("/expensive", "*/*")]).await;
+        assert_eq!(CALLS.swap(0, Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn can_build_signature() {
+        let request = HttpRequest::get("/feed?page=2")
+            .header(header::ACCEPT, "application/json")
+            .header("x-other", "ignored")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(
+            signature(&request, &["accept".to_string(), "cookie".to_string()]),
+            "GET /feed?page=2\naccept:application/json\ncookie"
+        );
+    }
+}
@@ -12,6 +12,7 @@ use tower_http::compression::CompressionLayer;
 use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Result};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct Compression {
     #[serde(default)]
     pub enable: bool,
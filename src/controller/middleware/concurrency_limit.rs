@@ -0,0 +1,198 @@
+//! Concurrency Limit Middleware
+//!
+//! This middleware caps the number of requests in flight, to protect the app
+//! and its downstreams under load. Requests above the cap are not queued: they
+//! are shed right away with `503 Service Unavailable` (or the configured
+//! `status`), so clients can retry or back off.
+//!
+//! The cap applies to all the routes of the app. To cap a group of routes, for
+//! example expensive report generations, use
+//! [`crate::controller::Routes::concurrency_limit`].
+use std::sync::Arc;
+
+use axum::{
+    extract::Request,
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router as AXRouter,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::Semaphore;
+
+use crate::{
+    app::AppContext,
+    controller::{middleware::MiddlewareLayer, ErrorDetail},
+    Error, Result,
+};
+
+/// Concurrency limit middleware configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+pub struct ConcurrencyLimit {
+    #[serde(default)]
+    pub enable: bool,
+    /// The maximum number of requests in flight.
+    #[serde(default = "default_max")]
+    pub max: usize,
+    /// The response status of the shed requests, such as `503` or `429`.
+    #[serde(default = "default_status")]
+    pub status: u16,
+}
+
+impl Default for ConcurrencyLimit {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+fn default_max() -> usize {
+    1024
+}
+
+fn default_status() -> u16 {
+    StatusCode::SERVICE_UNAVAILABLE.as_u16()
+}
+
+/// Sheds the requests above `max` in flight ones. Clones share the same
+/// permits.
+#[derive(Debug, Clone)]
+pub struct Limiter {
+    permits: Arc<Semaphore>,
+    max: usize,
+    status: StatusCode,
+}
+
+impl Limiter {
+    #[must_use]
+    pub fn new(max: usize, status: StatusCode) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max)),
+            max,
+            status,
+        }
+    }
+
+    /// Runs the request when a permit is available, sheds it otherwise.
+    pub async fn run(self, request: Request, next: Next) -> Response {
+        let Ok(_permit) = self.permits.try_acquire_owned() else {
+            tracing::warn!(
+                max = self.max,
+                uri = %request.uri(),
+                "request shed: too many requests in flight"
+            );
+            return Error::CustomError(
+                self.status,
+                ErrorDetail::new(
+                    "too_many_requests_in_flight",
+                    "The server is busy, please retry later",
+                ),
+            )
+            .into_response();
+        };
+        next.run(request).await
+    }
+}
+
+impl MiddlewareLayer for ConcurrencyLimit {
+    /// Returns the name of the middleware.
+    fn name(&self) -> &'static str {
+        "concurrency_limit"
+    }
+
+    /// Checks if the concurrency limit middleware is enabled.
+    fn is_enabled(&self) -> bool {
+        self.enable
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(self)
+    }
+
+    /// Applies the concurrency limit middleware to the application router.
+    fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        let status = StatusCode::from_u16(self.status).map_err(|err| {
+            Error::Message(format!(
+                "concurrency_limit: invalid status `{}`: {err}",
+                self.status
+            ))
+        })?;
+        let limiter = Limiter::new(self.max, status);
+        Ok(app.layer(axum::middleware::from_fn(move |request, next| {
+            limiter.clone().run(request, next)
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::{body::Body, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    async fn slow() -> &'static str {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        "done"
+    }
+
+    async fn statuses(app: &Router, count: usize) -> Vec<StatusCode> {
+        let handles = (0..count)
+            .map(|_| {
+                let app = app.clone();
+                tokio::spawn(async move {
+                    app.oneshot(Request::get("/").body(Body::empty()).unwrap())
+                        .await
+                        .unwrap()
+                        .status()
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut statuses = Vec::new();
+        for handle in handles {
+            statuses.push(handle.await.unwrap());
+        }
+        statuses.sort();
+        statuses
+    }
+
+    #[tokio::test]
+    async fn can_shed_requests_above_max() {
+        let app = ConcurrencyLimit {
+            enable: true,
+            max: 2,
+            status: 429,
+        }
+        .apply(Router::new().route("/", get(slow)))
+        .unwrap()
+        .with_state(tests_cfg::app::get_app_context().await);
+
+        assert_eq!(
+            statuses(&app, 3).await,
+            vec![
+                StatusCode::OK,
+                StatusCode::OK,
+                StatusCode::TOO_MANY_REQUESTS
+            ]
+        );
+        // permits are released once the requests complete
+        assert_eq!(
+            statuses(&app, 2).await,
+            vec![StatusCode::OK, StatusCode::OK]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_status() {
+        let limit = ConcurrencyLimit {
+            enable: true,
+            max: 1,
+            status: 1000,
+        };
+        assert!(limit.apply(Router::new()).is_err());
+    }
+}
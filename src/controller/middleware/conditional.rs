@@ -0,0 +1,214 @@
+//! Conditional Middleware
+//!
+//! Applies a layer only to the requests matching a predicate over their
+//! parts, such as their path, headers or extensions, for cross-cutting
+//! conditions that do not follow the route groups:
+//!
+//! ```rust,ignore
+//! use loco_rs::controller::middleware::conditional::{self, when};
+//! use tower_http::compression::CompressionLayer;
+//!
+//! // in `Hooks::after_routes`
+//! router
+//!     .layer(when(conditional::path_prefix("/api"), CompressionLayer::new()))
+//!     .layer(when(
+//!         |parts: &Parts| parts.headers.contains_key(header::AUTHORIZATION),
+//!         TraceLayer::new_for_http(),
+//!     ))
+//! ```
+//!
+//! Requests not matching go straight to the wrapped routes, and never through
+//! the layer.
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    body::Body,
+    http::{header, request::Parts, HeaderName, Request},
+    response::{IntoResponse, Response},
+};
+use futures_util::future::BoxFuture;
+use tower::{Layer, Service};
+
+/// Wraps `layer` to apply it only to the requests `predicate` matches.
+pub fn when<P, L>(predicate: P, layer: L) -> Conditional<P, L>
+where
+    P: Fn(&Parts) -> bool + Send + Sync + 'static,
+{
+    Conditional {
+        predicate: Arc::new(predicate),
+        layer,
+    }
+}
+
+/// Matches the requests whose path starts with `prefix`.
+pub fn path_prefix(prefix: &str) -> impl Fn(&Parts) -> bool + Send + Sync + 'static {
+    let prefix = prefix.to_string();
+    move |parts| parts.uri.path().starts_with(&prefix)
+}
+
+/// Matches the requests having the `name` header.
+pub fn has_header(name: HeaderName) -> impl Fn(&Parts) -> bool + Send + Sync + 'static {
+    move |parts| parts.headers.contains_key(&name)
+}
+
+/// Matches the requests whose content type is `mime`, ignoring its
+/// parameters such as a charset.
+pub fn content_type(mime: &str) -> impl Fn(&Parts) -> bool + Send + Sync + 'static {
+    let mime = mime.to_string();
+    move |parts| {
+        parts
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .is_some_and(|essence| essence.trim().eq_ignore_ascii_case(&mime))
+    }
+}
+
+/// A layer applied to the requests matching a predicate, see [`when`].
+pub struct Conditional<P, L> {
+    predicate: Arc<P>,
+    layer: L,
+}
+
+impl<P, L: Clone> Clone for Conditional<P, L> {
+    fn clone(&self) -> Self {
+        Self {
+            predicate: self.predicate.clone(),
+            layer: self.layer.clone(),
+        }
+    }
+}
+
+impl<S, P, L> Layer<S> for Conditional<P, L>
+where
+    S: Clone,
+    L: Layer<S>,
+{
+    type Service = ConditionalService<S, L::Service, P>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConditionalService {
+            layered: self.layer.layer(inner.clone()),
+            inner,
+            predicate: self.predicate.clone(),
+        }
+    }
+}
+
+/// The service of a [`Conditional`] layer.
+pub struct ConditionalService<S, T, P> {
+    inner: S,
+    layered: T,
+    predicate: Arc<P>,
+}
+
+impl<S: Clone, T: Clone, P> Clone for ConditionalService<S, T, P> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            layered: self.layered.clone(),
+            predicate: self.predicate.clone(),
+        }
+    }
+}
+
+impl<S, T, P> Service<Request<Body>> for ConditionalService<S, T, P>
+where
+    S: Service<Request<Body>>,
+    S::Response: IntoResponse,
+    S::Future: Send + 'static,
+    T: Service<Request<Body>, Error = S::Error>,
+    T::Response: IntoResponse,
+    T::Future: Send + 'static,
+    P: Fn(&Parts) -> bool,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.inner.poll_ready(cx)?.is_pending() {
+            return Poll::Pending;
+        }
+        self.layered.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let (parts, body) = request.into_parts();
+        let matches = (self.predicate)(&parts);
+        let request = Request::from_parts(parts, body);
+        if matches {
+            let future = self.layered.call(request);
+            Box::pin(async move { Ok(future.await?.into_response()) })
+        } else {
+            let future = self.inner.call(request);
+            Box::pin(async move { Ok(future.await?.into_response()) })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{http::HeaderValue, middleware::map_response, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    const LAYERED: HeaderName = HeaderName::from_static("x-layered");
+
+    async fn mark(mut response: Response) -> Response {
+        response
+            .headers_mut()
+            .insert(LAYERED, HeaderValue::from_static("1"));
+        response
+    }
+
+    async fn is_layered(app: &Router, request: Request<Body>) -> bool {
+        let response = app.clone().oneshot(request).await.unwrap();
+        response.headers().contains_key(LAYERED)
+    }
+
+    #[tokio::test]
+    async fn applies_layer_to_matching_requests() {
+        let app = Router::new()
+            .route("/api/users", get(|| async { "users" }))
+            .route("/home", get(|| async { "home" }))
+            .layer(when(path_prefix("/api"), map_response(mark)));
+
+        let api = Request::get("/api/users").body(Body::empty()).unwrap();
+        assert!(is_layered(&app, api).await);
+        let home = Request::get("/home").body(Body::empty()).unwrap();
+        assert!(!is_layered(&app, home).await);
+    }
+
+    #[tokio::test]
+    async fn can_match_headers() {
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(when(has_header(header::AUTHORIZATION), map_response(mark)));
+        let authenticated = Request::get("/")
+            .header(header::AUTHORIZATION, "Bearer token")
+            .body(Body::empty())
+            .unwrap();
+        assert!(is_layered(&app, authenticated).await);
+        assert!(!is_layered(&app, Request::get("/").body(Body::empty()).unwrap()).await);
+
+        let json = content_type("application/json");
+        let (parts, ()) = Request::post("/")
+            .header(header::CONTENT_TYPE, "Application/JSON; charset=utf-8")
+            .body(())
+            .unwrap()
+            .into_parts();
+        assert!(json(&parts));
+        let (parts, ()) = Request::post("/")
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(())
+            .unwrap()
+            .into_parts();
+        assert!(!json(&parts));
+    }
+}
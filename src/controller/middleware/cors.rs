@@ -16,6 +16,7 @@ use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Result};
 
 /// CORS middleware configuration
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct Cors {
     #[serde(default)]
     pub enable: bool,
@@ -0,0 +1,170 @@
+//! Request Decompression Middleware
+//!
+//! This middleware decompresses request bodies sent with a
+//! `Content-Encoding` header (`gzip`, `deflate`, `br` or `zstd`), so that
+//! extractors and handlers transparently see the decompressed content.
+//! Requests with an unsupported encoding are rejected with
+//! `415 Unsupported Media Type`.
+//!
+//! To protect against decompression bombs, the size of the decompressed body
+//! is limited by `max_size`. Reading past the limit fails with
+//! `413 Payload Too Large`.
+
+use axum::Router as AXRouter;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::json;
+use tower_http::{decompression::RequestDecompressionLayer, limit::RequestBodyLimitLayer};
+
+use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Result};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+pub struct RequestDecompression {
+    #[serde(default)]
+    pub enable: bool,
+    /// Maximum size of a decompressed request body, in bytes. Accepts byte
+    /// units in configuration, for example `10mb`.
+    #[serde(
+        default = "default_max_size",
+        deserialize_with = "deserialize_max_size"
+    )]
+    #[cfg_attr(
+        feature = "config_schema",
+        schemars(with = "String", extend("default" = "10mb"))
+    )]
+    pub max_size: usize,
+}
+
+impl Default for RequestDecompression {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+/// Returns the default decompressed body limit in bytes (10MB).
+fn default_max_size() -> usize {
+    10_000_000
+}
+
+fn deserialize_max_size<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = String::deserialize(deserializer)?;
+    let bytes = byte_unit::Byte::from_str(s)
+        .map_err(|err| serde::de::Error::custom(err.to_string()))?
+        .get_bytes();
+    usize::try_from(bytes).map_err(|err| serde::de::Error::custom(err.to_string()))
+}
+
+impl MiddlewareLayer for RequestDecompression {
+    /// Returns the name of the middleware
+    fn name(&self) -> &'static str {
+        "request_decompression"
+    }
+
+    /// Returns whether the middleware is enabled or not
+    fn is_enabled(&self) -> bool {
+        self.enable
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(self)
+    }
+
+    /// Applies the request decompression layer to the Axum router. The body
+    /// limit is applied inside the decompression layer, so it counts
+    /// decompressed bytes.
+    fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        Ok(app
+            .layer(RequestBodyLimitLayer::new(self.max_size))
+            .layer(RequestDecompressionLayer::new()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{Body, Bytes},
+        http::{header::CONTENT_ENCODING, Request, StatusCode},
+        routing::post,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    /// `{"name":"loco"}`, gzip compressed
+    const GZIP_BODY: &[u8] = &[
+        31, 139, 8, 0, 0, 0, 0, 0, 2, 3, 171, 86, 202, 75, 204, 77, 85, 178, 82, 202, 201, 79, 206,
+        87, 170, 5, 0, 139, 92, 127, 175, 15, 0, 0, 0,
+    ];
+
+    /// 1000 `a` characters, gzip compressed
+    const GZIP_BOMB: &[u8] = &[
+        31, 139, 8, 0, 0, 0, 0, 0, 2, 3, 75, 76, 28, 5, 163, 96, 20, 12, 119, 0, 0, 3, 218, 56,
+        154, 232, 3, 0, 0,
+    ];
+
+    async fn send(
+        middleware: &RequestDecompression,
+        encoding: &str,
+        body: &'static [u8],
+    ) -> (StatusCode, Bytes) {
+        async fn action(body: Bytes) -> Bytes {
+            body
+        }
+
+        let app = middleware
+            .apply(Router::new().route("/", post(action)))
+            .unwrap()
+            .with_state(tests_cfg::app::get_app_context().await);
+
+        let response = app
+            .oneshot(
+                Request::post("/")
+                    .header(CONTENT_ENCODING, encoding)
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn can_decompress_request_body() {
+        let (status, body) = send(&RequestDecompression::default(), "gzip", GZIP_BODY).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, r#"{"name":"loco"}"#);
+    }
+
+    #[tokio::test]
+    async fn rejects_unsupported_encoding() {
+        let (status, _) = send(&RequestDecompression::default(), "compress", GZIP_BODY).await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[tokio::test]
+    async fn limits_decompressed_size() {
+        // the compressed body fits, the decompressed one does not
+        let middleware = RequestDecompression {
+            enable: true,
+            max_size: 100,
+        };
+        let (status, _) = send(&middleware, "gzip", GZIP_BOMB).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn can_deserialize_max_size() {
+        let middleware: RequestDecompression =
+            serde_json::from_value(json!({"enable": true, "max_size": "5mb"})).unwrap();
+        assert_eq!(middleware.max_size, 5_000_000);
+    }
+}
@@ -0,0 +1,96 @@
+//! Route Deprecation
+//!
+//! Routes marked with [`crate::controller::Routes::deprecated`] answer with
+//! the `Deprecation` and `Sunset` headers, so clients learn that the endpoint
+//! goes away and when, and every call is logged with the caller, to find who
+//! still calls the deprecated endpoints before removing them.
+//!
+//! Each call also emits a `monotonic_counter.deprecated_route_calls` tracing
+//! field, counted as a metric by the tracing exporters that support it, and is
+//! counted in [`Deprecation::calls`].
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use axum::{
+    extract::Request,
+    http::{header, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use chrono::NaiveDate;
+
+use crate::controller::middleware::remote_ip::RemoteIP;
+
+const DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+const SUNSET: HeaderName = HeaderName::from_static("sunset");
+
+/// The deprecation of a group of routes. Clones share the same call count.
+#[derive(Debug, Clone)]
+pub struct Deprecation {
+    sunset: NaiveDate,
+    calls: Arc<AtomicU64>,
+}
+
+impl Deprecation {
+    #[must_use]
+    pub fn new(sunset: NaiveDate) -> Self {
+        Self {
+            sunset,
+            calls: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// The date after which the routes may be removed.
+    #[must_use]
+    pub const fn sunset(&self) -> NaiveDate {
+        self.sunset
+    }
+
+    /// The number of calls to the deprecated routes so far.
+    #[must_use]
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    /// The `Sunset` header value, an HTTP date.
+    fn sunset_header(&self) -> Option<HeaderValue> {
+        let date = self.sunset.and_hms_opt(0, 0, 0)?.and_utc();
+        HeaderValue::from_str(&date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()).ok()
+    }
+
+    /// Logs and counts the call, and adds the deprecation headers to its
+    /// response.
+    pub(crate) async fn run(self, request: Request, next: Next) -> Response {
+        let calls = self.calls.fetch_add(1, Ordering::Relaxed) + 1;
+        let caller = request
+            .extensions()
+            .get::<RemoteIP>()
+            .map_or_else(|| "unknown".to_string(), ToString::to_string);
+        let user_agent = request
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        tracing::info!(
+            method = %request.method(),
+            uri = %request.uri(),
+            caller,
+            user_agent,
+            sunset = %self.sunset,
+            calls,
+            monotonic_counter.deprecated_route_calls = 1_u64,
+            "deprecated route called"
+        );
+
+        let mut response = next.run(request).await;
+        let headers = response.headers_mut();
+        headers.insert(DEPRECATION, HeaderValue::from_static("true"));
+        if let Some(sunset) = self.sunset_header() {
+            headers.insert(SUNSET, sunset);
+        }
+        response
+    }
+}
@@ -25,6 +25,7 @@ use tower::{Layer, Service};
 use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Result};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct Etag {
     #[serde(default)]
     pub enable: bool,
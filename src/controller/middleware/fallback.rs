@@ -15,6 +15,7 @@ use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Result};
 pub struct StatusCodeWrapper(pub StatusCode);
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct Fallback {
     /// By default when enabled, returns a prebaked 404 not found page optimized
     /// for development. For production set something else (see fields below)
@@ -27,6 +28,7 @@ pub struct Fallback {
         serialize_with = "serialize_status_code",
         deserialize_with = "deserialize_status_code"
     )]
+    #[cfg_attr(feature = "config_schema", schemars(with = "u16"))]
     pub code: StatusCode,
     /// Returns content from a file pointed to by this field with a `404` status
     /// code.
@@ -0,0 +1,348 @@
+//! Force HTTPS Middleware
+//!
+//! When TLS is terminated at a proxy or a load balancer, this middleware
+//! makes sure requests arrived over HTTPS, as told by the `X-Forwarded-Proto`
+//! header. The header is only trusted when the request comes from a trusted
+//! proxy, the local networks by default. Plain HTTP requests are redirected
+//! to HTTPS, or rejected with a `400 Bad Request`, and HTTPS responses get a
+//! `Strict-Transport-Security` header:
+//!
+//! ```yaml
+//! middlewares:
+//!   force_https:
+//!     enable: true
+//!     redirect: true
+//!     # health checks from the load balancer may use plain HTTP
+//!     exclude:
+//!       - /_health
+//!     hsts:
+//!       max_age: 31536000
+//!       include_subdomains: true
+//!       preload: false
+//! ```
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router as AXRouter,
+};
+use ipnetwork::IpNetwork;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    app::AppContext,
+    controller::middleware::{
+        remote_ip::{is_trusted_proxy, parse_trusted_proxies},
+        MiddlewareLayer,
+    },
+    Error, Result,
+};
+
+const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+
+/// Force HTTPS middleware configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+pub struct ForceHttps {
+    #[serde(default)]
+    pub enable: bool,
+    /// Whether plain HTTP requests are redirected to HTTPS, or else rejected
+    /// with a `400 Bad Request`.
+    #[serde(default = "default_redirect")]
+    pub redirect: bool,
+    /// Paths served over plain HTTP too, such as health checks.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// The proxies trusted to set `X-Forwarded-Proto`, IP addresses and
+    /// network ranges (will replace the built-in local networks).
+    #[serde(default)]
+    pub trusted_proxies: Option<Vec<String>>,
+    /// The `Strict-Transport-Security` header of HTTPS responses, not set
+    /// when `null`.
+    #[serde(default = "default_hsts")]
+    pub hsts: Option<Hsts>,
+}
+
+/// The `Strict-Transport-Security` policy.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+pub struct Hsts {
+    /// How long browsers only use HTTPS for the host, in seconds.
+    #[serde(default = "default_max_age")]
+    pub max_age: u64,
+    #[serde(default)]
+    pub include_subdomains: bool,
+    #[serde(default)]
+    pub preload: bool,
+}
+
+impl Default for ForceHttps {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+const fn default_redirect() -> bool {
+    true
+}
+
+#[allow(clippy::unnecessary_wraps)]
+fn default_hsts() -> Option<Hsts> {
+    Some(Hsts {
+        max_age: default_max_age(),
+        include_subdomains: false,
+        preload: false,
+    })
+}
+
+const fn default_max_age() -> u64 {
+    31_536_000
+}
+
+impl Hsts {
+    fn header_value(&self) -> String {
+        let mut value = format!("max-age={}", self.max_age);
+        if self.include_subdomains {
+            value.push_str("; includeSubDomains");
+        }
+        if self.preload {
+            value.push_str("; preload");
+        }
+        value
+    }
+}
+
+impl MiddlewareLayer for ForceHttps {
+    /// Returns the name of the middleware.
+    fn name(&self) -> &'static str {
+        "force_https"
+    }
+
+    /// Checks if the force HTTPS middleware is enabled.
+    fn is_enabled(&self) -> bool {
+        self.enable
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(self)
+    }
+
+    /// Applies the force HTTPS middleware to the application router.
+    fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        let trusted_proxies = self
+            .trusted_proxies
+            .as_ref()
+            .map(|proxies| parse_trusted_proxies("force https", proxies))
+            .transpose()?;
+        let hsts = self
+            .hsts
+            .as_ref()
+            .map(|hsts| {
+                HeaderValue::from_str(&hsts.header_value()).map_err(|err| {
+                    Error::Message(format!("force https middleware has an invalid hsts: {err}"))
+                })
+            })
+            .transpose()?;
+        let config = self.clone();
+        Ok(app.layer(axum::middleware::from_fn(move |request, next| {
+            let config = config.clone();
+            let trusted_proxies = trusted_proxies.clone();
+            let hsts = hsts.clone();
+            async move {
+                config
+                    .enforce(trusted_proxies.as_ref(), hsts, request, next)
+                    .await
+            }
+        })))
+    }
+}
+
+impl ForceHttps {
+    async fn enforce(
+        &self,
+        trusted_proxies: Option<&Vec<IpNetwork>>,
+        hsts: Option<HeaderValue>,
+        request: Request,
+        next: Next,
+    ) -> Response {
+        if is_https(&request, trusted_proxies) {
+            let mut response = next.run(request).await;
+            if let Some(hsts) = hsts {
+                response
+                    .headers_mut()
+                    .insert(header::STRICT_TRANSPORT_SECURITY, hsts);
+            }
+            return response;
+        }
+        if self.is_excluded(request.uri().path()) {
+            return next.run(request).await;
+        }
+        if !self.redirect {
+            return Error::BadRequest("HTTPS is required".to_string()).into_response();
+        }
+        let Some(location) = https_location(&request) else {
+            return Error::BadRequest("HTTPS is required".to_string()).into_response();
+        };
+        // keep the method and body of non-idempotent requests
+        let status = if matches!(*request.method(), Method::GET | Method::HEAD) {
+            StatusCode::MOVED_PERMANENTLY
+        } else {
+            StatusCode::PERMANENT_REDIRECT
+        };
+        (status, [(header::LOCATION, location)]).into_response()
+    }
+
+    fn is_excluded(&self, path: &str) -> bool {
+        self.exclude.iter().any(|excluded| {
+            let excluded = excluded.trim_end_matches('/');
+            path == excluded
+                || path
+                    .strip_prefix(excluded)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+    }
+}
+
+/// Whether the request arrived over HTTPS: its `X-Forwarded-Proto` when set
+/// by a trusted proxy, or else its URI scheme.
+fn is_https(request: &Request, trusted_proxies: Option<&Vec<IpNetwork>>) -> bool {
+    let from_trusted_proxy = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .is_some_and(|ConnectInfo(addr)| is_trusted_proxy(addr.ip(), trusted_proxies));
+    let forwarded = request
+        .headers()
+        .get(X_FORWARDED_PROTO)
+        .and_then(|value| value.to_str().ok())
+        // the proxy closest to the client comes first
+        .and_then(|value| value.split(',').next())
+        .map(str::trim);
+    match forwarded {
+        Some(proto) if from_trusted_proxy => proto.eq_ignore_ascii_case("https"),
+        _ => request.uri().scheme_str() == Some("https"),
+    }
+}
+
+/// The HTTPS URL of the request, from its `Host`.
+fn https_location(request: &Request) -> Option<HeaderValue> {
+    let host = request
+        .headers()
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| request.uri().host())?;
+    let path = request
+        .uri()
+        .path_and_query()
+        .map_or("/", |path| path.as_str());
+    HeaderValue::from_str(&format!("https://{host}{path}")).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    async fn app(config: ForceHttps) -> Router {
+        config
+            .apply(Router::new().fallback(|| async { "ok" }))
+            .unwrap()
+            .with_state(tests_cfg::app::get_app_context().await)
+    }
+
+    async fn call(
+        app: &Router,
+        method: Method,
+        uri: &str,
+        peer: &str,
+        proto: Option<&str>,
+    ) -> Response {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::HOST, "example.com");
+        if let Some(proto) = proto {
+            request = request.header(X_FORWARDED_PROTO, proto);
+        }
+        let mut request = request.body(Body::empty()).unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn can_redirect_plain_http() {
+        let app = app(ForceHttps {
+            enable: true,
+            exclude: vec!["/_health".to_string()],
+            ..Default::default()
+        })
+        .await;
+
+        let response = call(
+            &app,
+            Method::GET,
+            "/notes?page=2",
+            "10.0.0.1:80",
+            Some("http"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "https://example.com/notes?page=2"
+        );
+
+        let response = call(&app, Method::POST, "/notes", "10.0.0.1:80", Some("http")).await;
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+
+        let response = call(&app, Method::GET, "/_health", "10.0.0.1:80", Some("http")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response
+            .headers()
+            .get(header::STRICT_TRANSPORT_SECURITY)
+            .is_none());
+
+        let response = call(&app, Method::GET, "/notes", "10.0.0.1:80", Some("https")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::STRICT_TRANSPORT_SECURITY],
+            "max-age=31536000"
+        );
+    }
+
+    #[tokio::test]
+    async fn can_only_trust_forwarded_proto_from_proxies() {
+        let app = app(ForceHttps {
+            enable: true,
+            redirect: false,
+            trusted_proxies: Some(vec!["192.168.1.0/24".to_string()]),
+            hsts: Some(Hsts {
+                max_age: 60,
+                include_subdomains: true,
+                preload: true,
+            }),
+            ..Default::default()
+        })
+        .await;
+
+        let response = call(&app, Method::GET, "/", "51.50.51.50:80", Some("https")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = call(&app, Method::GET, "/", "10.0.0.1:80", Some("https")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = call(&app, Method::GET, "/", "192.168.1.7:80", Some("https")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::STRICT_TRANSPORT_SECURITY],
+            "max-age=60; includeSubDomains; preload"
+        );
+    }
+}
@@ -0,0 +1,321 @@
+//! JSON Format Middleware
+//!
+//! Harmonizes the JSON responses of the app, without serde attributes on
+//! each struct: the keys of the JSON sent by [`crate::controller::format::json`],
+//! [`crate::controller::responder::Responder`] and the error responses are
+//! converted to a case like `camelCase`, `null` fields can be omitted, and
+//! date-times normalized to ISO-8601 in UTC.
+//!
+//! ```yaml
+//! server:
+//!   middlewares:
+//!     json_format:
+//!       enable: true
+//!       keys: camelCase
+//!       skip_nulls: true
+//!       datetimes: utc
+//! ```
+//!
+//! A route can override the app format with [`JsonFormat::layer`], for
+//! example to keep the keys of an endpoint consumed by another service:
+//!
+//! ```rust,ignore
+//! Routes::new()
+//!     .add("/legacy", get(legacy))
+//!     .layer(JsonFormat::default().layer())
+//! ```
+use std::task::{Context, Poll};
+
+use axum::Router as AXRouter;
+use chrono::{DateTime, NaiveDateTime, SecondsFormat};
+use heck::{ToKebabCase, ToLowerCamelCase, ToUpperCamelCase};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tokio::task::futures::TaskLocalFuture;
+use tower::{Layer, Service};
+
+use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Result};
+
+tokio::task_local! {
+    static JSON_FORMAT: JsonFormat;
+}
+
+/// The case of the JSON object keys.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+pub enum KeyCase {
+    /// The keys as serialized, usually `snake_case`
+    #[default]
+    #[serde(rename = "preserve")]
+    Preserve,
+    #[serde(rename = "camelCase")]
+    Camel,
+    #[serde(rename = "PascalCase")]
+    Pascal,
+    #[serde(rename = "kebab-case")]
+    Kebab,
+}
+
+/// The format of the date-time strings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum DateTimes {
+    /// The date-times as serialized
+    #[default]
+    Preserve,
+    /// ISO-8601 in UTC with milliseconds, like `2024-05-01T10:00:00.000Z`.
+    /// Date-times without an offset are taken as UTC.
+    Utc,
+}
+
+/// JSON format middleware configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+pub struct JsonFormat {
+    #[serde(default)]
+    pub enable: bool,
+    /// The case of the object keys.
+    #[serde(default)]
+    pub keys: KeyCase,
+    /// Whether object fields set to `null` are omitted.
+    #[serde(default)]
+    pub skip_nulls: bool,
+    /// The format of the date-time strings.
+    #[serde(default)]
+    pub datetimes: DateTimes,
+}
+
+impl Default for JsonFormat {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+impl MiddlewareLayer for JsonFormat {
+    /// Returns the name of the middleware.
+    fn name(&self) -> &'static str {
+        "json_format"
+    }
+
+    /// Checks if the JSON format middleware is enabled.
+    fn is_enabled(&self) -> bool {
+        self.enable
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(self)
+    }
+
+    /// Applies the JSON format to the responses of the application router.
+    fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        Ok(app.layer(self.clone().layer()))
+    }
+}
+
+impl JsonFormat {
+    /// A layer formatting the JSON responses of the routes it wraps, overriding
+    /// the format of the app.
+    #[must_use]
+    pub const fn layer(self) -> JsonFormatLayer {
+        JsonFormatLayer { format: self }
+    }
+
+    /// The format of the current response, set by the middleware.
+    pub(crate) fn current() -> Option<Self> {
+        JSON_FORMAT
+            .try_with(Clone::clone)
+            .ok()
+            .filter(|format| !format.is_identity())
+    }
+
+    const fn is_identity(&self) -> bool {
+        matches!(self.keys, KeyCase::Preserve)
+            && !self.skip_nulls
+            && matches!(self.datetimes, DateTimes::Preserve)
+    }
+
+    /// Formats a serialized value.
+    #[must_use]
+    pub fn apply(&self, value: Value) -> Value {
+        match value {
+            Value::Object(object) => Value::Object(
+                object
+                    .into_iter()
+                    .filter(|(_, value)| !(self.skip_nulls && value.is_null()))
+                    .map(|(key, value)| (self.key(key), self.apply(value)))
+                    .collect::<Map<_, _>>(),
+            ),
+            Value::Array(values) => {
+                Value::Array(values.into_iter().map(|value| self.apply(value)).collect())
+            }
+            Value::String(string) => Value::String(self.datetime(string)),
+            value => value,
+        }
+    }
+
+    fn key(&self, key: String) -> String {
+        match self.keys {
+            KeyCase::Preserve => key,
+            KeyCase::Camel => key.to_lower_camel_case(),
+            KeyCase::Pascal => key.to_upper_camel_case(),
+            KeyCase::Kebab => key.to_kebab_case(),
+        }
+    }
+
+    fn datetime(&self, string: String) -> String {
+        if self.datetimes == DateTimes::Preserve {
+            return string;
+        }
+        let utc = DateTime::parse_from_rfc3339(&string)
+            .map(|datetime| datetime.to_utc())
+            .or_else(|_| {
+                NaiveDateTime::parse_from_str(&string, "%Y-%m-%dT%H:%M:%S%.f")
+                    .map(|datetime| datetime.and_utc())
+            });
+        utc.map_or(string, |utc| {
+            utc.to_rfc3339_opts(SecondsFormat::Millis, true)
+        })
+    }
+}
+
+/// Formats the JSON responses of the services it wraps, see
+/// [`JsonFormat::layer`].
+#[derive(Debug, Clone)]
+pub struct JsonFormatLayer {
+    format: JsonFormat,
+}
+
+impl<S> Layer<S> for JsonFormatLayer {
+    type Service = JsonFormatService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        JsonFormatService {
+            inner,
+            format: self.format.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JsonFormatService<S> {
+    inner: S,
+    format: JsonFormat,
+}
+
+impl<S, R> Service<R> for JsonFormatService<S>
+where
+    S: Service<R>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = TaskLocalFuture<JsonFormat, S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        JSON_FORMAT.scope(self.format.clone(), self.inner.call(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use serde::Serialize;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::controller::format;
+
+    #[derive(Serialize)]
+    struct Profile {
+        user_id: i64,
+        display_name: Option<String>,
+        created_at: String,
+    }
+
+    fn user() -> Profile {
+        Profile {
+            user_id: 1,
+            display_name: None,
+            created_at: "2024-05-01T12:00:00+02:00".to_string(),
+        }
+    }
+
+    fn camel_case() -> JsonFormat {
+        JsonFormat {
+            enable: true,
+            keys: KeyCase::Camel,
+            skip_nulls: true,
+            datetimes: DateTimes::Utc,
+        }
+    }
+
+    #[test]
+    fn can_format_values() {
+        let value = json!({
+            "user_id": 1,
+            "nick_name": null,
+            "last_seen_at": "2024-05-01T10:00:00",
+            "day": "2024-05-01",
+            "posts": [{ "post_title": "hello", "hidden_at": null }],
+        });
+        assert_eq!(
+            camel_case().apply(value.clone()),
+            json!({
+                "userId": 1,
+                "lastSeenAt": "2024-05-01T10:00:00.000Z",
+                "day": "2024-05-01",
+                "posts": [{ "postTitle": "hello" }],
+            })
+        );
+        assert_eq!(JsonFormat::default().apply(value.clone()), value);
+
+        let kebab = JsonFormat {
+            keys: KeyCase::Kebab,
+            ..JsonFormat::default()
+        };
+        assert_eq!(
+            kebab.apply(json!({ "user_id": 1, "nick_name": null })),
+            json!({ "user-id": 1, "nick-name": null })
+        );
+    }
+
+    async fn get_json(app: &Router, uri: &str) -> Value {
+        let response = app
+            .clone()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn can_format_responses() {
+        let legacy = Router::new()
+            .route("/legacy", get(|| async { format::json(user()) }))
+            .layer(JsonFormat::default().layer());
+        let app = Router::new()
+            .route("/users", get(|| async { format::json(user()) }))
+            .route("/rendered", get(|| async { format::render().json(user()) }))
+            .merge(legacy)
+            .layer(camel_case().layer());
+
+        let expected = json!({ "userId": 1, "createdAt": "2024-05-01T10:00:00.000Z" });
+        assert_eq!(get_json(&app, "/users").await, expected);
+        assert_eq!(get_json(&app, "/rendered").await, expected);
+        assert_eq!(
+            get_json(&app, "/legacy").await,
+            json!({
+                "user_id": 1,
+                "display_name": null,
+                "created_at": "2024-05-01T12:00:00+02:00",
+            })
+        );
+    }
+}
@@ -10,9 +10,22 @@
 //! Ensure that the `body: axum::body::Bytes` variable is properly set in the
 //! request action to enforce the payload limit correctly. Without this, the
 //! middleware will not function as intended.
+//!
+//! # Streaming requests
+//!
+//! With `streaming` enabled, the limit is also enforced on the request body
+//! itself: requests with a larger `Content-Length` are rejected
+//! with `413 Payload Too Large` before reaching the handler, and bodies
+//! without one, like chunked uploads, are counted as they arrive and fail once
+//! over the limit, including for handlers reading the body as a stream. Such
+//! handlers can tell this failure apart with [`is_too_large`]. Routes cannot
+//! raise their limit above this one then, so it is disabled by default: the
+//! extractors buffering the body already stop reading chunked bodies over the
+//! limit of their route.
 
 use axum::Router as AXRouter;
 use serde::{Deserialize, Deserializer, Serialize};
+use tower_http::limit::RequestBodyLimitLayer;
 
 use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Result};
 
@@ -23,20 +36,63 @@ pub enum DefaultBodyLimitKind {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct LimitPayload {
     #[serde(
         default = "default_body_limit",
         deserialize_with = "deserialize_body_limit"
     )]
+    #[cfg_attr(
+        feature = "config_schema",
+        schemars(with = "String", extend("default" = "2mb"))
+    )]
     pub body_limit: DefaultBodyLimitKind,
+    /// Enforces the limit on streaming and chunked bodies as they arrive, not
+    /// only in the extractors buffering the body. A route cannot then raise
+    /// its own limit above this one with `DefaultBodyLimit`.
+    #[serde(default)]
+    pub streaming: bool,
 }
 
 impl Default for LimitPayload {
     fn default() -> Self {
         Self {
             body_limit: default_body_limit(),
+            streaming: false,
+        }
+    }
+}
+
+/// Whether `err`, from reading a request body, is caused by the body
+/// exceeding the payload limit.
+///
+/// ```rust
+/// use axum::{body::Body, http::StatusCode};
+/// use futures_util::StreamExt;
+/// use loco_rs::controller::middleware::limit_payload::is_too_large;
+///
+/// async fn upload(body: Body) -> StatusCode {
+///     let mut stream = body.into_data_stream();
+///     while let Some(chunk) = stream.next().await {
+///         match chunk {
+///             Ok(_chunk) => {} // write the chunk
+///             Err(err) if is_too_large(&err) => return StatusCode::PAYLOAD_TOO_LARGE,
+///             Err(_) => return StatusCode::BAD_REQUEST,
+///         }
+///     }
+///     StatusCode::OK
+/// }
+/// ```
+#[must_use]
+pub fn is_too_large(err: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if err.is::<http_body_util::LengthLimitError>() {
+            return true;
         }
+        source = err.source();
     }
+    false
 }
 
 /// Returns the default body limit in bytes (2MB).
@@ -76,13 +132,138 @@ impl MiddlewareLayer for LimitPayload {
     }
 
     /// Applies the payload limit middleware to the application router by adding
-    /// a `DefaultBodyLimit` layer.
+    /// a `DefaultBodyLimit` layer, and a `RequestBodyLimitLayer` limiting the
+    /// body as it streams when `streaming` is enabled.
     fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
-        let body_limit_layer = match self.body_limit {
-            DefaultBodyLimitKind::Disable => axum::extract::DefaultBodyLimit::disable(),
-            DefaultBodyLimitKind::Limit(limit) => axum::extract::DefaultBodyLimit::max(limit),
+        match self.body_limit {
+            DefaultBodyLimitKind::Disable => {
+                Ok(app.layer(axum::extract::DefaultBodyLimit::disable()))
+            }
+            DefaultBodyLimitKind::Limit(limit) => {
+                let app = app.layer(axum::extract::DefaultBodyLimit::max(limit));
+                if self.streaming {
+                    Ok(app.layer(RequestBodyLimitLayer::new(limit)))
+                } else {
+                    Ok(app)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{Body, Bytes},
+        http::{header, Request, StatusCode},
+        routing::post,
+    };
+    use futures_util::{stream, StreamExt};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{controller::extractor::payload::Payload, tests_cfg};
+
+    #[derive(serde::Deserialize)]
+    struct Note {
+        #[allow(dead_code)]
+        text: String,
+    }
+
+    /// A chunked request without `Content-Length`, of `chunks` chunks of 1kb.
+    fn chunked(uri: &str, chunks: usize) -> Request<Body> {
+        let json = format!("{{\"text\": \"{}\"}}", "a".repeat(chunks * 1000));
+        let json = Bytes::from(json);
+        let body = stream::iter((0..json.len()).step_by(1000).map(move |start| {
+            let end = (start + 1000).min(json.len());
+            Ok::<_, std::io::Error>(json.slice(start..end))
+        }));
+        Request::post(uri)
+            .header(header::TRANSFER_ENCODING, "chunked")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from_stream(body))
+            .unwrap()
+    }
+
+    async fn app(layer: LimitPayload) -> AXRouter {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let app = AXRouter::<AppContext>::new()
+            .route("/bytes", post(|_body: Bytes| async { StatusCode::OK }))
+            .route(
+                "/payload",
+                post(|_note: Payload<Note>| async { StatusCode::OK }),
+            )
+            .route(
+                "/stream",
+                post(|body: Body| async move {
+                    let mut stream = body.into_data_stream();
+                    while let Some(chunk) = stream.next().await {
+                        if let Err(err) = chunk {
+                            if is_too_large(&err) {
+                                return StatusCode::PAYLOAD_TOO_LARGE;
+                            }
+                            return StatusCode::BAD_REQUEST;
+                        }
+                    }
+                    StatusCode::OK
+                }),
+            );
+        layer.apply(app).unwrap().with_state(ctx)
+    }
+
+    #[tokio::test]
+    async fn rejects_chunked_requests_over_the_limit() {
+        let app = app(LimitPayload {
+            body_limit: DefaultBodyLimitKind::Limit(5_000),
+            streaming: true,
+        })
+        .await;
+
+        for uri in ["/bytes", "/payload", "/stream"] {
+            let response = app.clone().oneshot(chunked(uri, 1)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{uri}");
+            // a stream far longer than the limit, aborted once over it
+            let response = app.clone().oneshot(chunked(uri, 10_000)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE, "{uri}");
+        }
+
+        let request = Request::post("/stream")
+            .header(header::CONTENT_LENGTH, "6000")
+            .body(Body::from("a".repeat(6000)))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn can_raise_the_limit_of_a_route() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let app = AXRouter::<AppContext>::new().route(
+            "/upload",
+            post(|_body: Bytes| async { StatusCode::OK })
+                .layer(axum::extract::DefaultBodyLimit::max(100_000)),
+        );
+        let layer = LimitPayload {
+            body_limit: DefaultBodyLimitKind::Limit(5_000),
+            ..Default::default()
         };
+        let app = layer.apply(app).unwrap().with_state(ctx);
+
+        let response = app.oneshot(chunked("/upload", 10)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn can_leave_streams_unlimited() {
+        let app = app(LimitPayload {
+            body_limit: DefaultBodyLimitKind::Limit(5_000),
+            streaming: false,
+        })
+        .await;
 
-        Ok(app.layer(body_limit_layer))
+        let response = app.clone().oneshot(chunked("/stream", 10)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app.oneshot(chunked("/bytes", 10)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
     }
 }
@@ -19,6 +19,11 @@ use crate::{
 };
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(
+    feature = "config_schema",
+    derive(schemars::JsonSchema),
+    schemars(rename = "RequestLogger")
+)]
 pub struct Config {
     #[serde(default)]
     pub enable: bool,
@@ -85,7 +90,7 @@ impl MiddlewareLayer for Middleware {
                         .map(std::string::ToString::to_string)
                         .unwrap_or_default();
 
-                    tracing::error_span!(
+                    let span = tracing::error_span!(
                         "http-request",
                         "http.method" = tracing::field::display(request.method()),
                         "http.uri" = tracing::field::display(request.uri()),
@@ -93,7 +98,15 @@ impl MiddlewareLayer for Middleware {
                         "http.user_agent" = tracing::field::display(user_agent),
                         "environment" = tracing::field::display(env),
                         request_id = tracing::field::display(request_id),
-                    )
+                        // recorded by the slow request middleware
+                        handler_ms = tracing::field::Empty,
+                        middleware_ms = tracing::field::Empty,
+                        db_ms = tracing::field::Empty,
+                    );
+                    // continue the trace of the caller (`traceparent` header)
+                    #[cfg(feature = "otel")]
+                    crate::otel::set_parent_from_headers(&span, request.headers());
+                    span
                 }),
             )
             .layer(AddExtensionLayer::new(self.environment.clone())))
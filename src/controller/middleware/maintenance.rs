@@ -0,0 +1,261 @@
+//! Maintenance Mode Middleware
+//!
+//! While maintenance mode is active, this middleware answers every request
+//! with `503 Service Unavailable`, except for the allowlisted paths (health
+//! checks, admin pages, ...). The response body follows the requested format:
+//! JSON for API clients, an HTML page for browsers, and plain text otherwise.
+//!
+//! Maintenance mode is active when `active` is set in configuration, or at
+//! runtime while the configured `file` exists, so operators can toggle it
+//! during a deploy without restarting the app:
+//!
+//! ```sh
+//! $ touch tmp/maintenance   # enter maintenance mode
+//! $ rm tmp/maintenance      # leave maintenance mode
+//! ```
+use std::path::PathBuf;
+
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+    Json, Router as AXRouter,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    app::AppContext,
+    controller::{
+        middleware::{
+            format::{get_respond_to, RespondTo},
+            MiddlewareLayer,
+        },
+        ErrorDetail,
+    },
+    Result,
+};
+
+/// Maintenance mode middleware configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+pub struct Maintenance {
+    #[serde(default)]
+    pub enable: bool,
+    /// Serve the maintenance response regardless of `file`.
+    #[serde(default)]
+    pub active: bool,
+    /// Maintenance mode is active while this file exists.
+    #[serde(default = "default_file")]
+    pub file: PathBuf,
+    /// Paths that keep being served during maintenance. A path also allows
+    /// everything below it, for example `/admin` allows `/admin/users`.
+    #[serde(default = "default_allow")]
+    pub allow: Vec<String>,
+    /// The message shown to clients.
+    #[serde(default = "default_message")]
+    pub message: String,
+    /// Sent as the `Retry-After` header, in seconds.
+    pub retry_after: Option<u64>,
+}
+
+impl Default for Maintenance {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+fn default_file() -> PathBuf {
+    PathBuf::from("tmp/maintenance")
+}
+
+fn default_allow() -> Vec<String> {
+    vec!["/_ping".to_string(), "/_health".to_string()]
+}
+
+fn default_message() -> String {
+    "The application is down for maintenance, please try again later.".to_string()
+}
+
+impl MiddlewareLayer for Maintenance {
+    /// Returns the name of the middleware.
+    fn name(&self) -> &'static str {
+        "maintenance"
+    }
+
+    /// Checks if the maintenance middleware is enabled.
+    fn is_enabled(&self) -> bool {
+        self.enable
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(self)
+    }
+
+    /// Applies the maintenance middleware to the application router.
+    fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        let config = self.clone();
+        Ok(app.layer(axum::middleware::from_fn(move |request, next| {
+            let config = config.clone();
+            async move { maintenance_middleware(&config, request, next).await }
+        })))
+    }
+}
+
+impl Maintenance {
+    /// Returns whether maintenance mode is currently active.
+    pub async fn is_active(&self) -> bool {
+        self.active || tokio::fs::try_exists(&self.file).await.unwrap_or(false)
+    }
+
+    fn is_allowed(&self, path: &str) -> bool {
+        self.allow.iter().any(|allowed| {
+            let allowed = allowed.trim_end_matches('/');
+            path == allowed
+                || path
+                    .strip_prefix(allowed)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+    }
+
+    fn response(&self, respond_to: &RespondTo) -> Response {
+        let mut response = match respond_to {
+            RespondTo::Json => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(ErrorDetail::new("maintenance", &self.message)),
+            )
+                .into_response(),
+            RespondTo::Html => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Html(format!(
+                    "<!DOCTYPE html><html><head><title>Maintenance</title></head><body><p>{}</p></body></html>",
+                    self.message
+                )),
+            )
+                .into_response(),
+            _ => (StatusCode::SERVICE_UNAVAILABLE, self.message.clone()).into_response(),
+        };
+        if let Some(retry_after) = self.retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        response
+    }
+}
+
+async fn maintenance_middleware(config: &Maintenance, request: Request, next: Next) -> Response {
+    if config.is_allowed(request.uri().path()) || !config.is_active().await {
+        return next.run(request).await;
+    }
+    config.response(&get_respond_to(request.headers()))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    async fn send(middleware: &Maintenance, path: &str, accept: &str) -> Response {
+        let app = middleware
+            .apply(
+                Router::new()
+                    .route("/", get(|| async { "home" }))
+                    .route("/_health", get(|| async { "ok" }))
+                    .route("/admin/users", get(|| async { "users" })),
+            )
+            .unwrap()
+            .with_state(tests_cfg::app::get_app_context().await);
+
+        app.oneshot(
+            Request::get(path)
+                .header(header::ACCEPT, accept)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn body(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn can_toggle_with_file() {
+        let tree_fs = tree_fs::TreeBuilder::default().drop(true).create().unwrap();
+        let middleware = Maintenance {
+            enable: true,
+            file: tree_fs.root.join("maintenance"),
+            ..Default::default()
+        };
+
+        let response = send(&middleware, "/", "text/plain").await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        std::fs::write(&middleware.file, "").unwrap();
+        let response = send(&middleware, "/", "text/plain").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body(response).await, default_message());
+
+        std::fs::remove_file(&middleware.file).unwrap();
+        let response = send(&middleware, "/", "text/plain").await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn serves_allowlisted_paths() {
+        let middleware = Maintenance {
+            enable: true,
+            active: true,
+            allow: vec!["/_health".to_string(), "/admin/".to_string()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            send(&middleware, "/_health", "").await.status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            send(&middleware, "/admin/users", "").await.status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            send(&middleware, "/", "").await.status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    #[tokio::test]
+    async fn responds_in_requested_format() {
+        let middleware = Maintenance {
+            enable: true,
+            active: true,
+            retry_after: Some(120),
+            ..Default::default()
+        };
+
+        let response = send(&middleware, "/", "application/json").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "120");
+        let json: serde_json::Value = serde_json::from_str(&body(response).await).unwrap();
+        assert_eq!(
+            json,
+            json!({"error": "maintenance", "description": default_message()})
+        );
+
+        let response = send(&middleware, "/", "text/html").await;
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/html; charset=utf-8"
+        );
+        assert!(body(response).await.contains(&default_message()));
+    }
+}
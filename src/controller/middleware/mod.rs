@@ -7,17 +7,29 @@
 //! application's router.
 
 pub mod catch_panic;
+pub mod coalesce;
 pub mod compression;
+pub mod concurrency_limit;
+pub mod conditional;
 pub mod cors;
+pub mod decompression;
+pub mod deprecation;
 pub mod etag;
 pub mod fallback;
+pub mod force_https;
 pub mod format;
+pub mod json_format;
 pub mod limit_payload;
 pub mod logger;
+pub mod maintenance;
 pub mod powered_by;
 pub mod remote_ip;
+pub mod request_context;
 pub mod request_id;
+pub mod response_cache;
+pub mod response_headers;
 pub mod secure_headers;
+pub mod slow_request;
 #[cfg(feature = "embedded_assets")]
 pub mod static_assets_embedded;
 #[cfg(feature = "embedded_assets")]
@@ -25,7 +37,9 @@ pub use static_assets_embedded as static_assets;
 
 #[cfg(not(feature = "embedded_assets"))]
 pub mod static_assets;
+pub mod tenant;
 pub mod timeout;
+pub mod trailing_slash;
 
 use axum::Router as AXRouter;
 use serde::{Deserialize, Serialize};
@@ -71,13 +85,16 @@ pub trait MiddlewareLayer {
     fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>>;
 }
 
-#[allow(clippy::unnecessary_lazy_evaluations)]
+#[allow(clippy::unnecessary_lazy_evaluations, clippy::too_many_lines)]
 #[must_use]
 pub fn default_middleware_stack(ctx: &AppContext) -> Vec<Box<dyn MiddlewareLayer>> {
     // Shortened reference to middlewares
     let middlewares = &ctx.config.server.middlewares;
 
     vec![
+        // JSON format middleware with a default if none. Placed first, so that
+        // it wraps the handlers that serialize the responses
+        Box::new(middlewares.json_format.clone().unwrap_or_default()),
         // Limit Payload middleware with a default if none
         Box::new(middlewares.limit_payload.clone().unwrap_or_default()),
         // CORS middleware with a default if none
@@ -109,6 +126,16 @@ pub fn default_middleware_stack(ctx: &AppContext) -> Vec<Box<dyn MiddlewareLayer
                     ..Default::default()
                 }),
         ),
+        // Request coalescing middleware with a default if none
+        Box::new(
+            middlewares
+                .coalesce
+                .clone()
+                .unwrap_or_else(|| coalesce::Coalesce {
+                    enable: false,
+                    ..Default::default()
+                }),
+        ),
         // Compression middleware with a default if none
         Box::new(
             middlewares
@@ -116,6 +143,16 @@ pub fn default_middleware_stack(ctx: &AppContext) -> Vec<Box<dyn MiddlewareLayer
                 .clone()
                 .unwrap_or_else(|| compression::Compression { enable: false }),
         ),
+        // Request Decompression middleware with a default if none
+        Box::new(
+            middlewares
+                .request_decompression
+                .clone()
+                .unwrap_or_else(|| decompression::RequestDecompression {
+                    enable: false,
+                    ..Default::default()
+                }),
+        ),
         // Timeout Request middleware with a default if none
         Box::new(
             middlewares
@@ -140,6 +177,47 @@ pub fn default_middleware_stack(ctx: &AppContext) -> Vec<Box<dyn MiddlewareLayer
                 ..Default::default()
             }
         })),
+        // Maintenance mode middleware with a default if none
+        Box::new(
+            middlewares
+                .maintenance
+                .clone()
+                .unwrap_or_else(|| maintenance::Maintenance {
+                    enable: false,
+                    ..Default::default()
+                }),
+        ),
+        // Concurrency limit middleware with a default if none. Placed before
+        // the logger so that shed requests are logged
+        Box::new(middlewares.concurrency_limit.clone().unwrap_or_else(|| {
+            concurrency_limit::ConcurrencyLimit {
+                enable: false,
+                ..Default::default()
+            }
+        })),
+        // Slow Request middleware with a default if none. Placed before the
+        // logger so that its warnings are emitted within the request span
+        Box::new(
+            middlewares
+                .slow_request
+                .clone()
+                .unwrap_or_else(|| slow_request::SlowRequest {
+                    enable: false,
+                    ..Default::default()
+                }),
+        ),
+        // Request context middleware with a default if none. Placed before the
+        // tenant and request id middlewares, to run after them
+        Box::new(request_context::new(
+            &middlewares
+                .request_context
+                .clone()
+                .unwrap_or(request_context::Config { enable: true }),
+            ctx,
+        )),
+        // Tenant middleware with a default if none. Placed before the logger
+        // so that rejected requests are logged
+        Box::new(middlewares.tenant.clone().unwrap_or_default()),
         // Logger middleware with default logger configuration
         Box::new(logger::new(
             &middlewares
@@ -165,17 +243,35 @@ pub fn default_middleware_stack(ctx: &AppContext) -> Vec<Box<dyn MiddlewareLayer
                     ..Default::default()
                 }),
         ),
+        // Trailing slash middleware with a default if none. Placed after the
+        // fallback, since it wraps the router built so far
+        Box::new(middlewares.trailing_slash.clone().unwrap_or_default()),
+        // Force HTTPS middleware with a default if none. Placed after the
+        // routing middlewares, so plain HTTP requests are redirected first
+        Box::new(middlewares.force_https.clone().unwrap_or_default()),
         // Powered by middleware with a default identifier
         Box::new(powered_by::new(ctx.config.server.ident.as_deref())),
+        Box::new(response_headers::new(
+            &ctx.config.server.headers,
+            ctx.config.server.security_headers,
+        )),
     ]
 }
 
 /// Server middleware configuration structure.
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(
+    feature = "config_schema",
+    derive(schemars::JsonSchema),
+    schemars(rename = "Middlewares")
+)]
 pub struct Config {
     /// Compression for the response.
     pub compression: Option<compression::Compression>,
 
+    /// Decompression for the request body.
+    pub request_decompression: Option<decompression::RequestDecompression>,
+
     /// Etag cache headers.
     pub etag: Option<etag::Etag>,
 
@@ -191,6 +287,18 @@ pub struct Config {
     /// Setting a global timeout for requests
     pub timeout_request: Option<timeout::TimeOut>,
 
+    /// Answer with 503 while the app is in maintenance mode
+    pub maintenance: Option<maintenance::Maintenance>,
+
+    /// Shed the requests above a maximum number of requests in flight
+    pub concurrency_limit: Option<concurrency_limit::ConcurrencyLimit>,
+
+    /// Share the response of identical concurrent `GET` requests
+    pub coalesce: Option<coalesce::Coalesce>,
+
+    /// Log requests slower than a threshold, with a timing breakdown
+    pub slow_request: Option<slow_request::SlowRequest>,
+
     /// CORS configuration
     pub cors: Option<cors::Cors>,
 
@@ -204,9 +312,24 @@ pub struct Config {
     /// Calculates a remote IP based on `X-Forwarded-For` when behind a proxy
     pub remote_ip: Option<remote_ip::RemoteIpMiddleware>,
 
+    /// Redirect or reject plain HTTP requests, and set HSTS, behind a proxy
+    pub force_https: Option<force_https::ForceHttps>,
+
     /// Configure fallback behavior when hitting a missing URL
     pub fallback: Option<fallback::Fallback>,
 
     /// Request ID
     pub request_id: Option<request_id::RequestId>,
+
+    /// Request context propagated to handlers and enqueued jobs
+    pub request_context: Option<request_context::Config>,
+
+    /// Trim or redirect the trailing slash of request paths
+    pub trailing_slash: Option<trailing_slash::TrailingSlash>,
+
+    /// Bind requests to a tenant, from a header or a subdomain
+    pub tenant: Option<tenant::Tenant>,
+
+    /// Key case, `null` omission and date-times of the JSON responses
+    pub json_format: Option<json_format::JsonFormat>,
 }
@@ -93,6 +93,7 @@ const X_FORWARDED_FOR: &str = "X-Forwarded-For";
 ///   facto spec](https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/X-Forwarded-For#selecting_an_ip_address)
 ///   "Trusted proxy list"
 #[derive(Default, Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct RemoteIpMiddleware {
     #[serde(default)]
     pub enable: bool,
@@ -124,6 +125,36 @@ impl MiddlewareLayer for RemoteIpMiddleware {
     }
 }
 
+/// Parses the configured trusted proxies, IP addresses and network ranges.
+///
+/// # Errors
+/// When a proxy is neither an IP address nor a network range
+pub(crate) fn parse_trusted_proxies(
+    middleware: &str,
+    proxies: &[String],
+) -> Result<Vec<IpNetwork>> {
+    proxies
+        .iter()
+        .map(|proxy| {
+            IpNetwork::from_str(proxy).map_err(|err| {
+                Error::Message(format!(
+                    "{middleware} middleware cannot parse trusted proxy configuration: \
+                     `{proxy}`, reason: `{err}`",
+                ))
+            })
+        })
+        .collect()
+}
+
+/// Whether `ip` is a trusted proxy. Trusted proxies, when provided, REPLACE
+/// the default local proxies.
+pub(crate) fn is_trusted_proxy(ip: IpAddr, trusted_proxies: Option<&Vec<IpNetwork>>) -> bool {
+    trusted_proxies
+        .unwrap_or_else(|| get_local_trusted_proxies())
+        .iter()
+        .any(|trusted_proxy| trusted_proxy.contains(ip))
+}
+
 // implementation reference: https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/X-Forwarded-For
 fn maybe_get_forwarded(
     headers: &HeaderMap,
@@ -159,13 +190,7 @@ fn maybe_get_forwarded(
         > The X-Forwarded-For IP list is searched from the rightmost, skipping all addresses that
         > are on the trusted proxy list. The first non-matching address is the target address.
         */
-        .filter(|ip| {
-            // trusted proxies provided REPLACES our default local proxies
-            let proxies = trusted_proxies.unwrap_or_else(|| get_local_trusted_proxies());
-            !proxies
-                .iter()
-                .any(|trusted_proxy| trusted_proxy.contains(*ip))
-        })
+        .filter(|ip| !is_trusted_proxy(*ip, trusted_proxies))
         /*
         > When choosing the X-Forwarded-For client IP address closest to the client (untrustworthy
         > and not for security-related purposes), the first IP from the leftmost that is a valid
@@ -222,19 +247,7 @@ impl RemoteIPLayer {
             trusted_proxies: config
                 .trusted_proxies
                 .as_ref()
-                .map(|proxies| {
-                    proxies
-                        .iter()
-                        .map(|proxy| {
-                            IpNetwork::from_str(proxy).map_err(|err| {
-                                Error::Message(format!(
-                                    "remote ip middleare cannot parse trusted proxy \
-                                     configuration: `{proxy}`, reason: `{err}`",
-                                ))
-                            })
-                        })
-                        .collect::<Result<Vec<_>>>()
-                })
+                .map(|proxies| parse_trusted_proxies("remote ip", proxies))
                 .transpose()?,
         })
     }
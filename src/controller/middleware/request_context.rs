@@ -0,0 +1,217 @@
+//! Request Context Middleware
+//!
+//! This middleware populates the [`RequestContext`] of every request from the
+//! request id, the route, the `traceparent`, the user authenticated by JWT, the
+//! `Accept-Language` header and the tenant. It stores the context in the
+//! request extensions, for handlers to extract, and makes it the current
+//! context while the request is handled, so that the jobs enqueued by the
+//! request carry it.
+//!
+//! It runs after the `request_id` and `tenant` middlewares, to pick up what
+//! they resolved.
+
+use axum::{
+    extract::{FromRequestParts, MatchedPath, Request},
+    http::{header, request::Parts, HeaderMap},
+    middleware::Next,
+    response::Response,
+    Router as AXRouter,
+};
+use serde::{Deserialize, Serialize};
+
+pub use crate::request_context::RequestContext;
+use crate::{
+    app::AppContext,
+    controller::middleware::{request_id::LocoRequestId, tenant::CurrentTenant, MiddlewareLayer},
+    Error, Result,
+};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(
+    feature = "config_schema",
+    derive(schemars::JsonSchema),
+    schemars(rename = "RequestContextConfig")
+)]
+pub struct Config {
+    #[serde(default)]
+    pub enable: bool,
+}
+
+/// [`Middleware`] populating the [`RequestContext`] of requests.
+pub struct Middleware {
+    config: Config,
+    ctx: AppContext,
+}
+
+/// Creates a new instance of [`Middleware`], resolving the actor of requests
+/// with the JWT configuration of `ctx`.
+#[must_use]
+pub fn new(config: &Config, ctx: &AppContext) -> Middleware {
+    Middleware {
+        config: config.clone(),
+        ctx: ctx.clone(),
+    }
+}
+
+impl MiddlewareLayer for Middleware {
+    /// Returns the name of the middleware
+    fn name(&self) -> &'static str {
+        "request_context"
+    }
+
+    /// Returns whether the middleware is enabled or not
+    fn is_enabled(&self) -> bool {
+        self.config.enable
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(&self.config)
+    }
+
+    /// Applies the request context middleware to the application router.
+    fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        let ctx = self.ctx.clone();
+        Ok(app.layer(axum::middleware::from_fn(move |request, next| {
+            let ctx = ctx.clone();
+            async move { request_context_middleware(&ctx, request, next).await }
+        })))
+    }
+}
+
+async fn request_context_middleware(ctx: &AppContext, request: Request, next: Next) -> Response {
+    let (mut parts, body) = request.into_parts();
+    let context = resolve(ctx, &parts);
+    parts.extensions.insert(context.clone());
+    context
+        .scope(next.run(Request::from_parts(parts, body)))
+        .await
+}
+
+/// Builds the context of a request from its parts.
+#[allow(unused_variables)]
+fn resolve(ctx: &AppContext, parts: &Parts) -> RequestContext {
+    #[cfg(all(feature = "auth_jwt", feature = "with-db"))]
+    let actor = crate::controller::extractor::auth::extract_jwt_from_request_parts(parts, ctx)
+        .ok()
+        .map(|jwt| jwt.claims.pid);
+    #[cfg(not(all(feature = "auth_jwt", feature = "with-db")))]
+    let actor = None;
+
+    #[cfg(feature = "otel")]
+    let trace_context = crate::otel::current_traceparent();
+    #[cfg(not(feature = "otel"))]
+    let trace_context = header_value(&parts.headers, "traceparent");
+
+    RequestContext {
+        request_id: parts
+            .extensions
+            .get::<LocoRequestId>()
+            .map(|id| id.get().to_string()),
+        route: Some(route(parts)),
+        trace_context,
+        actor,
+        locale: locale(&parts.headers),
+        tenant: parts
+            .extensions
+            .get::<CurrentTenant>()
+            .map(|CurrentTenant(tenant)| tenant.clone()),
+    }
+}
+
+/// Returns the method and route of the request, or its path when the route is
+/// not matched yet.
+fn route(parts: &Parts) -> String {
+    let path = parts
+        .extensions
+        .get::<MatchedPath>()
+        .map_or_else(|| parts.uri.path(), MatchedPath::as_str);
+    format!("{} {path}", parts.method)
+}
+
+fn header_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(ToString::to_string)
+}
+
+/// Returns the first language of the `Accept-Language` header, such as
+/// `fr-CA` for `fr-CA,fr;q=0.9,en;q=0.8`.
+fn locale(headers: &HeaderMap) -> Option<String> {
+    header_value(headers, header::ACCEPT_LANGUAGE.as_str())?
+        .split(',')
+        .map(|language| language.split(';').next().unwrap_or_default().trim())
+        .find(|language| !language.is_empty() && *language != "*")
+        .map(ToString::to_string)
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for RequestContext {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self> {
+        parts.extensions.get::<Self>().cloned().ok_or_else(|| {
+            tracing::error!(
+                "no context for the request, is the request_context middleware enabled?"
+            );
+            Error::InternalServerError
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, routing::get, Json, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{controller::middleware::request_id::RequestId, tests_cfg};
+
+    async fn context(context: RequestContext) -> Json<(RequestContext, Option<RequestContext>)> {
+        Json((context, RequestContext::current()))
+    }
+
+    #[tokio::test]
+    async fn can_populate_request_context() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let app = new(&Config { enable: true }, &ctx)
+            .apply(Router::new().route("/", get(context)))
+            .unwrap();
+        let app = RequestId { enable: true }
+            .apply(app)
+            .unwrap()
+            .with_state(ctx);
+
+        let response = app
+            .oneshot(
+                Request::get("/")
+                    .header("x-request-id", "req-1")
+                    .header(header::ACCEPT_LANGUAGE, "fr-CA,fr;q=0.9,en;q=0.8")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let (extracted, current): (RequestContext, Option<RequestContext>) =
+            serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(extracted.request_id.as_deref(), Some("req-1"));
+        assert_eq!(extracted.route.as_deref(), Some("GET /"));
+        assert_eq!(extracted.locale.as_deref(), Some("fr-CA"));
+        assert_eq!(extracted.actor, None);
+        assert_eq!(extracted.tenant, None);
+        assert_eq!(current, Some(extracted));
+    }
+
+    #[test]
+    fn can_resolve_locale() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(locale(&headers), None);
+        headers.insert(header::ACCEPT_LANGUAGE, "*, en;q=0.5".parse().unwrap());
+        assert_eq!(locale(&headers).as_deref(), Some("en"));
+    }
+}
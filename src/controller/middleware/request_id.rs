@@ -11,7 +11,6 @@ use axum::{
 };
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use uuid::Uuid;
 
 use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Result};
 
@@ -26,6 +25,7 @@ fn get_id_cleanup() -> &'static Regex {
     ID_CLEANUP.get_or_init(|| Regex::new(r"[^\w\-@]").unwrap())
 }
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct RequestId {
     #[serde(default)]
     pub enable: bool,
@@ -59,6 +59,10 @@ impl MiddlewareLayer for RequestId {
     }
 }
 
+tokio::task_local! {
+    static CURRENT_REQUEST_ID: String;
+}
+
 /// Wrapper struct for storing the request ID in the request's extensions.
 #[derive(Debug, Clone)]
 pub struct LocoRequestId(String);
@@ -69,13 +73,20 @@ impl LocoRequestId {
     pub fn get(&self) -> &str {
         self.0.as_str()
     }
+
+    /// Retrieves the ID of the request being handled, without access to the
+    /// request, for example to propagate it to outbound calls.
+    #[must_use]
+    pub fn current() -> Option<String> {
+        CURRENT_REQUEST_ID.try_with(Clone::clone).ok()
+    }
 }
 
 /// Middleware function to ensure or generate a unique request ID.
 ///
 /// This function intercepts requests, checks for the presence of the
 /// `x-request-id` header, and either sanitizes its value or generates a new
-/// UUID if absent. The resulting request ID is added to both the request
+/// id with [`crate::id::generate`] if absent. The resulting request ID is added to both the request
 /// extensions and the response headers.
 pub async fn request_id_middleware(mut request: Request, next: Next) -> Response {
     let header_request_id = request.headers().get(X_REQUEST_ID).cloned();
@@ -83,7 +94,9 @@ pub async fn request_id_middleware(mut request: Request, next: Next) -> Response
     request
         .extensions_mut()
         .insert(LocoRequestId(request_id.clone()));
-    let mut res = next.run(request).await;
+    let mut res = CURRENT_REQUEST_ID
+        .scope(request_id.clone(), next.run(request))
+        .await;
 
     if let Ok(v) = HeaderValue::from_str(request_id.as_str()) {
         res.headers_mut().insert(X_REQUEST_ID, v);
@@ -107,7 +120,7 @@ fn make_request_id(maybe_request_id: Option<HeaderValue>) -> String {
             });
             id.filter(|s| !s.is_empty())
         })
-        .unwrap_or_else(|| Uuid::new_v4().to_string())
+        .unwrap_or_else(crate::id::generate)
 }
 
 #[cfg(test)]
@@ -0,0 +1,321 @@
+//! Response Caching
+//!
+//! Routes marked with [`crate::controller::Routes::cache`] store their
+//! successful `GET` responses in the [cache](crate::cache) of the app, Redis
+//! or in memory, and serve them from it until they expire, without running
+//! the handler.
+//!
+//! * Responses are cached by path and query, or only the query parameters
+//!   listed with [`ResponseCache::vary_query`], and the request headers
+//!   listed with [`ResponseCache::vary_header`].
+//! * Requests with an `Authorization` or `Cookie` header bypass the cache,
+//!   unless [`ResponseCache::allow_authenticated`] is set.
+//! * Responses setting cookies, marked as `private` or `no-store`, or with a
+//!   body of unknown size or larger than `max_body` bytes are not cached.
+//! * Served responses carry an `x-cache` header, `HIT` or `MISS`.
+//!
+//! The responses of a namespace, the prefix of the routes by default, are
+//! dropped at once with [`invalidate`], for example when the underlying data
+//! changes.
+use std::time::Duration;
+
+use axum::{
+    body::Bytes,
+    extract::Request,
+    http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::coalesce::is_shareable;
+use crate::{
+    cache::{Cache, CacheResult},
+    controller::routes::FilterContext,
+};
+
+const X_CACHE: HeaderName = HeaderName::from_static("x-cache");
+
+/// The namespace of the routes cached without a prefix nor an explicit
+/// namespace.
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+/// The response caching of a group of routes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseCache {
+    ttl: Duration,
+    namespace: Option<String>,
+    vary_query: Option<Vec<String>>,
+    vary_headers: Vec<HeaderName>,
+    allow_authenticated: bool,
+    max_body: u64,
+}
+
+impl ResponseCache {
+    /// Caches responses for `ttl`.
+    #[must_use]
+    pub const fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            namespace: None,
+            vary_query: None,
+            vary_headers: Vec::new(),
+            allow_authenticated: false,
+            max_body: 1024 * 1024,
+        }
+    }
+
+    /// Sets the namespace of the cached responses, for [`invalidate`]. Routes
+    /// use their prefix by default.
+    #[must_use]
+    pub fn namespace(mut self, namespace: &str) -> Self {
+        self.namespace = Some(namespace.to_string());
+        self
+    }
+
+    /// Caches responses by the query parameter `name`, and ignores the query
+    /// parameters which are not listed. All the query is part of the key
+    /// until a parameter is listed.
+    #[must_use]
+    pub fn vary_query(mut self, name: &str) -> Self {
+        self.vary_query
+            .get_or_insert_with(Vec::new)
+            .push(name.to_string());
+        self
+    }
+
+    /// Caches responses by the request header `name`, such as
+    /// `accept-language`.
+    ///
+    /// # Panics
+    ///
+    /// When `name` is not a valid header name
+    #[must_use]
+    pub fn vary_header(mut self, name: &str) -> Self {
+        self.vary_headers
+            .push(HeaderName::try_from(name.to_ascii_lowercase()).expect("a valid header name"));
+        self
+    }
+
+    /// Caches the responses of authenticated requests too. List the headers
+    /// identifying the user with [`ResponseCache::vary_header`], unless the
+    /// responses are the same for all users.
+    #[must_use]
+    pub const fn allow_authenticated(mut self) -> Self {
+        self.allow_authenticated = true;
+        self
+    }
+
+    /// Sets the largest response body (in bytes) cached, 1MB by default.
+    #[must_use]
+    pub const fn max_body(mut self, max_body: u64) -> Self {
+        self.max_body = max_body;
+        self
+    }
+
+    /// The time responses are cached for.
+    #[must_use]
+    pub const fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    pub(crate) fn namespace_or(&self, prefix: Option<&str>) -> String {
+        self.namespace
+            .clone()
+            .or_else(|| {
+                prefix
+                    .map(|prefix| prefix.trim_matches('/').to_string())
+                    .filter(|prefix| !prefix.is_empty())
+            })
+            .unwrap_or_else(|| DEFAULT_NAMESPACE.to_string())
+    }
+
+    /// Serves the request from the cache, or runs it and caches its response.
+    pub(crate) async fn run(self, namespace: String, request: Request, next: Next) -> Response {
+        if request.method() != Method::GET
+            || (!self.allow_authenticated && is_authenticated(&request))
+        {
+            return next.run(request).await;
+        }
+        let Some(FilterContext(ctx)) = request.extensions().get::<FilterContext>().cloned() else {
+            tracing::error!("no app context for the response cache, are the routes registered?");
+            return next.run(request).await;
+        };
+        let cache = &ctx.cache;
+
+        let key = match generation(cache, &namespace).await {
+            Ok(generation) => self.key(&namespace, generation, &request),
+            Err(err) => {
+                tracing::warn!(namespace, error = %err, "could not read the response cache");
+                return next.run(request).await;
+            }
+        };
+        match cache.get::<CachedResponse>(&key).await {
+            Ok(Some(cached)) => {
+                tracing::debug!(namespace, key, "response cache hit");
+                return cached.into_response();
+            }
+            Ok(None) => {}
+            Err(err) => tracing::warn!(key, error = %err, "could not read the response cache"),
+        }
+
+        let response = next.run(request).await;
+        if response.status() != StatusCode::OK || !is_shareable(&response, self.max_body) {
+            return response;
+        }
+        let (parts, body) = response.into_parts();
+        let Ok(body) = axum::body::to_bytes(body, usize::MAX).await else {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        };
+        let cached = CachedResponse::new(parts.status, &parts.headers, &body);
+        if let Err(err) = cache.insert_with_expiry(&key, &cached, self.ttl).await {
+            tracing::warn!(key, error = %err, "could not write the response cache");
+        }
+        let mut response = Response::from_parts(parts, body.into());
+        response
+            .headers_mut()
+            .insert(X_CACHE, HeaderValue::from_static("MISS"));
+        response
+    }
+
+    /// The cache key of a request: its path, the query parameters and the
+    /// headers it varies by.
+    fn key(&self, namespace: &str, generation: i64, request: &Request) -> String {
+        let uri = request.uri();
+        let mut query = uri
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .filter(|pair| {
+                self.vary_query.as_ref().map_or(true, |names| {
+                    let name = pair.split_once('=').map_or(*pair, |(name, _)| name);
+                    names.iter().any(|listed| listed == name)
+                })
+            })
+            .collect::<Vec<_>>();
+        query.sort_unstable();
+
+        let mut hasher = Sha256::new();
+        hasher.update(uri.path());
+        hasher.update("?");
+        hasher.update(query.join("&"));
+        for name in &self.vary_headers {
+            hasher.update("\n");
+            hasher.update(name.as_str());
+            for value in request.headers().get_all(name) {
+                hasher.update(":");
+                hasher.update(value.as_bytes());
+            }
+        }
+        format!(
+            "{}:{generation}:{}",
+            generation_key(namespace),
+            hex::encode(hasher.finalize())
+        )
+    }
+}
+
+fn is_authenticated(request: &Request) -> bool {
+    let headers = request.headers();
+    headers.contains_key(axum::http::header::AUTHORIZATION)
+        || headers.contains_key(axum::http::header::COOKIE)
+}
+
+fn generation_key(namespace: &str) -> String {
+    format!("response:{namespace}")
+}
+
+/// The generation of the cached responses of `namespace`, the time it was
+/// last invalidated. Responses cached before are never served again.
+async fn generation(cache: &Cache, namespace: &str) -> CacheResult<i64> {
+    Ok(cache
+        .get::<i64>(&generation_key(namespace))
+        .await?
+        .unwrap_or_default())
+}
+
+/// Drops the cached responses of the routes of `namespace`, the prefix of the
+/// routes unless they set another one with [`ResponseCache::namespace`].
+///
+/// # Errors
+///
+/// When the cache could not be written
+pub async fn invalidate(cache: &Cache, namespace: &str) -> CacheResult<()> {
+    tracing::debug!(namespace, "invalidating cached responses");
+    let namespace = namespace.trim_matches('/');
+    let namespace = if namespace.is_empty() {
+        DEFAULT_NAMESPACE
+    } else {
+        namespace
+    };
+    let generation = chrono::Utc::now()
+        .timestamp_micros()
+        .max(generation(cache, namespace).await? + 1);
+    cache.insert(&generation_key(namespace), &generation).await
+}
+
+/// A response, as stored in the cache.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: CachedBody,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum CachedBody {
+    Text(String),
+    /// Hex encoded
+    Binary(String),
+}
+
+impl CachedResponse {
+    fn new(status: StatusCode, headers: &HeaderMap, body: &Bytes) -> Self {
+        let headers = headers
+            .iter()
+            .filter_map(|(name, value)| {
+                value
+                    .to_str()
+                    .ok()
+                    .map(|value| (name.to_string(), value.to_string()))
+            })
+            .collect();
+        let body = std::str::from_utf8(body).map_or_else(
+            |_| CachedBody::Binary(hex::encode(body)),
+            |text| CachedBody::Text(text.to_string()),
+        );
+        Self {
+            status: status.as_u16(),
+            headers,
+            body,
+        }
+    }
+}
+
+impl IntoResponse for CachedResponse {
+    fn into_response(self) -> Response {
+        let body = match self.body {
+            CachedBody::Text(text) => Bytes::from(text),
+            CachedBody::Binary(hex) => match hex::decode(hex) {
+                Ok(bytes) => Bytes::from(bytes),
+                Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            },
+        };
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        let mut response = (status, body).into_response();
+        let headers = response.headers_mut();
+        headers.clear();
+        for (name, value) in self.headers {
+            if let (Ok(name), Ok(value)) =
+                (HeaderName::try_from(name), HeaderValue::try_from(value))
+            {
+                headers.append(name, value);
+            }
+        }
+        headers.insert(X_CACHE, HeaderValue::from_static("HIT"));
+        response
+    }
+}
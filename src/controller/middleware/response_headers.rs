@@ -0,0 +1,187 @@
+//! Response Headers Middleware
+//!
+//! This middleware adds the headers configured in `server.headers` to every
+//! response, and with `server.security_headers` a set of secure defaults
+//! (`Strict-Transport-Security`, `X-Content-Type-Options`, `X-Frame-Options`,
+//! `Referrer-Policy` and `Content-Security-Policy`):
+//!
+//! ```yaml
+//! server:
+//!   security_headers: true
+//!   headers:
+//!     Referrer-Policy: no-referrer
+//!     # an empty value leaves out a secure default
+//!     Content-Security-Policy: ""
+//! ```
+//!
+//! Headers are only added when the response does not have them yet, so a
+//! handler or a route layer can override them, for example with
+//! `tower_http::set_header::SetResponseHeaderLayer::overriding`.
+use std::collections::BTreeMap;
+
+use axum::{
+    http::header::{HeaderName, HeaderValue},
+    Router as AXRouter,
+};
+use tower_http::set_header::SetResponseHeaderLayer;
+
+use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Error, Result};
+
+/// The headers applied with `server.security_headers`.
+const SECURITY_HEADERS: [(&str, &str); 5] = [
+    (
+        "strict-transport-security",
+        "max-age=31536000; includeSubDomains",
+    ),
+    ("x-content-type-options", "nosniff"),
+    ("x-frame-options", "SAMEORIGIN"),
+    ("referrer-policy", "strict-origin-when-cross-origin"),
+    (
+        "content-security-policy",
+        "default-src 'self'; object-src 'none'; frame-ancestors 'self'",
+    ),
+];
+
+/// [`Middleware`] struct holding the headers added to every response.
+#[derive(Debug)]
+pub struct Middleware {
+    headers: BTreeMap<String, String>,
+}
+
+/// Creates a new instance of [`Middleware`] from the configured headers,
+/// on top of the secure defaults when `security_headers` is set.
+#[must_use]
+pub fn new(headers: &BTreeMap<String, String>, security_headers: bool) -> Middleware {
+    let mut all = BTreeMap::new();
+    if security_headers {
+        for (name, value) in SECURITY_HEADERS {
+            all.insert(name.to_string(), value.to_string());
+        }
+    }
+    for (name, value) in headers {
+        all.insert(name.to_ascii_lowercase(), value.clone());
+    }
+    all.retain(|_, value| !value.is_empty());
+
+    Middleware { headers: all }
+}
+
+impl Middleware {
+    fn as_headers(&self) -> Result<Vec<(HeaderName, HeaderValue)>> {
+        self.headers
+            .iter()
+            .map(|(name, value)| {
+                Ok((
+                    HeaderName::from_bytes(name.as_bytes()).map_err(|err| {
+                        Error::Message(format!("server.headers: invalid header `{name}`: {err}"))
+                    })?,
+                    HeaderValue::from_str(value).map_err(|err| {
+                        Error::Message(format!(
+                            "server.headers: invalid value for header `{name}`: {err}"
+                        ))
+                    })?,
+                ))
+            })
+            .collect()
+    }
+}
+
+impl MiddlewareLayer for Middleware {
+    /// Returns the name of the middleware
+    fn name(&self) -> &'static str {
+        "response_headers"
+    }
+
+    /// Returns whether the middleware is enabled or not
+    fn is_enabled(&self) -> bool {
+        !self.headers.is_empty()
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(&self.headers)
+    }
+
+    /// Applies the middleware to the application by adding the configured
+    /// headers to each response that does not have them yet.
+    fn apply(&self, mut app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        for (name, value) in self.as_headers()? {
+            app = app.layer(SetResponseHeaderLayer::if_not_present(name, value));
+        }
+        Ok(app)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Request},
+        response::IntoResponse,
+        routing::get,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    async fn headers(middleware: &Middleware) -> axum::http::HeaderMap {
+        let app = Router::new().route("/", get(|| async { "home" })).route(
+            "/embed",
+            get(|| async { ([(header::X_FRAME_OPTIONS, "ALLOWALL")], "embed") }),
+        );
+        let app = middleware
+            .apply(app)
+            .unwrap()
+            .with_state(tests_cfg::app::get_app_context().await);
+
+        let response = app
+            .clone()
+            .oneshot(Request::get("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let mut headers = response.headers().clone();
+
+        let embed = app
+            .oneshot(Request::get("/embed").body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .into_response();
+        if let Some(value) = embed.headers().get(header::X_FRAME_OPTIONS) {
+            headers.insert("x-embed-frame-options", value.clone());
+        }
+        headers
+    }
+
+    #[tokio::test]
+    async fn can_apply_secure_defaults_and_overrides() {
+        let configured = BTreeMap::from([
+            ("Referrer-Policy".to_string(), "no-referrer".to_string()),
+            ("Content-Security-Policy".to_string(), String::new()),
+            ("X-Custom".to_string(), "loco".to_string()),
+        ]);
+        let headers = headers(&new(&configured, true)).await;
+
+        assert_eq!(
+            headers.get(header::X_CONTENT_TYPE_OPTIONS).unwrap(),
+            "nosniff"
+        );
+        assert_eq!(headers.get(header::REFERRER_POLICY).unwrap(), "no-referrer");
+        assert_eq!(headers.get("x-custom").unwrap(), "loco");
+        assert!(headers.get(header::CONTENT_SECURITY_POLICY).is_none());
+        // a handler's own header wins
+        assert_eq!(headers.get("x-embed-frame-options").unwrap(), "ALLOWALL");
+    }
+
+    #[test]
+    fn is_disabled_without_headers() {
+        assert!(!new(&BTreeMap::new(), false).is_enabled());
+        assert!(new(&BTreeMap::new(), true).is_enabled());
+    }
+
+    #[test]
+    fn rejects_invalid_headers() {
+        let configured = BTreeMap::from([("bad header".to_string(), "x".to_string())]);
+        assert!(new(&configured, false).apply(Router::new()).is_err());
+    }
+}
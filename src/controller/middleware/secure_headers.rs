@@ -76,6 +76,7 @@ fn get_presets() -> &'static HashMap<String, BTreeMap<String, String>> {
 ///
 /// For the list of presets and their content look at [secure_headers.json](https://github.com/loco-rs/loco/blob/master/src/controller/middleware/secure_headers.rs)
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct SecureHeader {
     #[serde(default)]
     pub enable: bool,
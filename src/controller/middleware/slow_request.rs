@@ -0,0 +1,362 @@
+//! Slow Request Middleware
+//!
+//! This middleware measures how long each request takes and emits a
+//! structured `slow request` warning when the total latency exceeds a
+//! configured threshold. The warning is emitted inside the request span (see
+//! the logger middleware), so it carries the request id and request details.
+//!
+//! The time of each request is broken down into the handler, timed from the
+//! matched route down, the middlewares around it, and the database queries
+//! run by the request, from the query logging of the connection. The
+//! breakdown is recorded on the request span, as `handler_ms`,
+//! `middleware_ms` and `db_ms`.
+//!
+//! Handlers and the code they call can attribute time to more named parts of
+//! the request (rendering, remote calls, ...) through the [`RequestTimings`]
+//! extractor. The warning then includes a breakdown of the recorded
+//! durations, plus the handler time that was not attributed to any of them.
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{FromRequestParts, Request},
+    http::request::Parts,
+    middleware::Next,
+    response::Response,
+    Router as AXRouter,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Error, Result};
+
+/// Slow request middleware configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+pub struct SlowRequest {
+    #[serde(default)]
+    pub enable: bool,
+    /// Requests taking longer than this threshold (in milliseconds) are
+    /// logged.
+    #[serde(default = "default_threshold")]
+    pub threshold: u64,
+}
+
+impl Default for SlowRequest {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+fn default_threshold() -> u64 {
+    1_000
+}
+
+/// The name the time of the handler is recorded under.
+pub const HANDLER: &str = "handler";
+/// The name the time of the database queries is recorded under.
+pub const DB: &str = "db";
+
+tokio::task_local! {
+    static CURRENT: RequestTimings;
+}
+
+impl MiddlewareLayer for SlowRequest {
+    /// Returns the name of the middleware.
+    fn name(&self) -> &'static str {
+        "slow_request"
+    }
+
+    /// Checks if the slow request middleware is enabled.
+    fn is_enabled(&self) -> bool {
+        self.enable
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(self)
+    }
+
+    /// Applies the slow request middleware to the application router.
+    ///
+    /// A fresh [`RequestTimings`] is added to the request extensions, and
+    /// the request is timed from this layer down to the handler.
+    fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        let threshold = Duration::from_millis(self.threshold);
+        Ok(app.layer(axum::middleware::from_fn(move |request, next| {
+            slow_request_middleware(threshold, request, next)
+        })))
+    }
+}
+
+async fn slow_request_middleware(
+    threshold: Duration,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let timings = RequestTimings::default();
+    request.extensions_mut().insert(timings.clone());
+
+    let start = Instant::now();
+    let response = timings.clone().scope(next.run(request)).await;
+    let elapsed = start.elapsed();
+
+    let span = tracing::Span::current();
+    let recorded = timings.get();
+    let millis = |name: &str| {
+        recorded
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, elapsed)| u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX))
+    };
+    if let Some(handler) = millis(HANDLER) {
+        let total = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+        span.record("handler_ms", handler);
+        span.record("middleware_ms", total.saturating_sub(handler));
+    }
+    span.record("db_ms", millis(DB).unwrap_or_default());
+
+    if elapsed > threshold {
+        tracing::warn!(
+            "http.status" = response.status().as_u16(),
+            latency_ms = elapsed.as_millis(),
+            threshold_ms = threshold.as_millis(),
+            breakdown = breakdown(&recorded, elapsed),
+            "slow request"
+        );
+    }
+    response
+}
+
+/// Times the matched route, from its layers down to the handler, under
+/// [`HANDLER`]. Applied to the routes of the app, it is a no-op when the slow
+/// request middleware is disabled.
+pub(crate) async fn time_handler(request: Request, next: Next) -> Response {
+    match request.extensions().get::<RequestTimings>().cloned() {
+        Some(timings) => timings.measure(HANDLER, next.run(request)).await,
+        None => next.run(request).await,
+    }
+}
+
+/// Formats the handler time and the recorded durations as `name=12ms` pairs,
+/// followed by the handler time that was not attributed to any of them, and
+/// the time spent in the middlewares.
+fn breakdown(timings: &[(String, Duration)], total: Duration) -> String {
+    let handler = timings
+        .iter()
+        .find(|(name, _)| name == HANDLER)
+        .map(|(_, elapsed)| *elapsed);
+    let parts = timings.iter().filter(|(name, _)| name != HANDLER);
+    let recorded: Duration = parts.clone().map(|(_, elapsed)| *elapsed).sum();
+    handler
+        .map(|handler| (HANDLER, handler))
+        .into_iter()
+        .chain(parts.map(|(name, elapsed)| (name.as_str(), *elapsed)))
+        .chain(std::iter::once((
+            "other",
+            handler.unwrap_or(total).saturating_sub(recorded),
+        )))
+        .chain(handler.map(|handler| ("middleware", total.saturating_sub(handler))))
+        .map(|(name, elapsed)| format!("{name}={}ms", elapsed.as_millis()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Named durations recorded while handling a request.
+///
+/// Extract it in a handler and pass it down to attribute time to parts of the
+/// request, or get the one of the request being handled with
+/// [`RequestTimings::current`]. Durations recorded under the same name are
+/// added up. The time of the handler and of the database queries is recorded
+/// under [`HANDLER`] and [`DB`]. When the slow request middleware is
+/// disabled, recording is a no-op from the caller's point of view.
+///
+/// # Example
+/// ```rust,ignore
+/// async fn show(State(ctx): State<AppContext>, timings: RequestTimings) -> Result<Response> {
+///     let invoices = timings.measure("billing", billing::invoices(&ctx)).await?;
+///     format::json(invoices)
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RequestTimings {
+    timings: Arc<Mutex<Vec<(String, Duration)>>>,
+}
+
+impl RequestTimings {
+    /// Returns the timings of the request being handled, when the slow
+    /// request middleware is enabled.
+    #[must_use]
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+
+    /// Runs `future` with `self` as the current timings.
+    pub(crate) async fn scope<F: Future>(self, future: F) -> F::Output {
+        CURRENT.scope(self, future).await
+    }
+
+    /// Adds `elapsed` to the duration recorded under `name`.
+    ///
+    /// # Panics
+    /// When the timings lock is poisoned
+    pub fn record(&self, name: &str, elapsed: Duration) {
+        let mut timings = self.timings.lock().unwrap();
+        if let Some((_, total)) = timings.iter_mut().find(|(n, _)| n == name) {
+            *total += elapsed;
+        } else {
+            timings.push((name.to_string(), elapsed));
+        }
+    }
+
+    /// Awaits `future` and records the time it took under `name`.
+    pub async fn measure<F: Future>(&self, name: &str, future: F) -> F::Output {
+        let start = Instant::now();
+        let output = future.await;
+        self.record(name, start.elapsed());
+        output
+    }
+
+    /// Returns the recorded durations, in the order they were first recorded.
+    ///
+    /// # Panics
+    /// When the timings lock is poisoned
+    #[must_use]
+    pub fn get(&self) -> Vec<(String, Duration)> {
+        self.timings.lock().unwrap().clone()
+    }
+}
+
+impl<S> FromRequestParts<S> for RequestTimings
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Error> {
+        Ok(parts.extensions.get::<Self>().cloned().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request, routing::get, Extension, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    #[test]
+    fn can_format_breakdown() {
+        let timings = vec![
+            ("db".to_string(), Duration::from_millis(40)),
+            ("render".to_string(), Duration::from_millis(10)),
+        ];
+        assert_eq!(
+            breakdown(&timings, Duration::from_millis(75)),
+            "db=40ms render=10ms other=25ms"
+        );
+        assert_eq!(breakdown(&[], Duration::from_millis(5)), "other=5ms");
+
+        let timings = vec![
+            ("handler".to_string(), Duration::from_millis(70)),
+            ("db".to_string(), Duration::from_millis(40)),
+        ];
+        assert_eq!(
+            breakdown(&timings, Duration::from_millis(75)),
+            "handler=70ms db=40ms other=30ms middleware=5ms"
+        );
+    }
+
+    #[test]
+    fn can_accumulate_timings() {
+        let timings = RequestTimings::default();
+        timings.record("db", Duration::from_millis(3));
+        timings.record("render", Duration::from_millis(1));
+        timings.record("db", Duration::from_millis(4));
+        assert_eq!(
+            timings.get(),
+            vec![
+                ("db".to_string(), Duration::from_millis(7)),
+                ("render".to_string(), Duration::from_millis(1)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn middleware_provides_request_timings() {
+        // `Extension` rejects the request when the middleware did not insert
+        // the timings
+        async fn action(Extension(timings): Extension<RequestTimings>) -> String {
+            timings.record("db", Duration::from_millis(1));
+            format!("{:?}", timings.get())
+        }
+
+        let middleware = SlowRequest {
+            enable: true,
+            threshold: 0,
+        };
+        let app = middleware
+            .apply(Router::new().route("/", get(action)))
+            .unwrap()
+            .with_state(tests_cfg::app::get_app_context().await);
+
+        let response = app
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, r#"[("db", 1ms)]"#);
+    }
+
+    #[tokio::test]
+    async fn can_time_handlers() {
+        async fn action() -> String {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            format!(
+                "{:?}",
+                RequestTimings::current().map(|timings| timings.get())
+            )
+        }
+
+        let middleware = SlowRequest {
+            enable: true,
+            threshold: 0,
+        };
+        let app = middleware
+            .apply(
+                Router::new()
+                    .route("/", get(action))
+                    .route_layer(axum::middleware::from_fn(time_handler)),
+            )
+            .unwrap()
+            .with_state(tests_cfg::app::get_app_context().await);
+
+        let response = app
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        // the handler is timed once it returns
+        assert_eq!(body, "Some([])");
+
+        let timings = RequestTimings::default();
+        let app = Router::new()
+            .route("/", get(action))
+            .route_layer(axum::middleware::from_fn(time_handler))
+            .layer(Extension(timings.clone()));
+        app.oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let recorded = timings.get();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].0, HANDLER);
+        assert!(recorded[0].1 >= Duration::from_millis(20));
+    }
+}
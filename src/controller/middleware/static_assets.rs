@@ -20,6 +20,7 @@ use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Error, Res
 
 /// Static asset middleware configuration
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct StaticAssets {
     #[serde(default)]
     pub enable: bool,
@@ -63,6 +64,7 @@ fn default_folder_config() -> FolderConfig {
     }
 }
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct FolderConfig {
     /// Uri for the assets
     pub uri: String,
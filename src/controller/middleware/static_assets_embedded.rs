@@ -27,6 +27,7 @@ include!(concat!(env!("OUT_DIR"), "/generated_code/static_assets.rs"));
 
 /// Static asset middleware configuration
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct StaticAssets {
     #[serde(default)]
     pub enable: bool,
@@ -71,6 +72,7 @@ fn default_folder_config() -> FolderConfig {
 }
 
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct FolderConfig {
     /// Uri for the assets
     pub uri: String,
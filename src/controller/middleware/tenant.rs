@@ -0,0 +1,270 @@
+//! Tenant Middleware
+//!
+//! This middleware binds each request to a tenant, resolved from a request
+//! header or from the subdomain of the `Host`, and stores it as a
+//! [`CurrentTenant`] in the request extensions. Handlers then get a database
+//! transaction scoped to the tenant schema with the
+//! [`crate::controller::extractor::tenant::TenantDb`] extractor.
+//!
+//! Requests without a tenant get a `400 Bad Request` when `required`, and
+//! requests for an invalid tenant, or one missing from `allow` when set, a
+//! `404 Not Found`. The main schema, `public`, and the system schemas are
+//! never tenants, and [`crate::controller::extractor::tenant::TenantDb`]
+//! also responds `404 Not Found` for a tenant without a schema.
+use axum::{
+    extract::{FromRequestParts, Request},
+    http::{header, request::Parts, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router as AXRouter,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    app::AppContext,
+    controller::{middleware::MiddlewareLayer, ErrorDetail},
+    Error, Result,
+};
+
+/// The tenant of a request, set by the tenant middleware.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CurrentTenant(pub String);
+
+impl<S: Send + Sync> FromRequestParts<S> for CurrentTenant {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self> {
+        parts.extensions.get::<Self>().cloned().ok_or_else(|| {
+            tracing::error!("no tenant for the request, is the tenant middleware enabled?");
+            Error::InternalServerError
+        })
+    }
+}
+
+/// Schemas which are never tenants: the main schema and the system ones.
+const RESERVED_NAMES: &[&str] = &["public", "information_schema"];
+
+/// Whether `tenant` is a valid tenant name, which is also its schema name: a
+/// lowercase identifier of ASCII letters, digits and underscores, starting
+/// with a letter, of at most 63 characters, other than the main and system
+/// schemas.
+#[must_use]
+pub fn is_valid_name(tenant: &str) -> bool {
+    tenant.len() <= 63
+        && tenant.starts_with(|c: char| c.is_ascii_lowercase())
+        && tenant
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && !tenant.starts_with("pg_")
+        && !RESERVED_NAMES.contains(&tenant)
+}
+
+/// The error of a request for an unknown tenant.
+pub(crate) fn not_found() -> Error {
+    Error::CustomError(
+        StatusCode::NOT_FOUND,
+        ErrorDetail::new("tenant_not_found", "Tenant not found"),
+    )
+}
+
+/// Tenant middleware configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+pub struct Tenant {
+    #[serde(default)]
+    pub enable: bool,
+    /// The request header naming the tenant.
+    #[serde(default = "default_header")]
+    pub header: Option<String>,
+    /// The domain tenants are subdomains of, such as `example.com` for
+    /// `acme.example.com`, checked when the header is missing.
+    #[serde(default)]
+    pub subdomain_of: Option<String>,
+    /// The known tenants, any valid tenant is accepted when empty.
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// Whether requests without a tenant are rejected.
+    #[serde(default = "default_required")]
+    pub required: bool,
+}
+
+impl Default for Tenant {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+#[allow(clippy::unnecessary_wraps)]
+fn default_header() -> Option<String> {
+    Some("x-tenant".to_string())
+}
+
+const fn default_required() -> bool {
+    true
+}
+
+impl MiddlewareLayer for Tenant {
+    /// Returns the name of the middleware.
+    fn name(&self) -> &'static str {
+        "tenant"
+    }
+
+    /// Checks if the tenant middleware is enabled.
+    fn is_enabled(&self) -> bool {
+        self.enable
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(self)
+    }
+
+    /// Applies the tenant middleware to the application router.
+    fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        let config = self.clone();
+        Ok(app.layer(axum::middleware::from_fn(move |request, next| {
+            let config = config.clone();
+            async move { config.bind(request, next).await }
+        })))
+    }
+}
+
+impl Tenant {
+    async fn bind(&self, mut request: Request, next: Next) -> Response {
+        let Some(tenant) = self.resolve(&request) else {
+            if self.required {
+                return Error::BadRequest("missing tenant".to_string()).into_response();
+            }
+            return next.run(request).await;
+        };
+
+        let known = self.allow.is_empty() || self.allow.contains(&tenant);
+        if !known || !is_valid_name(&tenant) {
+            tracing::debug!(tenant, "unknown tenant");
+            return not_found().into_response();
+        }
+        request.extensions_mut().insert(CurrentTenant(tenant));
+        next.run(request).await
+    }
+
+    /// Returns the tenant named by the header, or else by the subdomain.
+    fn resolve(&self, request: &Request) -> Option<String> {
+        let headers = request.headers();
+        let from_header = self.header.as_ref().and_then(|name| {
+            headers
+                .get(name.as_str())
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        });
+        if let Some(tenant) = from_header {
+            return Some(tenant.to_string());
+        }
+
+        let domain = self.subdomain_of.as_deref()?;
+        let host = headers
+            .get(header::HOST)
+            .and_then(|value| value.to_str().ok())
+            .or_else(|| request.uri().host())?;
+        let host = host.split(':').next().unwrap_or(host);
+        host.strip_suffix(domain)
+            .and_then(|subdomain| subdomain.strip_suffix('.'))
+            .filter(|subdomain| !subdomain.is_empty() && !subdomain.contains('.'))
+            .map(ToString::to_string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    async fn tenant(CurrentTenant(tenant): CurrentTenant) -> String {
+        tenant
+    }
+
+    async fn app(config: Tenant) -> Router {
+        config
+            .apply(Router::new().route("/", get(tenant)))
+            .unwrap()
+            .with_state(tests_cfg::app::get_app_context().await)
+    }
+
+    async fn call(app: &Router, headers: &[(&str, &str)]) -> (StatusCode, String) {
+        let mut request = Request::get("/");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let response = app
+            .clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn can_resolve_tenant() {
+        let app = app(Tenant {
+            enable: true,
+            subdomain_of: Some("example.com".to_string()),
+            ..Default::default()
+        })
+        .await;
+
+        assert_eq!(
+            call(&app, &[("x-tenant", "acme")]).await,
+            (StatusCode::OK, "acme".to_string())
+        );
+        assert_eq!(
+            call(&app, &[("host", "globex.example.com:5150")]).await,
+            (StatusCode::OK, "globex".to_string())
+        );
+        // the header wins over the subdomain
+        assert_eq!(
+            call(
+                &app,
+                &[("host", "globex.example.com"), ("x-tenant", "acme")]
+            )
+            .await,
+            (StatusCode::OK, "acme".to_string())
+        );
+        assert_eq!(
+            call(&app, &[("host", "a.b.example.com")]).await.0,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(call(&app, &[]).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            call(&app, &[("x-tenant", "Acme; DROP")]).await.0,
+            StatusCode::NOT_FOUND
+        );
+        // the main schema is not a tenant
+        assert_eq!(
+            call(&app, &[("x-tenant", "public")]).await.0,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn can_allow_known_tenants() {
+        let app = app(Tenant {
+            enable: true,
+            allow: vec!["acme".to_string()],
+            ..Default::default()
+        })
+        .await;
+
+        assert_eq!(call(&app, &[("x-tenant", "acme")]).await.0, StatusCode::OK);
+        assert_eq!(
+            call(&app, &[("x-tenant", "globex")]).await.0,
+            StatusCode::NOT_FOUND
+        );
+    }
+}
@@ -9,17 +9,22 @@
 //! If a request exceeds the specified timeout duration, the middleware will
 //! return a `408 Request Timeout` status code to the client, indicating that
 //! the request took too long to process.
+//!
+//! Requests are handled with a [`crate::deadline::Deadline`] at the timeout,
+//! for the database and storage work done for them to stop early when the
+//! request times out or the client goes away.
 use std::time::Duration;
 
-use axum::Router as AXRouter;
+use axum::{extract::Request, middleware::Next, Router as AXRouter};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tower_http::timeout::TimeoutLayer;
 
-use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Result};
+use crate::{app::AppContext, controller::middleware::MiddlewareLayer, deadline::Deadline, Result};
 
 /// Timeout middleware configuration
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub struct TimeOut {
     #[serde(default)]
     pub enable: bool,
@@ -59,6 +64,13 @@ impl MiddlewareLayer for TimeOut {
     /// ensuring that requests exceeding the specified timeout duration will
     /// be interrupted.
     fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
-        Ok(app.layer(TimeoutLayer::new(Duration::from_millis(self.timeout))))
+        let timeout = Duration::from_millis(self.timeout);
+        Ok(app
+            .layer(axum::middleware::from_fn(
+                move |request: Request, next: Next| {
+                    Deadline::after(timeout).scope(next.run(request))
+                },
+            ))
+            .layer(TimeoutLayer::new(timeout)))
     }
 }
@@ -0,0 +1,241 @@
+//! Trailing Slash Middleware
+//!
+//! This middleware handles the trailing slash of request paths in one of
+//! three modes:
+//!
+//! * `off`: paths are routed as they are, so `/users/` does not match the
+//!   `/users` route.
+//! * `trim`: trailing slashes are removed before routing, so `/users/` is
+//!   served by the `/users` route.
+//! * `redirect_permanent`: requests to a non-canonical path are redirected to
+//!   the canonical one, `/users/` to `/users` (or `/users` to `/users/` with
+//!   `append_slash`), so that a single URL gets indexed. `GET` and `HEAD`
+//!   requests get a `301 Moved Permanently`, other methods a
+//!   `308 Permanent Redirect`, which keeps their method and body.
+//!
+//! Route paths never end with a slash, so unlike the other middlewares, the
+//! path is rewritten before the request is routed: the middleware wraps the
+//! router built so far instead of its routes.
+use std::sync::{Arc, OnceLock};
+
+use axum::{
+    extract::{Request, State},
+    http::{header, uri::PathAndQuery, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Router as AXRouter,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tower::Service;
+
+use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Result};
+
+/// How trailing slashes are handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum TrailingSlashMode {
+    /// Paths are routed as they are
+    #[default]
+    Off,
+    /// Trailing slashes are removed before routing
+    Trim,
+    /// Non-canonical paths are permanently redirected to the canonical one
+    RedirectPermanent,
+}
+
+/// Trailing slash middleware configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+pub struct TrailingSlash {
+    #[serde(default)]
+    pub mode: TrailingSlashMode,
+    /// Whether the canonical paths end with a slash, in `redirect_permanent`
+    /// mode.
+    #[serde(default)]
+    pub append_slash: bool,
+}
+
+impl Default for TrailingSlash {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+impl MiddlewareLayer for TrailingSlash {
+    /// Returns the name of the middleware.
+    fn name(&self) -> &'static str {
+        "trailing_slash"
+    }
+
+    /// Checks if the trailing slash middleware is enabled.
+    fn is_enabled(&self) -> bool {
+        self.mode != TrailingSlashMode::Off
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(self)
+    }
+
+    /// Wraps the application router, to handle trailing slashes before
+    /// routing.
+    fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        let config = self.clone();
+        // the wrapped router gets the app state on the first request
+        let inner = Arc::new(OnceLock::<AXRouter>::new());
+        Ok(
+            AXRouter::new().fallback(move |State(ctx): State<AppContext>, request: Request| {
+                let mut router = inner.get_or_init(|| app.clone().with_state(ctx)).clone();
+                let config = config.clone();
+                async move {
+                    match config.handle(request) {
+                        Ok(request) => router.call(request).await.into_response(),
+                        Err(redirect) => redirect,
+                    }
+                }
+            }),
+        )
+    }
+}
+
+impl TrailingSlash {
+    /// Returns the request to route, or the redirect to its canonical path.
+    fn handle(&self, mut request: Request) -> std::result::Result<Request, Response> {
+        let path = request.uri().path();
+        if path == "/" {
+            return Ok(request);
+        }
+
+        if self.mode == TrailingSlashMode::RedirectPermanent {
+            let canonical = if self.append_slash {
+                format!("{}/", path.trim_end_matches('/'))
+            } else {
+                path.trim_end_matches('/').to_string()
+            };
+            if canonical != path {
+                let location = with_query(canonical, request.uri().query());
+                let status = if matches!(*request.method(), Method::GET | Method::HEAD) {
+                    StatusCode::MOVED_PERMANENTLY
+                } else {
+                    StatusCode::PERMANENT_REDIRECT
+                };
+                return Err((status, [(header::LOCATION, location)]).into_response());
+            }
+        }
+
+        if path.ends_with('/') {
+            let trimmed = with_query(
+                path.trim_end_matches('/').to_string(),
+                request.uri().query(),
+            );
+            let mut parts = request.uri().clone().into_parts();
+            parts.path_and_query = PathAndQuery::try_from(trimmed).ok();
+            if let Ok(uri) = Uri::from_parts(parts) {
+                *request.uri_mut() = uri;
+            }
+        }
+        Ok(request)
+    }
+}
+
+fn with_query(mut path: String, query: Option<&str>) -> String {
+    if let Some(query) = query {
+        path.push('?');
+        path.push_str(query);
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    async fn app(mode: TrailingSlashMode, append_slash: bool) -> Router {
+        TrailingSlash { mode, append_slash }
+            .apply(Router::new().route("/", get(|| async { "home" })).route(
+                "/users",
+                get(|| async { "users" }).post(|| async { "created" }),
+            ))
+            .unwrap()
+            .with_state(tests_cfg::app::get_app_context().await)
+    }
+
+    async fn call(app: &Router, method: Method, uri: &str) -> (StatusCode, String) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let location = response
+            .headers()
+            .get(header::LOCATION)
+            .map(|location| location.to_str().unwrap().to_string());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            location.unwrap_or_else(|| String::from_utf8(body.to_vec()).unwrap()),
+        )
+    }
+
+    #[tokio::test]
+    async fn can_trim_before_routing() {
+        let app = app(TrailingSlashMode::Trim, false).await;
+        for uri in ["/users", "/users/", "/users//?page=2"] {
+            assert_eq!(
+                call(&app, Method::GET, uri).await,
+                (StatusCode::OK, "users".to_string())
+            );
+        }
+        assert_eq!(
+            call(&app, Method::GET, "/").await,
+            (StatusCode::OK, "home".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn can_redirect_to_canonical_path() {
+        let app = app(TrailingSlashMode::RedirectPermanent, false).await;
+        assert_eq!(
+            call(&app, Method::GET, "/users/?page=2").await,
+            (StatusCode::MOVED_PERMANENTLY, "/users?page=2".to_string())
+        );
+        assert_eq!(
+            call(&app, Method::POST, "/users/").await,
+            (StatusCode::PERMANENT_REDIRECT, "/users".to_string())
+        );
+        assert_eq!(
+            call(&app, Method::GET, "/users").await,
+            (StatusCode::OK, "users".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn can_redirect_to_appended_slash() {
+        let app = app(TrailingSlashMode::RedirectPermanent, true).await;
+        assert_eq!(
+            call(&app, Method::GET, "/users").await,
+            (StatusCode::MOVED_PERMANENTLY, "/users/".to_string())
+        );
+        assert_eq!(
+            call(&app, Method::GET, "/users/").await,
+            (StatusCode::OK, "users".to_string())
+        );
+        assert_eq!(
+            call(&app, Method::GET, "/").await,
+            (StatusCode::OK, "home".to_string())
+        );
+    }
+
+    #[test]
+    fn is_off_by_default() {
+        assert!(!TrailingSlash::default().is_enabled());
+    }
+}
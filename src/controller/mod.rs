@@ -66,12 +66,15 @@
 //! ```
 
 pub use app_routes::{AppRoutes, ListRoutes};
+use std::sync::Arc;
+
 use axum::{
-    extract::FromRequest,
-    http::StatusCode,
+    extract::{FromRequest, Request},
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
 };
 use colored::Colorize;
+pub use layer_registry::LayerRegistry;
 pub use routes::Routes;
 use serde::Serialize;
 
@@ -80,13 +83,22 @@ use crate::{errors::Error, Result};
 mod app_routes;
 mod backtrace;
 mod describe;
+pub mod error_format;
 pub mod extractor;
 pub mod format;
 #[cfg(feature = "with-db")]
 mod health;
+pub mod json;
+mod layer_registry;
+pub mod long_poll;
 pub mod middleware;
 mod ping;
+pub mod precondition;
+pub mod responder;
+pub mod response;
 mod routes;
+pub mod sse;
+mod versioning;
 pub mod views;
 
 /// Create an unauthorized error with a specified message.
@@ -132,7 +144,7 @@ pub fn bad_request<T: Into<String>, U>(msg: T) -> Result<U> {
 pub fn not_found<T>() -> Result<T> {
     Err(Error::NotFound)
 }
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 /// Structure representing details about an error.
 pub struct ErrorDetail {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -166,13 +178,41 @@ impl ErrorDetail {
     }
 }
 
-#[derive(Debug, FromRequest)]
-#[from_request(via(axum::Json), rejection(Error))]
+/// A JSON request body or response, read and written by the [`json`] library.
+#[derive(Debug)]
 pub struct Json<T>(pub T);
 
+impl<T, S> FromRequest<S> for Json<T>
+where
+    T: serde::de::DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self> {
+        json::from_request(req, state).await.map(Self)
+    }
+}
+
 impl<T: Serialize> IntoResponse for Json<T> {
     fn into_response(self) -> axum::response::Response {
-        axum::Json(self.0).into_response()
+        let body = match middleware::json_format::JsonFormat::current() {
+            Some(format) => serde_json::to_value(self.0)
+                .map_err(Error::from)
+                .and_then(|value| Ok(json::to_vec(&format.apply(value))?)),
+            None => json::to_vec(&self.0).map_err(Error::from),
+        };
+        match body {
+            Ok(body) => (
+                [(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/json"),
+                )],
+                body,
+            )
+                .into_response(),
+            Err(err) => err.into_response(),
+        }
     }
 }
 
@@ -200,7 +240,38 @@ impl IntoResponse for Error {
             }
         }
 
-        let public_facing_error = match self {
+        let (status, detail) = self.public_error();
+        let mut response = (status, Json(&detail)).into_response();
+        let extensions = response.extensions_mut();
+        extensions.insert(ErrorBody(detail));
+        extensions.insert(ErrorResponse(Arc::new(self)));
+        response
+    }
+}
+
+/// The error a response was made from, kept in the response extensions for
+/// [`crate::app::Hooks::on_error`].
+#[derive(Clone)]
+pub(crate) struct ErrorResponse(pub(crate) Arc<Error>);
+
+/// The public detail of the error a response was made from, to render it in
+/// another format, see [`error_format`].
+#[derive(Clone)]
+pub(crate) struct ErrorBody(pub(crate) ErrorDetail);
+
+impl Error {
+    /// The public facing status and detail of the error.
+    fn public_error(&self) -> (StatusCode, ErrorDetail) {
+        #[cfg(feature = "with-db")]
+        if let Some(violation) = self.constraint_violation() {
+            return (violation.status(), violation.detail());
+        }
+        #[cfg(feature = "with-db")]
+        if matches!(self, Self::Model(crate::model::ModelError::StaleVersion)) {
+            return Self::PreconditionFailed.public_error();
+        }
+
+        match self {
             Self::NotFound => (
                 StatusCode::NOT_FOUND,
                 ErrorDetail::new("not_found", "Resource was not found"),
@@ -215,10 +286,10 @@ impl IntoResponse for Error {
                     ),
                 )
             }
-            Self::CustomError(status_code, data) => (status_code, data),
+            Self::CustomError(status_code, data) => (*status_code, data.clone()),
             Self::WithBacktrace { inner, backtrace } => {
                 println!("\n{}", inner.to_string().red().underline());
-                backtrace::print_backtrace(&backtrace).unwrap();
+                backtrace::print_backtrace(backtrace).unwrap();
                 (
                     StatusCode::BAD_REQUEST,
                     ErrorDetail::with_reason("Bad Request"),
@@ -226,37 +297,40 @@ impl IntoResponse for Error {
             }
             Self::BadRequest(err) => (
                 StatusCode::BAD_REQUEST,
-                ErrorDetail::new("Bad Request", &err),
+                ErrorDetail::new("Bad Request", err.as_str()),
             ),
             Self::JsonRejection(err) => {
                 tracing::debug!(err = err.body_text(), "json rejection");
                 (err.status(), ErrorDetail::with_reason("Bad Request"))
             }
 
-            Self::ValidationError(ref errors) => serde_json::to_value(errors).map_or_else(
-                |_| {
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        ErrorDetail::new("internal_server_error", "Internal Server Error"),
-                    )
-                },
-                |errors| {
-                    (
-                        StatusCode::BAD_REQUEST,
-                        ErrorDetail {
-                            error: None,
-                            description: None,
-                            errors: Some(errors),
-                        },
-                    )
-                },
+            Self::ValidationError(errors) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                crate::validation::error_detail(errors),
             ),
+            Self::DeadlineExceeded
+            | Self::Storage(crate::storage::StorageError::DeadlineExceeded) => (
+                StatusCode::REQUEST_TIMEOUT,
+                ErrorDetail::new("request_timeout", "Request took too long"),
+            ),
+            Self::PreconditionFailed => (
+                StatusCode::PRECONDITION_FAILED,
+                ErrorDetail::new(
+                    "precondition_failed",
+                    "The resource was modified by another request",
+                ),
+            ),
+            Self::QueueFull { queue, .. } => {
+                tracing::warn!(queue, "rejecting request: {self}");
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    ErrorDetail::new("service_unavailable", "Service is busy, try again later"),
+                )
+            }
             _ => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 ErrorDetail::new("internal_server_error", "Internal Server Error"),
             ),
-        };
-
-        (public_facing_error.0, Json(public_facing_error.1)).into_response()
+        }
     }
 }
@@ -0,0 +1,189 @@
+//! # Conditional Updates
+//!
+//! Optimistic concurrency for `PUT` and `PATCH`: a client sends the `ETag` it
+//! read along with its update, in `If-Match`, or the `Last-Modified` date, in
+//! `If-Unmodified-Since`, and the update is refused with `412 Precondition
+//! Failed` when the resource changed meanwhile, instead of silently
+//! overwriting the changes of another client:
+//!
+//! ```rust,ignore
+//! async fn show(Path(id): Path<i32>, State(ctx): State<AppContext>) -> Result<Response> {
+//!     let note = load_item(&ctx, id).await?;
+//!     format::render().etag(&precondition::etag(note.version))?.json(note)
+//! }
+//!
+//! async fn update(
+//!     Path(id): Path<i32>,
+//!     preconditions: Preconditions,
+//!     State(ctx): State<AppContext>,
+//!     Json(params): Json<Params>,
+//! ) -> Result<Response> {
+//!     let note = load_item(&ctx, id).await?;
+//!     preconditions.check(&precondition::etag(note.version), None)?;
+//!     let version = note.version;
+//!     let mut note = note.into_active_model();
+//!     params.update(&mut note);
+//!     // fails with `412` too when another update won the race since the check
+//!     let note = note.update_versioned(&ctx.db, version).await?;
+//!     format::render().etag(&precondition::etag(note.version))?.json(note)
+//! }
+//! ```
+//!
+//! Requests without these headers are not conditional, and always pass.
+use std::fmt::Display;
+
+use axum::{
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap},
+};
+use chrono::{DateTime, Utc};
+
+use crate::{Error, Result};
+
+/// Returns the strong `ETag` of the version of a resource, such as `"3"`.
+#[must_use]
+pub fn etag(version: impl Display) -> String {
+    format!("\"{version}\"")
+}
+
+/// The preconditions of a request, from its `If-Match` and
+/// `If-Unmodified-Since` headers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Preconditions {
+    /// The entity tags of `If-Match`, `*` matching any.
+    pub if_match: Option<Vec<String>>,
+    pub if_unmodified_since: Option<DateTime<Utc>>,
+}
+
+impl Preconditions {
+    /// Reads the preconditions of the request headers. An
+    /// `If-Unmodified-Since` which is not a date is ignored.
+    #[must_use]
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let if_match = headers
+            .get_all(header::IF_MATCH)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect::<Vec<_>>();
+        let if_unmodified_since = headers
+            .get(header::IF_UNMODIFIED_SINCE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+            .map(|date| date.to_utc());
+        Self {
+            if_match: (!if_match.is_empty()).then_some(if_match),
+            if_unmodified_since,
+        }
+    }
+
+    /// Whether the request carries preconditions.
+    #[must_use]
+    pub const fn is_conditional(&self) -> bool {
+        self.if_match.is_some() || self.if_unmodified_since.is_some()
+    }
+
+    /// Checks the preconditions against the current `ETag` of the resource,
+    /// see [`etag`], and the time it was last modified, if known. As
+    /// specified by HTTP, `If-Unmodified-Since` is only checked without
+    /// `If-Match`, and weak tags never match.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::PreconditionFailed`] when the resource changed since the
+    /// client read it
+    pub fn check(&self, etag: &str, last_modified: Option<DateTime<Utc>>) -> Result<()> {
+        if let Some(tags) = &self.if_match {
+            let matches = tags
+                .iter()
+                .any(|tag| tag == "*" || (tag == etag && !tag.starts_with("W/")));
+            return if matches {
+                Ok(())
+            } else {
+                Err(Error::PreconditionFailed)
+            };
+        }
+        match (self.if_unmodified_since, last_modified) {
+            // HTTP dates have a resolution of a second
+            (Some(since), Some(modified)) if modified.timestamp() > since.timestamp() => {
+                Err(Error::PreconditionFailed)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Preconditions {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self> {
+        Ok(Self::from_headers(&parts.headers))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        http::{HeaderValue, StatusCode},
+        response::IntoResponse,
+    };
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn preconditions(headers: &[(header::HeaderName, &'static str)]) -> Preconditions {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.append(name, HeaderValue::from_static(value));
+        }
+        Preconditions::from_headers(&map)
+    }
+
+    #[test]
+    fn can_check_if_match() {
+        let current = etag(3);
+        assert_eq!(current, "\"3\"");
+
+        assert!(!preconditions(&[]).is_conditional());
+        assert!(preconditions(&[]).check(&current, None).is_ok());
+        assert!(preconditions(&[(header::IF_MATCH, "\"2\", \"3\"")])
+            .check(&current, None)
+            .is_ok());
+        assert!(preconditions(&[(header::IF_MATCH, "*")])
+            .check(&current, None)
+            .is_ok());
+
+        let stale = preconditions(&[(header::IF_MATCH, "\"2\"")]).check(&current, None);
+        assert_eq!(
+            stale.unwrap_err().into_response().status(),
+            StatusCode::PRECONDITION_FAILED
+        );
+        assert!(preconditions(&[(header::IF_MATCH, "W/\"3\"")])
+            .check(&current, None)
+            .is_err());
+    }
+
+    #[test]
+    fn can_check_if_unmodified_since() {
+        let read_at =
+            preconditions(&[(header::IF_UNMODIFIED_SINCE, "Wed, 01 May 2024 10:00:00 GMT")]);
+        assert_eq!(
+            read_at.if_unmodified_since,
+            Some(Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap())
+        );
+
+        let unchanged = Utc.with_ymd_and_hms(2024, 5, 1, 9, 0, 0).unwrap();
+        let changed = Utc.with_ymd_and_hms(2024, 5, 1, 11, 0, 0).unwrap();
+        assert!(read_at.check(&etag(3), Some(unchanged)).is_ok());
+        assert!(read_at.check(&etag(3), Some(changed)).is_err());
+
+        // If-Match takes precedence
+        let both = preconditions(&[
+            (header::IF_MATCH, "\"3\""),
+            (header::IF_UNMODIFIED_SINCE, "Wed, 01 May 2024 10:00:00 GMT"),
+        ]);
+        assert!(both.check(&etag(3), Some(changed)).is_ok());
+    }
+}
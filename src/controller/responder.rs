@@ -0,0 +1,229 @@
+//! # Responder
+//!
+//! A consistent API contract for JSON controllers. Values returned through
+//! [`Responder`] share the same success envelope:
+//!
+//! * a serializable value is sent as `{ "data": ... }`,
+//! * a [`PaginatedResponse`] is sent as `{ "data": [...], "meta": {...} }`
+//!   with the pagination details in `meta`,
+//! * `()` is sent as `204 No Content`,
+//! * errors keep the standard Loco error response.
+//!
+//! Return [`Api`] from a handler to make `Ok(value)` a consistent response,
+//! or call [`Responder::respond`] in a handler returning `Result<Response>`.
+//!
+//! # Example:
+//!
+//! ```rust
+//! use loco_rs::{controller::responder::Api, prelude::*};
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize)]
+//! pub struct Health {
+//!     pub ok: bool,
+//! }
+//!
+//! async fn health() -> Result<Api<Health>> {
+//!     Ok(Api(Health { ok: true }))
+//! }
+//!
+//! async fn ping() -> Result<Response> {
+//!     Health { ok: true }.respond()
+//! }
+//! ```
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::Value;
+
+#[cfg(feature = "with-db")]
+use crate::model::query::{PageResponse, PaginationQuery};
+use crate::{controller::Json, Result};
+
+/// Converts a handler's return value into a response following the API
+/// envelope.
+pub trait Responder {
+    /// Returns the response for this value.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the value fails to serialize
+    fn respond(self) -> Result<Response>;
+}
+
+/// Responds with a handler's return value through its [`Responder`]
+/// implementation.
+#[derive(Debug)]
+pub struct Api<T>(pub T);
+
+impl<T: Responder> IntoResponse for Api<T> {
+    fn into_response(self) -> Response {
+        self.0.respond().unwrap_or_else(IntoResponse::into_response)
+    }
+}
+
+#[derive(Serialize)]
+struct Envelope<T> {
+    data: T,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    meta: Option<Value>,
+}
+
+/// Serializable values are wrapped in `{ "data": ... }`. Values serializing to
+/// `null`, such as `()` or `None`, respond with `204 No Content`.
+impl<T: Serialize> Responder for T {
+    fn respond(self) -> Result<Response> {
+        let data = serde_json::to_value(self)?;
+        if data.is_null() {
+            return Ok(StatusCode::NO_CONTENT.into_response());
+        }
+        Ok(Json(Envelope { data, meta: None }).into_response())
+    }
+}
+
+/// A page of results with its pagination details, responding with
+/// `{ "data": [...], "meta": { "page", "page_size", "total_pages",
+/// "total_items" } }`.
+#[cfg(feature = "with-db")]
+#[derive(Debug)]
+pub struct PaginatedResponse<T> {
+    pub items: Vec<T>,
+    pub page: u64,
+    pub page_size: u64,
+    pub total_pages: u64,
+    pub total_items: u64,
+}
+
+#[cfg(feature = "with-db")]
+impl<T> PaginatedResponse<T> {
+    /// Creates the response for a page fetched with [`crate::model::query::paginate`].
+    #[must_use]
+    pub fn new(page: PageResponse<T>, query: &PaginationQuery) -> Self {
+        Self {
+            items: page.page,
+            page: query.page,
+            page_size: query.page_size,
+            total_pages: page.total_pages,
+            total_items: page.total_items,
+        }
+    }
+
+    /// Converts the items of the page, for example from models to views.
+    #[must_use]
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> PaginatedResponse<U> {
+        PaginatedResponse {
+            items: self.items.into_iter().map(f).collect(),
+            page: self.page,
+            page_size: self.page_size,
+            total_pages: self.total_pages,
+            total_items: self.total_items,
+        }
+    }
+}
+
+#[cfg(feature = "with-db")]
+impl<T: Serialize> Responder for PaginatedResponse<T> {
+    fn respond(self) -> Result<Response> {
+        let meta = serde_json::json!({
+            "page": self.page,
+            "page_size": self.page_size,
+            "total_pages": self.total_pages,
+            "total_items": self.total_items,
+        });
+        Ok(Json(Envelope {
+            data: self.items,
+            meta: Some(meta),
+        })
+        .into_response())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{routing::get, Router};
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::Error;
+
+    async fn call(router: Router) -> (StatusCode, String) {
+        let response = router
+            .oneshot(
+                axum::http::Request::get("/")
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn can_respond_with_data_envelope() {
+        async fn action() -> Result<Api<Value>> {
+            Ok(Api(json!({"name": "loco"})))
+        }
+
+        let (status, body) = call(Router::new().route("/", get(action))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, r#"{"data":{"name":"loco"}}"#);
+    }
+
+    #[tokio::test]
+    async fn unit_responds_with_no_content() {
+        async fn action() -> Result<Api<()>> {
+            Ok(Api(()))
+        }
+
+        let (status, body) = call(Router::new().route("/", get(action))).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn errors_keep_the_error_response() {
+        async fn action() -> Result<Api<()>> {
+            Err(Error::NotFound)
+        }
+
+        let (status, body) = call(Router::new().route("/", get(action))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(
+            body,
+            r#"{"error":"not_found","description":"Resource was not found"}"#
+        );
+    }
+
+    #[cfg(feature = "with-db")]
+    #[tokio::test]
+    async fn can_respond_with_pagination_meta() {
+        async fn action() -> Result<Response> {
+            let page = PageResponse {
+                page: vec![1, 2],
+                total_pages: 3,
+                total_items: 6,
+            };
+            PaginatedResponse::new(page, &PaginationQuery::page(2))
+                .map(|id| json!({ "id": id }))
+                .respond()
+        }
+
+        let (status, body) = call(Router::new().route("/", get(action))).await;
+        assert_eq!(status, StatusCode::OK);
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "data": [{"id": 1}, {"id": 2}],
+                "meta": {"page": 2, "page_size": 25, "total_pages": 3, "total_items": 6},
+            })
+        );
+    }
+}
@@ -0,0 +1,255 @@
+//! # Typed Responses
+//!
+//! Redirects and cookies that handlers return directly, rather than setting
+//! their headers by hand. A [`Cookies`] goes in front of any response, so a
+//! login flow returns its flash cookie along with the redirect:
+//!
+//! ```rust
+//! use loco_rs::prelude::*;
+//!
+//! async fn login() -> Result<(Cookies, Redirect)> {
+//!     let cookies = Cookies::new()
+//!         .set(cookie::Cookie::new("flash", "Welcome back!"))
+//!         .remove("return_to");
+//!     Ok((cookies, Redirect::to("/dashboard")))
+//! }
+//! ```
+//!
+//! Both also compose with [`Responder`], in handlers returning
+//! `Result<Response>`:
+//!
+//! ```rust
+//! use loco_rs::prelude::*;
+//!
+//! async fn logout() -> Result<Response> {
+//!     Cookies::new()
+//!         .remove("flash")
+//!         .respond(Redirect::to("/"))
+//! }
+//! ```
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, IntoResponseParts, Response, ResponseParts},
+};
+use axum_extra::extract::cookie::{Cookie, SameSite};
+
+use crate::{controller::responder::Responder, Error, Result};
+
+/// A redirect to another location.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    status: StatusCode,
+    location: String,
+}
+
+impl Redirect {
+    /// Redirects with `303 See Other`, for the page to show after a form was
+    /// submitted: the client follows it with a `GET`.
+    #[must_use]
+    pub fn to(location: impl Into<String>) -> Self {
+        Self::with_status(StatusCode::SEE_OTHER, location)
+    }
+
+    /// Redirects with `302 Found`, for a location which may change.
+    #[must_use]
+    pub fn found(location: impl Into<String>) -> Self {
+        Self::with_status(StatusCode::FOUND, location)
+    }
+
+    /// Redirects with `301 Moved Permanently`, for a location which moved for
+    /// good: clients and caches remember it.
+    #[must_use]
+    pub fn permanent(location: impl Into<String>) -> Self {
+        Self::with_status(StatusCode::MOVED_PERMANENTLY, location)
+    }
+
+    fn with_status(status: StatusCode, location: impl Into<String>) -> Self {
+        Self {
+            status,
+            location: location.into(),
+        }
+    }
+
+    /// The status of the redirect.
+    #[must_use]
+    pub const fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// The location redirected to.
+    #[must_use]
+    pub fn location(&self) -> &str {
+        &self.location
+    }
+}
+
+impl Responder for Redirect {
+    fn respond(self) -> Result<Response> {
+        let location = HeaderValue::try_from(self.location.as_str()).map_err(|err| {
+            Error::Message(format!(
+                "invalid redirect location `{}`: {err}",
+                self.location
+            ))
+        })?;
+        Ok((self.status, [(header::LOCATION, location)]).into_response())
+    }
+}
+
+impl IntoResponse for Redirect {
+    fn into_response(self) -> Response {
+        self.respond().unwrap_or_else(IntoResponse::into_response)
+    }
+}
+
+/// Cookies to set on, or remove from, the client along with a response.
+///
+/// Cookies are scoped to the whole site (`Path=/`), `HttpOnly` and
+/// `SameSite=Lax`, unless they set these attributes themselves.
+#[derive(Debug, Clone, Default)]
+pub struct Cookies {
+    cookies: Vec<Cookie<'static>>,
+}
+
+impl Cookies {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets a cookie, built with its attributes:
+    ///
+    /// ```rust
+    /// use loco_rs::prelude::*;
+    ///
+    /// let cookies = Cookies::new().set(
+    ///     cookie::Cookie::build(("theme", "dark"))
+    ///         .secure(true)
+    ///         .path("/settings"),
+    /// );
+    /// ```
+    #[must_use]
+    pub fn set(mut self, cookie: impl Into<Cookie<'static>>) -> Self {
+        let mut cookie = cookie.into();
+        if cookie.path().is_none() {
+            cookie.set_path("/");
+        }
+        if cookie.http_only().is_none() {
+            cookie.set_http_only(true);
+        }
+        if cookie.same_site().is_none() {
+            cookie.set_same_site(SameSite::Lax);
+        }
+        self.cookies.push(cookie);
+        self
+    }
+
+    /// Removes the cookie `name` from the client, by setting it expired. A
+    /// cookie set with another path or domain is removed with
+    /// [`Cookies::remove_cookie`].
+    #[must_use]
+    pub fn remove(self, name: impl Into<String>) -> Self {
+        self.remove_cookie(Cookie::new(name.into(), ""))
+    }
+
+    /// Removes a cookie, matched by its name, path and domain.
+    #[must_use]
+    pub fn remove_cookie(mut self, cookie: impl Into<Cookie<'static>>) -> Self {
+        let mut cookie = cookie.into();
+        if cookie.path().is_none() {
+            cookie.set_path("/");
+        }
+        cookie.make_removal();
+        self.cookies.push(cookie);
+        self
+    }
+
+    /// Responds with `value` through its [`Responder`], with the cookies.
+    ///
+    /// # Errors
+    ///
+    /// When `value` fails to respond, or a cookie is not a valid header
+    pub fn respond(self, value: impl Responder) -> Result<Response> {
+        let response = value.respond()?;
+        Ok((self, response).into_response())
+    }
+}
+
+impl IntoResponseParts for Cookies {
+    type Error = Error;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts> {
+        for cookie in self.cookies {
+            let value = HeaderValue::try_from(cookie.encoded().to_string()).map_err(|err| {
+                Error::Message(format!("invalid cookie `{}`: {err}", cookie.name()))
+            })?;
+            res.headers_mut().append(header::SET_COOKIE, value);
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderMap;
+
+    use super::*;
+
+    fn set_cookies(headers: &HeaderMap) -> Vec<&str> {
+        headers
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .map(|value| value.to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn can_redirect() {
+        let response = Redirect::to("/dashboard").into_response();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(response.headers()[header::LOCATION], "/dashboard");
+
+        assert_eq!(
+            Redirect::found("/").into_response().status(),
+            StatusCode::FOUND
+        );
+        assert_eq!(
+            Redirect::permanent("/").into_response().status(),
+            StatusCode::MOVED_PERMANENTLY
+        );
+        assert!(Redirect::to("/\n").respond().is_err());
+    }
+
+    #[test]
+    fn can_set_and_remove_cookies() {
+        let response = Cookies::new()
+            .set(Cookie::new("flash", "welcome"))
+            .set(
+                Cookie::build(("theme", "dark"))
+                    .path("/app")
+                    .http_only(false),
+            )
+            .remove("return_to")
+            .respond(Redirect::to("/dashboard"))
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let cookies = set_cookies(response.headers());
+        assert_eq!(cookies[0], "flash=welcome; HttpOnly; SameSite=Lax; Path=/");
+        assert_eq!(cookies[1], "theme=dark; SameSite=Lax; Path=/app");
+        assert!(cookies[2].starts_with("return_to=; Path=/; Max-Age=0; Expires="));
+    }
+
+    #[test]
+    fn cookies_compose_with_responses() {
+        let response = (
+            Cookies::new().set(Cookie::new("flash", "saved")),
+            Redirect::to("/"),
+        )
+            .into_response();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            set_cookies(response.headers()),
+            vec!["flash=saved; HttpOnly; SameSite=Lax; Path=/"]
+        );
+    }
+}
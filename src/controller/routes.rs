@@ -1,10 +1,29 @@
-use std::convert::Infallible;
+use std::{convert::Infallible, future::Future, panic::Location, sync::Arc};
 
-use axum::{extract::Request, response::IntoResponse, routing::Route};
+use axum::{
+    extract::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::Route,
+};
 use tower::{Layer, Service};
 
-use super::describe;
-use crate::app::AppContext;
+use chrono::NaiveDate;
+
+use super::{
+    describe,
+    error_format::{format_errors, ErrorFormat},
+    middleware::{
+        concurrency_limit::Limiter, deprecation::Deprecation, response_cache::ResponseCache,
+    },
+};
+use crate::{app::AppContext, Error};
+
+/// The context of the app, in the extensions of requests, for the filters of
+/// [`Routes::before`] and [`Routes::after`].
+#[derive(Clone)]
+pub(crate) struct FilterContext(pub(crate) Arc<AppContext>);
+
 #[derive(Clone, Default, Debug)]
 pub struct Routes {
     pub prefix: Option<String>,
@@ -17,6 +36,14 @@ pub struct Handler {
     pub uri: String,
     pub method: axum::routing::MethodRouter<AppContext>,
     pub actions: Vec<axum::http::Method>,
+    /// Where the handler was added, reported when a route is registered twice
+    pub source: Option<&'static Location<'static>>,
+    /// Replaces a handler registered earlier for the same method and path
+    pub overrides: bool,
+    /// Set when the handler is deprecated
+    pub deprecation: Option<Deprecation>,
+    /// Set when the responses of the handler are cached
+    pub cache: Option<ResponseCache>,
 }
 
 impl Routes {
@@ -78,12 +105,50 @@ impl Routes {
     /// Routes::new().add("/_ping", get(ping));
     /// ````
     #[must_use]
+    #[track_caller]
     pub fn add(mut self, uri: &str, method: axum::routing::MethodRouter<AppContext>) -> Self {
-        describe::method_action(&method);
         self.handlers.push(Handler {
             uri: uri.to_owned(),
             actions: describe::method_action(&method),
             method,
+            source: Some(Location::caller()),
+            overrides: false,
+            deprecation: None,
+            cache: None,
+        });
+        self
+    }
+
+    /// Adding a router that intentionally replaces a handler registered
+    /// earlier for the same methods and path, for example to customize a
+    /// route provided by a library. Without it, registering a route twice
+    /// fails the boot.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use loco_rs::prelude::*;
+    ///
+    /// async fn ping() -> Result<Response> {
+    ///     format::json("pong")
+    /// }
+    /// Routes::new().add_override("/_ping", get(ping));
+    /// ````
+    #[must_use]
+    #[track_caller]
+    pub fn add_override(
+        mut self,
+        uri: &str,
+        method: axum::routing::MethodRouter<AppContext>,
+    ) -> Self {
+        self.handlers.push(Handler {
+            uri: uri.to_owned(),
+            actions: describe::method_action(&method),
+            method,
+            source: Some(Location::caller()),
+            overrides: true,
+            deprecation: None,
+            cache: None,
         });
         self
     }
@@ -151,8 +216,221 @@ impl Routes {
                     uri: handler.uri.clone(),
                     actions: handler.actions.clone(),
                     method: handler.method.clone().layer(layer.clone()),
+                    source: handler.source,
+                    overrides: handler.overrides,
+                    deprecation: handler.deprecation.clone(),
+                    cache: handler.cache.clone(),
                 })
                 .collect(),
         }
     }
+
+    /// Caps the number of requests in flight across these routes, for
+    /// example to allow only a few concurrent report generations. Requests
+    /// above the cap are shed right away with `503 Service Unavailable`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use loco_rs::prelude::*;
+    ///
+    /// async fn report() -> Result<Response> {
+    ///     format::json("Ok")
+    /// }
+    /// Routes::new().add("/reports", post(report)).concurrency_limit(5);
+    /// ```
+    #[must_use]
+    pub fn concurrency_limit(self, max: usize) -> Self {
+        let limiter = Limiter::new(max, axum::http::StatusCode::SERVICE_UNAVAILABLE);
+        self.layer(axum::middleware::from_fn(move |request, next| {
+            limiter.clone().run(request, next)
+        }))
+    }
+
+    /// Sets the format of the error responses of these routes, overriding
+    /// `server.error_format`, for example JSON for an API next to HTML pages.
+    /// Like [`Routes::layer`], it applies to the routes added before. See
+    /// [`super::error_format`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use loco_rs::{controller::error_format::ErrorFormat, prelude::*};
+    ///
+    /// async fn list() -> Result<Response> {
+    ///     format::json("Ok")
+    /// }
+    /// Routes::at("api/notes")
+    ///     .add("/", get(list))
+    ///     .error_format(ErrorFormat::Json);
+    /// ```
+    #[must_use]
+    pub fn error_format(self, format: ErrorFormat) -> Self {
+        self.layer(axum::middleware::from_fn(move |request, next| {
+            format_errors(format, request, next)
+        }))
+    }
+
+    /// Marks these routes deprecated, to be removed after `sunset`. Their
+    /// responses get the `Deprecation` and `Sunset` headers, and every call is
+    /// logged with the caller and counted, to find who still calls them.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chrono::NaiveDate;
+    /// use loco_rs::prelude::*;
+    ///
+    /// async fn list() -> Result<Response> {
+    ///     format::json("Ok")
+    /// }
+    /// Routes::at("v1/notes")
+    ///     .add("/", get(list))
+    ///     .deprecated(NaiveDate::from_ymd_opt(2026, 6, 30).unwrap());
+    /// ```
+    #[must_use]
+    pub fn deprecated(self, sunset: NaiveDate) -> Self {
+        let deprecation = Deprecation::new(sunset);
+        let layer_deprecation = deprecation.clone();
+        let mut routes = self.layer(axum::middleware::from_fn(move |request, next| {
+            layer_deprecation.clone().run(request, next)
+        }));
+        for handler in &mut routes.handlers {
+            handler.deprecation = Some(deprecation.clone());
+        }
+        routes
+    }
+
+    /// Caches the successful `GET` responses of these routes, see
+    /// [`super::middleware::response_cache`]. The cached responses are
+    /// dropped with [`super::middleware::response_cache::invalidate`], by the
+    /// prefix of the routes unless [`ResponseCache::namespace`] sets another
+    /// one.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    ///
+    /// use loco_rs::{controller::middleware::response_cache::ResponseCache, prelude::*};
+    ///
+    /// async fn list() -> Result<Response> {
+    ///     format::json("Ok")
+    /// }
+    /// Routes::at("products")
+    ///     .add("/", get(list))
+    ///     .cache(ResponseCache::new(Duration::from_secs(60)).vary_query("page"));
+    /// ```
+    #[must_use]
+    pub fn cache(self, cache: ResponseCache) -> Self {
+        let namespace = cache.namespace_or(self.prefix.as_deref());
+        let layer_cache = cache.clone();
+        let mut routes = self.layer(axum::middleware::from_fn(move |request, next| {
+            layer_cache.clone().run(namespace.clone(), request, next)
+        }));
+        for handler in &mut routes.handlers {
+            handler.cache = Some(cache.clone());
+        }
+        routes
+    }
+
+    /// Runs `filter` before the handlers of these routes, like a Rails
+    /// `before_action`. The filter gets the request and passes it on, possibly
+    /// changed, or responds in place of the handler with its error, such as a
+    /// redirect to the login page.
+    ///
+    /// Filters wrap the routes added so far, so they are added after the
+    /// routes. The filter added last runs first.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use loco_rs::prelude::*;
+    ///
+    /// async fn list() -> Result<Response> {
+    ///     format::json("Ok")
+    /// }
+    /// Routes::at("admin")
+    ///     .add("/", get(list))
+    ///     .before(|_ctx: AppContext, request: axum::extract::Request| async move {
+    ///         if request.headers().contains_key("x-admin") {
+    ///             Ok(request)
+    ///         } else {
+    ///             Err(Redirect::to("/login"))
+    ///         }
+    ///     });
+    /// ```
+    #[must_use]
+    pub fn before<F, Fut, E>(self, filter: F) -> Self
+    where
+        F: Fn(AppContext, Request) -> Fut + Clone + Send + Sync + 'static,
+        Fut: Future<Output = std::result::Result<Request, E>> + Send + 'static,
+        E: IntoResponse + Send,
+    {
+        self.layer(axum::middleware::from_fn(
+            move |request: Request, next: Next| {
+                let filter = filter.clone();
+                async move {
+                    let Some(ctx) = filter_context(&request) else {
+                        return Error::InternalServerError.into_response();
+                    };
+                    match filter(ctx, request).await {
+                        Ok(request) => next.run(request).await,
+                        Err(response) => response.into_response(),
+                    }
+                }
+            },
+        ))
+    }
+
+    /// Runs `filter` on the responses of these routes, like a Rails
+    /// `after_action`, for example to add headers. It also gets the responses
+    /// of the [`Routes::before`] filters added before it.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use loco_rs::prelude::*;
+    ///
+    /// async fn list() -> Result<Response> {
+    ///     format::json("Ok")
+    /// }
+    /// Routes::at("notes")
+    ///     .add("/", get(list))
+    ///     .after(|_ctx: AppContext, mut response: Response| async move {
+    ///         response
+    ///             .headers_mut()
+    ///             .insert("cache-control", "no-store".parse().unwrap());
+    ///         response
+    ///     });
+    /// ```
+    #[must_use]
+    pub fn after<F, Fut>(self, filter: F) -> Self
+    where
+        F: Fn(AppContext, Response) -> Fut + Clone + Send + Sync + 'static,
+        Fut: Future<Output = Response> + Send + 'static,
+    {
+        self.layer(axum::middleware::from_fn(
+            move |request: Request, next: Next| {
+                let filter = filter.clone();
+                async move {
+                    let Some(ctx) = filter_context(&request) else {
+                        return Error::InternalServerError.into_response();
+                    };
+                    let response = next.run(request).await;
+                    filter(ctx, response).await
+                }
+            },
+        ))
+    }
+}
+
+/// Returns the context of the app for a filter, which is set when the routes
+/// are registered by [`super::AppRoutes`].
+fn filter_context(request: &Request) -> Option<AppContext> {
+    let context = request.extensions().get::<FilterContext>();
+    if context.is_none() {
+        tracing::error!("no app context for the route filters, are the routes registered?");
+    }
+    context.map(|FilterContext(ctx)| ctx.as_ref().clone())
 }
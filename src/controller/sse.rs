@@ -0,0 +1,156 @@
+//! # Server-Sent Events
+//!
+//! Helpers for streaming [Server-Sent Events](https://developer.mozilla.org/en-US/docs/Web/API/Server-sent_events)
+//! from a controller, for live updates that do not need a full WebSocket.
+//!
+//! * The response carries the `text/event-stream` content type, disables
+//!   caching and proxy buffering, and sends keep-alive comments so idle
+//!   connections are not closed by proxies.
+//! * The stream is only polled when the client is ready to receive more data,
+//!   so a slow client never accumulates produced events in memory.
+//! * When the client disconnects the stream is dropped, which stops the
+//!   production of events.
+//!
+//! # Example:
+//!
+//! ```rust
+//! use axum::extract::State;
+//! use futures_util::StreamExt;
+//! use loco_rs::{controller::sse, prelude::*};
+//! use tokio::sync::broadcast;
+//!
+//! #[derive(Clone)]
+//! struct Notifications(broadcast::Sender<String>);
+//!
+//! async fn live(State(ctx): State<AppContext>) -> Result<Response> {
+//!     let notifications = ctx
+//!         .shared_store
+//!         .get::<Notifications>()
+//!         .ok_or_else(|| Error::string("notifications are not configured"))?;
+//!     let events = sse::subscribe(notifications.0.subscribe())
+//!         .map(|message| sse::Event::default().event("notification").data(message));
+//!     sse::stream(events)
+//! }
+//! ```
+use std::{convert::Infallible, time::Duration};
+
+pub use axum::response::sse::{Event, KeepAlive};
+use axum::{
+    http::{HeaderName, HeaderValue},
+    response::{sse::Sse, IntoResponse, Response},
+};
+use futures_util::{Stream, StreamExt};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::Result;
+
+/// The interval of the keep-alive comments sent by [`stream`].
+pub const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// Returns a Server-Sent Events response streaming `events`, with a
+/// keep-alive comment sent every [`DEFAULT_KEEP_ALIVE`].
+///
+/// # Errors
+///
+/// Currently this function doesn't return any error. this is for feature
+/// functionality
+pub fn stream<S>(events: S) -> Result<Response>
+where
+    S: Stream<Item = Event> + Send + 'static,
+{
+    stream_with_keep_alive(events, KeepAlive::new().interval(DEFAULT_KEEP_ALIVE))
+}
+
+/// Returns a Server-Sent Events response streaming `events`, with the given
+/// keep-alive settings.
+///
+/// # Errors
+///
+/// Currently this function doesn't return any error. this is for feature
+/// functionality
+pub fn stream_with_keep_alive<S>(events: S, keep_alive: KeepAlive) -> Result<Response>
+where
+    S: Stream<Item = Event> + Send + 'static,
+{
+    let mut response = Sse::new(events.map(Ok::<_, Infallible>))
+        .keep_alive(keep_alive)
+        .into_response();
+    // nginx buffers responses by default, which would hold the events back
+    response.headers_mut().insert(
+        HeaderName::from_static("x-accel-buffering"),
+        HeaderValue::from_static("no"),
+    );
+    Ok(response)
+}
+
+/// Turns a broadcast subscription into a stream of messages, so an SSE
+/// endpoint can follow a channel shared by many clients.
+///
+/// The broadcast channel is bounded: a client that falls behind by more than
+/// the channel capacity skips the oldest messages instead of buffering them.
+/// The stream ends when all senders are dropped.
+pub fn subscribe<T>(receiver: broadcast::Receiver<T>) -> impl Stream<Item = T> + Send + 'static
+where
+    T: Clone + Send + 'static,
+{
+    futures_util::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(message) => return Some((message, receiver)),
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "sse client lagged behind, skipping messages");
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::header;
+
+    use super::*;
+
+    async fn body_to_string(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn can_stream_events() {
+        let events = futures_util::stream::iter([
+            Event::default().data("hello"),
+            Event::default().event("update").data("loco"),
+        ]);
+        let response = stream(events).unwrap();
+
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/event-stream"
+        );
+        assert_eq!(
+            response.headers().get(header::CACHE_CONTROL).unwrap(),
+            "no-cache"
+        );
+        assert_eq!(response.headers().get("x-accel-buffering").unwrap(), "no");
+        assert_eq!(
+            body_to_string(response).await,
+            "data: hello\n\nevent: update\ndata: loco\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn subscriber_skips_lagged_messages() {
+        let (sender, receiver) = broadcast::channel(2);
+        for message in ["1", "2", "3", "4"] {
+            sender.send(message.to_string()).unwrap();
+        }
+        drop(sender);
+
+        let messages: Vec<String> = subscribe(receiver).collect().await;
+        assert_eq!(messages, vec!["3".to_string(), "4".to_string()]);
+    }
+}
@@ -0,0 +1,133 @@
+//! API versions of [`super::AppRoutes`]: each version is mounted under its
+//! own path segment, like `/api/v1/users`, and, with a default version, the
+//! unversioned path `/api/users` routes to the version named by the `Accept`
+//! header, like `application/vnd.myapp.v2+json`, or else to the default one.
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+    routing::MethodRouter,
+};
+use tower::Service;
+
+use super::routes::Routes;
+use crate::Error;
+
+/// A version registered with [`super::AppRoutes::version`].
+#[derive(Clone, Debug)]
+pub(super) struct ApiVersion {
+    pub name: String,
+    /// The path of the version, like `/api/v1/`.
+    pub mount: String,
+    /// The path of the unversioned routes, like `/api/`.
+    pub alias: String,
+    /// The routes of the version, relative to its path.
+    pub routes: Vec<Routes>,
+}
+
+impl ApiVersion {
+    /// The unversioned path of `uri` when it is a route of this version.
+    pub fn unversioned(&self, uri: &str) -> Option<String> {
+        let rest = uri
+            .strip_prefix(&self.mount)
+            .or_else(|| (uri == self.mount.trim_end_matches('/')).then_some(""))?;
+        let alias = format!("{}{rest}", self.alias);
+        if alias == "/" {
+            return Some(alias);
+        }
+        Some(alias.trim_end_matches('/').to_string())
+    }
+}
+
+/// The version named by the `Accept` header, either in a vendor media type
+/// like `application/vnd.myapp.v2+json`, or as a `version` parameter like
+/// `application/json; version=v2`.
+pub(super) fn requested_version(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(|media_type| {
+            let mut parts = media_type.split(';').map(str::trim);
+            let essence = parts.next()?;
+            let from_param = parts.find_map(|param| {
+                param
+                    .strip_prefix("version=")
+                    .map(|version| version.trim_matches('"').to_string())
+            });
+            from_param.or_else(|| {
+                let vendor = essence.strip_prefix("application/vnd.")?;
+                let vendor = vendor.split('+').next().unwrap_or(vendor);
+                let (_, version) = vendor.rsplit_once('.')?;
+                Some(version.to_string())
+            })
+        })
+}
+
+/// Routes the requests of an unversioned path to the versions having it.
+#[derive(Clone)]
+pub(super) struct Dispatch {
+    pub versions: Arc<Vec<(String, MethodRouter)>>,
+    pub default: String,
+}
+
+impl Dispatch {
+    pub async fn call(self, request: Request) -> Response {
+        let version = requested_version(request.headers()).unwrap_or(self.default);
+        let Some((_, method)) = self.versions.iter().find(|(name, _)| *name == version) else {
+            tracing::debug!(version, uri = %request.uri(), "API version not found");
+            return Error::NotFound.into_response();
+        };
+        let mut method = method.clone();
+        let response: Result<Response, Infallible> = method.call(request).await;
+        let mut response = response.into_response();
+        response
+            .headers_mut()
+            .append(header::VARY, HeaderValue::from_static("Accept"));
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accept(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn can_read_requested_version() {
+        assert_eq!(
+            requested_version(&accept("application/vnd.myapp.v2+json")),
+            Some("v2".to_string())
+        );
+        assert_eq!(
+            requested_version(&accept("text/html, application/json; version=v1")),
+            Some("v1".to_string())
+        );
+        assert_eq!(requested_version(&accept("application/json")), None);
+        assert_eq!(requested_version(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn can_unversion_paths() {
+        let version = ApiVersion {
+            name: "v1".to_string(),
+            mount: "/api/v1/".to_string(),
+            alias: "/api/".to_string(),
+            routes: vec![],
+        };
+        assert_eq!(
+            version.unversioned("/api/v1/users/{id}"),
+            Some("/api/users/{id}".to_string())
+        );
+        assert_eq!(version.unversioned("/api/v1"), Some("/api".to_string()));
+        assert_eq!(version.unversioned("/api/v2/users"), None);
+    }
+}
@@ -7,8 +7,13 @@ use super::Result as AppResult;
 use crate::{
     app::{AppContext, Hooks},
     cargo_config::CargoConfig,
-    config, doctor, env_vars,
+    config,
+    controller::middleware::slow_request::{self, RequestTimings},
+    doctor, env_vars,
+    environment::Environment,
     errors::Error,
+    failover,
+    request_context::RequestContext,
 };
 use chrono::{DateTime, Utc};
 use regex::Regex;
@@ -118,23 +123,64 @@ pub async fn converge<H: Hooks, M: MigratorTrait>(
     config: &config::Database,
 ) -> AppResult<()> {
     if config.dangerously_recreate {
+        ensure_destructive_allowed(&ctx.environment, config, "recreate the schema")?;
         info!("recreating schema");
         reset::<M>(&ctx.db).await?;
         return Ok(());
     }
 
-    if config.auto_migrate {
-        info!("auto migrating");
-        migrate::<M>(&ctx.db).await?;
+    match config.migrate_on_boot() {
+        config::MigrateOnBoot::Auto => {
+            info!("auto migrating");
+            migrate::<M>(&ctx.db).await?;
+        }
+        config::MigrateOnBoot::Check => {
+            let pending = M::get_pending_migrations(&ctx.db).await?;
+            if !pending.is_empty() {
+                return Err(Error::PendingMigrations(
+                    pending.iter().map(|m| m.name().to_string()).collect(),
+                ));
+            }
+            info!("no pending migrations");
+        }
+        config::MigrateOnBoot::Off => {}
+    }
+    if !config.tenants.is_empty() {
+        crate::tenancy::converge::<M>(&ctx.db, config).await?;
     }
 
     if config.dangerously_truncate {
+        ensure_destructive_allowed(&ctx.environment, config, "truncate tables")?;
         info!("truncating tables");
         H::truncate(ctx).await?;
     }
     Ok(())
 }
 
+/// Make sure a destructive operation (truncating, recreating or resetting the
+/// database) may run in the given environment.
+///
+/// # Errors
+///
+/// Returns [`Error::DestructiveOperationNotAllowed`] in `production`, or when
+/// the environment is not listed in `destructive_environments`.
+pub fn ensure_destructive_allowed(
+    environment: &Environment,
+    config: &config::Database,
+    operation: &str,
+) -> AppResult<()> {
+    let environment = environment.to_string();
+    if environment != Environment::Production.to_string()
+        && config.destructive_environments.contains(&environment)
+    {
+        return Ok(());
+    }
+    Err(Error::DestructiveOperationNotAllowed {
+        operation: operation.to_string(),
+        environment,
+    })
+}
+
 /// Establish a connection to the database using the provided configuration
 /// settings.
 ///
@@ -154,7 +200,8 @@ pub async fn connect(config: &config::Database) -> Result<DbConn, sea_orm::DbErr
         opt.acquire_timeout(Duration::from_millis(acquire_timeout));
     }
 
-    let db = Database::connect(opt).await?;
+    let mut db = Database::connect(opt).await?;
+    set_metric_callback(&mut db, config);
 
     match db.get_database_backend() {
         DatabaseBackend::Sqlite => {
@@ -189,6 +236,69 @@ pub async fn connect(config: &config::Database) -> Result<DbConn, sea_orm::DbErr
     Ok(db)
 }
 
+/// Logs the queries run on `db` with the [`RequestContext`] of the request or
+/// job running them, following `log_queries` and `slow_query_threshold`,
+/// records their time in the [`RequestTimings`] of the request, and checks
+/// the database when a query fails, following `failover`.
+fn set_metric_callback(db: &mut DbConn, config: &config::Database) {
+    // sqlite connections do not fail over
+    let failover = (config.failover.enable
+        && db.get_database_backend() == DatabaseBackend::Postgres)
+        .then(|| failover::Failover::new(db.clone(), &config.failover));
+    let log_queries = config.log_queries;
+    let slow_query_threshold = config.slow_query_threshold.map(Duration::from_millis);
+    db.set_metric_callback(move |info| {
+        // the callback runs in the task of the query, within its request
+        if let Some(timings) = RequestTimings::current() {
+            timings.record(slow_request::DB, info.elapsed);
+        }
+        if info.failed {
+            if let Some(failover) = &failover {
+                failover.on_query_failed();
+            }
+        }
+        let Some(level) = query_log_level(info.elapsed, log_queries, slow_query_threshold) else {
+            return;
+        };
+        let context = RequestContext::current().unwrap_or_default();
+        if level == tracing::Level::WARN {
+            tracing::warn!(
+                request_id = context.request_id,
+                route = context.route,
+                elapsed = ?info.elapsed,
+                failed = info.failed,
+                query = %info.statement,
+                "slow query"
+            );
+        } else {
+            tracing::debug!(
+                request_id = context.request_id,
+                route = context.route,
+                elapsed = ?info.elapsed,
+                failed = info.failed,
+                query = %info.statement,
+                "query"
+            );
+        }
+    });
+}
+
+/// The level a query taking `elapsed` is logged at: `WARN` when slow, `DEBUG`
+/// when all queries are logged, and `None` when it is not logged.
+fn query_log_level(
+    elapsed: Duration,
+    log_queries: bool,
+    slow_query_threshold: Option<Duration>,
+) -> Option<tracing::Level> {
+    if slow_query_threshold.is_some_and(|threshold| elapsed >= threshold) {
+        Some(tracing::Level::WARN)
+    } else if log_queries {
+        Some(tracing::Level::DEBUG)
+    } else {
+        None
+    }
+}
+
 /// Extracts the database name from a given connection string.
 ///
 /// # Errors
@@ -232,6 +342,51 @@ pub async fn migrate<M: MigratorTrait>(db: &DatabaseConnection) -> Result<(), se
     M::up(db, None).await
 }
 
+/// Apply the pending migrations one by one, logging each of them with the
+/// time it took, then the ones of the tenant schemas of `config`. Returns the
+/// names of the applied migrations.
+///
+/// # Errors
+///
+/// Returns an error naming the migration that failed. The migrations applied
+/// before it stay applied.
+pub async fn migrate_pending<M: MigratorTrait>(
+    db: &DatabaseConnection,
+    config: &config::Database,
+) -> AppResult<Vec<String>> {
+    let started = std::time::Instant::now();
+    let pending = M::get_pending_migrations(db).await?;
+    info!(pending = pending.len(), "running pending migrations");
+
+    let mut applied = Vec::with_capacity(pending.len());
+    for migration in pending {
+        let name = migration.name().to_string();
+        let migration_started = std::time::Instant::now();
+        if let Err(err) = M::up(db, Some(1)).await {
+            tracing::error!(migration = name, err = %err, "migration failed");
+            return Err(Error::Message(format!("migration `{name}` failed: {err}")));
+        }
+        info!(
+            migration = name,
+            elapsed = ?migration_started.elapsed(),
+            "applied migration"
+        );
+        applied.push(name);
+    }
+    for tenant in &config.tenants {
+        let tenant_started = std::time::Instant::now();
+        crate::tenancy::migrate::<M>(db, config, tenant).await?;
+        info!(tenant, elapsed = ?tenant_started.elapsed(), "migrated tenant");
+    }
+
+    info!(
+        applied = applied.len(),
+        elapsed = ?started.elapsed(),
+        "migrations completed"
+    );
+    Ok(applied)
+}
+
 /// Revert migrations to the database using the provided migrator.
 ///
 /// # Errors
@@ -264,6 +419,135 @@ pub async fn reset<M: MigratorTrait>(db: &DatabaseConnection) -> Result<(), sea_
     migrate::<M>(db).await
 }
 
+static MIGRATION_ENVIRONMENT: std::sync::RwLock<Option<String>> = std::sync::RwLock::new(None);
+
+/// Sets the environment the migrations of the process run in, deciding which
+/// [`EnvMigration`]s apply. The app sets it when it creates its context.
+pub fn set_migration_environment(environment: &Environment) {
+    *MIGRATION_ENVIRONMENT
+        .write()
+        .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(environment.to_string());
+}
+
+/// The environment set by the app, or else the one of `LOCO_ENV` or
+/// `RAILS_ENV`, as for a migrator run on its own.
+fn migration_environment() -> Option<String> {
+    MIGRATION_ENVIRONMENT
+        .read()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .clone()
+        .or_else(|| {
+            env_vars::get(env_vars::LOCO_ENV)
+                .or_else(|_| env_vars::get(env_vars::RAILS_ENV))
+                .ok()
+        })
+}
+
+/// A migration which only runs in some environments, such as seed data for
+/// `development` or an extension only available in `production`:
+///
+/// ```rust,ignore
+/// fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+///     vec![
+///         Box::new(m20220101_000001_users::Migration),
+///         Box::new(only_in(m20220101_000002_seed_users::Migration, &["development", "test"])),
+///     ]
+/// }
+/// ```
+///
+/// In the other environments, the migration is skipped: it is recorded in
+/// the migrations table like an applied one, so that it is not pending
+/// anymore, and its `down` is skipped as well.
+///
+/// The environment is the one of the app, or else `LOCO_ENV` or `RAILS_ENV`
+/// for a migrator run on its own. Without any, the migration fails rather
+/// than guessing.
+pub struct EnvMigration {
+    migration: Box<dyn sea_orm_migration::MigrationTrait>,
+    environments: Vec<String>,
+}
+
+impl EnvMigration {
+    /// Returns `true` when the migration runs in `environment`.
+    #[must_use]
+    pub fn applies_to(&self, environment: &str) -> bool {
+        self.environments.iter().any(|env| env == environment)
+    }
+
+    fn applies_in(&self, environment: Option<&str>) -> Result<bool, DbErr> {
+        environment
+            .map(|environment| self.applies_to(environment))
+            .ok_or_else(|| {
+                DbErr::Migration(format!(
+                    "migration {} only runs in {:?}, but the environment is unknown: set LOCO_ENV",
+                    self.migration.name(),
+                    self.environments
+                ))
+            })
+    }
+
+    async fn up_in(
+        &self,
+        manager: &sea_orm_migration::SchemaManager<'_>,
+        environment: Option<&str>,
+    ) -> Result<(), DbErr> {
+        if !self.applies_in(environment)? {
+            info!(
+                migration = self.migration.name(),
+                environments = ?self.environments,
+                "skipped migration, it does not apply to this environment"
+            );
+            return Ok(());
+        }
+        self.migration.up(manager).await
+    }
+
+    async fn down_in(
+        &self,
+        manager: &sea_orm_migration::SchemaManager<'_>,
+        environment: Option<&str>,
+    ) -> Result<(), DbErr> {
+        if !self.applies_in(environment)? {
+            info!(
+                migration = self.migration.name(),
+                "skipped reverting migration, it does not apply to this environment"
+            );
+            return Ok(());
+        }
+        self.migration.down(manager).await
+    }
+}
+
+/// Tags `migration` to only run in `environments`, see [`EnvMigration`].
+pub fn only_in<M: sea_orm_migration::MigrationTrait + 'static>(
+    migration: M,
+    environments: &[&str],
+) -> EnvMigration {
+    EnvMigration {
+        migration: Box::new(migration),
+        environments: environments.iter().map(ToString::to_string).collect(),
+    }
+}
+
+impl sea_orm_migration::MigrationName for EnvMigration {
+    fn name(&self) -> &str {
+        self.migration.name()
+    }
+}
+
+#[async_trait::async_trait]
+impl sea_orm_migration::MigrationTrait for EnvMigration {
+    async fn up(&self, manager: &sea_orm_migration::SchemaManager) -> Result<(), DbErr> {
+        self.up_in(manager, migration_environment().as_deref())
+            .await
+    }
+
+    async fn down(&self, manager: &sea_orm_migration::SchemaManager) -> Result<(), DbErr> {
+        self.down_in(manager, migration_environment().as_deref())
+            .await
+    }
+}
+
 use sea_orm::EntityName;
 use serde_json::{json, Value};
 /// Seed the database with data from a specified file.
@@ -549,18 +833,19 @@ impl EntityCmd {
 /// Returns a [`AppResult`] if an error occurs during generate model entity.
 pub async fn entities<M: MigratorTrait>(ctx: &AppContext) -> AppResult<String> {
     doctor::check_seaorm_cli()?.to_result()?;
-    doctor::check_db(&ctx.config.database).await.to_result()?;
+    let database = ctx.config.database()?;
+    doctor::check_db(database).await.to_result()?;
 
     let flags = CargoConfig::from_current_dir()?
         .get_db_entities()
         .map_or_else(
-            || EntityCmd::new(&ctx.config.database),
+            || EntityCmd::new(database),
             |entity_config| {
                 tracing::info!(
                     ?entity_config,
                     "Found db.entity configuration in Cargo.toml"
                 );
-                EntityCmd::merge_with_config(&ctx.config.database, entity_config)
+                EntityCmd::merge_with_config(database, entity_config)
             },
         );
 
@@ -983,6 +1268,175 @@ mod tests {
         config::get_database_config, db::get_value, postgres::setup_postgres_container,
     };
 
+    #[test]
+    fn can_escalate_slow_queries() {
+        let threshold = Some(Duration::from_millis(100));
+        assert_eq!(
+            query_log_level(Duration::from_millis(150), false, threshold),
+            Some(tracing::Level::WARN)
+        );
+        assert_eq!(
+            query_log_level(Duration::from_millis(50), true, threshold),
+            Some(tracing::Level::DEBUG)
+        );
+        assert_eq!(
+            query_log_level(Duration::from_millis(50), false, threshold),
+            None
+        );
+        assert_eq!(query_log_level(Duration::from_secs(5), false, None), None);
+    }
+
+    mod pending {
+        use sea_orm_migration::prelude::*;
+
+        pub struct Migrator;
+
+        #[async_trait::async_trait]
+        impl MigratorTrait for Migrator {
+            fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+                vec![Box::new(CreateNotes)]
+            }
+        }
+
+        struct CreateNotes;
+
+        impl MigrationName for CreateNotes {
+            fn name(&self) -> &'static str {
+                "m20250101_000001_create_notes"
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl MigrationTrait for CreateNotes {
+            async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+                manager
+                    .get_connection()
+                    .execute_unprepared("CREATE TABLE notes (id INTEGER PRIMARY KEY)")
+                    .await?;
+                Ok(())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn can_check_pending_migrations_on_boot() {
+        let mut ctx = crate::tests_cfg::app::get_app_context().await;
+        let mut config = get_database_config();
+        ctx.db = connect(&config).await.unwrap();
+
+        config.migrate_on_boot = Some(config::MigrateOnBoot::Check);
+        let err = converge::<crate::tests_cfg::db::AppHook, pending::Migrator>(&ctx, &config)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            &err,
+            Error::PendingMigrations(pending) if pending == &vec!["m20250101_000001_create_notes".to_string()]
+        ));
+
+        // `migrate_on_boot` takes precedence over `auto_migrate`
+        config.auto_migrate = true;
+        assert!(
+            converge::<crate::tests_cfg::db::AppHook, pending::Migrator>(&ctx, &config)
+                .await
+                .is_err()
+        );
+
+        config.migrate_on_boot = None;
+        converge::<crate::tests_cfg::db::AppHook, pending::Migrator>(&ctx, &config)
+            .await
+            .unwrap();
+        config.migrate_on_boot = Some(config::MigrateOnBoot::Check);
+        converge::<crate::tests_cfg::db::AppHook, pending::Migrator>(&ctx, &config)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn can_migrate_pending_migrations() {
+        let config = get_database_config();
+        let db = connect(&config).await.unwrap();
+
+        let applied = migrate_pending::<pending::Migrator>(&db, &config)
+            .await
+            .unwrap();
+        assert_eq!(applied, vec!["m20250101_000001_create_notes".to_string()]);
+        assert!(pending::Migrator::get_pending_migrations(&db)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(migrate_pending::<pending::Migrator>(&db, &config)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn can_skip_migrations_of_other_environments() {
+        use sea_orm_migration::{MigrationName, MigrationTrait};
+
+        struct Failing;
+
+        impl sea_orm_migration::MigrationName for Failing {
+            fn name(&self) -> &'static str {
+                "m20250101_000002_failing"
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl MigrationTrait for Failing {
+            async fn up(&self, _: &sea_orm_migration::SchemaManager) -> Result<(), DbErr> {
+                Err(DbErr::Custom("ran".to_string()))
+            }
+        }
+
+        let config = get_database_config();
+        let db = connect(&config).await.unwrap();
+        let manager = sea_orm_migration::SchemaManager::new(&db);
+
+        let migration = only_in(Failing, &["production"]);
+        assert!(!migration.applies_to("test"));
+        assert_eq!(migration.name(), "m20250101_000002_failing");
+        migration.up_in(&manager, Some("test")).await.unwrap();
+        migration.down_in(&manager, Some("test")).await.unwrap();
+        // an unknown environment fails instead of running the migration
+        assert!(matches!(
+            migration.up_in(&manager, None).await,
+            Err(DbErr::Migration(_))
+        ));
+
+        let migration = only_in(Failing, &["test"]);
+        assert!(migration.applies_to("test"));
+        assert!(matches!(
+            migration.up_in(&manager, Some("test")).await,
+            Err(DbErr::Custom(_))
+        ));
+    }
+
+    #[test]
+    fn test_destructive_operations_allowlist() {
+        let mut config = get_database_config();
+        config.destructive_environments = vec!["test".to_string(), "production".to_string()];
+
+        assert!(ensure_destructive_allowed(&Environment::Test, &config, "truncate tables").is_ok());
+        assert!(matches!(
+            ensure_destructive_allowed(&Environment::Development, &config, "truncate tables"),
+            Err(Error::DestructiveOperationNotAllowed { environment, .. }) if environment == "development"
+        ));
+        // production is refused even when listed
+        assert!(matches!(
+            ensure_destructive_allowed(&Environment::Production, &config, "truncate tables"),
+            Err(Error::DestructiveOperationNotAllowed { environment, .. }) if environment == "production"
+        ));
+        assert!(matches!(
+            ensure_destructive_allowed(
+                &Environment::Any("production".to_string()),
+                &config,
+                "truncate tables"
+            ),
+            Err(Error::DestructiveOperationNotAllowed { .. })
+        ));
+    }
+
     #[tokio::test]
     async fn test_sqlite_connect_success() {
         let (config, _tree_fs) = crate::tests_cfg::config::get_sqlite_test_config("test");
@@ -1018,6 +1472,23 @@ mod tests {
         assert_eq!(db.get_database_backend(), DatabaseBackend::Postgres);
     }
 
+    #[tokio::test]
+    async fn can_record_query_time_of_requests() {
+        let (config, _tree_fs) = crate::tests_cfg::config::get_sqlite_test_config("test");
+        let db = connect(&config).await.expect("Failed to connect to SQLite");
+
+        let select = || Statement::from_string(DatabaseBackend::Sqlite, "SELECT 1");
+        let timings = RequestTimings::default();
+        timings.clone().scope(db.query_one(select())).await.unwrap();
+        let recorded = timings.get();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].0, slow_request::DB);
+
+        // queries outside of a request are not recorded
+        db.query_one(select()).await.unwrap();
+        assert_eq!(timings.get().len(), 1);
+    }
+
     #[tokio::test]
     async fn test_sqlite_default_run_on_start() {
         let (config, _tree_fs) = crate::tests_cfg::config::get_sqlite_test_config("test");
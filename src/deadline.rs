@@ -0,0 +1,162 @@
+//! # Request Deadlines
+//!
+//! With the `timeout_request` middleware, every request gets a [`Deadline`],
+//! the time its response is due by. The deadline is current while the request
+//! is handled, and ends when the request does: when it times out, when the
+//! client goes away, or when the response is sent. Work done for the request
+//! observes it, and stops early rather than completing work whose result is
+//! discarded:
+//!
+//! ```rust,ignore
+//! async fn report(State(ctx): State<AppContext>) -> Result<Response> {
+//!     // fails with `Error::DeadlineExceeded` once the deadline expired
+//!     let orders = deadline::bounded(orders::Entity::find().all(&ctx.db)).await?;
+//!     format::json(orders)
+//! }
+//! ```
+//!
+//! Storage operations observe the deadline on their own. Outside of a request
+//! with a deadline, work runs to completion.
+use std::{future::Future, time::Duration};
+
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+use crate::{Error, Result};
+
+tokio::task_local! {
+    static CURRENT: Deadline;
+}
+
+/// The time the response of a request is due by.
+#[derive(Debug, Clone)]
+pub struct Deadline {
+    at: Instant,
+    ended: CancellationToken,
+}
+
+impl Deadline {
+    /// A deadline `timeout` from now.
+    #[must_use]
+    pub fn after(timeout: Duration) -> Self {
+        Self {
+            at: Instant::now() + timeout,
+            ended: CancellationToken::new(),
+        }
+    }
+
+    /// Returns the deadline of the request being handled.
+    #[must_use]
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+
+    /// The time left until the deadline, zero once it expired.
+    #[must_use]
+    pub fn remaining(&self) -> Duration {
+        if self.ended.is_cancelled() {
+            return Duration::ZERO;
+        }
+        self.at.saturating_duration_since(Instant::now())
+    }
+
+    /// Whether the deadline passed, or the request ended.
+    #[must_use]
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    /// Ends the deadline now, for the work observing it to stop.
+    pub fn cancel(&self) {
+        self.ended.cancel();
+    }
+
+    /// Completes when the deadline passes, or the request ends.
+    pub async fn expired(&self) {
+        tokio::select! {
+            () = tokio::time::sleep_until(self.at) => {}
+            () = self.ended.cancelled() => {}
+        }
+    }
+
+    /// Runs `future` with `self` as the current deadline. The deadline ends
+    /// with the future, even when the future is dropped before it completes,
+    /// so tasks spawned with a clone of the deadline stop with the request.
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        let _ended = self.ended.clone().drop_guard();
+        CURRENT.scope(self, future).await
+    }
+}
+
+/// Runs `future` until the current deadline, returning `None` when the
+/// deadline expired first. Without a current deadline, `future` runs to
+/// completion.
+pub async fn within<F: Future>(future: F) -> Option<F::Output> {
+    let Some(deadline) = Deadline::current() else {
+        return Some(future.await);
+    };
+    if deadline.is_expired() {
+        return None;
+    }
+    tokio::select! {
+        output = future => Some(output),
+        () = deadline.expired() => None,
+    }
+}
+
+/// Runs the fallible `future` until the current deadline, see [`within`].
+///
+/// # Errors
+///
+/// When `future` fails, or with [`Error::DeadlineExceeded`] when the deadline
+/// expired first
+pub async fn bounded<T, E, F>(future: F) -> Result<T>
+where
+    F: Future<Output = std::result::Result<T, E>>,
+    E: Into<Error>,
+{
+    within(future)
+        .await
+        .ok_or(Error::DeadlineExceeded)?
+        .map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn can_bound_work_by_the_deadline() {
+        let slow = || async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok::<_, Error>(())
+        };
+
+        assert!(within(async { 1 }).await.is_some());
+        let result = Deadline::after(Duration::from_millis(20))
+            .scope(bounded(slow()))
+            .await;
+        assert!(matches!(result, Err(Error::DeadlineExceeded)));
+
+        let result = Deadline::after(Duration::from_secs(5))
+            .scope(bounded(async { Ok::<_, Error>(1) }))
+            .await;
+        assert_eq!(result.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn ends_the_deadline_with_the_request() {
+        let deadline = Deadline::after(Duration::from_secs(60));
+        let observed = deadline.clone();
+        let request = deadline.scope(async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+        // the client went away
+        assert!(tokio::time::timeout(Duration::from_millis(10), request)
+            .await
+            .is_err());
+
+        assert!(observed.is_expired());
+        assert_eq!(observed.remaining(), Duration::ZERO);
+    }
+}
@@ -161,12 +161,12 @@ impl std::fmt::Display for Check {
 /// # Errors
 /// Error when one of the checks fail
 pub async fn run_all(config: &Config, production: bool) -> Result<BTreeMap<Resource, Check>> {
-    let mut checks = BTreeMap::from(
-        #[cfg(feature = "with-db")]
-        [(Resource::Database, check_db(&config.database).await)],
-        #[cfg(not(feature = "with-db"))]
-        [],
-    );
+    let mut checks = BTreeMap::new();
+
+    #[cfg(feature = "with-db")]
+    if let Some(database) = &config.database {
+        checks.insert(Resource::Database, check_db(database).await);
+    }
 
     if config.workers.mode == config::WorkerMode::BackgroundQueue {
         checks.insert(Resource::Queue, check_queue(config).await);
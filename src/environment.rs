@@ -50,6 +50,21 @@ pub enum Environment {
 }
 
 impl Environment {
+    #[must_use]
+    pub const fn is_production(&self) -> bool {
+        matches!(self, Self::Production)
+    }
+
+    #[must_use]
+    pub const fn is_development(&self) -> bool {
+        matches!(self, Self::Development)
+    }
+
+    #[must_use]
+    pub const fn is_test(&self) -> bool {
+        matches!(self, Self::Test)
+    }
+
     /// Load environment variables from local configuration
     ///
     /// # Errors
@@ -123,6 +138,14 @@ mod tests {
         assert_eq!("custom", Environment::Any("custom".to_string()).to_string());
     }
 
+    #[test]
+    fn test_predicates() {
+        assert!(Environment::Production.is_production());
+        assert!(Environment::Development.is_development());
+        assert!(Environment::Test.is_test());
+        assert!(!Environment::Any("staging".to_string()).is_production());
+    }
+
     #[test]
     fn test_into() {
         let e: Environment = "production".to_string().into();
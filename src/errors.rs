@@ -27,6 +27,13 @@ impl From<serde_json::Error> for Error {
     }
 }
 
+#[cfg(feature = "simd_json")]
+impl From<simd_json::Error> for Error {
+    fn from(val: simd_json::Error) -> Self {
+        Self::Any(Box::new(val)).bt()
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("{inner}\n{backtrace}")]
@@ -44,6 +51,60 @@ pub enum Error {
     )]
     QueueProviderMissing,
 
+    #[error("queue `{queue}` is full: {depth} jobs are waiting, and its max depth is {max_depth}")]
+    QueueFull {
+        queue: String,
+        depth: usize,
+        max_depth: usize,
+    },
+
+    #[error(
+        "refusing to {operation} in the `{environment}` environment. Destructive database \
+         operations are never allowed in production, and otherwise only in the environments \
+         listed in `database.destructive_environments` in your config file"
+    )]
+    DestructiveOperationNotAllowed {
+        operation: String,
+        environment: String,
+    },
+
+    #[error("the request deadline expired before the work completed")]
+    DeadlineExceeded,
+
+    #[error("the resource was modified since the precondition of the request")]
+    PreconditionFailed,
+
+    #[error("the database is not configured: add a `database` section to your config file")]
+    DbNotConfigured,
+
+    #[error("the mailer is not configured: add a `mailer` section to your config file")]
+    MailerNotConfigured,
+
+    #[error("the mailer `{0}` is not configured: add it to `mailer.senders` in your config file")]
+    MailerSenderNotConfigured(String),
+
+    #[error(
+        "the storage is not configured: set `ctx.storage` in `Hooks::after_context`, see the \
+         storage documentation"
+    )]
+    StorageNotConfigured,
+
+    #[error("redis is not configured: add a redis `queue` section to your config file")]
+    RedisNotConfigured,
+
+    #[error(
+        "could not listen on {0}: the address is already in use. Is another instance of the app \
+         running? Stop it, or choose another port with `--port` or `server.port`"
+    )]
+    AddrInUse(String),
+
+    #[error(
+        "refusing to boot with pending migrations, since `database.migrate_on_boot` is `check`. \
+         Run `cargo loco db migrate` first:\n{}",
+        .0.iter().map(|name| format!("  * {name}")).collect::<Vec<_>>().join("\n")
+    )]
+    PendingMigrations(Vec<String>),
+
     #[error("task not found: '{0}'")]
     TaskNotFound(String),
 
@@ -138,6 +199,9 @@ pub enum Error {
     #[error(transparent)]
     Storage(#[from] crate::storage::StorageError),
 
+    #[error(transparent)]
+    HttpClient(#[from] reqwest::Error),
+
     #[error(transparent)]
     Cache(#[from] crate::cache::CacheError),
 
@@ -173,6 +237,18 @@ impl Error {
     pub fn string(s: &str) -> Self {
         Self::Message(s.to_string())
     }
+    /// Returns the constraint violation behind a database error, if any.
+    #[cfg(feature = "with-db")]
+    #[must_use]
+    pub fn constraint_violation(&self) -> Option<crate::model::constraint::ConstraintViolation> {
+        match self {
+            Self::WithBacktrace { inner, .. } => inner.constraint_violation(),
+            Self::DB(err) | Self::Model(crate::model::ModelError::DbErr(err)) => {
+                crate::model::constraint::ConstraintViolation::from_db_err(err)
+            }
+            _ => None,
+        }
+    }
     #[must_use]
     pub fn bt(self) -> Self {
         let backtrace = std::backtrace::Backtrace::capture();
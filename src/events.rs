@@ -0,0 +1,210 @@
+//! # Events
+//!
+//! A typed event bus within the process, available as
+//! [`crate::app::AppContext::events`], to decouple the parts of an app: code
+//! publishes an event, and the handlers subscribed to its type react to it,
+//! without the publisher knowing them.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! #[derive(Debug, Clone)]
+//! pub struct UserCreated {
+//!     pub id: i32,
+//!     pub email: String,
+//! }
+//!
+//! // in an initializer
+//! async fn on_context_built(&self, ctx: &AppContext) -> Result<()> {
+//!     let welcome_ctx = ctx.clone();
+//!     ctx.events().subscribe(move |event: UserCreated| {
+//!         let ctx = welcome_ctx.clone();
+//!         async move { WelcomeMailer::send(&ctx, &event.email).await }
+//!     });
+//!     Ok(())
+//! }
+//!
+//! // in a controller
+//! ctx.events().publish(UserCreated { id: user.id, email: user.email.clone() });
+//! ```
+//!
+//! # Backpressure
+//!
+//! Each event type has a bounded channel of `events.capacity` events (see
+//! [`crate::config::Events`]), which its subscribers read at their own pace:
+//!
+//! * publishing never waits, and events published while nothing subscribed
+//!   to their type are dropped,
+//! * a subscriber handles its events one at a time, in the order they were
+//!   published,
+//! * a subscriber falling more than `capacity` events behind misses the
+//!   oldest ones, logged with a warning, and goes on with the oldest event
+//!   still buffered. The other subscribers are not affected,
+//! * handler errors are logged, and the subscriber goes on,
+//! * subscribers stop when the app shuts down, dropping the events not
+//!   handled yet.
+//!
+//! Events live in memory: they are lost on restart and not shared between
+//! processes. Enqueue a job for work that must happen.
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    task::JoinHandle,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{config, Result};
+
+/// A value published on the [`EventBus`], delivered to the subscribers of its
+/// type. Implemented by all the types that can be cloned and sent to a task.
+pub trait Event: Clone + Send + 'static {}
+
+impl<T: Clone + Send + 'static> Event for T {}
+
+type Channels = HashMap<TypeId, Box<dyn Any + Send + Sync>>;
+
+/// The event bus of the app, see the [module documentation](self).
+#[derive(Clone)]
+pub struct EventBus {
+    capacity: usize,
+    channels: Arc<Mutex<Channels>>,
+    shutdown: CancellationToken,
+}
+
+impl std::fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventBus")
+            .field("capacity", &self.capacity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl EventBus {
+    /// Creates a bus whose subscribers stop once `shutdown` is cancelled.
+    #[must_use]
+    pub fn new(config: &config::Events, shutdown: CancellationToken) -> Self {
+        Self {
+            capacity: config.capacity.max(1),
+            channels: Arc::new(Mutex::new(HashMap::new())),
+            shutdown,
+        }
+    }
+
+    /// Publishes `event` to the subscribers of its type, without waiting for
+    /// them. Returns the number of subscribers it is delivered to, `0` when
+    /// the event is dropped.
+    pub fn publish<E: Event>(&self, event: E) -> usize {
+        self.sender::<E>().send(event).unwrap_or_else(|_| {
+            tracing::trace!(event = type_name::<E>(), "no subscriber for the event");
+            0
+        })
+    }
+
+    /// Runs `handler` for each event of type `E` published from now on, in a
+    /// task which stops when the app shuts down.
+    pub fn subscribe<E, F, Fut>(&self, handler: F) -> JoinHandle<()>
+    where
+        E: Event,
+        F: Fn(E) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let mut receiver = self.receiver::<E>();
+        let shutdown = self.shutdown.clone();
+        tokio::spawn(async move {
+            let event = type_name::<E>();
+            loop {
+                let received = tokio::select! {
+                    () = shutdown.cancelled() => break,
+                    received = receiver.recv() => received,
+                };
+                match received {
+                    Ok(published) => {
+                        if let Err(err) = handler(published).await {
+                            tracing::error!(event, error = %err, "event handler failed");
+                        }
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        tracing::warn!(event, missed, "event subscriber lagged, events dropped");
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    /// Returns a receiver of the events of type `E` published from now on,
+    /// to read them in a task of its own. See the [module
+    /// documentation](self) for what happens when it lags.
+    #[must_use]
+    pub fn receiver<E: Event>(&self) -> broadcast::Receiver<E> {
+        self.sender::<E>().subscribe()
+    }
+
+    /// Returns the number of subscribers and receivers of the events of type
+    /// `E`.
+    #[must_use]
+    pub fn subscribers<E: Event>(&self) -> usize {
+        self.sender::<E>().receiver_count()
+    }
+
+    fn sender<E: Event>(&self) -> broadcast::Sender<E> {
+        let mut channels = self.channels.lock().unwrap_or_else(PoisonError::into_inner);
+        channels
+            .entry(TypeId::of::<E>())
+            .or_insert_with(|| Box::new(broadcast::channel::<E>(self.capacity).0))
+            .downcast_ref::<broadcast::Sender<E>>()
+            .expect("channels are keyed by the type of their events")
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct UserCreated(u32);
+
+    #[tokio::test]
+    async fn can_publish_events() {
+        let shutdown = CancellationToken::new();
+        let bus = EventBus::new(&config::Events { capacity: 2 }, shutdown.clone());
+        assert_eq!(bus.publish(UserCreated(0)), 0);
+
+        let (handled, mut handled_rx) = tokio::sync::mpsc::unbounded_channel();
+        let subscriber = bus.subscribe(move |event: UserCreated| {
+            let handled = handled.clone();
+            async move {
+                handled.send(event).unwrap();
+                Ok(())
+            }
+        });
+        let mut lagging = bus.receiver::<UserCreated>();
+        assert_eq!(bus.subscribers::<UserCreated>(), 2);
+        // other event types have their own channel
+        assert_eq!(bus.publish("unrelated"), 0);
+
+        for id in 1..=3 {
+            assert_eq!(bus.publish(UserCreated(id)), 2);
+            assert_eq!(handled_rx.recv().await, Some(UserCreated(id)));
+        }
+        // the receiver read nothing, and missed the oldest event
+        assert_eq!(lagging.recv().await, Err(RecvError::Lagged(1)));
+        assert_eq!(lagging.recv().await, Ok(UserCreated(2)));
+
+        shutdown.cancel();
+        tokio::time::timeout(Duration::from_secs(1), subscriber)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(bus.subscribers::<UserCreated>(), 1);
+    }
+}
@@ -0,0 +1,218 @@
+//! # Database Failover
+//!
+//! When a managed database fails over, another server is promoted primary,
+//! usually behind the same host name. The pooled connections to the former
+//! primary break, or, when it stays up as a replica, turn read-only, and every
+//! query run on them fails until the app restarts.
+//!
+//! With `database.failover` enabled (the default), a failed query makes the
+//! app check the database. When the check fails with a connection error, or
+//! lands on a read-only server, the idle connections of the pool are closed,
+//! and the check is attempted again with a growing delay until it succeeds.
+//! New connections resolve the host name again, and reach the new primary.
+//! Connections in use meanwhile are closed by a later check, once their
+//! queries fail in turn.
+//!
+//! The `/_health` endpoint runs the same [`check`], so it reports the
+//! database unavailable until the app recovered.
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use sea_orm::{
+    sqlx, ConnectionTrait, DatabaseBackend, DatabaseConnection, DbErr, RuntimeErr, Statement,
+};
+use tokio::time::Instant;
+
+use crate::config::DatabaseFailover;
+
+/// The SQLSTATE codes of the errors caused by a failover, besides the `08`
+/// connection exceptions:
+///
+/// * `57P01`, `57P02` and `57P03`: the server shuts down, crashed, or is
+///   starting
+/// * `25006`: a write on a read-only server, such as a demoted primary
+const FAILOVER_CODES: &[&str] = &["57P01", "57P02", "57P03", "25006"];
+
+/// Whether `err` is caused by the database being unreachable or read-only,
+/// rather than by the query.
+#[must_use]
+pub fn is_connection_error(err: &DbErr) -> bool {
+    if matches!(err, DbErr::ConnectionAcquire(_)) {
+        return true;
+    }
+    let (DbErr::Conn(RuntimeErr::SqlxError(err))
+    | DbErr::Exec(RuntimeErr::SqlxError(err))
+    | DbErr::Query(RuntimeErr::SqlxError(err))) = err
+    else {
+        return false;
+    };
+    match err {
+        sqlx::Error::Io(_)
+        | sqlx::Error::Tls(_)
+        | sqlx::Error::Protocol(_)
+        | sqlx::Error::PoolTimedOut
+        | sqlx::Error::PoolClosed
+        | sqlx::Error::WorkerCrashed => true,
+        sqlx::Error::Database(err) => err
+            .code()
+            .is_some_and(|code| code.starts_with("08") || FAILOVER_CODES.contains(&code.as_ref())),
+        _ => false,
+    }
+}
+
+/// Checks that the database answers, and, for Postgres, that it accepts
+/// writes: a server found read-only is not the primary anymore.
+///
+/// # Errors
+///
+/// When the database does not answer, or is read-only
+pub async fn check(db: &DatabaseConnection) -> Result<(), DbErr> {
+    if db.get_database_backend() != DatabaseBackend::Postgres {
+        return db.ping().await;
+    }
+    let row = db
+        .query_one(Statement::from_string(
+            DatabaseBackend::Postgres,
+            "SELECT current_setting('transaction_read_only') = 'on'",
+        ))
+        .await?
+        .ok_or_else(|| DbErr::Custom("the database did not answer".to_string()))?;
+    if row.try_get_by_index::<bool>(0)? {
+        return Err(DbErr::Custom("the database is read-only".to_string()));
+    }
+    Ok(())
+}
+
+/// Closes the idle connections of the Postgres pool, for the next queries to
+/// open new ones.
+async fn close_idle_connections(db: &DatabaseConnection) {
+    if db.get_database_backend() != DatabaseBackend::Postgres {
+        return;
+    }
+    let pool = db.get_postgres_connection_pool();
+    for _ in 0..pool.size() {
+        let Some(connection) = pool.try_acquire() else {
+            break;
+        };
+        if let Err(err) = connection.close().await {
+            tracing::debug!(error = %err, "could not close a database connection");
+        }
+    }
+}
+
+/// Watches the queries of a connection, and recovers it after a failover.
+pub(crate) struct Failover {
+    db: DatabaseConnection,
+    config: DatabaseFailover,
+    recovering: AtomicBool,
+    last_check: Mutex<Option<Instant>>,
+}
+
+impl Failover {
+    /// Watches `db`, which is not itself watched, so the checks never trigger
+    /// another recovery.
+    pub(crate) fn new(db: DatabaseConnection, config: &DatabaseFailover) -> Arc<Self> {
+        Arc::new(Self {
+            db,
+            config: config.clone(),
+            recovering: AtomicBool::new(false),
+            last_check: Mutex::new(None),
+        })
+    }
+
+    /// Checks the database after a failed query, unless it is being checked
+    /// or was checked less than `min_backoff` ago.
+    pub(crate) fn on_query_failed(self: &Arc<Self>) {
+        let min_backoff = Duration::from_millis(self.config.min_backoff);
+        if let Ok(mut last_check) = self.last_check.lock() {
+            if last_check.is_some_and(|at| at.elapsed() < min_backoff) {
+                return;
+            }
+            *last_check = Some(Instant::now());
+        }
+        if self.recovering.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            self.recovering.store(false, Ordering::SeqCst);
+            return;
+        };
+        runtime.spawn(self.clone().recover());
+    }
+
+    async fn recover(self: Arc<Self>) {
+        let mut delay = Duration::from_millis(self.config.min_backoff);
+        let max_backoff = Duration::from_millis(self.config.max_backoff);
+        let mut attempts = 0_u32;
+        loop {
+            match check(&self.db).await {
+                Ok(()) => break,
+                Err(err) if attempts == 0 && !is_failover(&err) => {
+                    // the query failed on its own, the database is fine
+                    tracing::debug!(error = %err, "database check failed");
+                    break;
+                }
+                Err(err) => {
+                    attempts += 1;
+                    tracing::warn!(
+                        attempts,
+                        error = %err,
+                        retry_in = ?delay,
+                        "database unavailable, reconnecting"
+                    );
+                    close_idle_connections(&self.db).await;
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(max_backoff);
+                }
+            }
+        }
+        if attempts > 0 {
+            tracing::info!(attempts, "database connections recovered");
+        }
+        self.recovering.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Whether a failed check calls for new connections.
+fn is_failover(err: &DbErr) -> bool {
+    is_connection_error(err) || matches!(err, DbErr::Custom(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_detect_connection_errors() {
+        let io = DbErr::Conn(RuntimeErr::SqlxError(sqlx::Error::Io(
+            std::io::ErrorKind::ConnectionReset.into(),
+        )));
+        assert!(is_connection_error(&io));
+        assert!(is_connection_error(&DbErr::Query(RuntimeErr::SqlxError(
+            sqlx::Error::PoolTimedOut
+        ))));
+        assert!(!is_connection_error(&DbErr::Query(RuntimeErr::SqlxError(
+            sqlx::Error::RowNotFound
+        ))));
+        assert!(!is_connection_error(&DbErr::RecordNotFound(
+            "note".to_string()
+        )));
+    }
+
+    #[tokio::test]
+    async fn can_check_the_database() {
+        let db = crate::tests_cfg::db::dummy_connection().await;
+        assert!(check(&db).await.is_ok());
+
+        let failover = Failover::new(db, &DatabaseFailover::default());
+        failover.on_query_failed();
+        // a healthy database is checked once, then left alone
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!failover.recovering.load(Ordering::SeqCst));
+    }
+}
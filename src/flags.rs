@@ -0,0 +1,326 @@
+//! # Feature Flags
+//!
+//! Runtime feature flags, evaluated per request by user, percentage rollout
+//! and environment, with [`crate::app::AppContext::feature`] or the
+//! [`Features`] extractor.
+//!
+//! By default flags come from `flags` in the configuration:
+//!
+//! ```yaml
+//! flags:
+//!   new_checkout:
+//!     # always on for these users
+//!     users: ["42"]
+//!     # and for 20% of the others, always the same ones
+//!     percentage: 20
+//!     # only in these environments, all when empty
+//!     environments: [development, staging]
+//!   dark_mode: {}                  # on for everyone
+//!   legacy_export:
+//!     enabled: false               # off for everyone
+//! ```
+//!
+//! Unknown flags are off. Rollouts are by user id, so a percentage rollout is
+//! off for requests without a user.
+//!
+//! Evaluating a flag has no side effects and should be cheap, as it runs on
+//! every request asking for it: providers backed by an external service,
+//! like `LaunchDarkly`, implement [`FlagProvider`] over a local copy of the
+//! flags synced in the background, or wrap their lookups in [`Cached`]. Set
+//! the provider in [`crate::app::Hooks::after_context`]:
+//!
+//! ```rust,ignore
+//! async fn after_context(ctx: AppContext) -> Result<AppContext> {
+//!     let flags = Cached::new(MyFlags::connect().await?, Duration::from_secs(30));
+//!     Ok(AppContext {
+//!         flags: Arc::new(flags),
+//!         ..ctx
+//!     })
+//! }
+//! ```
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{extract::FromRequestParts, http::request::Parts};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{app::AppContext, environment::Environment, Error};
+
+/// What a flag is evaluated for: the user, and any attribute a provider
+/// targets.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct FlagContext {
+    pub user: Option<String>,
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl FlagContext {
+    /// Evaluates flags for the user `id`.
+    #[must_use]
+    pub fn user(id: impl Into<String>) -> Self {
+        Self {
+            user: Some(id.into()),
+            attributes: BTreeMap::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn attribute(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.insert(name.into(), value.into());
+        self
+    }
+}
+
+/// A source of feature flags.
+pub trait FlagProvider: Send + Sync {
+    /// Whether `flag` is on for `context`. Unknown flags are off.
+    fn is_enabled(&self, flag: &str, context: &FlagContext) -> bool;
+}
+
+/// A flag of the configuration, see the [module documentation](self).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+pub struct FlagRule {
+    /// Turns the flag off for everyone when `false`.
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// The ids of the users the flag is on for.
+    #[serde(default)]
+    pub users: Vec<String>,
+    /// The percentage of the users the flag is on for.
+    pub percentage: Option<u8>,
+    /// The environments the flag is on in, all when empty.
+    #[serde(default)]
+    pub environments: Vec<String>,
+}
+
+const fn default_true() -> bool {
+    true
+}
+
+impl FlagRule {
+    fn is_enabled(&self, flag: &str, context: &FlagContext, environment: &Environment) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        if !self.environments.is_empty()
+            && !self
+                .environments
+                .iter()
+                .any(|name| *name == environment.to_string())
+        {
+            return false;
+        }
+        if self.users.is_empty() && self.percentage.is_none() {
+            return true;
+        }
+        context.user.as_ref().is_some_and(|user| {
+            self.users.contains(user)
+                || self
+                    .percentage
+                    .is_some_and(|percentage| bucket(flag, user) < percentage)
+        })
+    }
+}
+
+/// The rollout bucket of `user` for `flag`, from 0 to 99, stable across
+/// processes and independent from one flag to the other.
+fn bucket(flag: &str, user: &str) -> u8 {
+    let digest = Sha256::digest(format!("{flag}:{user}"));
+    let value = u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]);
+    #[allow(clippy::cast_possible_truncation)]
+    let bucket = (value % 100) as u8;
+    bucket
+}
+
+/// The flags of the configuration, the default provider.
+pub struct StaticFlags {
+    rules: BTreeMap<String, FlagRule>,
+    environment: Environment,
+}
+
+impl StaticFlags {
+    #[must_use]
+    pub const fn new(rules: BTreeMap<String, FlagRule>, environment: Environment) -> Self {
+        Self { rules, environment }
+    }
+}
+
+impl FlagProvider for StaticFlags {
+    fn is_enabled(&self, flag: &str, context: &FlagContext) -> bool {
+        self.rules
+            .get(flag)
+            .is_some_and(|rule| rule.is_enabled(flag, context, &self.environment))
+    }
+}
+
+/// The number of evaluations [`Cached`] keeps before it starts over.
+const CACHE_CAPACITY: usize = 10_000;
+
+type CacheKey = (String, FlagContext);
+
+/// Caches the evaluations of another provider for `ttl`.
+pub struct Cached<P> {
+    provider: P,
+    ttl: Duration,
+    evaluations: Mutex<HashMap<CacheKey, (bool, Instant)>>,
+}
+
+impl<P: FlagProvider> Cached<P> {
+    #[must_use]
+    pub fn new(provider: P, ttl: Duration) -> Self {
+        Self {
+            provider,
+            ttl,
+            evaluations: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<P: FlagProvider> FlagProvider for Cached<P> {
+    fn is_enabled(&self, flag: &str, context: &FlagContext) -> bool {
+        let key = (flag.to_string(), context.clone());
+        let now = Instant::now();
+        {
+            let evaluations = self
+                .evaluations
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            if let Some((enabled, at)) = evaluations.get(&key) {
+                if now.duration_since(*at) < self.ttl {
+                    return *enabled;
+                }
+            }
+        }
+        let enabled = self.provider.is_enabled(flag, context);
+        let mut evaluations = self
+            .evaluations
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if evaluations.len() >= CACHE_CAPACITY {
+            evaluations.clear();
+        }
+        evaluations.insert(key, (enabled, now));
+        enabled
+    }
+}
+
+/// The feature flags of a request, evaluated for the [`FlagContext`] in the
+/// request extensions, inserted by an authentication middleware for
+/// example, or else for an anonymous context.
+///
+/// ```rust
+/// use loco_rs::{flags::Features, prelude::*};
+///
+/// async fn checkout(features: Features) -> Result<Response> {
+///     if features.enabled("new_checkout") {
+///         return format::text("new checkout");
+///     }
+///     format::text("checkout")
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Features {
+    provider: Arc<dyn FlagProvider>,
+    pub context: FlagContext,
+}
+
+impl Features {
+    /// Whether `flag` is on for the request.
+    #[must_use]
+    pub fn enabled(&self, flag: &str) -> bool {
+        self.provider.is_enabled(flag, &self.context)
+    }
+}
+
+impl FromRequestParts<AppContext> for Features {
+    type Rejection = Error;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppContext,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(Self {
+            provider: state.flags.clone(),
+            context: parts
+                .extensions
+                .get::<FlagContext>()
+                .cloned()
+                .unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    fn flags() -> StaticFlags {
+        let rules = serde_yaml::from_str(
+            r#"
+            everyone: {}
+            off:
+              enabled: false
+            beta:
+              users: ["1"]
+            rollout:
+              percentage: 50
+            staging_only:
+              environments: [staging]
+            "#,
+        )
+        .unwrap();
+        StaticFlags::new(rules, Environment::Test)
+    }
+
+    #[test]
+    fn can_evaluate_static_flags() {
+        let flags = flags();
+        let anonymous = FlagContext::default();
+        let user = FlagContext::user("1");
+
+        assert!(flags.is_enabled("everyone", &anonymous));
+        assert!(!flags.is_enabled("off", &user));
+        assert!(!flags.is_enabled("unknown", &user));
+        assert!(flags.is_enabled("beta", &user));
+        assert!(!flags.is_enabled("beta", &FlagContext::user("2")));
+        assert!(!flags.is_enabled("beta", &anonymous));
+        assert!(!flags.is_enabled("staging_only", &user));
+        assert!(!flags.is_enabled("rollout", &anonymous));
+    }
+
+    #[test]
+    fn rolls_out_to_a_stable_percentage() {
+        let flags = flags();
+        let enabled = (0..1000)
+            .filter(|id| flags.is_enabled("rollout", &FlagContext::user(id.to_string())))
+            .count();
+        assert!((400..600).contains(&enabled), "{enabled}");
+        assert_eq!(bucket("rollout", "7"), bucket("rollout", "7"));
+    }
+
+    struct Counting(AtomicUsize);
+
+    impl FlagProvider for Counting {
+        fn is_enabled(&self, _flag: &str, _context: &FlagContext) -> bool {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            true
+        }
+    }
+
+    #[test]
+    fn caches_evaluations() {
+        let cached = Cached::new(Counting(AtomicUsize::new(0)), Duration::from_secs(60));
+        let context = FlagContext::user("1");
+        assert!(cached.is_enabled("a", &context));
+        assert!(cached.is_enabled("a", &context));
+        assert!(cached.is_enabled("b", &context));
+        assert_eq!(cached.provider.0.load(Ordering::SeqCst), 2);
+    }
+}
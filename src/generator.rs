@@ -0,0 +1,211 @@
+//! # Generators
+//!
+//! Programmable scaffolding: a [`Generator`] renders templates into the
+//! project, parameterized by a name, the same way `cargo loco generate` does.
+//! Generators are tasks, so an app registers its own in
+//! [`crate::app::Hooks::register_tasks`] and runs them with
+//! `cargo loco task <generator> name:<name>`:
+//!
+//! ```rust
+//! use loco_rs::{generator::Generator, task::Tasks};
+//!
+//! fn register_tasks(tasks: &mut Tasks) {
+//!     tasks.register(Generator::worker());
+//!     tasks.register(
+//!         Generator::new("generate:policy", "Generates a policy").template(
+//!             r#"{% set file_name = name | snake_case -%}
+//! to: "src/policies/{{file_name}}.rs"
+//! skip_exists: true
+//! message: "The policy `{{file_name}}` was added."
+//! ---
+//! pub struct {{name | pascal_case}}Policy;
+//! "#,
+//!         ),
+//!     );
+//! }
+//! ```
+//!
+//! Templates are `rrgen` templates: a front matter with the destination
+//! file and the injections into existing files, then the content, both
+//! rendered with Tera and the `name` and other task arguments as variables.
+//! Built-in templates can be overridden from the `.loco-templates` folder of
+//! the project, like with `cargo loco generate`.
+use std::path::Path;
+
+use async_trait::async_trait;
+use loco_gen::{template::DEFAULT_LOCAL_TEMPLATE, GenResult, RRgen};
+use serde_json::{Map, Value};
+
+use crate::{
+    app::AppContext,
+    task::{Task, TaskInfo, Vars},
+    Error, Result,
+};
+
+/// A template of a [`Generator`].
+#[derive(Debug, Clone)]
+enum Template {
+    Content(String),
+    /// The path of a template of `loco-gen`.
+    Builtin(&'static str),
+}
+
+impl Template {
+    fn content(&self, dir: &Path) -> Result<String> {
+        match self {
+            Self::Content(content) => Ok(content.clone()),
+            Self::Builtin(path) => {
+                let local = dir.join(DEFAULT_LOCAL_TEMPLATE).join(path);
+                if local.exists() {
+                    return Ok(std::fs::read_to_string(local)?);
+                }
+                let files = loco_gen::template::collect_files_from_path(Path::new(path))?;
+                files
+                    .first()
+                    .and_then(|file| file.contents_utf8())
+                    .map(ToString::to_string)
+                    .ok_or_else(|| Error::Message(format!("could not read template `{path}`")))
+            }
+        }
+    }
+}
+
+/// Emits files from templates into the project, see the [module
+/// documentation](self).
+#[derive(Debug, Clone)]
+pub struct Generator {
+    name: String,
+    detail: String,
+    templates: Vec<Template>,
+}
+
+impl Generator {
+    /// A generator without templates, run as the task `name`.
+    #[must_use]
+    pub fn new(name: &str, detail: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            detail: detail.to_string(),
+            templates: Vec::new(),
+        }
+    }
+
+    /// Adds a template, rendered after the previous ones.
+    #[must_use]
+    pub fn template(mut self, template: impl Into<String>) -> Self {
+        self.templates.push(Template::Content(template.into()));
+        self
+    }
+
+    /// Generates a typed worker in `src/workers`, registered in
+    /// `connect_workers`, as the task `generate:worker`.
+    #[must_use]
+    pub fn worker() -> Self {
+        let mut generator = Self::new("generate:worker", "Generates a background worker");
+        generator
+            .templates
+            .push(Template::Builtin("worker/worker.t"));
+        generator
+    }
+
+    /// Renders the templates into the project at `dir` with `vars`, returning
+    /// the messages of the generated files.
+    ///
+    /// # Errors
+    ///
+    /// When a template cannot be read or rendered, or a file cannot be
+    /// written or injected into.
+    pub fn generate(&self, dir: &Path, vars: &Value) -> Result<Vec<String>> {
+        let rrgen = RRgen::with_working_dir(dir).add_template_engine(loco_gen::tera_ext::new());
+        let mut messages = Vec::new();
+        for template in &self.templates {
+            let result = rrgen
+                .generate(&template.content(dir)?, vars)
+                .map_err(loco_gen::Error::from)?;
+            if let GenResult::Generated {
+                message: Some(message),
+            } = result
+            {
+                messages.push(message);
+            }
+        }
+        Ok(messages)
+    }
+}
+
+#[async_trait]
+impl Task for Generator {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: self.name.clone(),
+            detail: self.detail.clone(),
+        }
+    }
+
+    /// Generates into the current directory, with the task arguments, which
+    /// must include `name`, as variables.
+    async fn run(&self, _app_context: &AppContext, vars: &Vars) -> Result<()> {
+        vars.cli_arg("name")?;
+        let vars = vars
+            .cli
+            .iter()
+            .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+            .collect::<Map<_, _>>();
+        for message in self.generate(Path::new("."), &Value::Object(vars))? {
+            println!("* {message}");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn can_generate_a_worker() {
+        let tree = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .add("src/workers/mod.rs", "pub mod downloader;\n")
+            .add(
+                "src/app.rs",
+                "impl Hooks for App {\n    async fn connect_workers(ctx: &AppContext, queue: &Queue) -> Result<()> {\n        Ok(())\n    }\n}\n",
+            )
+            .create()
+            .unwrap();
+
+        let messages = Generator::worker()
+            .generate(&tree.root, &json!({"name": "send_report"}))
+            .unwrap();
+        assert_eq!(messages.len(), 1);
+
+        let worker = std::fs::read_to_string(tree.root.join("src/workers/send_report.rs")).unwrap();
+        assert!(worker.contains("impl BackgroundWorker<WorkerArgs> for Worker"));
+        assert!(worker.contains("\"SendReport\".to_string()"));
+        let workers = std::fs::read_to_string(tree.root.join("src/workers/mod.rs")).unwrap();
+        assert!(workers.contains("pub mod send_report;"));
+        let app = std::fs::read_to_string(tree.root.join("src/app.rs")).unwrap();
+        assert!(
+            app.contains("queue.register(crate::workers::send_report::Worker::build(ctx)).await?;")
+        );
+    }
+
+    #[test]
+    fn can_generate_from_templates() {
+        let tree = tree_fs::TreeBuilder::default().drop(true).create().unwrap();
+        let generator = Generator::new("generate:policy", "Generates a policy").template(
+            "to: \"src/policies/{{name | snake_case}}.rs\"\nmessage: \"policy {{name}}\"\n---\npub struct {{name | pascal_case}}Policy;\n",
+        );
+        assert_eq!(generator.task().name, "generate:policy");
+
+        let messages = generator
+            .generate(&tree.root, &json!({"name": "admin_users"}))
+            .unwrap();
+        assert_eq!(messages, ["policy admin_users"]);
+        let policy =
+            std::fs::read_to_string(tree.root.join("src/policies/admin_users.rs")).unwrap();
+        assert_eq!(policy, "pub struct AdminUsersPolicy;\n");
+    }
+}
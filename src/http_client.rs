@@ -0,0 +1,278 @@
+//! # HTTP Client
+//!
+//! The shared client for the outbound calls of the app, available as
+//! [`crate::app::AppContext::http_client`], so that all the calls share one
+//! connection pool and the same settings, from `http_client` in the
+//! configuration (see [`crate::config::HttpClient`]):
+//!
+//! * connect and request timeouts,
+//! * retries of the idempotent requests (`GET`, `HEAD`, `OPTIONS`, `PUT`,
+//!   `DELETE` and `TRACE`) on connection errors, timeouts and `502`, `503`
+//!   or `504` responses, with an exponential backoff,
+//! * an optional proxy,
+//! * the `x-request-id` of the request being handled, with the `request_id`
+//!   middleware, so that upstream logs correlate with the app ones.
+//!
+//! # Example
+//!
+//! ```rust
+//! use loco_rs::prelude::*;
+//!
+//! async fn weather(State(ctx): State<AppContext>) -> Result<Response> {
+//!     let client = &ctx.http_client;
+//!     let forecast = client
+//!         .send(client.get("https://weather.example.com/forecast").query(&[("city", "Paris")]))
+//!         .await?
+//!         .text()
+//!         .await?;
+//!     format::text(&forecast)
+//! }
+//! ```
+use std::time::{Duration, Instant};
+
+use axum::http::{HeaderValue, Method, StatusCode};
+pub use reqwest;
+use reqwest::{Client, IntoUrl, RequestBuilder, Response};
+
+use crate::{config, controller::middleware::request_id::LocoRequestId, Result};
+
+const X_REQUEST_ID: &str = "x-request-id";
+
+/// The shared HTTP client, see the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: Client,
+    retries: u32,
+    retry_backoff: Duration,
+}
+
+impl HttpClient {
+    /// Builds the client of `config`.
+    ///
+    /// # Errors
+    ///
+    /// When the proxy is invalid, or the TLS backend cannot be initialized.
+    pub fn new(config: &config::HttpClient) -> Result<Self> {
+        let mut builder = Client::builder()
+            .connect_timeout(Duration::from_millis(config.connect_timeout))
+            .timeout(Duration::from_millis(config.timeout))
+            .pool_idle_timeout(Duration::from_millis(config.pool_idle_timeout));
+        if let Some(proxy) = &config.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+        if let Some(user_agent) = &config.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        Ok(Self {
+            client: builder.build()?,
+            retries: config.retries,
+            retry_backoff: Duration::from_millis(config.retry_backoff),
+        })
+    }
+
+    /// The underlying client, for requests sent without the retries and the
+    /// request id.
+    #[must_use]
+    pub const fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Starts a request, to send with [`HttpClient::send`].
+    pub fn request(&self, method: Method, url: impl IntoUrl) -> RequestBuilder {
+        self.client.request(method, url)
+    }
+
+    pub fn get(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::GET, url)
+    }
+
+    pub fn post(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::POST, url)
+    }
+
+    pub fn put(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::PUT, url)
+    }
+
+    pub fn patch(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::PATCH, url)
+    }
+
+    pub fn delete(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::DELETE, url)
+    }
+
+    /// Sends `request` with the request id of the request being handled,
+    /// retrying it when idempotent. Error responses are returned as
+    /// responses, once the retries are exhausted for the retried ones.
+    ///
+    /// # Errors
+    ///
+    /// When the request is invalid, or fails after its retries.
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        let mut request = request.build()?;
+        if let Some(id) = LocoRequestId::current() {
+            if let Ok(value) = HeaderValue::from_str(&id) {
+                request.headers_mut().entry(X_REQUEST_ID).or_insert(value);
+            }
+        }
+        let method = request.method().clone();
+        let url = request.url().to_string();
+        let idempotent = is_idempotent(&method);
+
+        let mut attempt = 0;
+        loop {
+            let retry = if idempotent && attempt < self.retries {
+                // requests with a streaming body cannot be cloned nor retried
+                request.try_clone()
+            } else {
+                None
+            };
+            let started = Instant::now();
+            let result = self.client.execute(request).await;
+            let elapsed_ms = started.elapsed().as_millis();
+            let outcome = match &result {
+                Ok(response) => response.status().as_u16().to_string(),
+                Err(err) => err.to_string(),
+            };
+            tracing::debug!(%method, url, attempt, elapsed_ms, outcome, "http client request");
+
+            let Some(retry) = retry.filter(|_| should_retry(&result)) else {
+                return Ok(result?);
+            };
+            let delay = self.retry_backoff * 2u32.saturating_pow(attempt);
+            tracing::warn!(%method, url, attempt, outcome, delay_ms = delay.as_millis(), "retrying http client request");
+            tokio::time::sleep(delay).await;
+            request = retry;
+            attempt += 1;
+        }
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    [
+        Method::GET,
+        Method::HEAD,
+        Method::OPTIONS,
+        Method::PUT,
+        Method::DELETE,
+        Method::TRACE,
+    ]
+    .contains(method)
+}
+
+fn should_retry(result: &reqwest::Result<Response>) -> bool {
+    match result {
+        Ok(response) => [
+            StatusCode::BAD_GATEWAY,
+            StatusCode::SERVICE_UNAVAILABLE,
+            StatusCode::GATEWAY_TIMEOUT,
+        ]
+        .contains(&response.status()),
+        Err(err) => err.is_connect() || err.is_timeout(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use axum::{
+        body::Body,
+        extract::State,
+        http::{HeaderMap, Request},
+        routing::get,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+
+    /// Serves `router` on a free port, returning its base url.
+    async fn upstream(router: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        format!("http://{addr}")
+    }
+
+    fn client() -> HttpClient {
+        HttpClient::new(&config::HttpClient {
+            retry_backoff: 1,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn retries_idempotent_requests() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let flaky = |State(calls): State<Arc<AtomicUsize>>| async move {
+            if calls.fetch_add(1, Ordering::SeqCst) % 2 == 0 {
+                StatusCode::SERVICE_UNAVAILABLE
+            } else {
+                StatusCode::OK
+            }
+        };
+        let url = upstream(
+            Router::new()
+                .route("/", get(flaky).post(flaky))
+                .with_state(calls.clone()),
+        )
+        .await;
+        let client = client();
+
+        let response = client.send(client.get(&url)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let response = client.send(client.post(&url)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn propagates_the_request_id() {
+        let url = upstream(Router::new().route(
+            "/",
+            get(|headers: HeaderMap| async move {
+                headers
+                    .get(X_REQUEST_ID)
+                    .map(|id| id.to_str().unwrap().to_string())
+                    .unwrap_or_default()
+            }),
+        ))
+        .await;
+
+        let app = Router::new()
+            .route(
+                "/",
+                get(|State(url): State<String>| async move {
+                    let client = client();
+                    client
+                        .send(client.get(&url))
+                        .await
+                        .unwrap()
+                        .text()
+                        .await
+                        .unwrap()
+                }),
+            )
+            .with_state(url)
+            .layer(axum::middleware::from_fn(
+                crate::controller::middleware::request_id::request_id_middleware,
+            ));
+        let request = Request::get("/")
+            .header(X_REQUEST_ID, "upstream-test")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, "upstream-test");
+    }
+}
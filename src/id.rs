@@ -0,0 +1,167 @@
+//! # Ids
+//!
+//! The ids generated by the app: request ids (see the `request_id`
+//! middleware), background job ids, and any id an app generates with
+//! [`generate`], so that the ids of a request and of the jobs it enqueued
+//! follow one scheme and correlate in logs.
+//!
+//! The scheme is set by `ids` in the configuration:
+//!
+//! ```yaml
+//! # one of: uuid_v7 (default), uuid_v4, ulid
+//! ids: uuid_v7
+//! ```
+//!
+//! * `uuid_v7`: time-ordered UUIDs, sorting (as strings too) in the order they
+//!   were generated, which keeps B-tree indexes compact.
+//! * `uuid_v4`: random UUIDs, with no ordering, and revealing nothing about
+//!   when they were generated.
+//! * `ulid`: time-ordered like `uuid_v7`, in a shorter, 26 characters,
+//!   case-insensitive encoding.
+//!
+//! Time-ordered ids expose their creation time to anyone seeing them.
+//!
+//! Other schemes, like KSUID, plug in with [`set_generator`], for example in
+//! [`crate::app::Hooks::after_context`]:
+//!
+//! ```rust
+//! use std::sync::Arc;
+//!
+//! use loco_rs::id::{self, IdGenerator};
+//!
+//! struct Prefixed;
+//!
+//! impl IdGenerator for Prefixed {
+//!     fn generate(&self) -> String {
+//!         format!("id_{}", ulid::Ulid::new())
+//!     }
+//! }
+//!
+//! id::set_generator(Arc::new(Prefixed));
+//! assert!(id::generate().starts_with("id_"));
+//! # id::set_generator(id::IdScheme::default().generator());
+//! ```
+use std::sync::{Arc, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+/// A source of unique ids.
+pub trait IdGenerator: Send + Sync {
+    fn generate(&self) -> String;
+}
+
+/// Generates random UUIDs (version 4).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UuidV4;
+
+impl IdGenerator for UuidV4 {
+    fn generate(&self) -> String {
+        uuid::Uuid::new_v4().to_string()
+    }
+}
+
+/// Generates time-ordered UUIDs (version 7), increasing within the process.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UuidV7;
+
+impl IdGenerator for UuidV7 {
+    fn generate(&self) -> String {
+        uuid::Uuid::now_v7().to_string()
+    }
+}
+
+/// Generates ULIDs, time-ordered, increasing within the process.
+#[derive(Default)]
+pub struct Ulid {
+    generator: std::sync::Mutex<ulid::Generator>,
+}
+
+impl IdGenerator for Ulid {
+    fn generate(&self) -> String {
+        self.generator
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .generate()
+            // overflowing the random part within a millisecond is unlikely
+            .unwrap_or_else(|_| ulid::Ulid::new())
+            .to_string()
+    }
+}
+
+/// The built-in id schemes, see the [module documentation](self).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum IdScheme {
+    #[default]
+    UuidV7,
+    UuidV4,
+    Ulid,
+}
+
+impl IdScheme {
+    #[must_use]
+    pub fn generator(self) -> Arc<dyn IdGenerator> {
+        match self {
+            Self::UuidV7 => Arc::new(UuidV7),
+            Self::UuidV4 => Arc::new(UuidV4),
+            Self::Ulid => Arc::new(Ulid::default()),
+        }
+    }
+}
+
+static GENERATOR: RwLock<Option<Arc<dyn IdGenerator>>> = RwLock::new(None);
+
+/// Sets the generator of the ids of the process. The app sets the one of the
+/// `ids` configuration when it creates its context.
+pub fn set_generator(generator: Arc<dyn IdGenerator>) {
+    *GENERATOR
+        .write()
+        .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(generator);
+}
+
+/// Returns the generator of the ids of the process.
+#[must_use]
+pub fn generator() -> Arc<dyn IdGenerator> {
+    GENERATOR
+        .read()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .clone()
+        .unwrap_or_else(|| IdScheme::default().generator())
+}
+
+/// Generates an id with the generator of the process.
+#[must_use]
+pub fn generate() -> String {
+    generator().generate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_increasing(generator: &dyn IdGenerator) {
+        let ids = (0..1000).map(|_| generator.generate()).collect::<Vec<_>>();
+        let mut sorted = ids.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(ids, sorted);
+    }
+
+    #[test]
+    fn time_ordered_schemes_increase() {
+        assert_increasing(IdScheme::UuidV7.generator().as_ref());
+        assert_increasing(IdScheme::Ulid.generator().as_ref());
+    }
+
+    #[test]
+    fn can_generate_built_in_schemes() {
+        assert_eq!(IdScheme::UuidV4.generator().generate().len(), 36);
+        assert_eq!(IdScheme::UuidV7.generator().generate().len(), 36);
+        assert_eq!(IdScheme::Ulid.generator().generate().len(), 26);
+        assert_eq!(
+            serde_yaml::from_str::<IdScheme>("ulid").unwrap(),
+            IdScheme::Ulid
+        );
+    }
+}
@@ -22,21 +22,38 @@ pub mod schema;
 mod tera;
 
 pub mod app;
+pub mod audit;
 pub mod auth;
 pub mod boot;
 pub mod cache;
 #[cfg(feature = "cli")]
 pub mod cli;
+pub mod clock;
 pub mod config;
+pub mod config_report;
 pub mod controller;
+pub mod deadline;
 mod env_vars;
 pub mod environment;
 pub mod errors;
+pub mod events;
+#[cfg(feature = "with-db")]
+pub mod failover;
+pub mod flags;
+pub mod generator;
 pub mod hash;
+pub mod http_client;
+pub mod id;
 pub mod logger;
 pub mod mailer;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod request_context;
 pub mod scheduler;
+pub mod stats;
 pub mod task;
+#[cfg(feature = "with-db")]
+pub mod tenancy;
 #[cfg(feature = "testing")]
 pub mod testing;
 #[cfg(feature = "testing")]
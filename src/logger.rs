@@ -1,7 +1,12 @@
 //! initialization application logger.
 
-use std::sync::OnceLock;
+use std::{
+    borrow::Cow,
+    io,
+    sync::{Arc, OnceLock},
+};
 
+use regex::{Captures, Regex};
 use serde::{Deserialize, Serialize};
 use serde_variant::to_variant_name;
 use tracing_appender::non_blocking::WorkerGuard;
@@ -13,6 +18,7 @@ use crate::{app::Hooks, config, Error, Result};
 
 // Define an enumeration for log levels
 #[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub enum LogLevel {
     /// The "off" level.
     #[serde(rename = "off")]
@@ -37,6 +43,7 @@ pub enum LogLevel {
 
 // Define an enumeration for log formats
 #[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub enum Format {
     #[serde(rename = "compact")]
     #[default]
@@ -49,6 +56,7 @@ pub enum Format {
 
 // Define an enumeration for log file appender rotation
 #[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub enum Rotation {
     #[serde(rename = "minutely")]
     Minutely,
@@ -61,6 +69,187 @@ pub enum Rotation {
     Never,
 }
 
+/// Sensitive values replaced with `***` in the logs, whatever the format, in
+/// the access logs and in the printed configuration.
+///
+/// ```yaml
+/// logger:
+///   redact:
+///     fields: [password, token, api_key]
+///     headers: [authorization, cookie, x-api-key]
+///     patterns: ['\b\d{4}-\d{4}-\d{4}-\d{4}\b']
+/// ```
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
+pub struct Redaction {
+    /// Names of the fields whose values are redacted, such as `password`
+    /// (in `password=...`, `"password":"..."` or `password: ...`, including
+    /// query strings). Case insensitive.
+    #[serde(default)]
+    pub fields: Vec<String>,
+    /// Names of the headers whose values are redacted. Case insensitive.
+    #[serde(default)]
+    pub headers: Vec<String>,
+    /// Regular expressions whose matches are redacted, wherever they appear.
+    #[serde(default)]
+    pub patterns: Vec<String>,
+}
+
+/// The replacement of redacted values.
+const REDACTED: &str = "***";
+
+/// ANSI styles the compact and pretty formats wrap field names with.
+const ANSI_STYLES: &str = r"(?:\x1b\[[0-9;]*m)*";
+
+/// Redacts the sensitive values of log lines, as configured by
+/// [`Redaction`].
+///
+/// [`init`] redacts the logs it sets up. An app setting up its own subscriber
+/// in [`Hooks::init_logger`] redacts its logs by wrapping its writers:
+///
+/// ```rust,ignore
+/// let redactor = logger::Redactor::new(&ctx.config.logger.redact)?;
+/// tracing_subscriber::fmt()
+///     .with_writer(redactor.writer(std::io::stdout))
+///     .init();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    inner: Arc<RedactorInner>,
+}
+
+#[derive(Debug, Default)]
+struct RedactorInner {
+    names: Option<Regex>,
+    patterns: Vec<Regex>,
+}
+
+impl Redactor {
+    /// Creates the redactor of `config`.
+    ///
+    /// # Errors
+    ///
+    /// When a pattern is not a valid regular expression
+    pub fn new(config: &Redaction) -> Result<Self> {
+        let names = config
+            .fields
+            .iter()
+            .chain(&config.headers)
+            .map(|name| regex::escape(name.trim()))
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<_>>();
+        let names = if names.is_empty() {
+            None
+        } else {
+            // `name`, quoted or styled, then `=` or `:`, then the value:
+            // quoted, or up to the next separator
+            let names = names.join("|");
+            Some(
+                Regex::new(&format!(
+                    r#"(?i)(?P<key>(?:\x1b\[[0-9;]*m|"|\b)(?:{names})\b"?{ANSI_STYLES}\s*[=:]{ANSI_STYLES}\s*)(?P<value>"(?:[^"\\]|\\.)*"|[^\s,&;}}\]"\x1b]+)"#
+                ))
+                .map_err(|err| Error::Message(format!("invalid logger.redact names: {err}")))?,
+            )
+        };
+        let patterns = config
+            .patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|err| {
+                    Error::Message(format!("invalid logger.redact pattern `{pattern}`: {err}"))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            inner: Arc::new(RedactorInner { names, patterns }),
+        })
+    }
+
+    /// Returns `text` with its sensitive values replaced with `***`.
+    #[must_use]
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        if let Some(names) = &self.inner.names {
+            if let Cow::Owned(redacted) = names.replace_all(&text, |caps: &Captures<'_>| {
+                let value = if caps["value"].starts_with('"') {
+                    format!("\"{REDACTED}\"")
+                } else {
+                    REDACTED.to_string()
+                };
+                format!("{}{value}", &caps["key"])
+            }) {
+                text = Cow::Owned(redacted);
+            }
+        }
+        for pattern in &self.inner.patterns {
+            if let Cow::Owned(redacted) = pattern.replace_all(&text, REDACTED) {
+                text = Cow::Owned(redacted);
+            }
+        }
+        text
+    }
+
+    /// Wraps `make_writer`, so that the log lines written with it are
+    /// redacted.
+    #[must_use]
+    pub fn writer<M>(&self, make_writer: M) -> RedactingMakeWriter<M> {
+        RedactingMakeWriter {
+            make_writer,
+            redactor: self.clone(),
+        }
+    }
+}
+
+/// A [`MakeWriter`] redacting the log lines written, see
+/// [`Redactor::writer`].
+#[derive(Debug, Clone)]
+pub struct RedactingMakeWriter<M> {
+    make_writer: M,
+    redactor: Redactor,
+}
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for RedactingMakeWriter<M> {
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter {
+            writer: self.make_writer.make_writer(),
+            redactor: self.redactor.clone(),
+        }
+    }
+
+    fn make_writer_for(&'a self, meta: &tracing::Metadata<'_>) -> Self::Writer {
+        RedactingWriter {
+            writer: self.make_writer.make_writer_for(meta),
+            redactor: self.redactor.clone(),
+        }
+    }
+}
+
+/// The writer of [`RedactingMakeWriter`]. Log lines are formatted whole, then
+/// written at once, so each write is redacted on its own.
+#[derive(Debug)]
+pub struct RedactingWriter<W> {
+    writer: W,
+    redactor: Redactor,
+}
+
+impl<W: io::Write> io::Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match std::str::from_utf8(buf) {
+            Ok(text) => self
+                .writer
+                .write_all(self.redactor.redact(text).as_bytes())?,
+            Err(_) => self.writer.write_all(buf)?,
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
 // Implement Display trait for LogLevel to enable pretty printing
 impl std::fmt::Display for LogLevel {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -103,6 +292,7 @@ static NONBLOCKING_WORK_GUARD_KEEP: OnceLock<WorkerGuard> = OnceLock::new();
 /// is enabled)
 pub fn init<H: Hooks>(config: &config::Logger) -> Result<()> {
     let mut layers: Vec<Box<dyn Layer<Registry> + Sync + Send>> = Vec::new();
+    let redactor = Redactor::new(&config.redact)?;
 
     if let Some(file_appender_config) = config.file_appender.as_ref() {
         if file_appender_config.enable {
@@ -152,22 +342,32 @@ pub fn init<H: Hooks>(config: &config::Logger) -> Result<()> {
                     .set(work_guard)
                     .map_err(|_| Error::string("cannot lock for appender"))?;
                 init_layer(
-                    non_blocking_file_appender,
+                    redactor.writer(non_blocking_file_appender),
                     &file_appender_config.format,
                     false,
                 )
             } else {
-                init_layer(file_appender, &file_appender_config.format, false)
+                init_layer(
+                    redactor.writer(file_appender),
+                    &file_appender_config.format,
+                    false,
+                )
             };
             layers.push(file_appender_layer);
         }
     }
 
     if config.enable {
-        let stdout_layer = init_layer(std::io::stdout, &config.format, true);
+        let stdout_layer = init_layer(redactor.writer(std::io::stdout), &config.format, true);
         layers.push(stdout_layer);
     }
 
+    let otel = config.otel.as_ref().filter(|otel| otel.enable);
+    #[cfg(feature = "otel")]
+    if let Some(otel) = otel {
+        layers.push(crate::otel::init_layer(otel, H::app_name())?);
+    }
+
     if !layers.is_empty() {
         let env_filter = init_env_filter::<H>(config.override_filter.as_ref(), &config.level);
         tracing_subscriber::registry()
@@ -175,6 +375,10 @@ pub fn init<H: Hooks>(config: &config::Logger) -> Result<()> {
             .with(env_filter)
             .init();
     }
+
+    if cfg!(not(feature = "otel")) && otel.is_some() {
+        tracing::warn!("`logger.otel` is enabled, but loco-rs is built without the `otel` feature");
+    }
     Ok(())
 }
 
@@ -226,3 +430,93 @@ where
             .boxed(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn redactor() -> Redactor {
+        Redactor::new(&Redaction {
+            fields: vec!["password".to_string(), "token".to_string()],
+            headers: vec!["Authorization".to_string()],
+            patterns: vec![r"\b\d{4}-\d{4}-\d{4}-\d{4}\b".to_string()],
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn can_redact_log_lines() {
+        let redactor = redactor();
+        assert_eq!(
+            redactor.redact("login password=hunter2 user=jane"),
+            "login password=*** user=jane"
+        );
+        assert_eq!(
+            redactor.redact(r#"{"fields":{"password":"hun\"ter2","user":"jane"}}"#),
+            r#"{"fields":{"password":"***","user":"jane"}}"#
+        );
+        assert_eq!(
+            redactor.redact("http.uri=/reset?token=abc123&next=/"),
+            "http.uri=/reset?token=***&next=/"
+        );
+        assert_eq!(
+            redactor.redact(r#"headers={"authorization": "Bearer xyz"}"#),
+            r#"headers={"authorization": "***"}"#
+        );
+        assert_eq!(
+            redactor.redact("\x1b[3mpassword\x1b[0m\x1b[2m=\x1b[0mhunter2 done"),
+            "\x1b[3mpassword\x1b[0m\x1b[2m=\x1b[0m*** done"
+        );
+        assert_eq!(
+            redactor.redact("card 4242-4242-4242-4242 charged"),
+            "card *** charged"
+        );
+        assert_eq!(
+            redactor.redact("database:\n  password: secret\n"),
+            "database:\n  password: ***\n"
+        );
+        assert!(matches!(
+            redactor.redact("csrf_token_id=1 nothing to hide"),
+            Cow::Borrowed(_)
+        ));
+
+        assert!(Redactor::new(&Redaction {
+            patterns: vec!["(".to_string()],
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn can_redact_written_logs() {
+        let buffer = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let make_writer = {
+            let buffer = buffer.clone();
+            move || SharedBuffer(buffer.clone())
+        };
+        let make_writer = redactor().writer(make_writer);
+        make_writer
+            .make_writer()
+            .write_all(b"token=abc user=jane\n")
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buffer.lock().unwrap().clone()).unwrap(),
+            "token=*** user=jane\n"
+        );
+    }
+
+    struct SharedBuffer(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+}
@@ -2,11 +2,18 @@
 //! either the SMTP protocol. It includes an asynchronous method `mail` for
 //! sending emails with options like sender, recipient, subject, and content.
 
+use std::{
+    borrow::Cow,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
 use lettre::{
     message::MultiPart,
     transport::smtp::{authentication::Credentials, extension::ClientId},
     AsyncTransport, Message, Tokio1Executor, Transport,
 };
+use tokio::time::Instant;
 use tracing::error;
 
 use super::{Email, Result, DEFAULT_FROM_SENDER};
@@ -27,6 +34,69 @@ pub enum EmailTransport {
 #[derive(Clone, Debug)]
 pub struct EmailSender {
     pub transport: EmailTransport,
+    /// Delivers every mail to this address instead of its recipients, for
+    /// example to keep staging mails in a test inbox
+    pub catch_all: Option<String>,
+    /// The mails sent with the test transport
+    pub sent: SentMails,
+    /// The `From` of the mails that do not set one
+    pub from: Option<String>,
+    /// Spaces out the mails sent
+    pub rate_limit: Option<RateLimit>,
+}
+
+/// Spaces out the mails of a sender to send at most a number of mails per
+/// second. Clones share the same limit.
+#[derive(Clone, Debug)]
+pub struct RateLimit {
+    interval: Duration,
+    next: Arc<tokio::sync::Mutex<Instant>>,
+}
+
+impl RateLimit {
+    #[must_use]
+    pub fn per_second(mails: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / mails.max(1),
+            next: Arc::new(tokio::sync::Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Waits for the next mail to be sent.
+    async fn wait(&self) {
+        let mut next = self.next.lock().await;
+        let now = Instant::now();
+        if *next > now {
+            tokio::time::sleep_until(*next).await;
+        }
+        *next = (*next).max(now) + self.interval;
+    }
+}
+
+/// The mails sent with the test transport, in the order they were sent.
+/// Clones share the same mails.
+#[derive(Clone, Debug, Default)]
+pub struct SentMails(Arc<Mutex<Vec<Email>>>);
+
+impl SentMails {
+    fn record(&self, email: Email) {
+        if let Ok(mut mails) = self.0.lock() {
+            mails.push(email);
+        }
+    }
+
+    /// Returns the sent mails.
+    #[must_use]
+    pub fn all(&self) -> Vec<Email> {
+        self.0.lock().map(|mails| mails.clone()).unwrap_or_default()
+    }
+
+    /// Forgets the sent mails.
+    pub fn clear(&self) {
+        if let Ok(mut mails) = self.0.lock() {
+            mails.clear();
+        }
+    }
 }
 
 #[cfg(feature = "testing")]
@@ -67,6 +137,10 @@ impl EmailSender {
 
         Ok(Self {
             transport: EmailTransport::Smtp(email_builder.build()),
+            catch_all: None,
+            sent: SentMails::default(),
+            from: None,
+            rate_limit: None,
         })
     }
 
@@ -74,9 +148,47 @@ impl EmailSender {
     pub fn stub() -> Self {
         Self {
             transport: EmailTransport::Test(lettre::transport::stub::StubTransport::new_ok()),
+            catch_all: None,
+            sent: SentMails::default(),
+            from: None,
+            rate_limit: None,
         }
     }
 
+    /// Whether the mails are recorded in memory instead of being sent.
+    #[must_use]
+    pub const fn is_stub(&self) -> bool {
+        matches!(self.transport, EmailTransport::Test(_))
+    }
+
+    /// Delivers every mail to `catch_all` instead of its recipients.
+    #[must_use]
+    pub fn with_catch_all(mut self, catch_all: Option<String>) -> Self {
+        self.catch_all = catch_all;
+        self
+    }
+
+    /// Sends the mails that do not set a `From` from `from`.
+    #[must_use]
+    pub fn with_from(mut self, from: Option<String>) -> Self {
+        self.from = from;
+        self
+    }
+
+    /// Sends at most `rate_limit` mails per second.
+    #[must_use]
+    pub fn with_rate_limit(mut self, rate_limit: Option<u32>) -> Self {
+        self.rate_limit = rate_limit.map(RateLimit::per_second);
+        self
+    }
+
+    /// Returns the mails sent with the test transport, after the catch-all
+    /// recipient was applied.
+    #[must_use]
+    pub fn sent_mails(&self) -> Vec<Email> {
+        self.sent.all()
+    }
+
     #[cfg(feature = "testing")]
     #[must_use]
     pub fn deliveries(&self) -> Deliveries {
@@ -101,12 +213,29 @@ impl EmailSender {
     /// When email doesn't send successfully or has an error to build the
     /// message
     pub async fn mail(&self, email: &Email) -> Result<()> {
+        let email = self
+            .catch_all
+            .as_ref()
+            .map_or(Cow::Borrowed(email), |catch_all| {
+                tracing::debug!(
+                    to = email.to,
+                    catch_all,
+                    "delivering mail to the catch-all recipient"
+                );
+                Cow::Owned(Email {
+                    to: catch_all.clone(),
+                    cc: None,
+                    bcc: None,
+                    ..email.clone()
+                })
+            });
         let content = MultiPart::alternative_plain_html(email.text.clone(), email.html.clone());
         let mut builder = Message::builder()
             .from(
                 email
                     .from
                     .clone()
+                    .or_else(|| self.from.clone())
                     .unwrap_or_else(|| DEFAULT_FROM_SENDER.to_string())
                     .parse()?,
             )
@@ -132,6 +261,9 @@ impl EmailSender {
                 error
             })?;
 
+        if let Some(rate_limit) = &self.rate_limit {
+            rate_limit.wait().await;
+        }
         match &self.transport {
             EmailTransport::Smtp(xp) => {
                 xp.send(msg).await?;
@@ -139,6 +271,7 @@ impl EmailSender {
             EmailTransport::Test(xp) => {
                 xp.send(&msg)
                     .map_err(|e| Error::Message(format!("sending email error: {e}")))?;
+                self.sent.record(email.into_owned());
             }
         }
         Ok(())
@@ -159,6 +292,10 @@ mod tests {
 
         let sender = EmailSender {
             transport: EmailTransport::Test(stub.clone()),
+            catch_all: None,
+            sent: SentMails::default(),
+            from: None,
+            rate_limit: None,
         };
 
         let html = r"
@@ -177,6 +314,7 @@ mod tests {
             html: html.to_string(),
             bcc: None,
             cc: None,
+            sender: None,
         };
         assert!(sender.mail(&data).await.is_ok());
 
@@ -187,4 +325,58 @@ mod tests {
             assert_debug_snapshot!(stub.messages());
         });
     }
+
+    #[tokio::test]
+    async fn can_record_mails_for_catch_all() {
+        let sender = EmailSender::stub().with_catch_all(Some("inbox@loco.rs".to_string()));
+
+        let email = Email {
+            to: "user1@framework.com".to_string(),
+            cc: Some("user2@framework.com".to_string()),
+            subject: "Email Subject".to_string(),
+            text: "Welcome".to_string(),
+            ..Default::default()
+        };
+        sender.mail(&email).await.unwrap();
+
+        let sent = sender.sent_mails();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].to, "inbox@loco.rs");
+        assert_eq!(sent[0].cc, None);
+        assert_eq!(sent[0].subject, "Email Subject");
+
+        let EmailTransport::Test(stub) = &sender.transport else {
+            panic!("expected the test transport");
+        };
+        assert_eq!(
+            stub.messages()[0].0.to(),
+            &["inbox@loco.rs".parse().unwrap()]
+        );
+
+        sender.sent.clear();
+        assert!(sender.sent_mails().is_empty());
+    }
+
+    #[tokio::test]
+    async fn applies_the_sender_from_and_rate_limit() {
+        let sender = EmailSender::stub()
+            .with_from(Some("News <news@loco.rs>".to_string()))
+            .with_rate_limit(Some(20));
+        let email = Email {
+            to: "user@loco.rs".to_string(),
+            ..Default::default()
+        };
+
+        let started = Instant::now();
+        for _ in 0..3 {
+            sender.mail(&email).await.unwrap();
+        }
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        let EmailTransport::Test(stub) = &sender.transport else {
+            panic!("expected the test transport");
+        };
+        let from = stub.messages()[0].1.clone();
+        assert!(from.contains("From: News <news@loco.rs>"), "{from}");
+    }
 }
@@ -48,6 +48,10 @@ pub struct Email {
     pub bcc: Option<String>,
     /// CC header to message
     pub cc: Option<String>,
+    /// The name of the sender to send with, from `mailer.senders`, instead of
+    /// the default one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender: Option<String>,
 }
 
 /// The options struct for configuring the email sender.
@@ -56,6 +60,9 @@ pub struct Email {
 pub struct MailerOpts {
     pub from: String,
     pub reply_to: Option<String>,
+    /// The name of the sender to send with, from `mailer.senders`, instead of
+    /// the default one
+    pub sender: Option<String>,
 }
 
 /// The `Mailer` trait defines methods for sending emails and processing email
@@ -76,8 +83,13 @@ pub trait Mailer {
         let opts = Self::opts();
         let mut email = email.clone();
 
-        email.from = Some(email.from.unwrap_or_else(|| opts.from.clone()));
+        // without a `from` of its own, the mailer sends from the `from` of
+        // its sender, if any
+        email.from = email
+            .from
+            .or_else(|| (opts.from != DEFAULT_FROM_SENDER).then(|| opts.from.clone()));
         email.reply_to = email.reply_to.or_else(|| opts.reply_to.clone());
+        email.sender = email.sender.or_else(|| opts.sender.clone());
 
         MailerWorker::perform_later(ctx, email.clone()).await?;
         Ok(())
@@ -98,6 +110,7 @@ pub trait Mailer {
                 html: content.html,
                 bcc: args.bcc.clone(),
                 cc: args.cc.clone(),
+                sender: None,
             },
         )
         .await
@@ -125,21 +138,52 @@ impl BackgroundWorker<Email> for MailerWorker {
     /// Performs the email sending operation using the provided [`AppContext`]
     /// and email details.
     async fn perform(&self, email: Email) -> crate::Result<()> {
-        if let Some(mailer) = &self.ctx.mailer {
-            let res = mailer.mail(&email).await;
-            match res {
-                Ok(res) => Ok(res),
-                Err(err) => {
-                    error!(err = err.to_string(), "mailer error");
-                    Err(err)
-                }
-            }
-        } else {
-            let err = crate::Error::Message(
-                "attempting to send email but no email sender configured".to_string(),
-            );
+        let mailer = email
+            .sender
+            .as_ref()
+            .map_or_else(|| self.ctx.mailer(), |name| self.ctx.mailer_named(name));
+        let res = match mailer {
+            Ok(mailer) => mailer.mail(&email).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = &res {
             error!(err = err.to_string(), "mailer error");
-            Err(err)
         }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, sync::Arc};
+
+    use super::*;
+    use crate::tests_cfg;
+
+    #[tokio::test]
+    async fn sends_with_the_named_sender() {
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        let marketing = EmailSender::stub();
+        ctx.mailer = Some(EmailSender::stub());
+        ctx.mailers = Arc::new(BTreeMap::from([("marketing".to_string(), marketing)]));
+        let worker = MailerWorker::build(&ctx);
+
+        let email = Email {
+            to: "user@loco.rs".to_string(),
+            sender: Some("marketing".to_string()),
+            ..Default::default()
+        };
+        worker.perform(email.clone()).await.unwrap();
+        assert_eq!(ctx.mailer_named("marketing").unwrap().sent_mails().len(), 1);
+        assert!(ctx.mailer().unwrap().sent_mails().is_empty());
+
+        let unknown = Email {
+            sender: Some("unknown".to_string()),
+            ..email
+        };
+        assert!(matches!(
+            worker.perform(unknown).await,
+            Err(crate::Error::MailerSenderNotConfigured(_))
+        ));
     }
 }
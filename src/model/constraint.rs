@@ -0,0 +1,278 @@
+//! # Constraint Violations
+//!
+//! Detects the database errors caused by a client's input (unique, foreign
+//! key and not-null constraint violations) so that they are answered with a
+//! meaningful client error instead of a `500`:
+//!
+//! * unique violations respond with `409 Conflict`,
+//! * foreign key and not-null violations respond with
+//!   `422 Unprocessable Entity`.
+//!
+//! The offending field is derived from the database error where possible
+//! (postgres, sqlite and mysql), and reported in the same shape as validation
+//! errors:
+//!
+//! ```json
+//! {
+//!   "error": "conflict",
+//!   "description": "email already exists",
+//!   "errors": { "email": [{ "code": "unique", "message": "already exists" }] }
+//! }
+//! ```
+use axum::http::StatusCode;
+use sea_orm::{
+    sqlx::{self, error::ErrorKind, postgres::PgDatabaseError},
+    DbErr, RuntimeErr,
+};
+use serde_json::json;
+
+use crate::controller::ErrorDetail;
+
+/// The kind of a violated constraint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstraintKind {
+    Unique,
+    ForeignKey,
+    NotNull,
+}
+
+/// A constraint violation reported by the database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstraintViolation {
+    pub kind: ConstraintKind,
+    /// The name of the violated constraint, when reported by the database.
+    pub constraint: Option<String>,
+    /// The column the violation is about, when it can be derived.
+    pub field: Option<String>,
+}
+
+impl ConstraintViolation {
+    /// Returns the constraint violation behind a database error, if any.
+    #[must_use]
+    pub fn from_db_err(err: &DbErr) -> Option<Self> {
+        let (DbErr::Exec(RuntimeErr::SqlxError(sqlx::Error::Database(err)))
+        | DbErr::Query(RuntimeErr::SqlxError(sqlx::Error::Database(err)))) = err
+        else {
+            return None;
+        };
+
+        let kind = match err.kind() {
+            ErrorKind::UniqueViolation => ConstraintKind::Unique,
+            ErrorKind::ForeignKeyViolation => ConstraintKind::ForeignKey,
+            ErrorKind::NotNullViolation => ConstraintKind::NotNull,
+            _ => return None,
+        };
+        let constraint = err.constraint().map(ToString::to_string);
+        let pg_field = err.try_downcast_ref::<PgDatabaseError>().and_then(|err| {
+            err.column()
+                .map(ToString::to_string)
+                .or_else(|| err.detail().and_then(field_from_pg_detail))
+        });
+        let field = pg_field
+            .or_else(|| field_from_message(err.message()))
+            .or_else(|| constraint.as_deref().and_then(field_from_constraint));
+
+        Some(Self {
+            kind,
+            constraint,
+            field,
+        })
+    }
+
+    /// The response status for this violation.
+    #[must_use]
+    pub const fn status(&self) -> StatusCode {
+        match self.kind {
+            ConstraintKind::Unique => StatusCode::CONFLICT,
+            ConstraintKind::ForeignKey | ConstraintKind::NotNull => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+        }
+    }
+
+    /// The response body for this violation.
+    #[must_use]
+    pub fn detail(&self) -> ErrorDetail {
+        let (error, code, message) = match self.kind {
+            ConstraintKind::Unique => ("conflict", "unique", "already exists"),
+            ConstraintKind::ForeignKey => (
+                "unprocessable_entity",
+                "foreign_key",
+                "violates a foreign key constraint",
+            ),
+            ConstraintKind::NotNull => ("unprocessable_entity", "not_null", "is required"),
+        };
+        self.field.as_ref().map_or_else(
+            || ErrorDetail::new(error.to_string(), format!("a value {message}")),
+            |field| ErrorDetail {
+                error: Some(error.to_string()),
+                description: Some(format!("{field} {message}")),
+                errors: Some(json!({ field: [{ "code": code, "message": message }] })),
+            },
+        )
+    }
+}
+
+/// Postgres details look like `Key (email)=(user@loco.rs) already exists.`
+fn field_from_pg_detail(detail: &str) -> Option<String> {
+    let columns = detail.strip_prefix("Key (")?.split_once(")=")?.0;
+    first_column(columns)
+}
+
+/// Finds the column in the message of a sqlite or mysql error:
+///
+/// * `UNIQUE constraint failed: users.email` (sqlite)
+/// * `NOT NULL constraint failed: users.email` (sqlite)
+/// * `Column 'email' cannot be null` (mysql)
+/// * ``... FOREIGN KEY (`user_id`) REFERENCES ...`` (mysql)
+/// * `Duplicate entry 'x' for key 'users.idx-users-email'` (mysql)
+fn field_from_message(message: &str) -> Option<String> {
+    if let Some((_, columns)) = message.split_once(" constraint failed: ") {
+        return first_column(columns);
+    }
+    if let Some(rest) = message.strip_prefix("Column '") {
+        return rest.split_once('\'').map(|(column, _)| column.to_string());
+    }
+    if let Some((_, rest)) = message.split_once("FOREIGN KEY (`") {
+        return rest.split_once('`').map(|(column, _)| column.to_string());
+    }
+    if let Some((_, rest)) = message.split_once(" for key '") {
+        return rest
+            .split_once('\'')
+            .and_then(|(key, _)| field_from_constraint(key));
+    }
+    None
+}
+
+/// Derives the column from the constraint names generated by Loco
+/// migrations: `idx-users-email` and `fk-notes-user_id-to-users`.
+fn field_from_constraint(constraint: &str) -> Option<String> {
+    let name = constraint.rsplit('.').next().unwrap_or(constraint);
+    let mut parts = name.split('-');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some("idx" | "fk"), Some(_table), Some(column)) => Some(column.to_string()),
+        _ => None,
+    }
+}
+
+/// Returns the first column of `table.column, table.other` like lists.
+fn first_column(columns: &str) -> Option<String> {
+    let column = columns.split(',').next()?.trim();
+    let column = column.rsplit('.').next().unwrap_or(column);
+    (!column.is_empty()).then(|| column.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{ConnectOptions, ConnectionTrait, Database, DatabaseConnection};
+
+    use super::*;
+
+    async fn connect() -> DatabaseConnection {
+        let mut options = ConnectOptions::new("sqlite::memory:");
+        options.max_connections(1);
+        let db = Database::connect(options).await.unwrap();
+        for statement in [
+            "PRAGMA foreign_keys = ON",
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT NOT NULL UNIQUE)",
+            "CREATE TABLE notes (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users(id))",
+            "INSERT INTO users (id, email) VALUES (1, 'user@loco.rs')",
+        ] {
+            db.execute_unprepared(statement).await.unwrap();
+        }
+        db
+    }
+
+    async fn violation(db: &DatabaseConnection, statement: &str) -> ConstraintViolation {
+        let err = db.execute_unprepared(statement).await.unwrap_err();
+        ConstraintViolation::from_db_err(&err).unwrap()
+    }
+
+    #[tokio::test]
+    async fn can_detect_sqlite_violations() {
+        let db = connect().await;
+
+        let unique = violation(
+            &db,
+            "INSERT INTO users (id, email) VALUES (2, 'user@loco.rs')",
+        )
+        .await;
+        assert_eq!(unique.kind, ConstraintKind::Unique);
+        assert_eq!(unique.field.as_deref(), Some("email"));
+        assert_eq!(unique.status(), StatusCode::CONFLICT);
+
+        let not_null = violation(&db, "INSERT INTO users (id) VALUES (3)").await;
+        assert_eq!(not_null.kind, ConstraintKind::NotNull);
+        assert_eq!(not_null.field.as_deref(), Some("email"));
+        assert_eq!(not_null.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let foreign_key = violation(&db, "INSERT INTO notes (user_id) VALUES (42)").await;
+        assert_eq!(foreign_key.kind, ConstraintKind::ForeignKey);
+        assert_eq!(foreign_key.field, None);
+
+        let err = db
+            .execute_unprepared("SELECT * FROM missing")
+            .await
+            .unwrap_err();
+        assert_eq!(ConstraintViolation::from_db_err(&err), None);
+    }
+
+    #[tokio::test]
+    async fn responds_with_client_error() {
+        use axum::response::IntoResponse;
+
+        let db = connect().await;
+        let err = db
+            .execute_unprepared("INSERT INTO users (id, email) VALUES (2, 'user@loco.rs')")
+            .await
+            .unwrap_err();
+        let response = crate::Error::from(crate::model::ModelError::DbErr(err)).into_response();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[test]
+    fn can_derive_field() {
+        assert_eq!(
+            field_from_pg_detail("Key (email)=(user@loco.rs) already exists."),
+            Some("email".to_string())
+        );
+        assert_eq!(
+            field_from_message("Column 'email' cannot be null"),
+            Some("email".to_string())
+        );
+        assert_eq!(
+            field_from_message(
+                "Cannot add or update a child row: a foreign key constraint fails (`app`.`notes`, \
+                 CONSTRAINT `fk-notes-user_id-to-users` FOREIGN KEY (`user_id`) REFERENCES \
+                 `users` (`id`))"
+            ),
+            Some("user_id".to_string())
+        );
+        assert_eq!(
+            field_from_message("Duplicate entry 'a' for key 'users.idx-users-email'"),
+            Some("email".to_string())
+        );
+        assert_eq!(
+            field_from_constraint("fk-notes-user_id-to-users"),
+            Some("user_id".to_string())
+        );
+        assert_eq!(field_from_constraint("users_email_key"), None);
+    }
+
+    #[test]
+    fn can_describe_violation() {
+        let violation = ConstraintViolation {
+            kind: ConstraintKind::Unique,
+            constraint: None,
+            field: Some("email".to_string()),
+        };
+        assert_eq!(
+            serde_json::to_value(violation.detail()).unwrap(),
+            json!({
+                "error": "conflict",
+                "description": "email already exists",
+                "errors": {"email": [{"code": "unique", "message": "already exists"}]},
+            })
+        );
+    }
+}
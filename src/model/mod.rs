@@ -2,7 +2,11 @@
 //!
 //! Useful when using `sea_orm` and want to propagate errors
 
+pub mod constraint;
 pub mod query;
+pub mod soft_delete;
+pub mod transaction;
+pub mod versioned;
 use async_trait::async_trait;
 use sea_orm::DatabaseConnection;
 
@@ -17,6 +21,9 @@ pub enum ModelError {
     #[error("Entity not found")]
     EntityNotFound,
 
+    #[error("Entity was updated since it was read")]
+    StaleVersion,
+
     #[error(transparent)]
     Validation(#[from] ModelValidationErrors),
 
@@ -0,0 +1,243 @@
+//! # Soft Deletes
+//!
+//! Opt-in, per model, soft deletes: the rows of a [`SoftDelete`] entity are
+//! marked deleted with a nullable `deleted_at` timestamp column instead of
+//! being removed, so they can be restored.
+//!
+//! ```rust,ignore
+//! impl SoftDelete for users::Entity {
+//!     fn deleted_at() -> Self::Column {
+//!         users::Column::DeletedAt
+//!     }
+//! }
+//!
+//! // the users not deleted
+//! let users = users::Entity::find_kept().all(&ctx.db).await?;
+//!
+//! user.into_active_model().soft_delete(&ctx.db).await?;
+//! let deleted = users::Entity::find_trashed().all(&ctx.db).await?;
+//! ```
+//!
+//! sea-orm has no default scopes, so `Entity::find()` still returns the
+//! deleted rows: query soft-deletable entities with [`SoftDelete::find_kept`]
+//! and [`SoftDelete::find_kept_by_id`], and with [`SoftDelete::with_trashed`]
+//! to explicitly include the deleted rows.
+use async_trait::async_trait;
+use chrono::Utc;
+use sea_orm::{
+    prelude::Expr,
+    sea_query::{Keyword, SimpleExpr},
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, EntityTrait, Iterable,
+    PrimaryKeyToColumn, QueryFilter, Select, UpdateMany,
+};
+
+use super::{ModelError, ModelResult};
+
+/// An entity whose rows are soft-deleted, see the [module
+/// documentation](self).
+pub trait SoftDelete: EntityTrait {
+    /// The nullable timestamp column set when a row is deleted.
+    fn deleted_at() -> Self::Column;
+
+    /// Selects the rows not deleted.
+    #[must_use]
+    fn find_kept() -> Select<Self> {
+        Self::find().filter(Self::deleted_at().is_null())
+    }
+
+    /// Selects the row of the primary key `id`, unless deleted.
+    #[must_use]
+    fn find_kept_by_id<T>(id: T) -> Select<Self>
+    where
+        T: Into<<Self::PrimaryKey as sea_orm::PrimaryKeyTrait>::ValueType>,
+    {
+        Self::find_by_id(id).filter(Self::deleted_at().is_null())
+    }
+
+    /// Selects the deleted rows.
+    #[must_use]
+    fn find_trashed() -> Select<Self> {
+        Self::find().filter(Self::deleted_at().is_not_null())
+    }
+
+    /// Selects all the rows, deleted or not.
+    #[must_use]
+    fn with_trashed() -> Select<Self> {
+        Self::find()
+    }
+
+    /// Deletes the rows not deleted yet, matching the filters added to the
+    /// returned update.
+    #[must_use]
+    fn soft_delete_many() -> UpdateMany<Self> {
+        Self::update_many()
+            .col_expr(Self::deleted_at(), Expr::value(Utc::now()))
+            .filter(Self::deleted_at().is_null())
+    }
+
+    /// Restores the deleted rows matching the filters added to the returned
+    /// update.
+    #[must_use]
+    fn restore_many() -> UpdateMany<Self> {
+        Self::update_many()
+            .col_expr(Self::deleted_at(), SimpleExpr::Keyword(Keyword::Null))
+            .filter(Self::deleted_at().is_not_null())
+    }
+}
+
+/// Soft-deletes and restores a row of a [`SoftDelete`] entity.
+#[async_trait]
+pub trait SoftDeleteModel: ActiveModelTrait {
+    /// Marks the row deleted.
+    ///
+    /// # Errors
+    ///
+    /// [`ModelError::EntityNotFound`] when the row does not exist or is
+    /// already deleted.
+    async fn soft_delete<C: ConnectionTrait>(&self, db: &C) -> ModelResult<()>;
+
+    /// Restores the deleted row.
+    ///
+    /// # Errors
+    ///
+    /// [`ModelError::EntityNotFound`] when the row does not exist or is not
+    /// deleted.
+    async fn restore<C: ConnectionTrait>(&self, db: &C) -> ModelResult<()>;
+}
+
+/// The condition matching the primary key of `model`.
+pub(super) fn primary_key<A: ActiveModelTrait>(model: &A) -> ModelResult<Condition> {
+    let mut condition = Condition::all();
+    for key in <A::Entity as EntityTrait>::PrimaryKey::iter() {
+        let column = key.into_column();
+        let value = model
+            .get(column)
+            .into_value()
+            .ok_or_else(|| ModelError::msg("the primary key of the model is not set"))?;
+        condition = condition.add(column.eq(value));
+    }
+    Ok(condition)
+}
+
+#[async_trait]
+impl<A> SoftDeleteModel for A
+where
+    A: ActiveModelTrait + Sync,
+    A::Entity: SoftDelete,
+{
+    async fn soft_delete<C: ConnectionTrait>(&self, db: &C) -> ModelResult<()> {
+        let result = A::Entity::soft_delete_many()
+            .filter(primary_key(self)?)
+            .exec(db)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(ModelError::EntityNotFound);
+        }
+        Ok(())
+    }
+
+    async fn restore<C: ConnectionTrait>(&self, db: &C) -> ModelResult<()> {
+        let result = A::Entity::restore_many()
+            .filter(primary_key(self)?)
+            .exec(db)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(ModelError::EntityNotFound);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{entity::prelude::*, IntoActiveModel, Set};
+
+    use super::*;
+
+    mod notes {
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+        #[sea_orm(table_name = "notes")]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub title: String,
+            pub deleted_at: Option<DateTimeWithTimeZone>,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    impl SoftDelete for notes::Entity {
+        fn deleted_at() -> Self::Column {
+            notes::Column::DeletedAt
+        }
+    }
+
+    async fn titles(select: Select<notes::Entity>, db: &DatabaseConnection) -> Vec<String> {
+        let mut titles = select
+            .all(db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|note| note.title)
+            .collect::<Vec<_>>();
+        titles.sort();
+        titles
+    }
+
+    #[tokio::test]
+    async fn can_soft_delete_and_restore() {
+        let db = crate::tests_cfg::db::dummy_connection().await;
+        db.execute_unprepared(
+            "CREATE TABLE notes (id INTEGER PRIMARY KEY, title TEXT NOT NULL, deleted_at TEXT)",
+        )
+        .await
+        .unwrap();
+        for title in ["a", "b", "c"] {
+            notes::ActiveModel {
+                title: Set(title.to_string()),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+
+        let note = notes::Entity::find_kept_by_id(1).one(&db).await.unwrap();
+        let note = note.unwrap().into_active_model();
+        note.soft_delete(&db).await.unwrap();
+        assert!(matches!(
+            note.soft_delete(&db).await,
+            Err(ModelError::EntityNotFound)
+        ));
+        assert!(notes::Entity::find_kept_by_id(1)
+            .one(&db)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(titles(notes::Entity::find_kept(), &db).await, ["b", "c"]);
+        assert_eq!(titles(notes::Entity::find_trashed(), &db).await, ["a"]);
+        assert_eq!(titles(notes::Entity::with_trashed(), &db).await.len(), 3);
+        let deleted = notes::Entity::find_by_id(1).one(&db).await.unwrap();
+        assert!(deleted.unwrap().deleted_at.is_some());
+
+        note.restore(&db).await.unwrap();
+        assert!(matches!(
+            note.restore(&db).await,
+            Err(ModelError::EntityNotFound)
+        ));
+        notes::Entity::soft_delete_many()
+            .filter(notes::Column::Title.ne("a"))
+            .exec(&db)
+            .await
+            .unwrap();
+        assert_eq!(titles(notes::Entity::find_kept(), &db).await, ["a"]);
+        notes::Entity::restore_many().exec(&db).await.unwrap();
+        assert_eq!(titles(notes::Entity::find_kept(), &db).await.len(), 3);
+    }
+}
@@ -0,0 +1,282 @@
+//! # Transactions
+//!
+//! Runs a closure in a transaction with a chosen isolation level, and runs it
+//! again when the database aborts the transaction on a serialization failure
+//! or a deadlock, which stricter isolation levels cause under contention:
+//!
+//! ```rust,ignore
+//! use loco_rs::model::transaction::{self, IsolationLevel, TransactionOptions};
+//!
+//! let options = TransactionOptions::new()
+//!     .isolation_level(IsolationLevel::Serializable)
+//!     .retries(3);
+//! let order = transaction::run(&ctx.db, &options, |txn| {
+//!     let params = params.clone();
+//!     Box::pin(async move {
+//!         let stock = stocks::Entity::find_by_id(params.item).one(txn).await?;
+//!         // ...
+//!         Ok(order)
+//!     })
+//! })
+//! .await?;
+//! ```
+//!
+//! The closure may run several times, so it must not have side effects
+//! outside of the transaction, such as sending an email or enqueueing a job.
+//!
+//! Database behavior differs:
+//!
+//! * Postgres aborts conflicting `REPEATABLE READ` and `SERIALIZABLE`
+//!   transactions with `40001`, and deadlocks with `40P01`, both retried.
+//! * MySQL reports deadlocks with `40001`, retried. Its default isolation
+//!   level is `REPEATABLE READ`.
+//! * SQLite transactions are always serializable and the isolation level is
+//!   ignored. A database locked by another connection is retried.
+use std::{future::Future, pin::Pin, time::Duration};
+
+use sea_orm::{sqlx, DatabaseConnection, DatabaseTransaction, DbErr, RuntimeErr, TransactionTrait};
+pub use sea_orm::{AccessMode, IsolationLevel};
+
+use super::ModelError;
+
+/// The SQLSTATE codes of the errors worth running a transaction again for.
+///
+/// * `40001`: serialization failure (postgres), deadlock (mysql)
+/// * `40P01`: deadlock detected (postgres)
+/// * `5` and `6`: `SQLITE_BUSY` and `SQLITE_LOCKED`
+const RETRYABLE_CODES: &[&str] = &["40001", "40P01", "5", "6"];
+
+/// The delay before the first retry, doubled for each of the next ones.
+const RETRY_DELAY: Duration = Duration::from_millis(10);
+
+/// The options of a transaction run with [`run`].
+#[derive(Debug, Clone, Default)]
+pub struct TransactionOptions {
+    pub isolation_level: Option<IsolationLevel>,
+    pub access_mode: Option<AccessMode>,
+    /// The number of times the transaction is run again after a retryable
+    /// failure.
+    pub retries: u32,
+}
+
+impl TransactionOptions {
+    /// A transaction with the default isolation level of the database, not
+    /// retried.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub const fn isolation_level(mut self, isolation_level: IsolationLevel) -> Self {
+        self.isolation_level = Some(isolation_level);
+        self
+    }
+
+    #[must_use]
+    pub const fn access_mode(mut self, access_mode: AccessMode) -> Self {
+        self.access_mode = Some(access_mode);
+        self
+    }
+
+    #[must_use]
+    pub const fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+}
+
+/// An error which may carry a database error, to tell whether the
+/// transaction failing with it is worth running again.
+pub trait RetryableError: From<DbErr> {
+    /// Whether the error is a serialization failure or a deadlock.
+    fn is_retryable(&self) -> bool;
+}
+
+impl RetryableError for DbErr {
+    fn is_retryable(&self) -> bool {
+        let (Self::Exec(RuntimeErr::SqlxError(sqlx::Error::Database(err)))
+        | Self::Query(RuntimeErr::SqlxError(sqlx::Error::Database(err)))
+        | Self::Conn(RuntimeErr::SqlxError(sqlx::Error::Database(err)))) = self
+        else {
+            return false;
+        };
+        err.code()
+            .is_some_and(|code| RETRYABLE_CODES.contains(&code.as_ref()))
+    }
+}
+
+impl RetryableError for ModelError {
+    fn is_retryable(&self) -> bool {
+        matches!(self, Self::DbErr(err) if err.is_retryable())
+    }
+}
+
+impl RetryableError for crate::Error {
+    fn is_retryable(&self) -> bool {
+        match self {
+            Self::DB(err) => err.is_retryable(),
+            Self::Model(err) => err.is_retryable(),
+            _ => false,
+        }
+    }
+}
+
+/// Runs `f` in a transaction with `options`, and commits it when `f`
+/// succeeds. When `f` or the commit fails with a serialization failure or a
+/// deadlock, the transaction is rolled back and run again, up to
+/// `options.retries` times, after a growing delay.
+///
+/// # Errors
+///
+/// The error of `f` or of the transaction, once the retries are exhausted
+pub async fn run<T, E, F>(
+    db: &DatabaseConnection,
+    options: &TransactionOptions,
+    mut f: F,
+) -> Result<T, E>
+where
+    F: for<'c> FnMut(
+            &'c DatabaseTransaction,
+        ) -> Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'c>>
+        + Send,
+    T: Send,
+    E: RetryableError + std::fmt::Display + Send,
+{
+    let mut attempt = 0;
+    loop {
+        match run_once(db, options, &mut f).await {
+            Err(err) if attempt < options.retries && err.is_retryable() => {
+                attempt += 1;
+                tracing::debug!(attempt, error = %err, "retrying transaction");
+                tokio::time::sleep(RETRY_DELAY.saturating_mul(2u32.saturating_pow(attempt - 1)))
+                    .await;
+            }
+            result => return result,
+        }
+    }
+}
+
+async fn run_once<T, E, F>(
+    db: &DatabaseConnection,
+    options: &TransactionOptions,
+    f: &mut F,
+) -> Result<T, E>
+where
+    F: for<'c> FnMut(
+            &'c DatabaseTransaction,
+        ) -> Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'c>>
+        + Send,
+    E: From<DbErr>,
+{
+    let txn = db
+        .begin_with_config(options.isolation_level, options.access_mode)
+        .await?;
+    // dropping the transaction rolls it back
+    let value = f(&txn).await?;
+    txn.commit().await?;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    use sea_orm::{ConnectOptions, ConnectionTrait, Database};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn can_retry_serialization_failures() {
+        let mut connect_options = ConnectOptions::new("sqlite::memory:");
+        connect_options.max_connections(1);
+        let db = Database::connect(connect_options).await.unwrap();
+        db.execute_unprepared("CREATE TABLE counters (value INTEGER NOT NULL)")
+            .await
+            .unwrap();
+        let runs = Arc::new(AtomicU32::new(0));
+
+        let options = TransactionOptions::new()
+            .isolation_level(IsolationLevel::Serializable)
+            .retries(2);
+        let value = run(&db, &options, |txn| {
+            let runs = runs.clone();
+            Box::pin(async move {
+                txn.execute_unprepared("INSERT INTO counters (value) VALUES (1)")
+                    .await?;
+                if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+                    // as reported by postgres
+                    return Err(DbErr::Query(RuntimeErr::SqlxError(sqlx::Error::Database(
+                        Box::new(SerializationFailure),
+                    ))));
+                }
+                Ok(42)
+            })
+        })
+        .await
+        .unwrap();
+        assert_eq!(value, 42);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+        // the failed run was rolled back
+        let count = db
+            .query_one(sea_orm::Statement::from_string(
+                db.get_database_backend(),
+                "SELECT COUNT(*) AS count FROM counters",
+            ))
+            .await
+            .unwrap()
+            .unwrap()
+            .try_get::<i64>("", "count")
+            .unwrap();
+        assert_eq!(count, 1);
+
+        // other errors are not retried
+        let err = run(&db, &options.retries(5), |txn| {
+            Box::pin(async move { txn.execute_unprepared("SELECT * FROM missing").await })
+        })
+        .await
+        .unwrap_err();
+        assert!(!err.is_retryable());
+    }
+
+    #[derive(Debug)]
+    struct SerializationFailure;
+
+    impl std::fmt::Display for SerializationFailure {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("could not serialize access due to concurrent update")
+        }
+    }
+
+    impl std::error::Error for SerializationFailure {}
+
+    impl sqlx::error::DatabaseError for SerializationFailure {
+        fn message(&self) -> &str {
+            "could not serialize access due to concurrent update"
+        }
+
+        fn code(&self) -> Option<std::borrow::Cow<'_, str>> {
+            Some("40001".into())
+        }
+
+        fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+            self
+        }
+
+        fn kind(&self) -> sqlx::error::ErrorKind {
+            sqlx::error::ErrorKind::Other
+        }
+    }
+}
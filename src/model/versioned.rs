@@ -0,0 +1,158 @@
+//! # Versioned Models
+//!
+//! Optimistic locking, per model: the rows of a [`Versioned`] entity carry an
+//! integer version column, bumped by every update. An update made from a
+//! stale read, after another update of the row, fails with
+//! [`ModelError::StaleVersion`] rather than overwriting it, and responds
+//! with `412 Precondition Failed`:
+//!
+//! ```rust,ignore
+//! impl Versioned for notes::Entity {
+//!     fn version() -> Self::Column {
+//!         notes::Column::Version
+//!     }
+//! }
+//!
+//! let version = note.version;
+//! let mut note = note.into_active_model();
+//! note.title = Set("loco".to_string());
+//! let note = note.update_versioned(&ctx.db, version).await?;
+//! assert_eq!(note.version, version + 1);
+//! ```
+//!
+//! Together with [`crate::controller::precondition`], clients updating a
+//! resource send the version they read with `If-Match`.
+use async_trait::async_trait;
+use sea_orm::{
+    prelude::Expr, ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter,
+};
+
+use super::{soft_delete::primary_key, ModelError, ModelResult};
+
+/// An entity whose rows carry a version, see the [module
+/// documentation](self).
+pub trait Versioned: EntityTrait {
+    /// The integer column holding the version of a row.
+    fn version() -> Self::Column;
+}
+
+/// Updates a row of a [`Versioned`] entity.
+#[async_trait]
+pub trait VersionedModel: ActiveModelTrait {
+    /// Saves the changes of the model and bumps the version of its row, when
+    /// the row is still at `version`, and returns the updated row.
+    ///
+    /// # Errors
+    ///
+    /// [`ModelError::StaleVersion`] when the row was updated since `version`,
+    /// [`ModelError::EntityNotFound`] when it does not exist
+    async fn update_versioned<C: ConnectionTrait>(
+        self,
+        db: &C,
+        version: i64,
+    ) -> ModelResult<<Self::Entity as EntityTrait>::Model>;
+}
+
+#[async_trait]
+impl<A> VersionedModel for A
+where
+    A: ActiveModelTrait + Send + Sync,
+    A::Entity: Versioned,
+{
+    async fn update_versioned<C: ConnectionTrait>(
+        mut self,
+        db: &C,
+        version: i64,
+    ) -> ModelResult<<Self::Entity as EntityTrait>::Model> {
+        let column = A::Entity::version();
+        let row = primary_key(&self)?;
+        // the version is only ever set by the update
+        self.not_set(column);
+        let result = A::Entity::update_many()
+            .set(self)
+            .col_expr(column, Expr::col(column).add(1))
+            .filter(row.clone())
+            .filter(column.eq(version))
+            .exec(db)
+            .await?;
+        let current = A::Entity::find().filter(row).one(db).await?;
+        match current {
+            Some(current) if result.rows_affected > 0 => Ok(current),
+            Some(_) => Err(ModelError::StaleVersion),
+            None => Err(ModelError::EntityNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{entity::prelude::*, IntoActiveModel, Set};
+
+    use super::*;
+
+    mod notes {
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+        #[sea_orm(table_name = "notes")]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub title: String,
+            pub version: i64,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    impl Versioned for notes::Entity {
+        fn version() -> Self::Column {
+            notes::Column::Version
+        }
+    }
+
+    #[tokio::test]
+    async fn can_update_versioned_rows() {
+        let db = crate::tests_cfg::db::dummy_connection().await;
+        db.execute_unprepared(
+            "CREATE TABLE notes (id INTEGER PRIMARY KEY, title TEXT NOT NULL, version INTEGER \
+             NOT NULL)",
+        )
+        .await
+        .unwrap();
+        let note = notes::ActiveModel {
+            title: Set("draft".to_string()),
+            version: Set(1),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        let mut mine = note.clone().into_active_model();
+        mine.title = Set("mine".to_string());
+        let updated = mine.update_versioned(&db, note.version).await.unwrap();
+        assert_eq!(updated.title, "mine");
+        assert_eq!(updated.version, 2);
+
+        // a concurrent edit, made from the same read
+        let mut theirs = note.clone().into_active_model();
+        theirs.title = Set("theirs".to_string());
+        assert!(matches!(
+            theirs.update_versioned(&db, note.version).await,
+            Err(ModelError::StaleVersion)
+        ));
+        let current = notes::Entity::find_by_id(note.id).one(&db).await.unwrap();
+        assert_eq!(current.unwrap().title, "mine");
+
+        let mut missing = note.into_active_model();
+        missing.id = Set(42);
+        assert!(matches!(
+            missing.update_versioned(&db, 1).await,
+            Err(ModelError::EntityNotFound)
+        ));
+    }
+}
@@ -0,0 +1,207 @@
+//! # OpenTelemetry
+//!
+//! Exports the application spans (HTTP requests, background jobs, and anything
+//! instrumented with `tracing`) to an OpenTelemetry collector over OTLP/HTTP,
+//! next to the regular Loco logs. Enable it with the `otel` feature and the
+//! `logger.otel` configuration section.
+//!
+//! Trace context is propagated with the W3C `traceparent` header:
+//! * incoming requests continue the trace of their caller,
+//! * [`inject_context`] adds the current context to outgoing requests,
+//! * jobs enqueued while handling a request are performed in the same trace.
+use std::{collections::HashMap, sync::OnceLock};
+
+use axum::http::HeaderMap;
+use opentelemetry::{global, trace::TracerProvider as _};
+use opentelemetry_http::{HeaderExtractor, HeaderInjector};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider, Resource};
+use serde_json::Value;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{Layer, Registry};
+
+use crate::{config, Error, Result};
+
+/// The job data key carrying the trace context of the code that enqueued the
+/// job.
+const JOB_CONTEXT_KEY: &str = "_loco_trace_context";
+
+static TRACER_PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+/// Creates the tracing layer exporting spans to the configured collector, and
+/// installs the W3C trace context propagator.
+pub(crate) fn init_layer(
+    config: &config::Otel,
+    app_name: &str,
+) -> Result<Box<dyn Layer<Registry> + Sync + Send>> {
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(&config.endpoint)
+        .build()
+        .map_err(Error::msg)?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(
+                    config
+                        .service_name
+                        .clone()
+                        .unwrap_or_else(|| app_name.to_string()),
+                )
+                .build(),
+        )
+        .build();
+    let tracer = provider.tracer("loco");
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    global::set_tracer_provider(provider.clone());
+    TRACER_PROVIDER
+        .set(provider)
+        .map_err(|_| Error::string("opentelemetry is already initialized"))?;
+
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer).boxed())
+}
+
+/// Flushes the spans that were not exported yet. Called when the app stops.
+pub fn shutdown() {
+    if let Some(provider) = TRACER_PROVIDER.get() {
+        if let Err(err) = provider.shutdown() {
+            tracing::warn!(err = %err, "could not flush opentelemetry spans");
+        }
+    }
+}
+
+/// Adds the trace context of the current span to the headers of an outgoing
+/// request, so the called service continues the trace.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let mut headers = HeaderMap::new();
+/// loco_rs::otel::inject_context(&mut headers);
+/// let response = client.get(url).headers(headers).send().await?;
+/// ```
+pub fn inject_context(headers: &mut HeaderMap) {
+    let context = Span::current().context();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut HeaderInjector(headers));
+    });
+}
+
+/// Returns the W3C `traceparent` of the current span, if it is sampled.
+pub(crate) fn current_traceparent() -> Option<String> {
+    let context = Span::current().context();
+    let mut carrier = HashMap::new();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut carrier);
+    });
+    carrier.remove("traceparent")
+}
+
+/// Makes `span` a child of the trace context carried by the request headers,
+/// if any.
+pub(crate) fn set_parent_from_headers(span: &Span, headers: &HeaderMap) {
+    let context =
+        global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)));
+    // an error only means the span is disabled
+    let _ = span.set_parent(context);
+}
+
+/// Adds the current trace context to the data of a job being enqueued. Only
+/// object arguments can carry it.
+pub(crate) fn inject_job_context(data: &mut Value) {
+    let Value::Object(data) = data else {
+        return;
+    };
+    let context = Span::current().context();
+    let mut carrier = HashMap::new();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut carrier);
+    });
+    if !carrier.is_empty() {
+        data.insert(
+            JOB_CONTEXT_KEY.to_string(),
+            serde_json::to_value(carrier).unwrap_or_default(),
+        );
+    }
+}
+
+/// Removes the trace context from the data of a job, and returns the span to
+/// perform the job in, continuing the trace of the code that enqueued it.
+pub(crate) fn job_span(job_id: &str, mut data: Value) -> (Value, Span) {
+    let span = tracing::info_span!("job", job_id);
+    let carrier = data
+        .as_object_mut()
+        .and_then(|data| data.remove(JOB_CONTEXT_KEY))
+        .and_then(|carrier| serde_json::from_value::<HashMap<String, String>>(carrier).ok());
+    if let Some(carrier) = carrier {
+        let context = global::get_text_map_propagator(|propagator| propagator.extract(&carrier));
+        let _ = span.set_parent(context);
+    }
+    (data, span)
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::trace::TraceContextExt;
+    use serde_json::json;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    fn with_tracing(f: impl FnOnce()) {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = SdkTracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        tracing::subscriber::with_default(subscriber, f);
+    }
+
+    fn trace_id(span: &Span) -> String {
+        span.context().span().span_context().trace_id().to_string()
+    }
+
+    #[test]
+    fn can_continue_request_trace() {
+        with_tracing(|| {
+            let mut headers = HeaderMap::new();
+            headers.insert("traceparent", TRACEPARENT.parse().unwrap());
+            let span = tracing::info_span!("http-request");
+            set_parent_from_headers(&span, &headers);
+            assert_eq!(trace_id(&span), "4bf92f3577b34da6a3ce929d0e0e4736");
+
+            let _guard = span.enter();
+            let mut outgoing = HeaderMap::new();
+            inject_context(&mut outgoing);
+            let traceparent = outgoing.get("traceparent").unwrap().to_str().unwrap();
+            assert!(traceparent.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+        });
+    }
+
+    #[test]
+    fn can_propagate_job_trace() {
+        with_tracing(|| {
+            let mut headers = HeaderMap::new();
+            headers.insert("traceparent", TRACEPARENT.parse().unwrap());
+            let span = tracing::info_span!("http-request");
+            set_parent_from_headers(&span, &headers);
+
+            let mut data = json!({"user_id": 1});
+            span.in_scope(|| inject_job_context(&mut data));
+            assert!(data.get(JOB_CONTEXT_KEY).is_some());
+
+            let (data, span) = job_span("job-1", data);
+            assert_eq!(data, json!({"user_id": 1}));
+            assert_eq!(trace_id(&span), "4bf92f3577b34da6a3ce929d0e0e4736");
+
+            // arguments other than objects are left untouched
+            let mut data = json!([1, 2]);
+            inject_job_context(&mut data);
+            assert_eq!(data, json!([1, 2]));
+        });
+    }
+}
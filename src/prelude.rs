@@ -22,27 +22,40 @@ pub use serde_json::json as data;
 #[cfg(all(feature = "auth_jwt", feature = "with-db"))]
 pub use crate::controller::extractor::auth;
 pub use crate::controller::extractor::{
+    normalize::{Normalize, Normalized},
+    payload::Payload,
     shared_store::SharedStore,
-    validate::{JsonValidate, JsonValidateWithMessage},
+    validate::{JsonValidate, JsonValidateWithMessage, Validated},
 };
 #[cfg(feature = "with-db")]
-pub use crate::model::{query, Authenticable, ModelError, ModelResult};
+pub use crate::model::{
+    query,
+    soft_delete::{SoftDelete, SoftDeleteModel},
+    versioned::{Versioned, VersionedModel},
+    Authenticable, ModelError, ModelResult,
+};
 pub use crate::{
     app::{AppContext, Initializer},
-    bgworker::{BackgroundWorker, Queue},
+    bgworker::{BackgroundWorker, Codec, Queue},
     controller::{
         bad_request, format,
         middleware::{
             format::{Format, RespondTo},
             remote_ip::RemoteIP,
+            slow_request::RequestTimings,
         },
-        not_found, unauthorized,
+        not_found,
+        precondition::{self, Preconditions},
+        responder::Responder,
+        response::{Cookies, Redirect},
+        unauthorized,
         views::{engines::TeraView, ViewEngine, ViewRenderer},
-        Json, Routes,
+        Json, LayerRegistry, Routes,
     },
     errors::Error,
     mailer,
     mailer::Mailer,
+    request_context::RequestContext,
     task::{self, Task, TaskInfo},
     validation::{self, Validatable},
     validator::Validate,
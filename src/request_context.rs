@@ -0,0 +1,159 @@
+//! # Request Context
+//!
+//! A [`RequestContext`] gathers the cross-cutting context of a request: its
+//! id, route, trace context, authenticated actor, locale and tenant. The
+//! `request_context` middleware populates it for every request, handlers
+//! extract it, and the jobs enqueued while handling the request carry it, so
+//! that workers log with the same context:
+//!
+//! ```rust,ignore
+//! async fn create(context: RequestContext, State(ctx): State<AppContext>) -> Result<Response> {
+//!     tracing::info!(actor = context.actor, "creating a report");
+//!     ReportWorker::perform_later(&ctx, ReportArgs { user_id: 1 }).await?;
+//!     format::empty()
+//! }
+//!
+//! async fn perform(&self, args: ReportArgs) -> Result<()> {
+//!     let context = RequestContext::current();
+//!     ...
+//! }
+//! ```
+//!
+//! Only jobs with object arguments can carry the context.
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The job data key carrying the request context of the code that enqueued
+/// the job.
+const JOB_CONTEXT_KEY: &str = "_loco_request_context";
+
+tokio::task_local! {
+    static CURRENT: RequestContext;
+}
+
+/// The cross-cutting context of a request, propagated to the jobs it
+/// enqueues.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestContext {
+    /// The id of the request, set by the `request_id` middleware.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// The method and route of the request, such as `GET /notes/{id}`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route: Option<String>,
+    /// The W3C `traceparent` of the request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_context: Option<String>,
+    /// Who performs the request, usually the pid of the user authenticated
+    /// by JWT.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    /// The preferred language of the request, from `Accept-Language`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    /// The tenant of the request, set by the `tenant` middleware.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+}
+
+impl RequestContext {
+    /// Returns the context of the request being handled, or of the request
+    /// which enqueued the job being performed.
+    #[must_use]
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+
+    /// Runs `future` with `self` as the current context, within a span
+    /// carrying its fields.
+    pub async fn scope<F: std::future::Future>(self, future: F) -> F::Output {
+        use tracing::Instrument;
+
+        let span = tracing::info_span!(
+            "request_context",
+            request_id = self.request_id,
+            route = self.route,
+            actor = self.actor,
+            locale = self.locale,
+            tenant = self.tenant,
+        );
+        CURRENT.scope(self, future).instrument(span).await
+    }
+}
+
+/// Runs `future` with the current context, if any, for futures spawned away
+/// from the request.
+pub(crate) async fn propagate<F: std::future::Future>(
+    context: Option<RequestContext>,
+    future: F,
+) -> F::Output {
+    match context {
+        Some(context) => context.scope(future).await,
+        None => future.await,
+    }
+}
+
+/// Adds the current context to the data of a job being enqueued. Only object
+/// arguments can carry it.
+pub(crate) fn inject_job_context(data: &mut Value) {
+    let (Value::Object(data), Some(context)) = (data, RequestContext::current()) else {
+        return;
+    };
+    if let Ok(context) = serde_json::to_value(context) {
+        data.insert(JOB_CONTEXT_KEY.to_string(), context);
+    }
+}
+
+/// Removes the context from the data of a job, and returns it.
+#[cfg_attr(
+    not(any(feature = "bg_redis", feature = "bg_pg", feature = "bg_sqlt")),
+    allow(dead_code)
+)]
+pub(crate) fn take_job_context(mut data: Value) -> (Value, Option<RequestContext>) {
+    let context = data
+        .as_object_mut()
+        .and_then(|data| data.remove(JOB_CONTEXT_KEY))
+        .and_then(|context| serde_json::from_value(context).ok());
+    (data, context)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn can_propagate_context_to_jobs() {
+        let context = RequestContext {
+            request_id: Some("req-1".to_string()),
+            actor: Some("user-1".to_string()),
+            tenant: Some("acme".to_string()),
+            ..Default::default()
+        };
+
+        let mut data = json!({ "user_id": 1 });
+        inject_job_context(&mut data);
+        assert_eq!(data, json!({ "user_id": 1 }));
+
+        let data = context
+            .clone()
+            .scope(async {
+                let mut data = json!({ "user_id": 1 });
+                inject_job_context(&mut data);
+                let mut scalar = json!(1);
+                inject_job_context(&mut scalar);
+                assert_eq!(scalar, json!(1));
+                data
+            })
+            .await;
+
+        let (data, taken) = take_job_context(data);
+        assert_eq!(data, json!({ "user_id": 1 }));
+        assert_eq!(taken, Some(context.clone()));
+
+        let current = propagate(taken, async { RequestContext::current() }).await;
+        assert_eq!(current, Some(context));
+        assert_eq!(RequestContext::current(), None);
+    }
+}
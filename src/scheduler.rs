@@ -1,20 +1,31 @@
 //! # Scheduler Module
-//! TBD
+//!
+//! Runs the jobs of the `scheduler` configuration when their cron schedule
+//! is due on the app clock, as shell commands or `cargo loco task` runs.
 
 use std::{
     collections::HashMap,
     fmt, io,
     path::{Path, PathBuf},
-    sync::OnceLock,
+    str::FromStr,
+    sync::{Arc, OnceLock},
     time::{Duration, Instant},
 };
 
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use tokio_cron_scheduler::{JobScheduler, JobSchedulerError};
+use tokio_cron_scheduler::JobSchedulerError;
 use uuid::Uuid;
 
-use crate::{app::Hooks, environment::Environment, task::Tasks};
+use crate::{
+    app::Hooks,
+    clock::{Clock, SystemClock},
+    environment::Environment,
+    task::Tasks,
+};
+
+/// How often the scheduler looks for due jobs.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
 
 static RE_IS_CRON_SYNTAX: OnceLock<Regex> = OnceLock::new();
 
@@ -52,6 +63,11 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 
 /// Configuration structure for the scheduler.
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "config_schema",
+    derive(schemars::JsonSchema),
+    schemars(rename = "Scheduler")
+)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
     /// A list of jobs to be scheduled.
@@ -63,6 +79,7 @@ pub struct Config {
 
 /// Representing a single job in the scheduler.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct Job {
     /// The command to run.
@@ -117,12 +134,24 @@ impl fmt::Display for Scheduler {
 }
 
 /// Representing the scheduler itself.
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct Scheduler {
     pub jobs: HashMap<String, Job>,
     binary_path: PathBuf,
     default_output: Output,
     environment: Environment,
+    clock: Arc<dyn Clock>,
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("jobs", &self.jobs)
+            .field("binary_path", &self.binary_path)
+            .field("default_output", &self.default_output)
+            .field("environment", &self.environment)
+            .finish_non_exhaustive()
+    }
 }
 
 /// Specification used to filter all scheduler job with the given Spec.
@@ -134,6 +163,7 @@ pub struct Spec {
 
 /// Enum representing the scheduler job output.
 #[derive(Clone, Default, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "config_schema", derive(schemars::JsonSchema))]
 pub enum Output {
     /// Silent output, the STDOUT or STDERR of the job will not view out.
     #[serde(rename = "silent")]
@@ -184,6 +214,28 @@ impl Job {
             environment: environment.clone(),
         }
     }
+
+    /// The schedule of the job in cron syntax, converting an English
+    /// schedule like `every 5 minutes`.
+    ///
+    /// # Errors
+    ///
+    /// When the English schedule could not be converted
+    pub fn cron_syntax(&self) -> Result<String> {
+        cron_syntax(&self.cron)
+    }
+}
+
+/// Returns `schedule` in cron syntax, converting an English schedule like
+/// `every 5 minutes`.
+pub(crate) fn cron_syntax(schedule: &str) -> Result<String> {
+    if get_re_is_cron_syntax().is_match(schedule) {
+        return Ok(schedule.to_string());
+    }
+    english_to_cron::str_cron_syntax(schedule).map_err(|err| Error::InvalidCronSyntax {
+        cron: schedule.to_string(),
+        error: err.to_string(),
+    })
 }
 
 impl JobDescription {
@@ -259,9 +311,16 @@ impl Scheduler {
             binary_path: std::env::current_exe()?,
             default_output: data.output.clone(),
             environment: environment.clone(),
+            clock: Arc::new(SystemClock),
         })
     }
 
+    /// Sets the clock the jobs are due on, the system clock by default.
+    #[must_use]
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self { clock, ..self }
+    }
+
     /// Filters the scheduler's jobs based on the provided specification.
     #[must_use]
     pub fn by_spec(self, include_jobs: &Spec) -> Self {
@@ -286,67 +345,77 @@ impl Scheduler {
         Self { jobs, ..self }
     }
 
-    /// Runs the scheduled jobs according to their cron expressions.
+    /// Runs the scheduled jobs according to their cron expressions, until
+    /// the process is interrupted. The jobs are due on the clock of the
+    /// scheduler (see [`Scheduler::with_clock`]), checked every second.
     ///
     /// # Errors
     ///
-    /// When could not add job to the scheduler
+    /// When the schedule of a job is invalid
     pub async fn run(self) -> Result<()> {
-        let mut sched = JobScheduler::new().await?;
-
+        let mut jobs = Vec::with_capacity(self.jobs.len());
+        let now = self.clock.now();
         for (job_name, job) in &self.jobs {
-            let job_description =
+            let description =
                 job.prepare_command(&self.binary_path, &self.default_output, &self.environment);
-
-            let cron_syntax = if get_re_is_cron_syntax().is_match(&job.cron) {
-                job.cron.clone()
-            } else {
-                english_to_cron::str_cron_syntax(&job.cron).map_err(|err| {
-                    Error::InvalidCronSyntax {
-                        cron: job.cron.clone(),
-                        error: err.to_string(),
-                    }
-                })?
+            let cron_syntax = job.cron_syntax()?;
+            let schedule =
+                cron::Schedule::from_str(&cron_syntax).map_err(|err| Error::InvalidCronSyntax {
+                    cron: job.cron.clone(),
+                    error: err.to_string(),
+                })?;
+            let due = DueJob {
+                name: job_name.to_string(),
+                id: Uuid::new_v4(),
+                next_run_at: schedule.after(&now).next(),
+                schedule,
+                description,
             };
-
             if job.run_on_start {
-                let job_description = job_description.clone();
-                let job_name = job_name.to_string();
-                sched
-                    .add(tokio_cron_scheduler::Job::new_one_shot_async(
-                        Duration::from_secs(0),
-                        move |uuid, _l| {
-                            let job_description = job_description.clone();
-                            let job_name = job_name.clone();
-                            Box::pin(async move {
-                                execute_job(job_name.as_str(), uuid, &job_description);
-                            })
-                        },
-                    )?)
-                    .await?;
+                due.spawn();
             }
-
-            let job_name = job_name.to_string();
-            sched
-                .add(tokio_cron_scheduler::Job::new_async(
-                    cron_syntax.as_str(),
-                    move |uuid, mut _l| {
-                        let job_description = job_description.clone();
-                        let job_name = job_name.to_string();
-                        Box::pin(async move {
-                            execute_job(job_name.as_str(), uuid, &job_description);
-                        })
-                    },
-                )?)
-                .await?;
+            jobs.push(due);
         }
 
-        sched.start().await?;
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let shutdown = tokio::signal::ctrl_c();
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                result = &mut shutdown => return Ok(result?),
+                _ = interval.tick() => {}
+            }
+            let now = self.clock.now();
+            for job in &mut jobs {
+                if job
+                    .next_run_at
+                    .is_some_and(|next_run_at| next_run_at <= now)
+                {
+                    job.spawn();
+                    job.next_run_at = job.schedule.after(&now).next();
+                }
+            }
+        }
+    }
+}
 
-        tokio::signal::ctrl_c().await?;
-        sched.shutdown().await?;
+/// A job of the running scheduler, with the next time it is due.
+struct DueJob {
+    name: String,
+    id: Uuid,
+    schedule: cron::Schedule,
+    next_run_at: Option<chrono::DateTime<chrono::Utc>>,
+    description: JobDescription,
+}
 
-        Ok(())
+impl DueJob {
+    /// Runs the job in the background, without blocking the scheduler.
+    fn spawn(&self) {
+        let name = self.name.clone();
+        let id = self.id;
+        let description = self.description.clone();
+        tokio::task::spawn_blocking(move || execute_job(&name, id, &description));
     }
 }
 
@@ -621,4 +690,50 @@ jobs:
             1
         );
     }
+
+    #[tokio::test]
+    pub async fn can_run_on_the_clock() {
+        use chrono::TimeZone;
+
+        let (mut scheduler, _config_tree) = setup_scheduler_config();
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .add("scheduler.txt", "")
+            .create()
+            .unwrap();
+        let lines = || {
+            std::fs::read_to_string(tree_fs.root.join("scheduler.txt"))
+                .unwrap()
+                .lines()
+                .count()
+        };
+
+        scheduler.jobs = HashMap::from([(
+            "hourly".to_string(),
+            Job {
+                run: format!(
+                    "echo loco >> {}",
+                    tree_fs.root.join("scheduler.txt").display()
+                ),
+                shell: true,
+                run_on_start: false,
+                cron: "0 0 * * * * *".to_string(),
+                tags: None,
+                output: None,
+            },
+        )]);
+        let clock = crate::clock::FrozenClock::new(
+            chrono::Utc.with_ymd_and_hms(2025, 1, 1, 0, 30, 0).unwrap(),
+        );
+        let handle = tokio::spawn(scheduler.with_clock(Arc::new(clock.clone())).run());
+
+        time::sleep(Duration::from_millis(1500)).await;
+        assert_eq!(lines(), 0);
+
+        // the job is due once the clock reaches the next hour
+        clock.advance(chrono::Duration::minutes(30));
+        time::sleep(Duration::from_millis(1500)).await;
+        handle.abort();
+        assert_eq!(lines(), 1);
+    }
 }
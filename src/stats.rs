@@ -0,0 +1,159 @@
+//! # Runtime Stats
+//!
+//! Point-in-time numbers about the connection pools and the job queue of an
+//! app, returned by [`crate::app::AppContext::stats`], to build custom admin
+//! views, dashboards or alerts:
+//!
+//! ```rust,ignore
+//! let stats = ctx.stats().await?;
+//! if let Some(queue) = stats.queue {
+//!     if queue.queued > 1_000 {
+//!         tracing::warn!(queued = queue.queued, "the job queue is backing up");
+//!     }
+//! }
+//! ```
+use serde::Serialize;
+
+use crate::{app::AppContext, bgworker::JobStatus, Result};
+
+/// The connections of a pool.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct PoolStats {
+    /// Open connections, idle or in use
+    pub size: u32,
+    /// Open connections waiting to be used
+    pub idle: u32,
+    /// Connections in use
+    pub used: u32,
+}
+
+impl PoolStats {
+    #[must_use]
+    pub const fn new(size: u32, idle: u32) -> Self {
+        Self {
+            size,
+            idle,
+            used: size.saturating_sub(idle),
+        }
+    }
+
+    /// From the `size()` and `num_idle()` of a sqlx pool.
+    #[must_use]
+    pub fn from_sqlx(size: u32, idle: usize) -> Self {
+        Self::new(size, u32::try_from(idle).unwrap_or(u32::MAX))
+    }
+}
+
+/// The jobs of the queue, by status.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct QueueStats {
+    /// The connections of the queue, for queues using a pool
+    pub pool: Option<PoolStats>,
+    pub queued: usize,
+    pub processing: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub cancelled: usize,
+}
+
+impl QueueStats {
+    /// Counts jobs by their status.
+    #[must_use]
+    pub fn count<'a>(
+        pool: Option<PoolStats>,
+        statuses: impl IntoIterator<Item = &'a JobStatus>,
+    ) -> Self {
+        let mut stats = Self {
+            pool,
+            ..Self::default()
+        };
+        for status in statuses {
+            match status {
+                JobStatus::Queued => stats.queued += 1,
+                JobStatus::Processing => stats.processing += 1,
+                JobStatus::Completed => stats.completed += 1,
+                JobStatus::Failed => stats.failed += 1,
+                JobStatus::Cancelled => stats.cancelled += 1,
+            }
+        }
+        stats
+    }
+}
+
+/// The runtime stats of an app.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Stats {
+    /// The database pool, when the app has a database
+    pub db: Option<PoolStats>,
+    /// The cache pool, with the Redis cache
+    pub cache: Option<PoolStats>,
+    /// The job queue, when the app has one
+    pub queue: Option<QueueStats>,
+}
+
+/// Computes the stats of `ctx`.
+///
+/// # Errors
+/// When the jobs of the queue cannot be read
+pub async fn collect(ctx: &AppContext) -> Result<Stats> {
+    let queue = match &ctx.queue_provider {
+        Some(queue) => queue.stats().await?,
+        None => None,
+    };
+    Ok(Stats {
+        db: db_stats(ctx),
+        cache: ctx.cache.driver.pool_stats(),
+        queue,
+    })
+}
+
+#[cfg(feature = "with-db")]
+fn db_stats(ctx: &AppContext) -> Option<PoolStats> {
+    use sea_orm::DatabaseConnection;
+
+    match &ctx.db {
+        DatabaseConnection::SqlxPostgresPoolConnection(_) => {
+            let pool = ctx.db.get_postgres_connection_pool();
+            Some(PoolStats::from_sqlx(pool.size(), pool.num_idle()))
+        }
+        DatabaseConnection::SqlxSqlitePoolConnection(_) => {
+            let pool = ctx.db.get_sqlite_connection_pool();
+            Some(PoolStats::from_sqlx(pool.size(), pool.num_idle()))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(not(feature = "with-db"))]
+const fn db_stats(_ctx: &AppContext) -> Option<PoolStats> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests_cfg;
+
+    #[test]
+    fn can_count_jobs() {
+        let stats = QueueStats::count(
+            Some(PoolStats::new(4, 3)),
+            &[JobStatus::Queued, JobStatus::Queued, JobStatus::Failed],
+        );
+        assert_eq!(stats.queued, 2);
+        assert_eq!(stats.failed, 1);
+        assert_eq!(stats.processing, 0);
+        assert_eq!(stats.pool.unwrap().used, 1);
+    }
+
+    #[tokio::test]
+    async fn can_collect_stats() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let stats = ctx.stats().await.unwrap();
+
+        #[cfg(feature = "with-db")]
+        assert!(stats.db.unwrap().size >= 1);
+        assert_eq!(stats.cache, None);
+        assert_eq!(stats.queue, None);
+    }
+}
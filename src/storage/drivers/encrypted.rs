@@ -0,0 +1,301 @@
+//! # Encrypted Storage Driver
+//!
+//! Wraps any driver to encrypt the objects at rest with AES-256-GCM: objects
+//! are encrypted on upload and decrypted on download, so that the files of
+//! the local driver are not stored in plaintext. Cloud stores offer
+//! server-side encryption instead; this targets the local driver, for
+//! development and air-gapped setups.
+//!
+//! Each object stores the id of the key it was encrypted with, so keys can be
+//! rotated: new objects are encrypted with the current key, while objects
+//! encrypted with a previous one can still be read, and re-encrypted with
+//! [`EncryptedStore::reencrypt`].
+//!
+//! # Examples
+//!
+//!```
+//! use loco_rs::storage::drivers::{encrypted, mem};
+//!
+//! let keys = encrypted::Keys::new("2025", &[7; 32])
+//!     .unwrap()
+//!     .with_previous("2024", &[3; 32])
+//!     .unwrap();
+//! let driver = encrypted::new(mem::new(), keys);
+//! ```
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+
+use super::{GetResponse, StorageResult, StoreDriver, UploadResponse};
+use crate::storage::StorageError;
+
+/// Starts the encrypted objects, followed by the key id length, the key id
+/// and the nonce.
+const MAGIC: &[u8] = b"LOCOENC1";
+
+/// The encryption keys, by id: the current key encrypts, and all decrypt.
+pub struct Keys {
+    current: String,
+    keys: BTreeMap<String, LessSafeKey>,
+}
+
+impl Keys {
+    /// Encrypts with the 32 bytes `key`, identified by `id`.
+    ///
+    /// # Errors
+    ///
+    /// When the key is not 32 bytes long, or its id longer than 255 bytes.
+    pub fn new(id: &str, key: &[u8]) -> StorageResult<Self> {
+        let mut keys = Self {
+            current: id.to_string(),
+            keys: BTreeMap::new(),
+        };
+        keys.insert(id, key)?;
+        Ok(keys)
+    }
+
+    /// Decrypts the objects encrypted with the previous `key`, identified by
+    /// `id`.
+    ///
+    /// # Errors
+    ///
+    /// When the key is not 32 bytes long, or its id longer than 255 bytes.
+    pub fn with_previous(mut self, id: &str, key: &[u8]) -> StorageResult<Self> {
+        self.insert(id, key)?;
+        Ok(self)
+    }
+
+    fn insert(&mut self, id: &str, key: &[u8]) -> StorageResult<()> {
+        if u8::try_from(id.len()).is_err() {
+            return Err(StorageError::Any(
+                format!("encryption key id `{id}` is longer than 255 bytes").into(),
+            ));
+        }
+        let key = UnboundKey::new(&AES_256_GCM, key).map_err(|_| {
+            StorageError::Any(format!("encryption key `{id}` must be 32 bytes long").into())
+        })?;
+        self.keys.insert(id.to_string(), LessSafeKey::new(key));
+        Ok(())
+    }
+}
+
+/// A driver encrypting the objects of another, see the [module
+/// documentation](self).
+pub struct EncryptedStore {
+    inner: Box<dyn StoreDriver>,
+    keys: Keys,
+    random: SystemRandom,
+}
+
+/// Encrypts the objects of `inner` with `keys`.
+#[must_use]
+pub fn new(inner: Box<dyn StoreDriver>, keys: Keys) -> Box<dyn StoreDriver> {
+    Box::new(EncryptedStore::new(inner, keys))
+}
+
+/// The header of an encrypted object, authenticated with its content.
+fn header(key_id: &str, nonce: &[u8]) -> Vec<u8> {
+    let mut header = MAGIC.to_vec();
+    #[allow(clippy::cast_possible_truncation)] // checked by `Keys::insert`
+    header.push(key_id.len() as u8);
+    header.extend_from_slice(key_id.as_bytes());
+    header.extend_from_slice(nonce);
+    header
+}
+
+/// Splits an encrypted object into its key id, its header and its nonce.
+fn parse(content: &[u8]) -> Option<(&str, usize, [u8; NONCE_LEN])> {
+    let rest = content.strip_prefix(MAGIC)?;
+    let (&id_len, rest) = rest.split_first()?;
+    let id = std::str::from_utf8(rest.get(..usize::from(id_len))?).ok()?;
+    let rest = &rest[usize::from(id_len)..];
+    let nonce = rest.get(..NONCE_LEN)?.try_into().ok()?;
+    Some((id, MAGIC.len() + 1 + usize::from(id_len) + NONCE_LEN, nonce))
+}
+
+impl EncryptedStore {
+    #[must_use]
+    pub fn new(inner: Box<dyn StoreDriver>, keys: Keys) -> Self {
+        Self {
+            inner,
+            keys,
+            random: SystemRandom::new(),
+        }
+    }
+
+    fn encrypt(&self, content: &[u8]) -> StorageResult<Vec<u8>> {
+        let key = &self.keys.keys[&self.keys.current];
+        let mut nonce = [0; NONCE_LEN];
+        self.random
+            .fill(&mut nonce)
+            .map_err(|_| StorageError::Any("could not generate a nonce".into()))?;
+        let mut encrypted = header(&self.keys.current, &nonce);
+        let mut in_out = content.to_vec();
+        key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(&encrypted),
+            &mut in_out,
+        )
+        .map_err(|_| StorageError::Any("could not encrypt the content".into()))?;
+        encrypted.extend_from_slice(&in_out);
+        Ok(encrypted)
+    }
+
+    /// Decrypts an object, returning it with the id of its key.
+    fn decrypt<'a>(&self, path: &Path, content: &'a [u8]) -> StorageResult<(&'a str, Vec<u8>)> {
+        let invalid = |reason: &str| {
+            StorageError::Any(format!("could not decrypt `{}`: {reason}", path.display()).into())
+        };
+        let (key_id, header_len, nonce) =
+            parse(content).ok_or_else(|| invalid("the content is not encrypted"))?;
+        let key = self
+            .keys
+            .keys
+            .get(key_id)
+            .ok_or_else(|| invalid(&format!("unknown key `{key_id}`")))?;
+        let mut in_out = content[header_len..].to_vec();
+        let plain_len = key
+            .open_in_place(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(&content[..header_len]),
+                &mut in_out,
+            )
+            .map_err(|_| invalid("the content was altered"))?
+            .len();
+        in_out.truncate(plain_len);
+        Ok((key_id, in_out))
+    }
+
+    /// Re-encrypts with the current key the objects under `prefix` that are
+    /// encrypted with a previous one, returning their count, so a previous
+    /// key can be removed once done.
+    ///
+    /// # Errors
+    ///
+    /// When an object cannot be read, decrypted, or written.
+    pub async fn reencrypt(&self, prefix: &Path) -> StorageResult<usize> {
+        let mut count = 0;
+        for path in self.inner.list(prefix).await? {
+            let content = self.inner.get(&path).await?.bytes().await?;
+            let (key_id, plain) = self.decrypt(&path, &content)?;
+            if key_id != self.keys.current {
+                self.upload(&path, &Bytes::from(plain)).await?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
+
+#[async_trait]
+impl StoreDriver for EncryptedStore {
+    fn backend(&self) -> String {
+        format!("encrypted {}", self.inner.backend())
+    }
+
+    async fn check(&self) -> StorageResult<()> {
+        self.inner.check().await
+    }
+
+    async fn upload(&self, path: &Path, content: &Bytes) -> StorageResult<UploadResponse> {
+        let encrypted = self.encrypt(content)?;
+        self.inner.upload(path, &Bytes::from(encrypted)).await
+    }
+
+    async fn get(&self, path: &Path) -> StorageResult<GetResponse> {
+        let content = self.inner.get(path).await?.bytes().await?;
+        let (_, plain) = self.decrypt(path, &content)?;
+        Ok(GetResponse::from_bytes(Bytes::from(plain)))
+    }
+
+    async fn delete(&self, path: &Path) -> StorageResult<()> {
+        self.inner.delete(path).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> StorageResult<()> {
+        self.inner.rename(from, to).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> StorageResult<()> {
+        self.inner.copy(from, to).await
+    }
+
+    async fn exists(&self, path: &Path) -> StorageResult<bool> {
+        self.inner.exists(path).await
+    }
+
+    async fn list(&self, prefix: &Path) -> StorageResult<Vec<PathBuf>> {
+        self.inner.list(prefix).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::drivers::mem;
+
+    fn keys() -> Keys {
+        Keys::new("new", &[1; 32]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn encrypts_at_rest() {
+        let inner = mem::new();
+        let store = EncryptedStore::new(mem::new(), keys());
+        let path = Path::new("notes/1.txt");
+        store
+            .upload(path, &Bytes::from("secret note"))
+            .await
+            .unwrap();
+        let content = store.get(path).await.unwrap().bytes().await.unwrap();
+        assert_eq!(content, Bytes::from("secret note"));
+
+        let stored = store.inner.get(path).await.unwrap().bytes().await.unwrap();
+        assert!(stored.starts_with(MAGIC));
+        assert!(!stored.windows(6).any(|window| window == b"secret"));
+
+        // plaintext and altered objects are rejected
+        inner.upload(path, &Bytes::from("plain")).await.unwrap();
+        let plain = EncryptedStore::new(inner, keys());
+        assert!(plain.get(path).await.is_err());
+        let mut altered = stored.to_vec();
+        *altered.last_mut().unwrap() ^= 1;
+        store
+            .inner
+            .upload(path, &Bytes::from(altered))
+            .await
+            .unwrap();
+        assert!(store.get(path).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn can_rotate_keys() {
+        let old = EncryptedStore::new(mem::new(), Keys::new("old", &[2; 32]).unwrap());
+        let path = Path::new("notes/1.txt");
+        old.upload(path, &Bytes::from("note")).await.unwrap();
+
+        let store = EncryptedStore::new(old.inner, keys().with_previous("old", &[2; 32]).unwrap());
+        let content = store.get(path).await.unwrap().bytes().await.unwrap();
+        assert_eq!(content, Bytes::from("note"));
+
+        assert_eq!(store.reencrypt(Path::new("notes")).await.unwrap(), 1);
+        assert_eq!(store.reencrypt(Path::new("notes")).await.unwrap(), 0);
+        let current = EncryptedStore::new(store.inner, keys());
+        let content = current.get(path).await.unwrap().bytes().await.unwrap();
+        assert_eq!(content, Bytes::from("note"));
+    }
+
+    #[test]
+    fn rejects_invalid_keys() {
+        assert!(Keys::new("short", &[1; 16]).is_err());
+        assert!(Keys::new(&"k".repeat(256), &[1; 32]).is_err());
+    }
+}
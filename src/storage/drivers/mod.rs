@@ -1,13 +1,19 @@
-use std::path::Path;
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
 
 use async_trait::async_trait;
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::{stream::BoxStream, StreamExt, TryStreamExt};
 use opendal::Reader;
 
 #[cfg(feature = "storage_aws_s3")]
 pub mod aws;
 #[cfg(feature = "storage_azure")]
 pub mod azure;
+pub mod encrypted;
 #[cfg(feature = "storage_gcp")]
 pub mod gcp;
 pub mod local;
@@ -15,7 +21,7 @@ pub mod mem;
 pub mod null;
 pub mod opendal_adapter;
 
-use super::StorageResult;
+use super::{StorageError, StorageResult};
 
 #[derive(Debug)]
 pub struct UploadResponse {
@@ -23,17 +29,85 @@ pub struct UploadResponse {
     pub version: Option<String>,
 }
 
-/// TODO: Add more methods to `GetResponse` to read the content in different
-/// ways
+/// An object listed by [`StoreDriver::list_page`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListEntry {
+    pub path: PathBuf,
+    /// The size in bytes.
+    pub size: u64,
+    /// When the object was last modified, when the store tracks it.
+    pub last_modified: Option<DateTime<Utc>>,
+    /// The content type, as stored or else guessed from the extension.
+    pub content_type: Option<String>,
+}
+
+impl ListEntry {
+    /// Guesses the content type of `path` from its extension.
+    pub(crate) fn guess_content_type(path: &Path) -> Option<String> {
+        mime_guess::from_path(path)
+            .first()
+            .map(|mime| mime.to_string())
+    }
+}
+
+/// A page of the objects under a prefix, listed in the order of their paths.
+#[derive(Debug, Default)]
+pub struct ListPage {
+    pub entries: Vec<ListEntry>,
+    /// The token listing the next page, `None` on the last page.
+    pub next_token: Option<String>,
+}
+
+/// Keeps the `limit` first of `items` after the `token` of the previous page,
+/// in the order of their paths, returning the token of the next page.
+pub(crate) fn paginate<T>(
+    items: impl IntoIterator<Item = T>,
+    path: impl Fn(&T) -> &Path,
+    token: Option<&str>,
+    limit: usize,
+) -> (Vec<T>, Option<String>) {
+    let limit = limit.max(1);
+    let mut items = items
+        .into_iter()
+        .map(|item| (path(&item).display().to_string(), item))
+        .filter(|(key, _)| token.map_or(true, |token| key.as_str() > token))
+        .collect::<Vec<_>>();
+    items.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let next_token = (items.len() > limit).then(|| items[limit - 1].0.clone());
+    items.truncate(limit);
+    (
+        items.into_iter().map(|(_, item)| item).collect(),
+        next_token,
+    )
+}
+
+/// The content returned by [`StoreDriver::get`].
 ///
-/// For example, we can read a specific range of bytes from the stream.
+/// Built-in drivers return a lazy reader over the stored object. Custom drivers
+/// can build a response from in-memory content with
+/// [`GetResponse::from_bytes`].
 pub struct GetResponse {
-    stream: Reader,
+    body: Body,
+}
+
+enum Body {
+    Reader(Reader),
+    Bytes(Bytes),
 }
 
 impl GetResponse {
     pub(crate) fn new(stream: Reader) -> Self {
-        Self { stream }
+        Self {
+            body: Body::Reader(stream),
+        }
+    }
+
+    /// Create a response from content that is already in memory.
+    #[must_use]
+    pub fn from_bytes(content: Bytes) -> Self {
+        Self {
+            body: Body::Bytes(content),
+        }
     }
 
     /// Read all content from the stream and return as `Bytes`.
@@ -42,12 +116,88 @@ impl GetResponse {
     ///
     /// Returns a `StorageError` with the reason for the failure.
     pub async fn bytes(&self) -> StorageResult<Bytes> {
-        Ok(self.stream.read(..).await?.to_bytes())
+        match &self.body {
+            Body::Reader(reader) => Ok(reader.read(..).await?.to_bytes()),
+            Body::Bytes(content) => Ok(content.clone()),
+        }
+    }
+
+    /// Turn the response into a stream of chunks, without buffering the whole
+    /// content in memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageError` when the stream could not be opened.
+    pub async fn into_stream(self) -> StorageResult<BoxStream<'static, StorageResult<Bytes>>> {
+        match self.body {
+            Body::Reader(reader) => Ok(reader
+                .into_bytes_stream(..)
+                .await?
+                .map_err(|err| StorageError::Any(Box::new(err)))
+                .boxed()),
+            Body::Bytes(content) => Ok(futures_util::stream::once(async { Ok(content) }).boxed()),
+        }
+    }
+    /// Turn the byte `range` of the content into a stream of chunks. The
+    /// range must be within the content.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageError` when the stream could not be opened.
+    pub async fn into_range_stream(
+        self,
+        range: Range<u64>,
+    ) -> StorageResult<BoxStream<'static, StorageResult<Bytes>>> {
+        match self.body {
+            Body::Reader(reader) => Ok(reader
+                .into_bytes_stream(range)
+                .await?
+                .map_err(|err| StorageError::Any(Box::new(err)))
+                .boxed()),
+            Body::Bytes(content) => {
+                let start =
+                    usize::try_from(range.start).map_err(|err| StorageError::Any(Box::new(err)))?;
+                let end =
+                    usize::try_from(range.end).map_err(|err| StorageError::Any(Box::new(err)))?;
+                let content = content.slice(start..end);
+                Ok(futures_util::stream::once(async { Ok(content) }).boxed())
+            }
+        }
     }
 }
 
 #[async_trait]
 pub trait StoreDriver: Sync + Send {
+    /// Whether this is the null driver, which stores nothing. Drivers other
+    /// than [`null`] keep the default `false`.
+    fn is_null(&self) -> bool {
+        false
+    }
+
+    /// Whether the store keeps its content as plain files on the local disk,
+    /// which a reverse proxy can serve itself. Drivers other than [`local`]
+    /// keep the default `false`.
+    fn is_local(&self) -> bool {
+        false
+    }
+
+    /// Describes the backend of the store, such as its service and bucket,
+    /// for error messages.
+    fn backend(&self) -> String {
+        "custom".to_string()
+    }
+
+    /// Checks that the store is usable: its bucket exists, its directory is
+    /// writable... [`crate::storage::Storage`] runs it before its first
+    /// operation.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the reason the store is unusable.
+    async fn check(&self) -> StorageResult<()> {
+        Ok(())
+    }
+
     /// Uploads the content represented by `Bytes` to the specified path in the
     /// object store.
     ///
@@ -94,4 +244,356 @@ pub trait StoreDriver: Sync + Send {
     /// Returns a `StorageResult` with a boolean indicating the existence of the
     /// content.
     async fn exists(&self, path: &Path) -> StorageResult<bool>;
+
+    /// Lists the paths of all content stored under the given prefix,
+    /// recursively. An empty prefix lists the whole store.
+    ///
+    /// The default implementation returns [`StorageError::Unsupported`];
+    /// drivers should override it when their backend can list.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the listed paths.
+    async fn list(&self, prefix: &Path) -> StorageResult<Vec<PathBuf>> {
+        let _ = prefix;
+        Err(StorageError::Unsupported {
+            operation: "list",
+            backend: self.backend(),
+        })
+    }
+
+    /// Lists a page of at most `limit` objects under the given prefix,
+    /// recursively, with their metadata. The first page is listed without a
+    /// token, and the next ones with the `next_token` of the previous page.
+    ///
+    /// The default implementation lists all the paths with
+    /// [`StoreDriver::list`] and reads the size of the listed ones; drivers
+    /// should override it when their backend paginates.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the listed page.
+    async fn list_page(
+        &self,
+        prefix: &Path,
+        token: Option<&str>,
+        limit: usize,
+    ) -> StorageResult<ListPage> {
+        let (paths, next_token) = paginate(self.list(prefix).await?, |path| path, token, limit);
+        let mut entries = Vec::with_capacity(paths.len());
+        for path in paths {
+            entries.push(ListEntry {
+                size: self.size(&path).await?,
+                last_modified: None,
+                content_type: ListEntry::guess_content_type(&path),
+                path,
+            });
+        }
+        Ok(ListPage {
+            entries,
+            next_token,
+        })
+    }
+
+    /// Returns the size in bytes of the content at the given path.
+    ///
+    /// The default implementation reads the whole content; drivers should
+    /// override it when the size is available from metadata.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the content size.
+    async fn size(&self, path: &Path) -> StorageResult<u64> {
+        Ok(self.get(path).await?.bytes().await?.len() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, sync::Mutex};
+
+    use super::*;
+    use crate::storage::Storage;
+
+    /// A user-defined driver, to check that custom backends can be plugged in.
+    #[derive(Default)]
+    struct MapStore {
+        files: Mutex<BTreeMap<PathBuf, Bytes>>,
+    }
+
+    #[async_trait]
+    impl StoreDriver for MapStore {
+        async fn upload(&self, path: &Path, content: &Bytes) -> StorageResult<UploadResponse> {
+            self.files
+                .lock()
+                .unwrap()
+                .insert(path.to_path_buf(), content.clone());
+            Ok(UploadResponse {
+                e_tag: None,
+                version: None,
+            })
+        }
+
+        async fn get(&self, path: &Path) -> StorageResult<GetResponse> {
+            let content = self.files.lock().unwrap().get(path).cloned();
+            content
+                .map(GetResponse::from_bytes)
+                .ok_or_else(|| StorageError::Any("not found".into()))
+        }
+
+        async fn delete(&self, path: &Path) -> StorageResult<()> {
+            self.files.lock().unwrap().remove(path);
+            Ok(())
+        }
+
+        async fn rename(&self, from: &Path, to: &Path) -> StorageResult<()> {
+            self.copy(from, to).await?;
+            self.delete(from).await
+        }
+
+        async fn copy(&self, from: &Path, to: &Path) -> StorageResult<()> {
+            let content = self.get(from).await?.bytes().await?;
+            self.upload(to, &content).await?;
+            Ok(())
+        }
+
+        async fn exists(&self, path: &Path) -> StorageResult<bool> {
+            Ok(self.files.lock().unwrap().contains_key(path))
+        }
+
+        async fn list(&self, prefix: &Path) -> StorageResult<Vec<PathBuf>> {
+            Ok(self
+                .files
+                .lock()
+                .unwrap()
+                .keys()
+                .filter(|path| path.starts_with(prefix))
+                .cloned()
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn can_use_custom_driver() {
+        let storage = Storage::single(Box::new(MapStore::default()));
+        let path = PathBuf::from("users").join("1.txt");
+
+        storage
+            .upload(&path, &Bytes::from("content"))
+            .await
+            .unwrap();
+        let content: String = storage.download(&path).await.unwrap();
+        assert_eq!(content, "content");
+
+        let store = storage.as_store("store").unwrap();
+        assert_eq!(store.list(Path::new("users")).await.unwrap(), vec![path]);
+    }
+
+    /// A driver written before listing was part of the trait.
+    struct UnlistedStore;
+
+    #[async_trait]
+    impl StoreDriver for UnlistedStore {
+        async fn upload(&self, _: &Path, _: &Bytes) -> StorageResult<UploadResponse> {
+            unimplemented!()
+        }
+
+        async fn get(&self, _: &Path) -> StorageResult<GetResponse> {
+            unimplemented!()
+        }
+
+        async fn delete(&self, _: &Path) -> StorageResult<()> {
+            unimplemented!()
+        }
+
+        async fn rename(&self, _: &Path, _: &Path) -> StorageResult<()> {
+            unimplemented!()
+        }
+
+        async fn copy(&self, _: &Path, _: &Path) -> StorageResult<()> {
+            unimplemented!()
+        }
+
+        async fn exists(&self, _: &Path) -> StorageResult<bool> {
+            unimplemented!()
+        }
+    }
+
+    #[tokio::test]
+    async fn cannot_list_drivers_without_listing() {
+        let err = UnlistedStore.list(Path::new("users")).await.unwrap_err();
+        assert!(matches!(
+            err,
+            StorageError::Unsupported {
+                operation: "list",
+                ..
+            }
+        ));
+        assert_eq!(err.to_string(), "the custom store does not support `list`");
+        assert!(UnlistedStore
+            .list_page(Path::new("users"), None, 10)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn can_list_recursively() {
+        let store = mem::new();
+        for path in ["users/1.txt", "users/nested/2.txt", "other/3.txt"] {
+            store
+                .upload(Path::new(path), &Bytes::from("content"))
+                .await
+                .unwrap();
+        }
+
+        let mut paths = store.list(Path::new("users/")).await.unwrap();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("users/1.txt"),
+                PathBuf::from("users/nested/2.txt")
+            ]
+        );
+        assert_eq!(store.list(Path::new("")).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn can_list_pages() {
+        let store = mem::new();
+        for path in [
+            "users/b.txt",
+            "users/a.json",
+            "users/nested/c.txt",
+            "other/d.txt",
+        ] {
+            store
+                .upload(Path::new(path), &Bytes::from("content"))
+                .await
+                .unwrap();
+        }
+
+        let first = store.list_page(Path::new("users/"), None, 2).await.unwrap();
+        let paths = first
+            .entries
+            .iter()
+            .map(|entry| entry.path.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("users/a.json"), PathBuf::from("users/b.txt")]
+        );
+        assert_eq!(first.entries[0].size, 7);
+        assert_eq!(
+            first.entries[0].content_type.as_deref(),
+            Some("application/json")
+        );
+        assert_eq!(first.next_token.as_deref(), Some("users/b.txt"));
+
+        let last = store
+            .list_page(Path::new("users/"), first.next_token.as_deref(), 2)
+            .await
+            .unwrap();
+        assert_eq!(last.entries.len(), 1);
+        assert_eq!(last.entries[0].path, PathBuf::from("users/nested/c.txt"));
+        assert_eq!(last.next_token, None);
+    }
+
+    #[tokio::test]
+    async fn can_list_pages_of_custom_driver() {
+        let storage = Storage::single(Box::new(MapStore::default()));
+        for path in ["1.txt", "2.txt", "3.txt"] {
+            storage
+                .upload(Path::new(path), &Bytes::from(path))
+                .await
+                .unwrap();
+        }
+
+        let page = storage
+            .list_with_policy(Path::new(""), Some("1.txt"), 1, &*storage.strategy)
+            .await
+            .unwrap();
+        assert_eq!(
+            page.entries,
+            vec![ListEntry {
+                path: PathBuf::from("2.txt"),
+                size: 5,
+                last_modified: None,
+                content_type: Some("text/plain".to_string()),
+            }]
+        );
+        assert_eq!(page.next_token.as_deref(), Some("2.txt"));
+        assert_eq!(
+            storage
+                .list(Path::new(""), None)
+                .await
+                .unwrap()
+                .entries
+                .len(),
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn can_stream_content() {
+        let store = mem::new();
+        let path = Path::new("file.txt");
+        store.upload(path, &Bytes::from("content")).await.unwrap();
+
+        let chunks: Vec<Bytes> = store
+            .get(path)
+            .await
+            .unwrap()
+            .into_stream()
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(chunks.concat(), b"content");
+
+        let chunks: Vec<Bytes> = GetResponse::from_bytes(Bytes::from("content"))
+            .into_stream()
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(chunks.concat(), b"content");
+    }
+
+    #[tokio::test]
+    async fn can_stream_range() {
+        let store = mem::new();
+        let path = Path::new("file.txt");
+        store.upload(path, &Bytes::from("content")).await.unwrap();
+        assert_eq!(store.size(path).await.unwrap(), 7);
+
+        let chunks: Vec<Bytes> = store
+            .get(path)
+            .await
+            .unwrap()
+            .into_range_stream(1..4)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(chunks.concat(), b"ont");
+
+        let custom = MapStore::default();
+        custom.upload(path, &Bytes::from("content")).await.unwrap();
+        assert_eq!(custom.size(path).await.unwrap(), 7);
+        let chunks: Vec<Bytes> = custom
+            .get(path)
+            .await
+            .unwrap()
+            .into_range_stream(4..7)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(chunks.concat(), b"ent");
+    }
 }
@@ -4,7 +4,7 @@
 //! Loco framework is initialized. The primary purpose of this driver is to
 //! simplify the user workflow by avoiding the need for feature flags or
 //! optional storage driver configurations.
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use async_trait::async_trait;
 use bytes::Bytes;
@@ -22,6 +22,25 @@ pub fn new() -> Box<dyn StoreDriver> {
 
 #[async_trait]
 impl StoreDriver for NullStorage {
+    fn is_null(&self) -> bool {
+        true
+    }
+
+    fn backend(&self) -> String {
+        "null".to_string()
+    }
+
+    /// The null storage stores nothing, so is never usable.
+    ///
+    /// # Errors
+    ///
+    /// Always returns an error
+    async fn check(&self) -> StorageResult<()> {
+        Err(StorageError::Any(
+            "no storage is configured: set `ctx.storage` in `Hooks::after_context`".into(),
+        ))
+    }
+
     /// Uploads the content represented by `Bytes` to the specified path in the
     /// object store.
     ///
@@ -92,4 +111,15 @@ impl StoreDriver for NullStorage {
             "Operation not supported by null storage".into(),
         ))
     }
+
+    /// Lists the paths of all content stored under the given prefix.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the listed paths.
+    async fn list(&self, _prefix: &Path) -> StorageResult<Vec<PathBuf>> {
+        Err(StorageError::Any(
+            "Operation not supported by null storage".into(),
+        ))
+    }
 }
@@ -1,11 +1,11 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use async_trait::async_trait;
 use bytes::Bytes;
-use futures_util::SinkExt;
-use opendal::{layers::RetryLayer, Operator};
+use futures_util::{SinkExt, TryStreamExt};
+use opendal::{layers::RetryLayer, Metakey, Operator};
 
-use super::{GetResponse, StoreDriver, UploadResponse};
+use super::{paginate, GetResponse, ListEntry, ListPage, StoreDriver, UploadResponse};
 use crate::storage::{StorageError, StorageResult};
 
 pub struct OpendalAdapter {
@@ -25,6 +25,44 @@ impl OpendalAdapter {
 
 #[async_trait]
 impl StoreDriver for OpendalAdapter {
+    fn is_local(&self) -> bool {
+        self.opendal_impl.info().scheme() == opendal::Scheme::Fs
+    }
+
+    fn backend(&self) -> String {
+        let info = self.opendal_impl.info();
+        let mut backend = info.scheme().to_string();
+        if !info.name().is_empty() {
+            backend.push_str(&format!(" `{}`", info.name()));
+        }
+        backend.push_str(&format!(" at `{}`", info.root()));
+        backend
+    }
+
+    /// Checks that the backend is reachable, and for the file system that the
+    /// root directory is writable.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the reason the backend is unusable.
+    async fn check(&self) -> StorageResult<()> {
+        self.opendal_impl.check().await?;
+        let info = self.opendal_impl.info();
+        if info.scheme() == opendal::Scheme::Fs {
+            let readonly = tokio::fs::metadata(info.root())
+                .await
+                .map_err(|err| StorageError::Any(Box::new(err)))?
+                .permissions()
+                .readonly();
+            if readonly {
+                return Err(StorageError::Any(
+                    format!("the directory `{}` is not writable", info.root()).into(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
     /// Uploads the content represented by `Bytes` to the specified path in the
     /// object store.
     ///
@@ -141,4 +179,101 @@ impl StoreDriver for OpendalAdapter {
         let path = path.display().to_string();
         Ok(self.opendal_impl.exists(&path).await.unwrap_or(false))
     }
+
+    /// Lists the paths of all content stored under the given prefix,
+    /// recursively.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the listed paths.
+    async fn list(&self, prefix: &Path) -> StorageResult<Vec<PathBuf>> {
+        let entries = self
+            .opendal_impl
+            .list_with(&prefix.display().to_string())
+            .recursive(true)
+            .await?;
+        Ok(entries
+            .into_iter()
+            .filter(|entry| entry.metadata().is_file())
+            .map(|entry| PathBuf::from(entry.path()))
+            .collect())
+    }
+
+    /// Lists a page of the objects under the given prefix, recursively, with
+    /// their metadata.
+    ///
+    /// # Behavior
+    ///
+    /// Backends listing in order from a key, like S3, list only the page.
+    /// Others list all the objects to sort them.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the listed page.
+    async fn list_page(
+        &self,
+        prefix: &Path,
+        token: Option<&str>,
+        limit: usize,
+    ) -> StorageResult<ListPage> {
+        let ordered = self
+            .opendal_impl
+            .info()
+            .full_capability()
+            .list_with_start_after;
+        let mut lister = self
+            .opendal_impl
+            .lister_with(&prefix.display().to_string())
+            .recursive(true)
+            .metakey(
+                Metakey::Mode
+                    | Metakey::ContentLength
+                    | Metakey::LastModified
+                    | Metakey::ContentType,
+            );
+        if ordered {
+            if let Some(token) = token {
+                lister = lister.start_after(token);
+            }
+            lister = lister.limit(limit + 1);
+        }
+        let mut lister = lister.await?;
+
+        let mut entries = Vec::new();
+        while let Some(entry) = lister.try_next().await? {
+            let metadata = entry.metadata();
+            if !metadata.is_file() {
+                continue;
+            }
+            let path = PathBuf::from(entry.path());
+            entries.push(ListEntry {
+                size: metadata.content_length(),
+                last_modified: metadata.last_modified(),
+                content_type: metadata
+                    .content_type()
+                    .map(ToString::to_string)
+                    .or_else(|| ListEntry::guess_content_type(&path)),
+                path,
+            });
+            if ordered && entries.len() > limit {
+                break;
+            }
+        }
+        let (entries, next_token) = paginate(entries, |entry| &entry.path, token, limit);
+        Ok(ListPage {
+            entries,
+            next_token,
+        })
+    }
+
+    /// Returns the size in bytes of the content at the given path, from its
+    /// metadata.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the content size.
+    async fn size(&self, path: &Path) -> StorageResult<u64> {
+        let metadata = self.opendal_impl.stat(&path.display().to_string()).await?;
+        Ok(metadata.content_length())
+    }
 }
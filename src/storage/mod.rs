@@ -2,7 +2,8 @@
 //!
 //! This module defines a generic storage abstraction represented by the
 //! [`Storage`] struct. It provides methods for performing common storage
-//! operations such as upload, download, delete, rename, and copy.
+//! operations such as upload, download, delete, rename (move), copy and
+//! list.
 //!
 //! ## Storage Strategy
 //!
@@ -20,7 +21,10 @@ use std::{
 
 use bytes::Bytes;
 
-use self::drivers::StoreDriver;
+use self::drivers::{ListPage, StoreDriver};
+
+/// The number of objects listed per page by [`Storage::list`].
+pub const LIST_PAGE_SIZE: usize = 1000;
 
 #[derive(thiserror::Error, Debug)]
 #[allow(clippy::module_name_repetitions)]
@@ -37,17 +41,49 @@ pub enum StorageError {
     #[error("secondaries errors")]
     Multi(BTreeMap<String, String>),
 
+    #[error("store `{store}` ({backend}) is unavailable: {reason}")]
+    StorageUnavailable {
+        store: String,
+        backend: String,
+        reason: String,
+    },
+
+    #[error("the request deadline expired before the storage operation completed")]
+    DeadlineExceeded,
+
+    #[error("the {backend} store does not support `{operation}`")]
+    Unsupported {
+        operation: &'static str,
+        backend: String,
+    },
+
     #[error(transparent)]
     Any(#[from] Box<dyn std::error::Error + Send + Sync>),
 }
 
 pub type StorageResult<T> = std::result::Result<T, StorageError>;
 
+/// Runs a storage operation until the deadline of the current request, if
+/// any, see [`crate::deadline`].
+async fn until_deadline<T>(
+    operation: impl std::future::Future<Output = StorageResult<T>>,
+) -> StorageResult<T> {
+    crate::deadline::within(operation)
+        .await
+        .unwrap_or(Err(StorageError::DeadlineExceeded))
+}
+
 pub struct Storage {
     pub stores: BTreeMap<String, Box<dyn StoreDriver>>,
     pub strategy: Box<dyn strategies::StorageStrategy>,
+    /// The outcome of checking the stores on first use, the unavailable store
+    /// if any.
+    available: tokio::sync::OnceCell<Option<Unavailable>>,
 }
 
+/// A store which failed its check, as `(store, backend, reason)`.
+type Unavailable = (String, String, String);
+
 impl Storage {
     /// Creates a new storage instance with a single store and the default
     /// strategy.
@@ -64,6 +100,7 @@ impl Storage {
         Self {
             strategy: Box::new(strategies::single::SingleStrategy::new(default_key)),
             stores: BTreeMap::from([(default_key.to_string(), store)]),
+            available: tokio::sync::OnceCell::new(),
         }
     }
 
@@ -73,7 +110,55 @@ impl Storage {
         stores: BTreeMap<String, Box<dyn StoreDriver>>,
         strategy: Box<dyn strategies::StorageStrategy>,
     ) -> Self {
-        Self { stores, strategy }
+        Self {
+            stores,
+            strategy,
+            available: tokio::sync::OnceCell::new(),
+        }
+    }
+
+    /// Whether any store of this storage actually stores content, as opposed
+    /// to the null storage apps boot with.
+    #[must_use]
+    pub fn is_configured(&self) -> bool {
+        self.stores.values().any(|store| !store.is_null())
+    }
+
+    /// Checks that every store is usable, once: a missing bucket or a
+    /// read-only directory fails the first operation with a clear error
+    /// rather than an obscure one later. The outcome is cached, so the next
+    /// operations do not check again.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StorageError::StorageUnavailable`] with the first store which
+    /// failed its check.
+    pub async fn ensure_available(&self) -> StorageResult<()> {
+        let unavailable = self
+            .available
+            .get_or_init(|| async {
+                for (name, store) in &self.stores {
+                    if let Err(err) = store.check().await {
+                        tracing::error!(
+                            store = name,
+                            backend = store.backend(),
+                            error = err.to_string(),
+                            "storage is unavailable"
+                        );
+                        return Some((name.clone(), store.backend(), err.to_string()));
+                    }
+                }
+                None
+            })
+            .await;
+        match unavailable {
+            Some((store, backend, reason)) => Err(StorageError::StorageUnavailable {
+                store: store.clone(),
+                backend: backend.clone(),
+                reason: reason.clone(),
+            }),
+            None => Ok(()),
+        }
     }
 
     /// Uploads content to the storage at the specified path.
@@ -119,7 +204,8 @@ impl Storage {
         content: &Bytes,
         strategy: &dyn strategies::StorageStrategy,
     ) -> StorageResult<()> {
-        strategy.upload(self, path, content).await
+        self.ensure_available().await?;
+        until_deadline(strategy.upload(self, path, content)).await
     }
 
     /// Downloads content from the storage at the specified path.
@@ -165,7 +251,8 @@ impl Storage {
         path: &Path,
         strategy: &dyn strategies::StorageStrategy,
     ) -> StorageResult<T> {
-        let res = strategy.download(self, path).await?;
+        self.ensure_available().await?;
+        let res = until_deadline(strategy.download(self, path)).await?;
         contents::Contents::from(res).try_into().map_or_else(
             |_| {
                 Err(StorageError::UnableToReadBytes {
@@ -219,10 +306,13 @@ impl Storage {
         path: &Path,
         strategy: &dyn strategies::StorageStrategy,
     ) -> StorageResult<()> {
-        strategy.delete(self, path).await
+        self.ensure_available().await?;
+        until_deadline(strategy.delete(self, path)).await
     }
 
-    /// Renames content from one path to another in the storage.
+    /// Renames content from one path to another in the storage, moving it.
+    /// Stores that cannot rename, like S3, copy it server-side and delete the
+    /// source.
     ///
     /// This method uses the selected strategy for the rename operation.
     ///
@@ -269,7 +359,8 @@ impl Storage {
         to: &Path,
         strategy: &dyn strategies::StorageStrategy,
     ) -> StorageResult<()> {
-        strategy.rename(self, from, to).await
+        self.ensure_available().await?;
+        until_deadline(strategy.rename(self, from, to)).await
     }
 
     /// Copies content from one path to another in the storage.
@@ -318,7 +409,65 @@ impl Storage {
         to: &Path,
         strategy: &dyn strategies::StorageStrategy,
     ) -> StorageResult<()> {
-        strategy.copy(self, from, to).await
+        self.ensure_available().await?;
+        until_deadline(strategy.copy(self, from, to)).await
+    }
+
+    /// Lists the objects under `prefix`, recursively, with their size,
+    /// modification time and content type, a page of [`LIST_PAGE_SIZE`]
+    /// objects at a time. The first page is listed without a token, and the
+    /// next ones with the `next_token` of the previous page.
+    ///
+    /// This method uses the selected strategy for the list operation.
+    ///
+    /// # Examples
+    ///```
+    /// use loco_rs::storage;
+    /// use std::path::Path;
+    /// use bytes::Bytes;
+    /// pub async fn list() {
+    ///     let storage = storage::Storage::single(storage::drivers::mem::new());
+    ///     storage.upload(Path::new("users/1.txt"), &Bytes::from("Loco!")).await;
+    ///
+    ///     let mut token = None;
+    ///     loop {
+    ///         let page = storage.list(Path::new("users/"), token.as_deref()).await.unwrap();
+    ///         for entry in page.entries {
+    ///             println!("{} {}", entry.path.display(), entry.size);
+    ///         }
+    ///         token = page.next_token;
+    ///         if token.is_none() {
+    ///             break;
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the list operation fails or if there is
+    /// an issue with the strategy configuration.
+    pub async fn list(&self, prefix: &Path, token: Option<&str>) -> StorageResult<ListPage> {
+        self.list_with_policy(prefix, token, LIST_PAGE_SIZE, &*self.strategy)
+            .await
+    }
+
+    /// Lists a page of at most `limit` objects under `prefix` using a specific
+    /// strategy.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the list operation fails or if there is
+    /// an issue with the strategy configuration.
+    pub async fn list_with_policy(
+        &self,
+        prefix: &Path,
+        token: Option<&str>,
+        limit: usize,
+        strategy: &dyn strategies::StorageStrategy,
+    ) -> StorageResult<ListPage> {
+        self.ensure_available().await?;
+        until_deadline(strategy.list(self, prefix, token, limit)).await
     }
 
     /// Returns a reference to the store with the specified name if exists.
@@ -365,3 +514,55 @@ impl Storage {
             .ok_or(StorageError::StoreNotFound(name.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn can_fail_on_unavailable_storage() {
+        let storage = Storage::new(
+            BTreeMap::from([
+                ("mem".to_string(), drivers::mem::new()),
+                ("null".to_string(), drivers::null::new()),
+            ]),
+            Box::new(strategies::single::SingleStrategy::new("mem")),
+        );
+
+        let err = storage
+            .upload(Path::new("file.txt"), &Bytes::from("Loco!"))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            StorageError::StorageUnavailable { ref store, ref backend, .. }
+                if store == "null" && backend == "null"
+        ));
+        assert!(matches!(
+            storage.list(Path::new(""), None).await,
+            Err(StorageError::StorageUnavailable { .. })
+        ));
+
+        let storage = Storage::single(drivers::mem::new());
+        assert!(storage
+            .upload(Path::new("file.txt"), &Bytes::from("Loco!"))
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn can_stop_at_the_request_deadline() {
+        let storage = Storage::single(drivers::mem::new());
+        let deadline = crate::deadline::Deadline::after(std::time::Duration::from_secs(5));
+        deadline.cancel();
+
+        let result = deadline
+            .scope(storage.upload(Path::new("file.txt"), &Bytes::from("Loco!")))
+            .await;
+        assert!(matches!(result, Err(StorageError::DeadlineExceeded)));
+        assert!(storage
+            .download::<String>(Path::new("file.txt"))
+            .await
+            .is_err());
+    }
+}
@@ -24,7 +24,9 @@ use std::{collections::BTreeMap, path::Path};
 
 use bytes::Bytes;
 
-use crate::storage::{strategies::StorageStrategy, Storage, StorageError, StorageResult};
+use crate::storage::{
+    drivers::ListPage, strategies::StorageStrategy, Storage, StorageError, StorageResult,
+};
 
 /// Enum representing the failure mode for the [`MirrorStrategy`].
 #[derive(Clone, Debug)]
@@ -211,6 +213,24 @@ impl StorageStrategy for MirrorStrategy {
 
         Ok(())
     }
+
+    /// Lists a page of the objects of the primary storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the listed page.
+    async fn list(
+        &self,
+        storage: &Storage,
+        prefix: &Path,
+        token: Option<&str>,
+        limit: usize,
+    ) -> StorageResult<ListPage> {
+        storage
+            .as_store_err(&self.primary)?
+            .list_page(prefix, token, limit)
+            .await
+    }
 }
 
 impl MirrorStrategy {
@@ -6,7 +6,7 @@ use std::path::Path;
 
 use bytes::Bytes;
 
-use crate::storage::{Storage, StorageResult};
+use crate::storage::{drivers::ListPage, Storage, StorageError, StorageResult};
 
 #[async_trait::async_trait]
 pub trait StorageStrategy: Sync + Send {
@@ -15,4 +15,18 @@ pub trait StorageStrategy: Sync + Send {
     async fn delete(&self, storage: &Storage, path: &Path) -> StorageResult<()>;
     async fn rename(&self, storage: &Storage, from: &Path, to: &Path) -> StorageResult<()>;
     async fn copy(&self, storage: &Storage, from: &Path, to: &Path) -> StorageResult<()>;
+
+    /// Lists a page of the objects under `prefix`, from the store the
+    /// strategy downloads from. Not supported by default.
+    async fn list(
+        &self,
+        _storage: &Storage,
+        _prefix: &Path,
+        _token: Option<&str>,
+        _limit: usize,
+    ) -> StorageResult<ListPage> {
+        Err(StorageError::Any(
+            "listing is not supported by this storage strategy".into(),
+        ))
+    }
 }
@@ -6,7 +6,7 @@ use std::path::Path;
 
 use bytes::Bytes;
 
-use crate::storage::{strategies::StorageStrategy, Storage, StorageResult};
+use crate::storage::{drivers::ListPage, strategies::StorageStrategy, Storage, StorageResult};
 
 /// Represents a single storage strategy.
 #[derive(Clone)]
@@ -80,6 +80,24 @@ impl StorageStrategy for SingleStrategy {
     async fn copy(&self, storage: &Storage, from: &Path, to: &Path) -> StorageResult<()> {
         Ok(storage.as_store_err(&self.primary)?.copy(from, to).await?)
     }
+
+    /// Lists a page of the objects of the primary storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the listed page.
+    async fn list(
+        &self,
+        storage: &Storage,
+        prefix: &Path,
+        token: Option<&str>,
+        limit: usize,
+    ) -> StorageResult<ListPage> {
+        storage
+            .as_store_err(&self.primary)?
+            .list_page(prefix, token, limit)
+            .await
+    }
 }
 
 #[cfg(test)]
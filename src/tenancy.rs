@@ -0,0 +1,219 @@
+//! # Schema-per-tenant Multitenancy
+//!
+//! With Postgres, each tenant of the app can get its own schema, holding its
+//! own copy of the app tables. The tenants listed in `database.tenants` get
+//! their schema created and migrated on boot, along with the main schema:
+//!
+//! ```yaml
+//! database:
+//!   uri: {{ get_env(name="DATABASE_URL") }}
+//!   migrate_on_boot: auto
+//!   tenants:
+//!     - acme
+//!     - globex
+//! ```
+//!
+//! Requests are bound to a tenant by the `tenant` middleware, from a header
+//! or a subdomain, and handlers query its schema through the
+//! [`crate::controller::extractor::tenant::TenantDb`] extractor, a
+//! transaction with the tenant schema first in its `search_path`.
+//!
+//! A tenant name is also its schema name, so it must be a lowercase
+//! identifier: ASCII letters, digits and underscores, starting with a letter,
+//! at most 63 characters. The main schema, `public`, and the system schemas
+//! are not tenants.
+use sea_orm::{
+    ConnectOptions, ConnectionTrait, Database, DatabaseConnection, DatabaseTransaction, Statement,
+    TransactionTrait,
+};
+use sea_orm_migration::MigratorTrait;
+use tracing::{debug, info};
+
+use crate::{
+    config::{self, MigrateOnBoot},
+    controller::middleware::tenant::{self, is_valid_name},
+    Error, Result,
+};
+
+/// Returns the schema of `tenant`.
+///
+/// # Errors
+///
+/// When `tenant` is not a valid schema name
+pub fn schema_name(tenant: &str) -> Result<&str> {
+    if is_valid_name(tenant) {
+        Ok(tenant)
+    } else {
+        Err(Error::BadRequest(format!("invalid tenant `{tenant}`")))
+    }
+}
+
+/// Creates the schema of `tenant`, when it does not exist yet.
+///
+/// # Errors
+///
+/// When the tenant is invalid or the schema could not be created
+pub async fn create_schema(db: &DatabaseConnection, tenant: &str) -> Result<()> {
+    let schema = schema_name(tenant)?;
+    db.execute_unprepared(&format!("CREATE SCHEMA IF NOT EXISTS \"{schema}\""))
+        .await?;
+    Ok(())
+}
+
+/// Connects to the database of `config` with the schema of `tenant` first in
+/// the `search_path`, to migrate it.
+async fn connect(config: &config::Database, tenant: &str) -> Result<DatabaseConnection> {
+    let schema = schema_name(tenant)?;
+    let mut opt = ConnectOptions::new(&config.uri);
+    opt.max_connections(1)
+        .min_connections(1)
+        .sqlx_logging(config.enable_logging)
+        .set_schema_search_path(schema);
+    Ok(Database::connect(opt).await?)
+}
+
+/// Creates the schema of `tenant` and runs the pending migrations in it. The
+/// migrations of each tenant are tracked in its own schema.
+///
+/// # Errors
+///
+/// When the schema could not be created or migrated
+pub async fn migrate<M: MigratorTrait>(
+    db: &DatabaseConnection,
+    config: &config::Database,
+    tenant: &str,
+) -> Result<()> {
+    create_schema(db, tenant).await?;
+    let tenant_db = connect(config, tenant).await?;
+    M::up(&tenant_db, None).await?;
+    tenant_db.close().await?;
+    Ok(())
+}
+
+/// Returns the pending migrations of `tenant`.
+///
+/// # Errors
+///
+/// When the migrations could not be read
+pub async fn pending_migrations<M: MigratorTrait>(
+    config: &config::Database,
+    tenant: &str,
+) -> Result<Vec<String>> {
+    let tenant_db = connect(config, tenant).await?;
+    let pending = M::get_pending_migrations(&tenant_db)
+        .await?
+        .iter()
+        .map(|migration| format!("{tenant}: {}", migration.name()))
+        .collect();
+    tenant_db.close().await?;
+    Ok(pending)
+}
+
+/// Migrates or checks the schemas of the `tenants` of `config`, following
+/// `migrate_on_boot`.
+///
+/// # Errors
+///
+/// When a schema could not be migrated, or has pending migrations with
+/// `migrate_on_boot: check`
+pub async fn converge<M: MigratorTrait>(
+    db: &DatabaseConnection,
+    config: &config::Database,
+) -> Result<()> {
+    match config.migrate_on_boot() {
+        MigrateOnBoot::Auto => {
+            for tenant in &config.tenants {
+                info!(tenant, "auto migrating tenant");
+                migrate::<M>(db, config, tenant).await?;
+            }
+        }
+        MigrateOnBoot::Check => {
+            let mut pending = Vec::new();
+            for tenant in &config.tenants {
+                create_schema(db, tenant).await?;
+                pending.extend(pending_migrations::<M>(config, tenant).await?);
+            }
+            if !pending.is_empty() {
+                return Err(Error::PendingMigrations(pending));
+            }
+        }
+        MigrateOnBoot::Off => {}
+    }
+    Ok(())
+}
+
+/// Begins a transaction scoped to the schema of `tenant`.
+///
+/// The tenant schema is first in the `search_path` of the transaction, and
+/// the main schema (`public`) second. The `search_path` is reset when the
+/// transaction ends, so the connection goes back to the pool unscoped.
+///
+/// # Errors
+///
+/// When the tenant is invalid, has no schema, or the transaction could not
+/// begin
+pub async fn scoped(db: &DatabaseConnection, tenant: &str) -> Result<DatabaseTransaction> {
+    let schema = schema_name(tenant)?;
+    let txn = db.begin().await?;
+    // without its schema, the tables of a tenant would resolve to the main
+    // schema ones
+    let exists = txn
+        .query_one(Statement::from_sql_and_values(
+            txn.get_database_backend(),
+            "SELECT 1 FROM pg_namespace WHERE nspname = $1",
+            [schema.into()],
+        ))
+        .await?
+        .is_some();
+    if !exists {
+        debug!(tenant, "no schema for the tenant");
+        return Err(tenant::not_found());
+    }
+    txn.execute_unprepared(&format!("SET LOCAL search_path TO \"{schema}\", public"))
+        .await?;
+    Ok(txn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_validate_schema_names() {
+        for tenant in ["acme", "acme_2", "a"] {
+            assert_eq!(schema_name(tenant).unwrap(), tenant);
+        }
+        for tenant in [
+            "",
+            "Acme",
+            "2acme",
+            "_acme",
+            "acme-corp",
+            "acme\"; DROP SCHEMA public; --",
+            "pg_catalog",
+            "public",
+            "information_schema",
+            &"a".repeat(64),
+        ] {
+            assert!(schema_name(tenant).is_err(), "{tenant}");
+        }
+    }
+
+    #[tokio::test]
+    async fn cannot_scope_unknown_tenants() {
+        let (pg_url, _container) = crate::tests_cfg::postgres::setup_postgres_container().await;
+        let db = Database::connect(&pg_url).await.unwrap();
+        create_schema(&db, "acme").await.unwrap();
+
+        let txn = scoped(&db, "acme").await.unwrap();
+        txn.commit().await.unwrap();
+        assert!(matches!(
+            scoped(&db, "globex").await,
+            Err(Error::CustomError(status, _)) if status == axum::http::StatusCode::NOT_FOUND
+        ));
+        assert!(matches!(
+            scoped(&db, "public").await,
+            Err(Error::BadRequest(_))
+        ));
+    }
+}
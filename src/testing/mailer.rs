@@ -0,0 +1,62 @@
+//! # Mailer Assertions
+//!
+//! With `mailer.stub: true` in the test config, mails are recorded in memory
+//! instead of being sent, so tests can assert on them:
+//!
+//! ```rust,ignore
+//! use loco_rs::testing::prelude::*;
+//!
+//! request::<App, _, _>(|request, ctx| async move {
+//!     request.post("/api/auth/forgot").json(&params).await;
+//!
+//!     let mails = sent_mails(&ctx);
+//!     assert_eq!(mails.len(), 1);
+//!     assert_eq!(mails[0].to, "user@loco.rs");
+//! })
+//! .await;
+//! ```
+use crate::{app::AppContext, mailer::Email};
+
+/// Returns the mails sent with the stub mailer of `ctx`, in the order they
+/// were sent. Without a stub mailer, no mails are recorded.
+#[must_use]
+pub fn sent_mails(ctx: &AppContext) -> Vec<Email> {
+    ctx.mailer
+        .as_ref()
+        .map(crate::mailer::EmailSender::sent_mails)
+        .unwrap_or_default()
+}
+
+/// Forgets the mails sent with the stub mailer of `ctx`.
+pub fn clear_sent_mails(ctx: &AppContext) {
+    if let Some(mailer) = &ctx.mailer {
+        mailer.sent.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mailer::EmailSender, tests_cfg};
+
+    #[tokio::test]
+    async fn can_query_sent_mails() {
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        assert!(sent_mails(&ctx).is_empty());
+
+        ctx.mailer = Some(EmailSender::stub());
+        let email = Email {
+            to: "user@loco.rs".to_string(),
+            subject: "Welcome".to_string(),
+            ..Default::default()
+        };
+        ctx.mailer.as_ref().unwrap().mail(&email).await.unwrap();
+
+        let mails = sent_mails(&ctx);
+        assert_eq!(mails.len(), 1);
+        assert_eq!(mails[0].subject, "Welcome");
+
+        clear_sent_mails(&ctx);
+        assert!(sent_mails(&ctx).is_empty());
+    }
+}
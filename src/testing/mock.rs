@@ -0,0 +1,263 @@
+//! # Scripted Database for Unit Tests
+//!
+//! [`MockDb`] is an in-process database connection that returns scripted
+//! results instead of executing SQL, so controllers and models can be unit
+//! tested without a real database or containers.
+//!
+//! It is built on the sea-orm proxy connection (rather than sea-orm's own
+//! `MockDatabase`) so that the resulting [`DatabaseConnection`], and therefore
+//! [`crate::app::AppContext`], stays `Clone`.
+//!
+//! # Limitations
+//!
+//! * SQL is never executed: results are returned in the order they were
+//!   appended, regardless of the statement.
+//! * Running out of scripted results is a [`DbErr`].
+//! * Constraints, transaction isolation and migrations are not emulated.
+//! * Model rows are keyed by column name. Queries selecting aliased columns
+//!   (for example `find_also_related`) need explicit rows via
+//!   [`MockDb::append_query_rows`].
+//! * On Postgres, `insert` and `update` use `RETURNING` and so consume *query*
+//!   results, not exec results.
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use sea_orm::{
+    DatabaseBackend, DatabaseConnection, DbErr, EntityTrait, IdenStatic, Iterable, ModelTrait,
+    ProxyDatabaseConnector, ProxyDatabaseTrait, ProxyExecResult, ProxyRow, Statement, Value,
+};
+
+#[derive(Debug, Default)]
+struct State {
+    query_results: VecDeque<Result<Vec<ProxyRow>, DbErr>>,
+    exec_results: VecDeque<Result<ProxyExecResult, DbErr>>,
+    statements: Vec<Statement>,
+}
+
+/// A scripted database. Clones share the same script and statement log.
+///
+/// # Example
+/// ```rust,ignore
+/// use loco_rs::{app::AppContext, testing::mock::MockDb};
+/// use sea_orm::DatabaseBackend;
+///
+/// let db = MockDb::new(DatabaseBackend::Postgres)
+///     .append_query_results([vec![users::Model { id: 1, ..Default::default() }]]);
+/// let ctx = AppContext::mock_with_db(&db);
+///
+/// let user = users::Entity::find_by_id(1).one(&ctx.db).await?;
+/// assert_eq!(db.statements().len(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockDb {
+    backend: DatabaseBackend,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockDb {
+    /// Create an empty script for the given backend.
+    #[must_use]
+    pub fn new(backend: DatabaseBackend) -> Self {
+        Self {
+            backend,
+            state: Arc::default(),
+        }
+    }
+
+    /// Append query results, one `Vec` of models per expected query.
+    ///
+    /// # Panics
+    /// When the script lock is poisoned
+    #[must_use]
+    pub fn append_query_results<M, I>(self, results: I) -> Self
+    where
+        M: ModelTrait,
+        I: IntoIterator<Item = Vec<M>>,
+    {
+        self.append_query_rows(
+            results
+                .into_iter()
+                .map(|models| models.iter().map(model_to_row).collect()),
+        )
+    }
+
+    /// Append raw query results, one `Vec` of rows (column name to value) per
+    /// expected query.
+    ///
+    /// # Panics
+    /// When the script lock is poisoned
+    #[must_use]
+    pub fn append_query_rows<I>(self, results: I) -> Self
+    where
+        I: IntoIterator<Item = Vec<BTreeMap<String, Value>>>,
+    {
+        self.state.lock().unwrap().query_results.extend(
+            results
+                .into_iter()
+                .map(|rows| Ok(rows.into_iter().map(ProxyRow::from).collect())),
+        );
+        self
+    }
+
+    /// Append errors to be returned by the next queries.
+    ///
+    /// # Panics
+    /// When the script lock is poisoned
+    #[must_use]
+    pub fn append_query_errors<I: IntoIterator<Item = DbErr>>(self, errors: I) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .query_results
+            .extend(errors.into_iter().map(Err));
+        self
+    }
+
+    /// Append results for the next executed (non-query) statements.
+    ///
+    /// # Panics
+    /// When the script lock is poisoned
+    #[must_use]
+    pub fn append_exec_results<I: IntoIterator<Item = ProxyExecResult>>(self, results: I) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .exec_results
+            .extend(results.into_iter().map(Ok));
+        self
+    }
+
+    /// Append errors to be returned by the next executed statements.
+    ///
+    /// # Panics
+    /// When the script lock is poisoned
+    #[must_use]
+    pub fn append_exec_errors<I: IntoIterator<Item = DbErr>>(self, errors: I) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .exec_results
+            .extend(errors.into_iter().map(Err));
+        self
+    }
+
+    /// All statements received so far, in order.
+    ///
+    /// # Panics
+    /// When the script lock is poisoned
+    #[must_use]
+    pub fn statements(&self) -> Vec<Statement> {
+        self.state.lock().unwrap().statements.clone()
+    }
+
+    /// Create a [`DatabaseConnection`] backed by this script.
+    ///
+    /// # Panics
+    /// Never: connecting a proxy database cannot fail
+    #[must_use]
+    pub fn connection(&self) -> DatabaseConnection {
+        ProxyDatabaseConnector::connect(self.backend, Arc::new(Box::new(self.clone())))
+            .expect("proxy connection is infallible")
+    }
+}
+
+fn model_to_row<M: ModelTrait>(model: &M) -> BTreeMap<String, Value> {
+    <<M::Entity as EntityTrait>::Column as Iterable>::iter()
+        .map(|column| (column.as_str().to_string(), model.get(column)))
+        .collect()
+}
+
+fn exhausted(kind: &str, statement: &Statement) -> DbErr {
+    DbErr::Custom(format!(
+        "mock database has no more {kind} results for statement: {statement}"
+    ))
+}
+
+#[async_trait::async_trait]
+impl ProxyDatabaseTrait for MockDb {
+    async fn query(&self, statement: Statement) -> Result<Vec<ProxyRow>, DbErr> {
+        let mut state = self.state.lock().unwrap();
+        let result = state
+            .query_results
+            .pop_front()
+            .unwrap_or_else(|| Err(exhausted("query", &statement)));
+        state.statements.push(statement);
+        result
+    }
+
+    async fn execute(&self, statement: Statement) -> Result<ProxyExecResult, DbErr> {
+        let mut state = self.state.lock().unwrap();
+        let result = state
+            .exec_results
+            .pop_front()
+            .unwrap_or_else(|| Err(exhausted("exec", &statement)));
+        state.statements.push(statement);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{ConnectionTrait, DatabaseBackend, EntityTrait};
+
+    use super::*;
+    use crate::tests_cfg::db::test_db;
+
+    fn model(id: i32) -> test_db::Model {
+        test_db::Model {
+            id,
+            name: "loco".to_string(),
+            created_at: chrono::NaiveDateTime::default(),
+            updated_at: chrono::NaiveDateTime::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn can_return_scripted_models() {
+        let db = MockDb::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![model(1)], vec![model(1), model(2)]]);
+        let conn = db.connection();
+
+        let found = test_db::Entity::find_by_id(1).one(&conn).await.unwrap();
+        assert_eq!(found, Some(model(1)));
+
+        let all = test_db::Entity::find().all(&conn).await.unwrap();
+        assert_eq!(all, vec![model(1), model(2)]);
+
+        let statements = db.statements();
+        assert_eq!(statements.len(), 2);
+        assert_eq!(
+            statements[0].sql,
+            r#"SELECT "loco"."id", "loco"."name", "loco"."created_at", "loco"."updated_at" FROM "loco" WHERE "loco"."id" = $1 LIMIT $2"#
+        );
+    }
+
+    #[tokio::test]
+    async fn can_return_scripted_errors() {
+        let db = MockDb::new(DatabaseBackend::Sqlite)
+            .append_query_errors([DbErr::Custom("boom".to_string())]);
+        let conn = db.connection();
+
+        let res = test_db::Entity::find().all(&conn).await;
+        assert_eq!(res, Err(DbErr::Custom("boom".to_string())));
+
+        // the script is now exhausted
+        let res = test_db::Entity::find().all(&conn).await;
+        assert!(
+            matches!(res, Err(DbErr::Custom(msg)) if msg.starts_with("mock database has no more query results"))
+        );
+    }
+
+    #[tokio::test]
+    async fn can_return_exec_results() {
+        let db =
+            MockDb::new(DatabaseBackend::Sqlite).append_exec_results([ProxyExecResult::new(0, 3)]);
+        let conn = db.connection();
+
+        let res = conn.execute_unprepared("DELETE FROM loco").await.unwrap();
+        assert_eq!(res.rows_affected(), 3);
+        assert_eq!(db.statements()[0].sql, "DELETE FROM loco");
+    }
+}
@@ -1,6 +1,10 @@
 #[cfg(feature = "with-db")]
 pub mod db;
+pub mod mailer;
+#[cfg(feature = "with-db")]
+pub mod mock;
 pub mod prelude;
 pub mod redaction;
 pub mod request;
 pub mod selector;
+pub mod time;
@@ -1,3 +1,4 @@
+pub use crate::clock::FrozenClock;
 #[cfg(feature = "with-db")]
 pub use crate::testing::db::*;
-pub use crate::testing::{redaction::*, request::*, selector::*};
+pub use crate::testing::{mailer::*, redaction::*, request::*, selector::*, time::*};
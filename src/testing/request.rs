@@ -1,4 +1,4 @@
-use std::net::SocketAddr;
+use std::{net::SocketAddr, sync::Arc};
 
 use axum_test::{TestServer, TestServerConfig};
 use tokio::net::TcpListener;
@@ -9,6 +9,7 @@ use crate::Error;
 use crate::{
     app::{AppContext, Hooks},
     boot::{self, BootResult},
+    clock::{self, Clock},
     config::Server,
     environment::Environment,
     Result,
@@ -189,6 +190,31 @@ pub async fn boot_test<H: Hooks>() -> Result<BootResult> {
     Ok(boot)
 }
 
+/// Bootstraps test application with test environment hard coded, and `clock`
+/// as the clock of its [`AppContext`], so time-sensitive behavior can be
+/// tested without sleeping.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use myapp::app::App;
+/// use loco_rs::testing::prelude::*;
+///
+/// #[tokio::test]
+/// async fn test_token_expiry() {
+///     let clock = FrozenClock::new(chrono::Utc::now());
+///     let boot = boot_test_with_clock::<App>(clock.clone()).await.unwrap();
+///     // ... sign in, then make the token expire
+///     clock.advance(chrono::Duration::days(30));
+/// }
+/// ```
+///
+/// # Errors
+/// when could not bootstrap the test environment
+pub async fn boot_test_with_clock<H: Hooks>(clock: impl Clock + 'static) -> Result<BootResult> {
+    clock::with_boot_clock(Arc::new(clock), boot_test::<H>()).await
+}
+
 /// Bootstraps the test application with a test environment and creates a new database.
 ///
 /// This function initializes the test environment and sets up a fresh database for testing.
@@ -210,9 +236,10 @@ pub async fn boot_test<H: Hooks>() -> Result<BootResult> {
 #[cfg(feature = "with-db")]
 pub async fn boot_test_with_create_db<H: Hooks>() -> Result<BootResultWrapper> {
     let mut config = H::load_config(&Environment::Test).await?;
-    let test_db = super::db::init_test_db_creation(&config.database.uri)?;
+    let database = config.database.as_mut().ok_or(Error::DbNotConfigured)?;
+    let test_db = super::db::init_test_db_creation(&database.uri)?;
     test_db.init_db().await;
-    config.database.uri = test_db.get_connection_str().to_string();
+    database.uri = test_db.get_connection_str().to_string();
     let boot = match H::boot(boot::StartMode::ServerOnly, &Environment::Test, config).await {
         Ok(boot) => boot,
         Err(err) => {
@@ -0,0 +1,440 @@
+//! # Time Travel
+//!
+//! Tests of time-based behavior, like a reminder sent a day after sign-up,
+//! move the clock of a test app with [`TestContext::travel_to`] instead of
+//! waiting: the delayed jobs of [`crate::bgworker::BackgroundWorker::perform_at`]
+//! and the scheduler jobs due on the way are performed, in time order,
+//! before `travel_to` returns.
+//!
+//! Delayed jobs are only held for travelling with the in-process worker
+//! modes, `ForegroundBlocking` being the mode of the test configuration. With
+//! the `BackgroundQueue` mode they are stored by the queue provider, which
+//! runs them on the time of its database rather than of the test clock.
+//!
+//! ```rust,ignore
+//! use loco_rs::testing::prelude::*;
+//!
+//! #[tokio::test]
+//! async fn test_sends_reminder() {
+//!     let test = TestContext::boot::<App>(chrono::Utc::now()).await.unwrap();
+//!     // ... sign up, which calls `ReminderWorker::perform_in(&ctx, args, Duration::hours(24))`
+//!     test.travel(chrono::Duration::hours(24)).await.unwrap();
+//!     // ... assert the reminder was delivered
+//! }
+//! ```
+use std::{str::FromStr, sync::Arc};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    app::{AppContext, Hooks},
+    bgworker::delayed::{DelayedJob, DelayedJobs},
+    boot::BootResult,
+    clock::{Clock, FrozenClock},
+    scheduler::{self, Job, Output},
+    task::{Tasks, Vars},
+    testing::request::boot_test_with_clock,
+    Result,
+};
+
+/// A scheduler job and its parsed schedule.
+struct ScheduledJob {
+    name: String,
+    job: Job,
+    schedule: cron::Schedule,
+}
+
+/// A test app on a frozen clock, performing the jobs due when its time moves.
+pub struct TestContext {
+    pub boot: BootResult,
+    pub clock: FrozenClock,
+    tasks: Tasks,
+    scheduled: Vec<ScheduledJob>,
+}
+
+/// What is due next while travelling.
+enum Due {
+    Delayed(DateTime<Utc>),
+    /// The scheduler job at an index of the scheduled jobs.
+    Scheduled(DateTime<Utc>, usize),
+}
+
+impl TestContext {
+    /// Bootstraps the test app of `H` on a frozen clock set to `now`, with the
+    /// scheduler jobs of its configuration.
+    ///
+    /// # Errors
+    ///
+    /// When the test app could not boot, or a scheduler job has an invalid
+    /// schedule
+    pub async fn boot<H: Hooks>(now: DateTime<Utc>) -> Result<Self> {
+        let clock = FrozenClock::new(now);
+        let boot = boot_test_with_clock::<H>(clock.clone()).await?;
+        Self::new::<H>(boot, clock)
+    }
+
+    /// Travels with an app booted on `clock`, registering the tasks of `H` to
+    /// perform the scheduler jobs running tasks.
+    ///
+    /// # Errors
+    ///
+    /// When a scheduler job of the configuration has an invalid schedule
+    pub fn new<H: Hooks>(mut boot: BootResult, clock: FrozenClock) -> Result<Self> {
+        boot.app_context.clock = Arc::new(clock.clone());
+        boot.app_context.shared_store.insert(DelayedJobs::default());
+        let mut tasks = Tasks::default();
+        H::register_tasks(&mut tasks);
+        let scheduler = boot.app_context.config.scheduler.clone();
+        let test = Self {
+            boot,
+            clock,
+            tasks,
+            scheduled: Vec::new(),
+        };
+        match scheduler {
+            Some(config) => test.with_scheduler(&config),
+            None => Ok(test),
+        }
+    }
+
+    /// Replaces the scheduler jobs performed when travelling by the jobs of
+    /// `config`.
+    ///
+    /// # Errors
+    ///
+    /// When a job has an invalid schedule
+    pub fn with_scheduler(mut self, config: &scheduler::Config) -> Result<Self> {
+        let mut scheduled = Vec::new();
+        for (name, job) in &config.jobs {
+            let cron = job.cron_syntax()?;
+            let schedule = cron::Schedule::from_str(&cron).map_err(|err| {
+                scheduler::Error::InvalidCronSyntax {
+                    cron,
+                    error: err.to_string(),
+                }
+            })?;
+            scheduled.push(ScheduledJob {
+                name: name.clone(),
+                job: job.clone(),
+                schedule,
+            });
+        }
+        // jobs due at the same time run in the order of their names
+        scheduled.sort_by(|a, b| a.name.cmp(&b.name));
+        self.scheduled = scheduled;
+        Ok(self)
+    }
+
+    #[must_use]
+    pub const fn ctx(&self) -> &AppContext {
+        &self.boot.app_context
+    }
+
+    /// The number of delayed jobs not performed yet.
+    #[must_use]
+    pub fn pending_jobs(&self) -> usize {
+        self.ctx()
+            .shared_store
+            .get_ref::<DelayedJobs>()
+            .map_or(0, |jobs| jobs.len())
+    }
+
+    /// Moves the clock forward by `duration`, see [`TestContext::travel_to`].
+    ///
+    /// # Errors
+    ///
+    /// When a job due on the way failed
+    pub async fn travel(&self, duration: Duration) -> Result<()> {
+        self.travel_to(self.clock.now() + duration).await
+    }
+
+    /// Moves the clock to `at`, performing the delayed and scheduler jobs due
+    /// up to `at` in time order, each with the clock at the time it was due.
+    /// The jobs these jobs delay are performed too when due by `at`.
+    ///
+    /// Travelling back in time only sets the clock.
+    ///
+    /// # Errors
+    ///
+    /// When a job failed, its error, the clock staying at the time the job
+    /// was due and the jobs after it not performed yet.
+    pub async fn travel_to(&self, at: DateTime<Utc>) -> Result<()> {
+        // the time each scheduler job last ran at, or the start of the travel
+        let mut ran_at = vec![self.clock.now(); self.scheduled.len()];
+        while let Some(due) = self.next_due(&ran_at, at) {
+            match due {
+                Due::Delayed(time) => {
+                    self.clock.set(time.max(self.clock.now()));
+                    for job in self.take_due(time) {
+                        job.perform(self.ctx()).await?;
+                    }
+                }
+                Due::Scheduled(time, index) => {
+                    self.clock.set(time);
+                    ran_at[index] = time;
+                    self.perform_scheduled(&self.scheduled[index]).await?;
+                }
+            }
+        }
+        self.clock.set(at);
+        Ok(())
+    }
+
+    /// The earliest job due by `at`, delayed jobs first.
+    fn next_due(&self, ran_at: &[DateTime<Utc>], at: DateTime<Utc>) -> Option<Due> {
+        let delayed = self
+            .ctx()
+            .shared_store
+            .get_ref::<DelayedJobs>()
+            .and_then(|jobs| jobs.next_at())
+            .filter(|time| *time <= at);
+        let scheduled = self
+            .scheduled
+            .iter()
+            .zip(ran_at)
+            .enumerate()
+            .filter_map(|(index, (scheduled, ran_at))| {
+                let time = scheduled.schedule.after(ran_at).next()?;
+                (time <= at).then_some((time, index))
+            })
+            .min_by_key(|(time, _)| *time);
+        match (delayed, scheduled) {
+            (Some(delayed), Some((time, _))) if delayed <= time => Some(Due::Delayed(delayed)),
+            (_, Some((time, index))) => Some(Due::Scheduled(time, index)),
+            (Some(delayed), None) => Some(Due::Delayed(delayed)),
+            (None, None) => None,
+        }
+    }
+
+    fn take_due(&self, now: DateTime<Utc>) -> Vec<DelayedJob> {
+        self.ctx()
+            .shared_store
+            .get_ref::<DelayedJobs>()
+            .map(|jobs| jobs.take_due(now))
+            .unwrap_or_default()
+    }
+
+    /// Performs a scheduler job, its task in process rather than in a
+    /// `cargo loco task` sub process.
+    async fn perform_scheduled(&self, scheduled: &ScheduledJob) -> Result<()> {
+        tracing::debug!(job = scheduled.name, now = %self.clock.now(), "performing scheduler job");
+        if scheduled.job.shell {
+            let description = scheduled.job.prepare_command(
+                std::path::Path::new(""),
+                &Output::Silent,
+                &self.ctx().environment,
+            );
+            description.run()?;
+            return Ok(());
+        }
+        let mut args = scheduled.job.run.split_whitespace();
+        let task = args.next().unwrap_or_default();
+        let vars = Vars::from_cli_args(
+            args.filter_map(|arg| arg.split_once(':'))
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        );
+        self.tasks.run(self.ctx(), task, &vars).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use async_trait::async_trait;
+    use chrono::TimeZone;
+    use serde::{Deserialize, Serialize};
+    use tokio::sync::Mutex;
+
+    use super::*;
+    use crate::{
+        bgworker::BackgroundWorker,
+        task::{Task, TaskInfo},
+        tests_cfg,
+    };
+
+    static SENT: Mutex<Vec<(String, DateTime<Utc>)>> = Mutex::const_new(Vec::new());
+    static SERIAL: Mutex<()> = Mutex::const_new(());
+    static TICKS: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Serialize, Deserialize)]
+    struct Reminder {
+        to: String,
+        again_in_hours: Option<i64>,
+    }
+
+    struct ReminderWorker {
+        ctx: AppContext,
+    }
+
+    #[async_trait]
+    impl BackgroundWorker<Reminder> for ReminderWorker {
+        fn build(ctx: &AppContext) -> Self {
+            Self { ctx: ctx.clone() }
+        }
+
+        async fn perform(&self, args: Reminder) -> Result<()> {
+            SENT.lock().await.push((args.to.clone(), self.ctx.now()));
+            if let Some(hours) = args.again_in_hours {
+                let again = Reminder {
+                    to: args.to,
+                    again_in_hours: None,
+                };
+                Self::perform_in(&self.ctx, again, Duration::hours(hours)).await?;
+            }
+            Ok(())
+        }
+    }
+
+    struct Tick;
+
+    #[async_trait]
+    impl Task for Tick {
+        fn task(&self) -> TaskInfo {
+            TaskInfo {
+                name: "tick".to_string(),
+                detail: "count the ticks".to_string(),
+            }
+        }
+
+        async fn run(&self, _app_context: &AppContext, vars: &Vars) -> Result<()> {
+            let by = vars.cli_arg("by")?.parse::<usize>().unwrap();
+            TICKS.fetch_add(by, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    struct TickApp;
+
+    #[async_trait]
+    impl Hooks for TickApp {
+        fn app_name() -> &'static str {
+            "tick"
+        }
+
+        fn routes(ctx: &AppContext) -> crate::controller::AppRoutes {
+            tests_cfg::db::AppHook::routes(ctx)
+        }
+
+        async fn boot(
+            mode: crate::boot::StartMode,
+            environment: &crate::environment::Environment,
+            config: crate::config::Config,
+        ) -> Result<BootResult> {
+            tests_cfg::db::AppHook::boot(mode, environment, config).await
+        }
+
+        async fn connect_workers(_ctx: &AppContext, _queue: &crate::bgworker::Queue) -> Result<()> {
+            Ok(())
+        }
+
+        fn register_tasks(tasks: &mut Tasks) {
+            tasks.register(Tick);
+        }
+
+        #[cfg(feature = "with-db")]
+        async fn truncate(_ctx: &AppContext) -> Result<()> {
+            Ok(())
+        }
+
+        #[cfg(feature = "with-db")]
+        async fn seed(_ctx: &AppContext, _base: &std::path::Path) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, 1, 9, 0, 0).unwrap()
+    }
+
+    async fn test_context() -> TestContext {
+        let boot = BootResult {
+            app_context: tests_cfg::app::get_app_context().await,
+            router: None,
+            worker: None,
+            run_scheduler: false,
+            initializers: vec![],
+            timings: vec![],
+        };
+        // without the every 5 seconds job of the test configuration
+        let scheduler = scheduler::Config {
+            jobs: std::collections::HashMap::new(),
+            output: Output::default(),
+        };
+        TestContext::new::<TickApp>(boot, FrozenClock::new(start()))
+            .and_then(|test| test.with_scheduler(&scheduler))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn can_travel_past_delayed_jobs() {
+        let _serial = SERIAL.lock().await;
+        SENT.lock().await.clear();
+        let test = test_context().await;
+        let reminder = Reminder {
+            to: "loco".to_string(),
+            again_in_hours: Some(24),
+        };
+        ReminderWorker::perform_in(test.ctx(), reminder, Duration::hours(24))
+            .await
+            .unwrap();
+        assert_eq!(test.pending_jobs(), 1);
+
+        test.travel(Duration::hours(23)).await.unwrap();
+        assert!(SENT.lock().await.is_empty());
+
+        // the reminder runs at its time, and the one it delays too
+        test.travel(Duration::days(2)).await.unwrap();
+        assert_eq!(
+            *SENT.lock().await,
+            vec![
+                ("loco".to_string(), start() + Duration::hours(24)),
+                ("loco".to_string(), start() + Duration::hours(48)),
+            ]
+        );
+        assert_eq!(test.clock.now(), start() + Duration::hours(71));
+        assert_eq!(test.pending_jobs(), 0);
+    }
+
+    #[tokio::test]
+    async fn performs_due_jobs_now() {
+        let _serial = SERIAL.lock().await;
+        SENT.lock().await.clear();
+        let test = test_context().await;
+        let reminder = Reminder {
+            to: "now".to_string(),
+            again_in_hours: None,
+        };
+        ReminderWorker::perform_at(test.ctx(), reminder, start() - Duration::hours(1))
+            .await
+            .unwrap();
+        assert_eq!(*SENT.lock().await, vec![("now".to_string(), start())]);
+        assert_eq!(test.pending_jobs(), 0);
+    }
+
+    #[tokio::test]
+    async fn can_travel_past_scheduler_jobs() {
+        let config: scheduler::Config = serde_yaml::from_str(
+            r#"
+jobs:
+  hourly:
+    run: "tick by:2"
+    schedule: "0 0 * * * *"
+  daily:
+    run: "tick by:100"
+    schedule: "0 0 12 * * *"
+"#,
+        )
+        .unwrap();
+        let test = test_context().await.with_scheduler(&config).unwrap();
+        TICKS.store(0, Ordering::SeqCst);
+
+        test.travel(Duration::minutes(30)).await.unwrap();
+        assert_eq!(TICKS.load(Ordering::SeqCst), 0);
+
+        // 10:00, 11:00 and 12:00, when the daily job runs too
+        test.travel_to(start() + Duration::hours(3)).await.unwrap();
+        assert_eq!(TICKS.load(Ordering::SeqCst), 106);
+    }
+}
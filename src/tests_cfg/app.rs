@@ -1,11 +1,16 @@
 use crate::{
     app::{AppContext, SharedStore},
     cache,
+    clock::SystemClock,
     environment::Environment,
+    flags::StaticFlags,
     storage::{self, Storage},
     tests_cfg::config::test_config,
 };
 
+/// # Panics
+///
+/// When the default HTTP client cannot be built
 pub async fn get_app_context() -> AppContext {
     // Always use in-memory cache for tests if feature is available, otherwise fall back to null
     #[cfg(feature = "cache_inmem")]
@@ -17,15 +22,29 @@ pub async fn get_app_context() -> AppContext {
     #[cfg(not(feature = "cache_inmem"))]
     let cache = cache::Cache::new(cache::drivers::null::new());
 
+    let config = test_config();
+    let shutdown = tokio_util::sync::CancellationToken::new();
     AppContext {
         environment: Environment::Test,
         #[cfg(feature = "with-db")]
         db: super::db::dummy_connection().await,
         queue_provider: None,
-        config: test_config(),
         mailer: None,
+        mailers: std::sync::Arc::new(std::collections::BTreeMap::new()),
         storage: Storage::single(storage::drivers::mem::new()).into(),
         cache: cache.into(),
         shared_store: std::sync::Arc::new(SharedStore::default()),
+        audit: None,
+        clock: std::sync::Arc::new(SystemClock),
+        flags: std::sync::Arc::new(StaticFlags::new(
+            std::collections::BTreeMap::new(),
+            Environment::Test,
+        )),
+        http_client: crate::http_client::HttpClient::new(&crate::config::HttpClient::default())
+            .unwrap(),
+        events: crate::events::EventBus::new(&config.events, shutdown.clone()),
+        config,
+        readiness: crate::boot::Readiness::default(),
+        shutdown,
     }
 }
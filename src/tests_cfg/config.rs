@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use crate::{
     config::{self, Config},
-    controller::middleware,
+    controller::{error_format::ErrorFormat, middleware},
     logger, scheduler,
 };
 
@@ -18,6 +18,8 @@ pub fn test_config() -> Config {
             format: logger::Format::Json,
             override_filter: None,
             file_appender: None,
+            otel: None,
+            redact: logger::Redaction::default(),
         },
         server: config::Server {
             binding: "localhost".to_string(),
@@ -25,13 +27,20 @@ pub fn test_config() -> Config {
             host: "localhost".to_string(),
             ident: None,
             middlewares: middleware::Config::default(),
+            sendfile: None,
+            headers: std::collections::BTreeMap::new(),
+            security_headers: false,
+            error_format: ErrorFormat::default(),
         },
         #[cfg(feature = "with-db")]
-        database: get_database_config(),
+        database: Some(get_database_config()),
         queue: None,
         auth: None,
         workers: config::Workers {
             mode: config::WorkerMode::ForegroundBlocking,
+            enable: true,
+            queues: None,
+            on_registration_error: config::OnRegistrationError::Fail,
         },
         mailer: None,
         initializers: None,
@@ -51,6 +60,11 @@ pub fn test_config() -> Config {
 
             output: scheduler::Output::STDOUT,
         }),
+        shutdown: config::Shutdown::default(),
+        ids: crate::id::IdScheme::default(),
+        flags: std::collections::BTreeMap::new(),
+        http_client: config::HttpClient::default(),
+        events: config::Events::default(),
         // Always use in-memory cache for tests if available
         #[cfg(feature = "cache_inmem")]
         cache: config::CacheConfig::InMem(config::InMemCacheConfig {
@@ -67,14 +81,20 @@ pub fn get_database_config() -> config::Database {
     config::Database {
         uri: "sqlite::memory:".to_string(),
         enable_logging: false,
+        log_queries: false,
+        slow_query_threshold: None,
         min_connections: 1,
         max_connections: 1,
         connect_timeout: 500,
         idle_timeout: 500,
         acquire_timeout: None,
         auto_migrate: false,
+        migrate_on_boot: None,
         dangerously_truncate: false,
         dangerously_recreate: false,
+        destructive_environments: vec!["development".to_string(), "test".to_string()],
+        tenants: vec![],
+        failover: config::DatabaseFailover::default(),
         run_on_start: None,
     }
 }
@@ -38,12 +38,18 @@
 //! }
 //! ```
 
-use std::ops::{Deref, DerefMut};
+use std::{
+    collections::BTreeMap,
+    ops::{Deref, DerefMut},
+};
 
 #[cfg(feature = "with-db")]
 use sea_orm::DbErr;
 use serde::{Deserialize, Serialize};
-use validator::{Validate, ValidationError, ValidationErrors};
+use serde_json::json;
+use validator::{Validate, ValidationError, ValidationErrors, ValidationErrorsKind};
+
+use crate::controller::ErrorDetail;
 
 // this is a line-serialization type. it is used as an intermediate format
 // to hold validation error data when we transform from
@@ -136,6 +142,82 @@ pub fn into_db_error(errors: &ModelValidationErrors) -> sea_orm::DbErr {
     }
 }
 
+/// Flattens validation errors into a map of field paths to their errors.
+/// Errors of nested structs and lists are keyed by dotted paths, such as
+/// `address.city` or `items.0.name`.
+#[must_use]
+pub fn flatten_errors(errors: &ValidationErrors) -> BTreeMap<String, Vec<ValidationError>> {
+    let mut flattened = BTreeMap::new();
+    flatten_into(&mut flattened, None, errors);
+    flattened
+}
+
+fn flatten_into(
+    flattened: &mut BTreeMap<String, Vec<ValidationError>>,
+    prefix: Option<&str>,
+    errors: &ValidationErrors,
+) {
+    for (field, kind) in errors.errors() {
+        let path = prefix.map_or_else(|| field.to_string(), |prefix| format!("{prefix}.{field}"));
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                flattened
+                    .entry(path)
+                    .or_default()
+                    .extend(errors.iter().cloned());
+            }
+            ValidationErrorsKind::Struct(errors) => flatten_into(flattened, Some(&path), errors),
+            ValidationErrorsKind::List(items) => {
+                for (index, errors) in items {
+                    flatten_into(flattened, Some(&format!("{path}.{index}")), errors);
+                }
+            }
+        }
+    }
+}
+
+/// The response body for validation errors, answered with
+/// `422 Unprocessable Entity`:
+///
+/// ```json
+/// {
+///   "error": "unprocessable_entity",
+///   "description": "validation failed",
+///   "errors": {
+///     "address.city": [{ "code": "length", "message": "city is too short", "params": { "min": 2 } }]
+///   }
+/// }
+/// ```
+///
+/// Errors without a message get an `invalid <code>` message.
+#[must_use]
+pub fn error_detail(errors: &ValidationErrors) -> ErrorDetail {
+    let errors = flatten_errors(errors)
+        .into_iter()
+        .map(|(path, errors)| {
+            let errors = errors
+                .iter()
+                .map(|err| {
+                    json!({
+                        "code": err.code,
+                        "message": err
+                            .message
+                            .as_ref()
+                            .map_or_else(|| format!("invalid {}", err.code), ToString::to_string),
+                        "params": err.params,
+                    })
+                })
+                .collect::<Vec<_>>();
+            (path, serde_json::Value::from(errors))
+        })
+        .collect::<serde_json::Map<_, _>>();
+    ErrorDetail {
+        error: Some("unprocessable_entity".to_string()),
+        description: Some("validation failed".to_string()),
+        errors: Some(errors.into()),
+    }
+}
+
 /// Implement `Validatable` for `ActiveModel` when you want it to have a
 /// `validate()` function.
 pub trait Validatable {
@@ -39,15 +39,17 @@ async fn can_validation_with_response() {
         .await
         .expect("Valid response");
 
-    assert_eq!(res.status(), 400);
+    assert_eq!(res.status(), 422);
 
     let res_text = res.text().await.expect("response text");
     let res_json: serde_json::Value = serde_json::from_str(&res_text).expect("Valid JSON response");
 
     let expected_json = serde_json::json!(
         {
+            "error": "unprocessable_entity",
+            "description": "validation failed",
             "errors":{
-                "email":[{"code":"email","message":null,"params":{"value":"invalid"}}],
+                "email":[{"code":"email","message":"invalid email","params":{"value":"invalid"}}],
                 "name":[{"code":"length","message":"message_str","params":{"min":5,"value":"test"}}]
         }
     });
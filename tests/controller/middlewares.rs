@@ -224,8 +224,10 @@ async fn limit_payload(#[case] limit: middleware::limit_payload::DefaultBodyLimi
 
     let mut ctx: AppContext = tests_cfg::app::get_app_context().await;
 
-    ctx.config.server.middlewares.limit_payload =
-        Some(middleware::limit_payload::LimitPayload { body_limit: limit });
+    ctx.config.server.middlewares.limit_payload = Some(middleware::limit_payload::LimitPayload {
+        body_limit: limit,
+        ..Default::default()
+    });
 
     let port = get_available_port().await;
     let handle = infra_cfg::server::start_from_ctx(ctx, Some(port)).await;
@@ -33,6 +33,7 @@ pub async fn start_from_boot(
             boot::ServeParams {
                 port: port.unwrap_or(TEST_PORT_SERVER),
                 binding: TEST_BINDING_SERVER.to_string(),
+                retry_bind: false,
             },
             false,
         )
@@ -61,6 +62,8 @@ pub async fn start_from_ctx(ctx: AppContext, port: Option<i32>) -> tokio::task::
         router: Some(app_router),
         worker: None,
         run_scheduler: false,
+        initializers: vec![],
+        timings: vec![],
     };
 
     start_from_boot(boot, port).await
@@ -84,6 +87,8 @@ pub async fn start_with_route(
         router: Some(app_router),
         worker: None,
         run_scheduler: false,
+        initializers: vec![],
+        timings: vec![],
     };
     start_from_boot(boot, port).await
 }